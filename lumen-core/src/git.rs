@@ -0,0 +1,157 @@
+//! Loading diffs from a repository with the `git` CLI, the same way `lumen` does: commits,
+//! ranges of commits and the working tree, without lock files and `node_modules` (see
+//! [`git_diff_pathspec`]).
+//!
+//! [`DiffSource::command`] gives the command without running it, for callers that run their
+//! commands themselves, e.g. in another directory or with logging.
+
+use std::io;
+use std::process::Command;
+
+use crate::patch::git_diff_pathspec;
+
+/// Which commits of a range contribute to its diff.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RangeOptions {
+    /// Follow only the first parent, so each merge counts as a single change
+    pub first_parent: bool,
+    /// Leave merge commits out entirely
+    pub no_merges: bool,
+}
+
+impl RangeOptions {
+    /// Whether the range's commits are walked one by one instead of diffing its ends.
+    pub fn filters_history(&self) -> bool {
+        self.first_parent || self.no_merges
+    }
+}
+
+/// Where a diff comes from. Revisions are anything `git rev-parse` understands.
+#[derive(Clone, Copy, Debug)]
+pub enum DiffSource<'a> {
+    /// The working tree's changes against the index, or the index's against `HEAD` with
+    /// `staged`
+    WorkingTree {
+        /// Diff the index instead of the working tree
+        staged: bool,
+    },
+    /// A commit's own changes, against its first parent
+    Commit {
+        /// The commit
+        revision: &'a str,
+    },
+    /// The changes between two revisions
+    Range {
+        /// Start of the range
+        from: &'a str,
+        /// End of the range
+        to: &'a str,
+        /// Diff `to` against the merge base of the two, like `git diff from...to`
+        triple_dot: bool,
+        /// Which commits count, see [`RangeOptions`]
+        options: RangeOptions,
+    },
+}
+
+impl DiffSource<'_> {
+    /// The `git` command printing the diff, limited to `paths` when there are any.
+    pub fn command(&self, paths: &[String]) -> Command {
+        let mut command = Command::new("git");
+        match *self {
+            DiffSource::WorkingTree { staged } => {
+                command.arg("diff");
+                if staged {
+                    command.arg("--staged");
+                }
+            }
+            DiffSource::Commit { revision } => {
+                command.args([
+                    "diff-tree",
+                    "-p",
+                    "--root",
+                    "--binary",
+                    "--no-color",
+                    "--compact-summary",
+                    revision,
+                ]);
+            }
+            DiffSource::Range {
+                from, to, options, ..
+            } if options.filters_history() => {
+                // A plain `git diff` can't drop merges, so walk the commits and collect their
+                // patches. `from..to` in log already means "commits on `to` since the merge
+                // base", which is what `from...to` means for diff.
+                command.args(["log", "-p", "--no-color", "--format=commit %h %s"]);
+                if options.first_parent {
+                    command.arg("--first-parent");
+                }
+                if options.no_merges {
+                    command.arg("--no-merges");
+                }
+                command.arg(format!("{from}..{to}"));
+            }
+            DiffSource::Range {
+                from,
+                to,
+                triple_dot,
+                ..
+            } => {
+                let separator = if triple_dot { "..." } else { ".." };
+                command.args(["diff", &format!("{from}{separator}{to}")]);
+            }
+        }
+        command.args(git_diff_pathspec(paths));
+        command
+    }
+
+    /// Run [`Self::command`] in the current directory. An empty diff isn't an error; a git
+    /// failure is, with git's message.
+    pub fn load(&self, paths: &[String]) -> io::Result<String> {
+        let output = self.command(paths).output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(io::Error::other(stderr.trim().to_string()));
+        }
+        String::from_utf8(output.stdout).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(source: DiffSource, paths: &[String]) -> Vec<String> {
+        source
+            .command(paths)
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_diff_commands() {
+        let paths = ["src/".to_string()];
+        assert_eq!(
+            args(DiffSource::WorkingTree { staged: true }, &paths)[..4],
+            ["diff", "--staged", "--", "src/"]
+        );
+        let range = |triple_dot, options| DiffSource::Range {
+            from: "main",
+            to: "HEAD",
+            triple_dot,
+            options,
+        };
+        assert_eq!(
+            args(range(true, RangeOptions::default()), &[])[..2],
+            ["diff", "main...HEAD"]
+        );
+        let first_parent = RangeOptions {
+            first_parent: true,
+            no_merges: false,
+        };
+        let log = args(range(true, first_parent), &[]);
+        assert_eq!(log[0], "log");
+        assert!(log.contains(&"--first-parent".to_string()));
+        assert!(log.contains(&"main..HEAD".to_string()));
+    }
+}
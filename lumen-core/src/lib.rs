@@ -0,0 +1,30 @@
+//! The parts of [lumen](https://github.com/jnsahaj/lumen) that work on diffs without a
+//! terminal or an AI provider, for editor plugins, bots and other tools that want the same
+//! results as the `lumen` binary without running it.
+//!
+//! - [`side_by_side`] pairs the lines of two versions of a file into the rows lumen's diff
+//!   viewer shows, and finds the hunks and line ranges on them.
+//! - [`patch`] works on unified diffs as git prints them: one part per file or hunk, CI
+//!   configuration files, and the token estimates, chunking, cutting and compaction lumen uses
+//!   to fit a diff into a model's context.
+//! - [`git`] loads diffs of commits, ranges and the working tree with the `git` CLI.
+//! - [`prompt`] builds the system and user prompts lumen sends for each command, and
+//!   [`response`] gets the JSON out of what a model answers.
+//!
+//! Sending the prompts is left to the caller: lumen's provider clients depend on its
+//! configuration, keychain, secret redaction and usage log, so they stay in the binary.
+//!
+//! ```
+//! use lumen_core::side_by_side::{compute_side_by_side, find_hunk_starts};
+//!
+//! let rows = compute_side_by_side("a\nb\n", "a\nc\n", 4, false);
+//! assert_eq!(find_hunk_starts(&rows), vec![1]);
+//! ```
+
+#![warn(missing_docs)]
+
+pub mod git;
+pub mod patch;
+pub mod prompt;
+pub mod response;
+pub mod side_by_side;
@@ -0,0 +1,718 @@
+//! Helpers for unified diffs as git prints them: splitting one per file, recognizing CI
+//! configuration, and fitting a diff into a model's context window.
+
+/// Pathspec for `git diff` and `git log -p` that leaves out lock files and `node_modules`,
+/// which cost tokens without saying much
+pub const GIT_DIFF_EXCLUSIONS: [&str; 7] = [
+    "--", // Separator for pathspecs
+    ".",  // Include everything
+    ":(exclude)package-lock.json",
+    ":(exclude)yarn.lock",
+    ":(exclude)pnpm-lock.yaml",
+    ":(exclude)Cargo.lock",
+    ":(exclude)node_modules/**",
+];
+
+/// Whether [`GIT_DIFF_EXCLUSIONS`] leaves `path`, relative to the repository root, out of
+/// diffs. For diffs that don't come from git, which can't take the pathspec.
+pub fn is_excluded(path: &str) -> bool {
+    GIT_DIFF_EXCLUSIONS
+        .iter()
+        .filter_map(|spec| spec.strip_prefix(":(exclude)"))
+        .any(|pattern| match pattern.strip_suffix("/**") {
+            Some(dir) => path
+                .strip_prefix(dir)
+                .is_some_and(|rest| rest.starts_with('/')),
+            None => path == pattern,
+        })
+}
+
+/// [`GIT_DIFF_EXCLUSIONS`] with the diff limited to `paths` (files, directories or any other
+/// git pathspec), or the whole tree when there are none.
+pub fn git_diff_pathspec(paths: &[String]) -> Vec<&str> {
+    if paths.is_empty() {
+        return GIT_DIFF_EXCLUSIONS.to_vec();
+    }
+    let mut pathspec = vec!["--"];
+    pathspec.extend(paths.iter().map(String::as_str));
+    pathspec.extend(&GIT_DIFF_EXCLUSIONS[2..]);
+    pathspec
+}
+
+/// Split a unified diff into each file's part, with the file's path.
+pub fn diff_files(diff: &str) -> Vec<(&str, &str)> {
+    let mut starts: Vec<usize> = Vec::new();
+    let mut offset = 0;
+    for line in diff.split_inclusive('\n') {
+        if line.starts_with("diff --git ") {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(diff.len());
+            let part = &diff[start..end];
+            let header = part.lines().next().unwrap_or_default();
+            let path = header
+                .rsplit_once(" b/")
+                .map_or(header, |(_, path)| path.trim_end());
+            (path, part)
+        })
+        .collect()
+}
+
+/// Whether `path` configures a CI pipeline: GitHub Actions workflows and actions, GitLab CI,
+/// and the other common hosted CI systems.
+pub fn is_ci_config(path: &str) -> bool {
+    let is_yaml = path.ends_with(".yml") || path.ends_with(".yaml");
+    let name = path.rsplit('/').next().unwrap_or(path);
+    (is_yaml
+        && (path.starts_with(".github/workflows/")
+            || path.starts_with(".gitlab/ci/")
+            || path.starts_with(".circleci/")
+            || path.starts_with(".buildkite/")
+            || (path.starts_with(".github/") && matches!(name, "action.yml" | "action.yaml"))))
+        || matches!(
+            path,
+            ".gitlab-ci.yml" | ".travis.yml" | "azure-pipelines.yml" | "bitbucket-pipelines.yml"
+        )
+        || name == "Jenkinsfile"
+}
+
+/// Paths of the CI configuration files a diff changes, see [`is_ci_config`].
+pub fn ci_config_files(diff: &str) -> Vec<&str> {
+    diff_files(diff)
+        .into_iter()
+        .map(|(path, _)| path)
+        .filter(|path| is_ci_config(path))
+        .collect()
+}
+
+/// One `@@` hunk of a diff, with the file it belongs to and its range in the new file.
+#[derive(Debug, PartialEq)]
+pub struct DiffHunk {
+    /// Path in the new version, or the old one for a deleted file
+    pub file: String,
+    /// First line of the hunk in the new version
+    pub new_start: usize,
+    /// Lines of the hunk in the new version, 0 when it only removes lines
+    pub new_count: usize,
+    /// The hunk's header and lines
+    pub text: String,
+}
+
+impl DiffHunk {
+    /// `file:start-end` in the new version, or just `file:start` for single-line hunks.
+    pub fn location(&self) -> String {
+        match self.new_count {
+            0 | 1 => format!("{}:{}", self.file, self.new_start),
+            count => format!(
+                "{}:{}-{}",
+                self.file,
+                self.new_start,
+                self.new_start + count - 1
+            ),
+        }
+    }
+}
+
+/// Split a unified diff into its hunks.
+pub fn parse_hunks(diff: &str) -> Vec<DiffHunk> {
+    let mut hunks: Vec<DiffHunk> = Vec::new();
+    let mut file = String::new();
+    // `---`/`+++` only name the file in the header; inside a hunk they are content
+    let mut in_header = true;
+    for line in diff.lines() {
+        if line.starts_with("diff --git ") {
+            in_header = true;
+        } else if line.starts_with("@@ ") {
+            in_header = false;
+            let (new_start, new_count) =
+                HunkHeader::parse(line).map_or((0, 0), |h| (h.new_start, h.new_count));
+            hunks.push(DiffHunk {
+                file: file.clone(),
+                new_start,
+                new_count,
+                text: format!("{line}\n"),
+            });
+        } else if in_header {
+            if let Some(path) = line.strip_prefix("+++ ") {
+                if path != "/dev/null" {
+                    file = path.strip_prefix("b/").unwrap_or(path).to_string();
+                }
+            } else if let Some(path) = line.strip_prefix("--- ") {
+                // Deleted files only have a real path on the old side
+                file = path.strip_prefix("a/").unwrap_or(path).to_string();
+            }
+        } else if let Some(hunk) = hunks.last_mut() {
+            if line.starts_with([' ', '+', '-', '\\']) {
+                hunk.text.push_str(line);
+                hunk.text.push('\n');
+            }
+        }
+    }
+    hunks
+}
+
+/// Rough average for code and English text; good enough to decide when to chunk.
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Estimate how many tokens `text` will use.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.len().div_ceil(CHARS_PER_TOKEN)
+}
+
+/// Split a diff into chunks of at most about `max_tokens` each.
+///
+/// Files are kept whole where possible. A file that is too large on its own is split per hunk,
+/// repeating the file header so each chunk stays readable, and a hunk that is still too large
+/// is split by lines.
+pub fn split_diff(diff: &str, max_tokens: usize) -> Vec<String> {
+    let max_chars = max_tokens.saturating_mul(CHARS_PER_TOKEN).max(1);
+
+    let mut pieces = Vec::new();
+    for file in split_before_lines(diff, "diff --git ") {
+        if file.len() <= max_chars {
+            pieces.push(file);
+            continue;
+        }
+
+        let mut parts = split_before_lines(&file, "@@ ").into_iter();
+        let header = parts.next().unwrap_or_default();
+        for hunk in parts {
+            if header.len() + hunk.len() <= max_chars {
+                pieces.push(format!("{header}{hunk}"));
+                continue;
+            }
+            let budget = max_chars.saturating_sub(header.len()).max(1);
+            let mut current = String::new();
+            for line in hunk.split_inclusive('\n') {
+                if !current.is_empty() && current.len() + line.len() > budget {
+                    pieces.push(format!("{header}{current}"));
+                    current.clear();
+                }
+                current.push_str(line);
+            }
+            if !current.is_empty() {
+                pieces.push(format!("{header}{current}"));
+            }
+        }
+    }
+
+    // Pack pieces greedily so small files share a chunk
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    for piece in pieces {
+        if !current.is_empty() && current.len() + piece.len() > max_chars {
+            chunks.push(std::mem::take(&mut current));
+        }
+        current.push_str(&piece);
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Cut a diff down to about `max_tokens` at file and hunk boundaries, so the model never
+/// sees half a hunk. Files are kept whole and in order while they fit; one that doesn't keeps
+/// as many of its leading hunks as fit. Returns the cut diff, ending with a note naming what
+/// was left out, and the files left out entirely or in part.
+pub fn fit_diff(diff: &str, max_tokens: usize) -> (String, Vec<String>) {
+    if estimate_tokens(diff) <= max_tokens {
+        return (diff.to_string(), Vec::new());
+    }
+    let max_chars = max_tokens.saturating_mul(CHARS_PER_TOKEN);
+    let files = diff_files(diff);
+    // Commit details and the like come before the first file
+    let preamble_len = diff.len() - files.iter().map(|(_, part)| part.len()).sum::<usize>();
+    let mut kept = diff[..preamble_len].to_string();
+    let mut omitted = Vec::new();
+    for (path, part) in files {
+        if kept.len() + part.len() <= max_chars {
+            kept.push_str(part);
+            continue;
+        }
+        let mut parts = split_before_lines(part, "@@ ").into_iter();
+        let header = parts.next().unwrap_or_default();
+        let hunks: Vec<String> = parts.collect();
+        let mut budget = max_chars.saturating_sub(kept.len() + header.len());
+        let fitting = hunks
+            .iter()
+            .take_while(|hunk| {
+                let fits = hunk.len() <= budget;
+                budget = budget.saturating_sub(hunk.len());
+                fits
+            })
+            .count();
+        if fitting == 0 {
+            omitted.push(path.to_string());
+            continue;
+        }
+        kept.push_str(&header);
+        kept.extend(hunks[..fitting].iter().map(String::as_str));
+        omitted.push(format!(
+            "{path} ({} of {} hunks)",
+            hunks.len() - fitting,
+            hunks.len()
+        ));
+    }
+    if !omitted.is_empty() {
+        kept.push_str(&format!("\n[{}]\n", omission_note(&omitted)));
+    }
+    (kept, omitted)
+}
+
+/// `2 files omitted to fit the model's context: a.rs, b.rs (3 of 4 hunks)`
+pub fn omission_note(omitted: &[String]) -> String {
+    let files = if omitted.len() == 1 { "file" } else { "files" };
+    format!(
+        "{} {files} omitted to fit the model's context: {}",
+        omitted.len(),
+        omitted.join(", ")
+    )
+}
+
+/// Unchanged lines kept on each side of a change by `compact_diff`
+const COMPACT_CONTEXT: usize = 1;
+
+/// Lines of a file's diff header that `compact_diff` folds into one `File:` line
+const HEADER_PREFIXES: &[&str] = &[
+    "index ",
+    "--- ",
+    "+++ ",
+    "new file mode",
+    "deleted file mode",
+    "old mode",
+    "new mode",
+    "similarity index",
+    "dissimilarity index",
+    "rename from ",
+    "rename to ",
+    "copy from ",
+    "copy to ",
+    "Binary files ",
+];
+
+/// Rewrite the git diffs in `text` to use fewer tokens (`ai.compact_diff`): each file's
+/// header becomes one `File: path` line, hunks keep one unchanged line around each change,
+/// and hunk headers drop the line counts but name the enclosing function. Every hunk still
+/// gives its starting line numbers, so locations stay exact. Text around the diffs is kept.
+pub fn compact_diff(text: &str) -> String {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(text.len());
+    let mut i = 0;
+    while i < lines.len() {
+        if !lines[i].starts_with("diff --git ") {
+            out.push_str(lines[i]);
+            i += 1;
+            continue;
+        }
+
+        let mut header = vec![lines[i]];
+        i += 1;
+        while i < lines.len() && HEADER_PREFIXES.iter().any(|p| lines[i].starts_with(p)) {
+            header.push(lines[i]);
+            i += 1;
+        }
+        out.push_str(&compact_file_header(&header));
+
+        while let Some(hunk) = lines.get(i).and_then(|line| HunkHeader::parse(line)) {
+            i += 1;
+            let (mut old_left, mut new_left) = (hunk.old_count, hunk.new_count);
+            let start = i;
+            while let Some(line) = lines.get(i) {
+                match line.as_bytes().first() {
+                    Some(b'\\') => {}
+                    _ if old_left == 0 && new_left == 0 => break,
+                    Some(b' ') => {
+                        old_left = old_left.saturating_sub(1);
+                        new_left = new_left.saturating_sub(1);
+                    }
+                    Some(b'-') => old_left = old_left.saturating_sub(1),
+                    Some(b'+') => new_left = new_left.saturating_sub(1),
+                    _ => break,
+                }
+                i += 1;
+            }
+            out.push_str(&compact_hunk(&hunk, &lines[start..i]));
+        }
+    }
+    out
+}
+
+/// `File: src/new.rs (renamed from src/old.rs)` from a `diff --git` header.
+fn compact_file_header(header: &[&str]) -> String {
+    let git_line = header[0].trim_end();
+    let mut path = git_line
+        .rsplit_once(" b/")
+        .map_or(git_line, |(_, path)| path)
+        .to_string();
+    let mut notes = Vec::new();
+    for line in &header[1..] {
+        let line = line.trim_end();
+        if let Some(new_path) = line.strip_prefix("+++ b/") {
+            path = new_path.to_string();
+        } else if line.starts_with("new file mode") {
+            notes.push("new".to_string());
+        } else if line.starts_with("deleted file mode") {
+            notes.push("deleted".to_string());
+        } else if let Some(from) = line.strip_prefix("rename from ") {
+            notes.push(format!("renamed from {from}"));
+        } else if let Some(from) = line.strip_prefix("copy from ") {
+            notes.push(format!("copied from {from}"));
+        } else if let Some(mode) = line.strip_prefix("new mode ") {
+            notes.push(format!("mode {mode}"));
+        } else if line.starts_with("Binary files ") {
+            notes.push("binary".to_string());
+        }
+    }
+    if notes.is_empty() {
+        format!("File: {path}\n")
+    } else {
+        format!("File: {path} ({})\n", notes.join(", "))
+    }
+}
+
+struct HunkHeader<'a> {
+    old_start: usize,
+    old_count: usize,
+    new_start: usize,
+    new_count: usize,
+    /// What git printed after the ranges, usually the enclosing function
+    scope: &'a str,
+}
+
+impl<'a> HunkHeader<'a> {
+    /// Parse `@@ -12,7 +12,8 @@ fn main() {`; a missing count means 1.
+    fn parse(line: &'a str) -> Option<Self> {
+        let rest = line.strip_prefix("@@ -")?;
+        let (ranges, scope) = rest.split_once(" @@")?;
+        let (old, new) = ranges.split_once(" +")?;
+        let range = |range: &str| -> Option<(usize, usize)> {
+            match range.split_once(',') {
+                Some((start, count)) => Some((start.parse().ok()?, count.parse().ok()?)),
+                None => Some((range.parse().ok()?, 1)),
+            }
+        };
+        let (old_start, old_count) = range(old)?;
+        let (new_start, new_count) = range(new)?;
+        Some(HunkHeader {
+            old_start,
+            old_count,
+            new_start,
+            new_count,
+            scope: scope.trim(),
+        })
+    }
+}
+
+/// The hunk's changes with `COMPACT_CONTEXT` lines around them, split into smaller hunks
+/// where unchanged lines were dropped.
+fn compact_hunk(hunk: &HunkHeader, body: &[&str]) -> String {
+    let is_change = |line: &str| line.starts_with('+') || line.starts_with('-');
+    let changes: Vec<usize> = (0..body.len()).filter(|&j| is_change(body[j])).collect();
+    let mut keep = vec![false; body.len()];
+    for &j in &changes {
+        let from = j.saturating_sub(COMPACT_CONTEXT);
+        let to = (j + COMPACT_CONTEXT).min(body.len() - 1);
+        keep[from..=to].iter_mut().for_each(|k| *k = true);
+    }
+    for j in 1..body.len() {
+        if body[j].starts_with('\\') {
+            keep[j] = keep[j - 1];
+        }
+    }
+
+    let mut out = String::new();
+    let (mut old_line, mut new_line) = (hunk.old_start, hunk.new_start);
+    let mut scope = hunk.scope.to_string();
+    for (j, line) in body.iter().enumerate() {
+        if keep[j] {
+            if j == 0 || !keep[j - 1] {
+                let scope = if scope.is_empty() {
+                    String::new()
+                } else {
+                    format!(" {scope}")
+                };
+                out.push_str(&format!("@@ -{old_line} +{new_line} @@{scope}\n"));
+            }
+            out.push_str(line);
+        }
+        match line.as_bytes().first() {
+            Some(b' ') => {
+                old_line += 1;
+                new_line += 1;
+            }
+            Some(b'-') => old_line += 1,
+            Some(b'+') => new_line += 1,
+            _ => {}
+        }
+        let new_side = line.strip_prefix(' ').or_else(|| line.strip_prefix('+'));
+        if let Some(code) = new_side.filter(|code| looks_like_definition(code)) {
+            scope = code.trim().to_string();
+        }
+    }
+    out
+}
+
+/// Whether a line likely starts a function, type or block worth naming in a hunk header:
+/// one starting with a common definition keyword, or an unindented line opening a block
+/// (roughly git's default), but not a statement.
+fn looks_like_definition(line: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "fn ",
+        "pub ",
+        "async ",
+        "impl ",
+        "struct ",
+        "enum ",
+        "trait ",
+        "def ",
+        "class ",
+        "func ",
+        "function ",
+        "interface ",
+        "export ",
+        "module ",
+    ];
+    let code = line.trim();
+    if code.ends_with(';') {
+        return false;
+    }
+    let unindented = line
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$');
+    let opens_block = code.ends_with(['{', ':', '(']);
+    KEYWORDS.iter().any(|k| code.starts_with(k)) || (unindented && opens_block)
+}
+
+/// Split `text` into segments that each start at a line beginning with `prefix`.
+/// Anything before the first such line becomes its own segment.
+fn split_before_lines(text: &str, prefix: &str) -> Vec<String> {
+    let mut segments = Vec::new();
+    let mut current = String::new();
+    for line in text.split_inclusive('\n') {
+        if line.starts_with(prefix) && !current.is_empty() {
+            segments.push(std::mem::take(&mut current));
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/Cargo.lock b/Cargo.lock\n\
+        --- a/Cargo.lock\n\
+        +++ b/Cargo.lock\n\
+        @@ -1 +1 @@\n\
+        -old\n\
+        +new\n\
+        diff --git a/src/main.rs b/src/main.rs\n\
+        --- a/src/main.rs\n\
+        +++ b/src/main.rs\n\
+        @@ -1 +1 @@\n\
+        -a\n\
+        +b\n";
+
+    fn file_diff(name: &str, hunks: &[&str]) -> String {
+        let mut diff = format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n");
+        for hunk in hunks {
+            diff.push_str(&format!("@@ -1 +1 @@\n{hunk}\n"));
+        }
+        diff
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let files = diff_files(DIFF);
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].0, "Cargo.lock");
+        assert_eq!(files[1].0, "src/main.rs");
+        assert!(files[1].1.starts_with("diff --git a/src/main.rs"));
+        assert_eq!(
+            files.iter().map(|(_, part)| *part).collect::<String>(),
+            DIFF
+        );
+    }
+
+    #[test]
+    fn test_ci_config_files() {
+        assert!(is_ci_config(".github/workflows/release.yaml"));
+        assert!(is_ci_config(".github/actions/setup/action.yml"));
+        assert!(is_ci_config(".gitlab-ci.yml"));
+        assert!(is_ci_config("ci/Jenkinsfile"));
+        assert!(!is_ci_config(".github/dependabot.yml"));
+        assert!(!is_ci_config("docs/.gitlab-ci.yml.md"));
+
+        let diff = format!(
+            "{DIFF}diff --git a/.github/workflows/ci.yml b/.github/workflows/ci.yml\n@@ -1 +1 @@\n-a\n+b\n"
+        );
+        assert_eq!(ci_config_files(&diff), vec![".github/workflows/ci.yml"]);
+        assert!(ci_config_files(DIFF).is_empty());
+    }
+
+    #[test]
+    fn test_fit_diff_cuts_at_hunk_boundaries() {
+        let a = file_diff("a", &["+x"]);
+        let b = file_diff("b", &[&"+y".repeat(20), &"+z".repeat(60)]);
+        let c = file_diff("c", &[&"+w".repeat(200)]);
+        let diff = format!("{a}{b}{c}");
+        assert_eq!(fit_diff(&a, 1000), (a.clone(), Vec::new()));
+
+        let budget = estimate_tokens(&a) + estimate_tokens(&file_diff("b", &[&"+y".repeat(20)]));
+        let (fitted, omitted) = fit_diff(&diff, budget);
+        assert_eq!(omitted, vec!["b (1 of 2 hunks)", "c"]);
+        assert!(fitted.starts_with(&format!("{a}{}", file_diff("b", &[&"+y".repeat(20)]))));
+        assert!(!fitted.contains("+z"));
+        assert!(fitted
+            .ends_with("\n[2 files omitted to fit the model's context: b (1 of 2 hunks), c]\n"));
+    }
+
+    #[test]
+    fn test_compact_diff() {
+        let diff = [
+            "Changes:",
+            "diff --git a/src/lib.rs b/src/lib.rs",
+            "index 1111111..2222222 100644",
+            "--- a/src/lib.rs",
+            "+++ b/src/lib.rs",
+            "@@ -10,9 +10,9 @@ mod parser;",
+            " use std::fmt;",
+            " ",
+            "-const A: u8 = 1;",
+            "+const A: u8 = 2;",
+            " ",
+            " fn parse() {",
+            "     let x = 1;",
+            "-    let y = 2;",
+            "+    let y = 3;",
+            " }",
+            "diff --git a/new.txt b/new.txt",
+            "new file mode 100644",
+            "--- /dev/null",
+            "+++ b/new.txt",
+            "@@ -0,0 +1 @@",
+            "+hello",
+            "Thanks.",
+            "",
+        ]
+        .join("\n");
+        let compact = [
+            "Changes:",
+            "File: src/lib.rs",
+            "@@ -11 +11 @@ mod parser;",
+            " ",
+            "-const A: u8 = 1;",
+            "+const A: u8 = 2;",
+            " ",
+            "@@ -15 +15 @@ fn parse() {",
+            "     let x = 1;",
+            "-    let y = 2;",
+            "+    let y = 3;",
+            " }",
+            "File: new.txt (new)",
+            "@@ -0 +1 @@",
+            "+hello",
+            "Thanks.",
+            "",
+        ]
+        .join("\n");
+        assert_eq!(compact_diff(&diff), compact);
+    }
+
+    #[test]
+    fn test_small_diff_is_one_chunk() {
+        let diff = format!("{}{}", file_diff("a", &["+x"]), file_diff("b", &["+y"]));
+        assert_eq!(split_diff(&diff, 1000), vec![diff]);
+    }
+
+    #[test]
+    fn test_files_go_to_separate_chunks() {
+        let a = file_diff("a", &[&"+x".repeat(30)]);
+        let b = file_diff("b", &[&"+y".repeat(30)]);
+        let chunks = split_diff(&format!("{a}{b}"), estimate_tokens(&a));
+        assert_eq!(chunks, vec![a, b]);
+    }
+
+    #[test]
+    fn test_large_file_split_per_hunk_with_header() {
+        let diff = file_diff("big", &[&"+x".repeat(40), &"+y".repeat(40)]);
+        let chunks = split_diff(&diff, 40);
+        assert_eq!(chunks.len(), 2);
+        for chunk in &chunks {
+            assert!(chunk.starts_with("diff --git a/big b/big\n"));
+        }
+        assert!(chunks[1].contains("+y"));
+    }
+
+    #[test]
+    fn test_chunks_cover_all_lines() {
+        let lines: Vec<String> = (0..200).map(|i| format!("+line {i}")).collect();
+        let diff = file_diff("f", &[&lines.join("\n")]);
+        let chunks = split_diff(&diff, 100);
+        assert!(chunks.len() > 1);
+        for line in &lines {
+            assert!(chunks.iter().any(|c| c.contains(&format!("{line}\n"))));
+        }
+    }
+
+    #[test]
+    fn test_git_diff_pathspec() {
+        assert_eq!(git_diff_pathspec(&[]), GIT_DIFF_EXCLUSIONS);
+        let paths = ["crates/api".to_string(), "README.md".to_string()];
+        let pathspec = git_diff_pathspec(&paths);
+        assert_eq!(pathspec[..3], ["--", "crates/api", "README.md"]);
+        assert!(!pathspec.contains(&"."));
+        assert!(pathspec.contains(&":(exclude)Cargo.lock"));
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        assert!(is_excluded("Cargo.lock"));
+        assert!(is_excluded("node_modules/left-pad/index.js"));
+        assert!(!is_excluded("node_modules_backup.txt"));
+        assert!(!is_excluded("src/Cargo.lock.rs"));
+    }
+
+    #[test]
+    fn test_parse_hunks() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n\
+            --- a/src/a.rs\n\
+            +++ b/src/a.rs\n\
+            @@ -1,2 +1,3 @@ fn main()\n \
+            keep\n\
+            +added\n \
+            keep\n\
+            @@ -10 +11 @@\n\
+            -old\n\
+            +new\n\
+            diff --git a/gone.rs b/gone.rs\n\
+            deleted file mode 100644\n\
+            --- a/gone.rs\n\
+            +++ /dev/null\n\
+            @@ -1 +0,0 @@\n\
+            -bye\n";
+        let hunks = parse_hunks(diff);
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[0].location(), "src/a.rs:1-3");
+        assert_eq!(
+            hunks[0].text,
+            "@@ -1,2 +1,3 @@ fn main()\n keep\n+added\n keep\n"
+        );
+        assert_eq!(hunks[1].location(), "src/a.rs:11");
+        assert_eq!(hunks[2].file, "gone.rs");
+        assert_eq!(hunks[2].new_count, 0);
+    }
+}
@@ -0,0 +1,1204 @@
+//! The prompts lumen sends for each of its commands, built from plain text and the options
+//! that shape them, so another tool asking the same questions gets the same answers.
+//!
+//! Commands whose prompt can be customized take a [`PromptTemplate`]; the placeholders each
+//! one offers are listed with the `ai.prompts` configuration in lumen's README.
+
+use indoc::{formatdoc, indoc};
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::patch::{ci_config_files, DiffHunk};
+
+/// A prompt that couldn't be built from what it was given
+#[derive(Error, Debug)]
+#[error("{0}")]
+pub struct AIPromptError(pub String);
+
+/// Replacements for the built-in system and user prompts of a command
+#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct PromptTemplate {
+    /// The system prompt, with `{{placeholder}}`s
+    #[serde(default)]
+    pub system: Option<String>,
+
+    /// The user prompt, with `{{placeholder}}`s
+    #[serde(default)]
+    pub user: Option<String>,
+}
+
+/// What `explain` is asked about, which decides the context it's given and what it's asked
+/// to cover
+#[derive(Debug, Clone, Copy)]
+pub enum Explained<'a> {
+    /// A commit, with its message
+    Commit {
+        /// The commit's message
+        message: &'a str,
+    },
+    /// Uncommitted changes
+    WorkingTree,
+    /// The changes between two commits, like a branch
+    Range,
+    /// A patch that didn't come from the repository
+    Patch,
+}
+
+impl<'a> Explained<'a> {
+    /// The commit's message, when a commit is explained
+    pub fn message(self) -> Option<&'a str> {
+        match self {
+            Explained::Commit { message } => Some(message),
+            _ => None,
+        }
+    }
+}
+
+/// What the `explain` prompt is built from
+#[derive(Debug, Clone, Copy)]
+pub struct ExplainInput<'a> {
+    /// What's being explained
+    pub explained: Explained<'a>,
+    /// The diff
+    pub diff: &'a str,
+    /// For a branch: its commits, oldest first, each with its short SHA
+    pub commits: Option<&'a str>,
+    /// A question to answer instead of explaining the changes as a whole
+    pub query: Option<&'a str>,
+    /// Whether to ask for the JSON report `--format json` and the like parse
+    pub structured: bool,
+    /// A section on the Cargo crates the change touches, added as it is
+    pub crates: Option<&'a str>,
+}
+
+/// What the `draft` prompt is built from
+#[derive(Debug, Clone, Copy)]
+pub struct DraftInput<'a> {
+    /// The changes to write a message for
+    pub diff: &'a str,
+    /// What the author says the changes are for
+    pub context: Option<&'a str>,
+    /// For a squash merge: the messages of the branch's commits, oldest first
+    pub squash: Option<&'a str>,
+    /// For `--amend`: the message of the commit being amended
+    pub amend: Option<&'a str>,
+    /// Alternatives to ask for
+    pub count: usize,
+    /// The commit types to choose from, as a JSON object of type to description
+    pub commit_types: &'a str,
+    /// Scopes the message has to use one of, any when empty
+    pub scopes: &'a [String],
+    /// Whether a gitmoji is added to the message afterwards
+    pub gitmoji: bool,
+    /// Longest header allowed, in characters
+    pub subject_limit: usize,
+}
+
+/// A system and a user prompt for one request
+pub struct AIPrompt {
+    /// Instructions for the model
+    pub system_prompt: String,
+    /// The question, with the changes it's about
+    pub user_prompt: String,
+    /// Length in bytes of the start of `user_prompt` that stays the same across requests
+    /// about the same changes, like the diff a question is asked about. Providers with prompt
+    /// caching can reuse it instead of reading it again; 0 when there's nothing to reuse.
+    pub stable_prefix: usize,
+}
+
+/// Names of the `{{placeholder}}`s used in a prompt template.
+pub fn template_placeholders(template: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        names.push(rest[start + 2..start + 2 + len].trim());
+        rest = &rest[start + 2 + len + 2..];
+    }
+    names
+}
+
+/// Substitute `{{name}}` placeholders in a prompt template.
+pub fn render_template(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + 2 + len].trim();
+        rendered.push_str(&rest[..start]);
+        match vars.iter().find(|(var, _)| *var == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + 2 + len + 2]),
+        }
+        rest = &rest[start + 2 + len + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// What to check in changed CI configuration, which can leak secrets or run untrusted code
+/// with the repository's credentials.
+const CI_CHECKS: &str = "new or newly exposed secrets, changes to `permissions` or token scopes, new or re-pinned third-party actions, images and scripts (and whether they're pinned to a commit SHA), triggers that run on untrusted input such as `pull_request_target`, and changes to what runs on which branches";
+
+/// Instruction to cover the CI configuration files `diff` changes in their own section,
+/// `None` when it changes none.
+fn ci_section(diff: &str) -> Option<String> {
+    let files = ci_config_files(diff);
+    if files.is_empty() {
+        return None;
+    }
+    Some(formatdoc! {"
+        The changes touch CI configuration ({files}). Always end the answer with a \"CI pipeline changes\" section analyzing how they affect the pipeline: {CI_CHECKS}. Say so when a file changes nothing pipeline-relevant.",
+        files = files.join(", "),
+    })
+}
+
+impl AIPrompt {
+    /// The user prompt split after its stable prefix.
+    pub fn split_user_prompt(&self) -> (&str, &str) {
+        if self.user_prompt.is_char_boundary(self.stable_prefix) {
+            self.user_prompt.split_at(self.stable_prefix)
+        } else {
+            ("", &self.user_prompt)
+        }
+    }
+
+    /// The system prompt, asking for the answer in `language` when one is set. Only the prose
+    /// changes language; what lumen or git parse stays as it is.
+    pub fn system_prompt_in(&self, language: Option<&str>) -> String {
+        match language {
+            Some(language) => formatdoc! {"
+                {system}
+
+                Write everything meant for people to read in {language}: explanations, summaries, notes, review comments and commit messages. Keep code, identifiers, file paths, commands, JSON keys and conventional commit types such as `feat` or `fix` as they are.",
+                system = self.system_prompt.trim_end(),
+            },
+            None => self.system_prompt.clone(),
+        }
+    }
+
+    /// Rewrite the user prompt with `rewrite`, applied to the stable prefix and the rest
+    /// separately so the prefix still ends in the same place.
+    pub fn rewrite_user_prompt(&mut self, mut rewrite: impl FnMut(&str) -> String) {
+        let (stable, rest) = self.split_user_prompt();
+        let stable = rewrite(stable);
+        let rest = rewrite(rest);
+        self.stable_prefix = stable.len();
+        self.user_prompt = stable + &rest;
+    }
+
+    /// Append the CI section to the user prompt when the diff changes CI configuration,
+    /// after any customization so a custom template can't drop it.
+    fn with_ci_section(mut self, diff: &str) -> Self {
+        if let Some(section) = ci_section(diff) {
+            self.user_prompt = format!("{}\n\n{section}", self.user_prompt.trim_end());
+        }
+        self
+    }
+
+    /// Append the section on the Cargo crates an explained change touches, like the CI
+    /// section.
+    fn with_crates_section(mut self, section: Option<&str>) -> Self {
+        if let Some(section) = section {
+            self.user_prompt = format!("{}\n\n{section}", self.user_prompt.trim_end());
+        }
+        self
+    }
+
+    /// Prompt explaining a change, or answering a question about it.
+    pub fn build_explain_prompt(
+        input: &ExplainInput,
+        template: Option<&PromptTemplate>,
+    ) -> Result<Self, AIPromptError> {
+        Self::build_explain_prompt_from(input, None, template)
+    }
+
+    /// Explain prompt that works from summaries of a diff too large to send in one request.
+    pub fn build_explain_synthesis_prompt(
+        input: &ExplainInput,
+        summaries: &[String],
+        template: Option<&PromptTemplate>,
+    ) -> Result<Self, AIPromptError> {
+        Self::build_explain_prompt_from(input, Some(summaries), template)
+    }
+
+    /// Replace the built-in prompts with the configured templates, if any. A `format`
+    /// variable describes the response the caller parses, so it's appended to a user
+    /// template that leaves it out.
+    fn customized(self, template: Option<&PromptTemplate>, vars: &[(&str, &str)]) -> Self {
+        let Some(template) = template else {
+            return self;
+        };
+
+        let system_prompt = match &template.system {
+            Some(system) => render_template(system, vars),
+            None => self.system_prompt,
+        };
+        let stable_prefix = match &template.user {
+            Some(_) => 0,
+            None => self.stable_prefix,
+        };
+        let user_prompt = match &template.user {
+            Some(user) => {
+                let mut rendered = render_template(user, vars);
+                let format = vars.iter().find(|(name, _)| *name == "format");
+                if let Some((_, format)) = format {
+                    if !template_placeholders(user).contains(&"format") {
+                        rendered = format!("{}\n\n{format}", rendered.trim_end());
+                    }
+                }
+                rendered
+            }
+            None => self.user_prompt,
+        };
+
+        AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix,
+        }
+    }
+
+    /// Prompt asking for a short explanation of each numbered hunk, returned as JSON.
+    /// `message` is that of the commit the hunks belong to, if any.
+    pub fn build_annotate_prompt(
+        hunks: &[DiffHunk],
+        message: Option<&str>,
+        query: Option<&str>,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that annotates Git changes hunk by hunk.
+            For each numbered hunk, write one or two sentences on what it changes and why it matters.
+            Respond with only a JSON array of objects: [{\"hunk\": <number>, \"note\": \"<explanation>\"}].
+            Skip hunks that need no explanation, such as formatting-only changes.
+        "});
+
+        let numbered = hunks
+            .iter()
+            .enumerate()
+            .map(|(i, hunk)| format!("Hunk {} ({}):\n{}", i + 1, hunk.location(), hunk.text))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let context = message
+            .map(|message| format!("Commit message: {message}\n\n"))
+            .unwrap_or_default();
+        let query = query
+            .map(|q| format!("Focus the notes on: {q}\n\n"))
+            .unwrap_or_default();
+
+        let user_prompt = format!("{context}{query}```diff\n{numbered}```");
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt asking for a risk assessment of the change, returned as JSON: the public APIs it
+    /// affects, callers likely to break, configuration and migration steps, and tests to add.
+    /// `message`, `query` and `crates` are as for [`ExplainInput`].
+    pub fn build_impact_prompt(
+        diff: &str,
+        message: Option<&str>,
+        query: Option<&str>,
+        crates: Option<&str>,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {r#"
+            You are a senior engineer assessing the impact of Git changes before they ship.
+            Judge what could break for the code and people depending on this change, not what the change does.
+            Respond with only a JSON object in this format, without code fences:
+            {"risk": "low|medium|high", "summary": "<one or two sentences on the overall risk>", "apis": [{"item": "<public function, type, endpoint, CLI flag or file format>", "change": "<how it changed and whether that's breaking>"}], "callers": [{"location": "<file:line or module>", "reason": "<why it's likely to break>"}], "migration": ["<configuration or migration step for users or deployers>"], "tests": ["<test worth adding or running>"]}
+            Leave a list empty when there's nothing for it. Only name callers you can see in the diff or reasonably infer from it.
+        "#});
+
+        let context = message
+            .map(|message| format!("Commit message: {message}\n\n"))
+            .unwrap_or_default();
+        let query = query
+            .map(|q| format!("Focus the assessment on: {q}\n\n"))
+            .unwrap_or_default();
+        let user_prompt = format!("{context}{query}```diff\n{diff}\n```");
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        }
+        .with_crates_section(crates))
+    }
+
+    /// Prompt summarizing one chunk of a large diff, for a later synthesis step.
+    pub fn build_diff_chunk_prompt(
+        chunk: &str,
+        index: usize,
+        total: usize,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You summarize one part of a larger Git diff. The summaries of all parts are combined later.
+            List the concrete changes (files, functions, behavior) tersely, without introduction or conclusion.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Part {index} of {total}:
+
+            ```diff
+            {chunk}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    fn build_explain_prompt_from(
+        input: &ExplainInput,
+        summaries: Option<&[String]>,
+        template: Option<&PromptTemplate>,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that explains Git changes in a concise way.
+            Focus only on the most significant changes and their direct impact.
+            When answering specific questions, address them directly and precisely.
+            Keep explanations brief but informative and don't ask for further explanations.
+            Use markdown for clarity.
+        "});
+
+        let changes = match summaries {
+            Some(summaries) => format!(
+                "The diff was too large to send at once. Summaries of its parts:\n\n{}",
+                summaries.join("\n\n")
+            ),
+            None => format!("```diff\n{}\n```", input.diff),
+        };
+
+        let base_content = match input.explained {
+            Explained::Commit { message } => {
+                formatdoc! {"
+                    Context - Commit:
+
+                    Message: {message}
+                    Changes:
+                    {changes}
+                    "
+                }
+            }
+            _ if input.commits.is_some() => {
+                formatdoc! {"
+                    Context - Branch:
+
+                    Commits, oldest first:
+                    {commits}
+
+                    Changes:
+                    {changes}
+                    ",
+                    commits = input.commits.unwrap_or_default(),
+                }
+            }
+            _ => {
+                formatdoc! {"
+                    Context - Changes:
+
+                    {changes}
+                    "
+                }
+            }
+        };
+
+        let user_prompt = match input.query {
+            Some(query) => {
+                formatdoc! {"
+                    {base_content}
+
+                    Question: {query}
+
+                    Provide a focused answer to the question based on the changes shown above.
+                    "
+                }
+            }
+            None => match input.explained {
+                Explained::Commit { .. } => formatdoc! {"
+                    {base_content}
+                    
+                    Provide a short explanation covering:
+                    1. Core changes made
+                    2. Direct impact
+                    "
+                },
+                Explained::WorkingTree => formatdoc! {"
+                    {base_content}
+                    
+                    Provide:
+                    1. Key changes
+                    2. Notable concerns (if any)
+                    "
+                },
+                Explained::Range if input.commits.is_some() => {
+                    formatdoc! {"
+                        {base_content}
+
+                        Provide:
+                        1. A short summary of the branch as a whole: what it sets out to do and its direct impact
+                        2. A \"Commits\" section with one line per commit, oldest first: `- <short sha>: <what it does>`
+                        "
+                    }
+                }
+                Explained::Range | Explained::Patch => formatdoc! {"
+                    {base_content}
+                    
+                    Provide:
+                    1. Core changes made
+                    2. Direct impact
+                    "
+                },
+            },
+        };
+
+        // What `explain` parses for structured formats like `--format json`, kept even when
+        // the user prompt is customized
+        let format = if input.structured {
+            String::from(indoc! {r#"
+                Respond with only a JSON object in this format, without code fences:
+                {"summary": "<the answer above, in markdown>", "files": [{"path": "<changed file>", "note": "<one or two sentences on what changed in it>"}]}
+                List every changed file once, in the order of the diff."#
+            })
+        } else {
+            String::new()
+        };
+        let user_prompt = if format.is_empty() {
+            user_prompt
+        } else {
+            format!("{}\n\n{format}", user_prompt.trim_end())
+        };
+
+        let message = input
+            .explained
+            .message()
+            .or(input.commits)
+            .unwrap_or_default();
+        let mut vars = vec![
+            ("diff", changes.as_str()),
+            ("message", message),
+            ("query", input.query.unwrap_or_default()),
+        ];
+        if !format.is_empty() {
+            vars.push(("format", &format));
+        }
+        // The changes come first and the question after, so asking another one about the
+        // same changes can reuse the cached start of the prompt
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: base_content.len(),
+        }
+        .customized(template, &vars)
+        .with_ci_section(input.diff)
+        .with_crates_section(input.crates))
+    }
+
+    /// Prompt for commit messages, returned as JSON, following the configured conventions.
+    pub fn build_draft_prompt(
+        input: &DraftInput,
+        template: Option<&PromptTemplate>,
+    ) -> Result<Self, AIPromptError> {
+        let diff = input.diff;
+
+        let system_prompt = String::from(indoc! {"
+            You are a commit message generator that follows these rules:
+            1. Write in present tense
+            2. Be concise and direct
+            3. Output only a JSON object, without any explanations or code fences
+            4. The message header is rendered as: <type>(<optional scope>): <subject>
+        "});
+
+        let context = if let Some(context) = input.context {
+            formatdoc!(
+                "
+                Use the following context to understand intent:
+                {context}
+                "
+            )
+        } else {
+            "".to_string()
+        };
+
+        let commits = input.squash.unwrap_or_default();
+        let squash = if input.squash.is_some() {
+            formatdoc! {"
+                The diff is a whole branch being squash-merged, so the messages of its commits will be discarded.
+                Write one message for the branch as a whole: a subject for its overall purpose and a body listing the notable changes as short `- ` bullets. Leave out fixups and back-and-forth between commits.
+                Commits on the branch, oldest first:
+                {commits}
+                "
+            }
+        } else if let Some(message) = input.amend {
+            formatdoc! {"
+                The diff is being added to the last commit with `git commit --amend`. Its current message is:
+                {message}
+                Update that message so it covers both what it already describes and the diff: keep what still applies, in the same style, and work the new changes in rather than appending a changelog of the amend.
+                "
+            }
+        } else {
+            String::new()
+        };
+
+        let count = if input.count > 1 {
+            formatdoc! {"
+                The messages array must contain {count} alternatives. Make them genuinely different, e.g. in scope, emphasis or wording.",
+                count = input.count,
+            }
+        } else {
+            "The messages array must contain exactly one message.".to_string()
+        };
+
+        // The taxonomy is validated too, so it goes with the format
+        let mut taxonomy = String::new();
+        let scopes = input.scopes;
+        if !scopes.is_empty() {
+            taxonomy.push_str(&format!(
+                "\nThe scope must be one of: {}; use null if none fits.",
+                scopes.join(", ")
+            ));
+        }
+        if input.gitmoji {
+            taxonomy.push_str("\nDon't add an emoji; the type's gitmoji is added for you.");
+        }
+
+        // What `draft` parses, kept even when the user prompt is customized
+        let format = formatdoc! {r#"
+            Respond with a JSON object in this format:
+            {{"messages": [{{"type": "<type>", "scope": "<optional scope, or null>", "subject": "<description without type or scope>", "body": "<optional body explaining what changed and why, or null>"}}]}}
+            {count}
+            The whole header must be a single line of at most {limit} characters.{taxonomy}"#,
+            limit = input.subject_limit,
+        };
+
+        let user_prompt = formatdoc! {"
+            Generate a concise git commit message written in present tense for the following code diff with the given specifications below:
+
+            {format}
+            Choose a type from the type-to-description JSON below that best describes the git diff:
+            {commit_types}
+            Focus on being accurate and concise.
+            {context}
+            {squash}
+            Leave the body null for small, self-explanatory changes. Never repeat the subject in the body.
+            Exclude anything unnecessary such as translation.
+
+            Code diff:
+            ```diff
+            {diff}
+            ```
+            ",
+            commit_types = input.commit_types,
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        }
+        .customized(
+            template,
+            &[
+                ("diff", diff),
+                ("context", input.context.unwrap_or_default()),
+                ("commit_types", input.commit_types),
+                ("commits", commits),
+                ("format", &format),
+            ],
+        ))
+    }
+
+    /// Prompt for the git command that does what `query` asks. With `can_inspect`, the model
+    /// is told to use the read-only git tools it's given before answering; with `dry_run`,
+    /// it's asked for each step's effect and risk too.
+    pub fn build_operate_prompt(
+        query: &str,
+        dry_run: bool,
+        template: Option<&PromptTemplate>,
+        can_inspect: bool,
+    ) -> Result<Self, AIPromptError> {
+        let mut system_prompt = String::from(indoc! {"
+        You're a Git assistant that provides commands with clear explanations.
+        - Include warnings ONLY for destructive commands (reset, push --force, clean, etc.)
+        - Omit warning tag completely for safe commands
+    "});
+        if can_inspect {
+            system_prompt.push_str(indoc! {"
+            - Use the git tools to check the repository (branches, status, history) when the request depends on it, and use the real names they return instead of placeholders
+            - Call tools only to gather facts; the command you propose is run by the user after confirmation
+        "});
+        }
+        let mut format = String::from(indoc! {"
+        <command>Git command</command>
+        <explanation>Brief explanation</explanation>
+        <warning>Required for destructive commands only - omit for safe commands</warning>
+        "});
+        // Nothing runs in a dry run, so the user reads each step and what could go wrong instead
+        if dry_run {
+            format.push_str(indoc! {"
+            <steps>
+            <step><run>One command of the chain</run><effect>What it changes in the repository</effect><risk>What could be lost or go wrong, or none</risk></step>
+            </steps>
+            "});
+        }
+        let user_prompt = formatdoc! {"
+        Generate Git command for: {query}
+        
+        {format}",
+            query = query
+        };
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        }
+        .customized(template, &[("query", query), ("format", &format)]))
+    }
+
+    /// Prompt explaining a single hunk, for editor integrations and the diff viewer.
+    pub fn build_explain_hunk_prompt(
+        file: Option<&str>,
+        hunk: &str,
+        query: Option<&str>,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that explains a single change in a file.
+            Explain what the change does and why it likely matters in a few sentences.
+            When answering specific questions, address them directly and precisely.
+            Use markdown for clarity and don't ask for further explanations.
+        "});
+
+        let file = file.map(|f| format!("File: {f}\n\n")).unwrap_or_default();
+        let query = query
+            .map(|q| format!("\n\nQuestion: {q}"))
+            .unwrap_or_default();
+        let user_prompt = format!("{file}```diff\n{hunk}\n```{query}");
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for a review of `diff` in markdown, with fixes as diffs `lumen apply` takes.
+    pub fn build_review_prompt(diff: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a careful code reviewer.
+            Point out bugs, risky changes, missing error handling and unclear code in the diff, most important first.
+            Reference the file and line for each finding and suggest a fix when it's not obvious.
+            Give code fixes as ```diff blocks with `--- a/path` and `+++ b/path` headers and a few lines of unchanged context, so they can be applied with `lumen apply`.
+            If nothing needs attention, say so in one sentence. Use markdown for clarity.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Review the following changes:
+
+            ```diff
+            {diff}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        }
+        .with_ci_section(diff))
+    }
+
+    /// Prompt for `lumen review`: findings as JSON, so they can be pinned to their lines.
+    pub fn build_structured_review_prompt(diff: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a careful code reviewer.
+            Point out bugs, risky changes, missing error handling and unclear code in the diff, most important first. Don't restate what the change does or list style nits.
+            Rate each finding `high` for bugs and security problems, `medium` for likely problems and missing handling, `low` for clarity and maintainability.
+            Give `line` as the line number in the new version of the file, on a line the diff adds or keeps.
+            Give each finding one `category`: `bug`, `security`, `performance`, `error-handling`, `style` or `maintainability`.
+            When a fix isn't obvious, give it in `fix` as a unified diff with `--- a/path` and `+++ b/path` headers and a few lines of unchanged context, otherwise leave `fix` out.
+            Return an empty list if nothing needs attention.
+            Output only a JSON object, without any explanations or code fences.
+        "});
+
+        // Findings have no sections, so pipeline-impacting changes are each reported instead
+        let ci = match ci_config_files(diff) {
+            files if files.is_empty() => String::new(),
+            files => formatdoc! {"
+                The changes touch CI configuration ({files}). Report every pipeline-impacting change in those files as a finding, even when it looks intended: {CI_CHECKS}. Rate new secrets, widened permissions, unpinned third-party code and untrusted triggers `high`.
+
+                ",
+                files = files.join(", "),
+            },
+        };
+        let user_prompt = formatdoc! {r#"
+            Respond with a JSON object in this format:
+            {{"findings": [{{"severity": "low" | "medium" | "high", "file": "<file path>", "line": <line number>, "category": "<category>", "title": "<one line>", "detail": "<why it matters>", "fix": "<unified diff>"}}]}}
+
+            {ci}Changes:
+            ```diff
+            {diff}
+            ```
+            "#
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt grouping the numbered hunks of a working tree diff into commits, for `lumen split`.
+    pub fn build_split_prompt(hunks: &str, context: Option<&str>) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You split a working tree full of changes into small, logical commits.
+            Each commit covers one concern that makes sense on its own, such as a feature, a fix, a refactor, tests or docs, and keeps hunks together that only work together.
+            Output only a JSON object, without any explanations or code fences.
+        "});
+
+        let context = context
+            .map(|context| format!("\nThe author says about the changes:\n{context}\n"))
+            .unwrap_or_default();
+        let user_prompt = formatdoc! {r#"
+            Respond with a JSON object in this format:
+            {{"commits": [{{"title": "<what the commit does, in a few words>", "hunks": [<hunk number>]}}]}}
+            Put every hunk in exactly one commit. Order the commits so each one builds on those before it.
+            {context}
+            Hunks:
+            {hunks}
+            "#
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt rating how much review attention each changed file needs. `diff` is left out
+    /// when it's too large, leaving the per-file summary to go on.
+    pub fn build_risk_prompt(files: &str, diff: Option<&str>) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a senior code reviewer triaging a change set.
+            Rate how much review attention each file needs: `high` for logic that is easy to get wrong (auth, data handling, concurrency, error paths), `medium` for ordinary logic or impactful config, `low` for tests, docs and mechanical edits.
+            The heuristic rating is a starting point; change it when the content says otherwise.
+            Output only a JSON object, without any explanations or code fences.
+        "});
+
+        let diff = diff
+            .map(|diff| format!("\nChanges:\n```diff\n{diff}\n```\n"))
+            .unwrap_or_default();
+        let user_prompt = formatdoc! {r#"
+            Respond with a JSON object in this format:
+            {{"files": [{{"path": "<file path as listed>", "risk": "low" | "medium" | "high"}}]}}
+
+            Files (status, kind, size of change, heuristic rating):
+            {files}{diff}"#
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for a one-line summary of the changes to one file, for the diff viewer's sidebar.
+    pub fn build_file_summary_prompt(path: &str, diff: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You summarize the changes to one file so a reviewer can decide which files to read first.
+            Answer with one line of at most 12 words on what changed and why it matters, without markdown or a leading file name.
+        "});
+
+        let user_prompt = formatdoc! {"
+            File: {path}
+
+            ```diff
+            {diff}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for a style review of a commit message, beyond the rules checked locally.
+    pub fn build_lint_message_prompt(message: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You review commit messages for a team that follows the Conventional Commits format.
+            The format, subject length and imperative mood are checked already; point out only what a careful reviewer would still ask to change: a vague or misleading subject, a body that explains how instead of why, or unclear wording.
+            Output only a JSON object, without any explanations or code fences:
+            {\"problems\": [\"<one sentence per problem>\"]}
+            Use an empty list when the message is fine; don't invent problems.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Commit message:
+
+            ```
+            {message}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for the subject of the commit being put together from the staged hunks.
+    pub fn build_commit_suggestion_prompt(diff: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You suggest the subject of the commit someone is putting together by staging hunks.
+            Write a Conventional Commits subject (`type(scope): description`) of at most 50 characters in the imperative mood.
+            If the staged hunks tell more than one story, name the main one in the subject and say in a short note which hunks don't belong.
+            Output only a JSON object, without any explanations or code fences:
+            {\"subject\": \"<subject>\", \"note\": \"<a few words, or empty when the hunks belong together>\"}
+        "});
+
+        let user_prompt = formatdoc! {"
+            Staged changes:
+
+            ```diff
+            {diff}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for a review summary written from the notes a reviewer took in the diff viewer.
+    pub fn build_review_notes_prompt(notes: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You tidy up the notes a reviewer took while reading a diff into a review summary they can send.
+            Start with a short paragraph on the overall impression, then list the notes by file as `- ` bullets.
+            Keep every `file:line` anchor and every point the reviewer made; fix wording and grammar, but don't add findings of your own.
+            Respond with only the markdown, without code fences or closing remarks.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Review notes:
+
+            {notes}
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for a pull request title and description covering a branch's commits and its
+    /// diff against `base`, with what the author says about it as `context`.
+    pub fn build_pr_prompt(
+        commits: &str,
+        base: &str,
+        diff: &str,
+        context: Option<&str>,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You write pull request descriptions for the reviewers of a change.
+            Explain what the branch does and why, not how each line changed; the reviewers will read the diff.
+            Output only a JSON object, without any explanations or code fences.
+        "});
+
+        let context = context
+            .map(|context| format!("\nThe author says about the change:\n{context}\n"))
+            .unwrap_or_default();
+        let user_prompt = formatdoc! {r#"
+            Respond with a JSON object in this format:
+            {{"title": "<PR title>", "summary": "<what the change does and why, in 1-3 sentences>", "changes": ["<notable change>"], "testing": ["<how it was or can be tested>"]}}
+            Keep the title under 72 characters, in the imperative mood, without a trailing period.
+            List 2-6 notable changes a reviewer should know about, leaving out fixups and back-and-forth between commits.
+            Base testing notes on the tests in the diff and what the change touches; use an empty array when there's nothing to say.
+            {context}
+            Commits on the branch, oldest first:
+            {commits}
+
+            Changes since {base}:
+            ```diff
+            {diff}
+            ```
+            "#
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for a changelog section following `layout`, from commits grouped by type.
+    pub fn build_changelog_prompt(commits: &str, layout: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You write release notes for the users of a project, not its developers.
+            Describe each change by what it means for users in one short line, merging related commits and leaving out refactors, tests, CI and other internal changes.
+            Respond with only the markdown section, without code fences or closing remarks.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Write the changelog section in this layout, keeping its heading as given and leaving out subsections with nothing in them:
+
+            {layout}
+
+            Commits, grouped by their conventional commit type (breaking changes first):
+
+            {commits}
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt tying together the explanations of several commits, for
+    /// `lumen explain <sha> <sha>...`.
+    pub fn build_commits_synthesis_prompt(
+        explanations: &str,
+        query: Option<&str>,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a helpful assistant that explains a series of Git commits taken together.
+            You are given each commit's subject and an explanation of what it does.
+            Describe what the commits achieve as a whole, how they build on each other, and anything that is still unfinished or inconsistent between them.
+            Do not repeat each commit's explanation; refer to commits by their short SHA when it helps.
+            Keep it under 200 words, in markdown.
+        "});
+
+        let task = match query {
+            Some(query) => format!("Answer this question about the commits as a whole: {query}"),
+            None => "Summarize what these commits do together.".to_string(),
+        };
+        let user_prompt = formatdoc! {"
+            {task}
+
+            {explanations}
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for the highlights of a team's recent commits, one line each.
+    pub fn build_digest_prompt(commits: &str) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You write the highlights section of a team activity digest for a standup.
+            Pick the 3-5 most notable changes and describe each in one short sentence, grouping related commits.
+            Respond with only a list of `- ` bullets, without headings or closing remarks.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Commits (sha, subject, author, top-level areas):
+
+            {commits}
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt for the periodic summary of a watch session, from the changes made since the
+    /// previous one (a diff, or only its stat when the diff is too large).
+    pub fn build_session_summary_prompt(
+        changes: &str,
+        minutes: u64,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a scribe keeping notes for a developer during a long work session.
+            Summarize what they did in the period you are given, as 2-5 terse `- ` bullets about intent and behavior rather than individual lines.
+            Respond with only the bullets, without headings or closing remarks.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Changes made in the last {minutes} minutes:
+
+            ```diff
+            {changes}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt any working model answers with `OK`, to check a provider is set up.
+    pub fn build_health_check_prompt() -> Result<Self, AIPromptError> {
+        Ok(AIPrompt {
+            system_prompt: "You are a health check endpoint.".to_string(),
+            user_prompt: "Reply with the single word: OK".to_string(),
+            stable_prefix: 0,
+        })
+    }
+
+    /// Prompt predicting whether picking the commit with `message` and `diff` conflicts with
+    /// what the current branch changed in the same files since its parent, `branch_diff`.
+    pub fn build_cherry_pick_prompt(
+        message: &str,
+        diff: &str,
+        branch_diff: &str,
+    ) -> Result<Self, AIPromptError> {
+        let system_prompt = String::from(indoc! {"
+            You are a Git assistant that predicts cherry-pick conflicts.
+            Compare the commit being picked with the changes the current branch made to the same files.
+            Start with a one-line verdict: `Conflict risk: none`, `Conflict risk: low` or `Conflict risk: high`.
+            Then briefly list the overlapping areas that are likely to conflict, if any.
+            Use markdown for clarity and don't ask for further explanations.
+        "});
+
+        let user_prompt = formatdoc! {"
+            Commit to cherry-pick:
+
+            Message: {message}
+            Changes:
+            ```diff
+            {diff}
+            ```
+
+            Changes on the current branch to the same files since the commit's parent:
+            ```diff
+            {branch_diff}
+            ```
+            "
+        };
+
+        Ok(AIPrompt {
+            system_prompt,
+            user_prompt,
+            stable_prefix: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_template() {
+        let rendered = render_template(
+            "Diff: {{diff}}\n{{ query }} {{unknown}}",
+            &[("diff", "+x"), ("query", "why?")],
+        );
+        assert_eq!(rendered, "Diff: +x\nwhy? {{unknown}}");
+        assert_eq!(
+            template_placeholders("{{diff}} and {{ query }} {{"),
+            vec!["diff", "query"]
+        );
+    }
+
+    #[test]
+    fn test_customized_keeps_response_format() {
+        let prompt = AIPrompt {
+            system_prompt: "default system".to_string(),
+            user_prompt: "default user".to_string(),
+            stable_prefix: 0,
+        };
+        let template = PromptTemplate {
+            system: None,
+            user: Some("House style. {{query}}".to_string()),
+        };
+        let prompt = prompt.customized(Some(&template), &[("query", "q"), ("format", "<tag>")]);
+        assert_eq!(prompt.system_prompt, "default system");
+        assert_eq!(prompt.user_prompt, "House style. q\n\n<tag>");
+    }
+
+    #[test]
+    fn test_system_prompt_in_language() {
+        let prompt = AIPrompt {
+            system_prompt: "Explain the changes.\n".to_string(),
+            user_prompt: String::new(),
+            stable_prefix: 0,
+        };
+        assert_eq!(prompt.system_prompt_in(None), "Explain the changes.\n");
+        let system = prompt.system_prompt_in(Some("Japanese"));
+        assert!(system.starts_with("Explain the changes.\n\nWrite everything"));
+        assert!(system.contains("in Japanese:"));
+    }
+
+    #[test]
+    fn test_rewrite_keeps_stable_prefix() {
+        let mut prompt = AIPrompt {
+            system_prompt: String::new(),
+            user_prompt: "diff: a b\nQuestion: a?".to_string(),
+            stable_prefix: 10,
+        };
+        assert_eq!(prompt.split_user_prompt(), ("diff: a b\n", "Question: a?"));
+        prompt.rewrite_user_prompt(|text| text.replace('a', "[a]"));
+        assert_eq!(
+            prompt.split_user_prompt(),
+            ("diff: [a] b\n", "Question: [a]?")
+        );
+    }
+
+    #[test]
+    fn test_ci_section_only_for_ci_changes() {
+        let prompt = || AIPrompt {
+            system_prompt: String::new(),
+            user_prompt: "Review this.\n".to_string(),
+            stable_prefix: 0,
+        };
+        let code = file_diff("src/a.rs", &["+x"]);
+        assert_eq!(
+            prompt().with_ci_section(&code).user_prompt,
+            "Review this.\n"
+        );
+
+        let diff = format!("{code}{}", file_diff(".github/workflows/ci.yml", &["+y"]));
+        let user_prompt = prompt().with_ci_section(&diff).user_prompt;
+        assert!(user_prompt.starts_with(
+            "Review this.\n\nThe changes touch CI configuration (.github/workflows/ci.yml)."
+        ));
+    }
+
+    fn file_diff(name: &str, hunks: &[&str]) -> String {
+        let mut diff = format!("diff --git a/{name} b/{name}\n--- a/{name}\n+++ b/{name}\n");
+        for hunk in hunks {
+            diff.push_str(&format!("@@ -1 +1 @@\n{hunk}\n"));
+        }
+        diff
+    }
+}
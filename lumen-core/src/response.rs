@@ -0,0 +1,36 @@
+//! Getting the JSON out of a model's response, which may wrap it in prose or code fences
+//! however much it's asked not to.
+
+/// The span from the first `{` to the last `}`, if there's one.
+pub fn json_object(response: &str) -> Option<&str> {
+    json_span(response, '{', '}')
+}
+
+/// The span from the first `[` to the last `]`, if there's one.
+pub fn json_array(response: &str) -> Option<&str> {
+    json_span(response, '[', ']')
+}
+
+fn json_span(response: &str, open: char, close: char) -> Option<&str> {
+    let start = response.find(open)?;
+    let end = response.rfind(close)?;
+    // A stray closing bracket before the first opening one is no span at all
+    (start < end).then(|| &response[start..=end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_object() {
+        assert_eq!(
+            json_object("Sure:\n```json\n{\"a\": {\"b\": 1}}\n```"),
+            Some("{\"a\": {\"b\": 1}}")
+        );
+        assert_eq!(json_object("}x{"), None);
+        assert_eq!(json_object("no JSON here"), None);
+        assert_eq!(json_array("[1, 2]"), Some("[1, 2]"));
+        assert_eq!(json_array("] then ["), None);
+    }
+}
@@ -0,0 +1,260 @@
+//! Side-by-side rows of two versions of a file, pairing deleted and inserted lines the way
+//! GitHub does, and the hunks and line ranges on them.
+
+use std::ops::RangeInclusive;
+
+use similar::{ChangeTag, TextDiff};
+use unicode_width::UnicodeWidthChar;
+
+/// Replace tabs with spaces up to the next multiple of `tab_width` columns; a width of 0
+/// drops them.
+pub fn expand_tabs(s: &str, tab_width: usize) -> String {
+    if tab_width == 0 {
+        return s.replace('\t', "");
+    }
+    let mut result = String::with_capacity(s.len());
+    let mut col = 0;
+    for c in s.chars() {
+        if c == '\t' {
+            let spaces = tab_width - (col % tab_width);
+            for _ in 0..spaces {
+                result.push(' ');
+            }
+            col += spaces;
+        } else {
+            result.push(c);
+            // Tab stops count columns, and CJK text and emoji take two
+            col += c.width().unwrap_or(0);
+        }
+    }
+    result
+}
+
+/// One row of a side-by-side diff
+pub struct DiffLine {
+    /// Line number and text on the old side, if the row has one
+    pub old_line: Option<(usize, String)>,
+    /// Line number and text on the new side, if the row has one
+    pub new_line: Option<(usize, String)>,
+    /// How the two sides differ
+    pub change_type: ChangeType,
+}
+
+/// How the two sides of a row differ
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ChangeType {
+    /// The same line on both sides
+    Equal,
+    /// Only on the old side
+    Delete,
+    /// Only on the new side
+    Insert,
+    /// A paired delete+insert, shown on the same row (GitHub-style)
+    Modified,
+}
+
+/// Computes a side-by-side diff using GitHub-style pairing.
+///
+/// This algorithm pairs consecutive deletions with consecutive insertions,
+/// showing them on the same row. This avoids the visual offset where a modified
+pub fn compute_side_by_side(
+    old: &str,
+    new: &str,
+    tab_width: usize,
+    ignore_whitespace: bool,
+) -> Vec<DiffLine> {
+    let old_lines: Vec<&str> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = new.split_inclusive('\n').collect();
+    // Lines are compared by key, but rows always show the real text of each side
+    let key = |line: &&str| {
+        if ignore_whitespace {
+            line.split_whitespace().collect::<String>()
+        } else {
+            line.to_string()
+        }
+    };
+    let old_keys: Vec<String> = old_lines.iter().map(key).collect();
+    let new_keys: Vec<String> = new_lines.iter().map(key).collect();
+    let old_keys: Vec<&str> = old_keys.iter().map(String::as_str).collect();
+    let new_keys: Vec<&str> = new_keys.iter().map(String::as_str).collect();
+    let diff = TextDiff::from_slices(&old_keys, &new_keys);
+    let old_text = |index: Option<usize>| {
+        expand_tabs(index.map_or("", |i| old_lines[i]).trim_end(), tab_width)
+    };
+    let new_text = |index: Option<usize>| {
+        expand_tabs(index.map_or("", |i| new_lines[i]).trim_end(), tab_width)
+    };
+
+    let mut lines = Vec::new();
+    let mut old_num = 1usize;
+    let mut new_num = 1usize;
+
+    // Collect all changes first
+    let changes: Vec<_> = diff.iter_all_changes().collect();
+    let mut i = 0;
+
+    while i < changes.len() {
+        let change = &changes[i];
+
+        match change.tag() {
+            ChangeTag::Equal => {
+                lines.push(DiffLine {
+                    old_line: Some((old_num, old_text(change.old_index()))),
+                    new_line: Some((new_num, new_text(change.new_index()))),
+                    change_type: ChangeType::Equal,
+                });
+                old_num += 1;
+                new_num += 1;
+                i += 1;
+            }
+            ChangeTag::Delete => {
+                // Collect consecutive deletions
+                let mut deletions = Vec::new();
+                while i < changes.len() && changes[i].tag() == ChangeTag::Delete {
+                    deletions.push((old_num, old_text(changes[i].old_index())));
+                    old_num += 1;
+                    i += 1;
+                }
+
+                // Collect consecutive insertions that follow
+                let mut insertions = Vec::new();
+                while i < changes.len() && changes[i].tag() == ChangeTag::Insert {
+                    insertions.push((new_num, new_text(changes[i].new_index())));
+                    new_num += 1;
+                    i += 1;
+                }
+
+                // Pair deletions with insertions
+                let max_len = deletions.len().max(insertions.len());
+                for j in 0..max_len {
+                    let old_line = deletions.get(j).cloned();
+                    let new_line = insertions.get(j).cloned();
+
+                    let change_type = match (&old_line, &new_line) {
+                        (Some(_), Some(_)) => ChangeType::Modified,
+                        (Some(_), None) => ChangeType::Delete,
+                        (None, Some(_)) => ChangeType::Insert,
+                        (None, None) => unreachable!(),
+                    };
+
+                    lines.push(DiffLine {
+                        old_line,
+                        new_line,
+                        change_type,
+                    });
+                }
+            }
+            ChangeTag::Insert => {
+                // Handle insertions that aren't preceded by deletions
+                lines.push(DiffLine {
+                    old_line: None,
+                    new_line: Some((new_num, new_text(change.new_index()))),
+                    change_type: ChangeType::Insert,
+                });
+                new_num += 1;
+                i += 1;
+            }
+        }
+    }
+    lines
+}
+
+/// Rows where each hunk (run of changed rows) starts.
+pub fn find_hunk_starts(lines: &[DiffLine]) -> Vec<usize> {
+    let mut hunks = Vec::new();
+    let mut in_hunk = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        let is_change = !matches!(line.change_type, ChangeType::Equal);
+        if is_change && !in_hunk {
+            hunks.push(i);
+            in_hunk = true;
+        } else if !is_change {
+            in_hunk = false;
+        }
+    }
+    hunks
+}
+
+/// Rows of the hunk the view is focused on: the one containing `row`, otherwise the next one
+/// below it, otherwise the last one above it.
+pub fn hunk_at(lines: &[DiffLine], row: usize) -> Option<RangeInclusive<usize>> {
+    let is_change = |i: usize| !matches!(lines[i].change_type, ChangeType::Equal);
+    let starts = find_hunk_starts(lines);
+    let start = starts
+        .iter()
+        .rev()
+        .find(|&&start| start <= row && (start..=row).all(is_change))
+        .or_else(|| starts.iter().find(|&&start| start > row))
+        .or_else(|| starts.last())?;
+    let end = (*start..lines.len())
+        .take_while(|&i| is_change(i))
+        .last()
+        .unwrap_or(*start);
+    Some(*start..=end)
+}
+
+/// First and last line number covered by `rows` on the old or the new side.
+pub fn line_range(
+    lines: &[DiffLine],
+    rows: RangeInclusive<usize>,
+    old_side: bool,
+) -> Option<(usize, usize)> {
+    let numbers: Vec<usize> = lines
+        .iter()
+        .skip(*rows.start())
+        .take(rows.count())
+        .filter_map(|line| {
+            let side = if old_side {
+                &line.old_line
+            } else {
+                &line.new_line
+            };
+            side.as_ref().map(|(num, _)| *num)
+        })
+        .collect();
+    Some((*numbers.iter().min()?, *numbers.iter().max()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_tabs_counts_columns() {
+        assert_eq!(expand_tabs("a\tb", 4), "a   b");
+        assert_eq!(expand_tabs("日\tb", 4), "日  b");
+        assert_eq!(expand_tabs("\tb", 0), "b");
+    }
+
+    #[test]
+    fn test_hunk_at() {
+        let lines = compute_side_by_side("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nx\ny\n", 4, false);
+        // Rows: a, b/B, c, d, e/x, -/y
+        assert_eq!(hunk_at(&lines, 1), Some(1..=1));
+        assert_eq!(hunk_at(&lines, 2), Some(4..=5));
+        assert_eq!(hunk_at(&lines, 0), Some(1..=1));
+        assert_eq!(hunk_at(&lines[..4], 3), Some(1..=1));
+    }
+
+    #[test]
+    fn test_line_range() {
+        let lines = compute_side_by_side("a\nb\nc\nd\ne\n", "a\nB\nc\nd\nx\ny\n", 4, false);
+        assert_eq!(line_range(&lines, 4..=5, false), Some((5, 6)));
+        assert_eq!(line_range(&lines, 4..=5, true), Some((5, 5)));
+        assert_eq!(line_range(&lines, 5..=5, true), None);
+    }
+
+    #[test]
+    fn test_ignore_whitespace_keeps_real_text() {
+        let old = "fn a() {\n  x\n}\n";
+        let new = "fn a() {\n    x\n}\n";
+        let lines = compute_side_by_side(old, new, 4, true);
+        assert!(find_hunk_starts(&lines).is_empty());
+        assert_eq!(lines[1].old_line, Some((2, "  x".to_string())));
+        assert_eq!(lines[1].new_line, Some((2, "    x".to_string())));
+
+        let lines = compute_side_by_side(old, new, 4, false);
+        assert_eq!(find_hunk_starts(&lines), vec![1]);
+    }
+}
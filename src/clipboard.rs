@@ -0,0 +1,78 @@
+//! Put text on the clipboard, from a local terminal or over SSH.
+//!
+//! The text is sent to the terminal as an OSC 52 escape, which terminals that support it put
+//! on the clipboard of the machine they run on, so copying works from an SSH session too
+//! (inside tmux, that needs `set -g set-clipboard on`). The native clipboard is set as well,
+//! for terminals that ignore the escape.
+
+use std::io::{self, IsTerminal, Write};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let group = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(BASE64_ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The escape that sets the system clipboard to `text`.
+fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+/// Send the escape to the terminal lumen runs in, which stdout may be piped away from.
+fn send_osc52(text: &str) -> io::Result<()> {
+    let sequence = osc52(text);
+    #[cfg(unix)]
+    if let Ok(mut tty) = std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        tty.write_all(sequence.as_bytes())?;
+        return tty.flush();
+    }
+    let mut stderr = io::stderr();
+    if !stderr.is_terminal() {
+        return Err(io::Error::other("not running in a terminal"));
+    }
+    stderr.write_all(sequence.as_bytes())?;
+    stderr.flush()
+}
+
+/// Copy `text` to the clipboard both ways; it only fails when neither could be tried.
+pub fn copy(text: &str) -> Result<(), String> {
+    let escape = send_osc52(text);
+    let native = arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text));
+    match (escape, native) {
+        (Err(e), Err(native)) => Err(format!("couldn't copy to the clipboard: {e}; {native}")),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_osc52_encodes_text() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("fix: ünïcode\n".as_bytes()), "Zml4OiDDvG7Dr2NvZGUK");
+        assert_eq!(osc52("hi"), "\x1b]52;c;aGk=\x07");
+    }
+}
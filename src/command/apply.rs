@@ -0,0 +1,534 @@
+//! Apply code suggestions from AI output to the working tree.
+//!
+//! Suggestions are read from ```` ```diff ```` blocks (or a bare unified diff) and from
+//! `<<<<<<< SEARCH` / `=======` / `>>>>>>> REPLACE` blocks preceded by the file path.
+//! Each one is applied in memory first, so a suggestion that no longer matches the file
+//! is reported as a conflict instead of being written.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+
+use inquire::Confirm;
+
+use crate::command::diff::{run_preview, FileDiff, FileStatus};
+use crate::config::configuration::DiffConfig;
+use crate::error::LumenError;
+use crate::logging;
+
+pub struct ApplyCommand {
+    /// File holding the suggestions; stdin when `None` or `-`
+    pub input: Option<PathBuf>,
+    /// Apply every suggestion that applies cleanly without asking
+    pub yes: bool,
+    /// Skip the diff viewer preview
+    pub no_preview: bool,
+    /// View settings for the preview
+    pub settings: DiffConfig,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Hunk {
+    /// 1-based line in the original file, when the header has one
+    old_start: Option<usize>,
+    /// Lines tagged ' ', '-' or '+'
+    lines: Vec<(char, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Change {
+    Patch(Vec<Hunk>),
+    Replace { search: String, replace: String },
+    Delete,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Suggestion {
+    path: String,
+    change: Change,
+}
+
+/// All suggestions for one file, applied together
+struct Planned {
+    path: String,
+    old: String,
+    existed: bool,
+    result: Result<Option<String>, String>,
+}
+
+/// `a/src/main.rs\t2024-01-01` -> `Some("src/main.rs")`, `/dev/null` -> `None`
+fn header_path(rest: &str) -> Option<String> {
+    let path = rest.split('\t').next().unwrap_or(rest).trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// `@@ -12,7 +12,8 @@` -> `Some(12)`. Counts are ignored since suggested hunks often get them wrong.
+fn hunk_start(header: &str) -> Option<usize> {
+    let old = header.strip_prefix("@@ -")?.split([',', ' ']).next()?;
+    old.parse().ok()
+}
+
+fn parse_unified(text: &str) -> Result<Vec<Suggestion>, LumenError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut suggestions = Vec::new();
+    let mut current: Option<(Option<String>, Option<String>, Vec<Hunk>)> = None;
+
+    let mut flush = |current: &mut Option<(Option<String>, Option<String>, Vec<Hunk>)>| {
+        if let Some((old, new, hunks)) = current.take() {
+            let change = if new.is_none() {
+                Change::Delete
+            } else {
+                Change::Patch(hunks)
+            };
+            if let Some(path) = new.or(old) {
+                suggestions.push(Suggestion { path, change });
+            }
+        }
+    };
+
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        // `--- ` only starts a file when `+++ ` follows; otherwise it removes a `-- ` line
+        if let (Some(old), Some(new)) = (
+            line.strip_prefix("--- "),
+            lines.get(i + 1).and_then(|next| next.strip_prefix("+++ ")),
+        ) {
+            flush(&mut current);
+            current = Some((header_path(old), header_path(new), Vec::new()));
+            i += 2;
+            continue;
+        }
+        if line.starts_with("@@") {
+            let Some((_, _, hunks)) = current.as_mut() else {
+                return Err(LumenError::CommandError(
+                    "patch hunk without a `--- a/path` / `+++ b/path` header".to_string(),
+                ));
+            };
+            hunks.push(Hunk {
+                old_start: hunk_start(line),
+                lines: Vec::new(),
+            });
+        } else if let Some(hunk) = current.as_mut().and_then(|(_, _, hunks)| hunks.last_mut()) {
+            match line.chars().next() {
+                Some(tag @ (' ' | '-' | '+')) => hunk.lines.push((tag, line[1..].to_string())),
+                // Context lines for blank lines often lose their leading space
+                None => hunk.lines.push((' ', String::new())),
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    flush(&mut current);
+    Ok(suggestions)
+}
+
+/// The file path on a line like ``In `src/main.rs`:`` or `**src/main.rs**`
+fn clean_path(line: &str) -> String {
+    let token = line.split_whitespace().last().unwrap_or(line);
+    token
+        .trim_matches(|c| matches!(c, '`' | '*' | ':' | '"' | '\''))
+        .to_string()
+}
+
+fn parse_suggestions(text: &str) -> Result<Vec<Suggestion>, LumenError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let mut suggestions = Vec::new();
+    let mut last_text: Option<&str> = None;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(info) = line.trim_start().strip_prefix("```") {
+            let mut words = info.split_whitespace();
+            if matches!(words.next(), Some("diff" | "patch")) {
+                let end = (i + 1..lines.len())
+                    .find(|&j| lines[j].trim_start().starts_with("```"))
+                    .unwrap_or(lines.len());
+                suggestions.extend(parse_unified(&lines[i + 1..end].join("\n"))?);
+                i = end + 1;
+                continue;
+            }
+            // ```rust src/main.rs
+            if let Some(path) = words.next() {
+                last_text = Some(path);
+            }
+            i += 1;
+            continue;
+        }
+
+        if line.trim() == "<<<<<<< SEARCH" {
+            let path = last_text.map(clean_path).ok_or_else(|| {
+                LumenError::CommandError(
+                    "SEARCH/REPLACE block without a file path before it".to_string(),
+                )
+            })?;
+            let mut search = Vec::new();
+            let mut replace = Vec::new();
+            let mut in_replace = false;
+            i += 1;
+            while i < lines.len() && lines[i].trim() != ">>>>>>> REPLACE" {
+                if !in_replace && lines[i].trim() == "=======" {
+                    in_replace = true;
+                } else if in_replace {
+                    replace.push(lines[i]);
+                } else {
+                    search.push(lines[i]);
+                }
+                i += 1;
+            }
+            if i == lines.len() {
+                return Err(LumenError::CommandError(format!(
+                    "SEARCH/REPLACE block for {path} is missing `>>>>>>> REPLACE`"
+                )));
+            }
+            suggestions.push(Suggestion {
+                path,
+                change: Change::Replace {
+                    search: search.join("\n"),
+                    replace: replace.join("\n"),
+                },
+            });
+            i += 1;
+            continue;
+        }
+
+        if !line.trim().is_empty() {
+            last_text = Some(line);
+        }
+        i += 1;
+    }
+
+    // Plain `git diff` output piped in without fences
+    if suggestions.is_empty() && text.lines().any(|line| line.starts_with("@@")) {
+        return parse_unified(text);
+    }
+    Ok(suggestions)
+}
+
+/// Where `block` occurs in `lines` at or after `from`, closest to `expected` when given.
+fn find_block(
+    lines: &[String],
+    block: &[&str],
+    from: usize,
+    expected: Option<usize>,
+) -> Result<usize, &'static str> {
+    if block.is_empty() {
+        return Ok(expected.unwrap_or(lines.len()).clamp(from, lines.len()));
+    }
+    let matches: Vec<usize> = (from..=lines.len().saturating_sub(block.len()))
+        .filter(|&start| {
+            start + block.len() <= lines.len()
+                && lines[start..start + block.len()]
+                    .iter()
+                    .zip(block)
+                    .all(|(line, expected)| line.trim_end() == expected.trim_end())
+        })
+        .collect();
+    match (matches.as_slice(), expected) {
+        ([], _) => Err("does not match"),
+        ([only], _) => Ok(*only),
+        (_, Some(expected)) => Ok(*matches
+            .iter()
+            .min_by_key(|start| start.abs_diff(expected))
+            .expect("matches is not empty")),
+        (_, None) => Err("matches more than one place"),
+    }
+}
+
+fn apply_hunks(old: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let mut lines: Vec<String> = old.lines().map(String::from).collect();
+    let mut offset: isize = 0;
+    let mut from = 0;
+
+    for (n, hunk) in hunks.iter().enumerate() {
+        let before: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|(tag, _)| *tag != '+')
+            .map(|(_, line)| line.as_str())
+            .collect();
+        let after: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|(tag, _)| *tag != '-')
+            .map(|(_, line)| line.as_str())
+            .collect();
+        let expected = hunk
+            .old_start
+            .map(|start| (start.saturating_sub(1) as isize + offset).max(0) as usize);
+        let start = find_block(&lines, &before, from, expected)
+            .map_err(|reason| format!("hunk {} {reason}", n + 1))?;
+        lines.splice(
+            start..start + before.len(),
+            after.iter().map(|line| line.to_string()),
+        );
+        offset += after.len() as isize - before.len() as isize;
+        from = start + after.len();
+    }
+
+    let mut new = lines.join("\n");
+    if !lines.is_empty() && (old.is_empty() || old.ends_with('\n')) {
+        new.push('\n');
+    }
+    Ok(new)
+}
+
+fn apply_replace(old: &str, search: &str, replace: &str) -> Result<String, String> {
+    if search.trim().is_empty() {
+        // An empty search creates the file
+        if !old.is_empty() {
+            return Err("empty SEARCH section for a file that already has content".to_string());
+        }
+        return Ok(format!("{replace}\n"));
+    }
+    match old.matches(search).count() {
+        0 => Err("SEARCH text not found".to_string()),
+        1 => Ok(old.replacen(search, replace, 1)),
+        n => Err(format!("SEARCH text matches {n} places")),
+    }
+}
+
+/// New content after applying `changes`, or `None` when the file is deleted
+fn apply_changes(old: &str, changes: &[&Change]) -> Result<Option<String>, String> {
+    let mut content = old.to_string();
+    for change in changes {
+        content = match change {
+            Change::Patch(hunks) => apply_hunks(&content, hunks)?,
+            Change::Replace { search, replace } => apply_replace(&content, search, replace)?,
+            Change::Delete => return Ok(None),
+        };
+    }
+    Ok(Some(content))
+}
+
+/// Suggested paths must stay inside the repository
+fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+fn repo_root() -> PathBuf {
+    logging::output(Command::new("git").args(["rev-parse", "--show-toplevel"]))
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+impl ApplyCommand {
+    fn read_input(&self) -> Result<String, LumenError> {
+        match self.input.as_deref() {
+            Some(path) if path != Path::new("-") => Ok(fs::read_to_string(path)?),
+            _ => {
+                let mut text = String::new();
+                io::stdin().read_to_string(&mut text)?;
+                Ok(text)
+            }
+        }
+    }
+
+    fn plan(root: &Path, suggestions: &[Suggestion]) -> Vec<Planned> {
+        let mut order: Vec<&str> = Vec::new();
+        let mut by_path: HashMap<&str, Vec<&Change>> = HashMap::new();
+        for suggestion in suggestions {
+            if !by_path.contains_key(suggestion.path.as_str()) {
+                order.push(&suggestion.path);
+            }
+            by_path
+                .entry(&suggestion.path)
+                .or_default()
+                .push(&suggestion.change);
+        }
+
+        order
+            .into_iter()
+            .map(|path| {
+                let existing = fs::read_to_string(root.join(path)).ok();
+                let old = existing.clone().unwrap_or_default();
+                let result = if is_safe_path(path) {
+                    apply_changes(&old, &by_path[path])
+                } else {
+                    Err("path is outside the repository".to_string())
+                };
+                Planned {
+                    path: path.to_string(),
+                    old,
+                    existed: existing.is_some(),
+                    result,
+                }
+            })
+            .collect()
+    }
+
+    pub fn execute(&self) -> Result<(), LumenError> {
+        let text = self.read_input()?;
+        let suggestions = parse_suggestions(&text)?;
+        if suggestions.is_empty() {
+            return Err(LumenError::CommandError(
+                "no code suggestions found (expected ```diff blocks or SEARCH/REPLACE blocks)"
+                    .to_string(),
+            ));
+        }
+
+        let root = repo_root();
+        let planned = Self::plan(&root, &suggestions);
+
+        let previews: Vec<FileDiff> = planned
+            .iter()
+            .filter_map(|plan| {
+                let new = plan.result.as_ref().ok()?;
+                Some(FileDiff {
+                    filename: plan.path.clone(),
+                    old_content: plan.old.clone(),
+                    new_content: new.clone().unwrap_or_default(),
+                    status: match (plan.existed, new) {
+                        (_, None) => FileStatus::Deleted,
+                        (false, Some(_)) => FileStatus::Added,
+                        (true, Some(_)) => FileStatus::Modified,
+                    },
+                    binary: None,
+                    submodule: None,
+                })
+            })
+            .collect();
+        if !previews.is_empty() && !self.no_preview && io::stdout().is_terminal() {
+            run_preview(previews, self.settings.clone())?;
+        }
+
+        let mut applied = 0;
+        let mut conflicts = Vec::new();
+        for plan in &planned {
+            let new = match &plan.result {
+                Ok(new) => new,
+                Err(reason) => {
+                    conflicts.push(format!("{}: {reason}", plan.path));
+                    continue;
+                }
+            };
+            let action = if new.is_some() {
+                "Apply changes to"
+            } else {
+                "Delete"
+            };
+            let accepted = self.yes
+                || Confirm::new(&format!("{action} {}?", plan.path))
+                    .with_default(true)
+                    .prompt()
+                    .map_err(|e| LumenError::CommandError(e.to_string()))?;
+            if !accepted {
+                continue;
+            }
+
+            // The file may have been edited while the preview was open
+            let path = root.join(&plan.path);
+            if fs::read_to_string(&path).unwrap_or_default() != plan.old {
+                conflicts.push(format!("{}: file changed since the preview", plan.path));
+                continue;
+            }
+            match new {
+                Some(content) => {
+                    if let Some(parent) = path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    fs::write(&path, content)?;
+                }
+                None => fs::remove_file(&path)?,
+            }
+            applied += 1;
+        }
+
+        println!("Applied {applied} of {} file(s).", planned.len());
+        if !conflicts.is_empty() {
+            println!("\x1b[33mNot applied:\x1b[0m");
+            for conflict in &conflicts {
+                println!("  {conflict}");
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "fn main() {\n    let x = 1;\n    println!(\"{x}\");\n}\n";
+
+    #[test]
+    fn test_diff_block_applies_with_wrong_line_numbers() {
+        let text = "Fix:\n\n```diff\n--- a/src/main.rs\n+++ b/src/main.rs\n@@ -40,3 +40,3 @@\n fn main() {\n-    let x = 1;\n+    let x = 2;\n     println!(\"{x}\");\n```\n";
+        let suggestions = parse_suggestions(text).unwrap();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].path, "src/main.rs");
+        let new = apply_changes(FILE, &[&suggestions[0].change]).unwrap();
+        assert_eq!(
+            new.as_deref(),
+            Some("fn main() {\n    let x = 2;\n    println!(\"{x}\");\n}\n")
+        );
+    }
+
+    #[test]
+    fn test_search_replace_block() {
+        let text = "In `src/main.rs`:\n```rust\n<<<<<<< SEARCH\n    let x = 1;\n=======\n    let x = 3;\n>>>>>>> REPLACE\n```\n";
+        let suggestions = parse_suggestions(text).unwrap();
+        assert_eq!(
+            suggestions,
+            vec![Suggestion {
+                path: "src/main.rs".to_string(),
+                change: Change::Replace {
+                    search: "    let x = 1;".to_string(),
+                    replace: "    let x = 3;".to_string(),
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn test_conflicts_are_detected() {
+        let stale = Change::Replace {
+            search: "let y = 1;".to_string(),
+            replace: "let y = 2;".to_string(),
+        };
+        assert!(apply_changes(FILE, &[&stale]).is_err());
+
+        let ambiguous = Hunk {
+            old_start: None,
+            lines: vec![('-', "a".to_string()), ('+', "b".to_string())],
+        };
+        assert_eq!(
+            apply_hunks("a\nx\na\n", &[ambiguous]),
+            Err("hunk 1 matches more than one place".to_string())
+        );
+    }
+
+    #[test]
+    fn test_new_and_deleted_files() {
+        let text = "--- /dev/null\n+++ b/NOTES.md\n@@ -0,0 +1,2 @@\n+# Notes\n+hello\n--- a/old.txt\n+++ /dev/null\n@@ -1 +0,0 @@\n-bye\n";
+        let suggestions = parse_suggestions(text).unwrap();
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(
+            apply_changes("", &[&suggestions[0].change]).unwrap(),
+            Some("# Notes\nhello\n".to_string())
+        );
+        assert_eq!(suggestions[1].change, Change::Delete);
+    }
+
+    #[test]
+    fn test_paths_stay_in_repo() {
+        assert!(is_safe_path("src/main.rs"));
+        assert!(!is_safe_path("../outside.rs"));
+        assert!(!is_safe_path("/etc/passwd"));
+    }
+}
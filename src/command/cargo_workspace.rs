@@ -0,0 +1,476 @@
+//! The crates of a Cargo project a change touches and how it changes their public API, so
+//! `lumen explain` can say whether a change is breaking.
+//!
+//! Public items are found with tree-sitter in both versions of each changed `.rs` file. Only a
+//! bare `pub` counts, and an item can still be out of reach through a private module, so the
+//! suggested version bump is a hint rather than a verdict.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use indoc::formatdoc;
+use lumen_core::patch::diff_files;
+use toml_edit::DocumentMut;
+use tree_sitter::{Node, Parser};
+
+use crate::git_entity::{diff::Diff, GitEntity};
+use crate::logging;
+
+/// Rust files parsed per explanation, so a sweeping change doesn't hold up the request
+const MAX_FILES: usize = 40;
+/// Changes listed per crate; the rest are only counted
+const MAX_LISTED: usize = 20;
+
+/// Item kinds that are part of the API when they're `pub`, with how they're labelled
+const ITEM_KINDS: &[(&str, &str)] = &[
+    ("function_item", "fn"),
+    ("function_signature_item", "fn"),
+    ("struct_item", "struct"),
+    ("union_item", "union"),
+    ("enum_item", "enum"),
+    ("trait_item", "trait"),
+    ("type_item", "type"),
+    ("const_item", "const"),
+    ("static_item", "static"),
+    ("mod_item", "mod"),
+    ("use_declaration", "use"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublicItem {
+    pub kind: &'static str,
+    /// Path within the file, like `Config::load` or `Mode::Fast`
+    pub name: String,
+    /// The declaration without its body, on one line
+    pub signature: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiChange {
+    Added(PublicItem),
+    Removed(PublicItem),
+    Changed {
+        before: PublicItem,
+        after: PublicItem,
+    },
+}
+
+impl ApiChange {
+    fn is_breaking(&self) -> bool {
+        !matches!(self, ApiChange::Added(_))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            ApiChange::Added(item) => format!("added {} `{}`", item.kind, item.name),
+            ApiChange::Removed(item) => {
+                format!(
+                    "removed {} `{}`: `{}`",
+                    item.kind, item.name, item.signature
+                )
+            }
+            ApiChange::Changed { before, after } => format!(
+                "changed {} `{}`: `{}` → `{}`",
+                after.kind, after.name, before.signature, after.signature
+            ),
+        }
+    }
+}
+
+/// Public items of a Rust source file, in the order they're declared.
+pub fn public_items(source: &str) -> Vec<PublicItem> {
+    let mut parser = Parser::new();
+    if parser
+        .set_language(&tree_sitter_rust::LANGUAGE.into())
+        .is_err()
+    {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+    let mut items = Vec::new();
+    collect(tree.root_node(), source.as_bytes(), "", false, &mut items);
+    items
+}
+
+/// Collect the public items among `node`'s children. `inherited` makes them public without
+/// `pub`, as for the methods of a public trait.
+fn collect(node: Node, source: &[u8], path: &str, inherited: bool, items: &mut Vec<PublicItem>) {
+    let text = |node: Node| {
+        node.utf8_text(source)
+            .unwrap_or_default()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let qualify = |name: &str| match path {
+        "" => name.to_string(),
+        path => format!("{path}::{name}"),
+    };
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() == "impl_item" {
+            // Methods of trait impls are as public as the trait, which is covered elsewhere
+            if child.child_by_field_name("trait").is_none() {
+                if let (Some(ty), Some(body)) = (
+                    child.child_by_field_name("type"),
+                    child.child_by_field_name("body"),
+                ) {
+                    collect(body, source, &qualify(&text(ty)), false, items);
+                }
+            }
+            continue;
+        }
+        let Some(&(_, kind)) = ITEM_KINDS.iter().find(|(k, _)| *k == child.kind()) else {
+            continue;
+        };
+        if !inherited && !is_pub(child, source) {
+            continue;
+        }
+
+        let name = match kind {
+            "use" => child.child_by_field_name("argument").map(text),
+            _ => child.child_by_field_name("name").map(text),
+        };
+        let Some(name) = name else {
+            continue;
+        };
+        // The declaration up to its body, or for constants up to their value
+        let end = child
+            .child_by_field_name("body")
+            .or_else(|| child.child_by_field_name("value"))
+            .map_or(child.end_byte(), |body| body.start_byte());
+        let signature = String::from_utf8_lossy(&source[child.start_byte()..end])
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches([' ', '='])
+            .to_string();
+        let name = qualify(&name);
+        items.push(PublicItem {
+            kind,
+            name: name.clone(),
+            signature,
+        });
+
+        let Some(body) = child.child_by_field_name("body") else {
+            continue;
+        };
+        match kind {
+            "mod" => collect(body, source, &name, false, items),
+            "trait" => collect(body, source, &name, true, items),
+            "struct" | "enum" => {
+                let mut cursor = body.walk();
+                for member in body.children(&mut cursor) {
+                    let (member_kind, public) = match member.kind() {
+                        "field_declaration" => ("field", is_pub(member, source)),
+                        "enum_variant" => ("variant", true),
+                        _ => continue,
+                    };
+                    if let (true, Some(member_name)) = (public, member.child_by_field_name("name"))
+                    {
+                        items.push(PublicItem {
+                            kind: member_kind,
+                            name: format!("{name}::{}", text(member_name)),
+                            signature: text(member),
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `node` is declared with a bare `pub`, not `pub(crate)` or the like.
+fn is_pub(node: Node, source: &[u8]) -> bool {
+    let mut cursor = node.walk();
+    let is_pub = node
+        .children(&mut cursor)
+        .any(|child| child.kind() == "visibility_modifier" && child.utf8_text(source) == Ok("pub"));
+    is_pub
+}
+
+/// How the public items changed from `before` to `after`: removed and changed items in the
+/// order of `before`, then added ones.
+pub fn api_changes(before: &[PublicItem], after: &[PublicItem]) -> Vec<ApiChange> {
+    let find = |items: &[PublicItem], item: &PublicItem| {
+        items
+            .iter()
+            .find(|other| other.kind == item.kind && other.name == item.name)
+            .cloned()
+    };
+    let mut changes = Vec::new();
+    for item in before {
+        match find(after, item) {
+            None => changes.push(ApiChange::Removed(item.clone())),
+            Some(new) if new.signature != item.signature => changes.push(ApiChange::Changed {
+                before: item.clone(),
+                after: new,
+            }),
+            Some(_) => {}
+        }
+    }
+    for item in after {
+        if find(before, item).is_none() {
+            changes.push(ApiChange::Added(item.clone()));
+        }
+    }
+    changes
+}
+
+/// The version bump the changes call for under Cargo's semver rules, where a `0.y.z` crate
+/// bumps `y` for breaking changes.
+pub fn suggested_bump(version: Option<&str>, changes: &[ApiChange]) -> &'static str {
+    let unstable = version.is_some_and(|v| v.starts_with("0."));
+    let breaking = changes.iter().any(ApiChange::is_breaking);
+    match (breaking, changes.is_empty(), unstable) {
+        (true, _, false) => "major",
+        (true, _, true) | (false, false, false) => "minor",
+        _ => "patch",
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CrateInfo {
+    name: String,
+    version: Option<String>,
+    /// Directory of its `Cargo.toml`, relative to the repository root
+    dir: String,
+}
+
+/// The crate `path` (relative to `root`) belongs to: the nearest `Cargo.toml` above it with a
+/// `[package]`.
+fn find_crate(root: &Path, path: &str) -> Option<CrateInfo> {
+    let mut dir = Path::new(path).parent();
+    while let Some(current) = dir {
+        let manifest = fs::read_to_string(root.join(current).join("Cargo.toml")).ok();
+        if let Some(doc) = manifest.and_then(|m| m.parse::<DocumentMut>().ok()) {
+            if let Some(name) = doc["package"]["name"].as_str() {
+                // `version.workspace = true` takes the workspace's version
+                let version = doc["package"]["version"]
+                    .as_str()
+                    .map(str::to_string)
+                    .or_else(|| workspace_version(root, current));
+                let dir = current.to_string_lossy();
+                return Some(CrateInfo {
+                    name: name.to_string(),
+                    version,
+                    dir: if dir.is_empty() {
+                        ".".into()
+                    } else {
+                        dir.into()
+                    },
+                });
+            }
+        }
+        dir = current.parent();
+    }
+    None
+}
+
+/// `[workspace.package] version` of the nearest workspace root above `dir`.
+fn workspace_version(root: &Path, dir: &Path) -> Option<String> {
+    dir.ancestors().find_map(|dir| {
+        let doc = fs::read_to_string(root.join(dir).join("Cargo.toml"))
+            .ok()?
+            .parse::<DocumentMut>()
+            .ok()?;
+        doc["workspace"]["package"]["version"]
+            .as_str()
+            .map(str::to_string)
+    })
+}
+
+/// `path` before (`old`) or after the change, `None` where it doesn't exist.
+fn file_version(entity: &GitEntity, root: &Path, path: &str, old: bool) -> Option<String> {
+    let rev = match entity {
+        GitEntity::Commit(commit) if old => format!("{}^", commit.full_hash),
+        GitEntity::Commit(commit) => commit.full_hash.clone(),
+        GitEntity::Diff(Diff::WorkingTree { staged: true, .. }) if old => "HEAD".to_string(),
+        // The index, which is what both sides of a working tree diff start from
+        GitEntity::Diff(Diff::WorkingTree { .. }) if old => String::new(),
+        GitEntity::Diff(Diff::WorkingTree { staged: true, .. }) => String::new(),
+        GitEntity::Diff(Diff::WorkingTree { .. }) => {
+            return fs::read_to_string(root.join(path)).ok();
+        }
+        GitEntity::Diff(Diff::CommitsRange { from, .. }) if old => from.clone(),
+        GitEntity::Diff(Diff::CommitsRange { to, .. }) => to.clone(),
+        // Not tied to any revision of this repository
+        GitEntity::Diff(Diff::Patch { .. }) => return None,
+    };
+    let output = logging::output(
+        Command::new("git")
+            .args(["show", &format!("{rev}:{path}")])
+            .current_dir(root),
+    )
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn repo_root() -> Option<PathBuf> {
+    let output =
+        logging::output(Command::new("git").args(["rev-parse", "--show-toplevel"])).ok()?;
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// The crates `entity` touches and their public API changes, as an instruction for the
+/// explain prompt. `None` outside Cargo projects.
+pub fn api_section(entity: &GitEntity) -> Option<String> {
+    let root = repo_root()?;
+    // Each crate's changed files, by crate name
+    let mut crates: BTreeMap<String, (CrateInfo, Vec<String>)> = BTreeMap::new();
+    for (path, _) in diff_files(entity.diff()) {
+        if let Some(info) = find_crate(&root, path) {
+            crates
+                .entry(info.name.clone())
+                .or_insert_with(|| (info, Vec::new()))
+                .1
+                .push(path.to_string());
+        }
+    }
+    if crates.is_empty() {
+        return None;
+    }
+
+    let mut parsed = 0;
+    let mut lines = Vec::new();
+    for (info, paths) in crates.values() {
+        let mut changes = Vec::new();
+        for path in paths.iter().filter(|path| path.ends_with(".rs")) {
+            if parsed == MAX_FILES {
+                break;
+            }
+            parsed += 1;
+            let items = |old| {
+                file_version(entity, &root, path, old)
+                    .map(|source| public_items(&source))
+                    .unwrap_or_default()
+            };
+            changes.extend(api_changes(&items(true), &items(false)));
+        }
+
+        let version = info
+            .version
+            .as_deref()
+            .map(|v| format!(" {v}"))
+            .unwrap_or_default();
+        let summary = if changes.is_empty() {
+            "no public API changes".to_string()
+        } else {
+            let breaking = changes.iter().filter(|c| c.is_breaking()).count();
+            format!(
+                "{} added, {breaking} removed or changed",
+                changes.len() - breaking
+            )
+        };
+        lines.push(format!(
+            "- `{}`{version} (`{}`): {summary}, suggested bump: {}",
+            info.name,
+            info.dir,
+            suggested_bump(info.version.as_deref(), &changes)
+        ));
+        for change in changes.iter().take(MAX_LISTED) {
+            lines.push(format!("  - {}", change.describe()));
+        }
+        if changes.len() > MAX_LISTED {
+            lines.push(format!("  - and {} more", changes.len() - MAX_LISTED));
+        }
+    }
+
+    Some(formatdoc! {"
+        The changes touch these crates of a Cargo project. Public API changes, found by comparing the `pub` items in both versions of each changed file:
+
+        {crates}
+
+        Always end the answer with a \"Crates and semver\" section saying, for each crate, what changed in its public API and whether it's a breaking change, with the version bump it needs. A `pub` item inside a private module isn't reachable from other crates, and new enum variants and struct fields are breaking unless the type is `#[non_exhaustive]`; take that into account rather than repeating the suggested bump.",
+        crates = lines.join("\n"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEFORE: &str = r#"
+        pub struct Config {
+            pub path: String,
+            retries: u32,
+        }
+
+        impl Config {
+            pub fn load(path: &str) -> Self { todo!() }
+            fn validate(&self) {}
+        }
+
+        pub(crate) fn helper() {}
+        pub const LIMIT: usize = 10;
+        pub enum Mode { Fast, Slow }
+    "#;
+
+    const AFTER: &str = r#"
+        pub struct Config {
+            pub path: String,
+            pub retries: u32,
+        }
+
+        impl Config {
+            pub fn load(path: &Path) -> Self { todo!() }
+        }
+
+        pub const LIMIT: usize = 20;
+        pub enum Mode { Fast }
+        pub trait Source { fn read(&self) -> String; }
+    "#;
+
+    #[test]
+    fn test_public_items() {
+        let names: Vec<String> = public_items(BEFORE)
+            .into_iter()
+            .map(|item| format!("{} {}", item.kind, item.name))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "struct Config",
+                "field Config::path",
+                "fn Config::load",
+                "const LIMIT",
+                "enum Mode",
+                "variant Mode::Fast",
+                "variant Mode::Slow",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_api_changes_and_bump() {
+        let changes = api_changes(&public_items(BEFORE), &public_items(AFTER));
+        let described: Vec<String> = changes.iter().map(ApiChange::describe).collect();
+        assert_eq!(
+            described,
+            vec![
+                "changed fn `Config::load`: `pub fn load(path: &str) -> Self` → `pub fn load(path: &Path) -> Self`",
+                "removed variant `Mode::Slow`: `Slow`",
+                "added field `Config::retries`",
+                "added trait `Source`",
+                "added fn `Source::read`",
+            ]
+        );
+        assert_eq!(suggested_bump(Some("1.2.0"), &changes), "major");
+        assert_eq!(suggested_bump(Some("0.4.1"), &changes), "minor");
+        assert_eq!(suggested_bump(Some("1.2.0"), &changes[2..]), "minor");
+        assert_eq!(suggested_bump(Some("0.4.1"), &changes[2..]), "patch");
+        assert_eq!(suggested_bump(None, &[]), "patch");
+    }
+}
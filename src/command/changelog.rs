@@ -0,0 +1,331 @@
+//! `lumen changelog`: a changelog section for the commits in a range, grouped by what they
+//! mean for users and written up by the provider.
+
+use std::fs;
+use std::path::PathBuf;
+
+use indoc::indoc;
+use serde_json::json;
+use spinoff::{spinners, Color, Spinner};
+
+use super::output::{Format, Report};
+use super::LumenCommand;
+
+use crate::vcs::git;
+use crate::{error::LumenError, provider::LumenProvider, webhook::WebhookConfig};
+use lumen_core::git::RangeOptions;
+
+/// Layout of a section, with `{version}` and `{date}` filled in before it goes to the model
+pub const KEEP_A_CHANGELOG: &str = indoc! {"
+    ## [{version}] - {date}
+
+    ### Added
+    ### Changed
+    ### Deprecated
+    ### Removed
+    ### Fixed
+    ### Security
+"};
+
+pub struct ChangelogCommand {
+    pub from: String,
+    pub to: String,
+    /// Version the section is for; `Unreleased` when the range ends at HEAD
+    pub version: String,
+    /// Which commits of the range get an entry
+    pub range_options: RangeOptions,
+    /// Layout to follow instead of Keep a Changelog
+    pub template: Option<String>,
+    pub format: Format,
+    /// File to add the section to the top of, instead of printing it
+    pub prepend: Option<PathBuf>,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+}
+
+/// How a commit matters to users, from its conventional commit type
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Group {
+    Breaking,
+    Features,
+    Fixes,
+    Other,
+}
+
+impl Group {
+    pub fn label(self) -> &'static str {
+        match self {
+            Group::Breaking => "breaking",
+            Group::Features => "features",
+            Group::Fixes => "fixes",
+            Group::Other => "other",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChangelogCommit {
+    pub sha: String,
+    pub subject: String,
+    pub body: String,
+    pub group: Group,
+}
+
+// Separators that can't appear in subjects or bodies
+const RECORD_SEP: char = '\x1e';
+const FIELD_SEP: char = '\x1f';
+
+/// Group a commit by its `type(scope)!:` prefix, after any gitmoji, and `BREAKING CHANGE:`
+/// footer.
+pub fn classify(subject: &str, body: &str) -> Group {
+    let prefix = subject.split_once(':').map_or("", |(prefix, _)| prefix);
+    let breaking = prefix.ends_with('!')
+        || body.lines().any(|line| {
+            line.starts_with("BREAKING CHANGE:") || line.starts_with("BREAKING-CHANGE:")
+        });
+    if breaking {
+        return Group::Breaking;
+    }
+    let kind = prefix
+        .split('(')
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches(|c: char| !c.is_alphanumeric())
+        .trim();
+    match kind.to_lowercase().as_str() {
+        "feat" | "feature" => Group::Features,
+        "fix" | "perf" | "security" => Group::Fixes,
+        _ => Group::Other,
+    }
+}
+
+/// Parse `git log --format=%x1e%h%x1f%s%x1f%b` output.
+pub fn parse_log(output: &str) -> Vec<ChangelogCommit> {
+    output
+        .split(RECORD_SEP)
+        .filter_map(|record| {
+            let mut fields = record.splitn(3, FIELD_SEP);
+            let sha = fields.next()?.trim().to_string();
+            let subject = fields.next()?.trim().to_string();
+            let body = fields.next().unwrap_or_default().trim().to_string();
+            Some(ChangelogCommit {
+                group: classify(&subject, &body),
+                sha,
+                subject,
+                body,
+            })
+        })
+        .collect()
+}
+
+/// Commits by group, for the changelog prompt. Bodies are indented under their subjects.
+pub fn format_commits_for_prompt(commits: &[ChangelogCommit]) -> String {
+    let mut sorted: Vec<&ChangelogCommit> = commits.iter().collect();
+    sorted.sort_by_key(|commit| commit.group);
+
+    let mut out = String::new();
+    let mut group = None;
+    for commit in sorted {
+        if group != Some(commit.group) {
+            group = Some(commit.group);
+            if !out.is_empty() {
+                out.push('\n');
+            }
+            out.push_str(&format!("{}:\n", commit.group.label()));
+        }
+        out.push_str(&format!("- {} {}\n", commit.sha, commit.subject));
+        for line in commit.body.lines().filter(|line| !line.trim().is_empty()) {
+            out.push_str(&format!("    {line}\n"));
+        }
+    }
+    out
+}
+
+/// `existing` with `section` added above its first `## ` section, keeping the title and
+/// introduction at the top. An empty file gets a `# Changelog` title.
+pub fn prepend_section(existing: &str, section: &str) -> String {
+    let section = format!("{}\n", section.trim_end());
+    if existing.trim().is_empty() {
+        return format!("# Changelog\n\n{section}");
+    }
+    let mut offset = 0;
+    for line in existing.split_inclusive('\n') {
+        if line.starts_with("## ") {
+            return format!("{}{section}\n{}", &existing[..offset], &existing[offset..]);
+        }
+        offset += line.len();
+    }
+    format!("{}\n\n{section}", existing.trim_end())
+}
+
+/// The section as the model wrote it, without a code fence around it.
+fn unfenced(response: &str) -> &str {
+    let trimmed = response.trim();
+    match trimmed.strip_prefix("```") {
+        Some(rest) => rest
+            .split_once('\n')
+            .map_or(rest, |(_, rest)| rest)
+            .trim_end()
+            .trim_end_matches("```")
+            .trim(),
+        None => trimmed,
+    }
+}
+
+/// `git log` arguments listing the commits of `range` that get an entry, merges included
+/// unless `options` say otherwise.
+fn log_args(range: &str, options: RangeOptions) -> Vec<String> {
+    let mut args = vec![
+        "log".to_string(),
+        "--no-color".to_string(),
+        format!("--format={RECORD_SEP}%h{FIELD_SEP}%s{FIELD_SEP}%b"),
+    ];
+    if options.first_parent {
+        args.push("--first-parent".to_string());
+    }
+    if options.no_merges {
+        args.push("--no-merges".to_string());
+    }
+    args.push(range.to_string());
+    args
+}
+
+impl ChangelogCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let range = format!("{}..{}", self.from, self.to);
+        let args = log_args(&range, self.range_options);
+        let commits = parse_log(&git(&args.iter().map(String::as_str).collect::<Vec<_>>())?);
+        if commits.is_empty() {
+            println!("No commits in {range}.");
+            return Ok(());
+        }
+
+        // A release is dated by its last commit; an unreleased section has no date yet
+        let date = if self.version == "Unreleased" {
+            String::new()
+        } else {
+            git(&["log", "-1", "--format=%cs", &self.to])?
+                .trim()
+                .to_string()
+        };
+        let layout = self
+            .template
+            .as_deref()
+            .unwrap_or(KEEP_A_CHANGELOG)
+            .replace("{version}", &self.version)
+            .replace("{date}", &date)
+            .replace(" - \n", "\n");
+
+        let mut spinner = Spinner::new(spinners::Dots, "Writing the changelog...", Color::Blue);
+        let response = provider
+            .changelog(&format_commits_for_prompt(&commits), &layout)
+            .await;
+        spinner.clear();
+        let section = unfenced(&response?).to_string();
+
+        if !self.format.interactive() {
+            let commits_json: Vec<_> = commits
+                .iter()
+                .map(|c| {
+                    json!({
+                        "sha": c.sha,
+                        "subject": c.subject,
+                        "group": c.group.label(),
+                    })
+                })
+                .collect();
+            let data = json!({
+                "version": self.version,
+                "date": (!date.is_empty()).then_some(&date),
+                "range": range,
+                "section": section,
+                "commits": commits_json,
+            });
+            let title = format!("Changelog for {}", self.version);
+            let report = Report::new("changelog", title, section, data);
+            return self.format.emit(&report, self.webhook.as_ref()).await;
+        }
+
+        match &self.prepend {
+            Some(path) => {
+                let existing = match fs::read_to_string(path) {
+                    Ok(existing) => existing,
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+                    Err(e) => return Err(e.into()),
+                };
+                fs::write(path, prepend_section(&existing, &section))?;
+                println!("Added {} to {}.", self.version, path.display());
+            }
+            None => LumenCommand::print_markdown(&section),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_and_group() {
+        assert_eq!(classify("feat(cli)!: drop --old", ""), Group::Breaking);
+        assert_eq!(
+            classify("fix: parse ranges", "BREAKING CHANGE: ranges need two dots"),
+            Group::Breaking
+        );
+        assert_eq!(classify("feat(diff): add themes", ""), Group::Features);
+        assert_eq!(classify("perf: cache lookups", ""), Group::Fixes);
+        assert_eq!(classify("✨ feat: add login", ""), Group::Features);
+        assert_eq!(classify("Update README", ""), Group::Other);
+
+        let log = "\x1ea1\x1ffix: x\x1f\n\x1eb2\x1ffeat: y\x1fMore about y\n\nSigned-off-by: A\n";
+        let commits = parse_log(log);
+        assert_eq!(commits[1].body, "More about y\n\nSigned-off-by: A");
+        assert_eq!(
+            format_commits_for_prompt(&commits),
+            "features:\n- b2 feat: y\n    More about y\n    Signed-off-by: A\n\nfixes:\n- a1 fix: x\n"
+        );
+    }
+
+    #[test]
+    fn test_log_args_follow_range_options() {
+        let flags = |first_parent, no_merges| -> Vec<String> {
+            log_args(
+                "v1..v2",
+                RangeOptions {
+                    first_parent,
+                    no_merges,
+                },
+            )
+            .into_iter()
+            .filter(|arg| arg == "--first-parent" || arg == "--no-merges")
+            .collect()
+        };
+        assert!(flags(false, false).is_empty());
+        assert_eq!(flags(true, false), ["--first-parent"]);
+        assert_eq!(flags(false, true), ["--no-merges"]);
+        assert_eq!(
+            log_args("v1..v2", RangeOptions::default()).last().unwrap(),
+            "v1..v2"
+        );
+    }
+
+    #[test]
+    fn test_prepend_section() {
+        let existing =
+            "# Changelog\n\nAll notable changes.\n\n## [1.0.0] - 2024-01-01\n\n- First\n";
+        assert_eq!(
+            prepend_section(existing, "## [1.1.0] - 2024-02-01\n\n### Fixed\n- Second\n\n"),
+            "# Changelog\n\nAll notable changes.\n\n## [1.1.0] - 2024-02-01\n\n### Fixed\n- Second\n\n## [1.0.0] - 2024-01-01\n\n- First\n"
+        );
+        assert_eq!(
+            prepend_section("", "## [Unreleased]"),
+            "# Changelog\n\n## [Unreleased]\n"
+        );
+        assert_eq!(
+            prepend_section("# Changelog\n", "## [Unreleased]"),
+            "# Changelog\n\n## [Unreleased]\n"
+        );
+        assert_eq!(unfenced("```markdown\n## [1.0.0]\n```"), "## [1.0.0]");
+    }
+}
@@ -0,0 +1,220 @@
+use std::io::{self, Write};
+use std::process::Command;
+
+use spinoff::{spinners, Color, Spinner};
+
+use crate::{
+    commit_reference::CommitReference,
+    config::configuration::{DiffConfig, DraftConfig},
+    error::LumenError,
+    git_entity::commit::Commit,
+    logging,
+    provider::LumenProvider,
+};
+
+use super::diff::{run_diff_ui, DiffOptions};
+use super::LumenCommand;
+
+pub struct CherryPickCommand {
+    pub shas: Vec<String>,
+}
+
+/// What the user decided to do with a single pick.
+#[derive(Debug, PartialEq)]
+enum PickDecision {
+    Apply,
+    Skip,
+    Abort,
+}
+
+impl PickDecision {
+    /// The decision for an answer to the `[y/N/q]` prompt, skipping on anything unrecognized.
+    fn from_answer(answer: &str) -> Self {
+        match answer.trim().to_lowercase().as_str() {
+            "y" | "yes" => PickDecision::Apply,
+            "q" | "quit" => PickDecision::Abort,
+            _ => PickDecision::Skip,
+        }
+    }
+}
+
+impl CherryPickCommand {
+    /// Walk the picks in order: preview each commit in the diff viewer, ask the provider to
+    /// flag likely conflicts with the current branch, then apply it after confirmation.
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let commits = self
+            .shas
+            .iter()
+            .map(|sha| Commit::new(sha.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let total = commits.len();
+        let mut applied = 0;
+
+        for (i, commit) in commits.iter().enumerate() {
+            let short = short_hash(&commit.full_hash);
+            let subject = commit.message.lines().next().unwrap_or_default();
+            LumenCommand::print_markdown(&format!(
+                "# Pick {}/{}: `{short}` {subject}",
+                i + 1,
+                total
+            ));
+
+            run_diff_ui(DiffOptions {
+                reference: Some(CommitReference::Single(commit.full_hash.clone())),
+                pr: None,
+                file: None,
+                watch: false,
+                watch_paths: Vec::new(),
+                review_flow: false,
+                read_only: true,
+                record: None,
+                replay: None,
+                risk: None,
+                provider: None,
+                provider_error: None,
+                summarize_every: None,
+                summary_log: None,
+                file_summaries: false,
+                preview: false,
+                snapshot: None,
+                notes: Vec::new(),
+                focus: None,
+                settings: DiffConfig::default(),
+                draft: DraftConfig::default(),
+            })?;
+
+            let branch_diff = Self::branch_divergence(commit)?;
+            if branch_diff.is_empty() {
+                println!("No changes on the current branch overlap with this commit.");
+            } else {
+                let mut spinner = Spinner::new(
+                    spinners::Dots,
+                    "Checking for likely conflicts...",
+                    Color::Blue,
+                );
+                let result = provider.cherry_pick(commit, &branch_diff).await?;
+                spinner.success("Done");
+                LumenCommand::print_markdown(&result);
+            }
+
+            match Self::confirm(&short)? {
+                PickDecision::Apply => {
+                    Self::apply(commit)?;
+                    applied += 1;
+                    println!("\x1b[32mApplied {short}\x1b[0m\n");
+                }
+                PickDecision::Skip => println!("Skipped {short}\n"),
+                PickDecision::Abort => {
+                    println!("Cherry-pick aborted.");
+                    break;
+                }
+            }
+        }
+
+        println!("{applied}/{total} commits applied.");
+        Ok(())
+    }
+
+    /// Diff of what the current branch changed, since the commit's parent, in the files the
+    /// commit touches. Overlap here is where a cherry-pick is likely to conflict.
+    fn branch_divergence(commit: &Commit) -> Result<String, LumenError> {
+        let parent = format!("{}^", commit.full_hash);
+        if Commit::is_valid_commit(&parent).is_err() {
+            // Root commits have no parent to compare against
+            return Ok(String::new());
+        }
+
+        let output = logging::output(Command::new("git").args([
+            "diff-tree",
+            "--no-commit-id",
+            "--name-only",
+            "-r",
+            &commit.full_hash,
+        ]))?;
+        let files = changed_files(&String::from_utf8(output.stdout)?);
+        if files.is_empty() {
+            return Ok(String::new());
+        }
+
+        let output = logging::output(
+            Command::new("git")
+                .args(["diff", "--no-color", &parent, "HEAD", "--"])
+                .args(&files),
+        )?;
+
+        Ok(String::from_utf8(output.stdout)?)
+    }
+
+    fn confirm(short: &str) -> Result<PickDecision, LumenError> {
+        print!("\nApply {short}? [y/N/q] ");
+        io::stdout().flush()?;
+
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+
+        Ok(PickDecision::from_answer(&input))
+    }
+
+    fn apply(commit: &Commit) -> Result<(), LumenError> {
+        let output = logging::output(Command::new("git").args(["cherry-pick", &commit.full_hash]))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8(output.stderr)?;
+            return Err(LumenError::CommandError(format!(
+                "cherry-pick of {} stopped: {}\n(hint: resolve the conflicts and run `git cherry-pick --continue`, or `git cherry-pick --abort`)",
+                short_hash(&commit.full_hash),
+                stderr.trim()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn short_hash(hash: &str) -> String {
+    hash.chars().take(7).collect()
+}
+
+/// The paths in `git diff-tree --name-only` output.
+fn changed_files(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter(|l| !l.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pick_decision_from_answer() {
+        assert_eq!(PickDecision::from_answer("y\n"), PickDecision::Apply);
+        assert_eq!(PickDecision::from_answer(" YES "), PickDecision::Apply);
+        assert_eq!(PickDecision::from_answer("q\n"), PickDecision::Abort);
+        assert_eq!(PickDecision::from_answer("quit"), PickDecision::Abort);
+        assert_eq!(PickDecision::from_answer("\n"), PickDecision::Skip);
+        assert_eq!(PickDecision::from_answer("n"), PickDecision::Skip);
+        assert_eq!(PickDecision::from_answer("maybe"), PickDecision::Skip);
+    }
+
+    #[test]
+    fn test_changed_files() {
+        assert_eq!(
+            changed_files("src/main.rs\nREADME.md\n\n"),
+            vec!["src/main.rs".to_string(), "README.md".to_string()]
+        );
+        assert!(changed_files("").is_empty());
+    }
+
+    #[test]
+    fn test_short_hash() {
+        assert_eq!(
+            short_hash("ec1a2d6aac5e10095fb5b2ac79c74c29e8fcffa9"),
+            "ec1a2d6"
+        );
+        assert_eq!(short_hash("abc"), "abc");
+    }
+}
@@ -0,0 +1,90 @@
+//! Shell completions and man pages, for installing by hand or from a package.
+//!
+//! Completion scripts call back into `lumen` on every Tab press (`COMPLETE=<shell> lumen --
+//! <words>`), so what they offer is always current: subcommands and flags of the installed
+//! version, provider names, and the profiles in the config files at that moment.
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use clap::CommandFactory;
+use clap_complete::env::Shells;
+use clap_complete::{CompletionCandidate, Shell};
+
+use crate::config::cli::Cli;
+use crate::config::configuration::profile_names;
+use crate::error::LumenError;
+
+/// The environment variable that tells `lumen` to complete instead of running
+const COMPLETE_VAR: &str = "COMPLETE";
+
+/// Print the script that registers completions for `shell`.
+pub fn print_completions(shell: Shell) -> Result<(), LumenError> {
+    let mut out = io::stdout().lock();
+    write_completions(shell, &mut out)?;
+    Ok(out.flush()?)
+}
+
+fn write_completions(shell: Shell, out: &mut dyn Write) -> Result<(), LumenError> {
+    let name = shell.to_string();
+    let shells = Shells::builtins();
+    let completer = shells.completer(&name).ok_or_else(|| {
+        LumenError::InvalidArguments(format!("completions aren't available for {name}"))
+    })?;
+    completer.write_registration(COMPLETE_VAR, "lumen", "lumen", "lumen", out)?;
+    Ok(())
+}
+
+/// Print the man page for `lumen`, or with `dir`, write one page per command into it, like
+/// `lumen.1` and `lumen-explain.1`.
+pub fn print_man(dir: Option<&Path>) -> Result<(), LumenError> {
+    match dir {
+        Some(dir) => {
+            std::fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(Cli::command(), dir)?;
+            println!("Wrote man pages to {}", dir.display());
+            Ok(())
+        }
+        None => {
+            let mut out = io::stdout().lock();
+            clap_mangen::Man::new(Cli::command()).render(&mut out)?;
+            Ok(out.flush()?)
+        }
+    }
+}
+
+/// Profiles to complete `--profile` with.
+pub fn profile_candidates() -> Vec<CompletionCandidate> {
+    profile_names()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_scripts_call_back_into_lumen() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut script = Vec::new();
+            write_completions(shell, &mut script).unwrap();
+            let script = String::from_utf8(script).unwrap();
+            assert!(script.contains("COMPLETE"), "{shell}: {script}");
+            assert!(script.contains("lumen"), "{shell}: {script}");
+        }
+    }
+
+    #[test]
+    fn test_man_page_covers_subcommands() {
+        let mut page = Vec::new();
+        clap_mangen::Man::new(Cli::command())
+            .render(&mut page)
+            .unwrap();
+        let page = String::from_utf8(page).unwrap();
+        assert!(page.contains(".TH lumen 1"), "{page}");
+        assert!(page.contains("explain"));
+        assert!(page.contains("completions"));
+    }
+}
@@ -0,0 +1,370 @@
+use crate::config::cli::ProviderType;
+use crate::config::{keychain, ProviderInfo, ALL_PROVIDERS};
+use crate::error::LumenError;
+use crate::provider::{ollama, LumenProvider};
+use dirs::home_dir;
+use inquire::{Confirm, Select, Text};
+use serde_json::{json, Value};
+use spinoff::{spinners, Color, Spinner};
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::time::Instant;
+
+mod settings;
+
+/// Wrapper for display in the selection prompt
+struct ProviderChoice(&'static ProviderInfo);
+
+impl fmt::Display for ProviderChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.display_name)
+    }
+}
+
+/// Command to handle interactive configuration of Lumen features.
+pub struct ConfigureCommand {
+    /// Use the step-by-step prompts instead of the settings editor
+    pub wizard: bool,
+}
+
+impl ConfigureCommand {
+    pub async fn execute(&self) -> Result<(), LumenError> {
+        if self.wizard {
+            Self::run_wizard().await
+        } else {
+            settings::run().await
+        }
+    }
+
+    /// Runs the interactive configuration wizard.
+    ///
+    /// This process:
+    /// 1. Prompts the user to select an AI provider
+    /// 2. Asks for the base URL (custom OpenAI-compatible endpoints and Ollama only)
+    /// 3. Asks for an API key (if needed), offering to keep it in the system keychain
+    /// 4. Allows specifying a custom model name, picked from the installed ones for Ollama,
+    ///    which offers to pull it when it isn't
+    /// 5. Sends a test request, offering to save anyway if it fails
+    /// 6. Saves the configuration to `~/.config/lumen/lumen.config.json`
+    async fn run_wizard() -> Result<(), LumenError> {
+        println!("\n  \x1b[1;36mLumen Configuration\x1b[0m\n");
+
+        let provider = Self::select_provider()?;
+        let api_base_url = Self::get_api_base_url(provider)?;
+        let api_key = Self::get_api_key(provider)?;
+        let model = if provider.provider_type == ProviderType::Ollama {
+            Self::select_ollama_model(provider, api_base_url.as_deref()).await?
+        } else {
+            Self::get_model_name(provider)?
+        };
+
+        if !Self::validate(provider, &api_key, &model, &api_base_url).await? {
+            println!("\n  Configuration not saved.\n");
+            return Ok(());
+        }
+        let api_key = api_key
+            .map(|key| Self::store_api_key(provider, key))
+            .transpose()?;
+
+        Self::save_config(
+            provider,
+            api_base_url.as_deref(),
+            api_key.as_deref(),
+            model.as_deref(),
+        )?;
+
+        let config_path = Self::get_config_path()?;
+        println!(
+            "\n  \x1b[1;32m✓\x1b[0m Configuration saved to \x1b[2m{}\x1b[0m\n",
+            config_path.join("lumen.config.json").display()
+        );
+
+        Ok(())
+    }
+
+    /// Prompts the user to select an AI provider from the supported list.
+    fn select_provider() -> Result<&'static ProviderInfo, LumenError> {
+        let options: Vec<ProviderChoice> = ALL_PROVIDERS.iter().map(ProviderChoice).collect();
+
+        let selection = Select::new("Select your default AI provider:", options)
+            .with_help_message("↑↓ to move, enter to select, type to filter")
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+
+        Ok(selection.0)
+    }
+
+    /// Prompts for the endpoint of a custom OpenAI-compatible provider.
+    /// Returns `None` for providers with a fixed endpoint.
+    fn get_api_base_url(provider: &ProviderInfo) -> Result<Option<String>, LumenError> {
+        if provider.provider_type == ProviderType::Ollama {
+            let host = Text::new("Ollama host:")
+                .with_default(&ollama::host(None))
+                .with_help_message("Press Enter unless Ollama runs on another machine or port")
+                .prompt()
+                .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+            let host = ollama::host(Some(&host));
+            return Ok((host != ollama::DEFAULT_HOST).then_some(host));
+        }
+        if provider.provider_type != ProviderType::CustomOpenai {
+            return Ok(None);
+        }
+
+        let url = Text::new("Enter the API base URL:")
+            .with_help_message("e.g. http://localhost:1234/v1 for LM Studio")
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+
+        if url.is_empty() {
+            return Err(LumenError::ConfigurationError(
+                "an API base URL is required for this provider".to_string(),
+            ));
+        }
+        Ok(Some(url))
+    }
+
+    /// Prompts the user for an API key if the provider requires one.
+    /// Returns `None` if the user leaves the input empty (to use env var) or if the provider
+    /// is local (e.g. Ollama).
+    fn get_api_key(provider: &ProviderInfo) -> Result<Option<String>, LumenError> {
+        if provider.env_key.is_empty() {
+            let note = match provider.provider_type {
+                ProviderType::Bedrock => "Bedrock uses your AWS credentials — no API key needed.",
+                _ => "Ollama runs locally — no API key needed.",
+            };
+            println!("\n  \x1b[2m{note}\x1b[0m");
+            return Ok(None);
+        }
+
+        let prompt = format!(
+            "Enter your API key (or leave empty to use {}):",
+            provider.env_key
+        );
+
+        let api_key = Text::new(&prompt)
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+
+        if api_key.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(api_key))
+        }
+    }
+
+    /// Sends a one-line request with the collected settings. Returns whether to save them:
+    /// always when the request works, and only if the user insists when it doesn't.
+    async fn validate(
+        provider: &ProviderInfo,
+        api_key: &Option<String>,
+        model: &Option<String>,
+        api_base_url: &Option<String>,
+    ) -> Result<bool, LumenError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            format!("Testing {}...", provider.display_name),
+            Color::Blue,
+        );
+        let start = Instant::now();
+        let result = match LumenProvider::new(
+            provider.provider_type,
+            api_key.clone(),
+            model.clone(),
+            api_base_url.clone(),
+        ) {
+            Ok(client) => client.health_check().await.map_err(LumenError::from),
+            Err(e) => Err(e),
+        };
+
+        let error = match result {
+            Ok(()) => {
+                spinner.success(&format!(
+                    "{} responded in {} ms",
+                    provider.display_name,
+                    start.elapsed().as_millis()
+                ));
+                return Ok(true);
+            }
+            Err(e) => e,
+        };
+        spinner.fail(&format!("Test request failed: {error}"));
+
+        Confirm::new("Save the configuration anyway?")
+            .with_default(false)
+            .with_help_message("Useful when the provider is offline or the key isn't active yet")
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))
+    }
+
+    /// Offers to store the key in the system keychain. Returns what to save as `api_key`
+    /// in the config: a `keyring:` reference, or the key itself if the user declines.
+    fn store_api_key(provider: &ProviderInfo, api_key: String) -> Result<String, LumenError> {
+        let use_keychain = Confirm::new("Store the API key in the system keychain?")
+            .with_default(true)
+            .with_help_message("Otherwise it is saved in plain text in the config file")
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+
+        if !use_keychain {
+            return Ok(api_key);
+        }
+        keychain::store(provider.id, &api_key)?;
+        Ok(keychain::reference(provider.id))
+    }
+
+    /// Prompts the user for a custom model name.
+    /// Returns `None` if the user accepts the default model by pressing Enter.
+    fn get_model_name(provider: &ProviderInfo) -> Result<Option<String>, LumenError> {
+        // Custom endpoints have no default model to fall back on
+        if provider.default_model.is_empty() {
+            let model = Text::new("Enter model name:")
+                .prompt()
+                .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+            if model.is_empty() {
+                return Err(LumenError::ConfigurationError(
+                    "a model name is required for this provider".to_string(),
+                ));
+            }
+            return Ok(Some(model));
+        }
+
+        let prompt = format!(
+            "Enter model name (leave empty for default: {}):",
+            provider.default_model
+        );
+
+        let model = Text::new(&prompt)
+            .with_help_message("Press Enter to use the default model")
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+
+        if model.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(model))
+        }
+    }
+
+    /// Offers the models installed in Ollama, or any other name, and pulls the chosen model
+    /// if it isn't installed yet and the user agrees. Returns `None` for the default model.
+    async fn select_ollama_model(
+        provider: &ProviderInfo,
+        api_base_url: Option<&str>,
+    ) -> Result<Option<String>, LumenError> {
+        let host = ollama::host(api_base_url);
+        let installed = match ollama::list_models(&host).await {
+            Ok(installed) => installed,
+            Err(e) => {
+                println!("\n  \x1b[33mCouldn't reach Ollama at {host}: {e}\x1b[0m");
+                println!("  \x1b[2mStart it with `ollama serve`, or check the host.\x1b[0m\n");
+                return Self::get_model_name(provider);
+            }
+        };
+
+        const OTHER: &str = "Another model...";
+        let model = if installed.is_empty() {
+            Self::get_model_name(provider)?
+        } else {
+            let mut options: Vec<&str> = installed.iter().map(String::as_str).collect();
+            options.push(OTHER);
+            let choice = Select::new("Select a model:", options)
+                .with_help_message("Installed in Ollama; pick another to pull it")
+                .prompt()
+                .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+            if choice == OTHER {
+                Self::get_model_name(provider)?
+            } else {
+                Some(choice.to_string())
+            }
+        };
+
+        let name = model.as_deref().unwrap_or(provider.default_model);
+        if ollama::is_installed(&installed, name) {
+            return Ok(model);
+        }
+        let pull = Confirm::new(&format!("{name} isn't installed. Pull it now?"))
+            .with_default(true)
+            .prompt()
+            .map_err(|e| LumenError::ConfigurationError(e.to_string()))?;
+        if pull {
+            Self::pull_ollama_model(&host, name).await?;
+        }
+        Ok(model)
+    }
+
+    /// Pulls `model` into Ollama, showing its progress on one line.
+    async fn pull_ollama_model(host: &str, model: &str) -> Result<(), LumenError> {
+        let mut stderr = std::io::stderr();
+        ollama::pull(host, model, |status, progress| {
+            let line = match progress {
+                Some((total, completed)) if total > 0 => {
+                    format!("{status} {}%", completed * 100 / total)
+                }
+                _ => status.to_string(),
+            };
+            let _ = write!(stderr, "\r\x1b[2K  {line}");
+            let _ = stderr.flush();
+        })
+        .await?;
+        eprintln!("\r\x1b[2K  \x1b[1;32m✓\x1b[0m Pulled {model}");
+        Ok(())
+    }
+
+    /// Resolves the path to the configuration directory (`~/.config/lumen`).
+    fn get_config_path() -> Result<std::path::PathBuf, LumenError> {
+        let mut path = home_dir().ok_or_else(|| {
+            LumenError::ConfigurationError("Could not determine home directory".to_string())
+        })?;
+        path.push(".config");
+        path.push("lumen");
+        Ok(path)
+    }
+
+    /// Saves the selected configuration to the JSON config file.
+    /// If `model` is `None`, any existing `model` key in the config is removed to ensure
+    /// the provider's default is used.
+    fn save_config(
+        provider: &ProviderInfo,
+        api_base_url: Option<&str>,
+        api_key: Option<&str>,
+        model: Option<&str>,
+    ) -> Result<(), LumenError> {
+        let config_dir = Self::get_config_path()?;
+        fs::create_dir_all(&config_dir)?;
+
+        let config_file = config_dir.join("lumen.config.json");
+
+        let mut config: Value = if config_file.exists() {
+            let content = fs::read_to_string(&config_file)?;
+            serde_json::from_str(&content).unwrap_or_else(|_| json!({}))
+        } else {
+            json!({})
+        };
+
+        // Get provider ID from the type
+        config["provider"] = json!(provider.id);
+
+        if let Some(url) = api_base_url {
+            config["api_base_url"] = json!(url);
+        } else {
+            config.as_object_mut().map(|obj| obj.remove("api_base_url"));
+        }
+
+        if let Some(key) = api_key {
+            config["api_key"] = json!(key);
+        }
+
+        if let Some(m) = model {
+            config["model"] = json!(m);
+        } else {
+            // Remove model key to use provider default
+            config.as_object_mut().map(|obj| obj.remove("model"));
+        }
+
+        let content = serde_json::to_string_pretty(&config)?;
+        fs::write(&config_file, content)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,855 @@
+//! Full-screen editor for the global config file. Values are edited in place in the file's
+//! JSON, so keys the editor doesn't know about are kept when it saves.
+
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+    KeyModifiers,
+};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use crossterm::ExecutableCommand;
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+use serde_json::{json, Value};
+
+use crate::command::diff::terminal::TerminalGuard;
+use crate::command::diff::theme::{self, ThemeMode};
+use crate::config::cli::ProviderType;
+use crate::config::configuration::{
+    default_commit_types, read_global_config, write_global_config, LumenConfig,
+};
+use crate::config::{keychain, ProviderInfo, ALL_PROVIDERS};
+use crate::error::LumenError;
+use crate::provider::LumenProvider;
+use crate::shell;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Kind {
+    /// One of the supported providers, by id
+    Provider,
+    /// One of a fixed set of strings
+    Choice(&'static [&'static str]),
+    Toggle,
+    /// Stepped from `start` when unset
+    Integer {
+        min: u64,
+        max: u64,
+        step: u64,
+        start: u64,
+    },
+    Float {
+        min: f64,
+        max: f64,
+        step: f64,
+        start: f64,
+    },
+    Text,
+    /// Kept in the system keychain; the config holds a `keyring:` reference
+    Secret,
+    /// A JSON object edited in `$EDITOR`
+    Json,
+}
+
+struct Field {
+    section: &'static str,
+    label: &'static str,
+    path: &'static [&'static str],
+    kind: Kind,
+    /// Shown as `default (...)` while the value is unset
+    unset: &'static str,
+    help: &'static str,
+}
+
+const FIELDS: &[Field] = &[
+    Field {
+        section: "Provider",
+        label: "Provider",
+        path: &["provider"],
+        kind: Kind::Provider,
+        unset: "openai",
+        help: "AI provider for explain, draft, operate and the other AI commands",
+    },
+    Field {
+        section: "Provider",
+        label: "Model",
+        path: &["model"],
+        kind: Kind::Text,
+        unset: "",
+        help: "Model name; leave empty for the provider's default",
+    },
+    Field {
+        section: "Provider",
+        label: "API key",
+        path: &["api_key"],
+        kind: Kind::Secret,
+        unset: "",
+        help:
+            "Stored in the system keychain; leave empty to use the provider's environment variable",
+    },
+    Field {
+        section: "Provider",
+        label: "API base URL",
+        path: &["api_base_url"],
+        kind: Kind::Text,
+        unset: "none",
+        help: "Endpoint for custom-openai, eg: http://localhost:1234/v1",
+    },
+    Field {
+        section: "AI",
+        label: "Temperature",
+        path: &["ai", "temperature"],
+        kind: Kind::Float {
+            min: 0.0,
+            max: 2.0,
+            step: 0.1,
+            start: 1.0,
+        },
+        unset: "provider's",
+        help: "Sampling temperature for every command (0-2)",
+    },
+    Field {
+        section: "AI",
+        label: "Max diff tokens",
+        path: &["ai", "max_tokens"],
+        kind: Kind::Integer {
+            min: 4_000,
+            max: 1_000_000,
+            step: 4_000,
+            start: 64_000,
+        },
+        unset: "64000",
+        help: "Token budget for a diff sent in one request; larger diffs are chunked",
+    },
+    Field {
+        section: "AI",
+        label: "Secrets",
+        path: &["ai", "secrets"],
+        kind: Kind::Choice(&["redact", "fail"]),
+        unset: "redact",
+        help: "Redact likely secrets from prompts, or refuse to send them",
+    },
+    Field {
+        section: "AI",
+        label: "Compact diffs",
+        path: &["ai", "compact_diff"],
+        kind: Kind::Toggle,
+        unset: "off",
+        help: "Send diffs with one header line per file and less unchanged context",
+    },
+    Field {
+        section: "Draft",
+        label: "Commit types",
+        path: &["draft", "commit_types"],
+        kind: Kind::Json,
+        unset: "conventional",
+        help: "Commit types and their descriptions for `lumen draft`; enter opens $EDITOR",
+    },
+    Field {
+        section: "Diff view",
+        label: "Tab width",
+        path: &["diff", "tab_width"],
+        kind: Kind::Integer {
+            min: 1,
+            max: 8,
+            step: 1,
+            start: 4,
+        },
+        unset: "4",
+        help: "Columns per tab",
+    },
+    Field {
+        section: "Diff view",
+        label: "Tab widths",
+        path: &["diff", "tab_widths"],
+        kind: Kind::Json,
+        unset: "none",
+        help: "Columns per tab by file extension, eg: {\"go\": 8}; enter opens $EDITOR",
+    },
+    Field {
+        section: "Diff view",
+        label: "Context lines",
+        path: &["diff", "context_lines"],
+        kind: Kind::Integer {
+            min: 0,
+            max: 10,
+            step: 1,
+            start: 5,
+        },
+        unset: "5",
+        help: "Enclosing scope lines pinned above the diff; 0 turns them off",
+    },
+    Field {
+        section: "Diff view",
+        label: "Ignore whitespace",
+        path: &["diff", "ignore_whitespace"],
+        kind: Kind::Toggle,
+        unset: "off",
+        help: "Hide whitespace-only changes",
+    },
+    Field {
+        section: "Diff view",
+        label: "Theme",
+        path: &["diff", "theme"],
+        kind: Kind::Choice(&["dark", "light"]),
+        unset: "detected",
+        help: "Color theme of the diff viewer; unset, it follows the terminal's background",
+    },
+    Field {
+        section: "Diff view",
+        label: "Custom theme",
+        path: &["diff", "custom_theme"],
+        kind: Kind::Text,
+        unset: "none",
+        help: "A built-in theme like gruvbox-dark, or one made with `lumen theme edit`, by name or path",
+    },
+    Field {
+        section: "Diff view",
+        label: "Sidebar width",
+        path: &["diff", "sidebar_width"],
+        kind: Kind::Integer {
+            min: 20,
+            max: 60,
+            step: 5,
+            start: 30,
+        },
+        unset: "auto",
+        help: "Sidebar width in columns; sized to the terminal when unset",
+    },
+    Field {
+        section: "Diff view",
+        label: "Wrap lines",
+        path: &["diff", "wrap"],
+        kind: Kind::Toggle,
+        unset: "off",
+        help: "Wrap long lines instead of scrolling horizontally",
+    },
+    Field {
+        section: "Diff view",
+        label: "Indent guides",
+        path: &["diff", "indent_guides"],
+        kind: Kind::Toggle,
+        unset: "off",
+        help: "Faint guides at each indentation level of the diff panes",
+    },
+    Field {
+        section: "Diff view",
+        label: "Unified view",
+        path: &["diff", "unified"],
+        kind: Kind::Toggle,
+        unset: "off",
+        help: "Old and new lines interleaved in one panel instead of side by side",
+    },
+    Field {
+        section: "Diff view",
+        label: "Moved code",
+        path: &["diff", "detect_moves"],
+        kind: Kind::Toggle,
+        unset: "off",
+        help: "Color definitions that were moved or reindented instead of showing them as deleted and added",
+    },
+];
+
+fn get<'a>(config: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    path.iter()
+        .try_fold(config, |value, key| value.get(key))
+        .filter(|value| !value.is_null())
+}
+
+/// Set the value at `path`, creating parent objects; `None` removes it.
+fn set(config: &mut Value, path: &[&str], value: Option<Value>) {
+    let Some((last, parents)) = path.split_last() else {
+        return;
+    };
+    let mut object = config;
+    for key in parents {
+        if !object.get(key).is_some_and(Value::is_object) {
+            if value.is_none() {
+                return;
+            }
+            object[*key] = json!({});
+        }
+        object = &mut object[*key];
+    }
+    match value {
+        Some(value) => object[*last] = value,
+        None => {
+            if let Some(map) = object.as_object_mut() {
+                map.remove(*last);
+            }
+        }
+    }
+}
+
+/// The next of `options` after `current` (or before it), wrapping around
+fn cycle<T: PartialEq + Clone>(options: &[T], current: &T, forward: bool) -> T {
+    let index = options.iter().position(|o| o == current).unwrap_or(0);
+    let next = if forward {
+        (index + 1) % options.len()
+    } else {
+        (index + options.len() - 1) % options.len()
+    };
+    options[next].clone()
+}
+
+fn provider_info(config: &Value) -> &'static ProviderInfo {
+    let provider = get(config, &["provider"])
+        .and_then(Value::as_str)
+        .and_then(|id| id.parse::<ProviderType>().ok())
+        .unwrap_or(ProviderType::Openai);
+    ProviderInfo::for_provider(provider)
+}
+
+impl Field {
+    fn display(&self, config: &Value) -> String {
+        let Some(value) = get(config, self.path) else {
+            return match self.kind {
+                Kind::Text if self.path == ["model"] => {
+                    format!("default ({})", provider_info(config).default_model)
+                }
+                Kind::Secret => match provider_info(config).env_key {
+                    "" => "not needed".to_string(),
+                    env_key => format!("from ${env_key}"),
+                },
+                _ => format!("default ({})", self.unset),
+            };
+        };
+        match (self.kind, value) {
+            (Kind::Toggle, Value::Bool(true)) => "on".to_string(),
+            (Kind::Toggle, Value::Bool(false)) => "off".to_string(),
+            (Kind::Secret, Value::String(key)) if key.starts_with("keyring:") => {
+                "in system keychain".to_string()
+            }
+            (Kind::Secret, Value::String(key)) => {
+                let tail: String = key
+                    .chars()
+                    .skip(key.chars().count().saturating_sub(4))
+                    .collect();
+                format!("••••{tail} (plain text)")
+            }
+            // Small maps of numbers such as tab widths read fine inline
+            (Kind::Json, Value::Object(map)) if map.values().all(Value::is_number) => map
+                .iter()
+                .map(|(key, value)| format!("{key}: {value}"))
+                .collect::<Vec<_>>()
+                .join(", "),
+            (Kind::Json, Value::Object(map)) => format!("{} types", map.len()),
+            (_, Value::String(text)) => text.clone(),
+            (_, value) => value.to_string(),
+        }
+    }
+
+    /// Step the value up (`forward`) or down. Unset values start from the default.
+    fn adjust(&self, config: &mut Value, forward: bool) {
+        let current = get(config, self.path).cloned();
+        let next = match self.kind {
+            Kind::Provider => {
+                let options: Vec<Option<Value>> = std::iter::once(None)
+                    .chain(ALL_PROVIDERS.iter().map(|p| Some(json!(p.id))))
+                    .collect();
+                cycle(&options, &current, forward)
+            }
+            Kind::Choice(choices) => {
+                let options: Vec<Option<Value>> = std::iter::once(None)
+                    .chain(choices.iter().map(|c| Some(json!(c))))
+                    .collect();
+                cycle(&options, &current, forward)
+            }
+            Kind::Toggle => cycle(
+                &[None, Some(json!(true)), Some(json!(false))],
+                &current,
+                forward,
+            ),
+            Kind::Integer {
+                min,
+                max,
+                step,
+                start,
+            } => {
+                let value = current.as_ref().and_then(Value::as_u64).unwrap_or(start);
+                let next = if forward {
+                    value.saturating_add(step)
+                } else {
+                    value.saturating_sub(step)
+                };
+                Some(json!(next.clamp(min, max)))
+            }
+            Kind::Float {
+                min,
+                max,
+                step,
+                start,
+            } => {
+                let value = current.as_ref().and_then(Value::as_f64).unwrap_or(start);
+                let next = if forward { value + step } else { value - step };
+                // Keep one decimal so repeated steps don't drift
+                Some(json!(((next.clamp(min, max)) * 10.0).round() / 10.0))
+            }
+            Kind::Text | Kind::Secret | Kind::Json => return,
+        };
+        set(config, self.path, next);
+    }
+
+    fn is_editable_text(&self) -> bool {
+        matches!(self.kind, Kind::Text | Kind::Secret)
+    }
+}
+
+enum Action {
+    None,
+    Quit,
+    Test,
+    EditJson,
+}
+
+struct Status {
+    text: String,
+    error: bool,
+}
+
+struct SettingsEditor {
+    path: PathBuf,
+    config: Value,
+    selected: usize,
+    /// Text being typed into the selected field
+    input: Option<String>,
+    status: Option<Status>,
+    dirty: bool,
+    /// Quit was pressed once with unsaved changes
+    confirm_quit: bool,
+    saved: bool,
+}
+
+impl SettingsEditor {
+    fn new(path: PathBuf, config: Value) -> Self {
+        Self {
+            path,
+            config,
+            selected: 0,
+            input: None,
+            status: None,
+            dirty: false,
+            confirm_quit: false,
+            saved: false,
+        }
+    }
+
+    fn field(&self) -> &'static Field {
+        &FIELDS[self.selected]
+    }
+
+    fn info(&mut self, text: impl Into<String>) {
+        self.status = Some(Status {
+            text: text.into(),
+            error: false,
+        });
+    }
+
+    fn error(&mut self, text: impl Into<String>) {
+        self.status = Some(Status {
+            text: text.into(),
+            error: true,
+        });
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if self.input.is_some() {
+            self.handle_input_key(key);
+            return Action::None;
+        }
+
+        let confirm_quit = std::mem::take(&mut self.confirm_quit);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if !self.dirty || confirm_quit {
+                    return Action::Quit;
+                }
+                self.confirm_quit = true;
+                self.error("Unsaved changes: w to save, q again to discard");
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Action::Quit
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(FIELDS.len() - 1);
+                self.status = None;
+            }
+            KeyCode::Char('k') | KeyCode::Up => {
+                self.selected = self.selected.saturating_sub(1);
+                self.status = None;
+            }
+            KeyCode::Char('l') | KeyCode::Right | KeyCode::Char('h') | KeyCode::Left => {
+                let forward = matches!(key.code, KeyCode::Char('l') | KeyCode::Right);
+                self.field().adjust(&mut self.config, forward);
+                self.dirty = true;
+            }
+            KeyCode::Enter => match self.field().kind {
+                Kind::Json => return Action::EditJson,
+                _ if self.field().is_editable_text() => {
+                    let current = match (self.field().kind, get(&self.config, self.field().path)) {
+                        (Kind::Text, Some(Value::String(text))) => text.clone(),
+                        _ => String::new(),
+                    };
+                    self.input = Some(current);
+                }
+                _ => {
+                    self.field().adjust(&mut self.config, true);
+                    self.dirty = true;
+                }
+            },
+            KeyCode::Backspace | KeyCode::Delete | KeyCode::Char('d') => {
+                let path = self.field().path;
+                set(&mut self.config, path, None);
+                self.dirty = true;
+                self.info(format!("{} reset to default", self.field().label));
+            }
+            KeyCode::Char('w') => self.save(),
+            KeyCode::Char('t') => return Action::Test,
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn handle_input_key(&mut self, key: KeyEvent) {
+        let Some(input) = self.input.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Esc => self.input = None,
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => input.push(c),
+            KeyCode::Enter => {
+                let text = self.input.take().unwrap_or_default().trim().to_string();
+                self.commit_text(text);
+            }
+            _ => {}
+        }
+    }
+
+    fn commit_text(&mut self, text: String) {
+        let field = self.field();
+        if text.is_empty() {
+            set(&mut self.config, field.path, None);
+            self.dirty = true;
+            return;
+        }
+        if field.kind != Kind::Secret {
+            set(&mut self.config, field.path, Some(json!(text)));
+            self.dirty = true;
+            return;
+        }
+
+        let provider = provider_info(&self.config);
+        match keychain::store(provider.id, &text) {
+            Ok(()) => {
+                set(
+                    &mut self.config,
+                    field.path,
+                    Some(json!(keychain::reference(provider.id))),
+                );
+                self.dirty = true;
+                self.info(format!(
+                    "Stored the {} key in the system keychain",
+                    provider.display_name
+                ));
+            }
+            Err(e) => self.error(format!("Could not store the key: {e}")),
+        }
+    }
+
+    fn save(&mut self) {
+        // Catch values the config loader would reject before they're written
+        if let Err(e) = serde_json::from_value::<LumenConfig>(self.config.clone()) {
+            self.error(format!("Not saved: {e}"));
+            return;
+        }
+        match write_global_config(&self.path, &self.config) {
+            Ok(()) => {
+                self.dirty = false;
+                self.saved = true;
+                self.info(format!("Saved to {}", self.path.display()));
+            }
+            Err(e) => self.error(format!("Not saved: {e}")),
+        }
+    }
+
+    /// Send a one-line request with the current provider settings.
+    async fn test_provider(&mut self) {
+        let provider = provider_info(&self.config);
+        let text = |path: &[&str]| {
+            get(&self.config, path)
+                .and_then(Value::as_str)
+                .map(String::from)
+        };
+        let start = Instant::now();
+        let result = match LumenProvider::new(
+            provider.provider_type,
+            text(&["api_key"]),
+            text(&["model"]),
+            text(&["api_base_url"]),
+        ) {
+            Ok(client) => client.health_check().await.map_err(LumenError::from),
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(()) => self.info(format!(
+                "{} responded in {} ms",
+                provider.display_name,
+                start.elapsed().as_millis()
+            )),
+            Err(e) => self.error(format!("Test request failed: {e}")),
+        }
+    }
+
+    /// Edit the selected JSON value in `$EDITOR`. The terminal must be out of TUI mode.
+    fn edit_json(&mut self) {
+        let field = self.field();
+        let current = match get(&self.config, field.path) {
+            Some(value) => value.clone(),
+            None => serde_json::from_str(&default_commit_types()).unwrap_or_else(|_| json!({})),
+        };
+        let file = std::env::temp_dir().join(format!("lumen-{}.json", field.path.join("-")));
+        let written = serde_json::to_string_pretty(&current)
+            .map_err(LumenError::from)
+            .and_then(|text| std::fs::write(&file, text).map_err(LumenError::from));
+        if let Err(e) = written {
+            self.error(format!("Could not write {}: {e}", file.display()));
+            return;
+        }
+
+        let editor = shell::editor();
+        let status = std::process::Command::new(&editor).arg(&file).status();
+        let edited = std::fs::read_to_string(&file);
+        let _ = std::fs::remove_file(&file);
+        if !status.is_ok_and(|status| status.success()) {
+            self.error(format!("{editor} exited without saving"));
+            return;
+        }
+
+        let parsed = edited
+            .map_err(LumenError::from)
+            .and_then(|text| serde_json::from_str::<Value>(&text).map_err(LumenError::from));
+        match parsed {
+            Ok(value)
+                if value
+                    .as_object()
+                    .is_some_and(|map| map.values().all(Value::is_string)) =>
+            {
+                if value != current {
+                    set(&mut self.config, field.path, Some(value));
+                    self.dirty = true;
+                }
+            }
+            Ok(_) => self.error("Expected an object of type names to descriptions"),
+            Err(e) => self.error(format!("Invalid JSON: {e}")),
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let t = theme::get();
+        let area = frame.area();
+        let block = Block::default()
+            .title(" Lumen Settings ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(4)])
+            .split(inner);
+        let (list_area, footer_area) = (chunks[0], chunks[1]);
+
+        let mut lines: Vec<Line> = Vec::new();
+        let mut selected_line = 0;
+        let mut section = "";
+        for (i, field) in FIELDS.iter().enumerate() {
+            if field.section != section {
+                section = field.section;
+                if !lines.is_empty() {
+                    lines.push(Line::from(""));
+                }
+                lines.push(Line::from(Span::styled(
+                    format!(" {section}"),
+                    Style::default().fg(t.ui.border_focused).bold(),
+                )));
+            }
+            let value = match &self.input {
+                Some(input) if i == self.selected && field.kind == Kind::Secret => {
+                    format!("{}█", "•".repeat(input.chars().count()))
+                }
+                Some(input) if i == self.selected => format!("{input}█"),
+                _ => format!("‹ {} ›", field.display(&self.config)),
+            };
+            let style = if i == self.selected {
+                selected_line = lines.len();
+                Style::default().fg(t.ui.selection_fg).bg(t.ui.selection_bg)
+            } else {
+                Style::default().fg(t.ui.text_primary)
+            };
+            lines.push(Line::from(Span::styled(
+                format!("   {:<20}{value}", field.label),
+                style,
+            )));
+        }
+        // Keep the selected row in view on short terminals
+        let scroll = selected_line.saturating_sub(list_area.height.saturating_sub(1) as usize);
+        frame.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), list_area);
+
+        let status = match &self.status {
+            Some(status) => Span::styled(
+                format!(" {}", status.text),
+                Style::default().fg(if status.error {
+                    Color::Red
+                } else {
+                    Color::Green
+                }),
+            ),
+            None => Span::raw(""),
+        };
+        let hints = if self.input.is_some() {
+            " enter confirm · esc cancel · empty resets to default"
+        } else {
+            " j/k select · h/l change · enter edit · d reset · t test provider · w save · q quit"
+        };
+        let footer = vec![
+            Line::from(Span::styled(
+                format!(" {}", self.field().help),
+                Style::default().fg(t.ui.text_muted),
+            )),
+            Line::from(status),
+            Line::from(""),
+            Line::from(Span::styled(hints, Style::default().fg(t.ui.text_muted))),
+        ];
+        frame.render_widget(Paragraph::new(footer), footer_area);
+    }
+}
+
+/// Leave TUI mode for an external program, run `f`, then come back.
+fn suspended<T>(f: impl FnOnce() -> T) -> io::Result<T> {
+    io::stdout().execute(DisableMouseCapture)?;
+    io::stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    let result = f();
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
+    Ok(result)
+}
+
+/// Run the settings editor on the global config file.
+pub async fn run() -> Result<(), LumenError> {
+    let (path, config) = read_global_config()?;
+    let mode = get(&config, &["diff", "theme"])
+        .and_then(|value| serde_json::from_value(value.clone()).ok())
+        .unwrap_or_else(ThemeMode::detect);
+    theme::set_mode(mode);
+
+    let mut editor = SettingsEditor::new(path, config);
+    {
+        let _guard = TerminalGuard::enter()?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        loop {
+            terminal.draw(|frame| editor.render(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match editor.handle_key(key) {
+                Action::None => {}
+                Action::Quit => break,
+                Action::Test => {
+                    editor.info("Testing the provider...");
+                    terminal.draw(|frame| editor.render(frame))?;
+                    editor.test_provider().await;
+                }
+                Action::EditJson => {
+                    suspended(|| editor.edit_json())?;
+                    terminal.clear()?;
+                }
+            }
+        }
+    }
+
+    if editor.saved {
+        println!(
+            "\n  \x1b[1;32m✓\x1b[0m Configuration saved to \x1b[2m{}\x1b[0m\n",
+            editor.path.display()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(label: &str) -> &'static Field {
+        FIELDS.iter().find(|f| f.label == label).unwrap()
+    }
+
+    #[test]
+    fn test_set_creates_and_removes_nested_values() {
+        let mut config = json!({ "provider": "groq" });
+        set(&mut config, &["diff", "tab_width"], Some(json!(2)));
+        assert_eq!(
+            config,
+            json!({ "provider": "groq", "diff": { "tab_width": 2 } })
+        );
+        set(&mut config, &["diff", "tab_width"], None);
+        set(&mut config, &["ai", "temperature"], None);
+        assert_eq!(config, json!({ "provider": "groq", "diff": {} }));
+    }
+
+    #[test]
+    fn test_adjust_steps_within_bounds() {
+        let mut config = json!({});
+        let tab_width = field("Tab width");
+        tab_width.adjust(&mut config, true);
+        assert_eq!(get(&config, tab_width.path), Some(&json!(5)));
+        for _ in 0..10 {
+            tab_width.adjust(&mut config, true);
+        }
+        assert_eq!(get(&config, tab_width.path), Some(&json!(8)));
+
+        let temperature = field("Temperature");
+        temperature.adjust(&mut config, false);
+        assert_eq!(get(&config, temperature.path), Some(&json!(0.9)));
+    }
+
+    #[test]
+    fn test_choices_cycle_through_default() {
+        let mut config = json!({});
+        let wrap = field("Wrap lines");
+        wrap.adjust(&mut config, true);
+        assert_eq!(wrap.display(&config), "on");
+        wrap.adjust(&mut config, true);
+        assert_eq!(wrap.display(&config), "off");
+        wrap.adjust(&mut config, true);
+        assert_eq!(wrap.display(&config), "default (off)");
+    }
+
+    #[test]
+    fn test_display_depends_on_provider() {
+        let config = json!({ "provider": "groq", "api_key": "keyring:groq" });
+        assert_eq!(
+            field("Model").display(&config),
+            "default (llama-3.3-70b-versatile)"
+        );
+        assert_eq!(field("API key").display(&config), "in system keychain");
+        assert_eq!(field("API key").display(&json!({})), "from $OPENAI_API_KEY");
+    }
+}
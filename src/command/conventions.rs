@@ -0,0 +1,248 @@
+//! Deterministic checks on drafted commit messages, run before they're shown so small slips
+//! don't need another round trip to the model.
+//!
+//! A past-tense or third-person verb opening a subject of a verb we know, and a trailing period,
+//! are fixed. A long subject, a verb we can only guess at, and the configured
+//! `draft.forbidden_words` are pointed out instead.
+
+use std::fmt;
+
+use regex::Regex;
+
+/// Length the prompt asks the subject to stay under; longer is allowed up to the hard limit
+pub const SUBJECT_RECOMMENDED_LEN: usize = 50;
+
+/// Verbs commit subjects commonly open with, whose other forms are fixed to these
+const VERBS: &[&str] = &[
+    "add",
+    "allow",
+    "bump",
+    "change",
+    "clean",
+    "convert",
+    "create",
+    "delete",
+    "disable",
+    "document",
+    "enable",
+    "ensure",
+    "extract",
+    "fix",
+    "handle",
+    "implement",
+    "improve",
+    "introduce",
+    "merge",
+    "move",
+    "optimize",
+    "prevent",
+    "refactor",
+    "remove",
+    "rename",
+    "replace",
+    "revert",
+    "simplify",
+    "support",
+    "update",
+    "upgrade",
+    "use",
+];
+
+/// Words ending in `-ed` or `-ing` that are fine at the start of a subject
+const NOT_INFLECTED: &[&str] = &[
+    "bleed", "breed", "bring", "embed", "exceed", "proceed", "spring", "string", "succeed",
+];
+
+/// Something a check found: fixed already, or for the user to look at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remark {
+    pub fixed: bool,
+    pub text: String,
+}
+
+impl fmt::Display for Remark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = if self.fixed { "fixed" } else { "warning" };
+        write!(f, "{label}: {}", self.text)
+    }
+}
+
+/// Third person, past tense and `-ing` forms of a regular verb
+fn inflections(verb: &str) -> [String; 3] {
+    if let Some(stem) = verb.strip_suffix('y') {
+        [
+            format!("{stem}ies"),
+            format!("{stem}ied"),
+            format!("{verb}ing"),
+        ]
+    } else if let Some(stem) = verb.strip_suffix('e') {
+        [format!("{verb}s"), format!("{verb}d"), format!("{stem}ing")]
+    } else if verb.ends_with(['s', 'x', 'z']) || verb.ends_with("sh") || verb.ends_with("ch") {
+        [
+            format!("{verb}es"),
+            format!("{verb}ed"),
+            format!("{verb}ing"),
+        ]
+    } else {
+        [
+            format!("{verb}s"),
+            format!("{verb}ed"),
+            format!("{verb}ing"),
+        ]
+    }
+}
+
+/// The imperative form of `word`, if it's another form of a verb we know
+pub fn imperative(word: &str) -> Option<&'static str> {
+    let word = word.to_lowercase();
+    VERBS
+        .iter()
+        .copied()
+        .find(|verb| inflections(verb).contains(&word))
+}
+
+/// Whether `word` looks like a past tense or `-ing` form we don't know the verb of
+pub fn looks_inflected(word: &str) -> bool {
+    let word = word.to_lowercase();
+    word.chars().count() > 5
+        && (word.ends_with("ed") || word.ends_with("ing"))
+        && !NOT_INFLECTED.contains(&word.as_str())
+}
+
+/// Give `replacement` the case of `original`'s first letter.
+fn match_case(original: &str, replacement: &str) -> String {
+    let mut chars = replacement.chars();
+    match (original.chars().next(), chars.next()) {
+        (Some(first), Some(r)) if first.is_uppercase() => r.to_uppercase().chain(chars).collect(),
+        _ => replacement.to_string(),
+    }
+}
+
+/// Fix the mood and trailing period of a `type(scope): description` header.
+pub fn fix_subject(header: &str) -> (String, Vec<Remark>) {
+    let mut remarks = Vec::new();
+    let (prefix, description) = match header.split_once(": ") {
+        Some((prefix, description)) => (format!("{prefix}: "), description),
+        None => (String::new(), header),
+    };
+
+    let mut description = description.to_string();
+    if description.ends_with('.') && !description.ends_with("..") {
+        description.pop();
+        remarks.push(Remark {
+            fixed: true,
+            text: "dropped the period at the end of the subject".to_string(),
+        });
+    }
+
+    let first = description
+        .split(' ')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    if let Some(verb) = imperative(&first) {
+        let verb = match_case(&first, verb);
+        description = format!("{verb}{}", &description[first.len()..]);
+        remarks.push(Remark {
+            fixed: true,
+            text: format!("changed \"{first}\" to \"{verb}\" (subjects use the imperative mood)"),
+        });
+    } else if looks_inflected(&first) {
+        remarks.push(Remark {
+            fixed: false,
+            text: format!(
+                "\"{first}\" may not be in the imperative mood (\"add\", not \"added\" or \"adding\")"
+            ),
+        });
+    }
+
+    (format!("{prefix}{description}"), remarks)
+}
+
+/// Point out what can't be fixed without rewording: a subject over the recommended length and
+/// any of `forbidden_words` (whole words, ignoring case).
+pub fn annotate(subject: &str, body: Option<&str>, forbidden_words: &[String]) -> Vec<Remark> {
+    let mut remarks = Vec::new();
+    let length = subject.chars().count();
+    if length > SUBJECT_RECOMMENDED_LEN {
+        remarks.push(Remark {
+            fixed: false,
+            text: format!(
+                "the subject is {length} characters; {SUBJECT_RECOMMENDED_LEN} or fewer reads best in `git log --oneline`"
+            ),
+        });
+    }
+
+    for word in forbidden_words
+        .iter()
+        .map(|w| w.trim())
+        .filter(|w| !w.is_empty())
+    {
+        let parts = [("subject", Some(subject)), ("body", body)];
+        for (part, text) in parts {
+            if text.is_some_and(|text| uses_word(text, word)) {
+                remarks.push(Remark {
+                    fixed: false,
+                    text: format!(
+                        "the {part} uses \"{word}\", which `draft.forbidden_words` rules out"
+                    ),
+                });
+            }
+        }
+    }
+    remarks
+}
+
+/// Whether `text` has `word` as a whole word, ignoring case.
+pub fn uses_word(text: &str, word: &str) -> bool {
+    Regex::new(&format!(r"(?i)\b{}\b", regex::escape(word)))
+        .is_ok_and(|pattern| pattern.is_match(text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fix_subject() {
+        let (subject, remarks) = fix_subject("feat(cli): Added a draft flag.");
+        assert_eq!(subject, "feat(cli): Add a draft flag");
+        assert_eq!(remarks.len(), 2);
+        assert!(remarks.iter().all(|r| r.fixed));
+
+        assert_eq!(
+            fix_subject("fix: simplifies parsing").0,
+            "fix: simplify parsing"
+        );
+        assert_eq!(fix_subject("chore: updating deps").0, "chore: update deps");
+        assert_eq!(
+            fix_subject("fix: handle empty diff"),
+            ("fix: handle empty diff".to_string(), vec![])
+        );
+        assert_eq!(fix_subject("fix: wait for it...").0, "fix: wait for it...");
+
+        let (subject, remarks) = fix_subject("feat: Tweaked colors");
+        assert_eq!(subject, "feat: Tweaked colors");
+        assert!(!remarks[0].fixed);
+        assert!(fix_subject("feat: string helpers").1.is_empty());
+    }
+
+    #[test]
+    fn test_annotate() {
+        let forbidden = vec!["WIP".to_string(), "hack".to_string()];
+        let remarks = annotate(
+            "fix: wip on parser",
+            Some("A quick hack.\nhacky"),
+            &forbidden,
+        );
+        assert_eq!(
+            remarks.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec![
+                "warning: the subject uses \"WIP\", which `draft.forbidden_words` rules out",
+                "warning: the body uses \"hack\", which `draft.forbidden_words` rules out",
+            ]
+        );
+        let long = format!("feat: {}", "x".repeat(50));
+        assert_eq!(annotate(&long, None, &[]).len(), 1);
+    }
+}
@@ -1,35 +1,193 @@
+use std::collections::HashSet;
 use std::io;
-use std::sync::mpsc::TryRecvError;
-use std::time::Duration;
+use std::ops::RangeInclusive;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crossterm::{
-    event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
-        MouseEventKind,
-    },
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-    ExecutableCommand,
-};
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers, MouseEventKind};
+use lumen_core::side_by_side::{compute_side_by_side, find_hunk_starts, hunk_at, line_range};
 use ratatui::prelude::*;
 
-use super::diff_algo::{compute_side_by_side, find_hunk_starts};
-use super::git::{get_current_branch, load_file_diffs, load_pr_file_diffs};
+use crate::clipboard;
+use crate::commit_reference::CommitReference;
+use crate::config::cli::RiskMode;
+use crate::shell;
+
+use super::commit::{changed_paths, commit, draft_in_background, Draft};
+use super::commit_suggestion::CommitSuggestion;
+use super::editor::{editor_command, new_line_near};
+use super::explain::{explain_in_background, ExplainUpdate};
+use super::export::{default_path, diff_patch, file_patch, html_report, Choice};
+use super::file_summaries::FileSummaries;
+use super::filter::FileFilter;
+use super::git::{
+    get_current_branch, line_history, load_file_diffs, load_file_diffs_async, load_pr_file_diffs,
+    DiffRefs,
+};
+use super::graphics;
 use super::highlight;
+use super::history::{load_history, FileHistory};
+use super::mentions::load_candidates_async;
+use super::moves::jump_target;
+use super::overview::file_stats;
+use super::pending_review::{self, submit_review_async, DraftComment};
+use super::pr_details::{load_details_async, poll_head_async, PrDetails};
 use super::render::{
-    render_diff, render_empty_state, FilePickerItem, KeyBind, KeyBindSection, Modal,
-    ModalFileStatus, ModalResult,
+    minimap_line_at, render_diff, render_empty_state, row_heights, DiffAreas, FilePickerItem,
+    KeyBind, KeyBindSection, Modal, ModalFileStatus, ModalResult, SuggestionItem,
 };
-use super::state::{adjust_scroll_to_line, AppState, PendingKey};
+use super::review_notes::{polish_async, ReviewNote, ReviewNotes};
+use super::risk::{refine_in_background, RiskScores};
+use super::scribe::{Scribe, ScribeNote};
+use super::search::SearchMode;
+use super::session::{EventSource, Position, Recorder, Replayer};
+use super::stage::{
+    apply_to_index, apply_to_worktree, build_index_patch, discard_from_worktree, index_content,
+    remove_from_index, restore_file, rows_patch, selected_line_numbers,
+};
+use super::state::{adjust_scroll_to_line, scroll_limit, AppState, HunkJump, PendingKey};
+use super::suggestions::{load_suggestions_async, suggestion_patch, Suggestion};
+use super::terminal::TerminalGuard;
 use super::theme;
-use super::types::{DiffFullscreen, FileStatus, FocusedPanel, SidebarItem};
+use super::threads::{load_threads_async, ReviewThreads};
+use super::types::{
+    ChangeType, DiffFullscreen, DiffViewSettings, FileStatus, Focus, FocusedPanel, LineNote,
+    SidebarItem,
+};
 use super::watcher::{setup_watcher, WatchEvent};
 use super::{
-    fetch_viewed_files, mark_file_as_viewed_async, unmark_file_as_viewed_async, DiffOptions, PrInfo,
+    fetch_viewed_files, mark_file_as_viewed_async, post_comment_async, report_title,
+    unmark_file_as_viewed_async, DiffOptions, PrInfo,
 };
 
+/// How long after staging from the viewer a change inside `.git` is taken to be our own
+/// index write, which leaves the diff against HEAD unchanged
+const STAGE_ECHO_WINDOW: Duration = Duration::from_secs(2);
+
+/// Working-tree changes waiting for the user to confirm they can be thrown away
+enum Discard {
+    Hunk {
+        patch: String,
+        staged: bool,
+    },
+    File {
+        filename: String,
+        status: FileStatus,
+    },
+}
+
+/// Rows and patch of the hunk the view is focused on in the current file.
+fn focused_hunk_patch(state: &AppState) -> Option<(RangeInclusive<usize>, String)> {
+    let diff = state.file_diffs.get(state.current_file)?;
+    let side_by_side = compute_side_by_side(
+        &diff.old_content,
+        &diff.new_content,
+        state.settings.tab_width_for(&diff.filename),
+        state.settings.ignore_whitespace,
+    );
+    // Hunk navigation leaves the focused hunk 5 rows below the top
+    let rows = hunk_at(&side_by_side, state.scroll as usize + 5)?;
+    let patch = rows_patch(diff, &side_by_side, rows.clone())?;
+    Some((rows, patch))
+}
+
+/// Write what `choice` asks for to the current directory, and say where it went.
+fn export(
+    choice: Choice,
+    state: &AppState,
+    options: &DiffOptions,
+    pr_info: Option<&PrInfo>,
+    review_notes: &ReviewNotes,
+) -> String {
+    let whole_diff = matches!(choice, Choice::Report | Choice::DiffPatch);
+    if whole_diff && !state.loading.is_empty() {
+        return "Wait for every file to load before exporting the whole diff".to_string();
+    }
+    let current = state.file_diffs.get(state.current_file);
+    let export = match choice {
+        Choice::Report => {
+            let title = report_title(options, pr_info);
+            let report = html_report(&title, &state.file_diffs, &state.settings);
+            Some((default_path(None, "html"), report))
+        }
+        Choice::DiffPatch => Some((default_path(None, "patch"), diff_patch(&state.file_diffs))),
+        Choice::FilePatch => current.and_then(|diff| {
+            Some((
+                default_path(Some(&diff.filename), "patch"),
+                file_patch(diff)?,
+            ))
+        }),
+        Choice::HunkPatch => current
+            .zip(focused_hunk_patch(state))
+            .map(|(diff, (rows, patch))| {
+                // Named after the line the hunk starts at, so hunks of a file don't overwrite each other
+                let line = state
+                    .view
+                    .rows
+                    .get(*rows.start())
+                    .and_then(|row| row.new_line.as_ref().or(row.old_line.as_ref()))
+                    .map_or(0, |(num, _)| *num);
+                let name = format!("{}-{line}", diff.filename);
+                (default_path(Some(&name), "patch"), patch)
+            }),
+        Choice::ReviewNotes => (!review_notes.is_empty()).then(|| {
+            let files: Vec<String> = state
+                .file_diffs
+                .iter()
+                .map(|diff| diff.filename.clone())
+                .collect();
+            (
+                default_path(Some("review-notes"), "md"),
+                review_notes.to_markdown(&files),
+            )
+        }),
+        // Written once the AI answers
+        Choice::PolishedNotes => None,
+    };
+    match export.filter(|(_, contents)| !contents.is_empty()) {
+        Some((path, contents)) => match std::fs::write(&path, contents) {
+            Ok(()) => format!("Wrote {}", path.display()),
+            Err(e) => format!("Exporting failed: {e}"),
+        },
+        None => "Nothing to export".to_string(),
+    }
+}
+
+/// The notes to show: those given in `options`, the PR's comment threads, the review
+/// comments not submitted yet, and the reviewer's own notes.
+fn all_notes(
+    options: &DiffOptions,
+    threads: &ReviewThreads,
+    threads_expanded: bool,
+    pending: &[DraftComment],
+    review_notes: &ReviewNotes,
+) -> Vec<LineNote> {
+    let mut notes = options.notes.clone();
+    notes.extend(threads.notes(threads_expanded));
+    notes.extend(pending_review::notes(pending));
+    notes.extend(review_notes.line_notes());
+    notes
+}
+
+/// Where a comment or note goes: the selected lines, or else the hunk in focus.
+fn line_target(state: &AppState) -> Option<DraftComment> {
+    let diff = state.file_diffs.get(state.current_file)?;
+    let side_by_side = compute_side_by_side(
+        &diff.old_content,
+        &diff.new_content,
+        state.settings.tab_width_for(&diff.filename),
+        state.settings.ignore_whitespace,
+    );
+    let rows = state
+        .selected_rows()
+        .or_else(|| hunk_at(&side_by_side, state.scroll as usize + 5))?;
+    DraftComment::on_rows(&diff.filename, &side_by_side, rows)
+}
+
 pub fn run_app_with_pr(options: DiffOptions, pr_info: PrInfo) -> io::Result<()> {
     match load_pr_file_diffs(&pr_info) {
-        Ok(file_diffs) => run_app_internal(options, Some(pr_info), file_diffs),
+        Ok(file_diffs) => run_app_internal(options, Some(pr_info), file_diffs, None),
         Err(e) => {
             eprintln!("\x1b[91merror:\x1b[0m {}", e);
             std::process::exit(1);
@@ -38,8 +196,26 @@ pub fn run_app_with_pr(options: DiffOptions, pr_info: PrInfo) -> io::Result<()>
 }
 
 pub fn run_app(options: DiffOptions, pr_info: Option<PrInfo>) -> io::Result<()> {
-    let file_diffs = load_file_diffs(&options);
-    run_app_internal(options, pr_info, file_diffs)
+    // Risk scores, a line to open at, status filters and replays need every file read first;
+    // otherwise the viewer opens right away and files fill in as they're read
+    let up_front = options.risk.is_some()
+        || options.focus.is_some()
+        || options.replay.is_some()
+        || options.file.as_ref().is_some_and(FileFilter::has_statuses);
+    if up_front {
+        let file_diffs = load_file_diffs(&options);
+        run_app_internal(options, pr_info, file_diffs, None)
+    } else {
+        let (file_diffs, loading_rx) = load_file_diffs_async(&options);
+        run_app_internal(options, pr_info, file_diffs, Some(loading_rx))
+    }
+}
+
+pub fn run_app_with_diffs(
+    options: DiffOptions,
+    file_diffs: Vec<super::types::FileDiff>,
+) -> io::Result<()> {
+    run_app_internal(options, None, file_diffs, None)
 }
 
 /// Sync viewed files from GitHub to local state
@@ -54,61 +230,439 @@ fn sync_viewed_files_from_github(pr_info: &PrInfo, state: &mut AppState) {
     }
 }
 
+/// `loading_rx` brings the diffs of files that are placeholders in `file_diffs` for now.
 fn run_app_internal(
-    options: DiffOptions,
+    mut options: DiffOptions,
     pr_info: Option<PrInfo>,
     file_diffs: Vec<super::types::FileDiff>,
+    mut loading_rx: Option<Receiver<(usize, super::types::FileDiff)>>,
 ) -> io::Result<()> {
-    theme::init();
     highlight::init();
 
-    enable_raw_mode()?;
-    io::stdout().execute(EnterAlternateScreen)?;
-    io::stdout().execute(EnableMouseCapture)?;
+    let mut events = match (&options.replay, &options.record) {
+        (Some(path), _) => EventSource::Replay(Replayer::open(path)?),
+        (None, Some(path)) => EventSource::Terminal(Some(Recorder::create(path)?)),
+        (None, None) => EventSource::Terminal(None),
+    };
+
+    let _guard = TerminalGuard::enter()?;
 
     let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
 
-    let watch_rx = if options.watch && pr_info.is_none() {
-        setup_watcher()
-    } else {
-        None
-    };
+    let watcher = (options.watch && pr_info.is_none()).then(|| setup_watcher(&options.watch_paths));
 
-    let mut state = AppState::new(file_diffs);
+    let mut settings = DiffViewSettings {
+        review_flow: options.review_flow,
+        read_only: options.read_only,
+        ..DiffViewSettings::from_config(&options.settings)
+    };
+    // A custom theme is shown in the mode it was made for
+    let custom_theme = settings.custom_theme.as_deref().map(theme::use_custom);
+    if let Some(Ok(mode)) = custom_theme {
+        settings.theme = mode;
+    }
+    theme::set_mode(settings.theme);
+    let mut state = AppState::new(file_diffs, settings);
+    if loading_rx.is_some() {
+        state.loading = (0..state.file_diffs.len()).collect();
+    }
+    if let Some(Err(e)) = custom_theme {
+        state.status_message = Some(format!("Custom theme not loaded: {e}"));
+    }
+    let watch_rx = match watcher {
+        Some(Ok(rx)) => Some(rx),
+        Some(Err(e)) => {
+            state.status_message = Some(format!("Not watching for changes: {e}"));
+            None
+        }
+        None => None,
+    };
+    if options.risk.is_some() {
+        state.risk = Some(RiskScores::new(&state.file_diffs));
+    }
+    if let Some(focus) = &options.focus {
+        let at = match focus.line {
+            Some(line) => format!("{}:{line}", focus.file),
+            None => focus.file.clone(),
+        };
+        state.status_message = Some(if state.focus(&focus.file, focus.line, true) {
+            format!("Opened at {at}")
+        } else {
+            format!("{at} isn't part of this diff")
+        });
+    }
+    let risk_rx = match (options.provider.clone(), &state.risk) {
+        (Some(provider), Some(scores))
+            if options.risk == Some(RiskMode::Ai) && !state.file_diffs.is_empty() =>
+        {
+            state.status_message = Some("Scoring file risk with AI...".to_string());
+            Some(refine_in_background(provider, &state.file_diffs, scores))
+        }
+        (None, Some(_)) if options.risk == Some(RiskMode::Ai) => {
+            state.status_message = Some(options.no_provider_message("score risk"));
+            None
+        }
+        _ => None,
+    };
+    if options.file_summaries {
+        match &options.provider {
+            Some(provider) => state.file_summaries = Some(FileSummaries::new(Arc::clone(provider))),
+            None => state.status_message = Some(options.no_provider_message("summarize files")),
+        }
+    }
+    // Summaries follow the working tree, so they only run alongside the file watcher
+    let mut scribe = match (options.summarize_every, &options.provider) {
+        (Some(interval), Some(provider)) if watch_rx.is_some() => Some(Scribe::start(
+            Arc::clone(provider),
+            interval,
+            options.summary_log.take(),
+        )),
+        (Some(_), None) if watch_rx.is_some() => {
+            state.status_message = Some(options.no_provider_message("summarize"));
+            None
+        }
+        _ => None,
+    };
+    let mut unread_summary: Option<Modal> = None;
     let mut active_modal: Option<Modal> = None;
     let mut pending_watch_event: Option<WatchEvent> = None;
+    let mut last_staged: Option<Instant> = None;
+    // Staging writes to the index, which only makes sense for the working tree
+    // Staging works on the index against HEAD, which a snapshot comparison doesn't show
+    let can_stage = !options.read_only
+        && options.reference.is_none()
+        && options.snapshot.is_none()
+        && pr_info.is_none();
 
     // Load viewed files from GitHub on startup in PR mode
     if let Some(ref pr) = pr_info {
         sync_viewed_files_from_github(pr, &mut state);
     }
+    if can_stage {
+        state.refresh_index();
+    }
+    // People to offer for @ mentions in PR comments
+    let mut mentions_rx = pr_info.as_ref().map(load_candidates_async);
+    let mut mention_candidates: Vec<String> = Vec::new();
+    let mut comment_rx: Option<Receiver<Result<(), String>>> = None;
+    let mut suggestions_rx = pr_info.as_ref().map(load_suggestions_async);
+    let mut threads_rx = pr_info.as_ref().map(load_threads_async);
+    let mut details_rx = pr_info.as_ref().map(load_details_async);
+    // The PR's head as last seen, to tell when commits are pushed to it while reviewing
+    let mut pr_head = pr_info
+        .as_ref()
+        .map(|pr| pr.head_sha.clone())
+        .filter(|sha| !sha.is_empty());
+    let head_rx = pr_info.as_ref().map(poll_head_async);
+    let mut pr_details: Option<PrDetails> = None;
+    let mut threads = ReviewThreads::default();
+    let mut threads_expanded = false;
+    // Index into `threads.threads` of the thread last jumped to
+    let mut thread_cursor: Option<usize> = None;
+    // The reviewer's own notes on lines, and the lines the one being written goes on
+    let mut review_notes = ReviewNotes::load(report_title(&options, pr_info.as_ref()));
+    let mut note_target: Option<DraftComment> = None;
+    let mut polish_rx: Option<Receiver<Result<String, String>>> = None;
+    let mut notes = all_notes(&options, &threads, threads_expanded, &[], &review_notes);
+    // Line comments for a review that's submitted in one go, and where the one being written goes
+    let mut pending_comments: Vec<DraftComment> = Vec::new();
+    let mut draft_target: Option<DraftComment> = None;
+    let mut review_rx: Option<Receiver<Result<pending_review::ReviewEvent, String>>> = None;
+    // Quitting with unsubmitted comments asks to be confirmed by pressing q again
+    let mut quit_warned = false;
+    let mut suggestions: Vec<Suggestion> = Vec::new();
+    let mut applied_suggestions: HashSet<usize> = HashSet::new();
+    // Index into `suggestions` and patch of each one in the open suggestions popup
+    let mut shown_suggestions: Vec<(usize, String)> = Vec::new();
+    let mut explain_rx: Option<Receiver<ExplainUpdate>> = None;
+    let mut draft_rx: Option<Receiver<Result<Draft, String>>> = None;
+    // Files committing the drafted message stages first, none when it was drafted from the index
+    let mut commit_files: Vec<String> = Vec::new();
+    // Revisions to blame, worked out the first time the blame gutter is turned on
+    let mut blame_refs: Option<DiffRefs> = None;
+    let mut pending_discard: Option<Discard> = None;
+    let show_images = graphics::supported();
+    // File and area of the image preview on screen, drawn over the frame after each render
+    let mut shown_preview: Option<(usize, Rect)> = None;
+    // Where the last frame put its minimaps, for clicks on them
+    let mut minimaps: Vec<Rect> = Vec::new();
 
     loop {
         if let Some(ref rx) = watch_rx {
-            match rx.try_recv() {
+            while let Ok(event) = rx.try_recv() {
+                let own_index_write = event.is_git_internal()
+                    && last_staged.is_some_and(|at| at.elapsed() < STAGE_ECHO_WINDOW);
+                if own_index_write {
+                    continue;
+                }
+                match pending_watch_event.as_mut() {
+                    Some(pending) => pending.merge(event),
+                    None => pending_watch_event = Some(event),
+                }
+            }
+            // Hold the reload while lines are being selected, so the selection isn't lost
+            if pending_watch_event.is_some() && state.line_selection.is_none() {
+                state.needs_reload = true;
+            }
+        }
+
+        if let Some(rx) = &loading_rx {
+            let mut diffs = Vec::new();
+            let done = loop {
+                match rx.try_recv() {
+                    Ok(diff) => diffs.push(diff),
+                    Err(TryRecvError::Empty) => break false,
+                    Err(TryRecvError::Disconnected) => break true,
+                }
+            };
+            state.fill_file_diffs(diffs);
+            if done {
+                loading_rx = None;
+                state.loading.clear();
+            }
+        }
+        if let Some(result) = risk_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            match (result, state.risk.as_mut()) {
+                (Ok(scores), Some(risk)) => {
+                    risk.refine(scores);
+                    state.status_message = Some("File risk scored with AI".to_string());
+                }
+                (Err(e), _) => {
+                    state.status_message = Some(format!("AI risk scoring failed: {e}"));
+                }
+                (Ok(_), None) => {}
+            }
+        }
+
+        if state.file_summaries.is_some() && state.show_sidebar {
+            let visible_height = terminal.size()?.height.saturating_sub(3) as usize;
+            let in_view = state.files_in_view(visible_height);
+            if let Some(summaries) = state.file_summaries.as_mut() {
+                if let Some(message) = summaries.poll(&state.file_diffs, &in_view) {
+                    state.status_message = Some(message);
+                }
+            }
+        }
+
+        if let Some(message) = state
+            .commit_suggestion
+            .as_mut()
+            .and_then(CommitSuggestion::poll)
+        {
+            state.status_message = Some(message);
+        }
+
+        if let Some(result) = mentions_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            mentions_rx = None;
+            match result {
+                Ok(people) => {
+                    mention_candidates = people;
+                    if let Some(ref mut modal) = active_modal {
+                        modal.set_mention_candidates(&mention_candidates);
+                    }
+                }
+                Err(e) => {
+                    state.status_message = Some(format!("Loading people to mention failed: {e}"));
+                }
+            }
+        }
+
+        if let Some(result) = comment_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            comment_rx = None;
+            state.status_message = Some(match result {
+                Ok(()) => "Comment posted".to_string(),
+                Err(e) => format!("Posting comment failed: {e}"),
+            });
+        }
+
+        // The drafted message waits for whatever popup is open to close
+        if let Some(result) = draft_rx
+            .as_ref()
+            .filter(|_| active_modal.is_none())
+            .and_then(|rx| rx.try_recv().ok())
+        {
+            draft_rx = None;
+            match result {
+                Ok(draft) => {
+                    // Like `git commit`, only what's staged is committed when anything is
+                    commit_files = if draft.staged {
+                        Vec::new()
+                    } else {
+                        changed_paths(&state.file_diffs)
+                    };
+                    state.status_message = None;
+                    active_modal = Some(Modal::commit_message(draft.message));
+                }
+                Err(e) => state.status_message = Some(format!("Drafting failed: {e}")),
+            }
+        }
+
+        if let Some(result) = polish_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            polish_rx = None;
+            let path = default_path(Some("review-notes"), "md");
+            state.status_message = Some(
+                match result.and_then(|summary| {
+                    std::fs::write(&path, summary.trim().to_string() + "\n")
+                        .map_err(|e| e.to_string())
+                }) {
+                    Ok(()) => format!("Wrote {}", path.display()),
+                    Err(e) => format!("Polishing review notes failed: {e}"),
+                },
+            );
+        }
+
+        if let Some(result) = review_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            review_rx = None;
+            match result {
                 Ok(event) => {
-                    state.needs_reload = true;
-                    pending_watch_event = Some(event);
+                    pending_comments.clear();
+                    notes = all_notes(
+                        &options,
+                        &threads,
+                        threads_expanded,
+                        &pending_comments,
+                        &review_notes,
+                    );
+                    state.status_message = Some(event.done_message().to_string());
+                    // The submitted comments come back as threads
+                    threads_rx = pr_info.as_ref().map(load_threads_async);
+                    details_rx = pr_info.as_ref().map(load_details_async);
+                }
+                Err(e) => {
+                    state.status_message = Some(format!("Submitting review failed: {e}"));
                 }
-                Err(TryRecvError::Empty) => {}
-                Err(TryRecvError::Disconnected) => {}
             }
         }
 
+        if let Some(result) = suggestions_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            suggestions_rx = None;
+            match result {
+                Ok(found) => {
+                    if !found.is_empty() {
+                        state.status_message = Some(format!(
+                            "{} review suggestion(s), press S to go through them",
+                            found.len()
+                        ));
+                    }
+                    suggestions = found;
+                }
+                Err(e) => {
+                    state.status_message = Some(format!("Loading review suggestions failed: {e}"));
+                }
+            }
+        }
+
+        if let Some(result) = threads_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            threads_rx = None;
+            match result {
+                Ok(mut found) => {
+                    found.sort_for(&state.file_diffs);
+                    if !found.threads.is_empty() {
+                        let hidden = match found.unplaced {
+                            0 => String::new(),
+                            n => format!(" ({n} on removed or outdated lines not shown)"),
+                        };
+                        state.status_message = Some(format!(
+                            "{} comment thread(s){hidden}, t/T to jump between them",
+                            found.threads.len()
+                        ));
+                    }
+                    threads = found;
+                    notes = all_notes(
+                        &options,
+                        &threads,
+                        threads_expanded,
+                        &pending_comments,
+                        &review_notes,
+                    );
+                }
+                Err(e) => {
+                    state.status_message = Some(format!("Loading review comments failed: {e}"));
+                }
+            }
+        }
+
+        if let Some(head) = head_rx.as_ref().and_then(|rx| rx.try_iter().last()) {
+            if pr_head.as_ref().is_some_and(|known| *known != head) {
+                state.status_message =
+                    Some("New commits were pushed to the PR, press r to reload".to_string());
+            }
+            pr_head = Some(head);
+        }
+
+        if let Some(result) = details_rx.as_ref().and_then(|rx| rx.try_recv().ok()) {
+            details_rx = None;
+            match result {
+                Ok(details) => pr_details = Some(details),
+                Err(e) => {
+                    state.status_message = Some(format!("Loading PR details failed: {e}"));
+                }
+            }
+        }
+
+        // Closing the popup drops the rest of the explanation
+        if explain_rx.is_some() && !active_modal.as_ref().is_some_and(Modal::is_explanation) {
+            explain_rx = None;
+        }
+        if let (Some(rx), Some(modal)) = (&explain_rx, active_modal.as_mut()) {
+            let mut finished = false;
+            for update in rx.try_iter() {
+                match update {
+                    ExplainUpdate::Delta(delta) => modal.push_explanation(&delta),
+                    ExplainUpdate::Done => {
+                        modal.finish_explanation(None);
+                        finished = true;
+                    }
+                    ExplainUpdate::Failed(e) => {
+                        modal.finish_explanation(Some(&e));
+                        finished = true;
+                    }
+                }
+            }
+            if finished {
+                explain_rx = None;
+            }
+        }
+
+        match scribe.as_mut().and_then(Scribe::poll) {
+            Some(ScribeNote::Summary { minutes, text }) => {
+                unread_summary = Some(Modal::info(format!("Last {minutes} min"), text));
+            }
+            Some(ScribeNote::Status(message)) => state.status_message = Some(message),
+            None => {}
+        }
+        // Wait for an open dialog to close instead of replacing it
+        if active_modal.is_none() {
+            active_modal = unread_summary.take();
+        }
+
         if state.needs_reload {
             let file_diffs = if let Some(ref pr) = pr_info {
-                // In PR mode, reload from GitHub
-                match load_pr_file_diffs(pr) {
-                    Ok(diffs) => diffs,
-                    Err(_) => Vec::new(), // On error, show empty state
-                }
+                // In PR mode, reload from GitHub (on error, show empty state)
+                load_pr_file_diffs(pr).unwrap_or_default()
+            } else if options.preview {
+                state.file_diffs.clone()
             } else {
                 load_file_diffs(&options)
             };
 
+            // The reload reads every file, so whatever is still being read is stale
+            loading_rx = None;
             // Pass changed files to reload so it can unmark them from viewed
-            let changed_files = pending_watch_event.take().map(|e| e.changed_files);
+            let watch_event = pending_watch_event.take();
+            if let Some(count) = watch_event.as_ref().map(WatchEvent::file_count) {
+                if count > 0 {
+                    state.status_message = Some(format!(
+                        "Reloaded {count} changed file{}",
+                        if count == 1 { "" } else { "s" }
+                    ));
+                }
+            }
+            let changed_files = watch_event.map(|e| e.changed_files);
             state.reload(file_diffs, changed_files.as_ref());
+            if can_stage {
+                state.refresh_index();
+            }
 
             // Re-sync viewed files from GitHub in PR mode
             if let Some(ref pr) = pr_info {
@@ -117,30 +671,62 @@ fn run_app_internal(
         }
 
         if state.file_diffs.is_empty() {
+            minimaps.clear();
             terminal.draw(|frame| {
                 render_empty_state(frame, options.watch);
                 if let Some(ref modal) = active_modal {
                     modal.render(frame);
                 }
             })?;
+            if shown_preview.take().is_some() {
+                graphics::clear()?;
+            }
         } else {
-            let diff = &state.file_diffs[state.current_file];
-            let side_by_side = compute_side_by_side(
-                &diff.old_content,
-                &diff.new_content,
-                state.settings.tab_width,
-            );
-            let hunk_count = find_hunk_starts(&side_by_side).len();
+            if can_stage && state.file_history.is_none() {
+                state.update_staged_hunks();
+            }
+            if let Some(e) = blame_refs
+                .as_ref()
+                .and_then(|refs| state.update_blame(refs))
+            {
+                state.status_message = Some(format!("Blame failed: {e}"));
+            }
+            state.update_view();
+            // Rows are highlighted as they come near the screen, and kept for the next frames
+            let top = state.scroll as usize;
+            let height = terminal.size()?.height as usize;
+            state.highlight_rows(top.saturating_sub(height / 2)..top + height * 3 / 2);
+            let hunk_count = find_hunk_starts(&state.view.rows).len();
             state
                 .search_state
-                .update_matches(&side_by_side, state.diff_fullscreen);
+                .update_matches(&state.view.rows, state.diff_fullscreen);
+            let diff = &state.file_diffs[state.current_file];
             let branch = get_current_branch();
+            let selection_hint = if can_stage {
+                "s stage selected lines · esc cancel"
+            } else {
+                "c comment on selected lines · esc cancel"
+            };
+            let suggestion = state
+                .commit_suggestion
+                .as_ref()
+                .map(CommitSuggestion::footer);
+            let status_message = state
+                .status_message
+                .as_deref()
+                .or(state.line_selection.map(|_| selection_hint))
+                .or(suggestion.as_deref());
+            let mut areas = DiffAreas::default();
             terminal.draw(|frame| {
-                render_diff(
+                areas = render_diff(
                     frame,
                     diff,
-                    &state.file_diffs,
+                    &state.view,
                     &state.sidebar_items,
+                    state
+                        .sidebar_filter
+                        .as_ref()
+                        .map(|filter| filter.text.as_str()),
                     state.current_file,
                     state.scroll,
                     state.h_scroll,
@@ -151,36 +737,79 @@ fn run_app_internal(
                     state.sidebar_scroll,
                     state.sidebar_h_scroll,
                     &state.viewed_files,
+                    state.file_diffs.len(),
+                    state.risk.as_ref(),
+                    state.file_summaries.as_ref(),
                     &state.settings,
                     hunk_count,
                     state.diff_fullscreen,
                     &state.search_state,
                     &branch,
                     pr_info.as_ref(),
+                    state.selected_rows(),
+                    state.staged_rows(),
+                    &state.index_states,
+                    &notes,
+                    state.blame.as_ref(),
+                    state.file_history.as_ref().map(FileHistory::footer),
+                    status_message,
+                    &state.loading,
                 );
                 if let Some(ref modal) = active_modal {
                     modal.render(frame);
                 }
             })?;
+            // Images sit on top of the text, so one would hide a popup
+            minimaps = areas.minimaps;
+            let wanted = areas
+                .preview
+                .filter(|_| show_images && active_modal.is_none())
+                .map(|area| (state.current_file, area));
+            if wanted != shown_preview {
+                if shown_preview.is_some() {
+                    graphics::clear()?;
+                }
+                let png = diff.binary.as_ref().and_then(|b| b.preview.as_deref());
+                if let (Some((_, area)), Some(png)) = (wanted, png) {
+                    graphics::show_png(png, area)?;
+                }
+                shown_preview = wanted;
+            }
         }
 
-        if event::poll(Duration::from_millis(100))? {
-            let visible_height = terminal.size()?.height.saturating_sub(2) as usize;
+        let position = Position {
+            file: state
+                .file_diffs
+                .get(state.current_file)
+                .map(|diff| diff.filename.clone()),
+            scroll: state.scroll,
+        };
+        let (event, notice) = events.next(Duration::from_millis(100), position)?;
+        if notice.is_some() {
+            state.status_message = notice;
+        }
+        if let Some(event) = event {
+            let size = terminal.size()?;
+            let visible_height = size.height.saturating_sub(2) as usize;
             let bottom_padding = 5;
-            let max_scroll = if !state.file_diffs.is_empty() {
-                let diff = &state.file_diffs[state.current_file];
-                let total_lines = compute_side_by_side(
-                    &diff.old_content,
-                    &diff.new_content,
-                    state.settings.tab_width,
-                )
-                .len();
-                total_lines.saturating_sub(visible_height.saturating_sub(bottom_padding))
-            } else {
-                0
+            // Screen lines each row of the diff takes, more than one where long lines wrap
+            state.update_view();
+            let heights = match state.file_diffs.get(state.current_file) {
+                Some(diff) => row_heights(
+                    Rect::new(0, 0, size.width, size.height),
+                    diff,
+                    &state.view.rows,
+                    &state.settings,
+                    state.show_sidebar,
+                    state.diff_fullscreen,
+                    state.blame.as_ref(),
+                ),
+                None => Vec::new(),
             };
+            let total_lines = heights.len();
+            let max_scroll = scroll_limit(&heights, visible_height, bottom_padding);
 
-            match event::read()? {
+            match event {
                 Event::Key(key)
                     if key.kind == KeyEventKind::Press && state.search_state.is_active() =>
                 {
@@ -202,6 +831,20 @@ fn run_app_internal(
                         KeyCode::Backspace => {
                             state.search_state.pop_char();
                         }
+                        KeyCode::Tab => {
+                            state.search_state.cycle_case();
+                        }
+                        KeyCode::Char('r') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            state.search_state.toggle_regex();
+                        }
+                        // `?` opens a backward search, so `??` is how to get help
+                        KeyCode::Char('?')
+                            if state.search_state.mode == SearchMode::InputBackward
+                                && !state.search_state.has_query() =>
+                        {
+                            state.search_state.cancel();
+                            active_modal = Some(keybindings_modal());
+                        }
                         KeyCode::Char(c) => {
                             state.search_state.push_char(c);
                         }
@@ -210,7 +853,184 @@ fn run_app_internal(
                 }
                 Event::Key(key) if key.kind == KeyEventKind::Press && active_modal.is_some() => {
                     if let Some(ref mut modal) = active_modal {
-                        if let Some(result) = modal.handle_input(key) {
+                        let result = modal.handle_input(key);
+                        if let Some(settings) = modal.edited_settings() {
+                            state.apply_settings(settings.clone());
+                        }
+                        if let Some(result) = result {
+                            if let ModalResult::SaveSettings = result {
+                                state.status_message = Some(if state.settings.read_only {
+                                    "Read-only mode: changes are disabled".to_string()
+                                } else {
+                                    match state.settings.to_config().save() {
+                                        Ok(path) => format!("Settings saved to {}", path.display()),
+                                        Err(e) => format!("Saving settings failed: {e}"),
+                                    }
+                                });
+                            }
+                            let noted = match (&result, note_target.take()) {
+                                (ModalResult::PostComment(text), Some(target)) => {
+                                    let note = ReviewNote::on(&target, text.clone());
+                                    state.status_message = Some(match review_notes.add(note) {
+                                        Ok(()) => format!(
+                                            "{} review note(s) · E to export them",
+                                            review_notes.len()
+                                        ),
+                                        Err(e) => format!("Saving the note failed: {e}"),
+                                    });
+                                    state.line_selection = None;
+                                    notes = all_notes(
+                                        &options,
+                                        &threads,
+                                        threads_expanded,
+                                        &pending_comments,
+                                        &review_notes,
+                                    );
+                                    true
+                                }
+                                _ => false,
+                            };
+                            match (&result, draft_target.take(), pr_info.as_ref()) {
+                                (ModalResult::PostComment(body), Some(mut draft), _) => {
+                                    draft.body = body.clone();
+                                    pending_comments.push(draft);
+                                    state.line_selection = None;
+                                    notes = all_notes(
+                                        &options,
+                                        &threads,
+                                        threads_expanded,
+                                        &pending_comments,
+                                        &review_notes,
+                                    );
+                                    state.status_message = Some(format!(
+                                        "{} pending comment(s) · R to submit the review",
+                                        pending_comments.len()
+                                    ));
+                                }
+                                (ModalResult::PostComment(body), None, Some(pr)) if !noted => {
+                                    comment_rx = Some(post_comment_async(pr, body.clone()));
+                                    state.status_message = Some("Posting comment...".to_string());
+                                }
+                                (ModalResult::SubmitReview(event, body), _, Some(pr)) => {
+                                    review_rx = Some(submit_review_async(
+                                        pr,
+                                        *event,
+                                        body.clone(),
+                                        pending_comments.clone(),
+                                    ));
+                                    state.status_message = Some("Submitting review...".to_string());
+                                }
+                                _ => {}
+                            }
+                            if let (ModalResult::Confirmed, Some(discard)) =
+                                (&result, pending_discard.take())
+                            {
+                                let outcome = match discard {
+                                    Discard::Hunk { patch, staged } => {
+                                        // A staged hunk would otherwise reappear as staged
+                                        let unstaged = if staged {
+                                            remove_from_index(&patch)
+                                        } else {
+                                            Ok(())
+                                        };
+                                        unstaged
+                                            .and_then(|()| discard_from_worktree(&patch))
+                                            .map(|()| "Discarded hunk".to_string())
+                                    }
+                                    Discard::File { filename, status } => {
+                                        restore_file(&filename, &status)
+                                            .map(|()| format!("Restored {filename}"))
+                                    }
+                                };
+                                state.status_message = Some(match outcome {
+                                    Ok(message) => message,
+                                    Err(e) => format!("Discarding failed: {e}"),
+                                });
+                                state.needs_reload = true;
+                            }
+                            pending_discard = None;
+                            if let ModalResult::AcceptSuggestion(i) = result {
+                                if let Some((index, patch)) = shown_suggestions.get(i) {
+                                    let suggestion = &suggestions[*index];
+                                    state.status_message = Some(if state.settings.read_only {
+                                        "Read-only mode: changes are disabled".to_string()
+                                    } else {
+                                        match apply_to_worktree(patch) {
+                                            Ok(()) => {
+                                                applied_suggestions.insert(*index);
+                                                format!(
+                                                    "Applied @{}'s suggestion to {}, commit and push to resolve it",
+                                                    suggestion.author, suggestion.path
+                                                )
+                                            }
+                                            Err(e) => format!(
+                                                "Applying suggestion failed (is the PR branch checked out?): {e}"
+                                            ),
+                                        }
+                                    });
+                                }
+                            }
+                            if let ModalResult::Selected(index, _) = &result {
+                                match Choice::ALL.get(*index) {
+                                    Some(Choice::PolishedNotes) if review_notes.is_empty() => {
+                                        state.status_message =
+                                            Some("No review notes yet, add one with A".to_string());
+                                    }
+                                    Some(Choice::PolishedNotes) => match &options.provider {
+                                        Some(provider) => {
+                                            let files: Vec<String> = state
+                                                .file_diffs
+                                                .iter()
+                                                .map(|diff| diff.filename.clone())
+                                                .collect();
+                                            polish_rx = Some(polish_async(
+                                                Arc::clone(provider),
+                                                review_notes.to_markdown(&files),
+                                            ));
+                                            state.status_message =
+                                                Some("Polishing review notes...".to_string());
+                                        }
+                                        None => {
+                                            state.status_message = Some(
+                                                options.no_provider_message("polish review notes"),
+                                            );
+                                        }
+                                    },
+                                    Some(&choice) => {
+                                        state.status_message = Some(export(
+                                            choice,
+                                            &state,
+                                            &options,
+                                            pr_info.as_ref(),
+                                            &review_notes,
+                                        ));
+                                    }
+                                    None => {}
+                                }
+                            }
+                            if let ModalResult::Commit(message) = &result {
+                                state.status_message = Some(match commit(message, &commit_files) {
+                                    Ok(subject) => format!("Committed: {subject}"),
+                                    Err(e) => format!("Committing failed: {e}"),
+                                });
+                                state.needs_reload = true;
+                            }
+                            if let ModalResult::Copy(text) = &result {
+                                state.status_message = Some(match clipboard::copy(text) {
+                                    Ok(()) => "Copied the explanation".to_string(),
+                                    Err(e) => e,
+                                });
+                            }
+                            if let ModalResult::Filter(text) = &result {
+                                let total = state.file_diffs.len();
+                                let filter = FileFilter::parse(text).ok();
+                                let shown = state.set_sidebar_filter(filter);
+                                state.status_message = Some(if text.is_empty() {
+                                    format!("Showing all {total} files")
+                                } else {
+                                    format!("Showing {shown} of {total} files")
+                                });
+                            }
                             if let ModalResult::FileSelected(file_index) = result {
                                 state.select_file(file_index);
                                 if let Some(idx) = state.sidebar_items.iter().position(|item| {
@@ -238,9 +1058,32 @@ fn run_app_internal(
                 Event::Mouse(mouse) if active_modal.is_none() => {
                     let term_size = terminal.size()?;
                     let footer_height = 1u16;
-                    let sidebar_width = if state.show_sidebar { 40u16 } else { 0u16 };
+                    let sidebar_width = if state.show_sidebar {
+                        state.settings.sidebar_width(term_size.width)
+                    } else {
+                        0
+                    };
 
+                    // Clicking or dragging on a minimap scrolls to the part of the file it marks
+                    let on_minimap = minimaps.iter().find(|area| {
+                        area.contains(ratatui::layout::Position::new(mouse.column, mouse.row))
+                    });
                     match mouse.kind {
+                        MouseEventKind::Down(crossterm::event::MouseButton::Left)
+                        | MouseEventKind::Drag(crossterm::event::MouseButton::Left)
+                            if on_minimap.is_some() =>
+                        {
+                            if let Some(area) = on_minimap {
+                                let line = minimap_line_at(
+                                    (mouse.row - area.y) as usize,
+                                    area.height as usize,
+                                    total_lines,
+                                );
+                                let centered = line.saturating_sub(visible_height / 2);
+                                state.scroll = centered.min(max_scroll) as u16;
+                                state.focused_panel = FocusedPanel::DiffView;
+                            }
+                        }
                         MouseEventKind::Down(crossterm::event::MouseButton::Left) => {
                             if state.show_sidebar
                                 && mouse.column < sidebar_width
@@ -248,18 +1091,18 @@ fn run_app_internal(
                             {
                                 let clicked_row =
                                     (mouse.row.saturating_sub(1)) as usize + state.sidebar_scroll;
-                                if clicked_row < state.sidebar_items.len() {
-                                    if matches!(
-                                        state.sidebar_items[clicked_row],
-                                        SidebarItem::File { .. }
-                                    ) {
-                                        state.sidebar_selected = clicked_row;
-                                        state.focused_panel = FocusedPanel::DiffView;
-                                        if let SidebarItem::File { file_index, .. } =
-                                            &state.sidebar_items[state.sidebar_selected]
-                                        {
-                                            state.select_file(*file_index);
-                                        }
+                                if let Some(SidebarItem::Directory { path, .. }) =
+                                    state.sidebar_items.get(clicked_row)
+                                {
+                                    let path = path.clone();
+                                    state.toggle_directory(&path);
+                                } else if clicked_row < state.sidebar_items.len() {
+                                    state.sidebar_selected = clicked_row;
+                                    state.focused_panel = FocusedPanel::DiffView;
+                                    if let SidebarItem::File { file_index, .. } =
+                                        &state.sidebar_items[state.sidebar_selected]
+                                    {
+                                        state.select_file(*file_index);
                                     }
                                 }
                             } else if mouse.column >= sidebar_width {
@@ -297,10 +1140,19 @@ fn run_app_internal(
                     }
                 }
                 Event::Key(key) if key.kind == KeyEventKind::Press && active_modal.is_none() => {
+                    // File history shows past commits, which can't be staged or discarded
+                    let can_stage = can_stage && state.file_history.is_none();
                     if key.code != KeyCode::Char('g') {
                         state.pending_key = PendingKey::None;
                     }
+                    state.status_message = None;
+                    if !matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                        quit_warned = false;
+                    }
                     match key.code {
+                        KeyCode::Esc if state.line_selection.is_some() => {
+                            state.line_selection = None;
+                        }
                         KeyCode::Esc | KeyCode::Char('c')
                             if (key.code == KeyCode::Esc
                                 || key.modifiers.contains(KeyModifiers::CONTROL))
@@ -308,10 +1160,28 @@ fn run_app_internal(
                         {
                             state.search_state.clear();
                         }
+                        KeyCode::Char('q') | KeyCode::Esc
+                            if !pending_comments.is_empty() && !quit_warned =>
+                        {
+                            quit_warned = true;
+                            state.status_message = Some(format!(
+                                "{} pending review comment(s) not submitted: R submits, q again discards",
+                                pending_comments.len()
+                            ));
+                        }
                         KeyCode::Char('q') | KeyCode::Esc => break,
                         KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                             break
                         }
+                        KeyCode::Char('v')
+                        | KeyCode::Char('e')
+                        | KeyCode::Char('c')
+                        | KeyCode::Char('R')
+                            if state.settings.read_only =>
+                        {
+                            state.status_message =
+                                Some("Read-only mode: changes are disabled".to_string());
+                        }
                         KeyCode::Char('1') => {
                             state.focused_panel = FocusedPanel::Sidebar;
                             state.show_sidebar = true;
@@ -337,54 +1207,60 @@ fn run_app_internal(
                                 state.focused_panel = FocusedPanel::DiffView;
                             }
                         }
-                        KeyCode::Char('j') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            if !state.file_diffs.is_empty() {
-                                let mut next = state.sidebar_selected + 1;
-                                while next < state.sidebar_items.len() {
-                                    if let SidebarItem::File { file_index, .. } =
-                                        &state.sidebar_items[next]
+                        KeyCode::Char('j')
+                            if key.modifiers.contains(KeyModifiers::CONTROL)
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            let mut next = state.sidebar_selected + 1;
+                            while next < state.sidebar_items.len() {
+                                if let SidebarItem::File { file_index, .. } =
+                                    &state.sidebar_items[next]
+                                {
+                                    state.sidebar_selected = next;
+                                    state.select_file(*file_index);
+                                    let visible_height =
+                                        terminal.size()?.height.saturating_sub(5) as usize;
+                                    if state.sidebar_selected
+                                        >= state.sidebar_scroll + visible_height
                                     {
-                                        state.sidebar_selected = next;
-                                        state.select_file(*file_index);
-                                        let visible_height =
-                                            terminal.size()?.height.saturating_sub(5) as usize;
-                                        if state.sidebar_selected
-                                            >= state.sidebar_scroll + visible_height
-                                        {
-                                            state.sidebar_scroll = state
-                                                .sidebar_selected
-                                                .saturating_sub(visible_height)
+                                        state.sidebar_scroll =
+                                            state.sidebar_selected.saturating_sub(visible_height)
                                                 + 1;
-                                        } else if state.sidebar_selected < state.sidebar_scroll {
-                                            state.sidebar_scroll = state.sidebar_selected;
-                                        }
-                                        break;
+                                    } else if state.sidebar_selected < state.sidebar_scroll {
+                                        state.sidebar_scroll = state.sidebar_selected;
                                     }
-                                    next += 1;
+                                    break;
                                 }
+                                next += 1;
                             }
                         }
-                        KeyCode::Char('k') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            if !state.file_diffs.is_empty() && state.sidebar_selected > 0 {
-                                let mut prev = state.sidebar_selected - 1;
-                                loop {
-                                    if let SidebarItem::File { file_index, .. } =
-                                        &state.sidebar_items[prev]
-                                    {
-                                        state.sidebar_selected = prev;
-                                        state.select_file(*file_index);
-                                        if state.sidebar_selected < state.sidebar_scroll {
-                                            state.sidebar_scroll = state.sidebar_selected;
-                                        }
-                                        break;
-                                    }
-                                    if prev == 0 {
-                                        break;
+                        KeyCode::Char('k')
+                            if key.modifiers.contains(KeyModifiers::CONTROL)
+                                && !state.file_diffs.is_empty()
+                                && state.sidebar_selected > 0 =>
+                        {
+                            let mut prev = state.sidebar_selected - 1;
+                            loop {
+                                if let SidebarItem::File { file_index, .. } =
+                                    &state.sidebar_items[prev]
+                                {
+                                    state.sidebar_selected = prev;
+                                    state.select_file(*file_index);
+                                    if state.sidebar_selected < state.sidebar_scroll {
+                                        state.sidebar_scroll = state.sidebar_selected;
                                     }
-                                    prev -= 1;
+                                    break;
+                                }
+                                if prev == 0 {
+                                    break;
                                 }
+                                prev -= 1;
                             }
                         }
+                        // Without a file to move to, the sidebar shortcuts do nothing rather
+                        // than scroll like plain `j` and `k`
+                        KeyCode::Char('j' | 'k')
+                            if key.modifiers.contains(KeyModifiers::CONTROL) => {}
                         KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
                             let half_screen = (visible_height / 2) as u16;
                             state.scroll = (state.scroll + half_screen).min(max_scroll as u16);
@@ -393,49 +1269,121 @@ fn run_app_internal(
                             let half_screen = (visible_height / 2) as u16;
                             state.scroll = state.scroll.saturating_sub(half_screen);
                         }
-                        KeyCode::Char('p') if key.modifiers.contains(KeyModifiers::CONTROL) => {
-                            if !state.file_diffs.is_empty() {
-                                let items: Vec<FilePickerItem> = state
-                                    .file_diffs
-                                    .iter()
-                                    .enumerate()
-                                    .map(|(i, diff)| {
-                                        let status = match diff.status {
-                                            FileStatus::Added => ModalFileStatus::Added,
-                                            FileStatus::Modified => ModalFileStatus::Modified,
-                                            FileStatus::Deleted => ModalFileStatus::Deleted,
-                                        };
-                                        FilePickerItem {
-                                            name: diff.filename.clone(),
-                                            file_index: i,
-                                            status,
-                                            viewed: state.viewed_files.contains(&i),
-                                        }
-                                    })
-                                    .collect();
-                                active_modal = Some(Modal::file_picker("Find File", items));
+                        KeyCode::Char('u') => {
+                            state.settings.unified = !state.settings.unified;
+                            state.diff_fullscreen = DiffFullscreen::None;
+                        }
+                        KeyCode::Char('w') => {
+                            let mut settings = state.settings.clone();
+                            settings.ignore_whitespace = !settings.ignore_whitespace;
+                            state.apply_settings(settings);
+                        }
+                        KeyCode::Char('W') => {
+                            let mut settings = state.settings.clone();
+                            settings.wrap = !settings.wrap;
+                            state.status_message = Some(
+                                if settings.wrap {
+                                    "Wrapping long lines"
+                                } else {
+                                    "Not wrapping long lines"
+                                }
+                                .to_string(),
+                            );
+                            state.apply_settings(settings);
+                        }
+                        KeyCode::Char('M') => {
+                            let mut settings = state.settings.clone();
+                            settings.detect_moves = !settings.detect_moves;
+                            state.status_message = Some(
+                                if settings.detect_moves {
+                                    "Detecting moved code"
+                                } else {
+                                    "Not detecting moved code"
+                                }
+                                .to_string(),
+                            );
+                            state.apply_settings(settings);
+                        }
+                        KeyCode::Char('P') => {
+                            if state.commit_suggestion.take().is_some() {
+                                state.status_message =
+                                    Some("Not suggesting commit subjects".to_string());
+                            } else if !can_stage {
+                                state.status_message = Some(
+                                    "Commit subjects are suggested while staging the working tree"
+                                        .to_string(),
+                                );
+                            } else {
+                                match &options.provider {
+                                    Some(provider) => {
+                                        state.commit_suggestion =
+                                            Some(CommitSuggestion::new(Arc::clone(provider)))
+                                    }
+                                    None => {
+                                        state.status_message = Some(
+                                            options.no_provider_message("suggest commit subjects"),
+                                        )
+                                    }
+                                }
                             }
                         }
-                        KeyCode::Char(']') => {
-                            if !state.file_diffs.is_empty() {
-                                let diff = &state.file_diffs[state.current_file];
-                                if !diff.new_content.is_empty() {
-                                    state.diff_fullscreen = match state.diff_fullscreen {
-                                        DiffFullscreen::NewOnly => DiffFullscreen::None,
-                                        _ => DiffFullscreen::NewOnly,
-                                    };
+                        KeyCode::Char('m') if state.settings.detect_moves => {
+                            state.update_view();
+                            let row = state.scroll as usize + 5;
+                            match jump_target(&state.view.rows, &state.view.moves, row) {
+                                Some(target) => {
+                                    state.scroll = target.saturating_sub(5) as u16;
+                                }
+                                None => {
+                                    state.status_message = Some("No moved code below".to_string());
                                 }
                             }
                         }
-                        KeyCode::Char('[') => {
-                            if !state.file_diffs.is_empty() {
-                                let diff = &state.file_diffs[state.current_file];
-                                if !diff.old_content.is_empty() {
-                                    state.diff_fullscreen = match state.diff_fullscreen {
-                                        DiffFullscreen::OldOnly => DiffFullscreen::None,
-                                        _ => DiffFullscreen::OldOnly,
+                        KeyCode::Char('O') if !state.file_diffs.is_empty() => {
+                            active_modal = Some(Modal::overview(file_stats(&state.file_diffs)));
+                        }
+                        KeyCode::Char('p')
+                            if key.modifiers.contains(KeyModifiers::CONTROL)
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            let items: Vec<FilePickerItem> = state
+                                .file_diffs
+                                .iter()
+                                .enumerate()
+                                .map(|(i, diff)| {
+                                    let status = match diff.status {
+                                        FileStatus::Added => ModalFileStatus::Added,
+                                        FileStatus::Modified => ModalFileStatus::Modified,
+                                        FileStatus::Deleted => ModalFileStatus::Deleted,
+                                        FileStatus::Renamed { .. } => ModalFileStatus::Renamed,
+                                        FileStatus::Copied { .. } => ModalFileStatus::Copied,
                                     };
-                                }
+                                    FilePickerItem {
+                                        name: diff.filename.clone(),
+                                        file_index: i,
+                                        status,
+                                        viewed: state.viewed_files.contains(&i),
+                                    }
+                                })
+                                .collect();
+                            active_modal = Some(Modal::file_picker("Find File", items));
+                        }
+                        KeyCode::Char(']') if !state.file_diffs.is_empty() => {
+                            let diff = &state.file_diffs[state.current_file];
+                            if !diff.new_content.is_empty() {
+                                state.diff_fullscreen = match state.diff_fullscreen {
+                                    DiffFullscreen::NewOnly => DiffFullscreen::None,
+                                    _ => DiffFullscreen::NewOnly,
+                                };
+                            }
+                        }
+                        KeyCode::Char('[') if !state.file_diffs.is_empty() => {
+                            let diff = &state.file_diffs[state.current_file];
+                            if !diff.old_content.is_empty() {
+                                state.diff_fullscreen = match state.diff_fullscreen {
+                                    DiffFullscreen::OldOnly => DiffFullscreen::None,
+                                    _ => DiffFullscreen::OldOnly,
+                                };
                             }
                         }
                         KeyCode::Char('=') => {
@@ -451,6 +1399,7 @@ fn run_app_internal(
                                     state.scroll,
                                     visible_height,
                                     max_scroll,
+                                    &heights,
                                 );
                             }
                         }
@@ -464,19 +1413,39 @@ fn run_app_internal(
                                     state.scroll,
                                     visible_height,
                                     max_scroll,
+                                    &heights,
+                                );
+                            }
+                        }
+                        KeyCode::Down | KeyCode::Char('j') if state.line_selection.is_some() => {
+                            if let Some((_, cursor)) = state.line_selection.as_mut() {
+                                *cursor = (*cursor + 1).min(total_lines.saturating_sub(1));
+                                state.scroll = adjust_scroll_to_line(
+                                    *cursor,
+                                    state.scroll,
+                                    visible_height,
+                                    max_scroll,
+                                    &heights,
+                                );
+                            }
+                        }
+                        KeyCode::Up | KeyCode::Char('k') if state.line_selection.is_some() => {
+                            if let Some((_, cursor)) = state.line_selection.as_mut() {
+                                *cursor = cursor.saturating_sub(1);
+                                state.scroll = adjust_scroll_to_line(
+                                    *cursor,
+                                    state.scroll,
+                                    visible_height,
+                                    max_scroll,
+                                    &heights,
                                 );
                             }
                         }
                         KeyCode::Down | KeyCode::Char('j') => {
                             if state.focused_panel == FocusedPanel::Sidebar {
-                                let mut next = state.sidebar_selected + 1;
-                                while next < state.sidebar_items.len() {
-                                    if matches!(state.sidebar_items[next], SidebarItem::File { .. })
-                                    {
-                                        state.sidebar_selected = next;
-                                        break;
-                                    }
-                                    next += 1;
+                                // Directories can be selected too, to collapse them
+                                if state.sidebar_selected + 1 < state.sidebar_items.len() {
+                                    state.sidebar_selected += 1;
                                 }
                                 let visible_height =
                                     terminal.size()?.height.saturating_sub(5) as usize;
@@ -490,22 +1459,7 @@ fn run_app_internal(
                         }
                         KeyCode::Up | KeyCode::Char('k') => {
                             if state.focused_panel == FocusedPanel::Sidebar {
-                                if state.sidebar_selected > 0 {
-                                    let mut prev = state.sidebar_selected - 1;
-                                    loop {
-                                        if matches!(
-                                            state.sidebar_items[prev],
-                                            SidebarItem::File { .. }
-                                        ) {
-                                            state.sidebar_selected = prev;
-                                            break;
-                                        }
-                                        if prev == 0 {
-                                            break;
-                                        }
-                                        prev -= 1;
-                                    }
-                                }
+                                state.sidebar_selected = state.sidebar_selected.saturating_sub(1);
                                 if state.sidebar_selected < state.sidebar_scroll {
                                     state.sidebar_scroll = state.sidebar_selected;
                                 }
@@ -527,16 +1481,19 @@ fn run_app_internal(
                                 state.sidebar_h_scroll = state.sidebar_h_scroll.saturating_add(4);
                             }
                         }
-                        KeyCode::Enter => {
+                        KeyCode::Enter
                             if state.focused_panel == FocusedPanel::Sidebar
-                                && state.sidebar_selected < state.sidebar_items.len()
-                            {
-                                if let SidebarItem::File { file_index, .. } =
-                                    &state.sidebar_items[state.sidebar_selected]
-                                {
+                                && state.sidebar_selected < state.sidebar_items.len() =>
+                        {
+                            match &state.sidebar_items[state.sidebar_selected] {
+                                SidebarItem::File { file_index, .. } => {
                                     state.select_file(*file_index);
                                     state.focused_panel = FocusedPanel::DiffView;
                                 }
+                                SidebarItem::Directory { path, .. } => {
+                                    let path = path.clone();
+                                    state.toggle_directory(&path);
+                                }
                             }
                         }
                         KeyCode::Char(' ') => {
@@ -554,10 +1511,22 @@ fn run_app_internal(
                                             state.viewed_files.remove(&file_idx);
                                         } else {
                                             state.viewed_files.insert(file_idx);
+                                            // Review flow: jump straight into the next unviewed file
+                                            if state.settings.review_flow
+                                                && state.advance_to_next_unviewed()
+                                            {
+                                                state.focused_panel = FocusedPanel::DiffView;
+                                                state.ensure_sidebar_visible(
+                                                    terminal.size()?.height.saturating_sub(5)
+                                                        as usize,
+                                                );
+                                            }
                                         }
 
-                                        // Fire off async API call if in PR mode
-                                        if let Some(ref pr) = pr_info {
+                                        // Fire off async API call if in PR mode (never in read-only mode)
+                                        if let Some(pr) =
+                                            pr_info.as_ref().filter(|_| !state.settings.read_only)
+                                        {
                                             if was_viewed {
                                                 unmark_file_as_viewed_async(pr, &filename);
                                             } else {
@@ -565,25 +1534,9 @@ fn run_app_internal(
                                             }
                                         }
                                     }
-                                    SidebarItem::Directory { path, .. } => {
-                                        let dir_prefix = format!("{}/", path);
-                                        let child_indices: Vec<usize> = state
-                                            .sidebar_items
-                                            .iter()
-                                            .filter_map(|item| {
-                                                if let SidebarItem::File {
-                                                    path: file_path,
-                                                    file_index,
-                                                    ..
-                                                } = item
-                                                {
-                                                    if file_path.starts_with(&dir_prefix) {
-                                                        return Some(*file_index);
-                                                    }
-                                                }
-                                                None
-                                            })
-                                            .collect();
+                                    SidebarItem::Directory { files, .. } => {
+                                        // Collapsed or not, the directory's files are all marked
+                                        let child_indices = files.clone();
 
                                         let all_viewed = child_indices
                                             .iter()
@@ -600,8 +1553,10 @@ fn run_app_internal(
                                             }
                                         }
 
-                                        // Fire off async API calls if in PR mode
-                                        if let Some(ref pr) = pr_info {
+                                        // Fire off async API calls if in PR mode (never in read-only mode)
+                                        if let Some(pr) =
+                                            pr_info.as_ref().filter(|_| !state.settings.read_only)
+                                        {
                                             for &idx in &child_indices {
                                                 let filename = &state.file_diffs[idx].filename;
                                                 if all_viewed {
@@ -624,55 +1579,17 @@ fn run_app_internal(
                                 } else {
                                     state.viewed_files.insert(current_file);
                                     // Move to next unviewed file
-                                    let mut next_file: Option<(usize, usize)> = None;
-                                    for (idx, item) in state
-                                        .sidebar_items
-                                        .iter()
-                                        .enumerate()
-                                        .skip(state.sidebar_selected + 1)
-                                    {
-                                        if let SidebarItem::File { file_index, .. } = item {
-                                            if !state.viewed_files.contains(file_index) {
-                                                next_file = Some((idx, *file_index));
-                                                break;
-                                            }
-                                        }
-                                    }
-                                    if next_file.is_none() {
-                                        for (idx, item) in state
-                                            .sidebar_items
-                                            .iter()
-                                            .enumerate()
-                                            .take(state.sidebar_selected)
-                                        {
-                                            if let SidebarItem::File { file_index, .. } = item {
-                                                if !state.viewed_files.contains(file_index) {
-                                                    next_file = Some((idx, *file_index));
-                                                    break;
-                                                }
-                                            }
-                                        }
-                                    }
-                                    if let Some((idx, file_idx)) = next_file {
-                                        state.sidebar_selected = idx;
-                                        state.select_file(file_idx);
-                                        let visible_height =
-                                            terminal.size()?.height.saturating_sub(5) as usize;
-                                        if state.sidebar_selected
-                                            >= state.sidebar_scroll + visible_height
-                                        {
-                                            state.sidebar_scroll = state
-                                                .sidebar_selected
-                                                .saturating_sub(visible_height)
-                                                + 1;
-                                        } else if state.sidebar_selected < state.sidebar_scroll {
-                                            state.sidebar_scroll = state.sidebar_selected;
-                                        }
+                                    if state.advance_to_next_unviewed() {
+                                        state.ensure_sidebar_visible(
+                                            terminal.size()?.height.saturating_sub(5) as usize,
+                                        );
                                     }
                                 }
 
-                                // Fire off async API call if in PR mode
-                                if let Some(ref pr) = pr_info {
+                                // Fire off async API call if in PR mode (never in read-only mode)
+                                if let Some(pr) =
+                                    pr_info.as_ref().filter(|_| !state.settings.read_only)
+                                {
                                     if was_viewed {
                                         unmark_file_as_viewed_async(pr, &filename);
                                     } else {
@@ -680,6 +1597,44 @@ fn run_app_internal(
                                     }
                                 }
                             }
+                            if can_stage
+                                && options.provider.is_some()
+                                && !state.file_diffs.is_empty()
+                                && state.viewed_files.len() == state.file_diffs.len()
+                            {
+                                state.status_message = Some(
+                                    "All files viewed · D to commit with a drafted message"
+                                        .to_string(),
+                                );
+                            }
+                        }
+                        KeyCode::Char('V') if !state.file_diffs.is_empty() => {
+                            // Unlike space, never unmarks, so it can be pressed file after file
+                            let current_file = state.current_file;
+                            if state.viewed_files.insert(current_file) {
+                                if let Some(pr) =
+                                    pr_info.as_ref().filter(|_| !state.settings.read_only)
+                                {
+                                    mark_file_as_viewed_async(
+                                        pr,
+                                        &state.file_diffs[current_file].filename,
+                                    );
+                                }
+                            }
+                            if state.advance_to_next_unviewed() {
+                                state.focused_panel = FocusedPanel::DiffView;
+                                state.ensure_sidebar_visible(
+                                    terminal.size()?.height.saturating_sub(5) as usize,
+                                );
+                            } else {
+                                state.status_message =
+                                    Some(if can_stage && options.provider.is_some() {
+                                        "All files viewed · D to commit with a drafted message"
+                                            .to_string()
+                                    } else {
+                                        "All files viewed".to_string()
+                                    });
+                            }
                         }
                         KeyCode::PageDown => {
                             state.scroll = (state.scroll + 20).min(max_scroll as u16);
@@ -687,67 +1642,553 @@ fn run_app_internal(
                         KeyCode::PageUp => {
                             state.scroll = state.scroll.saturating_sub(20);
                         }
-                        KeyCode::Char('}') => {
-                            if !state.file_diffs.is_empty() {
+                        KeyCode::Char('}') | KeyCode::Char('{') if !state.file_diffs.is_empty() => {
+                            let forward = key.code == KeyCode::Char('}');
+                            match state.jump_to_hunk(forward) {
+                                HunkJump::SameFile => {}
+                                HunkJump::OtherFile => {
+                                    state.ensure_sidebar_visible(
+                                        terminal.size()?.height.saturating_sub(5) as usize,
+                                    );
+                                }
+                                HunkJump::NoneLeft => {
+                                    state.status_message = Some(
+                                        if forward {
+                                            "No more hunks below"
+                                        } else {
+                                            "No more hunks above"
+                                        }
+                                        .to_string(),
+                                    );
+                                }
+                            }
+                        }
+                        KeyCode::Char('r') => {
+                            state.needs_reload = true;
+                            if details_rx.is_none() {
+                                details_rx = pr_info.as_ref().map(load_details_async);
+                            }
+                        }
+                        KeyCode::Char('v')
+                            if (can_stage || pr_info.is_some())
+                                && state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            if state.line_selection.is_some() {
+                                state.line_selection = None;
+                            } else {
                                 let diff = &state.file_diffs[state.current_file];
                                 let side_by_side = compute_side_by_side(
                                     &diff.old_content,
                                     &diff.new_content,
-                                    state.settings.tab_width,
+                                    state.settings.tab_width_for(&diff.filename),
+                                    state.settings.ignore_whitespace,
                                 );
-                                let hunks = find_hunk_starts(&side_by_side);
-                                if let Some(&next) =
-                                    hunks.iter().find(|&&h| h > state.scroll as usize + 5)
-                                {
-                                    state.scroll = (next as u16).saturating_sub(5);
+                                // Start on the first change at or below the top of the view
+                                let start = side_by_side
+                                    .iter()
+                                    .enumerate()
+                                    .skip(state.scroll as usize)
+                                    .find(|(_, line)| {
+                                        !matches!(line.change_type, ChangeType::Equal)
+                                    })
+                                    .map(|(i, _)| i)
+                                    .unwrap_or(state.scroll as usize);
+                                state.line_selection = Some((start, start));
+                            }
+                        }
+                        KeyCode::Char('s') if can_stage && state.line_selection.is_some() => {
+                            let diff = &state.file_diffs[state.current_file];
+                            if diff.status != FileStatus::Modified {
+                                state.status_message =
+                                    Some("Line staging only works on modified files".to_string());
+                            } else if let Some(rows) = state.selected_rows() {
+                                let side_by_side = compute_side_by_side(
+                                    &diff.old_content,
+                                    &diff.new_content,
+                                    state.settings.tab_width_for(&diff.filename),
+                                    state.settings.ignore_whitespace,
+                                );
+                                let (old_selected, new_selected) =
+                                    selected_line_numbers(&side_by_side, rows);
+                                let line_count = old_selected.len() + new_selected.len();
+                                // Against the index, which earlier line staging has changed
+                                let patch = index_content(&diff.filename).map(|index| {
+                                    build_index_patch(
+                                        &diff.filename,
+                                        &diff.old_content,
+                                        &index,
+                                        &diff.new_content,
+                                        &old_selected,
+                                        &new_selected,
+                                    )
+                                });
+                                match patch {
+                                    Err(e) => {
+                                        active_modal = Some(Modal::info("Staging failed", e));
+                                    }
+                                    Ok(None) => {
+                                        state.status_message =
+                                            Some("No changed lines selected".to_string());
+                                    }
+                                    Ok(Some(patch)) => match apply_to_index(&patch) {
+                                        Ok(()) => {
+                                            last_staged = Some(Instant::now());
+                                            state.status_message =
+                                                Some(format!("Staged {line_count} line(s)"));
+                                            state.line_selection = None;
+                                            state.refresh_index();
+                                        }
+                                        Err(e) => {
+                                            active_modal = Some(Modal::info("Staging failed", e));
+                                        }
+                                    },
+                                }
+                            }
+                        }
+                        KeyCode::Char('s')
+                            if can_stage
+                                && state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            if state.file_diffs[state.current_file].status != FileStatus::Modified {
+                                state.status_message =
+                                    Some("Hunk staging only works on modified files".to_string());
+                            } else if let Some((rows, patch)) = focused_hunk_patch(&state) {
+                                let staged = state.is_hunk_staged(&rows);
+                                let result = if staged {
+                                    remove_from_index(&patch)
+                                } else {
+                                    apply_to_index(&patch)
+                                };
+                                match result {
+                                    Ok(()) => {
+                                        last_staged = Some(Instant::now());
+                                        state.status_message = Some(
+                                            if staged {
+                                                "Unstaged hunk"
+                                            } else {
+                                                "Staged hunk"
+                                            }
+                                            .to_string(),
+                                        );
+                                        state.refresh_index();
+                                    }
+                                    Err(e) => {
+                                        let title = if staged {
+                                            "Unstaging failed"
+                                        } else {
+                                            "Staging failed"
+                                        };
+                                        active_modal = Some(Modal::info(title, e));
+                                    }
                                 }
+                            } else {
+                                state.status_message = Some("No hunk to stage".to_string());
                             }
                         }
-                        KeyCode::Char('{') => {
-                            if !state.file_diffs.is_empty() {
+                        KeyCode::Char('i')
+                            if state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            match (options.provider.clone(), focused_hunk_patch(&state)) {
+                                (None, _) => {
+                                    state.status_message =
+                                        Some(options.no_provider_message("explain"));
+                                }
+                                (Some(_), None) => {
+                                    state.status_message = Some("No hunk to explain".to_string());
+                                }
+                                (Some(provider), Some((_, patch))) => {
+                                    let filename =
+                                        state.file_diffs[state.current_file].filename.clone();
+                                    active_modal =
+                                        Some(Modal::explanation(format!("Explain {filename}")));
+                                    explain_rx =
+                                        Some(explain_in_background(provider, filename, patch));
+                                }
+                            }
+                        }
+                        KeyCode::Char('x')
+                            if can_stage
+                                && state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            let filename = state.file_diffs[state.current_file].filename.clone();
+                            if state.file_diffs[state.current_file].status != FileStatus::Modified {
+                                state.status_message = Some(
+                                    "Only hunks of modified files can be discarded; X restores the whole file"
+                                        .to_string(),
+                                );
+                            } else if let Some((rows, patch)) = focused_hunk_patch(&state) {
+                                pending_discard = Some(Discard::Hunk {
+                                    staged: state.is_hunk_staged(&rows),
+                                    patch,
+                                });
+                                active_modal = Some(Modal::confirm(
+                                    "Discard hunk",
+                                    format!(
+                                        "Throw away this hunk of {filename}?\nThis can't be undone."
+                                    ),
+                                ));
+                            } else {
+                                state.status_message = Some("No hunk to discard".to_string());
+                            }
+                        }
+                        KeyCode::Char('X') if can_stage && !state.file_diffs.is_empty() => {
+                            let diff = &state.file_diffs[state.current_file];
+                            let message = if let FileStatus::Renamed { from, .. } = &diff.status {
+                                format!(
+                                    "Move {} back to {from}, dropping its staged and unstaged changes?\nThis can't be undone.",
+                                    diff.filename
+                                )
+                            } else if matches!(
+                                diff.status,
+                                FileStatus::Added | FileStatus::Copied { .. }
+                            ) {
+                                format!(
+                                    "Delete {}? HEAD doesn't have it.\nThis can't be undone.",
+                                    diff.filename
+                                )
+                            } else {
+                                format!(
+                                    "Restore {} to HEAD, dropping its staged and unstaged changes?\nThis can't be undone.",
+                                    diff.filename
+                                )
+                            };
+                            pending_discard = Some(Discard::File {
+                                filename: diff.filename.clone(),
+                                status: diff.status.clone(),
+                            });
+                            active_modal = Some(Modal::confirm("Restore file", message));
+                        }
+                        KeyCode::Char('a') => {
+                            state.settings.review_flow = !state.settings.review_flow;
+                        }
+                        KeyCode::Char('b')
+                            if state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            if pr_info.is_some() {
+                                state.status_message =
+                                    Some("Hunk history is not available in PR mode".to_string());
+                            } else {
                                 let diff = &state.file_diffs[state.current_file];
                                 let side_by_side = compute_side_by_side(
                                     &diff.old_content,
                                     &diff.new_content,
-                                    state.settings.tab_width,
+                                    state.settings.tab_width_for(&diff.filename),
+                                    state.settings.ignore_whitespace,
                                 );
-                                let hunks = find_hunk_starts(&side_by_side);
-                                if let Some(&prev) = hunks
-                                    .iter()
-                                    .rev()
-                                    .find(|&&h| (h as u16) < state.scroll.saturating_sub(5))
-                                {
-                                    state.scroll = (prev as u16).saturating_sub(5);
+                                // Hunk navigation leaves the focused hunk 5 rows below the top
+                                let rows = state
+                                    .selected_rows()
+                                    .or_else(|| hunk_at(&side_by_side, state.scroll as usize + 5));
+                                let refs = match &state.file_history {
+                                    Some(history) => {
+                                        DiffRefs::Single(history.current().sha.clone())
+                                    }
+                                    None => DiffRefs::from_options(&options),
+                                };
+                                // Prefer the committed new side so the history includes the change itself
+                                let target = rows.and_then(|rows| {
+                                    refs.new_revision()
+                                        .zip(line_range(&side_by_side, rows.clone(), false))
+                                        .or_else(|| {
+                                            line_range(&side_by_side, rows, true)
+                                                .map(|range| (refs.old_revision(), range))
+                                        })
+                                });
+                                match target {
+                                    None => {
+                                        state.status_message = Some(
+                                            "This hunk only adds lines, so it has no history yet"
+                                                .to_string(),
+                                        );
+                                    }
+                                    Some((revision, (start, end))) => {
+                                        match line_history(&revision, &diff.filename, start, end) {
+                                            Ok(history) if history.is_empty() => {
+                                                state.status_message =
+                                                    Some("No history found".to_string());
+                                            }
+                                            Ok(history) => {
+                                                active_modal = Some(Modal::info(
+                                                    format!(
+                                                        "History of {}:{start}-{end}",
+                                                        diff.filename
+                                                    ),
+                                                    history,
+                                                ));
+                                            }
+                                            Err(e) => {
+                                                active_modal =
+                                                    Some(Modal::info("History failed", e));
+                                            }
+                                        }
+                                    }
                                 }
                             }
                         }
-                        KeyCode::Char('r') => {
-                            state.needs_reload = true;
+                        KeyCode::Char('B') if !state.file_diffs.is_empty() => {
+                            if pr_info.is_some() {
+                                state.status_message =
+                                    Some("Blame is not available in PR mode".to_string());
+                            } else if state.file_history.is_some() {
+                                state.status_message = Some(
+                                    "Blame is not available in file history, H leaves it"
+                                        .to_string(),
+                                );
+                            } else {
+                                state.show_blame = !state.show_blame;
+                                state.blame = None;
+                                if state.show_blame {
+                                    blame_refs
+                                        .get_or_insert_with(|| DiffRefs::from_options(&options));
+                                    state.status_message = Some(
+                                        "enter opens the commit that last changed the focused line"
+                                            .to_string(),
+                                    );
+                                }
+                            }
+                        }
+                        KeyCode::Enter
+                            if state.focused_panel == FocusedPanel::DiffView
+                                && state.view.held_back.is_some() =>
+                        {
+                            state.load_large_file();
                         }
-                        KeyCode::Char('y') => {
-                            if !state.file_diffs.is_empty() {
-                                if let Ok(mut clipboard) = arboard::Clipboard::new() {
-                                    let _ = clipboard
-                                        .set_text(&state.file_diffs[state.current_file].filename);
+                        KeyCode::Enter
+                            if state.focused_panel == FocusedPanel::DiffView
+                                && state
+                                    .file_diffs
+                                    .get(state.current_file)
+                                    .is_some_and(|diff| diff.submodule.is_some()) =>
+                        {
+                            let diff = &state.file_diffs[state.current_file];
+                            let path = diff.filename.clone();
+                            let range = diff
+                                .submodule
+                                .as_ref()
+                                .and_then(|change| change.range())
+                                .map(|(from, to)| (from.to_string(), to.to_string()));
+                            match range {
+                                None => {
+                                    state.status_message =
+                                        Some(format!("{path} isn't checked out with both commits"));
+                                }
+                                Some((from, to)) => {
+                                    let nested = DiffOptions {
+                                        reference: Some(CommitReference::Range { from, to }),
+                                        pr: None,
+                                        file: None,
+                                        watch: false,
+                                        watch_paths: Vec::new(),
+                                        review_flow: state.settings.review_flow,
+                                        read_only: options.read_only,
+                                        record: None,
+                                        replay: None,
+                                        risk: None,
+                                        provider: options.provider.clone(),
+                                        provider_error: options.provider_error.clone(),
+                                        summarize_every: None,
+                                        summary_log: None,
+                                        file_summaries: false,
+                                        preview: false,
+                                        snapshot: None,
+                                        notes: Vec::new(),
+                                        focus: None,
+                                        settings: state.settings.to_config(),
+                                        draft: options.draft.clone(),
+                                    };
+                                    // The submodule's viewer runs git in the submodule
+                                    let superproject = std::env::current_dir()?;
+                                    std::env::set_current_dir(&path)?;
+                                    let result = run_app(nested, None);
+                                    std::env::set_current_dir(superproject)?;
+                                    result?;
+                                    super::terminal::resume()?;
+                                    terminal.clear()?;
+                                    theme::set_mode(state.settings.theme);
+                                    state.status_message = Some(format!("Back from {path}"));
                                 }
                             }
                         }
-                        KeyCode::Char('e') => {
-                            if !state.file_diffs.is_empty() {
-                                io::stdout().execute(DisableMouseCapture)?;
-                                io::stdout().execute(LeaveAlternateScreen)?;
-                                disable_raw_mode()?;
-
-                                let editor =
-                                    std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
-                                let _ = std::process::Command::new(&editor)
-                                    .arg(&state.file_diffs[state.current_file].filename)
-                                    .status();
+                        KeyCode::Enter
+                            if state.focused_panel == FocusedPanel::DiffView
+                                && state.blame.is_some() =>
+                        {
+                            // The selection's cursor, or else the row hunk navigation focuses
+                            let row = state
+                                .line_selection
+                                .map_or(state.scroll as usize + 5, |(_, cursor)| cursor)
+                                .min(total_lines.saturating_sub(1));
+                            let target = state.blamed_at(row).map(|(commit, line)| {
+                                (commit.clone(), line.file.clone(), line.line)
+                            });
+                            match target {
+                                None => {
+                                    state.status_message =
+                                        Some("No blame for this line".to_string());
+                                }
+                                Some((commit, _, _)) if commit.is_uncommitted() => {
+                                    state.status_message =
+                                        Some("This line isn't committed yet".to_string());
+                                }
+                                Some((commit, file, line)) => {
+                                    let blamed = DiffOptions {
+                                        reference: Some(CommitReference::Single(
+                                            commit.sha.clone(),
+                                        )),
+                                        pr: None,
+                                        file: None,
+                                        watch: false,
+                                        watch_paths: Vec::new(),
+                                        review_flow: state.settings.review_flow,
+                                        read_only: options.read_only,
+                                        record: None,
+                                        replay: None,
+                                        risk: None,
+                                        provider: options.provider.clone(),
+                                        provider_error: options.provider_error.clone(),
+                                        summarize_every: None,
+                                        summary_log: None,
+                                        file_summaries: false,
+                                        preview: false,
+                                        snapshot: None,
+                                        notes: Vec::new(),
+                                        focus: Some(Focus {
+                                            file,
+                                            line: Some(line),
+                                        }),
+                                        settings: state.settings.to_config(),
+                                        draft: options.draft.clone(),
+                                    };
+                                    run_app(blamed, None)?;
+                                    // The blamed commit's viewer restored the terminal on its
+                                    // way out
+                                    super::terminal::resume()?;
+                                    terminal.clear()?;
+                                    theme::set_mode(state.settings.theme);
+                                    state.status_message = Some(format!(
+                                        "Back from {} {}",
+                                        &commit.sha[..7],
+                                        commit.summary
+                                    ));
+                                }
+                            }
+                        }
+                        KeyCode::Char('H') if state.file_history.is_some() => {
+                            state.leave_history();
+                        }
+                        KeyCode::Char('H') if !state.file_diffs.is_empty() => {
+                            if pr_info.is_some() || options.preview {
+                                state.status_message = Some(
+                                    "File history is only available for local diffs".to_string(),
+                                );
+                            } else {
+                                let revision = DiffRefs::from_options(&options)
+                                    .new_revision()
+                                    .unwrap_or_else(|| "HEAD".to_string());
+                                let filename =
+                                    state.file_diffs[state.current_file].filename.clone();
+                                match load_history(&revision, &filename) {
+                                    Ok(commits) if commits.is_empty() => {
+                                        state.status_message =
+                                            Some(format!("No commits have touched {filename}"));
+                                    }
+                                    Ok(commits) => {
+                                        state.enter_history(commits);
+                                        state.status_message = Some(
+                                            "< / > older / newer commit · H back to the diff"
+                                                .to_string(),
+                                        );
+                                    }
+                                    Err(e) => {
+                                        state.status_message =
+                                            Some(format!("Loading file history failed: {e}"));
+                                    }
+                                }
+                            }
+                        }
+                        KeyCode::Char(c @ ('<' | '>')) if state.file_history.is_some() => {
+                            let older = c == '<';
+                            if !state.step_history(older) {
+                                state.status_message = Some(
+                                    if older {
+                                        "This is the oldest commit that touched the file"
+                                    } else {
+                                        "This is the newest commit that touched the file"
+                                    }
+                                    .to_string(),
+                                );
+                            }
+                        }
+                        KeyCode::Char('y') if !state.file_diffs.is_empty() => {
+                            let _ = clipboard::copy(&state.file_diffs[state.current_file].filename);
+                        }
+                        KeyCode::Char('D') if !state.file_diffs.is_empty() => {
+                            match options.provider.clone() {
+                                _ if !can_stage => {
+                                    state.status_message = Some(
+                                        "Committing only works on the working tree".to_string(),
+                                    );
+                                }
+                                _ if draft_rx.is_some() => {
+                                    state.status_message =
+                                        Some("Already drafting a commit message".to_string());
+                                }
+                                None => {
+                                    state.status_message =
+                                        Some(options.no_provider_message("draft"));
+                                }
+                                Some(provider) => {
+                                    state.status_message =
+                                        Some("Drafting a commit message...".to_string());
+                                    draft_rx = Some(draft_in_background(
+                                        provider,
+                                        options.draft.clone(),
+                                        diff_patch(&state.file_diffs),
+                                    ));
+                                }
+                            }
+                        }
+                        KeyCode::Char('E') if !state.file_diffs.is_empty() => {
+                            let items = Choice::ALL.iter().map(|c| c.label().to_string()).collect();
+                            active_modal =
+                                Some(Modal::select("Export to the current directory", items));
+                        }
+                        KeyCode::Char('e')
+                            if !state.file_diffs.is_empty()
+                                && state.file_diffs[state.current_file].status
+                                    == FileStatus::Deleted =>
+                        {
+                            let filename = &state.file_diffs[state.current_file].filename;
+                            state.status_message = Some(format!("{filename} was deleted"));
+                        }
+                        KeyCode::Char('e') if !state.file_diffs.is_empty() => {
+                            let filename = state.file_diffs[state.current_file].filename.clone();
+                            // The selection's cursor, or else the row hunk navigation focuses
+                            let line = (state.focused_panel == FocusedPanel::DiffView)
+                                .then(|| {
+                                    state.update_view();
+                                    let row = state
+                                        .line_selection
+                                        .map_or(state.scroll as usize + 5, |(_, cursor)| cursor);
+                                    new_line_near(&state.view.rows, row)
+                                })
+                                .flatten();
 
-                                enable_raw_mode()?;
-                                io::stdout().execute(EnterAlternateScreen)?;
-                                io::stdout().execute(EnableMouseCapture)?;
-                                terminal.clear()?;
+                            let editor = shell::editor();
+                            let (program, args) = editor_command(&editor, &filename, line);
+                            super::terminal::restore();
+                            let status = std::process::Command::new(&program).args(&args).status();
+                            super::terminal::resume()?;
+                            terminal.clear()?;
+                            // Show what was just edited when the working tree is being viewed
+                            state.needs_reload |= can_stage;
+                            if let Err(e) = status {
+                                state.status_message = Some(format!("Couldn't run {program}: {e}"));
                             }
                         }
                         KeyCode::Char('o') => {
@@ -761,10 +2202,199 @@ fn run_app_internal(
                                         pr.number,
                                         generate_file_anchor(filename)
                                     );
-                                    let _ = open_url(&file_url);
+                                    let _ = shell::open_url(&file_url);
+                                }
+                            }
+                        }
+                        KeyCode::Char('C') => match pr_info {
+                            Some(ref pr) if !state.settings.read_only => {
+                                active_modal = Some(Modal::comment(
+                                    format!("Comment on #{}", pr.number),
+                                    mention_candidates.clone(),
+                                ));
+                            }
+                            Some(_) => {
+                                state.status_message =
+                                    Some("Read-only mode: changes are disabled".to_string());
+                            }
+                            None => {
+                                state.status_message =
+                                    Some("Comments are only available in PR mode".to_string());
+                            }
+                        },
+                        KeyCode::Char('c') if pr_info.is_some() && review_rx.is_some() => {
+                            state.status_message = Some("Still submitting the review".to_string());
+                        }
+                        KeyCode::Char('c')
+                            if pr_info.is_some()
+                                && state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            match line_target(&state) {
+                                Some(draft) => {
+                                    active_modal = Some(Modal::comment(
+                                        format!(
+                                            "Comment on {} (added to your review)",
+                                            draft.location()
+                                        ),
+                                        mention_candidates.clone(),
+                                    ));
+                                    draft_target = Some(draft);
+                                }
+                                None => {
+                                    state.status_message = Some(
+                                        "Select lines with v or move to a hunk to comment on it"
+                                            .to_string(),
+                                    );
+                                }
+                            }
+                        }
+                        KeyCode::Char('A')
+                            if state.focused_panel == FocusedPanel::DiffView
+                                && !state.file_diffs.is_empty() =>
+                        {
+                            match line_target(&state) {
+                                Some(target) => {
+                                    let text = review_notes.text_on(&target).unwrap_or_default();
+                                    active_modal = Some(Modal::note(
+                                        format!("Note on {} (kept locally)", target.location()),
+                                        text,
+                                    ));
+                                    note_target = Some(target);
+                                }
+                                None => {
+                                    state.status_message = Some(
+                                        "Select lines with v or move to a hunk to note on it"
+                                            .to_string(),
+                                    );
                                 }
                             }
                         }
+                        KeyCode::Char('R') if pr_info.is_some() => {
+                            if review_rx.is_some() {
+                                state.status_message =
+                                    Some("Still submitting the review".to_string());
+                            } else {
+                                let title = match pending_comments.len() {
+                                    0 => "Submit review".to_string(),
+                                    1 => "Submit review (1 comment)".to_string(),
+                                    n => format!("Submit review ({n} comments)"),
+                                };
+                                active_modal =
+                                    Some(Modal::review(title, mention_candidates.clone()));
+                            }
+                        }
+                        KeyCode::Char('I') => match (&pr_info, &pr_details) {
+                            (Some(pr), Some(details)) => {
+                                active_modal = Some(Modal::pr_details(
+                                    format!("PR #{}", pr.number),
+                                    details.clone(),
+                                ));
+                            }
+                            (Some(pr), None) => {
+                                // Try again if loading failed
+                                if details_rx.is_none() {
+                                    details_rx = Some(load_details_async(pr));
+                                }
+                                state.status_message = Some("Still loading PR details".to_string());
+                            }
+                            (None, _) => {
+                                state.status_message =
+                                    Some("PR details are only available in PR mode".to_string());
+                            }
+                        },
+                        KeyCode::Char('t') | KeyCode::Char('T') if pr_info.is_some() => {
+                            let count = threads.threads.len();
+                            if threads_rx.is_some() {
+                                state.status_message =
+                                    Some("Still loading review comments".to_string());
+                            } else if count == 0 {
+                                state.status_message =
+                                    Some("No review comments on the diff".to_string());
+                            } else {
+                                let next = match (thread_cursor, key.code) {
+                                    (None, KeyCode::Char('t')) => 0,
+                                    (None, _) => count - 1,
+                                    (Some(i), KeyCode::Char('t')) => (i + 1) % count,
+                                    (Some(i), _) => (i + count - 1) % count,
+                                };
+                                thread_cursor = Some(next);
+                                let thread = &threads.threads[next];
+                                state.focus(&thread.path, Some(thread.line), false);
+                                state.status_message = Some(format!(
+                                    "Thread {}/{count} at {}:{}",
+                                    next + 1,
+                                    thread.path,
+                                    thread.line
+                                ));
+                            }
+                        }
+                        KeyCode::Char('z') if !threads.threads.is_empty() => {
+                            threads_expanded = !threads_expanded;
+                            notes = all_notes(
+                                &options,
+                                &threads,
+                                threads_expanded,
+                                &pending_comments,
+                                &review_notes,
+                            );
+                        }
+                        KeyCode::Char('S') => match pr_info {
+                            Some(_) if suggestions_rx.is_some() => {
+                                state.status_message =
+                                    Some("Still loading review suggestions".to_string());
+                            }
+                            Some(_) => {
+                                // Suggestions are on lines of the PR's head version
+                                shown_suggestions = suggestions
+                                    .iter()
+                                    .enumerate()
+                                    .filter_map(|(index, suggestion)| {
+                                        let diff = state
+                                            .file_diffs
+                                            .iter()
+                                            .find(|diff| diff.filename == suggestion.path)?;
+                                        let patch =
+                                            suggestion_patch(suggestion, &diff.new_content)?;
+                                        Some((index, patch))
+                                    })
+                                    .collect();
+                                if shown_suggestions.is_empty() {
+                                    state.status_message =
+                                        Some("No review suggestions to apply".to_string());
+                                } else {
+                                    let items = shown_suggestions
+                                        .iter()
+                                        .map(|(index, patch)| {
+                                            let suggestion = &suggestions[*index];
+                                            let lines =
+                                                if suggestion.start_line == suggestion.end_line {
+                                                    suggestion.end_line.to_string()
+                                                } else {
+                                                    format!(
+                                                        "{}-{}",
+                                                        suggestion.start_line, suggestion.end_line
+                                                    )
+                                                };
+                                            SuggestionItem {
+                                                heading: format!(
+                                                    "{}:{} by @{}",
+                                                    suggestion.path, lines, suggestion.author
+                                                ),
+                                                patch: patch.clone(),
+                                                applied: applied_suggestions.contains(index),
+                                            }
+                                        })
+                                        .collect();
+                                    active_modal = Some(Modal::suggestions(items));
+                                }
+                            }
+                            None => {
+                                state.status_message = Some(
+                                    "Review suggestions are only available in PR mode".to_string(),
+                                );
+                            }
+                        },
                         KeyCode::Char('g') => {
                             if state.pending_key == PendingKey::G {
                                 state.scroll = 0;
@@ -783,163 +2413,40 @@ fn run_app_internal(
                             state.search_state.start_forward();
                         }
                         KeyCode::Char('n') if state.search_state.has_query() => {
-                            if let Some(line) = state.search_state.find_next() {
+                            if let Some(line) = state.search_state.repeat(false) {
                                 state.scroll = adjust_scroll_to_line(
                                     line,
                                     state.scroll,
                                     visible_height,
                                     max_scroll,
+                                    &heights,
                                 );
                             }
                         }
                         KeyCode::Char('N') if state.search_state.has_query() => {
-                            if let Some(line) = state.search_state.find_prev() {
+                            if let Some(line) = state.search_state.repeat(true) {
                                 state.scroll = adjust_scroll_to_line(
                                     line,
                                     state.scroll,
                                     visible_height,
                                     max_scroll,
+                                    &heights,
                                 );
                             }
                         }
+                        KeyCode::Char(',') => {
+                            active_modal = Some(Modal::settings(state.settings.clone()));
+                        }
+                        KeyCode::Char('f') => {
+                            let text = state
+                                .sidebar_filter
+                                .as_ref()
+                                .map(|filter| filter.text.clone())
+                                .unwrap_or_default();
+                            active_modal = Some(Modal::filter(text));
+                        }
                         KeyCode::Char('?') => {
-                            active_modal = Some(Modal::keybindings(
-                                "Keybindings",
-                                vec![
-                                    KeyBindSection {
-                                        title: "Global",
-                                        bindings: vec![
-                                            KeyBind {
-                                                key: "q / esc",
-                                                description: "Quit",
-                                            },
-                                            KeyBind {
-                                                key: "tab",
-                                                description: "Toggle sidebar",
-                                            },
-                                            KeyBind {
-                                                key: "1 / 2",
-                                                description: "Focus sidebar / diff",
-                                            },
-                                            KeyBind {
-                                                key: "ctrl+j / ctrl+k",
-                                                description: "Next / previous file",
-                                            },
-                                            KeyBind {
-                                                key: "ctrl+d / ctrl+u",
-                                                description: "Scroll half page down / up",
-                                            },
-                                            KeyBind {
-                                                key: "ctrl+p",
-                                                description: "Open file picker",
-                                            },
-                                            KeyBind {
-                                                key: "r",
-                                                description: "Refresh diff / PR",
-                                            },
-                                            KeyBind {
-                                                key: "y",
-                                                description: "Copy current filename",
-                                            },
-                                            KeyBind {
-                                                key: "e",
-                                                description: "Open current file in editor",
-                                            },
-                                            KeyBind {
-                                                key: "o",
-                                                description: "Open file in browser (PR mode)",
-                                            },
-                                            KeyBind {
-                                                key: "?",
-                                                description: "Show keybindings",
-                                            },
-                                        ],
-                                    },
-                                    KeyBindSection {
-                                        title: "Sidebar",
-                                        bindings: vec![
-                                            KeyBind {
-                                                key: "j/k or up/down",
-                                                description: "Navigate files",
-                                            },
-                                            KeyBind {
-                                                key: "h/l or left/right",
-                                                description: "Scroll horizontally",
-                                            },
-                                            KeyBind {
-                                                key: "enter",
-                                                description: "Open file in diff view",
-                                            },
-                                            KeyBind {
-                                                key: "space",
-                                                description: "Toggle file as viewed",
-                                            },
-                                        ],
-                                    },
-                                    KeyBindSection {
-                                        title: "Diff View",
-                                        bindings: vec![
-                                            KeyBind {
-                                                key: "j/k or up/down",
-                                                description: "Scroll vertically",
-                                            },
-                                            KeyBind {
-                                                key: "h/l or left/right",
-                                                description: "Scroll horizontally",
-                                            },
-                                            KeyBind {
-                                                key: "gg / G",
-                                                description: "Scroll to top / bottom",
-                                            },
-                                            KeyBind {
-                                                key: "{ / }",
-                                                description: "Previous / next hunk",
-                                            },
-                                            KeyBind {
-                                                key: "pageup / pagedown",
-                                                description: "Scroll by page",
-                                            },
-                                            KeyBind {
-                                                key: "space",
-                                                description: "Mark viewed & next file",
-                                            },
-                                            KeyBind {
-                                                key: "]",
-                                                description: "Toggle new panel fullscreen",
-                                            },
-                                            KeyBind {
-                                                key: "[",
-                                                description: "Toggle old panel fullscreen",
-                                            },
-                                            KeyBind {
-                                                key: "=",
-                                                description: "Reset fullscreen to side-by-side",
-                                            },
-                                        ],
-                                    },
-                                    KeyBindSection {
-                                        title: "Search",
-                                        bindings: vec![
-                                            KeyBind {
-                                                key: "/ or ctrl+f",
-                                                description: "Start search",
-                                            },
-                                            KeyBind {
-                                                key: "n or down",
-                                                description: "Next match",
-                                            },
-                                            KeyBind {
-                                                key: "N or up",
-                                                description: "Previous match",
-                                            },
-                                            KeyBind {
-                                                key: "ctrl+c or esc",
-                                                description: "Cancel search",
-                                            },
-                                        ],
-                                    },
-                                ],
-                            ));
+                            state.search_state.start_backward();
                         }
                         _ => {}
                     }
@@ -949,29 +2456,287 @@ fn run_app_internal(
         }
     }
 
-    io::stdout().execute(DisableMouseCapture)?;
-    disable_raw_mode()?;
-    io::stdout().execute(LeaveAlternateScreen)?;
-
+    if shown_preview.is_some() {
+        graphics::clear()?;
+    }
     Ok(())
 }
 
-fn open_url(url: &str) -> io::Result<()> {
-    #[cfg(target_os = "macos")]
-    {
-        std::process::Command::new("open").arg(url).spawn()?;
-    }
-    #[cfg(target_os = "linux")]
-    {
-        std::process::Command::new("xdg-open").arg(url).spawn()?;
-    }
-    #[cfg(target_os = "windows")]
-    {
-        std::process::Command::new("cmd")
-            .args(["/C", "start", url])
-            .spawn()?;
-    }
-    Ok(())
+/// The popup listing every key of the diff viewer
+fn keybindings_modal() -> Modal {
+    Modal::keybindings(
+        "Keybindings",
+        vec![
+            KeyBindSection {
+                title: "Global",
+                bindings: vec![
+                    KeyBind {
+                        key: "q / esc",
+                        description: "Quit",
+                    },
+                    KeyBind {
+                        key: "tab",
+                        description: "Toggle sidebar",
+                    },
+                    KeyBind {
+                        key: "1 / 2",
+                        description: "Focus sidebar / diff",
+                    },
+                    KeyBind {
+                        key: "ctrl+j / ctrl+k",
+                        description: "Next / previous file",
+                    },
+                    KeyBind {
+                        key: "ctrl+d / ctrl+u",
+                        description: "Scroll half page down / up",
+                    },
+                    KeyBind {
+                        key: "ctrl+p",
+                        description: "Open file picker",
+                    },
+                    KeyBind {
+                        key: "O",
+                        description: "Overview: every file's line counts, sortable",
+                    },
+                    KeyBind {
+                        key: "f",
+                        description: "Filter files by glob or status",
+                    },
+                    KeyBind {
+                        key: "r",
+                        description: "Refresh diff / PR",
+                    },
+                    KeyBind {
+                        key: "a",
+                        description: "Toggle review flow (auto-advance)",
+                    },
+                    KeyBind {
+                        key: "V",
+                        description: "Mark file viewed and open the next unviewed one",
+                    },
+                    KeyBind {
+                        key: "y",
+                        description: "Copy current filename",
+                    },
+                    KeyBind {
+                        key: "e",
+                        description: "Open current file in $EDITOR at the focused line",
+                    },
+                    KeyBind {
+                        key: "D",
+                        description: "Commit the working tree with an AI-drafted message",
+                    },
+                    KeyBind {
+                        key: "E",
+                        description: "Export an HTML report, a patch, or the review notes",
+                    },
+                    KeyBind {
+                        key: "o",
+                        description: "Open file in browser (PR mode)",
+                    },
+                    KeyBind {
+                        key: "C",
+                        description: "Comment on the PR, @ to mention (PR mode)",
+                    },
+                    KeyBind {
+                        key: "c",
+                        description: "Comment on selected lines or the hunk (PR mode)",
+                    },
+                    KeyBind {
+                        key: "A",
+                        description: "Note on selected lines or the hunk, kept locally",
+                    },
+                    KeyBind {
+                        key: "R",
+                        description: "Submit review: comment, approve, request changes",
+                    },
+                    KeyBind {
+                        key: "S",
+                        description: "Review suggestions (PR mode)",
+                    },
+                    KeyBind {
+                        key: "I",
+                        description: "PR description, labels, reviewers and checks",
+                    },
+                    KeyBind {
+                        key: "t / T",
+                        description: "Next / previous comment thread (PR mode)",
+                    },
+                    KeyBind {
+                        key: "z",
+                        description: "Expand / collapse comment threads",
+                    },
+                    KeyBind {
+                        key: ",",
+                        description: "Settings (tab width, theme, wrap...)",
+                    },
+                    KeyBind {
+                        key: "??",
+                        description: "Show keybindings",
+                    },
+                ],
+            },
+            KeyBindSection {
+                title: "Sidebar",
+                bindings: vec![
+                    KeyBind {
+                        key: "j/k or up/down",
+                        description: "Navigate files and directories",
+                    },
+                    KeyBind {
+                        key: "h/l or left/right",
+                        description: "Scroll horizontally",
+                    },
+                    KeyBind {
+                        key: "enter",
+                        description: "Open file in diff view, or collapse / expand directory",
+                    },
+                    KeyBind {
+                        key: "space",
+                        description: "Toggle file, or every file in directory, as viewed",
+                    },
+                ],
+            },
+            KeyBindSection {
+                title: "Diff View",
+                bindings: vec![
+                    KeyBind {
+                        key: "j/k or up/down",
+                        description: "Scroll vertically",
+                    },
+                    KeyBind {
+                        key: "h/l or left/right",
+                        description: "Scroll horizontally",
+                    },
+                    KeyBind {
+                        key: "gg / G",
+                        description: "Scroll to top / bottom",
+                    },
+                    KeyBind {
+                        key: "{ / }",
+                        description: "Previous / next hunk, on into unviewed files",
+                    },
+                    KeyBind {
+                        key: "pageup / pagedown",
+                        description: "Scroll by page",
+                    },
+                    KeyBind {
+                        key: "space",
+                        description: "Mark viewed & next file",
+                    },
+                    KeyBind {
+                        key: "v",
+                        description: "Select lines (working tree, PR mode)",
+                    },
+                    KeyBind {
+                        key: "s",
+                        description: "Stage selected lines, or stage/unstage the hunk",
+                    },
+                    KeyBind {
+                        key: "i",
+                        description: "Explain the hunk with AI",
+                    },
+                    KeyBind {
+                        key: "x / X",
+                        description: "Discard the hunk / restore the file to HEAD",
+                    },
+                    KeyBind {
+                        key: "enter",
+                        description: "Open the commits a submodule moved across",
+                    },
+                    KeyBind {
+                        key: "b",
+                        description: "History of hunk (git log -L)",
+                    },
+                    KeyBind {
+                        key: "B",
+                        description: "Toggle blame gutter",
+                    },
+                    KeyBind {
+                        key: "H",
+                        description: "Toggle file history mode",
+                    },
+                    KeyBind {
+                        key: "< / >",
+                        description: "Older / newer commit in file history",
+                    },
+                    KeyBind {
+                        key: "enter",
+                        description: "Open the commit blamed for the line",
+                    },
+                    KeyBind {
+                        key: "]",
+                        description: "Toggle new panel fullscreen",
+                    },
+                    KeyBind {
+                        key: "[",
+                        description: "Toggle old panel fullscreen",
+                    },
+                    KeyBind {
+                        key: "=",
+                        description: "Reset fullscreen to side-by-side",
+                    },
+                    KeyBind {
+                        key: "u",
+                        description: "Toggle unified / side-by-side view",
+                    },
+                    KeyBind {
+                        key: "w",
+                        description: "Toggle ignoring whitespace-only changes",
+                    },
+                    KeyBind {
+                        key: "W",
+                        description: "Toggle wrapping long lines",
+                    },
+                    KeyBind {
+                        key: "M",
+                        description: "Toggle detecting moved code",
+                    },
+                    KeyBind {
+                        key: "m",
+                        description: "Jump between the ends of moved code",
+                    },
+                    KeyBind {
+                        key: "P",
+                        description: "Toggle AI commit subjects for the staged hunks",
+                    },
+                ],
+            },
+            KeyBindSection {
+                title: "Search",
+                bindings: vec![
+                    KeyBind {
+                        key: "/ or ctrl+f",
+                        description: "Start search",
+                    },
+                    KeyBind {
+                        key: "?",
+                        description: "Start search upwards",
+                    },
+                    KeyBind {
+                        key: "ctrl+r",
+                        description: "Toggle regex (while typing)",
+                    },
+                    KeyBind {
+                        key: "tab",
+                        description: "Ignore case / smart case / match case (while typing)",
+                    },
+                    KeyBind {
+                        key: "n / N",
+                        description: "Next / previous match in the search's direction",
+                    },
+                    KeyBind {
+                        key: "down / up",
+                        description: "Match below / above",
+                    },
+                    KeyBind {
+                        key: "ctrl+c or esc",
+                        description: "Cancel search",
+                    },
+                ],
+            },
+        ],
+    )
 }
 
 fn generate_file_anchor(filename: &str) -> String {
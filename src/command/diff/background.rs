@@ -0,0 +1,161 @@
+//! Find out whether the terminal's background is light or dark.
+//!
+//! The terminal is asked for its background and foreground colors (OSC 11 and OSC 10), then
+//! for its device attributes. Practically every terminal answers the last one, so a terminal
+//! that ignores the color queries doesn't cost the whole timeout. When the colors can't be
+//! queried, `COLORFGBG`, which some terminals set, is used instead.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, is_raw_mode_enabled};
+
+/// How long to wait for the terminal to answer
+const TIMEOUT: Duration = Duration::from_millis(150);
+
+/// Background color, foreground color, device attributes
+const QUERY: &[u8] = b"\x1b]11;?\x07\x1b]10;?\x07\x1b[c";
+
+/// Whether the background is light, or `None` when neither the terminal nor `COLORFGBG`
+/// tells.
+pub fn is_light() -> Option<bool> {
+    query()
+        .and_then(|answer| light_from_answer(&answer))
+        .or_else(|| {
+            std::env::var("COLORFGBG")
+                .ok()
+                .and_then(|value| light_from_colorfgbg(&value))
+        })
+}
+
+/// Send the queries to the terminal and collect its answer, in raw mode so it's neither
+/// echoed nor held back until a newline.
+fn query() -> Option<String> {
+    if !io::stdout().is_terminal() || std::env::var("TERM").is_ok_and(|term| term == "dumb") {
+        return None;
+    }
+    let tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open("/dev/tty")
+        .ok()?;
+    // The diff viewer may already be in raw mode, and has to stay in it
+    let was_raw = is_raw_mode_enabled().unwrap_or(false);
+    if !was_raw {
+        enable_raw_mode().ok()?;
+    }
+    let answer = exchange(tty);
+    if !was_raw {
+        let _ = disable_raw_mode();
+    }
+    answer
+}
+
+/// Reads happen on a thread so they can be given up on. A terminal that answers none of the
+/// queries leaves it waiting for input, which it then takes; those are rare enough to accept.
+fn exchange(mut tty: File) -> Option<String> {
+    tty.write_all(QUERY).ok()?;
+    tty.flush().ok()?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut answer = Vec::new();
+        let mut buf = [0u8; 64];
+        while !has_attributes(&answer) {
+            match tty.read(&mut buf) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => answer.extend_from_slice(&buf[..n]),
+            }
+        }
+        let _ = tx.send(answer);
+    });
+    let answer = rx.recv_timeout(TIMEOUT).ok()?;
+    Some(String::from_utf8_lossy(&answer).into_owned())
+}
+
+/// Whether the answer to the device attributes query, `ESC [ ? ... c`, has arrived; it
+/// comes after the colors.
+fn has_attributes(answer: &[u8]) -> bool {
+    answer
+        .windows(3)
+        .rposition(|window| window == b"\x1b[?")
+        .is_some_and(|start| answer[start..].contains(&b'c'))
+}
+
+/// The background decides; a terminal that only reports its foreground has a light
+/// background when the text is dark.
+fn light_from_answer(answer: &str) -> Option<bool> {
+    if let Some(background) = reported_color(answer, 11) {
+        return Some(luminance(background) > 0.5);
+    }
+    reported_color(answer, 10).map(|foreground| luminance(foreground) < 0.5)
+}
+
+/// The color in the answer to OSC `code`, like `ESC ] 11 ; rgb:1e1e/1e1e/2e2e BEL`, with
+/// each channel from 0 to 1.
+fn reported_color(answer: &str, code: u8) -> Option<[f64; 3]> {
+    let prefix = format!("\x1b]{code};");
+    let start = answer.find(&prefix)? + prefix.len();
+    let rest = &answer[start..];
+    // Terminated by BEL or by ESC \
+    let end = rest.find(['\x07', '\x1b'])?;
+    let spec = &rest[..end];
+    let channels = spec
+        .strip_prefix("rgb:")
+        .or_else(|| spec.strip_prefix("rgba:"))?;
+
+    let mut color = [0.0; 3];
+    let mut parts = channels.split('/');
+    for channel in &mut color {
+        let hex = parts.next()?;
+        if !(1..=4).contains(&hex.len()) {
+            return None;
+        }
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        *channel = f64::from(value) / f64::from((1u32 << (4 * hex.len())) - 1);
+    }
+    Some(color)
+}
+
+fn luminance([r, g, b]: [f64; 3]) -> f64 {
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// `COLORFGBG` is `fg;bg` (sometimes with a field in between), as indexes into the 16 ANSI
+/// colors. White and the bright colors other than bright black are light.
+fn light_from_colorfgbg(value: &str) -> Option<bool> {
+    let background: u8 = value.rsplit(';').next()?.parse().ok()?;
+    Some(background == 7 || (9..=15).contains(&background))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_from_terminal_answer() {
+        let dark = "\x1b]11;rgb:1e1e/1e1e/2e2e\x07\x1b]10;rgb:cdcd/d6d6/f4f4\x07\x1b[?62;22c";
+        assert_eq!(light_from_answer(dark), Some(false));
+        let light = "\x1b]11;rgb:ffff/ffff/ffff\x1b\\\x1b[?1;2c";
+        assert_eq!(light_from_answer(light), Some(true));
+        let foreground_only = "\x1b]10;rgb:00/00/00\x07\x1b[?1;2c";
+        assert_eq!(light_from_answer(foreground_only), Some(true));
+        assert_eq!(light_from_answer("\x1b[?1;2c"), None);
+        assert_eq!(light_from_answer("\x1b]11;rgb:zz/00/00\x07"), None);
+
+        assert!(has_attributes(dark.as_bytes()));
+        assert!(!has_attributes(b"\x1b]11;rgb:1e1e/1e1e/2e2e\x07\x1b[?62"));
+    }
+
+    #[test]
+    fn test_light_from_colorfgbg() {
+        assert_eq!(light_from_colorfgbg("15;0"), Some(false));
+        assert_eq!(light_from_colorfgbg("0;15"), Some(true));
+        assert_eq!(light_from_colorfgbg("0;default;7"), Some(true));
+        assert_eq!(light_from_colorfgbg("7;8"), Some(false));
+        assert_eq!(light_from_colorfgbg("default;default"), None);
+    }
+}
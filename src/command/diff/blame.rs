@@ -0,0 +1,246 @@
+//! Blame gutter for the file in view: who last changed each line, from
+//! `git blame --porcelain`, on both sides of the diff.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::command::diff::width;
+use crate::vcs;
+
+/// Blamed for lines that aren't committed yet
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+/// Columns of the author name in the gutter
+const AUTHOR_WIDTH: usize = 10;
+/// Columns of the whole gutter: short SHA, author and age, each followed by a space
+pub const GUTTER_WIDTH: usize = 8 + AUTHOR_WIDTH + 1 + 5;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlameCommit {
+    pub sha: String,
+    pub author: String,
+    /// Author time, in seconds since the epoch
+    pub time: u64,
+    pub summary: String,
+}
+
+impl BlameCommit {
+    /// Whether this stands for changes in the working tree rather than a commit
+    pub fn is_uncommitted(&self) -> bool {
+        self.sha == UNCOMMITTED
+    }
+}
+
+/// Where a line came from: the commit that last changed it, and its path and line number there
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlameLine {
+    /// Index into [`Blame::commits`]
+    pub commit: usize,
+    pub file: String,
+    pub line: usize,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Blame {
+    pub commits: Vec<BlameCommit>,
+    /// By line number of the blamed version, from 1
+    pub lines: HashMap<usize, BlameLine>,
+}
+
+impl Blame {
+    pub fn commit_of(&self, line: usize) -> Option<(&BlameCommit, &BlameLine)> {
+        let blamed = self.lines.get(&line)?;
+        Some((&self.commits[blamed.commit], blamed))
+    }
+
+    /// Gutter text for `line`, [`GUTTER_WIDTH`] columns wide; blank if it isn't blamed.
+    pub fn gutter(&self, line: usize, now: u64) -> String {
+        let Some((commit, _)) = self.commit_of(line) else {
+            return " ".repeat(GUTTER_WIDTH);
+        };
+        if commit.is_uncommitted() {
+            return format!("{:GUTTER_WIDTH$}", "uncommitted");
+        }
+        let author = width::fit(&commit.author, AUTHOR_WIDTH);
+        format!(
+            "{} {author} {:>4} ",
+            &commit.sha[..7.min(commit.sha.len())],
+            short_age(now.saturating_sub(commit.time))
+        )
+    }
+}
+
+/// Blame for both sides of the file in view, loaded for the file it was asked for
+#[derive(Debug, Default)]
+pub struct FileBlame {
+    pub file_index: usize,
+    pub old: Option<Blame>,
+    pub new: Option<Blame>,
+}
+
+impl FileBlame {
+    /// Gutter text for `line` of the given side, or for a row without a line on that side.
+    /// A side that couldn't be blamed gets a blank gutter while the other has one, and no
+    /// gutter when neither does.
+    pub fn gutter(&self, old: bool, line: Option<usize>, now: u64) -> String {
+        let side = if old { &self.old } else { &self.new };
+        match (side, line) {
+            (Some(blame), Some(line)) => blame.gutter(line, now),
+            _ if self.old.is_some() || self.new.is_some() => " ".repeat(GUTTER_WIDTH),
+            _ => String::new(),
+        }
+    }
+}
+
+/// Age in the largest unit that fits: `45s`, `3h`, `2mo`, `4y`.
+pub fn short_age(seconds: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    for (unit, suffix) in [
+        (365 * DAY, "y"),
+        (30 * DAY, "mo"),
+        (7 * DAY, "w"),
+        (DAY, "d"),
+        (HOUR, "h"),
+        (MINUTE, "m"),
+    ] {
+        if seconds >= unit {
+            return format!("{}{suffix}", seconds / unit);
+        }
+    }
+    format!("{seconds}s")
+}
+
+/// Seconds since the epoch, for working out ages
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Parse `git blame --porcelain` output. Commit details are only given the first time a
+/// commit shows up, and the filename only when it changes, so both carry over.
+pub fn parse_porcelain(output: &str) -> Blame {
+    let mut blame = Blame::default();
+    let mut by_sha: HashMap<String, usize> = HashMap::new();
+    let mut files: HashMap<usize, String> = HashMap::new();
+    // Commit, original line and final line of the group being read
+    let mut current: Option<(usize, usize, usize)> = None;
+
+    for line in output.lines() {
+        if line.starts_with('\t') {
+            if let Some((commit, orig, last)) = current {
+                let file = files.get(&commit).cloned().unwrap_or_default();
+                blame.lines.insert(
+                    last,
+                    BlameLine {
+                        commit,
+                        file,
+                        line: orig,
+                    },
+                );
+            }
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        let is_header = key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit());
+        if is_header {
+            let mut numbers = value.split(' ').filter_map(|n| n.parse::<usize>().ok());
+            let (Some(orig), Some(last)) = (numbers.next(), numbers.next()) else {
+                continue;
+            };
+            let commit = *by_sha.entry(key.to_string()).or_insert_with(|| {
+                blame.commits.push(BlameCommit {
+                    sha: key.to_string(),
+                    author: String::new(),
+                    time: 0,
+                    summary: String::new(),
+                });
+                blame.commits.len() - 1
+            });
+            current = Some((commit, orig, last));
+            continue;
+        }
+        let Some((commit, _, _)) = current else {
+            continue;
+        };
+        match key {
+            "author" => blame.commits[commit].author = value.to_string(),
+            "author-time" => blame.commits[commit].time = value.parse().unwrap_or(0),
+            "summary" => blame.commits[commit].summary = value.to_string(),
+            "filename" => {
+                files.insert(commit, value.to_string());
+            }
+            _ => {}
+        }
+    }
+    blame
+}
+
+/// Blame `filename` as of `revision`, or as it is in the working tree without one.
+pub fn blame_file(revision: Option<&str>, filename: &str) -> Result<Blame, String> {
+    let mut command = Command::new("git");
+    command.args(["blame", "--porcelain"]);
+    if let Some(revision) = revision {
+        command.arg(revision);
+    }
+    vcs::run_git(command.args(["--", filename]))
+        .map(|stdout| parse_porcelain(&stdout))
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PORCELAIN: &str = "\
+9a28bec6d283811b4e8db30293042919fd7b618c 1 1 1
+author Ada Lovelace-Byron
+author-time 1000
+summary first
+boundary
+filename old.rs
+\ta
+81be061cf3e1eb919c9fe94b1e3f4c84d24a61bf 2 2 2
+author Bob
+author-time 4600
+summary second
+previous 9a28bec6d283811b4e8db30293042919fd7b618c old.rs
+filename f.rs
+\tB
+81be061cf3e1eb919c9fe94b1e3f4c84d24a61bf 3 3
+\tc
+0000000000000000000000000000000000000000 4 4 1
+author Not Committed Yet
+author-time 5000
+summary Version of f.rs from f.rs
+filename f.rs
+\tx
+";
+
+    #[test]
+    fn test_parse_porcelain_carries_commit_details_over() {
+        let blame = parse_porcelain(PORCELAIN);
+        assert_eq!(blame.commits.len(), 3);
+        let (commit, line) = blame.commit_of(3).unwrap();
+        assert_eq!(commit.author, "Bob");
+        assert_eq!(commit.summary, "second");
+        assert_eq!((line.file.as_str(), line.line), ("f.rs", 3));
+        assert_eq!(blame.commit_of(1).unwrap().1.file, "old.rs");
+        assert!(blame.commit_of(5).is_none());
+    }
+
+    #[test]
+    fn test_gutter_is_fixed_width() {
+        let blame = parse_porcelain(PORCELAIN);
+        let now = 1000 + 3 * 24 * 3600;
+        assert_eq!(blame.gutter(1, now), "9a28bec Ada Lovela   3d ");
+        assert_eq!(blame.gutter(2, now), "81be061 Bob          2d ");
+        for line in 1..=5 {
+            assert_eq!(width::width(&blame.gutter(line, now)), GUTTER_WIDTH);
+        }
+        assert_eq!(short_age(59), "59s");
+        assert_eq!(short_age(2 * 365 * 24 * 3600), "2y");
+    }
+}
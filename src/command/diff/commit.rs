@@ -0,0 +1,143 @@
+//! Draft a commit message for the working tree under review and commit with it, without
+//! leaving the viewer for `lumen draft`.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use crate::command::draft::DraftCommand;
+use crate::command::output::Format;
+use crate::config::configuration::DraftConfig;
+use crate::git_entity::{diff::Diff, GitEntity};
+use crate::provider::LumenProvider;
+use crate::vcs;
+
+use super::types::{FileDiff, FileStatus};
+
+/// A drafted message and what it describes
+pub struct Draft {
+    pub message: String,
+    /// Whether it covers only what's staged, which is then all that gets committed
+    pub staged: bool,
+}
+
+/// Draft a message in the background for what's staged, or when nothing is, for `patch`: the
+/// changes the viewer shows.
+pub fn draft_in_background(
+    provider: Arc<LumenProvider>,
+    draft_config: DraftConfig,
+    patch: String,
+) -> Receiver<Result<Draft, String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| runtime.block_on(draft(&provider, draft_config, patch)));
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+async fn draft(
+    provider: &LumenProvider,
+    draft_config: DraftConfig,
+    patch: String,
+) -> Result<Draft, String> {
+    let diff = match Diff::from_working_tree(true, &[]) {
+        Ok(diff) => diff,
+        Err(_) => Diff::WorkingTree {
+            staged: false,
+            diff: patch,
+        },
+    };
+    let staged = matches!(diff, Diff::WorkingTree { staged: true, .. });
+    let mut git_entity = GitEntity::Diff(diff);
+    // Files that don't fit are left out of the prompt, not the commit
+    git_entity.fit_to(provider.max_tokens());
+    let command = DraftCommand {
+        git_entity,
+        context: None,
+        draft_config,
+        subject_only: false,
+        commit: false,
+        edit: false,
+        count: 1,
+        format: Format::default(),
+        squash: None,
+        amend: None,
+        copy: false,
+        message_file: None,
+        hook: None,
+        webhook: None,
+    };
+    let message = command
+        .generate(provider)
+        .await
+        .map_err(|e| e.to_string())?
+        .swap_remove(0)
+        .to_commit_message();
+    Ok(Draft { message, staged })
+}
+
+/// Paths `diffs` touch, with the old path of a rename so its removal is committed too.
+pub fn changed_paths(diffs: &[FileDiff]) -> Vec<String> {
+    let mut paths = Vec::new();
+    for diff in diffs {
+        if let FileStatus::Renamed { from, .. } = &diff.status {
+            paths.push(from.clone());
+        }
+        paths.push(diff.filename.clone());
+    }
+    paths
+}
+
+/// Commit with `message`, staging `files` first, all of their changes, unless it's empty.
+/// Returns the new commit's subject.
+pub fn commit(message: &str, files: &[String]) -> Result<String, String> {
+    if !files.is_empty() {
+        vcs::run_git(Command::new("git").args(["add", "-A", "--"]).args(files))
+            .map_err(|e| e.to_string())?;
+    }
+    vcs::git(&["commit", "-m", message]).map_err(|e| e.to_string())?;
+    Ok(message.lines().next().unwrap_or_default().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(filename: &str, status: FileStatus) -> FileDiff {
+        FileDiff {
+            filename: filename.to_string(),
+            old_content: String::new(),
+            new_content: String::new(),
+            status,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_changed_paths_include_both_ends_of_a_rename() {
+        let renamed = FileStatus::Renamed {
+            from: "old.rs".to_string(),
+            to: "new.rs".to_string(),
+        };
+        let copied = FileStatus::Copied {
+            from: "base.rs".to_string(),
+            to: "copy.rs".to_string(),
+        };
+        let diffs = [
+            file("a.rs", FileStatus::Modified),
+            file("new.rs", renamed),
+            file("copy.rs", copied),
+        ];
+        assert_eq!(
+            changed_paths(&diffs),
+            ["a.rs", "old.rs", "new.rs", "copy.rs"]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! A commit subject for what's staged, suggested in the footer while hunks are staged and
+//! unstaged, so staging becomes putting together a commit that tells one story.
+//!
+//! Staging several hunks in a row asks only once: the staged changes are read and sent a
+//! moment after the index last changed, on a thread of their own. The `suggest` entry of
+//! `ai.per_command` applies, so its `model` can point this at a cheaper model.
+
+use std::collections::HashSet;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lumen_core::patch::parse_hunks;
+use lumen_core::response::json_object;
+use serde::Deserialize;
+
+use crate::logging;
+use crate::provider::LumenProvider;
+
+use super::width::floor_char_boundary;
+
+/// How long the index has to stay unchanged before the staged changes are sent
+const DEBOUNCE: Duration = Duration::from_millis(1500);
+/// Longest staged diff sent; the rest is left out
+const MAX_DIFF_BYTES: usize = 30_000;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Suggestion {
+    pub subject: String,
+    /// Why the staged hunks don't belong in one commit, when they don't
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+enum Answer {
+    NothingStaged,
+    /// The staged changes are the ones the suggestion is already for
+    Unchanged,
+    Suggested {
+        diff: String,
+        suggestion: Result<Suggestion, String>,
+    },
+}
+
+pub struct CommitSuggestion {
+    provider: Arc<LumenProvider>,
+    /// The staged diff the suggestion is for
+    staged: String,
+    suggestion: Option<Suggestion>,
+    /// When the index last changed, until the staged changes are read again
+    changed_at: Option<Instant>,
+    job: Option<Receiver<Answer>>,
+}
+
+impl CommitSuggestion {
+    /// Start suggesting, with the first look at the index right away.
+    pub fn new(provider: Arc<LumenProvider>) -> Self {
+        CommitSuggestion {
+            provider,
+            staged: String::new(),
+            suggestion: None,
+            changed_at: Instant::now().checked_sub(DEBOUNCE),
+            job: None,
+        }
+    }
+
+    /// Note that hunks were staged or unstaged, to suggest again once that settles.
+    pub fn index_changed(&mut self) {
+        self.changed_at = Some(Instant::now());
+    }
+
+    /// Collect a finished suggestion, and ask for the next once the index has settled.
+    /// Meant to be called on every tick of the event loop; returns a message for the status
+    /// bar when asking fails.
+    pub fn poll(&mut self) -> Option<String> {
+        let mut status = None;
+        if let Some(job) = &self.job {
+            match job.try_recv() {
+                Ok(Answer::NothingStaged) => {
+                    self.staged.clear();
+                    self.suggestion = None;
+                }
+                Ok(Answer::Unchanged) => {}
+                Ok(Answer::Suggested { diff, suggestion }) => match suggestion {
+                    Ok(suggestion) => {
+                        self.staged = diff;
+                        self.suggestion = Some(suggestion);
+                    }
+                    Err(e) => status = Some(format!("Commit suggestion failed: {e}")),
+                },
+                Err(TryRecvError::Empty) => return None,
+                Err(TryRecvError::Disconnected) => {}
+            }
+            self.job = None;
+        }
+
+        if self
+            .changed_at
+            .is_some_and(|changed| changed.elapsed() >= DEBOUNCE)
+        {
+            self.changed_at = None;
+            self.job = Some(suggest_in_background(
+                Arc::clone(&self.provider),
+                self.staged.clone(),
+            ));
+        }
+        status
+    }
+
+    /// What to show in the footer: the suggested subject with what's staged, marked `…`
+    /// while the staged changes are being looked at again.
+    pub fn footer(&self) -> String {
+        let pending = self.job.is_some() || self.changed_at.is_some();
+        let Some(suggestion) = &self.suggestion else {
+            return if pending {
+                "Suggesting a commit subject…".to_string()
+            } else {
+                "Stage hunks with s for a commit subject".to_string()
+            };
+        };
+        let mut footer = format!(
+            "commit: {} ({}){}",
+            suggestion.subject,
+            staged_counts(&self.staged),
+            if pending { " …" } else { "" }
+        );
+        if let Some(note) = &suggestion.note {
+            footer.push_str(&format!(" · {note}"));
+        }
+        footer
+    }
+}
+
+/// Read the staged changes and, unless they're `previous`, ask for a subject for them.
+fn suggest_in_background(provider: Arc<LumenProvider>, previous: String) -> Receiver<Answer> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let diff = match staged_diff() {
+            Some(diff) if diff.trim().is_empty() => {
+                let _ = tx.send(Answer::NothingStaged);
+                return;
+            }
+            Some(diff) if diff == previous => {
+                let _ = tx.send(Answer::Unchanged);
+                return;
+            }
+            Some(diff) => diff,
+            None => {
+                let _ = tx.send(Answer::Suggested {
+                    diff: previous,
+                    suggestion: Err("couldn't read the staged changes".to_string()),
+                });
+                return;
+            }
+        };
+        let suggestion = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                let mut patch = diff.clone();
+                if patch.len() > MAX_DIFF_BYTES {
+                    patch.truncate(floor_char_boundary(&patch, MAX_DIFF_BYTES));
+                    patch.push_str("\n[rest of the diff left out]\n");
+                }
+                runtime
+                    .block_on(provider.suggest_commit(&patch))
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|answer| extract_suggestion(&answer));
+        let _ = tx.send(Answer::Suggested { diff, suggestion });
+    });
+    rx
+}
+
+fn staged_diff() -> Option<String> {
+    let output = logging::output(Command::new("git").args([
+        "diff",
+        "--cached",
+        "--no-color",
+        "--no-ext-diff",
+    ]))
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The suggestion in the AI response, ignoring any surrounding prose or code fences.
+fn extract_suggestion(ai_response: &str) -> Result<Suggestion, String> {
+    let json = json_object(ai_response).ok_or("AI response did not contain a suggestion")?;
+    let mut suggestion: Suggestion = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    suggestion.subject = suggestion
+        .subject
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    suggestion.note = suggestion
+        .note
+        .map(|note| note.trim().to_string())
+        .filter(|note| !note.is_empty());
+    if suggestion.subject.is_empty() {
+        return Err("AI response had an empty subject".to_string());
+    }
+    Ok(suggestion)
+}
+
+/// `3 hunks in 2 files`, for the staged diff.
+fn staged_counts(diff: &str) -> String {
+    let hunks = parse_hunks(diff);
+    let files: HashSet<&str> = hunks.iter().map(|hunk| hunk.file.as_str()).collect();
+    let plural = |count: usize, word: &str| {
+        if count == 1 {
+            format!("1 {word}")
+        } else {
+            format!("{count} {word}s")
+        }
+    };
+    format!(
+        "{} in {}",
+        plural(hunks.len(), "hunk"),
+        plural(files.len(), "file")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_suggestion() {
+        let response =
+            "```json\n{\"subject\": \"feat(diff): add staging hints\\nmore\", \"note\": \"\"}\n```";
+        assert_eq!(
+            extract_suggestion(response),
+            Ok(Suggestion {
+                subject: "feat(diff): add staging hints".to_string(),
+                note: None,
+            })
+        );
+        assert!(extract_suggestion("{\"subject\": \" \"}").is_err());
+        assert!(extract_suggestion("no idea").is_err());
+    }
+
+    #[test]
+    fn test_staged_counts() {
+        let diff = "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1 +1 @@\n-a\n+b\n\
+                    @@ -9 +9 @@\n-c\n+d\ndiff --git a/b.rs b/b.rs\n--- a/b.rs\n+++ b/b.rs\n\
+                    @@ -1 +1 @@\n-e\n+f\n";
+        assert_eq!(staged_counts(diff), "3 hunks in 2 files");
+        assert_eq!(staged_counts(""), "0 hunks in 0 files");
+    }
+}
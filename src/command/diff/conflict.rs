@@ -0,0 +1,611 @@
+//! Conflict mode: files left conflicted by a merge, rebase or cherry-pick, shown as ours,
+//! base and theirs side by side so each conflict can be resolved by picking a side or
+//! editing the result, then written back and staged.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crossterm::{
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, KeyModifiers,
+    },
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+use ratatui::prelude::*;
+
+use crate::logging;
+
+use super::encoding::decode_working_tree;
+use super::highlight;
+use super::render::render_conflicts;
+use super::terminal::TerminalGuard;
+use super::theme;
+use super::types::DiffViewSettings;
+
+const OURS_MARKER: &str = "<<<<<<<";
+const BASE_MARKER: &str = "|||||||";
+const SEPARATOR: &str = "=======";
+const THEIRS_MARKER: &str = ">>>>>>>";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Conflict {
+    /// Lines of each side, with their line endings
+    pub ours: Vec<String>,
+    /// `None` when the markers have no base section and it couldn't be recovered
+    pub base: Option<Vec<String>>,
+    pub theirs: Vec<String>,
+    pub resolution: Option<Vec<String>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Segment {
+    Common(Vec<String>),
+    Conflict(Conflict),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pick {
+    Ours,
+    Theirs,
+    /// Ours followed by theirs
+    Both,
+    Base,
+}
+
+impl Conflict {
+    pub fn pick(&mut self, pick: Pick) {
+        let lines = match pick {
+            Pick::Ours => self.ours.clone(),
+            Pick::Theirs => self.theirs.clone(),
+            Pick::Both => [self.ours.clone(), self.theirs.clone()].concat(),
+            Pick::Base => match &self.base {
+                Some(base) => base.clone(),
+                None => return,
+            },
+        };
+        self.resolution = Some(lines);
+    }
+
+    /// Which side the resolution matches, if it's exactly one of them
+    pub fn picked(&self) -> Option<Pick> {
+        let resolution = self.resolution.as_ref()?;
+        if *resolution == self.ours {
+            Some(Pick::Ours)
+        } else if *resolution == self.theirs {
+            Some(Pick::Theirs)
+        } else if *resolution == [self.ours.clone(), self.theirs.clone()].concat() {
+            Some(Pick::Both)
+        } else if self.base.as_ref() == Some(resolution) {
+            Some(Pick::Base)
+        } else {
+            None
+        }
+    }
+
+    /// The conflict with its markers, as git writes it
+    fn with_markers(&self) -> String {
+        let mut text = format!("{OURS_MARKER} ours\n");
+        text.extend(self.ours.iter().map(String::as_str));
+        if let Some(base) = &self.base {
+            text.push_str(&format!("{BASE_MARKER} base\n"));
+            text.extend(base.iter().map(String::as_str));
+        }
+        text.push_str(&format!("{SEPARATOR}\n"));
+        text.extend(self.theirs.iter().map(String::as_str));
+        text.push_str(&format!("{THEIRS_MARKER} theirs\n"));
+        text
+    }
+}
+
+pub struct ConflictFile {
+    pub path: String,
+    pub segments: Vec<Segment>,
+    /// Written back and staged
+    pub done: bool,
+}
+
+impl ConflictFile {
+    pub fn conflicts(&self) -> impl Iterator<Item = &Conflict> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Conflict(conflict) => Some(conflict),
+            Segment::Common(_) => None,
+        })
+    }
+
+    pub fn conflict_mut(&mut self, index: usize) -> Option<&mut Conflict> {
+        self.segments
+            .iter_mut()
+            .filter_map(|segment| match segment {
+                Segment::Conflict(conflict) => Some(conflict),
+                Segment::Common(_) => None,
+            })
+            .nth(index)
+    }
+
+    /// Index into `segments` of the `index`th conflict
+    pub fn segment_of(&self, index: usize) -> Option<usize> {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| matches!(segment, Segment::Conflict(_)))
+            .map(|(i, _)| i)
+            .nth(index)
+    }
+
+    pub fn unresolved(&self) -> usize {
+        self.conflicts().filter(|c| c.resolution.is_none()).count()
+    }
+
+    /// The file with every conflict replaced by its resolution, `None` while any is open.
+    pub fn resolved_text(&self) -> Option<String> {
+        let mut text = String::new();
+        for segment in &self.segments {
+            let lines = match segment {
+                Segment::Common(lines) => lines,
+                Segment::Conflict(conflict) => conflict.resolution.as_ref()?,
+            };
+            text.extend(lines.iter().map(String::as_str));
+        }
+        Some(text)
+    }
+}
+
+/// Split a file with conflict markers into the text they share and the conflicts.
+pub fn parse_conflicts(text: &str) -> Vec<Segment> {
+    enum Section {
+        Common,
+        Ours,
+        Base,
+        Theirs,
+    }
+    let mut segments = Vec::new();
+    let mut common = Vec::new();
+    let mut conflict = Conflict::default();
+    let mut section = Section::Common;
+    for line in text.split_inclusive('\n') {
+        let is_marker = |marker: &str| {
+            line.strip_prefix(marker)
+                .is_some_and(|rest| rest.trim().is_empty() || rest.starts_with(' '))
+        };
+        match section {
+            Section::Common if is_marker(OURS_MARKER) => {
+                if !common.is_empty() {
+                    segments.push(Segment::Common(std::mem::take(&mut common)));
+                }
+                section = Section::Ours;
+            }
+            Section::Common => common.push(line.to_string()),
+            Section::Ours if is_marker(BASE_MARKER) => {
+                conflict.base = Some(Vec::new());
+                section = Section::Base;
+            }
+            Section::Ours | Section::Base if is_marker(SEPARATOR) => section = Section::Theirs,
+            Section::Ours => conflict.ours.push(line.to_string()),
+            Section::Base => conflict
+                .base
+                .get_or_insert_with(Vec::new)
+                .push(line.to_string()),
+            Section::Theirs if is_marker(THEIRS_MARKER) => {
+                segments.push(Segment::Conflict(std::mem::take(&mut conflict)));
+                section = Section::Common;
+            }
+            Section::Theirs => conflict.theirs.push(line.to_string()),
+        }
+    }
+    if !common.is_empty() {
+        segments.push(Segment::Common(common));
+    }
+    segments
+}
+
+/// Paths git reports as unmerged.
+pub fn conflicted_files() -> Vec<String> {
+    let output =
+        logging::output(Command::new("git").args(["diff", "--name-only", "--diff-filter=U"]));
+    match output {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The versions of a conflicted file `git mergetool` hands over, in place of the index stages
+pub struct MergeSides {
+    pub local: PathBuf,
+    pub base: PathBuf,
+    pub remote: PathBuf,
+}
+
+/// Read a conflicted file from the working tree, so resolutions made by hand are kept. When
+/// its markers lack the base (git's default conflict style), it's recovered from `sides`, or
+/// without them from the index.
+fn load_conflict_file(path: &str, sides: Option<&MergeSides>) -> Option<ConflictFile> {
+    let bytes = fs::read(path).ok()?;
+    let mut segments = parse_conflicts(&decode_working_tree(path, &bytes));
+    let missing_base = segments
+        .iter()
+        .any(|s| matches!(s, Segment::Conflict(c) if c.base.is_none()));
+    if missing_base {
+        let diff3 = match sides {
+            Some(sides) => merge_files(path, [&sides.local, &sides.base, &sides.remote]),
+            None => merge_with_base(path),
+        };
+        if let Some(diff3) = diff3 {
+            fill_bases(&mut segments, &parse_conflicts(&diff3));
+        }
+    }
+    if !segments.iter().any(|s| matches!(s, Segment::Conflict(_))) {
+        return None;
+    }
+    Some(ConflictFile {
+        path: path.to_string(),
+        segments,
+        done: false,
+    })
+}
+
+/// Take the base of each conflict from the same merge redone with the base in its markers.
+/// Only conflicts with the same sides in both are filled in.
+fn fill_bases(segments: &mut [Segment], with_bases: &[Segment]) {
+    let theirs: Vec<&Conflict> = with_bases
+        .iter()
+        .filter_map(|segment| match segment {
+            Segment::Conflict(conflict) => Some(conflict),
+            Segment::Common(_) => None,
+        })
+        .collect();
+    let ours = segments.iter_mut().filter_map(|segment| match segment {
+        Segment::Conflict(conflict) => Some(conflict),
+        Segment::Common(_) => None,
+    });
+    for (conflict, other) in ours.zip(theirs) {
+        if conflict.base.is_none() && conflict.ours == other.ours && conflict.theirs == other.theirs
+        {
+            conflict.base = other.base.clone();
+        }
+    }
+}
+
+/// The file merged again from the index stages with `git merge-file --diff3`.
+fn merge_with_base(path: &str) -> Option<String> {
+    let dir = std::env::temp_dir().join(format!("lumen-conflict-{}", std::process::id()));
+    fs::create_dir_all(&dir).ok()?;
+    let mut stage_files = Vec::new();
+    for (stage, name) in [(2, "ours"), (1, "base"), (3, "theirs")] {
+        let output =
+            logging::output(Command::new("git").args(["show", &format!(":{stage}:{path}")]))
+                .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let file = dir.join(name);
+        fs::write(&file, &output.stdout).ok()?;
+        stage_files.push(file);
+    }
+    let [ours, base, theirs] = &stage_files[..] else {
+        return None;
+    };
+    let merged = merge_files(path, [ours, base, theirs]);
+    let _ = fs::remove_dir_all(&dir);
+    merged
+}
+
+/// Ours, base and theirs of `path` merged with `git merge-file --diff3`.
+fn merge_files(path: &str, files: [&Path; 3]) -> Option<String> {
+    let output = logging::output(
+        Command::new("git")
+            .args(["merge-file", "-p", "--diff3"])
+            .args(files),
+    )
+    .ok()?;
+    // The exit code is the number of conflicts, and negative on error
+    output
+        .status
+        .code()
+        .is_some_and(|code| code >= 0)
+        .then(|| decode_working_tree(path, &output.stdout))
+}
+
+/// Write the resolved file and stage it.
+fn write_resolved(file: &ConflictFile) -> Result<(), String> {
+    let text = file
+        .resolved_text()
+        .ok_or_else(|| format!("{} still has unresolved conflicts", file.path))?;
+    fs::write(&file.path, text).map_err(|e| format!("Failed to write {}: {e}", file.path))?;
+    let output = logging::output(Command::new("git").args(["add", "--", &file.path]))
+        .map_err(|e| format!("Failed to run git add: {e}"))?;
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    Ok(())
+}
+
+/// Let the user edit a conflict's result in `$EDITOR`, starting from its resolution or, while
+/// it has none, from the conflict with its markers. Markers left in keep it unresolved.
+fn edit_in_editor(conflict: &mut Conflict) -> io::Result<()> {
+    let path = std::env::temp_dir().join(format!("lumen-resolve-{}", std::process::id()));
+    let seed = match &conflict.resolution {
+        Some(lines) => lines.concat(),
+        None => conflict.with_markers(),
+    };
+    fs::write(&path, seed)?;
+
+    io::stdout().execute(DisableMouseCapture)?;
+    io::stdout().execute(LeaveAlternateScreen)?;
+    disable_raw_mode()?;
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vim".to_string());
+    let status = Command::new(&editor).arg(&path).status();
+    enable_raw_mode()?;
+    io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
+
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    if status.is_ok_and(|s| s.success()) {
+        let edited = edited?;
+        let still_conflicted = parse_conflicts(&edited)
+            .iter()
+            .any(|s| matches!(s, Segment::Conflict(_)));
+        conflict.resolution =
+            (!still_conflicted).then(|| edited.split_inclusive('\n').map(String::from).collect());
+    }
+    Ok(())
+}
+
+/// Where the user is in conflict mode
+pub struct ConflictState {
+    pub files: Vec<ConflictFile>,
+    pub current_file: usize,
+    pub current_conflict: usize,
+    pub scroll: u16,
+    pub status_message: Option<String>,
+}
+
+impl ConflictState {
+    pub fn file(&self) -> &ConflictFile {
+        &self.files[self.current_file]
+    }
+
+    fn conflict_count(&self) -> usize {
+        self.file().conflicts().count()
+    }
+
+    fn go_to_conflict(&mut self, index: usize) {
+        self.current_conflict = index.min(self.conflict_count().saturating_sub(1));
+        self.scroll = 0;
+    }
+
+    fn go_to_file(&mut self, index: usize) {
+        self.current_file = index.min(self.files.len() - 1);
+        self.go_to_conflict(0);
+    }
+
+    /// Resolve the current conflict and move on to the next open one in the file.
+    fn pick(&mut self, pick: Pick) {
+        let index = self.current_conflict;
+        let file = &mut self.files[self.current_file];
+        let Some(conflict) = file.conflict_mut(index) else {
+            return;
+        };
+        if pick == Pick::Base && conflict.base.is_none() {
+            self.status_message = Some("This conflict has no base to take".to_string());
+            return;
+        }
+        conflict.pick(pick);
+        let next = file
+            .conflicts()
+            .enumerate()
+            .skip(index + 1)
+            .find(|(_, c)| c.resolution.is_none())
+            .map(|(i, _)| i);
+        if let Some(next) = next {
+            self.go_to_conflict(next);
+        }
+    }
+
+    fn write(&mut self) {
+        let file = &mut self.files[self.current_file];
+        self.status_message = Some(match write_resolved(file) {
+            Ok(()) => {
+                file.done = true;
+                format!("Wrote {} and staged it", file.path)
+            }
+            Err(e) => e,
+        });
+        if let Some(next) = self.files.iter().position(|f| !f.done) {
+            if self.files[self.current_file].done {
+                self.go_to_file(next);
+            }
+        }
+    }
+}
+
+/// Resolve the conflicted files in `paths`. Returns whether every file was written, in which
+/// case the regular viewer can show the result.
+pub fn run_conflict_ui(paths: &[String], settings: &DiffViewSettings) -> io::Result<bool> {
+    let files = paths
+        .iter()
+        .filter_map(|p| load_conflict_file(p, None))
+        .collect();
+    resolve(files, settings)
+}
+
+/// Resolve `merged` for `git mergetool`, which passes the versions it was merged from.
+/// Returns whether it was written, or had no conflicts left to resolve.
+pub fn run_merge_tool(
+    merged: &str,
+    sides: &MergeSides,
+    settings: &DiffViewSettings,
+) -> io::Result<bool> {
+    let files = load_conflict_file(merged, Some(sides))
+        .into_iter()
+        .collect();
+    resolve(files, settings)
+}
+
+fn resolve(files: Vec<ConflictFile>, settings: &DiffViewSettings) -> io::Result<bool> {
+    if files.is_empty() {
+        return Ok(true);
+    }
+    highlight::init();
+    let mode = settings
+        .custom_theme
+        .as_deref()
+        .and_then(|name| theme::use_custom(name).ok())
+        .unwrap_or(settings.theme);
+    theme::set_mode(mode);
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut state = ConflictState {
+        files,
+        current_file: 0,
+        current_conflict: 0,
+        scroll: 0,
+        status_message: None,
+    };
+
+    loop {
+        terminal.draw(|frame| render_conflicts(frame, &state))?;
+        if state.files.iter().all(|f| f.done) {
+            return Ok(true);
+        }
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        state.status_message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Ok(false)
+            }
+            KeyCode::Char('o') => state.pick(Pick::Ours),
+            KeyCode::Char('t') => state.pick(Pick::Theirs),
+            KeyCode::Char('b') => state.pick(Pick::Both),
+            KeyCode::Char('B') => state.pick(Pick::Base),
+            KeyCode::Char('u') => {
+                let index = state.current_conflict;
+                if let Some(conflict) = state.files[state.current_file].conflict_mut(index) {
+                    conflict.resolution = None;
+                }
+            }
+            KeyCode::Char('e') => {
+                let index = state.current_conflict;
+                if let Some(conflict) = state.files[state.current_file].conflict_mut(index) {
+                    edit_in_editor(conflict)?;
+                }
+                terminal.clear()?;
+            }
+            KeyCode::Char('w') => state.write(),
+            KeyCode::Char('n') | KeyCode::Tab => {
+                state.go_to_conflict((state.current_conflict + 1) % state.conflict_count());
+            }
+            KeyCode::Char('N') | KeyCode::BackTab => {
+                let count = state.conflict_count();
+                state.go_to_conflict((state.current_conflict + count - 1) % count);
+            }
+            KeyCode::Char(']') => {
+                state.go_to_file((state.current_file + 1) % state.files.len());
+            }
+            KeyCode::Char('[') => {
+                let count = state.files.len();
+                state.go_to_file((state.current_file + count - 1) % count);
+            }
+            KeyCode::Char('j') | KeyCode::Down => state.scroll = state.scroll.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => state.scroll = state.scroll.saturating_sub(1),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MERGED: &str = "fn main() {\n<<<<<<< HEAD\n    run(1);\n||||||| base\n    run(0);\n=======\n    run(2);\n>>>>>>> feature\n}\n";
+
+    #[test]
+    fn test_parse_conflicts() {
+        let segments = parse_conflicts(MERGED);
+        assert_eq!(segments.len(), 3);
+        assert_eq!(
+            segments[0],
+            Segment::Common(vec!["fn main() {\n".to_string()])
+        );
+        let Segment::Conflict(conflict) = &segments[1] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(conflict.ours, vec!["    run(1);\n"]);
+        assert_eq!(conflict.base, Some(vec!["    run(0);\n".to_string()]));
+        assert_eq!(conflict.theirs, vec!["    run(2);\n"]);
+        // `=======` on its own is only a separator inside a conflict
+        assert_eq!(parse_conflicts("a\n=======\n").len(), 1);
+    }
+
+    #[test]
+    fn test_resolved_text_needs_every_conflict() {
+        let mut file = ConflictFile {
+            path: "main.rs".to_string(),
+            segments: parse_conflicts(MERGED),
+            done: false,
+        };
+        assert_eq!(file.resolved_text(), None);
+        let conflict = file.conflict_mut(0).unwrap();
+        conflict.pick(Pick::Both);
+        assert_eq!(conflict.picked(), Some(Pick::Both));
+        assert_eq!(
+            file.resolved_text().unwrap(),
+            "fn main() {\n    run(1);\n    run(2);\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_fill_bases_only_matching_conflicts() {
+        let mut segments = parse_conflicts("<<<<<<< HEAD\na\n=======\nb\n>>>>>>> x\n");
+        fill_bases(
+            &mut segments,
+            &parse_conflicts("<<<<<<< ours\na\n||||||| base\no\n=======\nb\n>>>>>>> theirs\n"),
+        );
+        let Segment::Conflict(conflict) = &segments[0] else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(conflict.base, Some(vec!["o\n".to_string()]));
+    }
+
+    #[test]
+    fn test_mergetool_sides_supply_the_base() {
+        let dir = std::env::temp_dir().join(format!("lumen-mergetool-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, text: &str| {
+            let path = dir.join(name);
+            fs::write(&path, text).unwrap();
+            path
+        };
+        let sides = MergeSides {
+            local: write("local", "a\nrun(1);\nz\n"),
+            base: write("base", "a\nrun(0);\nz\n"),
+            remote: write("remote", "a\nrun(2);\nz\n"),
+        };
+        let merged = write(
+            "merged",
+            "a\n<<<<<<< HEAD\nrun(1);\n=======\nrun(2);\n>>>>>>> feature\nz\n",
+        );
+        let file = load_conflict_file(&merged.to_string_lossy(), Some(&sides)).unwrap();
+        let conflict = file.conflicts().next().unwrap();
+        assert_eq!(conflict.base, Some(vec!["run(0);\n".to_string()]));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
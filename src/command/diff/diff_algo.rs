@@ -1,106 +0,0 @@
-use similar::{ChangeTag, TextDiff};
-
-use super::types::{expand_tabs, ChangeType, DiffLine};
-
-/// Computes a side-by-side diff using GitHub-style pairing.
-///
-/// This algorithm pairs consecutive deletions with consecutive insertions,
-/// showing them on the same row. This avoids the visual offset where a modified
-pub fn compute_side_by_side(old: &str, new: &str, tab_width: usize) -> Vec<DiffLine> {
-    let diff = TextDiff::from_lines(old, new);
-    let mut lines = Vec::new();
-    let mut old_num = 1usize;
-    let mut new_num = 1usize;
-
-    // Collect all changes first
-    let changes: Vec<_> = diff.iter_all_changes().collect();
-    let mut i = 0;
-
-    while i < changes.len() {
-        let change = &changes[i];
-
-        match change.tag() {
-            ChangeTag::Equal => {
-                let text = expand_tabs(change.value().trim_end(), tab_width);
-                lines.push(DiffLine {
-                    old_line: Some((old_num, text.clone())),
-                    new_line: Some((new_num, text)),
-                    change_type: ChangeType::Equal,
-                });
-                old_num += 1;
-                new_num += 1;
-                i += 1;
-            }
-            ChangeTag::Delete => {
-                // Collect consecutive deletions
-                let mut deletions = Vec::new();
-                while i < changes.len() && changes[i].tag() == ChangeTag::Delete {
-                    deletions.push((
-                        old_num,
-                        expand_tabs(changes[i].value().trim_end(), tab_width),
-                    ));
-                    old_num += 1;
-                    i += 1;
-                }
-
-                // Collect consecutive insertions that follow
-                let mut insertions = Vec::new();
-                while i < changes.len() && changes[i].tag() == ChangeTag::Insert {
-                    insertions.push((
-                        new_num,
-                        expand_tabs(changes[i].value().trim_end(), tab_width),
-                    ));
-                    new_num += 1;
-                    i += 1;
-                }
-
-                // Pair deletions with insertions
-                let max_len = deletions.len().max(insertions.len());
-                for j in 0..max_len {
-                    let old_line = deletions.get(j).cloned();
-                    let new_line = insertions.get(j).cloned();
-
-                    let change_type = match (&old_line, &new_line) {
-                        (Some(_), Some(_)) => ChangeType::Modified,
-                        (Some(_), None) => ChangeType::Delete,
-                        (None, Some(_)) => ChangeType::Insert,
-                        (None, None) => unreachable!(),
-                    };
-
-                    lines.push(DiffLine {
-                        old_line,
-                        new_line,
-                        change_type,
-                    });
-                }
-            }
-            ChangeTag::Insert => {
-                // Handle insertions that aren't preceded by deletions
-                lines.push(DiffLine {
-                    old_line: None,
-                    new_line: Some((new_num, expand_tabs(change.value().trim_end(), tab_width))),
-                    change_type: ChangeType::Insert,
-                });
-                new_num += 1;
-                i += 1;
-            }
-        }
-    }
-    lines
-}
-
-pub fn find_hunk_starts(lines: &[DiffLine]) -> Vec<usize> {
-    let mut hunks = Vec::new();
-    let mut in_hunk = false;
-
-    for (i, line) in lines.iter().enumerate() {
-        let is_change = !matches!(line.change_type, ChangeType::Equal);
-        if is_change && !in_hunk {
-            hunks.push(i);
-            in_hunk = true;
-        } else if !is_change {
-            in_hunk = false;
-        }
-    }
-    hunks
-}
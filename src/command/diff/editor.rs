@@ -0,0 +1,100 @@
+//! Open a file in `$EDITOR` at the line being looked at, spelling the line the way the
+//! editor expects it.
+
+use std::path::Path;
+
+use super::types::DiffLine;
+
+/// Editors that take `--goto file:line`
+const GOTO_EDITORS: &[&str] = &["code", "code-insiders", "codium", "cursor", "windsurf"];
+/// Editors that take `file:line`
+const SUFFIX_EDITORS: &[&str] = &["subl", "zed", "hx", "helix"];
+
+/// The program and arguments that open `file` at `line` with `editor`, the value of
+/// `$EDITOR`, which may carry arguments of its own like `code --wait`. Anything not known
+/// to want otherwise gets `+line`, which vi, emacs, nano, micro and kakoune all take.
+pub fn editor_command(editor: &str, file: &str, line: Option<usize>) -> (String, Vec<String>) {
+    let mut words = editor.split_whitespace().map(str::to_string);
+    let program = words.next().unwrap_or_else(|| "vim".to_string());
+    let mut args: Vec<String> = words.collect();
+    let name = Path::new(&program)
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+    match line {
+        None => args.push(file.to_string()),
+        Some(line) if GOTO_EDITORS.contains(&name) => {
+            args.push("--goto".to_string());
+            args.push(format!("{file}:{line}"));
+        }
+        Some(line) if SUFFIX_EDITORS.contains(&name) => args.push(format!("{file}:{line}")),
+        Some(line) => {
+            args.push(format!("+{line}"));
+            args.push(file.to_string());
+        }
+    }
+    (program, args)
+}
+
+/// The line of the new version at `row`, or where a deleted line at `row` used to be: the
+/// nearest new line above it, or below it at the top of a file.
+pub fn new_line_near(rows: &[DiffLine], row: usize) -> Option<usize> {
+    let number = |r: &DiffLine| r.new_line.as_ref().map(|(n, _)| *n);
+    let row = row.min(rows.len().checked_sub(1)?);
+    rows[..=row]
+        .iter()
+        .rev()
+        .find_map(number)
+        .or_else(|| rows[row..].iter().find_map(number))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumen_core::side_by_side::compute_side_by_side;
+
+    fn args(editor: &str, line: Option<usize>) -> (String, Vec<String>) {
+        editor_command(editor, "src/main.rs", line)
+    }
+
+    #[test]
+    fn test_line_is_spelled_for_each_editor() {
+        assert_eq!(
+            args("vim", Some(12)),
+            ("vim".into(), vec!["+12".into(), "src/main.rs".into()])
+        );
+        assert_eq!(
+            args("/usr/local/bin/code --wait", Some(12)),
+            (
+                "/usr/local/bin/code".into(),
+                vec!["--wait".into(), "--goto".into(), "src/main.rs:12".into()]
+            )
+        );
+        assert_eq!(
+            args("hx", Some(3)),
+            ("hx".into(), vec!["src/main.rs:3".into()])
+        );
+        assert_eq!(
+            args("emacsclient -t", None),
+            (
+                "emacsclient".into(),
+                vec!["-t".into(), "src/main.rs".into()]
+            )
+        );
+    }
+
+    #[test]
+    fn test_deleted_lines_open_where_they_were() {
+        let rows = compute_side_by_side("a\nb\nc\n", "a\nc\n", 4, false);
+        let deleted = rows
+            .iter()
+            .position(|r| r.new_line.is_none())
+            .expect("a deleted row");
+        assert_eq!(new_line_near(&rows, deleted), Some(1));
+        assert_eq!(new_line_near(&rows, rows.len() + 10), Some(2));
+
+        let rows = compute_side_by_side("gone\nkept\n", "kept\n", 4, false);
+        assert_eq!(new_line_near(&rows, 0), Some(1));
+        assert_eq!(new_line_near(&[], 0), None);
+    }
+}
@@ -0,0 +1,133 @@
+//! Decode file contents that aren't UTF-8 for display, so Latin-1 or Shift-JIS files don't
+//! turn into replacement characters: the encoding `.gitattributes` declares for the working
+//! tree, a UTF-16 byte order mark, or else a guess from the bytes themselves.
+
+use std::process::Command;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_8};
+
+use crate::logging;
+
+/// Text of `bytes`, decoded as `declared` when given, otherwise as UTF-8 when valid and
+/// as the likeliest legacy encoding when not.
+pub fn decode(bytes: &[u8], declared: Option<&'static Encoding>) -> String {
+    if let Some(encoding) = declared {
+        return encoding.decode(bytes).0.into_owned();
+    }
+    // UTF-16 text is mostly valid UTF-8 too, with a NUL after every ASCII letter
+    if let Some((encoding, _)) = Encoding::for_bom(bytes).filter(|(e, _)| *e != UTF_8) {
+        return encoding.decode(bytes).0.into_owned();
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return text.to_string();
+    }
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true).decode(bytes).0.into_owned()
+}
+
+/// Whether `bytes` are a binary file rather than text: a NUL in the first 8000 bytes, as git
+/// checks, unless a UTF-16 byte order mark accounts for it.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    if Encoding::for_bom(bytes).is_some_and(|(e, _)| e != UTF_8) {
+        return false;
+    }
+    bytes.iter().take(8000).any(|&b| b == 0)
+}
+
+/// Decode a working-tree file, which git keeps in its `working-tree-encoding` if one is set.
+/// The attribute is only looked up for files that don't read as UTF-8.
+pub fn decode_working_tree(path: &str, bytes: &[u8]) -> String {
+    let plain = std::str::from_utf8(bytes).is_ok() && !bytes.contains(&0);
+    let declared = if plain {
+        None
+    } else {
+        working_tree_encoding(path)
+    };
+    decode(bytes, declared)
+}
+
+/// The `working-tree-encoding` attribute of `path`, if set to an encoding we know.
+fn working_tree_encoding(path: &str) -> Option<&'static Encoding> {
+    let output = logging::output(Command::new("git").args([
+        "check-attr",
+        "working-tree-encoding",
+        "--",
+        path,
+    ]))
+    .ok()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    // `<path>: working-tree-encoding: <value>`
+    let value = stdout.trim().rsplit(": ").next()?;
+    Encoding::for_label(value.as_bytes())
+}
+
+/// Decode the output of `git diff` one file at a time, as each file has its own encoding.
+pub fn decode_diff(bytes: &[u8]) -> String {
+    let mut starts: Vec<usize> = bytes
+        .windows(11)
+        .enumerate()
+        .filter(|(i, window)| *window == b"diff --git " && (*i == 0 || bytes[i - 1] == b'\n'))
+        .map(|(i, _)| i)
+        .collect();
+    if starts.first() != Some(&0) {
+        starts.insert(0, 0);
+    }
+    starts.push(bytes.len());
+    starts
+        .windows(2)
+        .map(|range| decode(&bytes[range[0]..range[1]], None))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+
+    #[test]
+    fn test_utf8_is_kept_as_is() {
+        assert_eq!(decode("héllo\n".as_bytes(), None), "héllo\n");
+        assert_eq!(decode(b"plain\n", Some(UTF_8)), "plain\n");
+    }
+
+    #[test]
+    fn test_declared_encoding_wins() {
+        let (bytes, _, _) = SHIFT_JIS.encode("日本語のテキスト\n");
+        assert_eq!(decode(&bytes, Some(SHIFT_JIS)), "日本語のテキスト\n");
+    }
+
+    #[test]
+    fn test_legacy_encoding_is_detected() {
+        let text = "Le garçon a mangé une crème brûlée à côté de la fenêtre.\n";
+        let (bytes, _, _) = WINDOWS_1252.encode(text);
+        assert!(std::str::from_utf8(&bytes).is_err());
+        assert_eq!(decode(&bytes, None), text);
+    }
+
+    #[test]
+    fn test_utf16_with_bom() {
+        let mut bytes = vec![0xFF, 0xFE];
+        for unit in "hi\n".encode_utf16() {
+            bytes.extend(unit.to_le_bytes());
+        }
+        assert_eq!(decode(&bytes, None), "hi\n");
+    }
+
+    #[test]
+    fn test_binary_detection() {
+        assert!(is_binary(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+        assert!(!is_binary("plain text\n".as_bytes()));
+        assert!(!is_binary(&[0xFF, 0xFE, b'h', 0, b'i', 0]));
+    }
+
+    #[test]
+    fn test_diff_files_are_decoded_separately() {
+        let mut bytes = b"diff --git a/a b/a\n+caf\xe9 cr\xe8me br\xfbl\xe9e\n".to_vec();
+        bytes.extend("diff --git a/b b/b\n+naïve\n".as_bytes());
+        let decoded = decode_diff(&bytes);
+        assert!(decoded.contains("+café crème brûlée\n"));
+        assert!(decoded.ends_with("+naïve\n"));
+    }
+}
@@ -0,0 +1,42 @@
+//! Ask the provider to explain the focused hunk, streaming the answer back to the viewer.
+
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use crate::provider::LumenProvider;
+
+pub enum ExplainUpdate {
+    /// The next piece of the explanation
+    Delta(String),
+    Done,
+    Failed(String),
+}
+
+/// Explain `hunk` of `file` in the background. Updates arrive in order and end with `Done`
+/// or `Failed`.
+pub fn explain_in_background(
+    provider: Arc<LumenProvider>,
+    file: String,
+    hunk: String,
+) -> Receiver<ExplainUpdate> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime
+                    .block_on(provider.explain_hunk(&file, &hunk, |delta| {
+                        let _ = tx.send(ExplainUpdate::Delta(delta.to_string()));
+                    }))
+                    .map_err(|e| e.to_string())
+            });
+        let _ = tx.send(match result {
+            Ok(_) => ExplainUpdate::Done,
+            Err(e) => ExplainUpdate::Failed(e),
+        });
+    });
+    rx
+}
@@ -0,0 +1,458 @@
+//! The diff written out for people without lumen: a standalone HTML report, side by side and
+//! syntax highlighted with the viewer's theme, or a `.patch` that `git apply` takes.
+
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use lumen_core::side_by_side::compute_side_by_side;
+use ratatui::style::Color;
+use similar::TextDiff;
+
+use super::highlight::highlight_colored;
+use super::submodule;
+use super::theme::{self, ThemeMode};
+use super::types::{ChangeType, DiffLine, DiffViewSettings, FileDiff, FileStatus, SubmoduleCommit};
+
+/// Unchanged rows shown around each change in the report, same as `git diff`
+const CONTEXT: usize = 3;
+
+/// What `lumen diff --export-html` and `--export-patch` write
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Html,
+    Patch,
+}
+
+/// What the export menu offers, in the order it lists them
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Choice {
+    Report,
+    DiffPatch,
+    FilePatch,
+    HunkPatch,
+    ReviewNotes,
+    PolishedNotes,
+}
+
+impl Choice {
+    pub const ALL: [Choice; 6] = [
+        Choice::Report,
+        Choice::DiffPatch,
+        Choice::FilePatch,
+        Choice::HunkPatch,
+        Choice::ReviewNotes,
+        Choice::PolishedNotes,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Choice::Report => "HTML report of the whole diff",
+            Choice::DiffPatch => "Patch of the whole diff",
+            Choice::FilePatch => "Patch of this file",
+            Choice::HunkPatch => "Patch of the focused hunk",
+            Choice::ReviewNotes => "Review notes as a markdown summary",
+            Choice::PolishedNotes => "Review notes as a summary polished by the AI",
+        }
+    }
+}
+
+/// Where the viewer saves an export of `filename`, or of the whole diff for `None`: in the
+/// current directory, named after the file.
+pub fn default_path(filename: Option<&str>, extension: &str) -> PathBuf {
+    let stem = filename
+        .and_then(|name| Path::new(name).file_name())
+        .map_or("lumen-diff".into(), |name| name.to_string_lossy());
+    PathBuf::from(format!("{stem}.{extension}"))
+}
+
+/// The file's changes as a git patch, or `None` for a binary file or one whose contents
+/// didn't change.
+pub fn file_patch(diff: &FileDiff) -> Option<String> {
+    if diff.binary.is_some() {
+        return None;
+    }
+    // A submodule is written the way git writes one, as the commit it points at
+    let (mode, old_content, new_content) = match &diff.submodule {
+        Some(change) => {
+            let (old, new) = submodule::patch_lines(change);
+            let (old, new) = (old.unwrap_or_default(), new.unwrap_or_default());
+            ("160000", Cow::Owned(old), Cow::Owned(new))
+        }
+        None => (
+            "100644",
+            Cow::Borrowed(diff.old_content.as_str()),
+            Cow::Borrowed(diff.new_content.as_str()),
+        ),
+    };
+    let (old_name, new_name, extra) = match &diff.status {
+        FileStatus::Added => (
+            diff.filename.clone(),
+            diff.filename.clone(),
+            format!("new file mode {mode}\n"),
+        ),
+        FileStatus::Deleted => (
+            diff.filename.clone(),
+            diff.filename.clone(),
+            format!("deleted file mode {mode}\n"),
+        ),
+        FileStatus::Renamed { from, to } => (
+            from.clone(),
+            to.clone(),
+            format!("rename from {from}\nrename to {to}\n"),
+        ),
+        FileStatus::Copied { from, to } => (
+            from.clone(),
+            to.clone(),
+            format!("copy from {from}\ncopy to {to}\n"),
+        ),
+        FileStatus::Modified => match &diff.submodule {
+            Some(change) => {
+                let sha = |commit: Option<&SubmoduleCommit>| {
+                    commit.map_or(String::new(), |commit| commit.sha.clone())
+                };
+                (
+                    diff.filename.clone(),
+                    diff.filename.clone(),
+                    format!(
+                        "index {}..{} {mode}\n",
+                        sha(change.old.as_ref()),
+                        sha(change.new.as_ref())
+                    ),
+                )
+            }
+            None => (diff.filename.clone(), diff.filename.clone(), String::new()),
+        },
+    };
+    let old_path = match diff.status {
+        FileStatus::Added => "/dev/null".to_string(),
+        _ => format!("a/{old_name}"),
+    };
+    let new_path = match diff.status {
+        FileStatus::Deleted => "/dev/null".to_string(),
+        _ => format!("b/{new_name}"),
+    };
+    let hunks = TextDiff::from_lines(old_content.as_ref(), new_content.as_ref())
+        .unified_diff()
+        .context_radius(CONTEXT)
+        .header(&old_path, &new_path)
+        .to_string();
+    // A rename or copy says something even without changes
+    if hunks.is_empty() && extra.is_empty() {
+        return None;
+    }
+    Some(format!(
+        "diff --git a/{old_name} b/{new_name}\n{extra}{hunks}"
+    ))
+}
+
+/// Patches of every file that has one, one after the other.
+pub fn diff_patch(diffs: &[FileDiff]) -> String {
+    diffs.iter().filter_map(file_patch).collect()
+}
+
+/// A color as CSS, with the named terminal colors taken from the xterm palette.
+fn css(color: Color) -> String {
+    let rgb = match color {
+        Color::Rgb(r, g, b) => (r, g, b),
+        Color::Black => (0, 0, 0),
+        Color::Red => (205, 49, 49),
+        Color::Green => (13, 188, 121),
+        Color::Yellow => (229, 229, 16),
+        Color::Blue => (36, 114, 200),
+        Color::Magenta => (188, 63, 188),
+        Color::Cyan => (17, 168, 205),
+        Color::Gray => (204, 204, 204),
+        Color::DarkGray => (118, 118, 118),
+        Color::LightRed => (241, 76, 76),
+        Color::LightGreen => (35, 209, 139),
+        Color::LightYellow => (245, 245, 67),
+        Color::LightBlue => (59, 142, 234),
+        Color::LightMagenta => (214, 112, 214),
+        Color::LightCyan => (41, 184, 219),
+        Color::White => (229, 229, 229),
+        Color::Reset | Color::Indexed(_) => return "inherit".to_string(),
+    };
+    format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn status_label(status: &FileStatus) -> String {
+    match status {
+        FileStatus::Added => "added".to_string(),
+        FileStatus::Modified => "modified".to_string(),
+        FileStatus::Deleted => "deleted".to_string(),
+        FileStatus::Renamed { from, .. } => format!("renamed from {}", escape(from)),
+        FileStatus::Copied { from, .. } => format!("copied from {}", escape(from)),
+    }
+}
+
+/// One side of a row: its line number and highlighted code.
+fn side_cells(line: &Option<(usize, String)>, class: &str, filename: &str, plain: bool) -> String {
+    let Some((num, text)) = line else {
+        return "<td class=\"num empty\"></td><td class=\"code empty\"></td>".to_string();
+    };
+    let code = if plain {
+        escape(text)
+    } else {
+        highlight_colored(text, filename)
+            .into_iter()
+            .map(|(piece, color)| {
+                format!(
+                    "<span style=\"color:{}\">{}</span>",
+                    css(color),
+                    escape(&piece)
+                )
+            })
+            .collect()
+    };
+    format!("<td class=\"num {class}\">{num}</td><td class=\"code {class}\">{code}</td>")
+}
+
+/// Rows within [`CONTEXT`] rows of a change.
+fn shown_rows(rows: &[DiffLine]) -> Vec<bool> {
+    let mut shown = vec![false; rows.len()];
+    for (i, row) in rows.iter().enumerate() {
+        if row.change_type != ChangeType::Equal {
+            let end = (i + CONTEXT + 1).min(rows.len());
+            shown[i.saturating_sub(CONTEXT)..end].fill(true);
+        }
+    }
+    shown
+}
+
+/// A standalone HTML page showing `diffs` side by side, with a list of the files linking to
+/// each one. Colors come from the viewer's current theme.
+pub fn html_report(title: &str, diffs: &[FileDiff], settings: &DiffViewSettings) -> String {
+    let t = theme::get();
+    let page_bg = match t.mode {
+        ThemeMode::Dark => "#1e1e24",
+        ThemeMode::Light => "#ffffff",
+    };
+    let mut files = String::new();
+    let mut sections = String::new();
+    for (index, diff) in diffs.iter().enumerate() {
+        let anchor = format!("file-{}", index + 1);
+        let name = escape(&diff.filename);
+        let status = status_label(&diff.status);
+        let rows = match diff.binary {
+            Some(_) => Vec::new(),
+            None => compute_side_by_side(
+                &diff.old_content,
+                &diff.new_content,
+                settings.tab_width_for(&diff.filename),
+                settings.ignore_whitespace,
+            ),
+        };
+        let changed: Vec<&DiffLine> = rows
+            .iter()
+            .filter(|row| row.change_type != ChangeType::Equal)
+            .collect();
+        let added = changed.iter().filter(|row| row.new_line.is_some()).count();
+        let removed = changed.iter().filter(|row| row.old_line.is_some()).count();
+        let _ = writeln!(
+            files,
+            "<li><a href=\"#{anchor}\">{name}</a> <span class=\"added\">+{added}</span> \
+             <span class=\"removed\">-{removed}</span></li>"
+        );
+
+        let _ = writeln!(
+            sections,
+            "<section id=\"{anchor}\"><h2><a href=\"#{anchor}\">{name}</a> \
+             <span class=\"status\">{status}</span></h2>"
+        );
+        if diff.binary.is_some() {
+            sections.push_str("<p class=\"note\">Binary file</p></section>\n");
+            continue;
+        }
+        if let Some(change) = &diff.submodule {
+            let lines: Vec<String> = change.describe().iter().map(|l| escape(l)).collect();
+            let _ = writeln!(
+                sections,
+                "<p class=\"note\">{}</p></section>",
+                lines.join("<br>")
+            );
+            continue;
+        }
+        // Large files are left plain, as in the viewer
+        let plain = settings.is_large(diff);
+        let shown = shown_rows(&rows);
+        sections.push_str("<table>\n");
+        let gap = "<tr class=\"gap\"><td colspan=\"4\">⋯</td></tr>\n";
+        let mut skipped = false;
+        for (row, show) in rows.iter().zip(&shown) {
+            if !show {
+                skipped = true;
+                continue;
+            }
+            if skipped {
+                sections.push_str(gap);
+                skipped = false;
+            }
+            let (old_class, new_class) = match row.change_type {
+                ChangeType::Equal => ("context", "context"),
+                _ => ("deleted", "added"),
+            };
+            let _ = writeln!(
+                sections,
+                "<tr>{}{}</tr>",
+                side_cells(&row.old_line, old_class, &diff.filename, plain),
+                side_cells(&row.new_line, new_class, &diff.filename, plain)
+            );
+        }
+        if skipped {
+            sections.push_str(gap);
+        }
+        sections.push_str("</table></section>\n");
+    }
+
+    let title = escape(title);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ background: {page_bg}; color: {text}; font-family: sans-serif; margin: 2em; }}
+a {{ color: {link}; text-decoration: none; }}
+h2 {{ font-size: 1em; font-family: monospace; border-bottom: 1px solid {border}; padding-bottom: 0.3em; margin-top: 2em; }}
+.status, .note {{ color: {muted}; font-weight: normal; }}
+.added {{ color: {stats_added}; }}
+.removed {{ color: {stats_removed}; }}
+table {{ border-collapse: collapse; width: 100%; table-layout: fixed; font-family: monospace; font-size: 0.85em; }}
+td.num {{ width: 4em; text-align: right; padding-right: 0.6em; color: {muted}; user-select: none; }}
+td.code {{ white-space: pre-wrap; word-break: break-all; color: {text}; }}
+td.code.deleted {{ background: {deleted_bg}; }}
+td.code.added {{ background: {added_bg}; }}
+td.num.deleted {{ background: {deleted_gutter_bg}; }}
+td.num.added {{ background: {added_gutter_bg}; }}
+tr.gap td {{ color: {muted}; text-align: center; background: {context_bg}; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<ul>
+{files}</ul>
+{sections}</body>
+</html>
+"#,
+        text = css(t.syntax.default_text),
+        link = css(t.ui.highlight),
+        border = css(t.ui.border_unfocused),
+        muted = css(t.ui.text_muted),
+        stats_added = css(t.ui.stats_added),
+        stats_removed = css(t.ui.stats_removed),
+        deleted_bg = css(t.diff.deleted_bg),
+        added_bg = css(t.diff.added_bg),
+        deleted_gutter_bg = css(t.diff.deleted_gutter_bg),
+        added_gutter_bg = css(t.diff.added_gutter_bg),
+        context_bg = css(t.diff.context_bg),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::types::SubmoduleChange;
+
+    fn file(filename: &str, old: &str, new: &str, status: FileStatus) -> FileDiff {
+        FileDiff {
+            filename: filename.to_string(),
+            old_content: old.to_string(),
+            new_content: new.to_string(),
+            status,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_file_patch_headers() {
+        let modified = file("a.rs", "x\ny\n", "x\nz\n", FileStatus::Modified);
+        assert_eq!(
+            file_patch(&modified).unwrap(),
+            "diff --git a/a.rs b/a.rs\n--- a/a.rs\n+++ b/a.rs\n@@ -1,2 +1,2 @@\n x\n-y\n+z\n"
+        );
+        let added = file("new.rs", "", "hi\n", FileStatus::Added);
+        let patch = file_patch(&added).unwrap();
+        assert!(patch.contains("new file mode 100644\n--- /dev/null\n+++ b/new.rs\n"));
+        let renamed = FileStatus::Renamed {
+            from: "old.rs".to_string(),
+            to: "new.rs".to_string(),
+        };
+        let moved = file("new.rs", "same\n", "same\n", renamed);
+        assert_eq!(
+            file_patch(&moved).unwrap(),
+            "diff --git a/old.rs b/new.rs\nrename from old.rs\nrename to new.rs\n"
+        );
+        let unchanged = file("a.rs", "x\n", "x\n", FileStatus::Modified);
+        assert!(file_patch(&unchanged).is_none());
+    }
+
+    #[test]
+    fn test_submodule_patch_records_commits() {
+        let commit = |sha: &str| SubmoduleCommit {
+            sha: sha.to_string(),
+            subject: None,
+        };
+        let mut bumped = file("vendor/lib", "", "", FileStatus::Modified);
+        bumped.submodule = Some(SubmoduleChange {
+            old: Some(commit("6df2f32")),
+            new: Some(commit("e4c3baa")),
+            behind_ahead: None,
+        });
+        assert_eq!(
+            file_patch(&bumped).unwrap(),
+            "diff --git a/vendor/lib b/vendor/lib\nindex 6df2f32..e4c3baa 160000\n\
+             --- a/vendor/lib\n+++ b/vendor/lib\n@@ -1 +1 @@\n\
+             -Subproject commit 6df2f32\n+Subproject commit e4c3baa\n"
+        );
+    }
+
+    #[test]
+    fn test_report_links_files_and_escapes_code() {
+        let diffs = vec![
+            file("a.html", "<p>\n", "<p class=\"x\">\n", FileStatus::Modified),
+            file("b.txt", "", "b\n", FileStatus::Added),
+        ];
+        let html = html_report("main..feature", &diffs, &DiffViewSettings::default());
+        assert!(html.contains("<a href=\"#file-1\">a.html</a>"));
+        assert!(html.contains("<section id=\"file-2\">"));
+        assert!(html.contains("&lt;"));
+        assert!(!html.contains("<p class=\"x\">"));
+    }
+
+    #[test]
+    fn test_report_leaves_out_unchanged_stretches() {
+        let old: String = (1..=20).map(|n| format!("line {n}\n")).collect();
+        let new = old.replace("line 10\n", "line ten\n");
+        let diffs = vec![file("a.txt", &old, &new, FileStatus::Modified)];
+        let html = html_report("HEAD", &diffs, &DiffViewSettings::default());
+        assert!(html.contains(">7<") && html.contains(">13<"));
+        assert!(!html.contains(">6<") && !html.contains(">14<"));
+        assert_eq!(html.matches("class=\"gap\"").count(), 2);
+    }
+
+    #[test]
+    fn test_default_path_is_named_after_the_file() {
+        assert_eq!(
+            default_path(Some("src/main.rs"), "patch"),
+            PathBuf::from("main.rs.patch")
+        );
+        assert_eq!(default_path(None, "html"), PathBuf::from("lumen-diff.html"));
+    }
+}
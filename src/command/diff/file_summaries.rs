@@ -0,0 +1,232 @@
+//! One-line AI summaries of the changed files, shown with their names in the sidebar so a
+//! large diff can be triaged without opening every file.
+//!
+//! Summaries are asked for lazily, a few files at a time, as files come into view in the
+//! sidebar. Answers are cached on disk by the file's diff, so opening the same changes again
+//! costs nothing, and a file that changes while watching is summarized again.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use similar::TextDiff;
+
+use crate::provider::LumenProvider;
+
+use super::types::FileDiff;
+use super::width::floor_char_boundary;
+
+/// Files asked about at once
+const BATCH: usize = 4;
+/// Longest diff sent for one file; the rest is left out
+const MAX_DIFF_BYTES: usize = 20_000;
+
+type Answer = (String, Result<String, String>);
+
+pub struct FileSummaries {
+    provider: Arc<LumenProvider>,
+    summaries: HashMap<String, String>,
+    /// Files not to ask about again until they change: those the provider failed on, and
+    /// those without a textual change, like binary files
+    skipped: HashSet<String>,
+    /// Files asked about in the running batch. One that changes meanwhile is taken out, so
+    /// its outdated summary is dropped when it arrives.
+    requested: HashSet<String>,
+    job: Option<Receiver<Answer>>,
+}
+
+impl FileSummaries {
+    pub fn new(provider: Arc<LumenProvider>) -> Self {
+        FileSummaries {
+            provider,
+            summaries: HashMap::new(),
+            skipped: HashSet::new(),
+            requested: HashSet::new(),
+            job: None,
+        }
+    }
+
+    pub fn get(&self, filename: &str) -> Option<&str> {
+        self.summaries.get(filename).map(String::as_str)
+    }
+
+    /// Forget the summaries of files that are gone or changed since a reload.
+    pub fn update(&mut self, file_diffs: &[FileDiff], changed_files: Option<&HashSet<String>>) {
+        let current: HashSet<&str> = file_diffs.iter().map(|d| d.filename.as_str()).collect();
+        let keep = |filename: &String| {
+            current.contains(filename.as_str())
+                && !changed_files.is_some_and(|changed| changed.contains(filename))
+        };
+        self.summaries.retain(|filename, _| keep(filename));
+        self.skipped.retain(keep);
+        self.requested.retain(keep);
+    }
+
+    /// Collect finished summaries, and ask about the next files of `in_view` (indices into
+    /// `file_diffs`) once the running batch is done. Meant to be called on every tick of the
+    /// event loop; returns a message for the status bar when a summary fails.
+    pub fn poll(&mut self, file_diffs: &[FileDiff], in_view: &[usize]) -> Option<String> {
+        let mut status = None;
+        if let Some(job) = &self.job {
+            loop {
+                match job.try_recv() {
+                    Ok((filename, answer)) => {
+                        if !self.requested.remove(&filename) {
+                            continue;
+                        }
+                        match answer {
+                            Ok(summary) => {
+                                self.summaries.insert(filename, one_line(&summary));
+                            }
+                            Err(e) => {
+                                status = Some(format!("AI summary of {filename} failed: {e}"));
+                                self.skipped.insert(filename);
+                            }
+                        }
+                    }
+                    Err(TryRecvError::Empty) => return status,
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+            self.job = None;
+            self.requested.clear();
+        }
+
+        let mut batch = Vec::new();
+        for diff in in_view.iter().filter_map(|&i| file_diffs.get(i)) {
+            if batch.len() == BATCH {
+                break;
+            }
+            let filename = &diff.filename;
+            if self.summaries.contains_key(filename) || self.skipped.contains(filename) {
+                continue;
+            }
+            match file_patch(diff) {
+                Some(patch) => batch.push((filename.clone(), patch)),
+                None => {
+                    self.skipped.insert(filename.clone());
+                }
+            }
+        }
+        if !batch.is_empty() {
+            self.requested = batch.iter().map(|(filename, _)| filename.clone()).collect();
+            self.job = Some(summarize_in_background(Arc::clone(&self.provider), batch));
+        }
+        status
+    }
+}
+
+/// Ask about each file of `batch` at the same time, sending each answer as it arrives.
+fn summarize_in_background(
+    provider: Arc<LumenProvider>,
+    batch: Vec<(String, String)>,
+) -> Receiver<Answer> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let runtime = match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => runtime,
+            Err(e) => {
+                for (filename, _) in batch {
+                    let _ = tx.send((filename, Err(e.to_string())));
+                }
+                return;
+            }
+        };
+        runtime.block_on(async {
+            let mut requests: FuturesUnordered<_> = batch
+                .iter()
+                .map(|(filename, patch)| {
+                    let provider = &provider;
+                    async move {
+                        let answer = provider
+                            .summarize_file(filename, patch)
+                            .await
+                            .map_err(|e| e.to_string());
+                        (filename.clone(), answer)
+                    }
+                })
+                .collect();
+            while let Some(answer) = requests.next().await {
+                let _ = tx.send(answer);
+            }
+        });
+    });
+    rx
+}
+
+/// The file's changes as a unified diff, cut off at [`MAX_DIFF_BYTES`], or `None` when its
+/// text didn't change.
+fn file_patch(diff: &FileDiff) -> Option<String> {
+    let mut patch = TextDiff::from_lines(&diff.old_content, &diff.new_content)
+        .unified_diff()
+        .header(
+            &format!("a/{}", diff.filename),
+            &format!("b/{}", diff.filename),
+        )
+        .to_string();
+    if patch.trim().is_empty() {
+        return None;
+    }
+    if patch.len() > MAX_DIFF_BYTES {
+        patch.truncate(floor_char_boundary(&patch, MAX_DIFF_BYTES));
+        patch.push_str("\n[rest of the diff left out]\n");
+    }
+    Some(patch)
+}
+
+/// The first line of an answer, without the quotes or list marker a model may add.
+fn one_line(answer: &str) -> String {
+    let line = answer
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default();
+    line.trim_start_matches("- ")
+        .trim_matches(|c| c == '"' || c == '`')
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::types::FileStatus;
+
+    fn file(filename: &str, old: &str, new: &str) -> FileDiff {
+        FileDiff {
+            filename: filename.to_string(),
+            old_content: old.to_string(),
+            new_content: new.to_string(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_one_line() {
+        assert_eq!(
+            one_line("\n- \"Adds retry with backoff to the HTTP client\"\n\nMore detail."),
+            "Adds retry with backoff to the HTTP client"
+        );
+        assert_eq!(one_line(""), "");
+    }
+
+    #[test]
+    fn test_file_patch() {
+        let patch = file_patch(&file("src/a.rs", "one\n", "two\n")).unwrap();
+        assert!(patch.contains("+++ b/src/a.rs"));
+        assert!(patch.contains("-one\n+two"));
+        assert!(file_patch(&file("logo.png", "", "")).is_none());
+
+        let long = "é".repeat(MAX_DIFF_BYTES);
+        let patch = file_patch(&file("big.txt", "", &long)).unwrap();
+        assert!(patch.len() < MAX_DIFF_BYTES + 100);
+        assert!(patch.ends_with("[rest of the diff left out]\n"));
+    }
+}
@@ -0,0 +1,201 @@
+//! Which files the diff viewer shows: globs to include or (with `!`) exclude, and statuses.
+//!
+//! Globs follow `.gitignore`: `*` stays within a directory, `**` crosses them, a pattern
+//! without a `/` matches a file or directory name anywhere, and matching a directory matches
+//! everything in it. Statuses are written `:added`, `:modified`, `:deleted`, `:renamed` and
+//! `:copied`, or by their first letter.
+
+use regex::Regex;
+
+use super::types::{FileDiff, FileStatus};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Status {
+    Added,
+    Modified,
+    Deleted,
+    Renamed,
+    Copied,
+}
+
+impl Status {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "a" | "added" => Some(Status::Added),
+            "m" | "modified" => Some(Status::Modified),
+            "d" | "deleted" => Some(Status::Deleted),
+            "r" | "renamed" => Some(Status::Renamed),
+            "c" | "copied" => Some(Status::Copied),
+            _ => None,
+        }
+    }
+
+    fn of(status: &FileStatus) -> Self {
+        match status {
+            FileStatus::Added => Status::Added,
+            FileStatus::Modified => Status::Modified,
+            FileStatus::Deleted => Status::Deleted,
+            FileStatus::Renamed { .. } => Status::Renamed,
+            FileStatus::Copied { .. } => Status::Copied,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct FileFilter {
+    /// The terms as written, to edit them again
+    pub text: String,
+    statuses: Vec<Status>,
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+impl FileFilter {
+    /// Parse whitespace-separated terms, like `*.rs !**/generated/** :added`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::from_terms(text.split_whitespace())
+    }
+
+    /// The terms of `--file`, one per value.
+    pub fn from_terms<'a>(terms: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut filter = FileFilter::default();
+        let mut text = Vec::new();
+        for term in terms {
+            text.push(term);
+            if let Some(name) = term.strip_prefix(':') {
+                let status = Status::parse(name).ok_or_else(|| {
+                    format!("unknown status `{term}`, expected :added, :modified, :deleted, :renamed or :copied")
+                })?;
+                filter.statuses.push(status);
+            } else if let Some(glob) = term.strip_prefix('!') {
+                filter.exclude.push(glob_regex(glob)?);
+            } else {
+                filter.include.push(glob_regex(term)?);
+            }
+        }
+        filter.text = text.join(" ");
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statuses.is_empty() && self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether it picks files by status, which is only known once they're read.
+    pub fn has_statuses(&self) -> bool {
+        !self.statuses.is_empty()
+    }
+
+    /// Whether `path` passes the globs, whatever its status.
+    pub fn matches_path(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|glob| glob.is_match(path)))
+            && !self.exclude.iter().any(|glob| glob.is_match(path))
+    }
+
+    pub fn matches(&self, diff: &FileDiff) -> bool {
+        (self.statuses.is_empty() || self.statuses.contains(&Status::of(&diff.status)))
+            && self.matches_path(&diff.filename)
+    }
+}
+
+/// The regex a glob matches paths with.
+fn glob_regex(glob: &str) -> Result<Regex, String> {
+    let trimmed = glob.trim_end_matches('/');
+    if trimmed.is_empty() {
+        return Err(format!("empty pattern `{glob}`"));
+    }
+    // Anchored to the root when it has a slash, matching a name at any depth otherwise
+    let (anchored, pattern) = match trimmed.strip_prefix('/') {
+        Some(pattern) => (true, pattern),
+        None => (trimmed.contains('/'), trimmed),
+    };
+
+    let mut source = String::from(if anchored { "^" } else { "^(?:.*/)?" });
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    source.push_str("(?:.*/)?");
+                } else {
+                    source.push_str(".*");
+                }
+            }
+            '*' => source.push_str("[^/]*"),
+            '?' => source.push_str("[^/]"),
+            c => source.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    source.push_str("(?:/.*)?$");
+    Regex::new(&source).map_err(|e| format!("invalid pattern `{glob}`: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(filter: &str) -> Vec<&'static str> {
+        let filter = FileFilter::parse(filter).unwrap();
+        [
+            "main.rs",
+            "src/lib.rs",
+            "src/generated/api.rs",
+            "src/generated.txt",
+            "docs/guide.md",
+            "web/src/app.ts",
+        ]
+        .into_iter()
+        .filter(|path| filter.matches_path(path))
+        .collect()
+    }
+
+    #[test]
+    fn test_globs() {
+        assert_eq!(
+            paths("*.rs"),
+            vec!["main.rs", "src/lib.rs", "src/generated/api.rs"]
+        );
+        assert_eq!(
+            paths("*.rs !**/generated/**"),
+            vec!["main.rs", "src/lib.rs"]
+        );
+        assert_eq!(
+            paths("src"),
+            vec![
+                "src/lib.rs",
+                "src/generated/api.rs",
+                "src/generated.txt",
+                "web/src/app.ts"
+            ]
+        );
+        assert_eq!(paths("/src/*.rs"), vec!["src/lib.rs"]);
+        assert_eq!(paths("docs/guide.md"), vec!["docs/guide.md"]);
+        assert_eq!(
+            paths("web/**/*.ts ma?n.rs"),
+            vec!["main.rs", "web/src/app.ts"]
+        );
+        assert_eq!(paths("").len(), 6);
+    }
+
+    #[test]
+    fn test_statuses() {
+        let diff = |filename: &str, status| FileDiff {
+            filename: filename.to_string(),
+            old_content: String::new(),
+            new_content: String::new(),
+            status,
+            binary: None,
+            submodule: None,
+        };
+        let filter = FileFilter::parse(":a :deleted *.rs").unwrap();
+        assert!(filter.matches(&diff("new.rs", FileStatus::Added)));
+        assert!(filter.matches(&diff("old.rs", FileStatus::Deleted)));
+        assert!(!filter.matches(&diff("lib.rs", FileStatus::Modified)));
+        assert!(!filter.matches(&diff("new.md", FileStatus::Added)));
+
+        assert!(FileFilter::parse(":staged").is_err());
+        assert!(FileFilter::parse("!").is_err());
+    }
+}
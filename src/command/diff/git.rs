@@ -1,14 +1,23 @@
 use std::fs;
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
 
-use super::types::{FileDiff, FileStatus};
+use super::encoding::{decode, decode_diff, decode_working_tree, is_binary};
+use super::submodule;
+use super::types::{BinaryChange, FileDiff, FileStatus};
 use super::{DiffOptions, PrInfo};
+use crate::command::snapshot;
 use crate::commit_reference::CommitReference;
+use crate::logging;
 
 pub fn get_current_branch() -> String {
-    let output = Command::new("git")
-        .args(["rev-parse", "--abbrev-ref", "HEAD"])
-        .output();
+    #[cfg(feature = "libgit2")]
+    if let Some(branch) = crate::libgit::current_branch() {
+        return branch;
+    }
+    let output = logging::output(Command::new("git").args(["rev-parse", "--abbrev-ref", "HEAD"]));
 
     match output {
         Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).trim().to_string(),
@@ -24,175 +33,469 @@ pub enum DiffRefs {
     Single(String),
     /// Range between two refs
     Range { from: String, to: String },
+    /// Working tree vs a snapshot commit
+    Snapshot(String),
 }
 
 impl DiffRefs {
     pub fn from_options(options: &DiffOptions) -> Self {
-        match &options.reference {
+        if let Some(commit) = &options.snapshot {
+            return DiffRefs::Snapshot(commit.clone());
+        }
+        Self::from_reference(options.reference.as_ref())
+    }
+
+    /// Resolve a commit reference, `None` meaning uncommitted changes.
+    pub fn from_reference(reference: Option<&CommitReference>) -> Self {
+        match reference {
             None => DiffRefs::WorkingTree,
             Some(CommitReference::Single(sha)) => DiffRefs::Single(sha.clone()),
             Some(CommitReference::Range { from, to }) => DiffRefs::Range {
                 from: from.clone(),
                 to: to.clone(),
             },
-            Some(CommitReference::TripleDots { from, to }) => {
-                // Get merge-base for triple dots
-                let output = Command::new("git")
-                    .args(["merge-base", from, to])
-                    .output()
-                    .expect("Failed to run git merge-base");
-                let merge_base = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                DiffRefs::Range {
-                    from: merge_base,
-                    to: to.clone(),
-                }
-            }
+            Some(CommitReference::TripleDots { from, to }) => DiffRefs::Range {
+                // Without common history there's no merge base, so compare with `from` itself
+                from: merge_base(from, to).unwrap_or_else(|| from.clone()),
+                to: to.clone(),
+            },
         }
     }
 }
 
-/// Get the list of files changed
-pub fn get_changed_files(options: &DiffOptions) -> Vec<String> {
-    let refs = DiffRefs::from_options(options);
+fn merge_base(from: &str, to: &str) -> Option<String> {
+    #[cfg(feature = "libgit2")]
+    if let Some(base) = crate::libgit::merge_base(from, to) {
+        return base;
+    }
+    git_lines(&["merge-base", from, to]).into_iter().next()
+}
 
-    let files: Vec<String> = match refs {
-        DiffRefs::Single(sha) => {
-            let output = Command::new("git")
-                .args(["diff-tree", "--no-commit-id", "--name-only", "-r", &sha])
-                .output()
-                .expect("Failed to run git");
-            String::from_utf8_lossy(&output.stdout)
-                .lines()
-                .filter(|s| !s.is_empty())
-                .map(String::from)
-                .collect()
-        }
-        DiffRefs::Range { from, to } => {
-            let output = Command::new("git")
-                .args(["diff", "--name-only", &from, &to])
-                .output()
-                .expect("Failed to run git");
-            String::from_utf8_lossy(&output.stdout)
-                .lines()
-                .filter(|s| !s.is_empty())
-                .map(String::from)
-                .collect()
+/// The non-empty lines `git` prints for `args`, none when it fails.
+fn git_lines(args: &[&str]) -> Vec<String> {
+    match logging::output(Command::new("git").args(args)) {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+impl DiffRefs {
+    /// Revision holding the old side of the diff.
+    pub fn old_revision(&self) -> String {
+        match self {
+            DiffRefs::WorkingTree => "HEAD".to_string(),
+            DiffRefs::Single(sha) => format!("{sha}^"),
+            DiffRefs::Range { from, .. } => from.clone(),
+            DiffRefs::Snapshot(commit) => commit.clone(),
         }
-        DiffRefs::WorkingTree => {
-            // Get unstaged changes (tracked files modified in working tree)
-            let unstaged = Command::new("git")
-                .args(["diff", "--name-only", "HEAD"])
-                .output()
-                .expect("Failed to run git");
-
-            // Get staged changes (including newly added files)
-            let staged = Command::new("git")
-                .args(["diff", "--cached", "--name-only"])
-                .output()
-                .expect("Failed to run git");
-
-            // Get untracked files (new files not yet added to git)
-            let untracked = Command::new("git")
-                .args(["ls-files", "--others", "--exclude-standard"])
-                .output()
-                .expect("Failed to run git");
-
-            let mut all_files: std::collections::HashSet<String> = std::collections::HashSet::new();
-
-            for line in String::from_utf8_lossy(&unstaged.stdout).lines() {
-                if !line.is_empty() {
-                    all_files.insert(line.to_string());
-                }
-            }
-            for line in String::from_utf8_lossy(&staged.stdout).lines() {
-                if !line.is_empty() {
-                    all_files.insert(line.to_string());
-                }
-            }
-            for line in String::from_utf8_lossy(&untracked.stdout).lines() {
-                if !line.is_empty() {
-                    all_files.insert(line.to_string());
-                }
-            }
+    }
 
-            all_files.into_iter().collect()
+    /// Revision holding the new side, or `None` when it's the uncommitted working tree.
+    pub fn new_revision(&self) -> Option<String> {
+        match self {
+            DiffRefs::WorkingTree | DiffRefs::Snapshot(_) => None,
+            DiffRefs::Single(sha) => Some(sha.clone()),
+            DiffRefs::Range { to, .. } => Some(to.clone()),
         }
-    };
+    }
+}
+
+/// Commits that shaped lines `start..=end` of `filename` as of `revision`, newest first.
+pub fn line_history(
+    revision: &str,
+    filename: &str,
+    start: usize,
+    end: usize,
+) -> Result<String, String> {
+    let output = logging::output(Command::new("git").args([
+        "log",
+        &format!("-L{start},{end}:{filename}"),
+        "--no-patch",
+        "--date=short",
+        "--format=%h %ad %an%n    %s",
+        "-n",
+        "30",
+        revision,
+    ]))
+    .map_err(|e| format!("Failed to run git log: {e}"))?;
 
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .trim_end()
+            .to_string())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Get the list of files changed
+pub fn get_changed_files(options: &DiffOptions) -> Vec<String> {
+    let files = changed_paths(&DiffRefs::from_options(options));
     if let Some(ref filter) = options.file {
-        files.into_iter().filter(|f| filter.contains(f)).collect()
+        files
+            .into_iter()
+            .filter(|f| filter.matches_path(f))
+            .collect()
     } else {
         files
     }
 }
 
-/// Get content of a file at the "old" side of the diff
-pub fn get_old_content(filename: &str, refs: &DiffRefs) -> String {
-    let ref_spec = match refs {
-        DiffRefs::Single(sha) => format!("{}^:{}", sha, filename),
-        DiffRefs::Range { from, .. } => format!("{}:{}", from, filename),
-        DiffRefs::WorkingTree => format!("HEAD:{}", filename),
-    };
-    let output = Command::new("git").args(["show", &ref_spec]).output();
+fn changed_paths(refs: &DiffRefs) -> Vec<String> {
+    // Untracked files count for a snapshot too, so compare against a tree of the whole
+    // working tree
+    let snapshot_tree = || snapshot::working_tree().unwrap_or_else(|_| "HEAD".to_string());
 
-    match output {
-        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
-        _ => String::new(),
+    #[cfg(feature = "libgit2")]
+    {
+        use crate::libgit;
+        let paths = match refs {
+            DiffRefs::Single(sha) => libgit::changed_between(&format!("{sha}^"), sha),
+            DiffRefs::Range { from, to } => libgit::changed_between(from, to),
+            DiffRefs::Snapshot(commit) => libgit::changed_between(commit, &snapshot_tree()),
+            DiffRefs::WorkingTree => libgit::working_tree_changes(),
+        };
+        if let Some(paths) = paths {
+            return paths;
+        }
+    }
+
+    match refs {
+        DiffRefs::Single(sha) => {
+            git_lines(&["diff-tree", "--no-commit-id", "--name-only", "-r", sha])
+        }
+        DiffRefs::Range { from, to } => git_lines(&["diff", "--name-only", from, to]),
+        DiffRefs::Snapshot(commit) => git_lines(&["diff", "--name-only", commit, &snapshot_tree()]),
+        DiffRefs::WorkingTree => {
+            // Unstaged changes, staged ones (including newly added files) and untracked files
+            let mut all_files = std::collections::HashSet::new();
+            all_files.extend(git_lines(&["diff", "--name-only", "HEAD"]));
+            all_files.extend(git_lines(&["diff", "--cached", "--name-only"]));
+            all_files.extend(git_lines(&["ls-files", "--others", "--exclude-standard"]));
+            all_files.into_iter().collect()
+        }
     }
 }
 
+/// Get content of a file at the "old" side of the diff
+pub fn get_old_content(filename: &str, refs: &DiffRefs) -> String {
+    read_old(filename, refs)
+        .map(|bytes| decode(&bytes, None))
+        .unwrap_or_default()
+}
+
 /// Get content of a file at the "new" side of the diff
 pub fn get_new_content(filename: &str, refs: &DiffRefs) -> String {
+    read_new(filename, refs)
+        .map(|bytes| decode_new(filename, refs, &bytes))
+        .unwrap_or_default()
+}
+
+/// Raw bytes of a file at the "old" side of the diff, `None` when it isn't there
+fn read_old(filename: &str, refs: &DiffRefs) -> Option<Vec<u8>> {
+    read_blob(&refs.old_revision(), filename)
+}
+
+/// Raw bytes of a file at the "new" side of the diff, `None` when it isn't there
+fn read_new(filename: &str, refs: &DiffRefs) -> Option<Vec<u8>> {
+    match refs.new_revision() {
+        Some(revision) => read_blob(&revision, filename),
+        // Read from working tree
+        None => fs::read(filename).ok(),
+    }
+}
+
+fn decode_new(filename: &str, refs: &DiffRefs, bytes: &[u8]) -> String {
     match refs {
-        DiffRefs::Single(sha) => {
-            let output = Command::new("git")
-                .args(["show", &format!("{}:{}", sha, filename)])
-                .output();
+        DiffRefs::WorkingTree | DiffRefs::Snapshot(_) => decode_working_tree(filename, bytes),
+        DiffRefs::Single(_) | DiffRefs::Range { .. } => decode(bytes, None),
+    }
+}
 
-            match output {
-                Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
-                _ => String::new(),
-            }
-        }
-        DiffRefs::Range { to, .. } => {
-            let output = Command::new("git")
-                .args(["show", &format!("{}:{}", to, filename)])
-                .output();
-
-            match output {
-                Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout).to_string(),
-                _ => String::new(),
-            }
+fn read_blob(revision: &str, filename: &str) -> Option<Vec<u8>> {
+    #[cfg(feature = "libgit2")]
+    if let Some(blob) = crate::libgit::blob(revision, filename) {
+        return blob;
+    }
+    let output =
+        logging::output(Command::new("git").args(["show", &format!("{revision}:{filename}")]));
+    match output {
+        Ok(o) if o.status.success() => Some(o.stdout),
+        _ => None,
+    }
+}
+
+/// Renamed and copied files between the two sides of `refs`, found by git's similarity
+/// detection. Pure renames never show up as a delete plus an add this way.
+fn find_moves(refs: &DiffRefs) -> Vec<FileStatus> {
+    let detect = ["-M", "-C", "--name-status", "-z"];
+    let output = match refs {
+        DiffRefs::Single(sha) => logging::output(
+            Command::new("git")
+                .args(["diff-tree", "--no-commit-id", "-r"])
+                .args(detect)
+                .arg(sha),
+        ),
+        DiffRefs::Range { from, to } => logging::output(
+            Command::new("git")
+                .arg("diff")
+                .args(detect)
+                .args([from, to]),
+        ),
+        DiffRefs::Snapshot(commit) => {
+            let tree = snapshot::working_tree().unwrap_or_else(|_| "HEAD".to_string());
+            logging::output(
+                Command::new("git")
+                    .arg("diff")
+                    .args(detect)
+                    .args([commit, &tree]),
+            )
         }
         DiffRefs::WorkingTree => {
-            // Read from working tree
-            fs::read_to_string(filename).unwrap_or_default()
+            logging::output(Command::new("git").arg("diff").args(detect).arg("HEAD"))
         }
+    };
+    match output {
+        Ok(o) if o.status.success() => parse_moves(&String::from_utf8_lossy(&o.stdout)),
+        _ => Vec::new(),
     }
 }
 
+/// The renames and copies in `git diff --name-status -z` output.
+fn parse_moves(name_status: &str) -> Vec<FileStatus> {
+    let mut fields = name_status.split('\0').filter(|field| !field.is_empty());
+    let mut moves = Vec::new();
+    while let Some(status) = fields.next() {
+        // Renames and copies carry a similarity score and two paths, the rest one path
+        if !status.starts_with(['R', 'C']) {
+            fields.next();
+            continue;
+        }
+        let (Some(from), Some(to)) = (fields.next(), fields.next()) else {
+            break;
+        };
+        let (from, to) = (from.to_string(), to.to_string());
+        moves.push(if status.starts_with('R') {
+            FileStatus::Renamed { from, to }
+        } else {
+            FileStatus::Copied { from, to }
+        });
+    }
+    moves
+}
+
+/// The most files read at once
+const READ_THREADS: usize = 8;
+
+/// A changed file, before its contents are read
+pub struct ChangedFile {
+    filename: String,
+    /// Where it was renamed or copied from, if it was
+    moved: Option<FileStatus>,
+}
+
+impl ChangedFile {
+    /// What stands in for the file's diff until it's read
+    fn placeholder(&self) -> FileDiff {
+        FileDiff {
+            filename: self.filename.clone(),
+            old_content: String::new(),
+            new_content: String::new(),
+            status: self.moved.clone().unwrap_or(FileStatus::Modified),
+            binary: None,
+            submodule: None,
+        }
+    }
+}
+
+/// The files `options` changes between `refs`, with the renames and copies among them.
+fn changed_files(options: &DiffOptions, refs: &DiffRefs) -> Vec<ChangedFile> {
+    let mut files = get_changed_files(options);
+    let moved_to = |status: &FileStatus| match status {
+        FileStatus::Renamed { to, .. } | FileStatus::Copied { to, .. } => Some(to.clone()),
+        _ => None,
+    };
+    let moves: Vec<FileStatus> = find_moves(refs)
+        .into_iter()
+        .filter(|status| moved_to(status).is_some_and(|to| files.contains(&to)))
+        .collect();
+    // A renamed file is shown once, under its new name
+    files.retain(|file| {
+        !moves
+            .iter()
+            .any(|status| matches!(status, FileStatus::Renamed { from, .. } if from == file))
+    });
+
+    files
+        .into_iter()
+        .map(|filename| {
+            let moved = moves
+                .iter()
+                .find(|status| moved_to(status).as_ref() == Some(&filename))
+                .cloned();
+            ChangedFile { filename, moved }
+        })
+        .collect()
+}
+
 pub fn load_file_diffs(options: &DiffOptions) -> Vec<FileDiff> {
     let refs = DiffRefs::from_options(options);
-    get_changed_files(options)
+    let files = changed_files(options, &refs);
+    let mut diffs: Vec<Option<FileDiff>> = vec![None; files.len()];
+    read_file_diffs(&refs, &files, |idx, diff| diffs[idx] = Some(diff));
+    diffs
         .into_iter()
-        .map(|filename| {
-            let old_content = get_old_content(&filename, &refs);
-            let new_content = get_new_content(&filename, &refs);
-            let status = if old_content.is_empty() && !new_content.is_empty() {
-                FileStatus::Added
-            } else if !old_content.is_empty() && new_content.is_empty() {
-                FileStatus::Deleted
-            } else {
-                FileStatus::Modified
+        .flatten()
+        // Statuses are only known once the diff is read
+        .filter(|diff| {
+            options
+                .file
+                .as_ref()
+                .is_none_or(|filter| filter.matches(diff))
+        })
+        .collect()
+}
+
+/// Placeholders for the files `options` changes, and the channel their diffs arrive on with
+/// their indexes as they're read in the background. Status filters aren't applied.
+pub fn load_file_diffs_async(
+    options: &DiffOptions,
+) -> (Vec<FileDiff>, Receiver<(usize, FileDiff)>) {
+    let refs = DiffRefs::from_options(options);
+    let files = changed_files(options, &refs);
+    let placeholders = files.iter().map(ChangedFile::placeholder).collect();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        read_file_diffs(&refs, &files, |idx, diff| {
+            let _ = tx.send((idx, diff));
+        });
+    });
+    (placeholders, rx)
+}
+
+/// Read `files` a few at a time, since reading one is mostly waiting on git, and hand each
+/// diff to `done` with its index as it's read.
+fn read_file_diffs(refs: &DiffRefs, files: &[ChangedFile], mut done: impl FnMut(usize, FileDiff)) {
+    let threads = thread::available_parallelism()
+        .map_or(4, |n| n.get())
+        .clamp(1, READ_THREADS)
+        .min(files.len());
+    let next = AtomicUsize::new(0);
+    let (tx, rx) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(file) = files.get(idx) else {
+                    break;
+                };
+                let diff = file_diff(refs, file.filename.clone(), file.moved.as_ref());
+                if tx.send((idx, diff)).is_err() {
+                    break;
+                }
+            });
+        }
+        // The workers hold the only senders left, so this ends once they're done
+        drop(tx);
+        for (idx, diff) in rx {
+            done(idx, diff);
+        }
+    });
+}
+
+/// `filename` as it changed in commit `sha`, read from `status`'s source for a move.
+pub fn commit_file_diff(sha: &str, filename: String, status: &FileStatus) -> FileDiff {
+    let moved = status.source().is_some().then_some(status);
+    file_diff(&DiffRefs::Single(sha.to_string()), filename, moved)
+}
+
+/// Both sides of `filename` between `refs`, with the old side read from where it was moved
+/// from if it was.
+fn file_diff(refs: &DiffRefs, filename: String, moved: Option<&FileStatus>) -> FileDiff {
+    let source = moved.and_then(FileStatus::source).unwrap_or(&filename);
+    let old_bytes = read_old(source, refs);
+    let new_bytes = read_new(&filename, refs);
+    // A submodule has no contents on either side, only a commit
+    if old_bytes.is_none() && new_bytes.is_none() {
+        let old = submodule::gitlink(Some(&refs.old_revision()), source);
+        let new = submodule::gitlink(refs.new_revision().as_deref(), &filename);
+        if let Some(change) = submodule::change(&filename, old, new) {
+            let status = match (&change.old, &change.new) {
+                (None, _) => FileStatus::Added,
+                (_, None) => FileStatus::Deleted,
+                _ => FileStatus::Modified,
             };
-            FileDiff {
+            return FileDiff {
                 filename,
-                old_content,
-                new_content,
+                old_content: String::new(),
+                new_content: String::new(),
                 status,
-            }
-        })
-        .collect()
+                binary: None,
+                submodule: Some(change),
+            };
+        }
+    }
+    let mut diff = diff_from_bytes(filename.clone(), old_bytes, new_bytes, |bytes| {
+        decode_new(&filename, refs, bytes)
+    });
+    if let Some(moved) = moved {
+        diff.status = moved.clone();
+    }
+    diff
+}
+
+/// `filename`'s diff from its bytes on each side, `None` on a side it isn't on, with the new
+/// side decoded by `decode_new`. Binary files are described rather than decoded.
+pub fn diff_from_bytes(
+    filename: String,
+    old_bytes: Option<Vec<u8>>,
+    new_bytes: Option<Vec<u8>>,
+    decode_new: impl FnOnce(&[u8]) -> String,
+) -> FileDiff {
+    let binary = [&old_bytes, &new_bytes]
+        .into_iter()
+        .flatten()
+        .any(|bytes| is_binary(bytes))
+        .then(|| BinaryChange::new(&filename, old_bytes.as_deref(), new_bytes.as_deref()));
+    let (old_content, new_content) = if binary.is_some() {
+        (String::new(), String::new())
+    } else {
+        (
+            old_bytes
+                .map(|bytes| decode(&bytes, None))
+                .unwrap_or_default(),
+            new_bytes
+                .map(|bytes| decode_new(&bytes))
+                .unwrap_or_default(),
+        )
+    };
+    let status = if let Some(binary) = &binary {
+        match (binary.old_size, binary.new_size) {
+            (None, Some(_)) => FileStatus::Added,
+            (Some(_), None) => FileStatus::Deleted,
+            _ => FileStatus::Modified,
+        }
+    } else if old_content.is_empty() && !new_content.is_empty() {
+        FileStatus::Added
+    } else if !old_content.is_empty() && new_content.is_empty() {
+        FileStatus::Deleted
+    } else {
+        FileStatus::Modified
+    };
+    FileDiff {
+        filename,
+        old_content,
+        new_content,
+        status,
+        binary,
+        submodule: None,
+    }
 }
 
 pub fn load_pr_file_diffs(pr_info: &PrInfo) -> Result<Vec<FileDiff>, String> {
@@ -215,7 +518,7 @@ pub fn load_pr_file_diffs(pr_info: &PrInfo) -> Result<Vec<FileDiff>, String> {
         return Err(format!("gh pr diff failed: {}", stderr.trim()));
     }
 
-    let diff_output = String::from_utf8_lossy(&output.stdout);
+    let diff_output = decode_diff(&output.stdout);
     Ok(parse_unified_diff(&diff_output))
 }
 
@@ -225,17 +528,25 @@ fn parse_unified_diff(diff: &str) -> Vec<FileDiff> {
     let mut old_content = String::new();
     let mut new_content = String::new();
     let mut in_hunk = false;
+    // Set by the `rename from`/`copy from` header lines, with whether it's a copy
+    let mut moved_from: Option<(String, bool)> = None;
+    // Set by a `Binary files ... differ` line, the only content git prints for one
+    let mut binary: Option<(BinaryChange, FileStatus)> = None;
 
     for line in diff.lines() {
         if line.starts_with("diff --git") {
             // Save previous file if exists
             if let Some(filename) = current_file.take() {
-                let status = determine_file_status(&old_content, &new_content);
+                let (binary, binary_status) = binary.take().unzip();
+                let status = binary_status
+                    .unwrap_or_else(|| determine_file_status(&old_content, &new_content));
                 file_diffs.push(FileDiff {
+                    status: moved_status(moved_from.take(), &filename).unwrap_or(status),
                     filename,
                     old_content: std::mem::take(&mut old_content),
                     new_content: std::mem::take(&mut new_content),
-                    status,
+                    binary,
+                    submodule: None,
                 });
             }
 
@@ -246,6 +557,19 @@ fn parse_unified_diff(diff: &str) -> Vec<FileDiff> {
                 current_file = Some(b_path.strip_prefix("b/").unwrap_or(b_path).to_string());
             }
             in_hunk = false;
+        } else if let (false, Some(from)) = (in_hunk, line.strip_prefix("rename from ")) {
+            moved_from = Some((from.to_string(), false));
+        } else if let (false, Some(from)) = (in_hunk, line.strip_prefix("copy from ")) {
+            moved_from = Some((from.to_string(), true));
+        } else if let (false, Some(files)) = (in_hunk, line.strip_prefix("Binary files ")) {
+            let status = if files.starts_with("/dev/null ") {
+                FileStatus::Added
+            } else if files.ends_with(" /dev/null differ") {
+                FileStatus::Deleted
+            } else {
+                FileStatus::Modified
+            };
+            binary = Some((BinaryChange::unknown(), status));
         } else if line.starts_with("@@") {
             in_hunk = true;
         } else if in_hunk && current_file.is_some() {
@@ -276,18 +600,41 @@ fn parse_unified_diff(diff: &str) -> Vec<FileDiff> {
 
     // Don't forget the last file
     if let Some(filename) = current_file {
-        let status = determine_file_status(&old_content, &new_content);
+        let (binary, binary_status) = binary.unzip();
+        let status =
+            binary_status.unwrap_or_else(|| determine_file_status(&old_content, &new_content));
         file_diffs.push(FileDiff {
+            status: moved_status(moved_from, &filename).unwrap_or(status),
             filename,
             old_content,
             new_content,
-            status,
+            binary,
+            submodule: None,
         });
     }
 
+    // Submodules come as a `Subproject commit` line on each side
+    for diff in &mut file_diffs {
+        diff.submodule =
+            submodule::from_patch(&diff.filename, &diff.old_content, &diff.new_content);
+        if diff.submodule.is_some() {
+            diff.old_content.clear();
+            diff.new_content.clear();
+        }
+    }
     file_diffs
 }
 
+fn moved_status(moved_from: Option<(String, bool)>, filename: &str) -> Option<FileStatus> {
+    let (from, copied) = moved_from?;
+    let to = filename.to_string();
+    Some(if copied {
+        FileStatus::Copied { from, to }
+    } else {
+        FileStatus::Renamed { from, to }
+    })
+}
+
 fn determine_file_status(old_content: &str, new_content: &str) -> FileStatus {
     let old_empty = old_content.trim().is_empty();
     let new_empty = new_content.trim().is_empty();
@@ -300,3 +647,54 @@ fn determine_file_status(old_content: &str, new_content: &str) -> FileStatus {
         FileStatus::Modified
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_moves_keeps_renames_and_copies() {
+        let output =
+            "M\0src/lib.rs\0R087\0src/old.rs\0src/new.rs\0A\0README.md\0C100\0a.txt\0b.txt\0";
+        assert_eq!(
+            parse_moves(output),
+            vec![
+                FileStatus::Renamed {
+                    from: "src/old.rs".to_string(),
+                    to: "src/new.rs".to_string(),
+                },
+                FileStatus::Copied {
+                    from: "a.txt".to_string(),
+                    to: "b.txt".to_string(),
+                },
+            ]
+        );
+        assert!(parse_moves("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_unified_diff_renames_and_binaries() {
+        let diff = "diff --git a/old.rs b/new.rs\nsimilarity index 90%\nrename from old.rs\nrename to new.rs\n--- a/old.rs\n+++ b/new.rs\n@@ -1 +1 @@\n-a\n+b\ndiff --git a/logo.png b/logo.png\nnew file mode 100644\nBinary files /dev/null and b/logo.png differ\n";
+        let files = parse_unified_diff(diff);
+        assert_eq!(
+            files[0].status,
+            FileStatus::Renamed {
+                from: "old.rs".to_string(),
+                to: "new.rs".to_string(),
+            }
+        );
+        assert_eq!(files[0].new_content, "b\n");
+        assert_eq!(files[1].status, FileStatus::Added);
+        assert_eq!(files[1].binary, Some(BinaryChange::unknown()));
+    }
+
+    #[test]
+    fn test_parse_unified_diff_submodule() {
+        let diff = "diff --git a/vendor/lib b/vendor/lib\nindex 6df2f32..e4c3baa 160000\n--- a/vendor/lib\n+++ b/vendor/lib\n@@ -1 +1 @@\n-Subproject commit 6df2f32\n+Subproject commit e4c3baa\n";
+        let files = parse_unified_diff(diff);
+        let change = files[0].submodule.as_ref().expect("a submodule");
+        assert_eq!(change.new.as_ref().unwrap().sha, "e4c3baa");
+        assert_eq!(files[0].status, FileStatus::Modified);
+        assert!(files[0].new_content.is_empty());
+    }
+}
@@ -0,0 +1,115 @@
+//! Image previews for binary files over the kitty graphics protocol, which takes PNG data
+//! as is, so nothing has to be decoded here. Kitty, WezTerm and Ghostty speak it.
+
+use std::io::{self, Write};
+
+use crossterm::{cursor::MoveTo, queue};
+use ratatui::layout::Rect;
+
+/// Payload bytes per escape sequence, the most the protocol allows
+const CHUNK_SIZE: usize = 4096;
+
+/// Whether the terminal can draw images inline.
+pub fn supported() -> bool {
+    let env = |name| std::env::var(name).unwrap_or_default();
+    std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || env("TERM").contains("kitty")
+        || matches!(env("TERM_PROGRAM").as_str(), "WezTerm" | "ghostty")
+}
+
+/// Draw `png` in `area`, scaled to fit while keeping its proportions.
+pub fn show_png(png: &[u8], area: Rect) -> io::Result<()> {
+    if area.width == 0 || area.height == 0 {
+        return Ok(());
+    }
+    // Only one dimension is given, so the terminal keeps the aspect ratio
+    let size = match png_dimensions(png) {
+        // Cells are about twice as tall as they're wide
+        Some((width, height))
+            if width as f64 / height as f64 > area.width as f64 / (area.height as f64 * 2.0) =>
+        {
+            format!("c={}", area.width)
+        }
+        _ => format!("r={}", area.height),
+    };
+
+    let mut out = io::stdout();
+    queue!(out, MoveTo(area.x, area.y))?;
+    let data = base64(png);
+    let mut chunks = data.as_bytes().chunks(CHUNK_SIZE).peekable();
+    let mut first = true;
+    while let Some(chunk) = chunks.next() {
+        let more = u8::from(chunks.peek().is_some());
+        // The cursor stays put (C=1) so the next frame isn't drawn out of place
+        let keys = if first {
+            format!("a=T,f=100,q=2,C=1,{size},m={more}")
+        } else {
+            format!("m={more}")
+        };
+        first = false;
+        out.write_all(b"\x1b_G")?;
+        out.write_all(keys.as_bytes())?;
+        out.write_all(b";")?;
+        out.write_all(chunk)?;
+        out.write_all(b"\x1b\\")?;
+    }
+    out.flush()
+}
+
+/// Remove every image drawn so far.
+pub fn clear() -> io::Result<()> {
+    let mut out = io::stdout();
+    out.write_all(b"\x1b_Ga=d,q=2\x1b\\")?;
+    out.flush()
+}
+
+/// Width and height from the PNG's IHDR chunk, which always comes first.
+fn png_dimensions(png: &[u8]) -> Option<(u32, u32)> {
+    if png.len() < 24 || &png[..8] != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let width = u32::from_be_bytes(png[16..20].try_into().ok()?);
+    let height = u32::from_be_bytes(png[20..24].try_into().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for group in bytes.chunks(3) {
+        let n = group
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | ((b as u32) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= group.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_png_dimensions() {
+        let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        png.extend(640u32.to_be_bytes());
+        png.extend(480u32.to_be_bytes());
+        assert_eq!(png_dimensions(&png), Some((640, 480)));
+        assert_eq!(png_dimensions(b"GIF89a"), None);
+    }
+}
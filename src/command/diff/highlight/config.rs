@@ -1,3 +1,7 @@
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+
 use once_cell::sync::Lazy;
 use tree_sitter_highlight::HighlightConfiguration;
 
@@ -37,27 +41,65 @@ pub struct LanguageConfig {
     pub config: HighlightConfiguration,
 }
 
-fn load_config(
+/// Languages whose grammar or highlight query failed to load, with why. Their files are
+/// shown as plain text.
+static DISABLED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+
+pub fn disabled_languages() -> Vec<(String, String)> {
+    Lazy::force(&CONFIGS);
+    DISABLED
+        .lock()
+        .map(|disabled| disabled.clone())
+        .unwrap_or_default()
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "unknown panic".to_string())
+}
+
+/// Load one language, or disable it with a warning when its query is invalid or the grammar
+/// panics, so one broken grammar can't take down the rest.
+pub(super) fn load_config(
     language: tree_sitter::Language,
     name: &str,
     highlights: &str,
     ext: &'static str,
     configs: &mut Vec<(&'static str, LanguageConfig)>,
 ) {
-    match HighlightConfiguration::new(language, name, highlights, "", "") {
-        Ok(mut config) => {
-            config.configure(HIGHLIGHT_NAMES);
+    let loaded = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut config = HighlightConfiguration::new(language, name, highlights, "", "")
+            .map_err(|e| format!("{e:?}"))?;
+        config.configure(HIGHLIGHT_NAMES);
+        Ok::<_, String>(config)
+    }));
+    let reason = match loaded {
+        Ok(Ok(config)) => {
             configs.push((ext, LanguageConfig { config }));
+            return;
         }
-        Err(_e) => {
-            #[cfg(debug_assertions)]
-            eprintln!("[WARN] Failed to load {} highlight config: {:?}", name, _e);
-        }
+        Ok(Err(e)) => format!("invalid highlight query: {e}"),
+        Err(payload) => format!("grammar panicked: {}", panic_message(payload.as_ref())),
+    };
+
+    let Ok(mut disabled) = DISABLED.lock() else {
+        return;
+    };
+    // Languages registered under several extensions are reported once
+    if disabled.iter().all(|(disabled, _)| disabled != name) {
+        eprintln!("warning: {name} syntax highlighting is disabled ({reason})");
+        disabled.push((name.to_string(), reason));
     }
 }
 
 pub static CONFIGS: Lazy<Vec<(&'static str, LanguageConfig)>> = Lazy::new(|| {
     let mut configs = Vec::new();
+    // A panicking grammar is reported as a warning, not as a crash
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
 
     load_config(
         tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
@@ -163,6 +205,14 @@ pub static CONFIGS: Lazy<Vec<(&'static str, LanguageConfig)>> = Lazy::new(|| {
         &mut configs,
     );
 
+    load_config(
+        tree_sitter_yaml::LANGUAGE.into(),
+        "yaml",
+        YAML_HIGHLIGHTS,
+        "yaml",
+        &mut configs,
+    );
+
     load_config(
         tree_sitter_md::LANGUAGE.into(),
         "markdown",
@@ -179,5 +229,69 @@ pub static CONFIGS: Lazy<Vec<(&'static str, LanguageConfig)>> = Lazy::new(|| {
         &mut configs,
     );
 
+    #[cfg(feature = "lang-c")]
+    load_config(
+        tree_sitter_c::LANGUAGE.into(),
+        "c",
+        C_HIGHLIGHTS,
+        "c",
+        &mut configs,
+    );
+
+    #[cfg(feature = "lang-cpp")]
+    load_config(
+        tree_sitter_cpp::LANGUAGE.into(),
+        "cpp",
+        CPP_HIGHLIGHTS,
+        "cpp",
+        &mut configs,
+    );
+
+    #[cfg(feature = "lang-java")]
+    load_config(
+        tree_sitter_java::LANGUAGE.into(),
+        "java",
+        JAVA_HIGHLIGHTS,
+        "java",
+        &mut configs,
+    );
+
+    #[cfg(feature = "lang-ruby")]
+    load_config(
+        tree_sitter_ruby::LANGUAGE.into(),
+        "ruby",
+        RUBY_HIGHLIGHTS,
+        "rb",
+        &mut configs,
+    );
+
+    #[cfg(feature = "lang-php")]
+    load_config(
+        tree_sitter_php::LANGUAGE_PHP.into(),
+        "php",
+        PHP_HIGHLIGHTS,
+        "php",
+        &mut configs,
+    );
+
+    #[cfg(feature = "lang-kotlin")]
+    load_config(
+        tree_sitter_kotlin_ng::LANGUAGE.into(),
+        "kotlin",
+        KOTLIN_HIGHLIGHTS,
+        "kt",
+        &mut configs,
+    );
+
+    #[cfg(feature = "lang-swift")]
+    load_config(
+        tree_sitter_swift::LANGUAGE.into(),
+        "swift",
+        SWIFT_HIGHLIGHTS,
+        "swift",
+        &mut configs,
+    );
+
+    panic::set_hook(hook);
     configs
 });
@@ -1,12 +1,17 @@
 mod config;
 mod queries;
 
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
 
 use ratatui::prelude::*;
 use tree_sitter_highlight::{HighlightEvent, Highlighter};
 
 use super::theme;
+pub use config::disabled_languages;
 use config::{LanguageConfig, CONFIGS, HIGHLIGHT_NAMES};
 
 pub fn highlight_color(index: usize) -> Color {
@@ -34,12 +39,149 @@ pub fn highlight_color(index: usize) -> Color {
     }
 }
 
+/// Well-known files whose name, not extension, tells the language. Makefiles and Dockerfiles
+/// get the shell grammar, the closest available one since recipes and `RUN` lines are shell
+/// commands; ignore files get it for their `#` comments.
+const FILENAME_PATTERNS: &[(&str, &str)] = &[
+    ("Makefile", "sh"),
+    ("makefile", "sh"),
+    ("GNUmakefile", "sh"),
+    ("Justfile", "sh"),
+    ("justfile", "sh"),
+    ("PKGBUILD", "sh"),
+    ("Dockerfile", "sh"),
+    ("Containerfile", "sh"),
+    (".gitignore", "sh"),
+    (".dockerignore", "sh"),
+    (".gitattributes", "sh"),
+    (".bashrc", "sh"),
+    (".bash_profile", "sh"),
+    (".zshrc", "sh"),
+    (".zprofile", "sh"),
+    (".profile", "sh"),
+    (".envrc", "sh"),
+    ("Cargo.lock", "toml"),
+    ("Pipfile", "toml"),
+    ("poetry.lock", "toml"),
+    ("Gemfile", "rb"),
+    ("Rakefile", "rb"),
+    (".babelrc", "json"),
+    (".eslintrc", "json"),
+    (".prettierrc", "json"),
+];
+
+/// Extensions that share a grammar with a configured one
+const EXTENSION_ALIASES: &[(&str, &str)] = &[
+    ("mk", "sh"),
+    ("zsh", "sh"),
+    ("mjs", "js"),
+    ("cjs", "js"),
+    ("mts", "ts"),
+    ("cts", "ts"),
+    ("pyi", "py"),
+    ("pyw", "py"),
+    ("jsonc", "json"),
+    ("yml", "yaml"),
+    ("dockerfile", "sh"),
+    ("h", "c"),
+    ("cc", "cpp"),
+    ("cxx", "cpp"),
+    ("hpp", "cpp"),
+    ("hh", "cpp"),
+    ("hxx", "cpp"),
+    ("rake", "rb"),
+    ("gemspec", "rb"),
+    ("kts", "kt"),
+];
+
+/// Interpreters named in shebang lines, mapped to the extension of their grammar
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "py"),
+    ("bash", "sh"),
+    ("sh", "sh"),
+    ("zsh", "sh"),
+    ("dash", "sh"),
+    ("ksh", "sh"),
+    ("node", "js"),
+    ("nodejs", "js"),
+    ("deno", "ts"),
+    ("bun", "js"),
+    ("ts-node", "ts"),
+];
+
+/// Languages of extension-less files found by sniffing their shebang, keyed by filename
+static SNIFFED: Lazy<RwLock<HashMap<String, &'static str>>> =
+    Lazy::new(|| RwLock::new(HashMap::new()));
+
+fn config_for_key(key: &str) -> Option<&'static LanguageConfig> {
+    CONFIGS.iter().find(|(e, _)| *e == key).map(|(_, c)| c)
+}
+
 fn get_config_for_file(filename: &str) -> Option<&'static LanguageConfig> {
-    let ext = Path::new(filename).extension().and_then(|e| e.to_str())?;
-    CONFIGS.iter().find(|(e, _)| *e == ext).map(|(_, c)| c)
+    let key = known_key(filename).or_else(|| SNIFFED.read().ok()?.get(filename).copied())?;
+    config_for_key(key)
+}
+
+/// Grammar key for a file by its extension or name alone, without sniffing its content.
+fn known_key(filename: &str) -> Option<&'static str> {
+    let path = Path::new(filename);
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        let key = EXTENSION_ALIASES
+            .iter()
+            .find(|(alias, _)| *alias == ext)
+            .map_or(ext, |(_, key)| key);
+        if let Some((key, _)) = CONFIGS.iter().find(|(e, _)| *e == key) {
+            return Some(key);
+        }
+    }
+
+    let name = path.file_name().and_then(|n| n.to_str())?;
+    FILENAME_PATTERNS
+        .iter()
+        .find(|(pattern, _)| *pattern == name)
+        .map(|(_, key)| *key)
+}
+
+/// Grammar for a `#!` line, e.g. `#!/usr/bin/env python3` or `#!/bin/bash -e`.
+fn language_from_shebang(first_line: &str) -> Option<&'static str> {
+    let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        // Skip env's own options such as `-S`
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+    // python3.12 -> python
+    let interpreter = interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    INTERPRETERS
+        .iter()
+        .find(|(name, _)| *name == interpreter)
+        .map(|(_, key)| *key)
 }
 
-fn highlight_code(code: &str, filename: &str) -> Vec<(String, Option<usize>)> {
+/// Remember the grammar of files that only a shebang identifies, so lines of those files
+/// are highlighted. Call whenever the set of diffed files is (re)loaded.
+pub fn sniff_languages<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) {
+    let Ok(mut sniffed) = SNIFFED.write() else {
+        return;
+    };
+    for (filename, content) in files {
+        // The read lock `get_config_for_file` takes would wait on this write lock forever
+        if known_key(filename).is_some() {
+            continue;
+        }
+        match content.lines().next().and_then(language_from_shebang) {
+            Some(key) => sniffed.insert(filename.to_string(), key),
+            None => sniffed.remove(filename),
+        };
+    }
+}
+
+/// A line of code in pieces, each with the index of its highlight, if it has one
+pub type Highlighted = Vec<(String, Option<usize>)>;
+
+/// Split code into its highlighted pieces, which [`spans_of`] turns into spans. Parsing is
+/// the slow part, so the viewer keeps the pieces of the lines it has shown.
+pub fn highlight_code(code: &str, filename: &str) -> Highlighted {
     let Some(lang_config) = get_config_for_file(filename) else {
         return code.lines().map(|l| (l.to_string(), None)).collect();
     };
@@ -51,7 +193,7 @@ fn highlight_code(code: &str, filename: &str) -> Vec<(String, Option<usize>)> {
         return code.lines().map(|l| (l.to_string(), None)).collect();
     };
 
-    let mut result: Vec<(String, Option<usize>)> = Vec::new();
+    let mut result: Highlighted = Vec::new();
     let mut current_highlight: Option<usize> = None;
 
     for event in highlights.flatten() {
@@ -72,16 +214,34 @@ fn highlight_code(code: &str, filename: &str) -> Vec<(String, Option<usize>)> {
     result
 }
 
+/// Pieces of a line of code with their syntax colors, for output outside the viewer.
+pub fn highlight_colored(line: &str, filename: &str) -> Vec<(String, Color)> {
+    let default_fg = theme::get().syntax.default_text;
+    highlight_code(line, filename)
+        .into_iter()
+        .map(|(text, highlight_idx)| {
+            (
+                text,
+                highlight_idx.map(highlight_color).unwrap_or(default_fg),
+            )
+        })
+        .collect()
+}
+
 pub fn highlight_line_spans<'a>(line: &str, filename: &str, bg: Option<Color>) -> Vec<Span<'a>> {
-    let highlighted = highlight_code(line, filename);
+    spans_of(&highlight_code(line, filename), bg)
+}
+
+/// Spans in the theme's colors for pieces from [`highlight_code`].
+pub fn spans_of<'a>(pieces: &[(String, Option<usize>)], bg: Option<Color>) -> Vec<Span<'a>> {
     let bg_color = bg.unwrap_or(Color::Reset);
     let default_fg = theme::get().syntax.default_text;
 
-    highlighted
-        .into_iter()
+    pieces
+        .iter()
         .map(|(text, highlight_idx)| {
             let fg = highlight_idx.map(highlight_color).unwrap_or(default_fg);
-            Span::styled(text, Style::default().fg(fg).bg(bg_color))
+            Span::styled(text.clone(), Style::default().fg(fg).bg(bg_color))
         })
         .collect()
 }
@@ -115,6 +275,98 @@ mod tests {
         assert!(extensions.contains(&"py"), "Python config should be loaded");
         assert!(extensions.contains(&"go"), "Go config should be loaded");
         assert!(extensions.contains(&"json"), "JSON config should be loaded");
+        assert!(extensions.contains(&"yaml"), "YAML config should be loaded");
+    }
+
+    #[test]
+    fn test_optional_languages_load() {
+        let extensions: Vec<&str> = CONFIGS.iter().map(|(ext, _)| *ext).collect();
+        let enabled = [
+            (cfg!(feature = "lang-c"), "c"),
+            (cfg!(feature = "lang-cpp"), "cpp"),
+            (cfg!(feature = "lang-java"), "java"),
+            (cfg!(feature = "lang-ruby"), "rb"),
+            (cfg!(feature = "lang-php"), "php"),
+            (cfg!(feature = "lang-kotlin"), "kt"),
+            (cfg!(feature = "lang-swift"), "swift"),
+        ];
+        for (_, ext) in enabled.into_iter().filter(|(enabled, _)| *enabled) {
+            assert!(
+                extensions.contains(&ext),
+                "{ext} should be loaded: {:?}",
+                disabled_languages()
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_query_disables_only_its_language() {
+        let mut configs = Vec::new();
+        config::load_config(
+            tree_sitter_rust::LANGUAGE.into(),
+            "broken",
+            "(no_such_node) @keyword",
+            "broken",
+            &mut configs,
+        );
+        assert!(configs.is_empty());
+        assert!(disabled_languages()
+            .iter()
+            .any(|(name, reason)| name == "broken" && reason.contains("query")));
+        assert!(get_config_for_file("main.rs").is_some());
+        assert_eq!(
+            highlight_code("fn main() {}", "x.broken"),
+            vec![("fn main() {}".to_string(), None)]
+        );
+    }
+
+    #[test]
+    fn test_language_from_shebang() {
+        assert_eq!(language_from_shebang("#!/usr/bin/env python3"), Some("py"));
+        assert_eq!(
+            language_from_shebang("#!/usr/bin/python3.12 -u"),
+            Some("py")
+        );
+        assert_eq!(language_from_shebang("#!/bin/bash -e"), Some("sh"));
+        assert_eq!(
+            language_from_shebang("#!/usr/bin/env -S node --no-warnings"),
+            Some("js")
+        );
+        assert_eq!(language_from_shebang("#!/usr/bin/env ruby"), None);
+        assert_eq!(language_from_shebang("print('no shebang')"), None);
+    }
+
+    #[test]
+    fn test_extensionless_files_resolve() {
+        assert!(get_config_for_file("Makefile").is_some());
+        assert!(get_config_for_file("docker/Dockerfile").is_some());
+        assert!(get_config_for_file(".gitignore").is_some());
+        assert!(get_config_for_file(".github/workflows/ci.yml").is_some());
+        assert!(get_config_for_file("src/Cargo.lock").is_some());
+        assert!(get_config_for_file("lib/index.mjs").is_some());
+        assert!(get_config_for_file("bin/tool").is_none());
+
+        sniff_languages([("bin/tool", "#!/usr/bin/env python3\nprint(1)\n")]);
+        assert!(get_config_for_file("bin/tool").is_some());
+        let result = highlight_code("import os", "bin/tool");
+        assert!(result.iter().any(|(_, h)| h.is_some()));
+    }
+
+    #[test]
+    fn test_resniffing_follows_the_shebang() {
+        sniff_languages([("scripts/run", "#!/bin/bash\necho hi\n")]);
+        assert_eq!(SNIFFED.read().unwrap().get("scripts/run"), Some(&"sh"));
+
+        // Loading the files again sniffs the ones already known by their shebang once more
+        sniff_languages([
+            ("scripts/run", "#!/usr/bin/env node\nconsole.log(1)\n"),
+            ("scripts/build.rs", "#!/bin/bash\n"),
+        ]);
+        assert_eq!(SNIFFED.read().unwrap().get("scripts/run"), Some(&"js"));
+        assert!(!SNIFFED.read().unwrap().contains_key("scripts/build.rs"));
+
+        sniff_languages([("scripts/run", "no shebang\n")]);
+        assert!(get_config_for_file("scripts/run").is_none());
     }
 
     #[test]
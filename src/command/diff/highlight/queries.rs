@@ -435,6 +435,22 @@ pub const BASH_HIGHLIGHTS: &str = r#"
 (variable_name) @variable
 "#;
 
+pub const YAML_HIGHLIGHTS: &str = r#"
+(comment) @comment
+(block_mapping_pair key: (flow_node) @property)
+(flow_pair key: (flow_node) @property)
+(double_quote_scalar) @string
+(single_quote_scalar) @string
+(block_scalar) @string
+(integer_scalar) @number
+(float_scalar) @number
+(boolean_scalar) @constant.builtin
+(null_scalar) @constant.builtin
+(anchor_name) @label
+(alias_name) @label
+(tag) @type
+"#;
+
 pub const MD_HIGHLIGHTS: &str = r#"
 (atx_heading) @keyword
 (setext_heading) @keyword
@@ -450,3 +466,191 @@ pub const MD_HIGHLIGHTS: &str = r#"
 (link_destination) @string
 (link_title) @string
 "#;
+
+#[cfg(feature = "lang-c")]
+pub const C_HIGHLIGHTS: &str = r##"
+(comment) @comment
+(string_literal) @string
+(char_literal) @string
+(system_lib_string) @string
+(number_literal) @number
+(true) @constant.builtin
+(false) @constant.builtin
+(null) @constant.builtin
+(primitive_type) @type.builtin
+(sized_type_specifier) @type.builtin
+(type_identifier) @type
+(field_identifier) @property
+(statement_identifier) @label
+(function_declarator declarator: (identifier) @function)
+(call_expression function: (identifier) @function)
+(preproc_function_def name: (identifier) @function.macro)
+(preproc_def name: (identifier) @constant)
+
+["#define" "#include" "#if" "#ifdef" "#ifndef" "#else" "#elif" "#endif"] @keyword
+
+[
+  "break" "case" "const" "continue" "default" "do" "else" "enum" "extern" "for" "goto" "if"
+  "return" "sizeof" "static" "struct" "switch" "typedef" "union" "volatile" "while"
+] @keyword
+"##;
+
+#[cfg(feature = "lang-cpp")]
+pub const CPP_HIGHLIGHTS: &str = r##"
+(comment) @comment
+(string_literal) @string
+(raw_string_literal) @string
+(char_literal) @string
+(system_lib_string) @string
+(number_literal) @number
+(true) @constant.builtin
+(false) @constant.builtin
+(this) @variable.builtin
+(primitive_type) @type.builtin
+(sized_type_specifier) @type.builtin
+(type_identifier) @type
+(namespace_identifier) @module
+(statement_identifier) @label
+(function_declarator declarator: (identifier) @function)
+(function_declarator declarator: (field_identifier) @function.method)
+(function_declarator declarator: (qualified_identifier name: (identifier) @function))
+(call_expression function: (identifier) @function)
+(call_expression function: (field_expression field: (field_identifier) @function.method))
+(field_identifier) @property
+(preproc_function_def name: (identifier) @function.macro)
+(preproc_def name: (identifier) @constant)
+
+["#define" "#include" "#if" "#ifdef" "#ifndef" "#else" "#elif" "#endif"] @keyword
+
+[
+  "break" "case" "catch" "class" "const" "continue" "default" "delete" "do" "else" "enum"
+  "explicit" "extern" "final" "for" "friend" "if" "namespace" "new" "operator" "override"
+  "private" "protected" "public" "return" "sizeof" "static" "struct" "switch" "template"
+  "throw" "try" "typedef" "typename" "union" "using" "virtual" "volatile" "while"
+] @keyword
+"##;
+
+#[cfg(feature = "lang-java")]
+pub const JAVA_HIGHLIGHTS: &str = r#"
+(line_comment) @comment
+(block_comment) @comment
+(string_literal) @string
+(character_literal) @string
+(decimal_integer_literal) @number
+(hex_integer_literal) @number
+(octal_integer_literal) @number
+(binary_integer_literal) @number
+(decimal_floating_point_literal) @number
+(hex_floating_point_literal) @number
+(true) @constant.builtin
+(false) @constant.builtin
+(null_literal) @constant.builtin
+(this) @variable.builtin
+(super) @variable.builtin
+(integral_type) @type.builtin
+(floating_point_type) @type.builtin
+(boolean_type) @type.builtin
+(void_type) @type.builtin
+(type_identifier) @type
+(method_declaration name: (identifier) @function.method)
+(constructor_declaration name: (identifier) @constructor)
+(method_invocation name: (identifier) @function.method)
+(annotation name: (identifier) @attribute)
+(marker_annotation name: (identifier) @attribute)
+(field_access field: (identifier) @property)
+
+[
+  "abstract" "break" "case" "catch" "class" "continue" "default" "do" "else" "enum" "extends"
+  "final" "finally" "for" "if" "implements" "import" "instanceof" "interface" "new" "package"
+  "private" "protected" "public" "return" "static" "switch" "synchronized" "throw" "throws"
+  "try" "while"
+] @keyword
+"#;
+
+#[cfg(feature = "lang-ruby")]
+pub const RUBY_HIGHLIGHTS: &str = r#"
+(comment) @comment
+(string) @string
+(heredoc_body) @string
+(regex) @string
+(simple_symbol) @string.special
+(hash_key_symbol) @string.special
+(integer) @number
+(float) @number
+(nil) @constant.builtin
+(true) @constant.builtin
+(false) @constant.builtin
+(self) @variable.builtin
+(constant) @type
+(instance_variable) @variable.member
+(class_variable) @variable.member
+(method name: (identifier) @function)
+(singleton_method name: (identifier) @function)
+(call method: (identifier) @function.method)
+
+[
+  "alias" "and" "begin" "case" "class" "def" "do" "else" "elsif" "end" "ensure" "for" "if"
+  "in" "module" "not" "or" "rescue" "return" "then" "unless" "until" "when" "while" "yield"
+] @keyword
+"#;
+
+#[cfg(feature = "lang-php")]
+pub const PHP_HIGHLIGHTS: &str = r#"
+(comment) @comment
+(string) @string
+(encapsed_string) @string
+(heredoc) @string
+(nowdoc) @string
+(integer) @number
+(float) @number
+(boolean) @constant.builtin
+(null) @constant.builtin
+(primitive_type) @type.builtin
+(php_tag) @tag
+"?>" @tag
+(namespace_name) @module
+(variable_name) @variable
+(function_definition name: (name) @function)
+(method_declaration name: (name) @function.method)
+(function_call_expression function: (name) @function)
+(member_call_expression name: (name) @function.method)
+(class_declaration name: (name) @type)
+
+[
+  "abstract" "as" "break" "case" "catch" "class" "const" "continue" "default" "echo" "else"
+  "elseif" "extends" "final" "finally" "for" "foreach" "function" "if" "implements"
+  "interface" "namespace" "new" "private" "protected" "public" "return" "static" "switch"
+  "throw" "trait" "try" "use" "while"
+] @keyword
+"#;
+
+#[cfg(feature = "lang-kotlin")]
+pub const KOTLIN_HIGHLIGHTS: &str = r#"
+(line_comment) @comment
+(block_comment) @comment
+(string_literal) @string
+(number_literal) @number
+
+[
+  "class" "else" "for" "fun" "if" "import" "interface" "object" "package" "return" "val" "var"
+  "when" "while"
+] @keyword
+"#;
+
+#[cfg(feature = "lang-swift")]
+pub const SWIFT_HIGHLIGHTS: &str = r#"
+(comment) @comment
+(multiline_comment) @comment
+(line_string_literal) @string
+(multi_line_string_literal) @string
+(integer_literal) @number
+(real_literal) @number
+(boolean_literal) @constant.builtin
+(type_identifier) @type
+(function_declaration name: (simple_identifier) @function)
+
+[
+  "case" "class" "else" "enum" "extension" "for" "func" "guard" "if" "import" "in" "let"
+  "protocol" "return" "struct" "switch" "var" "while"
+] @keyword
+"#;
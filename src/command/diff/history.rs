@@ -0,0 +1,195 @@
+//! File history mode: the commits that touched the file in view, stepped through one diff at
+//! a time, like `git log -p --follow <file>`.
+
+use std::process::Command;
+
+use crate::logging;
+
+use super::git::commit_file_diff;
+use super::types::{FileDiff, FileStatus};
+
+/// Commits loaded for one file, newest first
+const MAX_COMMITS: usize = 500;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryCommit {
+    pub sha: String,
+    pub short: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+    /// Path of the file in this commit
+    pub filename: String,
+    /// How the commit changed the file, with the old path for a rename
+    pub status: FileStatus,
+}
+
+/// The history being stepped through, and the diff it stands in for
+pub struct FileHistory {
+    pub file_index: usize,
+    pub commits: Vec<HistoryCommit>,
+    /// Index into `commits` of the commit shown
+    pub index: usize,
+    /// The file's diff from before history mode, put back when leaving it
+    pub original: FileDiff,
+}
+
+impl FileHistory {
+    pub fn current(&self) -> &HistoryCommit {
+        &self.commits[self.index]
+    }
+
+    /// The shown commit's diff of the file.
+    pub fn diff(&self) -> FileDiff {
+        let commit = self.current();
+        commit_file_diff(&commit.sha, commit.filename.clone(), &commit.status)
+    }
+
+    /// Move to an older or newer commit. Returns false at either end.
+    pub fn step(&mut self, older: bool) -> bool {
+        let next = if older {
+            self.index + 1
+        } else {
+            match self.index.checked_sub(1) {
+                Some(next) => next,
+                None => return false,
+            }
+        };
+        if next >= self.commits.len() {
+            return false;
+        }
+        self.index = next;
+        true
+    }
+
+    /// Badge and description of the shown commit, for the footer.
+    pub fn footer(&self) -> (String, String) {
+        let commit = self.current();
+        (
+            format!(
+                " {} {}/{} ",
+                commit.short,
+                self.index + 1,
+                self.commits.len()
+            ),
+            format!(" {}, {}: {}", commit.author, commit.date, commit.subject),
+        )
+    }
+}
+
+fn parse_status(line: &str) -> Option<(String, FileStatus)> {
+    let mut fields = line.split('\t');
+    let code = fields.next()?;
+    let first = fields.next()?.to_string();
+    let status = match code.chars().next()? {
+        'A' => FileStatus::Added,
+        'D' => FileStatus::Deleted,
+        'R' | 'C' => {
+            let to = fields.next()?.to_string();
+            let (from, filename) = (first, to.clone());
+            let status = if code.starts_with('R') {
+                FileStatus::Renamed { from, to }
+            } else {
+                FileStatus::Copied { from, to }
+            };
+            return Some((filename, status));
+        }
+        _ => FileStatus::Modified,
+    };
+    Some((first, status))
+}
+
+/// Parse `git log --name-status` output whose records start with `\x1e` and whose header
+/// fields are separated by `\x1f`.
+pub fn parse_history(output: &str) -> Vec<HistoryCommit> {
+    output
+        .split('\x1e')
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let fields: Vec<&str> = lines.next()?.split('\x1f').collect();
+            let [sha, short, author, date, subject] = fields[..] else {
+                return None;
+            };
+            let (filename, status) = lines.find_map(parse_status)?;
+            Some(HistoryCommit {
+                sha: sha.to_string(),
+                short: short.to_string(),
+                author: author.to_string(),
+                date: date.to_string(),
+                subject: subject.to_string(),
+                filename,
+                status,
+            })
+        })
+        .collect()
+}
+
+/// Commits that touched `filename` up to `revision`, newest first, following renames.
+pub fn load_history(revision: &str, filename: &str) -> Result<Vec<HistoryCommit>, String> {
+    let output = logging::output(Command::new("git").args([
+        "log",
+        "--follow",
+        "--name-status",
+        "--date=short",
+        &format!("--max-count={MAX_COMMITS}"),
+        "--format=%x1e%H%x1f%h%x1f%an%x1f%ad%x1f%s",
+        revision,
+        "--",
+        filename,
+    ]))
+    .map_err(|e| format!("Failed to run git log: {e}"))?;
+    if output.status.success() {
+        Ok(parse_history(&String::from_utf8_lossy(&output.stdout)))
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_history_follows_renames() {
+        let output = "\x1eccc333\x1fccc\x1fAda\x1f2024-03-02\x1fTweak\n\nM\tsrc/new.rs\n\
+                      \x1ebbb222\x1fbbb\x1fBob\x1f2024-03-01\x1fMove it\n\nR087\tsrc/old.rs\tsrc/new.rs\n\
+                      \x1eaaa111\x1faaa\x1fAda\x1f2024-02-28\x1fAdd it\n\nA\tsrc/old.rs\n";
+        let commits = parse_history(output);
+        assert_eq!(commits.len(), 3);
+        assert_eq!(commits[0].status, FileStatus::Modified);
+        assert_eq!(commits[1].filename, "src/new.rs");
+        assert_eq!(commits[1].status.source(), Some("src/old.rs"));
+        assert_eq!(
+            (commits[2].filename.as_str(), &commits[2].status),
+            ("src/old.rs", &FileStatus::Added)
+        );
+    }
+
+    #[test]
+    fn test_step_stops_at_the_ends() {
+        let commits = parse_history(
+            "\x1eb\x1fb\x1fBob\x1f2024-03-01\x1fSecond\n\nM\tf\n\x1ea\x1fa\x1fAda\x1f2024-02-28\x1fFirst\n\nA\tf\n",
+        );
+        let mut history = FileHistory {
+            file_index: 0,
+            commits,
+            index: 0,
+            original: FileDiff {
+                filename: "f".to_string(),
+                old_content: String::new(),
+                new_content: String::new(),
+                status: FileStatus::Modified,
+                binary: None,
+                submodule: None,
+            },
+        };
+        assert!(!history.step(false));
+        assert!(history.step(true));
+        assert_eq!(history.current().subject, "First");
+        assert!(!history.step(true));
+        assert_eq!(
+            history.footer(),
+            (" a 2/2 ".to_string(), " Ada, 2024-02-28: First".to_string())
+        );
+    }
+}
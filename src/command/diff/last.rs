@@ -0,0 +1,106 @@
+//! The last comparison `lumen diff` opened in a repository, for `lumen diff --last`.
+//!
+//! It's kept in `.git/lumen/last-diff.json` (per worktree), so each repository remembers
+//! its own ref range or PR.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+use crate::commit_reference::CommitReference;
+use crate::logging;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastComparison {
+    /// The reference as typed, e.g. `main..feature`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// PR number or URL given with `--pr`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr: Option<String>,
+}
+
+impl LastComparison {
+    pub fn reference(&self) -> Option<CommitReference> {
+        self.reference.as_deref().and_then(|r| r.parse().ok())
+    }
+
+    pub fn describe(&self) -> String {
+        match (&self.reference, &self.pr) {
+            (_, Some(pr)) if pr.contains("/pull/") => pr.clone(),
+            (_, Some(pr)) => format!("PR #{pr}"),
+            (Some(reference), None) => reference.clone(),
+            (None, None) => "the working tree".to_string(),
+        }
+    }
+}
+
+fn state_path() -> Option<PathBuf> {
+    let output = logging::output(Command::new("git").args([
+        "rev-parse",
+        "--git-path",
+        "lumen/last-diff.json",
+    ]))
+    .ok()?;
+    output
+        .status
+        .success()
+        .then(|| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+}
+
+/// The comparison saved for the current repository, if any.
+pub fn load() -> Option<LastComparison> {
+    let contents = fs::read_to_string(state_path()?).ok()?;
+    let last: LastComparison = serde_json::from_str(&contents).ok()?;
+    (last.reference.is_some() || last.pr.is_some()).then_some(last)
+}
+
+/// Remember a ref range or PR. Failing to write it isn't worth interrupting the diff for.
+pub fn save(reference: Option<&CommitReference>, pr: Option<&str>) {
+    if reference.is_none() && pr.is_none() {
+        return;
+    }
+    let last = LastComparison {
+        reference: reference.map(|r| r.to_string()),
+        pr: pr.map(str::to_string),
+    };
+    let Some(path) = state_path() else {
+        return;
+    };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    if let Ok(json) = serde_json::to_string_pretty(&last) {
+        let _ = fs::write(path, json);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe() {
+        let range = LastComparison {
+            reference: Some("main..feature".to_string()),
+            pr: None,
+        };
+        assert_eq!(range.describe(), "main..feature");
+        assert_eq!(
+            range.reference(),
+            Some(CommitReference::Range {
+                from: "main".to_string(),
+                to: "feature".to_string(),
+            })
+        );
+
+        let pr = LastComparison {
+            reference: None,
+            pr: Some("123".to_string()),
+        };
+        assert_eq!(pr.describe(), "PR #123");
+        assert_eq!(serde_json::to_string(&pr).unwrap(), r#"{"pr":"123"}"#);
+    }
+}
@@ -0,0 +1,182 @@
+//! `@` mentions in PR comments: who can be tagged, completing a handle as it's typed, and
+//! finding the handles a finished comment tags.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde_json::Value;
+
+use super::{fuzzy_match, PrInfo};
+
+/// Commits on the default branch whose authors are offered after the PR's own people
+const RECENT_COMMITS: usize = 100;
+/// Suggestions listed under the comment at once
+pub const MAX_SUGGESTIONS: usize = 5;
+
+/// Load the people to offer in the background, see [`fetch_candidates`].
+pub fn load_candidates_async(pr: &PrInfo) -> Receiver<Result<Vec<String>, String>> {
+    let pr = pr.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(fetch_candidates(&pr));
+    });
+    rx
+}
+
+/// GitHub logins worth mentioning: the PR's author, participants and requested reviewers
+/// first, then recent committers to the default branch.
+fn fetch_candidates(pr: &PrInfo) -> Result<Vec<String>, String> {
+    let query = format!(
+        r#"query {{ repository(owner: "{}", name: "{}") {{ pullRequest(number: {}) {{ author {{ login }} participants(first: 100) {{ nodes {{ login }} }} reviewRequests(first: 50) {{ nodes {{ requestedReviewer {{ ... on User {{ login }} }} }} }} }} defaultBranchRef {{ target {{ ... on Commit {{ history(first: {}) {{ nodes {{ author {{ user {{ login }} }} }} }} }} }} }} }} }}"#,
+        pr.repo_owner, pr.repo_name, pr.number, RECENT_COMMITS
+    );
+
+    let output = Command::new("gh")
+        .args(["api", "graphql", "-f", &format!("query={}", query)])
+        .output()
+        .map_err(|e| format!("Failed to run gh api graphql: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh api graphql failed: {}", stderr.trim()));
+    }
+
+    parse_candidates(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_candidates(json: &str) -> Result<Vec<String>, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let repo = &value["data"]["repository"];
+    let pr = &repo["pullRequest"];
+    let logins = std::iter::once(&pr["author"]["login"])
+        .chain(nodes(&pr["participants"]).map(|n| &n["login"]))
+        .chain(nodes(&pr["reviewRequests"]).map(|n| &n["requestedReviewer"]["login"]))
+        .chain(
+            nodes(&repo["defaultBranchRef"]["target"]["history"])
+                .map(|n| &n["author"]["user"]["login"]),
+        );
+
+    let mut candidates: Vec<String> = Vec::new();
+    for login in logins {
+        if let Some(login) = login.as_str() {
+            if !candidates.iter().any(|c| c == login) {
+                candidates.push(login.to_string());
+            }
+        }
+    }
+    Ok(candidates)
+}
+
+fn nodes(connection: &Value) -> impl Iterator<Item = &Value> {
+    connection["nodes"].as_array().into_iter().flatten()
+}
+
+/// Letters allowed in a handle; `/` is for teams such as `@org/reviewers`
+fn is_handle_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '/' || c == '_'
+}
+
+/// The handle being typed at the end of `text`, without its `@`. An `@` inside a word, as in
+/// an email address, doesn't start a mention.
+pub fn partial_mention(text: &str) -> Option<&str> {
+    let at = text.rfind('@')?;
+    let partial = &text[at + 1..];
+    let starts_word = !matches!(text[..at].chars().next_back(), Some(c) if c.is_alphanumeric());
+    (starts_word && partial.chars().all(is_handle_char)).then_some(partial)
+}
+
+/// Candidates for `partial`: those starting with it first, then those matching it fuzzily.
+pub fn suggest<'a>(candidates: &'a [String], partial: &str) -> Vec<&'a str> {
+    let partial = partial.to_lowercase();
+    let (prefixed, others): (Vec<&String>, Vec<&String>) = candidates
+        .iter()
+        .filter(|c| fuzzy_match(&c.to_lowercase(), &partial))
+        .partition(|c| c.to_lowercase().starts_with(&partial));
+    prefixed
+        .into_iter()
+        .chain(others)
+        .filter(|c| c.to_lowercase() != partial)
+        .take(MAX_SUGGESTIONS)
+        .map(String::as_str)
+        .collect()
+}
+
+/// Replace the handle being typed at the end of `text` with `login`.
+pub fn complete(text: &mut String, login: &str) {
+    if let Some(partial) = partial_mention(text) {
+        let len = text.len() - partial.len();
+        text.truncate(len);
+        text.push_str(login);
+        text.push(' ');
+    }
+}
+
+/// Handles tagged in `text`, in order and without repeats.
+pub fn extract_mentions(text: &str) -> Vec<&str> {
+    let mut mentions: Vec<&str> = Vec::new();
+    for (at, _) in text.match_indices('@') {
+        if !partial_mention(&text[..=at]).is_some_and(str::is_empty) {
+            continue;
+        }
+        let rest = &text[at + 1..];
+        let end = rest.find(|c| !is_handle_char(c)).unwrap_or(rest.len());
+        let handle = rest[..end].trim_end_matches(['-', '/', '_']);
+        if !handle.is_empty() && !mentions.contains(&handle) {
+            mentions.push(handle);
+        }
+    }
+    mentions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_candidates_orders_and_dedups() {
+        let json = r#"{"data":{"repository":{
+            "pullRequest":{
+                "author":{"login":"ada"},
+                "participants":{"nodes":[{"login":"ada"},{"login":"bob"}]},
+                "reviewRequests":{"nodes":[{"requestedReviewer":{"login":"cyd"}},{"requestedReviewer":{}}]}
+            },
+            "defaultBranchRef":{"target":{"history":{"nodes":[
+                {"author":{"user":{"login":"dee"}}},
+                {"author":{"user":null}},
+                {"author":{"user":{"login":"bob"}}}
+            ]}}}
+        }}}"#;
+        assert_eq!(
+            parse_candidates(json).unwrap(),
+            vec!["ada", "bob", "cyd", "dee"]
+        );
+    }
+
+    #[test]
+    fn test_partial_mention() {
+        assert_eq!(partial_mention("thanks @ad"), Some("ad"));
+        assert_eq!(partial_mention("@"), Some(""));
+        assert_eq!(partial_mention("mail me at a@b"), None);
+        assert_eq!(partial_mention("@ada done"), None);
+    }
+
+    #[test]
+    fn test_suggest_and_complete() {
+        let candidates: Vec<String> = ["bob", "abby", "barbara"].map(String::from).to_vec();
+        assert_eq!(suggest(&candidates, "ab"), vec!["abby", "barbara"]);
+        assert_eq!(suggest(&candidates, "BOB"), Vec::<&str>::new());
+
+        let mut text = "cc @ab".to_string();
+        complete(&mut text, "abby");
+        assert_eq!(text, "cc @abby ");
+    }
+
+    #[test]
+    fn test_extract_mentions() {
+        assert_eq!(
+            extract_mentions("@ada, could @org/team look? ping @ada. not a@b.com or @"),
+            vec!["ada", "org/team"]
+        );
+    }
+}
@@ -1,28 +1,116 @@
 mod app;
+mod background;
+mod blame;
+mod commit;
+mod commit_suggestion;
+mod conflict;
 mod context;
-mod diff_algo;
+mod editor;
+mod encoding;
+mod explain;
+mod export;
+mod file_summaries;
+pub mod filter;
 mod git;
+mod graphics;
 pub mod highlight;
+mod history;
+pub mod last;
+mod mentions;
+mod moves;
+mod overview;
+mod paths;
+mod pending_review;
+mod pr_details;
+mod prefetch;
+pub mod range_picker;
 mod render;
+mod review_notes;
+mod risk;
+mod scribe;
 mod search;
+mod session;
+mod stage;
 mod state;
 mod sticky_lines;
+mod submodule;
+mod suggestions;
+mod symbol;
+pub mod terminal;
 pub mod theme;
+mod threads;
 mod types;
-mod watcher;
+pub mod watcher;
+mod width;
 
 use std::collections::HashSet;
 use std::io;
+use std::path::{Path, PathBuf};
 use std::process::{self, Command};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 
 use crate::commit_reference::CommitReference;
+use crate::config::cli::RiskMode;
+use crate::config::configuration::{DiffConfig, DraftConfig};
+use crate::error::LumenError;
+use crate::preflight;
+use crate::provider::LumenProvider;
+
+pub use export::ExportFormat;
+pub use filter::FileFilter;
+pub use render::{fuzzy_match, markdown_lines};
+pub use risk::RiskLevel;
+pub use types::{FileDiff, FileStatus, Focus, LineNote};
 
 pub struct DiffOptions {
     pub reference: Option<CommitReference>,
     pub pr: Option<String>,
-    pub file: Option<Vec<String>>,
+    /// Only the files `--file` lets through are loaded
+    pub file: Option<FileFilter>,
     pub watch: bool,
+    /// Paths `--watch` watches, the whole repository when empty
+    pub watch_paths: Vec<PathBuf>,
+    pub review_flow: bool,
+    pub read_only: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub risk: Option<RiskMode>,
+    /// Provider for the AI features: `--risk ai`, `--summarize-every`, `--file-summaries` and
+    /// explaining hunks
+    pub provider: Option<Arc<LumenProvider>>,
+    /// Why `provider` couldn't be set up, shown when one of those features is used
+    pub provider_error: Option<String>,
+    /// In watch mode, how often to summarize the changes made since the last summary
+    pub summarize_every: Option<Duration>,
+    /// Append those summaries to this file instead of showing them in a popup
+    pub summary_log: Option<PathBuf>,
+    /// Show a one-line AI summary of each file in the sidebar
+    pub file_summaries: bool,
+    /// Show fixed diffs instead of loading them from git, so reloading keeps them
+    pub preview: bool,
+    /// Commit of a snapshot to compare the working tree against
+    pub snapshot: Option<String>,
+    /// Notes to show under lines of the new version, like `lumen review` findings
+    pub notes: Vec<LineNote>,
+    /// File and line to open at instead of the first file
+    pub focus: Option<Focus>,
+    /// View settings from the config file
+    pub settings: DiffConfig,
+    /// How to draft the message when committing the reviewed working tree
+    pub draft: DraftConfig,
+}
+
+impl DiffOptions {
+    /// Status line for an AI feature used while there's no provider.
+    fn no_provider_message(&self, action: &str) -> String {
+        match &self.provider_error {
+            Some(reason) => format!("Can't {action} without an AI provider: {reason}"),
+            None => format!("No provider to {action} with here"),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -33,6 +121,8 @@ pub struct PrInfo {
     pub repo_name: String,
     pub base_ref: String,
     pub head_ref: String,
+    /// The commit the PR's branch pointed at when it was loaded, empty when unknown
+    pub head_sha: String,
     pub base_repo_owner: String,
     pub head_repo_owner: Option<String>, // None if head repo was deleted (fork deleted)
 }
@@ -96,10 +186,12 @@ fn fetch_pr_info(pr_input: &str) -> Result<PrInfo, String> {
                 .output()
                 .map_err(|e| format!("Failed to get current repo: {}", e))?;
             if !output.status.success() {
-                return Err(
-                    "Could not determine repository. Run from a git repo or specify --repo"
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                return Err(match preflight::gh_failure("gh repo view", &stderr) {
+                    auth @ LumenError::GhNotAuthenticated(_) => auth.to_string(),
+                    _ => "Could not determine the GitHub repository; pass the PR's URL instead"
                         .to_string(),
-                );
+                });
             }
             String::from_utf8_lossy(&output.stdout).trim().to_string()
         }
@@ -118,7 +210,7 @@ fn fetch_pr_info(pr_input: &str) -> Result<PrInfo, String> {
 
     // Use GraphQL to get the PR node ID, branch refs, and repo owners
     let query = format!(
-        r#"query {{ repository(owner: "{}", name: "{}") {{ pullRequest(number: {}) {{ id url baseRefName headRefName baseRepository {{ owner {{ login }} }} headRepository {{ owner {{ login }} }} }} }} }}"#,
+        r#"query {{ repository(owner: "{}", name: "{}") {{ pullRequest(number: {}) {{ id url baseRefName headRefName headRefOid baseRepository {{ owner {{ login }} }} headRepository {{ owner {{ login }} }} }} }} }}"#,
         repo_owner, repo_name, number
     );
 
@@ -129,7 +221,7 @@ fn fetch_pr_info(pr_input: &str) -> Result<PrInfo, String> {
 
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("gh api graphql failed: {}", stderr.trim()));
+        return Err(preflight::gh_failure("gh api graphql", &stderr).to_string());
     }
 
     let json_str = String::from_utf8_lossy(&output.stdout);
@@ -141,6 +233,7 @@ fn fetch_pr_info(pr_input: &str) -> Result<PrInfo, String> {
         extract_json_string(&json_str, "baseRefName").unwrap_or_else(|| "base".to_string());
     let head_ref =
         extract_json_string(&json_str, "headRefName").unwrap_or_else(|| "head".to_string());
+    let head_sha = extract_json_string(&json_str, "headRefOid").unwrap_or_default();
 
     // Extract repo owners from nested structure
     let base_repo_owner =
@@ -154,6 +247,7 @@ fn fetch_pr_info(pr_input: &str) -> Result<PrInfo, String> {
         repo_name,
         base_ref,
         head_ref,
+        head_sha,
         base_repo_owner,
         head_repo_owner,
     })
@@ -265,6 +359,31 @@ pub fn unmark_file_as_viewed_async(pr_info: &PrInfo, file_path: &str) {
     });
 }
 
+/// Post a comment on the PR's conversation (non-blocking, reports back once it's done)
+pub fn post_comment_async(pr_info: &PrInfo, body: String) -> Receiver<Result<(), String>> {
+    let endpoint = format!(
+        "repos/{}/{}/issues/{}/comments",
+        pr_info.repo_owner, pr_info.repo_name, pr_info.number
+    );
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let result = Command::new("gh")
+            .args(["api", &endpoint, "-f", &format!("body={}", body)])
+            .output()
+            .map_err(|e| format!("Failed to run gh api: {}", e))
+            .and_then(|output| {
+                if output.status.success() {
+                    Ok(())
+                } else {
+                    Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+                }
+            });
+        let _ = tx.send(result);
+    });
+    rx
+}
+
 /// Mark a file as viewed on GitHub PR (blocking)
 fn mark_file_as_viewed_sync(node_id: &str, file_path: &str) -> Result<(), String> {
     let mutation = format!(
@@ -333,5 +452,151 @@ pub fn run_diff_ui(options: DiffOptions) -> io::Result<()> {
         }
     }
 
+    // Conflicts come first: the regular view of a conflicted file is mostly markers
+    let working_tree = options.reference.is_none()
+        && options.snapshot.is_none()
+        && options.file.is_none()
+        && options.replay.is_none()
+        && !options.read_only;
+    if working_tree {
+        let conflicted = conflict::conflicted_files();
+        if !conflicted.is_empty() {
+            let settings = types::DiffViewSettings::from_config(&options.settings);
+            if !conflict::run_conflict_ui(&conflicted, &settings)? {
+                return Ok(());
+            }
+        }
+    }
+
     app::run_app(options, None)
 }
+
+/// Show how the function or type `name` changed in `reference`, scoped to its definition.
+pub fn run_symbol_diff(
+    name: &str,
+    reference: Option<&CommitReference>,
+    settings: DiffConfig,
+) -> Result<(), String> {
+    let refs = git::DiffRefs::from_reference(reference);
+    let file_diffs = symbol::load_symbol_diffs(name, &refs)?;
+    run_preview(file_diffs, settings).map_err(|e| e.to_string())
+}
+
+/// Compare two files or two directories outside of git, showing only the files `filter`
+/// lets through. Two files are shown under `name` when given, like the path `git difftool`
+/// compares, rather than a temporary copy's.
+pub fn run_path_diff(
+    old: &Path,
+    new: &Path,
+    name: Option<&str>,
+    filter: Option<&FileFilter>,
+    settings: DiffConfig,
+) -> Result<(), String> {
+    let mut file_diffs: Vec<FileDiff> = paths::load_path_diffs(old, new)?
+        .into_iter()
+        .filter(|diff| filter.is_none_or(|filter| filter.matches(diff)))
+        .collect();
+    if let (Some(name), false) = (name, new.is_dir()) {
+        for diff in &mut file_diffs {
+            diff.filename = name.to_string();
+        }
+    }
+    if file_diffs.is_empty() {
+        println!(
+            "No differences between {} and {}",
+            old.display(),
+            new.display()
+        );
+        return Ok(());
+    }
+    // Files are named relative to the directories, so `e` finds the new ones from there
+    if new.is_dir() {
+        std::env::set_current_dir(new)
+            .map_err(|e| format!("Failed to enter {}: {e}", new.display()))?;
+    }
+    run_preview(file_diffs, settings).map_err(|e| e.to_string())
+}
+
+/// Resolve `merged` for `git mergetool`, from the `local`, `base` and `remote` versions it
+/// passes. Fails when conflicts are left, so git doesn't take the file as merged.
+pub fn run_merge_tool(
+    merged: &Path,
+    local: PathBuf,
+    base: PathBuf,
+    remote: PathBuf,
+    settings: DiffConfig,
+) -> Result<(), String> {
+    let merged = merged.to_string_lossy();
+    let sides = conflict::MergeSides {
+        local,
+        base,
+        remote,
+    };
+    let settings = types::DiffViewSettings::from_config(&settings);
+    if conflict::run_merge_tool(&merged, &sides, &settings).map_err(|e| e.to_string())? {
+        Ok(())
+    } else {
+        Err(format!("{merged} still has unresolved conflicts"))
+    }
+}
+
+/// Write the diff `options` describe to `path` as an HTML report or a patch, instead of
+/// opening the viewer.
+pub fn export_diff(options: &DiffOptions, format: ExportFormat, path: &Path) -> Result<(), String> {
+    let pr_info = options.pr.as_deref().map(fetch_pr_info).transpose()?;
+    let file_diffs = match &pr_info {
+        Some(pr_info) => git::load_pr_file_diffs(pr_info)?,
+        None => git::load_file_diffs(options),
+    };
+    let contents = match format {
+        ExportFormat::Patch => export::diff_patch(&file_diffs),
+        ExportFormat::Html => {
+            let mut settings = types::DiffViewSettings::from_config(&options.settings);
+            if let Some(Ok(mode)) = settings.custom_theme.as_deref().map(theme::use_custom) {
+                settings.theme = mode;
+            }
+            theme::set_mode(settings.theme);
+            let title = report_title(options, pr_info.as_ref());
+            export::html_report(&title, &file_diffs, &settings)
+        }
+    };
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// What an HTML report is of: the PR, the compared revisions or the working tree.
+fn report_title(options: &DiffOptions, pr_info: Option<&PrInfo>) -> String {
+    match (pr_info, &options.reference, &options.snapshot) {
+        (Some(pr), _, _) => format!("{}/{} #{}", pr.repo_owner, pr.repo_name, pr.number),
+        (None, Some(reference), _) => reference.to_string(),
+        (None, None, Some(_)) => "Working tree against a snapshot".to_string(),
+        (None, None, None) => "Working tree changes".to_string(),
+    }
+}
+
+/// Show `file_diffs` read-only, e.g. to review changes before they're written.
+pub fn run_preview(file_diffs: Vec<FileDiff>, settings: DiffConfig) -> io::Result<()> {
+    let options = DiffOptions {
+        reference: None,
+        pr: None,
+        file: None,
+        watch: false,
+        watch_paths: Vec::new(),
+        review_flow: false,
+        read_only: true,
+        record: None,
+        replay: None,
+        risk: None,
+        provider: None,
+        provider_error: None,
+        summarize_every: None,
+        summary_log: None,
+        file_summaries: false,
+        preview: true,
+        snapshot: None,
+        notes: Vec::new(),
+        focus: None,
+        settings,
+        draft: DraftConfig::default(),
+    };
+    app::run_app_with_diffs(options, file_diffs)
+}
@@ -0,0 +1,198 @@
+//! Definitions that were moved, or only reindented, rather than changed. A function deleted
+//! in one place and added with the same name and the same lines elsewhere in the file is
+//! paired up, so the view can set both ends apart from real changes and jump between them.
+
+use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+use crate::command::diff::symbol::definitions;
+use crate::command::diff::types::{ChangeType, DiffLine};
+
+/// A definition found at `old` before the change and at `new` after it, as line numbers
+/// counted from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MovedBlock {
+    pub name: String,
+    pub old: RangeInclusive<usize>,
+    pub new: RangeInclusive<usize>,
+}
+
+struct Definition {
+    name: String,
+    lines: RangeInclusive<usize>,
+    /// Its non-blank lines with the indentation taken off, for comparing the two sides
+    text: Vec<String>,
+}
+
+fn contains(outer: &RangeInclusive<usize>, inner: &RangeInclusive<usize>) -> bool {
+    outer.contains(inner.start()) && outer.contains(inner.end())
+}
+
+/// The definitions in `source` that the diff mostly shows as changed, largest first so a
+/// moved type takes its methods along with it.
+fn changed_definitions(filename: &str, source: &str, changed: &HashSet<usize>) -> Vec<Definition> {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut found: Vec<Definition> = definitions(filename, source)
+        .into_iter()
+        .filter_map(|(name, start, end)| {
+            let body = lines.get(start..=end)?;
+            let numbered = (start + 1..=end + 1).zip(body.iter().copied());
+            let (text, changes): (Vec<String>, Vec<bool>) = numbered
+                .filter(|(_, line)| !line.trim().is_empty())
+                .map(|(number, line)| (line.trim().to_string(), changed.contains(&number)))
+                .unzip();
+            // Lone braces and blank lines of a moved block often line up with others
+            // around it, so a few unchanged lines don't rule it out
+            let changed_count = changes.iter().filter(|&&c| c).count();
+            (changed_count * 2 > changes.len()).then(|| Definition {
+                name,
+                lines: start + 1..=end + 1,
+                text,
+            })
+        })
+        .collect();
+    found.sort_by_key(|d| std::cmp::Reverse(d.lines.end() - d.lines.start()));
+    found
+}
+
+/// The line numbers on one side of `rows` that the diff shows as deleted, added or changed.
+fn changed_lines(rows: &[DiffLine], old: bool) -> HashSet<usize> {
+    rows.iter()
+        .filter(|row| row.change_type != ChangeType::Equal)
+        .filter_map(|row| if old { &row.old_line } else { &row.new_line }.as_ref())
+        .map(|(number, _)| *number)
+        .collect()
+}
+
+/// Definitions of `filename` that `rows` show as removed from `old` and added to `new`
+/// unchanged apart from indentation, in the order they appear in `new`.
+pub fn find_moves(filename: &str, old: &str, new: &str, rows: &[DiffLine]) -> Vec<MovedBlock> {
+    let old_defs = changed_definitions(filename, old, &changed_lines(rows, true));
+    let new_defs = changed_definitions(filename, new, &changed_lines(rows, false));
+    let mut used = vec![false; old_defs.len()];
+    let mut moves: Vec<MovedBlock> = Vec::new();
+    for def in new_defs {
+        if moves.iter().any(|m| contains(&m.new, &def.lines)) {
+            continue;
+        }
+        let Some(index) = old_defs.iter().enumerate().position(|(i, old)| {
+            !used[i]
+                && old.name == def.name
+                && old.text == def.text
+                && !moves.iter().any(|m| contains(&m.old, &old.lines))
+        }) else {
+            continue;
+        };
+        used[index] = true;
+        moves.push(MovedBlock {
+            name: def.name,
+            old: old_defs[index].lines.clone(),
+            new: def.lines,
+        });
+    }
+    moves.sort_by_key(|m| *m.new.start());
+    moves
+}
+
+/// The row `m` jumps to from `row`: the other end of the moved block it's in, or else the
+/// old end of the next block below it.
+pub fn jump_target(rows: &[DiffLine], moves: &[MovedBlock], row: usize) -> Option<usize> {
+    let number = |row: &DiffLine, old: bool| {
+        let side = if old { &row.old_line } else { &row.new_line };
+        side.as_ref().map(|(n, _)| *n)
+    };
+    let find = |old: bool, line: usize| rows.iter().position(|r| number(r, old) == Some(line));
+    let current = rows.get(row);
+    for m in moves {
+        let on_old = current
+            .and_then(|r| number(r, true))
+            .is_some_and(|n| m.old.contains(&n));
+        let on_new = current
+            .and_then(|r| number(r, false))
+            .is_some_and(|n| m.new.contains(&n));
+        // A row pairing the two sides belongs to whichever end it changes
+        let changed = current.is_some_and(|r| r.change_type != ChangeType::Equal);
+        if changed && on_new {
+            return find(true, *m.old.start());
+        }
+        if changed && on_old {
+            return find(false, *m.new.start());
+        }
+    }
+    moves
+        .iter()
+        .filter_map(|m| find(true, *m.old.start()))
+        .filter(|&target| target > row)
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumen_core::side_by_side::compute_side_by_side;
+
+    const OLD: &str = "fn first() {
+    let a = 1;
+    println!(\"{a}\");
+}
+
+fn second() {
+    let b = 2;
+    println!(\"{b}\");
+}
+
+fn third() {
+    let c = 3;
+}
+";
+
+    const NEW: &str = "fn second() {
+    let b = 2;
+    println!(\"{b}\");
+}
+
+fn third() {
+    let c = 4;
+}
+
+mod inner {
+    fn first() {
+        let a = 1;
+        println!(\"{a}\");
+    }
+}
+";
+
+    #[test]
+    fn test_moved_and_reindented_function_is_found() {
+        let rows = compute_side_by_side(OLD, NEW, 4, false);
+        let moves = find_moves("lib.rs", OLD, NEW, &rows);
+        let first = moves
+            .iter()
+            .find(|m| m.name == "first")
+            .expect("first moved");
+        assert_eq!(first.old, 1..=4);
+        assert_eq!(first.new, 11..=14);
+        // `third` was edited, not moved
+        assert!(moves.iter().all(|m| m.name != "third"));
+    }
+
+    #[test]
+    fn test_jump_goes_between_both_ends() {
+        let rows = compute_side_by_side(OLD, NEW, 4, false);
+        let moves = find_moves("lib.rs", OLD, NEW, &rows);
+        let source = rows
+            .iter()
+            .position(|r| r.old_line.as_ref().is_some_and(|(n, _)| *n == 1))
+            .unwrap();
+        let destination = jump_target(&rows, &moves, source).unwrap();
+        assert_eq!(rows[destination].new_line.as_ref().unwrap().0, 11);
+        assert_eq!(jump_target(&rows, &moves, destination), Some(source));
+    }
+
+    #[test]
+    fn test_unsupported_language_has_no_moves() {
+        let rows = compute_side_by_side("a\nb\n", "b\na\n", 4, false);
+        assert!(find_moves("notes.txt", "a\nb\n", "b\na\n", &rows).is_empty());
+    }
+}
@@ -0,0 +1,258 @@
+//! The numbers behind the overview popup: per-file line counts and languages, so a large
+//! diff can be triaged before reading any of it.
+
+use std::path::Path;
+
+use similar::{ChangeTag, TextDiff};
+
+use super::types::{FileDiff, FileStatus};
+
+/// Names for extensions
+const LANGUAGES: &[(&str, &str)] = &[
+    ("rs", "Rust"),
+    ("ts", "TypeScript"),
+    ("tsx", "TypeScript"),
+    ("mts", "TypeScript"),
+    ("cts", "TypeScript"),
+    ("js", "JavaScript"),
+    ("jsx", "JavaScript"),
+    ("mjs", "JavaScript"),
+    ("cjs", "JavaScript"),
+    ("py", "Python"),
+    ("pyi", "Python"),
+    ("go", "Go"),
+    ("java", "Java"),
+    ("kt", "Kotlin"),
+    ("kts", "Kotlin"),
+    ("swift", "Swift"),
+    ("rb", "Ruby"),
+    ("php", "PHP"),
+    ("c", "C"),
+    ("h", "C"),
+    ("cc", "C++"),
+    ("cpp", "C++"),
+    ("cxx", "C++"),
+    ("hpp", "C++"),
+    ("cs", "C#"),
+    ("scala", "Scala"),
+    ("lua", "Lua"),
+    ("zig", "Zig"),
+    ("ex", "Elixir"),
+    ("exs", "Elixir"),
+    ("sh", "Shell"),
+    ("bash", "Shell"),
+    ("zsh", "Shell"),
+    ("html", "HTML"),
+    ("css", "CSS"),
+    ("scss", "SCSS"),
+    ("vue", "Vue"),
+    ("svelte", "Svelte"),
+    ("sql", "SQL"),
+    ("json", "JSON"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+    ("toml", "TOML"),
+    ("xml", "XML"),
+    ("md", "Markdown"),
+    ("proto", "Protobuf"),
+];
+
+/// Names for files named after their language rather than by extension
+const NAMED_FILES: &[(&str, &str)] = &[
+    ("Makefile", "Make"),
+    ("Dockerfile", "Docker"),
+    ("Cargo.lock", "TOML"),
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FileStats {
+    pub file_index: usize,
+    pub name: String,
+    pub status: FileStatus,
+    /// Empty when neither the name nor the extension says
+    pub language: String,
+    pub added: usize,
+    pub removed: usize,
+    pub binary: bool,
+}
+
+impl FileStats {
+    pub fn changed(&self) -> usize {
+        self.added + self.removed
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OverviewSort {
+    /// Most changed lines first
+    #[default]
+    Changes,
+    Path,
+    Language,
+    Status,
+}
+
+impl OverviewSort {
+    pub fn next(self) -> Self {
+        match self {
+            OverviewSort::Changes => OverviewSort::Path,
+            OverviewSort::Path => OverviewSort::Language,
+            OverviewSort::Language => OverviewSort::Status,
+            OverviewSort::Status => OverviewSort::Changes,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            OverviewSort::Changes => "changes",
+            OverviewSort::Path => "path",
+            OverviewSort::Language => "language",
+            OverviewSort::Status => "status",
+        }
+    }
+}
+
+/// Line counts and the language of each of `diffs`, in the order given.
+pub fn file_stats(diffs: &[FileDiff]) -> Vec<FileStats> {
+    diffs
+        .iter()
+        .enumerate()
+        .map(|(file_index, diff)| {
+            let (added, removed) = line_counts(diff);
+            FileStats {
+                file_index,
+                name: diff.filename.clone(),
+                status: diff.status.clone(),
+                language: language(&diff.filename).unwrap_or_default().to_string(),
+                added,
+                removed,
+                binary: diff.binary.is_some(),
+            }
+        })
+        .collect()
+}
+
+/// Order `stats` by `sort`, by path among equals.
+pub fn sort_stats(stats: &mut [FileStats], sort: OverviewSort) {
+    stats.sort_by(|a, b| {
+        let order = match sort {
+            OverviewSort::Changes => b.changed().cmp(&a.changed()),
+            OverviewSort::Path => std::cmp::Ordering::Equal,
+            // Files without a language go last
+            OverviewSort::Language => {
+                (a.language.is_empty(), &a.language).cmp(&(b.language.is_empty(), &b.language))
+            }
+            OverviewSort::Status => status_rank(&a.status).cmp(&status_rank(&b.status)),
+        };
+        order.then_with(|| a.name.cmp(&b.name))
+    });
+}
+
+fn status_rank(status: &FileStatus) -> u8 {
+    match status {
+        FileStatus::Added => 0,
+        FileStatus::Modified => 1,
+        FileStatus::Renamed { .. } => 2,
+        FileStatus::Copied { .. } => 3,
+        FileStatus::Deleted => 4,
+    }
+}
+
+/// Lines added to and removed from `diff`, none for a binary file.
+pub fn line_counts(diff: &FileDiff) -> (usize, usize) {
+    if diff.binary.is_some() {
+        return (0, 0);
+    }
+    let mut added = 0;
+    let mut removed = 0;
+    for change in TextDiff::from_lines(&diff.old_content, &diff.new_content).iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Insert => added += 1,
+            ChangeTag::Delete => removed += 1,
+            ChangeTag::Equal => {}
+        }
+    }
+    (added, removed)
+}
+
+fn language(filename: &str) -> Option<&'static str> {
+    let path = Path::new(filename);
+    let name = path.file_name()?.to_str()?;
+    let extension = path.extension().and_then(|e| e.to_str());
+    // A file named `c` isn't C source
+    NAMED_FILES
+        .iter()
+        .find(|(key, _)| *key == name)
+        .or_else(|| LANGUAGES.iter().find(|(key, _)| Some(*key) == extension))
+        .map(|(_, language)| *language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(filename: &str, old: &str, new: &str, status: FileStatus) -> FileDiff {
+        FileDiff {
+            filename: filename.to_string(),
+            old_content: old.to_string(),
+            new_content: new.to_string(),
+            status,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_file_stats_count_lines_and_name_languages() {
+        let diffs = [
+            diff(
+                "src/lib.rs",
+                "a\nb\nc\n",
+                "a\nB\nc\nd\n",
+                FileStatus::Modified,
+            ),
+            diff("Makefile", "", "all:\n", FileStatus::Added),
+            diff("notes.unknown", "x\n", "", FileStatus::Deleted),
+            diff("web/app.test.tsx", "", "", FileStatus::Modified),
+        ];
+        let stats = file_stats(&diffs);
+        assert_eq!((stats[0].added, stats[0].removed), (2, 1));
+        assert_eq!(stats[0].language, "Rust");
+        assert_eq!(stats[1].language, "Make");
+        assert_eq!((stats[2].added, stats[2].removed), (0, 1));
+        assert_eq!(stats[2].language, "");
+        assert_eq!(stats[3].language, "TypeScript");
+    }
+
+    #[test]
+    fn test_language_by_extension_or_name() {
+        assert_eq!(language("src/main.c"), Some("C"));
+        assert_eq!(language("c"), None);
+        assert_eq!(language("bin/rs"), None);
+        assert_eq!(language("build/Dockerfile"), Some("Docker"));
+        assert_eq!(language("Cargo.lock"), Some("TOML"));
+        assert_eq!(language("Makefile.rs"), Some("Rust"));
+    }
+
+    #[test]
+    fn test_sort_stats() {
+        let diffs = [
+            diff("b.rs", "", "1\n", FileStatus::Added),
+            diff("a.md", "1\n2\n", "", FileStatus::Deleted),
+            diff("c", "1\n", "2\n", FileStatus::Modified),
+        ];
+        let mut stats = file_stats(&diffs);
+        let names =
+            |stats: &[FileStats]| -> Vec<String> { stats.iter().map(|s| s.name.clone()).collect() };
+
+        sort_stats(&mut stats, OverviewSort::Changes);
+        assert_eq!(names(&stats), ["a.md", "c", "b.rs"]);
+        sort_stats(&mut stats, OverviewSort::Path);
+        assert_eq!(names(&stats), ["a.md", "b.rs", "c"]);
+        sort_stats(&mut stats, OverviewSort::Language);
+        assert_eq!(names(&stats), ["a.md", "b.rs", "c"]);
+        sort_stats(&mut stats, OverviewSort::Status);
+        assert_eq!(names(&stats), ["b.rs", "c", "a.md"]);
+        assert_eq!(OverviewSort::Status.next(), OverviewSort::Changes);
+    }
+}
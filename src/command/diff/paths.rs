@@ -0,0 +1,171 @@
+//! Diffs of two paths outside of git history: two files, or two directories compared file
+//! by file, so the viewer can stand in as a general difftool.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::encoding::decode;
+use super::git::diff_from_bytes;
+use super::types::{FileDiff, FileStatus};
+
+/// The changes from `old` to `new`, two files or two directories. Directories are compared
+/// recursively by their relative paths, leaving out identical files and `.git`.
+pub fn load_path_diffs(old: &Path, new: &Path) -> Result<Vec<FileDiff>, String> {
+    for path in [old, new] {
+        if !path.exists() {
+            return Err(format!("{} doesn't exist", path.display()));
+        }
+    }
+    match (old.is_dir(), new.is_dir()) {
+        (false, false) => {
+            let old_bytes = read(old)?;
+            let new_bytes = read(new)?;
+            if old_bytes == new_bytes {
+                return Ok(Vec::new());
+            }
+            let mut diff = path_diff(new.display().to_string(), Some(old_bytes), Some(new_bytes));
+            // Two existing files are always compared, even when one of them is empty
+            diff.status = FileStatus::Modified;
+            Ok(vec![diff])
+        }
+        (true, true) => {
+            let mut names = BTreeSet::new();
+            names.extend(files_under(old)?);
+            names.extend(files_under(new)?);
+            let mut diffs = Vec::new();
+            for name in names {
+                let old_bytes = read_if_file(&old.join(&name))?;
+                let new_bytes = read_if_file(&new.join(&name))?;
+                if old_bytes != new_bytes {
+                    let filename = name.to_string_lossy().replace('\\', "/");
+                    diffs.push(path_diff(filename, old_bytes, new_bytes));
+                }
+            }
+            Ok(diffs)
+        }
+        _ => Err("compare a file with a file, or a directory with a directory".to_string()),
+    }
+}
+
+fn path_diff(filename: String, old: Option<Vec<u8>>, new: Option<Vec<u8>>) -> FileDiff {
+    let status = match (&old, &new) {
+        (None, _) => Some(FileStatus::Added),
+        (_, None) => Some(FileStatus::Deleted),
+        _ => None,
+    };
+    let mut diff = diff_from_bytes(filename, old, new, |bytes| decode(bytes, None));
+    // Which side a file is on says more than whether it's empty
+    if let Some(status) = status {
+        diff.status = status;
+    }
+    diff
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read {}: {e}", path.display()))
+}
+
+fn read_if_file(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    if path.is_file() {
+        read(path).map(Some)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Paths of the files under `root`, relative to it. Symlinked directories aren't followed,
+/// so a link back up the tree can't loop.
+fn files_under(root: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+    while let Some(dir) = pending.pop() {
+        let entries = fs::read_dir(root.join(&dir))
+            .map_err(|e| format!("Failed to read {}: {e}", root.join(&dir).display()))?;
+        for entry in entries.flatten() {
+            let relative = dir.join(entry.file_name());
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if file_type.is_dir() {
+                if entry.file_name() != ".git" {
+                    pending.push(relative);
+                }
+            } else if entry.path().is_file() {
+                files.push(relative);
+            }
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("lumen-paths-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for (path, contents) in files {
+            let path = root.join(path);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_directories_are_compared_file_by_file() {
+        let old = tree(
+            "old",
+            &[
+                ("same.txt", "x\n"),
+                ("src/lib.rs", "a\n"),
+                ("gone.txt", "bye\n"),
+            ],
+        );
+        let new = tree(
+            "new",
+            &[
+                ("same.txt", "x\n"),
+                ("src/lib.rs", "b\n"),
+                ("src/new.rs", ""),
+            ],
+        );
+        let diffs = load_path_diffs(&old, &new).unwrap();
+        let summary: Vec<(&str, &FileStatus)> = diffs
+            .iter()
+            .map(|diff| (diff.filename.as_str(), &diff.status))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("gone.txt", &FileStatus::Deleted),
+                ("src/lib.rs", &FileStatus::Modified),
+                ("src/new.rs", &FileStatus::Added),
+            ]
+        );
+        assert_eq!(diffs[1].new_content, "b\n");
+
+        assert!(load_path_diffs(&old, &new.join("same.txt")).is_err());
+        let _ = fs::remove_dir_all(old);
+        let _ = fs::remove_dir_all(new);
+    }
+
+    #[test]
+    fn test_two_files() {
+        let dir = tree(
+            "files",
+            &[("a.txt", "one\n"), ("b.txt", "two\n"), ("c.txt", "one\n")],
+        );
+        let diffs = load_path_diffs(&dir.join("a.txt"), &dir.join("b.txt")).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].old_content, "one\n");
+        assert!(diffs[0].filename.ends_with("b.txt"));
+        assert!(load_path_diffs(&dir.join("a.txt"), &dir.join("c.txt"))
+            .unwrap()
+            .is_empty());
+        assert!(load_path_diffs(&dir.join("a.txt"), &dir.join("missing.txt")).is_err());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
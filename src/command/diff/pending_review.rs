@@ -0,0 +1,229 @@
+//! Line comments written in the viewer and sent to GitHub together as one review, like the
+//! "Start a review" button does.
+
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde_json::{json, Value};
+
+use super::risk::RiskLevel;
+use super::types::{DiffLine, LineNote};
+use super::PrInfo;
+
+/// Which version of the file a comment's lines are in
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Side {
+    /// The base, for comments on removed lines
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DraftComment {
+    pub path: String,
+    /// First line of a multi-line comment
+    pub start_line: Option<usize>,
+    pub line: usize,
+    pub side: Side,
+    pub body: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReviewEvent {
+    Comment,
+    Approve,
+    RequestChanges,
+}
+
+impl ReviewEvent {
+    fn api_name(self) -> &'static str {
+        match self {
+            ReviewEvent::Comment => "COMMENT",
+            ReviewEvent::Approve => "APPROVE",
+            ReviewEvent::RequestChanges => "REQUEST_CHANGES",
+        }
+    }
+
+    pub fn done_message(self) -> &'static str {
+        match self {
+            ReviewEvent::Comment => "Review submitted",
+            ReviewEvent::Approve => "Approved the PR",
+            ReviewEvent::RequestChanges => "Requested changes",
+        }
+    }
+}
+
+impl DraftComment {
+    /// A comment, without its body yet, on `rows` of the side-by-side diff of `path`. It goes
+    /// on the new version's lines where the rows have any, and on the removed lines otherwise.
+    pub fn on_rows(path: &str, lines: &[DiffLine], rows: RangeInclusive<usize>) -> Option<Self> {
+        let rows = lines.get(rows)?;
+        let numbers = |old: bool| -> Vec<usize> {
+            rows.iter()
+                .filter_map(|row| {
+                    if old {
+                        row.old_line.as_ref()
+                    } else {
+                        row.new_line.as_ref()
+                    }
+                })
+                .map(|(num, _)| *num)
+                .collect()
+        };
+        let (numbers, side) = match numbers(false) {
+            new if !new.is_empty() => (new, Side::Right),
+            _ => (numbers(true), Side::Left),
+        };
+        let (&first, &last) = (numbers.first()?, numbers.last()?);
+        Some(DraftComment {
+            path: path.to_string(),
+            start_line: (first < last).then_some(first),
+            line: last,
+            side,
+            body: String::new(),
+        })
+    }
+
+    /// `src/a.rs:10-12`, with `(removed)` for lines of the base
+    pub fn location(&self) -> String {
+        let lines = match self.start_line {
+            Some(start) => format!("{start}-{}", self.line),
+            None => self.line.to_string(),
+        };
+        let removed = if self.side == Side::Left {
+            " (removed)"
+        } else {
+            ""
+        };
+        format!("{}:{lines}{removed}", self.path)
+    }
+
+    fn to_json(&self) -> Value {
+        let side = match self.side {
+            Side::Left => "LEFT",
+            Side::Right => "RIGHT",
+        };
+        let mut comment = json!({
+            "path": self.path,
+            "line": self.line,
+            "side": side,
+            "body": self.body,
+        });
+        if let Some(start) = self.start_line {
+            comment["start_line"] = json!(start);
+            comment["start_side"] = json!(side);
+        }
+        comment
+    }
+}
+
+/// The request body for `POST /pulls/{number}/reviews`.
+fn review_payload(event: ReviewEvent, body: &str, comments: &[DraftComment]) -> Value {
+    json!({
+        "event": event.api_name(),
+        "body": body,
+        "comments": comments.iter().map(DraftComment::to_json).collect::<Vec<_>>(),
+    })
+}
+
+/// Submit the review with its comments (non-blocking, reports back with `event` once it's
+/// done).
+pub fn submit_review_async(
+    pr: &PrInfo,
+    event: ReviewEvent,
+    body: String,
+    comments: Vec<DraftComment>,
+) -> Receiver<Result<ReviewEvent, String>> {
+    let endpoint = format!(
+        "repos/{}/{}/pulls/{}/reviews",
+        pr.repo_owner, pr.repo_name, pr.number
+    );
+    let payload = review_payload(event, &body, &comments).to_string();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = (|| {
+            let mut child = Command::new("gh")
+                .args(["api", "--method", "POST", &endpoint, "--input", "-"])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::piped())
+                .spawn()
+                .map_err(|e| format!("Failed to run gh api: {}", e))?;
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin
+                    .write_all(payload.as_bytes())
+                    .map_err(|e| e.to_string())?;
+            }
+            let output = child.wait_with_output().map_err(|e| e.to_string())?;
+            if output.status.success() {
+                Ok(event)
+            } else {
+                Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+            }
+        })();
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+/// Comments on the new version as notes under their last line, marked as not sent yet.
+pub fn notes(comments: &[DraftComment]) -> Vec<LineNote> {
+    comments
+        .iter()
+        .filter(|comment| comment.side == Side::Right)
+        .map(|comment| LineNote {
+            file: comment.path.clone(),
+            line: comment.line,
+            level: RiskLevel::Medium,
+            text: format!("Pending: {}", comment.body),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumen_core::side_by_side::compute_side_by_side;
+
+    #[test]
+    fn test_comment_on_rows() {
+        let lines = compute_side_by_side("a\nb\nc\n", "a\nB\nc\nd\n", 4, false);
+        let comment = DraftComment::on_rows("f.rs", &lines, 1..=3).unwrap();
+        assert_eq!(comment.location(), "f.rs:2-4");
+        assert_eq!(comment.side, Side::Right);
+
+        let lines = compute_side_by_side("a\nb\n", "a\n", 4, false);
+        let comment = DraftComment::on_rows("f.rs", &lines, 1..=1).unwrap();
+        assert_eq!(comment.location(), "f.rs:2 (removed)");
+    }
+
+    #[test]
+    fn test_review_payload() {
+        let comment = DraftComment {
+            path: "src/a.rs".to_string(),
+            start_line: Some(3),
+            line: 5,
+            side: Side::Right,
+            body: "Needs a test".to_string(),
+        };
+        let payload = review_payload(ReviewEvent::RequestChanges, "See inline", &[comment]);
+        assert_eq!(
+            payload,
+            json!({
+                "event": "REQUEST_CHANGES",
+                "body": "See inline",
+                "comments": [{
+                    "path": "src/a.rs",
+                    "line": 5,
+                    "side": "RIGHT",
+                    "body": "Needs a test",
+                    "start_line": 3,
+                    "start_side": "RIGHT",
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! What the PR is about, beyond its diff: title, description, labels, requested reviewers
+//! and CI checks, shown in a popup while reviewing.
+
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use serde_json::Value;
+
+use super::PrInfo;
+
+/// Outcome of one CI check, from the check run's conclusion or the commit status' state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckState {
+    Passed,
+    Failed,
+    Pending,
+    /// Skipped, neutral or cancelled
+    Skipped,
+}
+
+impl CheckState {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CheckState::Passed => "✓",
+            CheckState::Failed => "✗",
+            CheckState::Pending => "●",
+            CheckState::Skipped => "-",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Check {
+    pub name: String,
+    pub state: CheckState,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrDetails {
+    pub title: String,
+    pub author: String,
+    /// `OPEN`, `CLOSED` or `MERGED`, with `DRAFT` for open drafts
+    pub state: String,
+    pub url: String,
+    /// The description as written, in markdown
+    pub body: String,
+    pub labels: Vec<String>,
+    /// Users by login and teams by name
+    pub reviewers: Vec<String>,
+    pub checks: Vec<Check>,
+}
+
+impl PrDetails {
+    /// `3 passed, 1 failed, 2 pending`, leaving out the states no check is in
+    pub fn checks_summary(&self) -> String {
+        let count = |state| self.checks.iter().filter(|c| c.state == state).count();
+        let parts: Vec<String> = [
+            (CheckState::Passed, "passed"),
+            (CheckState::Failed, "failed"),
+            (CheckState::Pending, "pending"),
+            (CheckState::Skipped, "skipped"),
+        ]
+        .into_iter()
+        .filter_map(|(state, label)| match count(state) {
+            0 => None,
+            n => Some(format!("{n} {label}")),
+        })
+        .collect();
+        if parts.is_empty() {
+            "none".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+/// Load the PR's details in the background.
+pub fn load_details_async(pr: &PrInfo) -> Receiver<Result<PrDetails, String>> {
+    let pr = pr.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(fetch_details(&pr));
+    });
+    rx
+}
+
+/// How often to ask GitHub whether commits were pushed to the PR
+const HEAD_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Ask for the PR's head commit in the background every `HEAD_POLL_INTERVAL`, sending
+/// each answer. It stops once the receiver is gone.
+pub fn poll_head_async(pr: &PrInfo) -> Receiver<String> {
+    let pr = pr.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || loop {
+        thread::sleep(HEAD_POLL_INTERVAL);
+        if let Ok(head) = fetch_head(&pr) {
+            if tx.send(head).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+fn fetch_head(pr: &PrInfo) -> Result<String, String> {
+    let output = Command::new("gh")
+        .args([
+            "pr",
+            "view",
+            &pr.number.to_string(),
+            "--repo",
+            &format!("{}/{}", pr.repo_owner, pr.repo_name),
+            "--json",
+            "headRefOid",
+            "-q",
+            ".headRefOid",
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run gh pr view: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh pr view failed: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn fetch_details(pr: &PrInfo) -> Result<PrDetails, String> {
+    let output = Command::new("gh")
+        .args([
+            "pr",
+            "view",
+            &pr.number.to_string(),
+            "--repo",
+            &format!("{}/{}", pr.repo_owner, pr.repo_name),
+            "--json",
+            "title,author,state,isDraft,url,body,labels,reviewRequests,statusCheckRollup",
+        ])
+        .output()
+        .map_err(|e| format!("Failed to run gh pr view: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh pr view failed: {}", stderr.trim()));
+    }
+    parse_details(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn parse_details(json: &str) -> Result<PrDetails, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let text = |value: &Value| value.as_str().unwrap_or_default().to_string();
+    let items = |key: &str| value[key].as_array().cloned().unwrap_or_default();
+
+    let state = match (text(&value["state"]).as_str(), value["isDraft"].as_bool()) {
+        ("OPEN", Some(true)) => "DRAFT".to_string(),
+        (state, _) => state.to_string(),
+    };
+    let reviewers = items("reviewRequests")
+        .iter()
+        .filter_map(|request| {
+            request["login"]
+                .as_str()
+                .or_else(|| request["name"].as_str())
+                .map(str::to_string)
+        })
+        .collect();
+    let checks = items("statusCheckRollup")
+        .iter()
+        .map(|check| {
+            // Check runs have a name and conclusion, commit statuses a context and state
+            let name = check["name"]
+                .as_str()
+                .or_else(|| check["context"].as_str())
+                .unwrap_or("check");
+            let state = match (check["status"].as_str(), check["conclusion"].as_str()) {
+                (Some("COMPLETED"), Some(conclusion)) => conclusion,
+                (Some(_), _) => "PENDING",
+                (None, _) => check["state"].as_str().unwrap_or("PENDING"),
+            };
+            Check {
+                name: name.to_string(),
+                state: match state {
+                    "SUCCESS" => CheckState::Passed,
+                    "FAILURE" | "ERROR" | "TIMED_OUT" | "ACTION_REQUIRED" | "STARTUP_FAILURE" => {
+                        CheckState::Failed
+                    }
+                    "SKIPPED" | "NEUTRAL" | "CANCELLED" | "STALE" => CheckState::Skipped,
+                    _ => CheckState::Pending,
+                },
+            }
+        })
+        .collect();
+
+    Ok(PrDetails {
+        title: text(&value["title"]),
+        author: text(&value["author"]["login"]),
+        state,
+        url: text(&value["url"]),
+        body: text(&value["body"]).replace("\r\n", "\n"),
+        labels: items("labels").iter().map(|l| text(&l["name"])).collect(),
+        reviewers,
+        checks,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_details() {
+        let json = r#"{
+            "title": "Add retries", "author": {"login": "alice"}, "state": "OPEN", "isDraft": true,
+            "url": "https://github.com/o/r/pull/7", "body": "Retries failed uploads.\r\n\r\n- three times",
+            "labels": [{"name": "enhancement", "color": "a2eeef"}],
+            "reviewRequests": [{"__typename": "User", "login": "bob"}, {"__typename": "Team", "name": "core", "slug": "core"}],
+            "statusCheckRollup": [
+                {"__typename": "CheckRun", "name": "test", "status": "COMPLETED", "conclusion": "SUCCESS"},
+                {"__typename": "CheckRun", "name": "lint", "status": "IN_PROGRESS", "conclusion": ""},
+                {"__typename": "StatusContext", "context": "ci/deploy", "state": "FAILURE"}
+            ]
+        }"#;
+        let details = parse_details(json).unwrap();
+        assert_eq!(details.state, "DRAFT");
+        assert_eq!(details.body, "Retries failed uploads.\n\n- three times");
+        assert_eq!(details.labels, vec!["enhancement"]);
+        assert_eq!(details.reviewers, vec!["bob", "core"]);
+        assert_eq!(
+            details
+                .checks
+                .iter()
+                .map(|c| (c.name.as_str(), c.state))
+                .collect::<Vec<_>>(),
+            vec![
+                ("test", CheckState::Passed),
+                ("lint", CheckState::Pending),
+                ("ci/deploy", CheckState::Failed),
+            ]
+        );
+        assert_eq!(details.checks_summary(), "1 passed, 1 failed, 1 pending");
+    }
+}
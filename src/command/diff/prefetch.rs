@@ -0,0 +1,186 @@
+//! Rows and highlighting of the files next to the current one in the sidebar, worked out on a
+//! thread while the current one is read, so moving to them is instant even for large files.
+//!
+//! Views are kept in a small cache by file name and content, least recently used going first,
+//! so going back to a file skips the work too. A file whose content changed gets a new key,
+//! and its old view just ages out.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use lumen_core::side_by_side::{find_hunk_starts, DiffLine};
+
+use super::state::FileView;
+use super::types::{DiffViewSettings, FileDiff};
+
+/// Views kept, counting the ones for files next to the current one
+const CAPACITY: usize = 16;
+/// Rows highlighted ahead of time above and below where a file opens
+const ROWS_ABOVE: usize = 20;
+const ROWS_BELOW: usize = 80;
+
+/// What a file's view depends on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ViewKey {
+    pub filename: String,
+    /// Hash of the old and new content
+    pub content: u64,
+    pub tab_width: usize,
+    pub ignore_whitespace: bool,
+    pub detect_moves: bool,
+}
+
+impl ViewKey {
+    pub fn new(diff: &FileDiff, settings: &DiffViewSettings) -> Self {
+        let mut hasher = DefaultHasher::new();
+        diff.old_content.hash(&mut hasher);
+        diff.new_content.hash(&mut hasher);
+        ViewKey {
+            filename: diff.filename.clone(),
+            content: hasher.finish(),
+            tab_width: settings.tab_width_for(&diff.filename),
+            ignore_whitespace: settings.ignore_whitespace,
+            detect_moves: settings.detect_moves,
+        }
+    }
+}
+
+pub struct ViewCache {
+    /// Least recently used first
+    views: VecDeque<(ViewKey, FileView)>,
+    /// Views being worked out on a thread
+    in_flight: HashSet<ViewKey>,
+    tx: Sender<(ViewKey, FileView)>,
+    rx: Receiver<(ViewKey, FileView)>,
+}
+
+impl Default for ViewCache {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        ViewCache {
+            views: VecDeque::new(),
+            in_flight: HashSet::new(),
+            tx,
+            rx,
+        }
+    }
+}
+
+impl ViewCache {
+    /// Take the view for `key` out of the cache, to show it.
+    pub fn take(&mut self, key: &ViewKey) -> Option<FileView> {
+        self.receive();
+        let position = self.views.iter().position(|(k, _)| k == key)?;
+        self.views.remove(position).map(|(_, view)| view)
+    }
+
+    /// The rows of the view for `key`, if it's cached.
+    pub fn rows(&mut self, key: &ViewKey) -> Option<&[DiffLine]> {
+        self.receive();
+        self.views
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, view)| view.rows.as_slice())
+    }
+
+    /// Keep `view`, dropping the least recently used view when the cache is full.
+    pub fn put(&mut self, key: ViewKey, view: FileView) {
+        self.views.retain(|(k, _)| *k != key);
+        if self.views.len() == CAPACITY {
+            self.views.pop_front();
+        }
+        self.views.push_back((key, view));
+    }
+
+    /// Work out the view of `diff` on a thread, unless it's cached or on its way. The rows
+    /// around `scroll`, or around the first hunk without one, are highlighted too.
+    pub fn prefetch(&mut self, key: ViewKey, diff: &FileDiff, plain: bool, scroll: Option<usize>) {
+        self.receive();
+        if self.in_flight.contains(&key) || self.views.iter().any(|(k, _)| *k == key) {
+            return;
+        }
+        self.in_flight.insert(key.clone());
+        let tx = self.tx.clone();
+        let diff = diff.clone();
+        thread::spawn(move || {
+            let mut view = FileView::compute(&diff, &key, plain);
+            let scroll = scroll.unwrap_or_else(|| {
+                find_hunk_starts(&view.rows)
+                    .first()
+                    .map_or(0, |&start| start.saturating_sub(5))
+            });
+            view.highlight(rows_around(scroll), &diff.filename);
+            let _ = tx.send((key, view));
+        });
+    }
+
+    /// Keep the views finished on threads since the last call.
+    fn receive(&mut self) {
+        while let Ok((key, view)) = self.rx.try_recv() {
+            self.in_flight.remove(&key);
+            self.put(key, view);
+        }
+    }
+}
+
+fn rows_around(scroll: usize) -> Range<usize> {
+    scroll.saturating_sub(ROWS_ABOVE)..scroll + ROWS_BELOW
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::types::FileStatus;
+
+    fn file(filename: &str, new: &str) -> FileDiff {
+        FileDiff {
+            filename: filename.to_string(),
+            old_content: "a\n".to_string(),
+            new_content: new.to_string(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_cache_drops_least_recently_used() {
+        let settings = DiffViewSettings::default();
+        let mut cache = ViewCache::default();
+        let keys: Vec<ViewKey> = (0..=CAPACITY)
+            .map(|i| ViewKey::new(&file(&format!("{i}.txt"), "b\n"), &settings))
+            .collect();
+        for key in &keys {
+            cache.put(key.clone(), FileView::default());
+        }
+        assert!(cache.take(&keys[0]).is_none());
+        assert!(cache.take(&keys[1]).is_some());
+        assert!(cache.take(&keys[1]).is_none());
+
+        let changed = ViewKey::new(&file("2.txt", "c\n"), &settings);
+        assert_ne!(changed, keys[2]);
+        assert!(cache.take(&changed).is_none());
+    }
+
+    #[test]
+    fn test_prefetch_highlights_ahead() {
+        let settings = DiffViewSettings::default();
+        let mut cache = ViewCache::default();
+        let diff = file("main.rs", "fn main() {}\n");
+        let key = ViewKey::new(&diff, &settings);
+        cache.prefetch(key.clone(), &diff, false, None);
+
+        let view = loop {
+            if let Some(view) = cache.take(&key) {
+                break view;
+            }
+            thread::sleep(std::time::Duration::from_millis(5));
+        };
+        assert!(!view.rows.is_empty());
+        assert!((0..view.rows.len()).any(|row| view.highlighted(row, false).is_some()));
+    }
+}
@@ -0,0 +1,279 @@
+//! What a bare `lumen diff` compares against, picked from a short list instead of typed: the
+//! working tree, the last comparison, the upstream and default branches, what isn't pushed
+//! yet, recent branches and tags, and where HEAD was recently.
+
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::process::Command;
+
+use inquire::Select;
+
+use crate::logging;
+
+use super::last::{self, LastComparison};
+
+/// Branches offered, most recently committed to first
+const MAX_BRANCHES: usize = 8;
+/// Tags offered, newest first
+const MAX_TAGS: usize = 5;
+/// Earlier positions of HEAD offered from the reflog
+const MAX_REFLOG: usize = 6;
+
+/// One line of the picker
+#[derive(Debug, Clone, PartialEq)]
+struct Choice {
+    kind: &'static str,
+    label: String,
+    /// `None` for the working tree
+    comparison: Option<LastComparison>,
+}
+
+impl fmt::Display for Choice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<15} {}", self.kind, self.label)
+    }
+}
+
+fn range(kind: &'static str, reference: String, note: Option<&str>) -> Choice {
+    let label = match note {
+        Some(note) => format!("{reference}  {note}"),
+        None => reference.clone(),
+    };
+    Choice {
+        kind,
+        label,
+        comparison: Some(LastComparison {
+            reference: Some(reference),
+            pr: None,
+        }),
+    }
+}
+
+/// What the repository offers to compare against
+#[derive(Debug, Default)]
+struct Sources {
+    last: Option<LastComparison>,
+    current: Option<String>,
+    /// Upstream of the current branch, like `origin/feature`
+    upstream: Option<String>,
+    /// Where `git push` would push the current branch
+    push: Option<String>,
+    /// The remote's default branch, like `origin/main`
+    default_branch: Option<String>,
+    branches: Vec<String>,
+    tags: Vec<String>,
+    /// Earlier positions of HEAD: short SHA and what moved it there
+    reflog: Vec<(String, String)>,
+}
+
+/// The non-empty lines `git` prints for `args`, none when it fails.
+fn git_lines(args: &[&str]) -> Vec<String> {
+    match logging::output(Command::new("git").args(args)) {
+        Ok(o) if o.status.success() => String::from_utf8_lossy(&o.stdout)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(String::from)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn git_line(args: &[&str]) -> Option<String> {
+    git_lines(args).into_iter().next()
+}
+
+impl Sources {
+    fn load() -> Self {
+        let refs = git_lines(&[
+            "for-each-ref",
+            "--sort=-committerdate",
+            "--format=%(refname:short)%09%(symref)",
+            "refs/heads",
+            "refs/remotes",
+        ]);
+        Sources {
+            last: last::load(),
+            current: git_line(&["symbolic-ref", "--quiet", "--short", "HEAD"]),
+            upstream: git_line(&["rev-parse", "--abbrev-ref", "@{upstream}"]),
+            push: git_line(&["rev-parse", "--abbrev-ref", "@{push}"]),
+            default_branch: git_line(&[
+                "symbolic-ref",
+                "--quiet",
+                "--short",
+                "refs/remotes/origin/HEAD",
+            ]),
+            // Symbolic refs like `origin/HEAD` only repeat a branch
+            branches: refs
+                .iter()
+                .filter_map(|line| match line.split_once('\t') {
+                    Some((name, "")) => Some(name.to_string()),
+                    Some(_) => None,
+                    None => Some(line.clone()),
+                })
+                .collect(),
+            tags: git_lines(&["tag", "--sort=-creatordate"])
+                .into_iter()
+                .take(MAX_TAGS)
+                .collect(),
+            reflog: git_lines(&["reflog", "-n", "20", "--format=%h%x1f%gs"])
+                .iter()
+                .filter_map(|line| line.split_once('\x1f'))
+                .map(|(sha, subject)| (sha.to_string(), subject.to_string()))
+                .collect(),
+        }
+    }
+
+    fn choices(&self) -> Vec<Choice> {
+        let mut choices = vec![Choice {
+            kind: "Working tree",
+            label: "uncommitted changes".to_string(),
+            comparison: None,
+        }];
+        if let Some(last) = &self.last {
+            choices.push(Choice {
+                kind: "Last diff",
+                label: last.describe(),
+                comparison: Some(last.clone()),
+            });
+        }
+        // The branch's own remote is usually where it's pushed too; one entry does for both
+        if let Some(push) = &self.push {
+            choices.push(range(
+                "Last push",
+                format!("{push}..HEAD"),
+                Some("commits not pushed yet"),
+            ));
+        }
+        if let Some(upstream) = self
+            .upstream
+            .as_ref()
+            .filter(|u| self.push.as_ref() != Some(u))
+        {
+            choices.push(range("Upstream", format!("{upstream}...HEAD"), None));
+        }
+        if let Some(default) = self
+            .default_branch
+            .as_ref()
+            .filter(|d| self.upstream.as_ref() != Some(d) && self.push.as_ref() != Some(d))
+        {
+            choices.push(range("Default branch", format!("{default}...HEAD"), None));
+        }
+
+        let taken = [
+            &self.current,
+            &self.upstream,
+            &self.push,
+            &self.default_branch,
+        ];
+        for branch in self
+            .branches
+            .iter()
+            .filter(|branch| !taken.iter().any(|t| t.as_ref() == Some(branch)))
+            .take(MAX_BRANCHES)
+        {
+            choices.push(range("Branch", format!("{branch}...HEAD"), None));
+        }
+        for tag in &self.tags {
+            choices.push(range("Tag", format!("{tag}..HEAD"), None));
+        }
+
+        // The first entry is where HEAD is now; of the rest, each commit once
+        let head = self.reflog.first().map(|(sha, _)| sha);
+        let mut seen = Vec::new();
+        for (sha, subject) in self.reflog.iter().skip(1) {
+            if Some(sha) == head || seen.contains(&sha) {
+                continue;
+            }
+            seen.push(sha);
+            choices.push(range("Reflog", format!("{sha}..HEAD"), Some(subject)));
+            if seen.len() == MAX_REFLOG {
+                break;
+            }
+        }
+        choices
+    }
+}
+
+/// On a terminal, ask what to compare against. `None` is the working tree, which is also what
+/// a non-interactive run, an empty repository or Escape gets.
+pub fn pick() -> Option<LastComparison> {
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return None;
+    }
+    let choices = Sources::load().choices();
+    if choices.len() == 1 {
+        return None;
+    }
+    Select::new("Compare against:", choices)
+        .with_page_size(12)
+        .with_help_message(
+            "↑↓ to move, type to filter, Esc for the working tree; `lumen diff <ref>` or `--last` skips this",
+        )
+        .prompt()
+        .ok()?
+        .comparison
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn references(choices: &[Choice]) -> Vec<String> {
+        choices
+            .iter()
+            .map(|choice| {
+                choice
+                    .comparison
+                    .as_ref()
+                    .map_or("working tree".to_string(), LastComparison::describe)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_choices() {
+        let sources = Sources {
+            last: Some(LastComparison {
+                reference: None,
+                pr: Some("42".to_string()),
+            }),
+            current: Some("feature".to_string()),
+            upstream: Some("origin/feature".to_string()),
+            push: Some("origin/feature".to_string()),
+            default_branch: Some("origin/main".to_string()),
+            branches: ["feature", "origin/feature", "main", "origin/main", "fix"]
+                .map(String::from)
+                .to_vec(),
+            tags: vec!["v1.2.0".to_string()],
+            reflog: [
+                ("aaa", "commit: Add x"),
+                ("bbb", "checkout: moving from main to feature"),
+                ("aaa", "commit: Add x"),
+                ("bbb", "rebase"),
+                ("ccc", "pull"),
+            ]
+            .map(|(sha, subject)| (sha.to_string(), subject.to_string()))
+            .to_vec(),
+        };
+        assert_eq!(
+            references(&sources.choices()),
+            vec![
+                "working tree",
+                "PR #42",
+                "origin/feature..HEAD",
+                "origin/main...HEAD",
+                "main...HEAD",
+                "fix...HEAD",
+                "v1.2.0..HEAD",
+                "bbb..HEAD",
+                "ccc..HEAD",
+            ]
+        );
+        assert_eq!(
+            sources.choices()[2].to_string(),
+            "Last push       origin/feature..HEAD  commits not pushed yet"
+        );
+
+        assert_eq!(Sources::default().choices().len(), 1);
+    }
+}
@@ -0,0 +1,171 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Paragraph},
+};
+
+use crate::command::diff::conflict::{ConflictState, Pick, Segment};
+use crate::command::diff::highlight::highlight_line_spans;
+use crate::command::diff::theme;
+use crate::command::diff::types::expand_tabs;
+
+/// Unchanged lines shown above and below the conflict
+const CONTEXT_LINES: usize = 3;
+
+fn code_line<'a>(text: &str, filename: &str, bg: Option<Color>) -> Line<'a> {
+    let text = expand_tabs(text.trim_end_matches(['\n', '\r']), 4);
+    Line::from(highlight_line_spans(&text, filename, bg))
+}
+
+fn context_lines<'a>(lines: &[String], filename: &str) -> Vec<Line<'a>> {
+    let t = theme::get();
+    lines
+        .iter()
+        .map(|line| code_line(line, filename, Some(t.diff.context_bg)))
+        .collect()
+}
+
+pub fn render_conflicts(frame: &mut Frame, state: &ConflictState) {
+    let t = theme::get();
+    let file = state.file();
+    let filename = file.path.as_str();
+    let segment = file.segment_of(state.current_conflict);
+    let conflict = segment.and_then(|i| match &file.segments[i] {
+        Segment::Conflict(conflict) => Some(conflict),
+        Segment::Common(_) => None,
+    });
+    let Some((segment, conflict)) = segment.zip(conflict) else {
+        return;
+    };
+    let common = |index: Option<usize>| match index.and_then(|i| file.segments.get(i)) {
+        Some(Segment::Common(lines)) => lines.as_slice(),
+        _ => Default::default(),
+    };
+    let before = common(segment.checked_sub(1));
+    let before = &before[before.len().saturating_sub(CONTEXT_LINES)..];
+    let after = common(Some(segment + 1));
+    let after = &after[..after.len().min(CONTEXT_LINES)];
+
+    let side = |lines: &[String], bg: Color| -> Vec<Line<'static>> {
+        let mut rows = context_lines(before, filename);
+        rows.extend(lines.iter().map(|line| code_line(line, filename, Some(bg))));
+        rows.extend(context_lines(after, filename));
+        rows
+    };
+
+    let area = frame.area();
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(55),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Ratio(1, 3); 3])
+        .split(rows[0]);
+
+    let picked = conflict.picked();
+    let border_style = Style::default().fg(t.ui.border_unfocused);
+    let panel = |title: &str, pick: Pick| {
+        let (title, style) = if picked == Some(pick) {
+            (
+                format!(" {title} ✓ "),
+                Style::default().fg(t.ui.border_focused),
+            )
+        } else {
+            (format!(" {title} "), border_style)
+        };
+        Block::default()
+            .title(Line::styled(title, style))
+            .borders(Borders::ALL)
+            .border_style(border_style)
+    };
+
+    let base_lines = match &conflict.base {
+        Some(base) => side(base, t.diff.context_bg),
+        None => vec![Line::styled(
+            "No base in the conflict markers",
+            Style::default().fg(t.ui.text_muted),
+        )],
+    };
+    let panels = [
+        (
+            side(&conflict.ours, t.diff.deleted_bg),
+            panel("Ours", Pick::Ours),
+        ),
+        (base_lines, panel("Base", Pick::Base)),
+        (
+            side(&conflict.theirs, t.diff.added_bg),
+            panel("Theirs", Pick::Theirs),
+        ),
+    ];
+    for ((lines, block), area) in panels.into_iter().zip(columns.iter()) {
+        let para = Paragraph::new(lines).scroll((state.scroll, 0)).block(block);
+        frame.render_widget(para, *area);
+    }
+
+    let (result_title, result_lines) = match &conflict.resolution {
+        Some(lines) => {
+            let how = match picked {
+                Some(Pick::Ours) => " (ours)",
+                Some(Pick::Theirs) => " (theirs)",
+                Some(Pick::Both) => " (both)",
+                Some(Pick::Base) => " (base)",
+                None => " (edited)",
+            };
+            let mut rows = context_lines(before, filename);
+            rows.extend(lines.iter().map(|line| code_line(line, filename, None)));
+            rows.extend(context_lines(after, filename));
+            (format!(" Result{how} "), rows)
+        }
+        None => (
+            " Result ".to_string(),
+            vec![Line::styled(
+                "Unresolved: o ours · t theirs · b both · B base · e edit",
+                Style::default().fg(t.ui.text_muted),
+            )],
+        ),
+    };
+    let result = Paragraph::new(result_lines)
+        .scroll((state.scroll, 0))
+        .block(
+            Block::default()
+                .title(Line::styled(
+                    result_title,
+                    Style::default().fg(t.ui.border_focused),
+                ))
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+    frame.render_widget(result, rows[1]);
+
+    let bg = t.ui.footer_bg;
+    let progress = format!(
+        " file {}/{} · conflict {}/{} · {} open ",
+        state.current_file + 1,
+        state.files.len(),
+        state.current_conflict + 1,
+        file.conflicts().count(),
+        file.unresolved()
+    );
+    let hint = state.status_message.clone().unwrap_or_else(|| {
+        "o/t/b/B pick · u undo · e edit · n/N conflict · ]/[ file · w write + git add · q quit"
+            .to_string()
+    });
+    let footer = Line::from(vec![
+        Span::styled(
+            format!(" {filename} "),
+            Style::default()
+                .fg(t.ui.footer_branch_fg)
+                .bg(t.ui.footer_branch_bg),
+        ),
+        Span::styled(progress, Style::default().fg(t.ui.text_primary).bg(bg)),
+        Span::styled(hint, Style::default().fg(t.ui.text_muted).bg(bg)),
+    ]);
+    frame.render_widget(
+        Paragraph::new(footer).style(Style::default().bg(bg)),
+        rows[2],
+    );
+}
@@ -1,96 +1,322 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use ratatui::{
     prelude::*,
     widgets::{Block, Borders, Paragraph},
 };
 
-use crate::command::diff::context::{compute_context_lines, ContextLine};
-use crate::command::diff::diff_algo::compute_side_by_side;
-use crate::command::diff::highlight::highlight_line_spans;
+use crate::command::diff::blame::{self, FileBlame};
+use crate::command::diff::context::{compute_context_lines, ContextConfig, ContextLine};
+use crate::command::diff::file_summaries::FileSummaries;
+use crate::command::diff::highlight::{highlight_line_spans, spans_of};
+use crate::command::diff::risk::{RiskLevel, RiskScores};
 use crate::command::diff::search::{MatchPanel, SearchState};
+use crate::command::diff::stage::IndexState;
+use crate::command::diff::state::FileView;
 use crate::command::diff::theme;
 use crate::command::diff::types::{
-    ChangeType, DiffFullscreen, DiffLine, DiffViewSettings, FileDiff, FocusedPanel, SidebarItem,
+    ChangeType, DiffFullscreen, DiffLine, DiffViewSettings, FileDiff, FocusedPanel, LineNote,
+    SidebarItem,
 };
+use crate::command::diff::width::{ceil_char_boundary, char_width, floor_char_boundary};
 use crate::command::diff::PrInfo;
 
 use super::footer::{render_footer, FooterData};
-use super::sidebar::render_sidebar;
+use super::minimap::{minimap_area, render_minimap};
+use super::sidebar::{render_sidebar, render_summary_tooltip};
+
+/// Where parts of the diff were drawn, for the mouse and for images.
+#[derive(Default)]
+pub struct DiffAreas {
+    /// The area left free for an image preview of a binary file, if it has one
+    pub preview: Option<Rect>,
+    /// The minimaps on the right edge of the panels
+    pub minimaps: Vec<Rect>,
+}
 
 pub struct LineStats {
     pub added: usize,
     pub removed: usize,
 }
 
+/// Swap the space at each indentation level of `text` for a faint `│`, keeping the
+/// highlighting of the rest of the line.
+fn with_indent_guides<'a>(spans: Vec<Span<'a>>, text: &str, tab_width: usize) -> Vec<Span<'a>> {
+    let indent = text.len() - text.trim_start_matches(' ').len();
+    if tab_width == 0 || indent == 0 {
+        return spans;
+    }
+    let guide_fg = theme::get().diff.empty_placeholder_fg;
+    let mut result = Vec::with_capacity(spans.len() + indent / tab_width * 2);
+    let mut col = 0;
+    for span in spans {
+        let content = span.content.to_string();
+        let mut start = 0;
+        // The indentation is all spaces, so byte offsets there are columns
+        for (i, c) in content.char_indices() {
+            if col + i >= indent {
+                break;
+            }
+            if c == ' ' && (col + i) % tab_width == 0 {
+                if i > start {
+                    result.push(Span::styled(content[start..i].to_string(), span.style));
+                }
+                result.push(Span::styled("│", span.style.fg(guide_fg)));
+                start = i + 1;
+            }
+        }
+        col += content.len();
+        if start == 0 {
+            result.push(span);
+        } else if start < content.len() {
+            result.push(Span::styled(content[start..].to_string(), span.style));
+        }
+    }
+    result
+}
+
+/// Set the search matches apart in a line's spans. Matches are byte ranges of the whole line,
+/// moved out to the nearest char boundaries so a match never splits a character.
 fn apply_search_highlight<'a>(
-    text: &str,
-    filename: &str,
-    bg: Option<Color>,
+    base_spans: Vec<Span<'a>>,
     match_ranges: &[(usize, usize, bool)],
 ) -> Vec<Span<'a>> {
-    let t = theme::get();
-
     if match_ranges.is_empty() {
-        return highlight_line_spans(text, filename, bg);
+        return base_spans;
     }
+    let t = theme::get();
 
-    let base_spans = highlight_line_spans(text, filename, bg);
     let mut result: Vec<Span<'a>> = Vec::new();
-    let mut char_pos = 0;
-
+    // Byte offset of the span in the line
+    let mut offset = 0;
     for span in base_spans {
-        let span_text = span.content.to_string();
-        let span_len = span_text.len();
-        let span_end = char_pos + span_len;
-
-        let mut current_pos = 0;
-        let mut remaining = span_text.as_str();
-
+        let text = span.content.to_string();
+        let end = offset + text.len();
+        // Bytes of the span already pushed
+        let mut done = 0;
         for &(match_start, match_end, is_current) in match_ranges {
-            if match_end <= char_pos || match_start >= span_end {
+            if match_end <= offset || match_start >= end {
+                continue;
+            }
+            let from = floor_char_boundary(&text, match_start.saturating_sub(offset));
+            let from = from.max(done);
+            let to = ceil_char_boundary(&text, match_end - offset);
+            if to <= from {
                 continue;
             }
+            if from > done {
+                result.push(Span::styled(text[done..from].to_string(), span.style));
+            }
+            let (fg, bg) = if is_current {
+                (t.ui.search_current_fg, t.ui.search_current_bg)
+            } else {
+                (t.ui.search_match_fg, t.ui.search_match_bg)
+            };
+            result.push(Span::styled(
+                text[from..to].to_string(),
+                Style::default().fg(fg).bg(bg).bold(),
+            ));
+            done = to;
+        }
+        if done == 0 {
+            result.push(span);
+        } else if done < text.len() {
+            result.push(Span::styled(text[done..].to_string(), span.style));
+        }
+        offset = end;
+    }
 
-            let rel_start = match_start.saturating_sub(char_pos);
-            let rel_end = (match_end - char_pos).min(span_len);
+    result
+}
 
-            if rel_start > current_pos {
-                let before = &remaining[..(rel_start - current_pos)];
-                if !before.is_empty() {
-                    result.push(Span::styled(before.to_string(), span.style));
-                }
-            }
+/// Add a gutter-prefixed row for a single panel of `area_width`, wrapped if `wrap` is set.
+fn push_row<'a>(lines: &mut Vec<Line<'a>>, spans: Vec<Span<'a>>, wrap: bool, area_width: u16) {
+    if wrap {
+        lines.extend(wrap_row(spans, area_width.saturating_sub(2) as usize));
+    } else {
+        lines.push(Line::from(spans));
+    }
+}
 
-            let match_portion_start = rel_start.max(current_pos) - current_pos;
-            let match_portion_end = rel_end - current_pos;
-            if match_portion_end > match_portion_start {
-                let match_text = &remaining[match_portion_start..match_portion_end];
-                if !match_text.is_empty() {
-                    let (fg, bg) = if is_current {
-                        (t.ui.search_current_fg, t.ui.search_current_bg)
-                    } else {
-                        (t.ui.search_match_fg, t.ui.search_match_bg)
-                    };
-                    result.push(Span::styled(
-                        match_text.to_string(),
-                        Style::default().fg(fg).bg(bg).bold(),
-                    ));
+/// Rows for the notes pinned to line `num` of the new version of `filename`, wrapped to
+/// `width` columns.
+fn note_rows<'a>(notes: &'a [LineNote], filename: &str, num: usize, width: usize) -> Vec<Line<'a>> {
+    let t = theme::get();
+    let mut rows = Vec::new();
+    for note in notes
+        .iter()
+        .filter(|note| note.file == filename && note.line == num)
+    {
+        let color = match note.level {
+            RiskLevel::High => t.ui.risk_high,
+            RiskLevel::Medium => t.ui.risk_medium,
+            RiskLevel::Low => t.ui.text_muted,
+        };
+        for (i, line) in note.text.lines().enumerate() {
+            let style = if i == 0 {
+                Style::default().fg(color).bold()
+            } else {
+                Style::default().fg(t.ui.text_secondary)
+            };
+            rows.extend(wrap_row(
+                vec![
+                    Span::styled("     ▎ ", Style::default().fg(color)),
+                    Span::styled(line, style),
+                ],
+                width,
+            ));
+        }
+    }
+    rows
+}
+
+/// Split a row into lines at most `width` columns wide. The first span is the line number
+/// gutter; continuation lines get a blank gutter of the same width so the text stays aligned.
+/// A character two columns wide that would stick out goes to the next line.
+fn wrap_row<'a>(spans: Vec<Span<'a>>, width: usize) -> Vec<Line<'a>> {
+    let mut spans = spans.into_iter();
+    let Some(gutter) = spans.next() else {
+        return vec![Line::default()];
+    };
+    let gutter_width = gutter.width();
+    let text_width = width.saturating_sub(gutter_width).max(1);
+    let blank_gutter = Span::styled(format!("{:>gutter_width$}", "| "), gutter.style);
+
+    let mut rows = vec![vec![gutter]];
+    let mut used = 0;
+    for span in spans {
+        let mut head = String::new();
+        for c in span.content.chars() {
+            let columns = char_width(c);
+            if used > 0 && used + columns > text_width {
+                if let Some(row) = rows.last_mut().filter(|_| !head.is_empty()) {
+                    row.push(Span::styled(std::mem::take(&mut head), span.style));
                 }
+                rows.push(vec![blank_gutter.clone()]);
+                used = 0;
             }
+            head.push(c);
+            used += columns;
+        }
+        if let Some(row) = rows.last_mut().filter(|_| !head.is_empty()) {
+            row.push(Span::styled(head, span.style));
+        }
+    }
+    rows.into_iter().map(Line::from).collect()
+}
 
-            remaining = &remaining[(rel_end - current_pos).min(remaining.len())..];
-            current_pos = rel_end;
+/// The old and new panels side by side in `main_area`, either of which may be hidden.
+fn split_panels(main_area: Rect, diff_fullscreen: DiffFullscreen) -> (Option<Rect>, Option<Rect>) {
+    match diff_fullscreen {
+        DiffFullscreen::OldOnly => (Some(main_area), None),
+        DiffFullscreen::NewOnly => (None, Some(main_area)),
+        DiffFullscreen::None => {
+            let content_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(main_area);
+            (Some(content_chunks[0]), Some(content_chunks[1]))
         }
+    }
+}
 
-        if !remaining.is_empty() {
-            result.push(Span::styled(remaining.to_string(), span.style));
+/// How many lines of a panel `width` columns wide a line of `text` takes when wrapped,
+/// after a gutter of `gutter` columns, as [`wrap_row`] splits it.
+fn wrapped_lines(text: &str, width: usize, gutter: usize) -> usize {
+    let text_width = width.saturating_sub(gutter).max(1);
+    let mut lines = 1;
+    let mut used = 0;
+    for c in text.chars() {
+        let columns = char_width(c);
+        if used > 0 && used + columns > text_width {
+            lines += 1;
+            used = 0;
         }
+        used += columns;
+    }
+    lines
+}
 
-        char_pos = span_end;
+/// How many screen lines each row of the diff takes in a terminal of `area`, laid out the
+/// way [`render_diff`] lays it out. Rows take one line each unless long lines are wrapped.
+#[allow(clippy::too_many_arguments)]
+pub fn row_heights(
+    area: Rect,
+    diff: &FileDiff,
+    side_by_side: &[DiffLine],
+    settings: &DiffViewSettings,
+    show_sidebar: bool,
+    diff_fullscreen: DiffFullscreen,
+    blame: Option<&FileBlame>,
+) -> Vec<usize> {
+    if !settings.wrap || diff.binary.is_some() {
+        return vec![1; side_by_side.len()];
     }
+    let sidebar_width = if show_sidebar {
+        settings.sidebar_width(area.width)
+    } else {
+        0
+    };
+    let main_area = Rect {
+        x: area.x + sidebar_width,
+        width: area.width.saturating_sub(sidebar_width),
+        height: area.height.saturating_sub(1),
+        ..area
+    };
+    let width = main_area.width.saturating_sub(2) as usize;
+    let blame_width = blame
+        .filter(|blame| blame.old.is_some() || blame.new.is_some())
+        .map_or(0, |_| blame::GUTTER_WIDTH);
+    // `{:4}` grows past four digits
+    let gutter = |num: usize| blame_width + num.to_string().len().max(4) + 3;
+    let side = |line: &Option<(usize, String)>, width: usize| {
+        line.as_ref()
+            .map_or(0, |(num, text)| wrapped_lines(text, width, gutter(*num)))
+    };
 
-    result
+    let is_new_file = diff.old_content.is_empty() && !diff.new_content.is_empty();
+    let is_deleted_file = !diff.old_content.is_empty() && diff.new_content.is_empty();
+    if is_new_file {
+        return side_by_side
+            .iter()
+            .map(|line| side(&line.new_line, width))
+            .collect();
+    }
+    if is_deleted_file {
+        return side_by_side
+            .iter()
+            .map(|line| side(&line.old_line, width))
+            .collect();
+    }
+    if settings.unified && diff_fullscreen == DiffFullscreen::None {
+        // Both line numbers go in the gutter, and a changed row is its old line then its new one
+        let unified = |line: &Option<(usize, String)>| {
+            line.as_ref().map_or(0, |(num, text)| {
+                wrapped_lines(text, width, gutter(*num) + 5)
+            })
+        };
+        return side_by_side
+            .iter()
+            .map(|line| match line.change_type {
+                ChangeType::Equal => unified(&line.new_line).max(1),
+                _ => unified(&line.old_line) + unified(&line.new_line),
+            })
+            .collect();
+    }
+
+    let (old_area, new_area) = split_panels(main_area, diff_fullscreen);
+    let old_width = old_area.map(|area| area.width.saturating_sub(2) as usize);
+    // The new panel shares its left border with the old one when both are shown
+    let new_border = if old_area.is_some() { 1 } else { 2 };
+    let new_width = new_area.map(|area| area.width.saturating_sub(new_border) as usize);
+    side_by_side
+        .iter()
+        .map(|line| {
+            let old = old_width.map_or(0, |width| side(&line.old_line, width));
+            let new = new_width.map_or(0, |width| side(&line.new_line, width));
+            old.max(new).max(1)
+        })
+        .collect()
 }
 
 pub fn compute_line_stats(side_by_side: &[DiffLine]) -> LineStats {
@@ -157,8 +383,9 @@ fn render_context_lines(
 pub fn render_diff(
     frame: &mut Frame,
     diff: &FileDiff,
-    _file_diffs: &[FileDiff],
+    view: &FileView,
     sidebar_items: &[SidebarItem],
+    sidebar_filter: Option<&str>,
     current_file: usize,
     scroll: u16,
     h_scroll: u16,
@@ -169,25 +396,64 @@ pub fn render_diff(
     sidebar_scroll: usize,
     sidebar_h_scroll: u16,
     viewed_files: &HashSet<usize>,
+    file_count: usize,
+    risk: Option<&RiskScores>,
+    summaries: Option<&FileSummaries>,
     settings: &DiffViewSettings,
     hunk_count: usize,
     diff_fullscreen: DiffFullscreen,
     search_state: &SearchState,
     branch: &str,
     pr_info: Option<&PrInfo>,
-) {
+    selection: Option<std::ops::RangeInclusive<usize>>,
+    staged_rows: &[std::ops::RangeInclusive<usize>],
+    index_states: &HashMap<usize, IndexState>,
+    notes: &[LineNote],
+    blame: Option<&FileBlame>,
+    history: Option<(String, String)>,
+    status_message: Option<&str>,
+    loading: &HashSet<usize>,
+) -> DiffAreas {
     let area = frame.area();
-    let side_by_side =
-        compute_side_by_side(&diff.old_content, &diff.new_content, settings.tab_width);
-    let line_stats = compute_line_stats(&side_by_side);
+    // Wrapped lines never extend past the panel, so there's nothing to scroll sideways
+    let h_scroll = if settings.wrap { 0 } else { h_scroll };
+    let tab_width = settings.tab_width_for(&diff.filename);
+    let side_by_side = &view.rows;
+    // Context lines parse the whole file each frame, which large files are spared
+    let context = ContextConfig {
+        enabled: settings.context.enabled && !view.plain,
+        ..settings.context.clone()
+    };
+    let line_stats = compute_line_stats(side_by_side);
+    // Blame goes in front of the line numbers of each side
+    let now = blame::now();
+    let blamed = |old: bool, num: Option<usize>| {
+        blame.map_or_else(String::new, |blame| blame.gutter(old, num, now))
+    };
+    // Rows that have been on screen are highlighted already
+    let code_spans =
+        |row: usize, old: bool, text: &str, bg: Option<Color>, matches: &[(usize, usize, bool)]| {
+            let spans = match view.highlighted(row, old) {
+                Some(pieces) => spans_of(pieces, bg),
+                None if view.plain => spans_of(&[(text.to_string(), None)], bg),
+                None => highlight_line_spans(text, &diff.filename, bg),
+            };
+            let spans = apply_search_highlight(spans, matches);
+            if settings.indent_guides {
+                with_indent_guides(spans, text, tab_width)
+            } else {
+                spans
+            }
+        };
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Min(0), Constraint::Length(1)])
         .split(area);
 
+    let mut sidebar_area = None;
     let main_area = if show_sidebar {
-        let sidebar_width = (area.width / 4).min(35).max(20);
+        let sidebar_width = settings.sidebar_width(area.width);
         let main_chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Length(sidebar_width), Constraint::Min(0)])
@@ -202,8 +468,14 @@ pub fn render_diff(
             sidebar_scroll,
             sidebar_h_scroll,
             viewed_files,
+            risk,
+            summaries,
+            index_states,
             focused_panel == FocusedPanel::Sidebar,
+            sidebar_filter,
+            loading,
         );
+        sidebar_area = Some(main_chunks[0]);
 
         main_chunks[1]
     } else {
@@ -221,14 +493,108 @@ pub fn render_diff(
         Style::default().fg(t.ui.border_unfocused)
     };
 
-    if is_new_file {
+    let mut areas = DiffAreas::default();
+    let mut minimap = |frame: &mut Frame, area: Rect, panel: Option<MatchPanel>, visible| {
+        render_minimap(
+            frame,
+            area,
+            side_by_side,
+            search_state,
+            panel,
+            scroll as usize,
+            visible,
+        );
+        areas.minimaps.push(minimap_area(area));
+    };
+    if loading.contains(&current_file) {
+        let para = Paragraph::new(Line::styled(
+            format!("Reading {}...", diff.filename),
+            Style::default().fg(t.ui.text_muted),
+        ))
+        .block(
+            Block::default()
+                .title(Line::styled(" [2] Loading ", title_style))
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+        frame.render_widget(para, main_area);
+    } else if let Some(lines) = view.held_back {
+        let block = Block::default()
+            .title(Line::styled(" [2] Large File ", title_style))
+            .borders(Borders::ALL)
+            .border_style(border_style);
+        let para = Paragraph::new(vec![
+            Line::styled(
+                format!(
+                    "{lines} lines, more than the {} the viewer diffs right away",
+                    settings.large_file_lines
+                ),
+                Style::default().fg(t.ui.text_muted),
+            ),
+            Line::default(),
+            Line::styled(
+                "Press enter to load it (without syntax highlighting)",
+                Style::default().fg(t.ui.text_secondary),
+            ),
+        ])
+        .block(block);
+        frame.render_widget(para, main_area);
+    } else if let Some(submodule) = &diff.submodule {
+        let block = Block::default()
+            .title(Line::styled(" [2] Submodule ", title_style))
+            .borders(Borders::ALL)
+            .border_style(border_style);
+        let mut lines: Vec<Line> = submodule
+            .describe()
+            .into_iter()
+            .map(|line| {
+                let fg = match line.chars().next() {
+                    Some('-') => t.ui.status_deleted,
+                    Some('+') => t.ui.status_added,
+                    _ => t.ui.text_muted,
+                };
+                Line::styled(line, Style::default().fg(fg))
+            })
+            .collect();
+        let hint = match (submodule.range(), &submodule.old, &submodule.new) {
+            (Some(_), _, _) => Some("Press enter to open the changes between them"),
+            (None, Some(_), Some(_)) => {
+                Some("Check out the submodule with both commits to see the changes between them")
+            }
+            _ => None,
+        };
+        if let Some(hint) = hint {
+            lines.push(Line::default());
+            lines.push(Line::styled(hint, Style::default().fg(t.ui.text_secondary)));
+        }
+        frame.render_widget(Paragraph::new(lines).block(block), main_area);
+    } else if let Some(binary) = &diff.binary {
+        let block = Block::default()
+            .title(Line::styled(" [2] Binary ", title_style))
+            .borders(Borders::ALL)
+            .border_style(border_style);
+        let inner = block.inner(main_area);
+        let para = Paragraph::new(Line::styled(
+            binary.describe(),
+            Style::default().fg(t.ui.text_muted),
+        ))
+        .block(block);
+        frame.render_widget(para, main_area);
+        if binary.preview.is_some() {
+            areas.preview = Some(Rect {
+                y: inner.y + 2,
+                height: inner.height.saturating_sub(2),
+                ..inner
+            });
+        }
+    } else if is_new_file {
         let visible_height = main_area.height.saturating_sub(2) as usize;
         let new_context = compute_context_lines(
             &diff.new_content,
             &diff.filename,
             scroll as usize,
-            &settings.context,
-            settings.tab_width,
+            &context,
+            tab_width,
         );
         let context_count = new_context.len();
         let content_height = visible_height.saturating_sub(context_count);
@@ -240,14 +606,14 @@ pub fn render_diff(
             .collect();
 
         let mut new_lines: Vec<Line> = Vec::new();
-        if settings.context.enabled && context_count > 0 {
+        if context.enabled && context_count > 0 {
             render_context_lines(&new_context, context_count, &mut new_lines, &diff.filename);
         }
 
         for (i, diff_line) in visible_lines.iter().enumerate() {
             let line_idx = scroll as usize + i;
             if let Some((num, text)) = &diff_line.new_line {
-                let prefix = format!("{:4} | ", num);
+                let prefix = format!("{}{:4} | ", blamed(false, Some(*num)), num);
                 let mut spans: Vec<Span> = vec![Span::styled(
                     prefix,
                     Style::default()
@@ -255,13 +621,20 @@ pub fn render_diff(
                         .bg(t.diff.added_gutter_bg),
                 )];
                 let matches = search_state.get_matches_for_line(line_idx, MatchPanel::New);
-                spans.extend(apply_search_highlight(
+                spans.extend(code_spans(
+                    line_idx,
+                    false,
                     text,
-                    &diff.filename,
                     Some(t.diff.added_bg),
                     &matches,
                 ));
-                new_lines.push(Line::from(spans));
+                push_row(&mut new_lines, spans, settings.wrap, main_area.width);
+                new_lines.extend(note_rows(
+                    notes,
+                    &diff.filename,
+                    *num,
+                    main_area.width.saturating_sub(2) as usize,
+                ));
             }
         }
 
@@ -272,14 +645,15 @@ pub fn render_diff(
                 .border_style(border_style),
         );
         frame.render_widget(new_para, main_area);
+        minimap(frame, main_area, Some(MatchPanel::New), content_height);
     } else if is_deleted_file {
         let visible_height = main_area.height.saturating_sub(2) as usize;
         let old_context = compute_context_lines(
             &diff.old_content,
             &diff.filename,
             scroll as usize,
-            &settings.context,
-            settings.tab_width,
+            &context,
+            tab_width,
         );
         let context_count = old_context.len();
         let content_height = visible_height.saturating_sub(context_count);
@@ -291,14 +665,14 @@ pub fn render_diff(
             .collect();
 
         let mut old_lines: Vec<Line> = Vec::new();
-        if settings.context.enabled && context_count > 0 {
+        if context.enabled && context_count > 0 {
             render_context_lines(&old_context, context_count, &mut old_lines, &diff.filename);
         }
 
         for (i, diff_line) in visible_lines.iter().enumerate() {
             let line_idx = scroll as usize + i;
             if let Some((num, text)) = &diff_line.old_line {
-                let prefix = format!("{:4} | ", num);
+                let prefix = format!("{}{:4} | ", blamed(true, Some(*num)), num);
                 let mut spans: Vec<Span> = vec![Span::styled(
                     prefix,
                     Style::default()
@@ -306,13 +680,14 @@ pub fn render_diff(
                         .bg(t.diff.deleted_gutter_bg),
                 )];
                 let matches = search_state.get_matches_for_line(line_idx, MatchPanel::Old);
-                spans.extend(apply_search_highlight(
+                spans.extend(code_spans(
+                    line_idx,
+                    true,
                     text,
-                    &diff.filename,
                     Some(t.diff.deleted_bg),
                     &matches,
                 ));
-                old_lines.push(Line::from(spans));
+                push_row(&mut old_lines, spans, settings.wrap, main_area.width);
             }
         }
 
@@ -323,32 +698,122 @@ pub fn render_diff(
                 .border_style(border_style),
         );
         frame.render_widget(old_para, main_area);
-    } else {
-        let (old_area, new_area) = match diff_fullscreen {
-            DiffFullscreen::OldOnly => (Some(main_area), None),
-            DiffFullscreen::NewOnly => (None, Some(main_area)),
-            DiffFullscreen::None => {
-                let content_chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
-                    .split(main_area);
-                (Some(content_chunks[0]), Some(content_chunks[1]))
+        minimap(frame, main_area, Some(MatchPanel::Old), content_height);
+    } else if settings.unified && diff_fullscreen == DiffFullscreen::None {
+        let visible_height = main_area.height.saturating_sub(2) as usize;
+        let new_context = compute_context_lines(
+            &diff.new_content,
+            &diff.filename,
+            scroll as usize,
+            &context,
+            tab_width,
+        );
+        let context_count = new_context.len();
+
+        let mut lines: Vec<Line> = Vec::new();
+        if context.enabled && context_count > 0 {
+            render_context_lines(&new_context, context_count, &mut lines, &diff.filename);
+        }
+
+        // Scrolling still counts rows, but a modified row takes two lines here: old, then new
+        for (line_idx, diff_line) in side_by_side.iter().enumerate().skip(scroll as usize) {
+            if lines.len() >= visible_height {
+                break;
             }
-        };
+            let selected = selection
+                .as_ref()
+                .is_some_and(|rows| rows.contains(&line_idx));
+            let staged = staged_rows.iter().any(|rows| rows.contains(&line_idx));
+            let gutter_style = |fg: Color, bg: Color| {
+                if selected {
+                    Style::default().fg(t.ui.selection_fg).bg(t.ui.selection_bg)
+                } else {
+                    Style::default().fg(fg).bg(bg)
+                }
+            };
+
+            if let (ChangeType::Equal, Some((old_num, _)), Some((new_num, text))) = (
+                diff_line.change_type,
+                &diff_line.old_line,
+                &diff_line.new_line,
+            ) {
+                let mut spans = vec![Span::styled(
+                    format!(
+                        "{}{old_num:4} {new_num:4} | ",
+                        blamed(false, Some(*new_num))
+                    ),
+                    gutter_style(t.ui.line_number, Color::Reset),
+                )];
+                let matches = search_state.get_matches_for_line(line_idx, MatchPanel::New);
+                spans.extend(code_spans(line_idx, false, text, None, &matches));
+                push_row(&mut lines, spans, settings.wrap, main_area.width);
+            } else {
+                if let Some((num, text)) = &diff_line.old_line {
+                    let marker = if staged { "●" } else { "-" };
+                    let (bg, gutter_bg) = if view.is_moved(*num, true) {
+                        (t.diff.moved_bg, t.diff.moved_bg)
+                    } else {
+                        (t.diff.deleted_bg, t.diff.deleted_gutter_bg)
+                    };
+                    let mut spans = vec![Span::styled(
+                        format!("{}{num:4}      {marker} ", blamed(true, Some(*num))),
+                        gutter_style(t.diff.deleted_gutter_fg, gutter_bg),
+                    )];
+                    let matches = search_state.get_matches_for_line(line_idx, MatchPanel::Old);
+                    spans.extend(code_spans(line_idx, true, text, Some(bg), &matches));
+                    push_row(&mut lines, spans, settings.wrap, main_area.width);
+                }
+                if let Some((num, text)) = &diff_line.new_line {
+                    let marker = if staged { "●" } else { "+" };
+                    let (bg, gutter_bg) = if view.is_moved(*num, false) {
+                        (t.diff.moved_bg, t.diff.moved_bg)
+                    } else {
+                        (t.diff.added_bg, t.diff.added_gutter_bg)
+                    };
+                    let mut spans = vec![Span::styled(
+                        format!("{}     {num:4} {marker} ", blamed(false, Some(*num))),
+                        gutter_style(t.diff.added_gutter_fg, gutter_bg),
+                    )];
+                    let matches = search_state.get_matches_for_line(line_idx, MatchPanel::New);
+                    spans.extend(code_spans(line_idx, false, text, Some(bg), &matches));
+                    push_row(&mut lines, spans, settings.wrap, main_area.width);
+                }
+            }
+
+            if let Some((num, _)) = &diff_line.new_line {
+                lines.extend(note_rows(
+                    notes,
+                    &diff.filename,
+                    *num,
+                    main_area.width.saturating_sub(2) as usize,
+                ));
+            }
+        }
+
+        let para = Paragraph::new(lines).scroll((0, h_scroll)).block(
+            Block::default()
+                .title(Line::styled(" [2] Unified ", title_style))
+                .borders(Borders::ALL)
+                .border_style(border_style),
+        );
+        frame.render_widget(para, main_area);
+        minimap(frame, main_area, None, visible_height);
+    } else {
+        let (old_area, new_area) = split_panels(main_area, diff_fullscreen);
 
         let old_context = compute_context_lines(
             &diff.old_content,
             &diff.filename,
             scroll as usize,
-            &settings.context,
-            settings.tab_width,
+            &context,
+            tab_width,
         );
         let new_context = compute_context_lines(
             &diff.new_content,
             &diff.filename,
             scroll as usize,
-            &settings.context,
-            settings.tab_width,
+            &context,
+            tab_width,
         );
         let context_count = old_context.len().max(new_context.len());
 
@@ -366,7 +831,7 @@ pub fn render_diff(
         let mut old_lines: Vec<Line> = Vec::new();
         let mut new_lines: Vec<Line> = Vec::new();
 
-        if settings.context.enabled && context_count > 0 {
+        if context.enabled && context_count > 0 {
             if old_area.is_some() {
                 render_context_lines(&old_context, context_count, &mut old_lines, &diff.filename);
             }
@@ -377,6 +842,15 @@ pub fn render_diff(
 
         for (i, diff_line) in visible_lines.iter().enumerate() {
             let line_idx = scroll_usize + i;
+            let selected = selection
+                .as_ref()
+                .is_some_and(|rows| rows.contains(&line_idx));
+            // Staged hunks get a dot in place of the gutter bar
+            let bar = if staged_rows.iter().any(|rows| rows.contains(&line_idx)) {
+                "●"
+            } else {
+                "|"
+            };
             let (old_bg, old_gutter_bg, old_gutter_fg, new_bg, new_gutter_bg, new_gutter_fg) =
                 match diff_line.change_type {
                     ChangeType::Equal => (None, None, None, None, None, None),
@@ -405,12 +879,38 @@ pub fn render_diff(
                         Some(t.diff.added_gutter_fg),
                     ),
                 };
+            // Moved definitions keep their gutter text but trade the red and green for
+            // the moved color
+            let moved = |num: &Option<(usize, String)>, old: bool| {
+                num.as_ref().is_some_and(|(n, _)| view.is_moved(*n, old))
+            };
+            let (old_bg, old_gutter_bg) = if old_bg.is_some() && moved(&diff_line.old_line, true) {
+                (Some(t.diff.moved_bg), Some(t.diff.moved_bg))
+            } else {
+                (old_bg, old_gutter_bg)
+            };
+            let (new_bg, new_gutter_bg) = if new_bg.is_some() && moved(&diff_line.new_line, false) {
+                (Some(t.diff.moved_bg), Some(t.diff.moved_bg))
+            } else {
+                (new_bg, new_gutter_bg)
+            };
+            // Selected rows swap the gutter colors so the selection reads at a glance
+            let (old_gutter_bg, old_gutter_fg, new_gutter_bg, new_gutter_fg) = if selected {
+                (
+                    Some(t.ui.selection_bg),
+                    Some(t.ui.selection_fg),
+                    Some(t.ui.selection_bg),
+                    Some(t.ui.selection_fg),
+                )
+            } else {
+                (old_gutter_bg, old_gutter_fg, new_gutter_bg, new_gutter_fg)
+            };
 
+            let mut old_spans: Vec<Span> = Vec::new();
             if old_area.is_some() {
-                let mut old_spans: Vec<Span> = Vec::new();
                 match &diff_line.old_line {
                     Some((num, text)) => {
-                        let prefix = format!("{:4} | ", num);
+                        let prefix = format!("{}{:4} {bar} ", blamed(true, Some(*num)), num);
                         old_spans.push(Span::styled(
                             prefix,
                             Style::default()
@@ -418,28 +918,22 @@ pub fn render_diff(
                                 .bg(old_gutter_bg.unwrap_or(Color::Reset)),
                         ));
                         let matches = search_state.get_matches_for_line(line_idx, MatchPanel::Old);
-                        old_spans.extend(apply_search_highlight(
-                            text,
-                            &diff.filename,
-                            old_bg,
-                            &matches,
-                        ));
+                        old_spans.extend(code_spans(line_idx, true, text, old_bg, &matches));
                     }
                     None => {
                         old_spans.push(Span::styled(
-                            "     |",
-                            Style::default().fg(t.ui.line_number),
+                            format!("{}     |", blamed(true, None)),
+                            Style::default().fg(t.diff.empty_placeholder_fg),
                         ));
                     }
                 }
-                old_lines.push(Line::from(old_spans));
             }
 
+            let mut new_spans: Vec<Span> = Vec::new();
             if new_area.is_some() {
-                let mut new_spans: Vec<Span> = Vec::new();
                 match &diff_line.new_line {
                     Some((num, text)) => {
-                        let prefix = format!("{:4} | ", num);
+                        let prefix = format!("{}{:4} {bar} ", blamed(false, Some(*num)), num);
                         new_spans.push(Span::styled(
                             prefix,
                             Style::default()
@@ -447,21 +941,58 @@ pub fn render_diff(
                                 .bg(new_gutter_bg.unwrap_or(Color::Reset)),
                         ));
                         let matches = search_state.get_matches_for_line(line_idx, MatchPanel::New);
-                        new_spans.extend(apply_search_highlight(
-                            text,
-                            &diff.filename,
-                            new_bg,
-                            &matches,
-                        ));
+                        new_spans.extend(code_spans(line_idx, false, text, new_bg, &matches));
                     }
                     None => {
                         new_spans.push(Span::styled(
-                            "     |",
-                            Style::default().fg(t.ui.line_number),
+                            format!("{}     |", blamed(false, None)),
+                            Style::default().fg(t.diff.empty_placeholder_fg),
                         ));
                     }
                 }
-                new_lines.push(Line::from(new_spans));
+            }
+
+            if settings.wrap {
+                let old_rows = old_area.map_or(Vec::new(), |area| {
+                    wrap_row(old_spans, area.width.saturating_sub(2) as usize)
+                });
+                // The new panel shares its left border with the old one when both are shown
+                let new_border = if old_area.is_some() { 1 } else { 2 };
+                let new_rows = new_area.map_or(Vec::new(), |area| {
+                    wrap_row(new_spans, area.width.saturating_sub(new_border) as usize)
+                });
+                let height = old_rows.len().max(new_rows.len());
+                for (lines, rows, shown) in [
+                    (&mut old_lines, old_rows, old_area.is_some()),
+                    (&mut new_lines, new_rows, new_area.is_some()),
+                ] {
+                    if shown {
+                        let padding = height - rows.len();
+                        lines.extend(rows);
+                        lines.extend(std::iter::repeat_n(Line::default(), padding));
+                    }
+                }
+            } else {
+                if old_area.is_some() {
+                    old_lines.push(Line::from(old_spans));
+                }
+                if new_area.is_some() {
+                    new_lines.push(Line::from(new_spans));
+                }
+            }
+
+            // Notes go under their line, with blank rows keeping the old side level
+            if let (Some(area), Some((num, _))) = (new_area, &diff_line.new_line) {
+                let rows = note_rows(
+                    notes,
+                    &diff.filename,
+                    *num,
+                    area.width.saturating_sub(2) as usize,
+                );
+                if old_area.is_some() {
+                    old_lines.extend(std::iter::repeat_n(Line::default(), rows.len()));
+                }
+                new_lines.extend(rows);
             }
         }
 
@@ -473,6 +1004,7 @@ pub fn render_diff(
                     .border_style(border_style),
             );
             frame.render_widget(old_para, area);
+            minimap(frame, area, Some(MatchPanel::Old), content_height);
         }
 
         if let Some(area) = new_area {
@@ -489,9 +1021,28 @@ pub fn render_diff(
                     .border_style(border_style),
             );
             frame.render_widget(new_para, area);
+            minimap(frame, area, Some(MatchPanel::New), content_height);
         }
     }
 
+    // Drawn over the diff, so it comes after it
+    let tooltip = sidebar_area
+        .filter(|_| focused_panel == FocusedPanel::Sidebar)
+        .zip(summaries)
+        .and_then(|(sidebar, summaries)| {
+            let row = sidebar_selected.checked_sub(sidebar_scroll)?;
+            if row >= sidebar.height.saturating_sub(2) as usize {
+                return None;
+            }
+            match sidebar_items.get(sidebar_selected)? {
+                SidebarItem::File { path, .. } => Some((sidebar, row, summaries.get(path)?)),
+                SidebarItem::Directory { .. } => None,
+            }
+        });
+    if let Some((sidebar, row, summary)) = tooltip {
+        render_summary_tooltip(frame, sidebar, row as u16, summary);
+    }
+
     render_footer(
         frame,
         chunks[1],
@@ -500,13 +1051,112 @@ pub fn render_diff(
             branch,
             pr_info,
             watching,
+            review_flow: settings.review_flow,
+            read_only: settings.read_only,
+            ignore_whitespace: settings.ignore_whitespace,
+            status_message,
             current_file,
             viewed_files,
+            file_count,
             line_stats_added: line_stats.added,
             line_stats_removed: line_stats.removed,
             hunk_count,
             search_state,
+            history,
             area_width: area.width,
         },
     );
+    areas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_indent_guides_mark_each_level() {
+        let text = "        let x = 1;";
+        let spans = vec![
+            Span::raw("      "),
+            Span::raw("  let"),
+            Span::raw(" x = 1;"),
+        ];
+        let guided = with_indent_guides(spans, text, 4);
+        let rendered: String = guided.iter().map(|span| span.content.as_ref()).collect();
+        assert_eq!(rendered, "│   │   let x = 1;");
+        // Spaces after the code starts are left alone
+        let plain = with_indent_guides(vec![Span::raw("x    y")], "x    y", 4);
+        assert_eq!(plain.len(), 1);
+    }
+
+    #[test]
+    fn test_row_heights_follow_wrapping() {
+        let diff = FileDiff {
+            filename: "notes.md".to_string(),
+            old_content: "a\n".to_string(),
+            new_content: "b\n".to_string(),
+            status: crate::command::diff::types::FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        };
+        let row = |old: &str, new: &str| DiffLine {
+            old_line: Some((1, old.to_string())),
+            new_line: Some((1, new.to_string())),
+            change_type: ChangeType::Modified,
+        };
+        let lines = vec![row("short", &"x".repeat(50)), row("", "")];
+        let area = Rect::new(0, 0, 80, 20);
+        let mut settings = DiffViewSettings::default();
+        let heights = |settings: &DiffViewSettings| {
+            row_heights(
+                area,
+                &diff,
+                &lines,
+                settings,
+                false,
+                DiffFullscreen::None,
+                None,
+            )
+        };
+        assert_eq!(heights(&settings), vec![1, 1]);
+
+        // Each panel is 40 columns: 38 inside the borders (39 for the new one), 7 of gutter
+        settings.wrap = true;
+        assert_eq!(heights(&settings), vec![2, 1]);
+        settings.unified = true;
+        assert_eq!(heights(&settings), vec![2, 2]);
+    }
+
+    #[test]
+    fn test_wrap_row_indents_continuation_lines() {
+        let spans = vec![Span::raw("   1 | "), Span::raw("abcdef"), Span::raw("ghij")];
+        let rows: Vec<String> = wrap_row(spans, 11)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(rows, vec!["   1 | abcd", "     | efgh", "     | ij"]);
+    }
+
+    #[test]
+    fn test_wrap_row_keeps_wide_characters_whole() {
+        let spans = vec![Span::raw("   1 | "), Span::raw("a日本語")];
+        let rows: Vec<String> = wrap_row(spans, 11)
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+        assert_eq!(rows, vec!["   1 | a日", "     | 本語"]);
+        assert_eq!(wrapped_lines("a日本語", 11, 7), 2);
+    }
+
+    #[test]
+    fn test_search_highlight_never_splits_characters() {
+        let spans = vec![Span::raw("日本"), Span::raw("語 text")];
+        // The second match starts and ends inside characters
+        let matches = [(3, 6, true), (7, 8, false)];
+        let parts: Vec<String> = apply_search_highlight(spans, &matches)
+            .iter()
+            .map(|span| span.content.to_string())
+            .collect();
+        assert_eq!(parts, vec!["日", "本", "語", " text"]);
+    }
 }
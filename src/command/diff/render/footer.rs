@@ -4,6 +4,7 @@ use ratatui::{prelude::*, widgets::Paragraph};
 
 use crate::command::diff::search::{SearchMode, SearchState};
 use crate::command::diff::theme;
+use crate::command::diff::width::{self, truncate_middle};
 use crate::command::diff::PrInfo;
 
 pub struct FooterData<'a> {
@@ -11,47 +12,55 @@ pub struct FooterData<'a> {
     pub branch: &'a str,
     pub pr_info: Option<&'a PrInfo>,
     pub watching: bool,
+    pub review_flow: bool,
+    pub read_only: bool,
+    /// Whitespace-only changes are shown as unchanged
+    pub ignore_whitespace: bool,
+    pub status_message: Option<&'a str>,
     pub current_file: usize,
     pub viewed_files: &'a HashSet<usize>,
+    /// Files in the diff, for the review progress next to the viewed count
+    pub file_count: usize,
     pub line_stats_added: usize,
     pub line_stats_removed: usize,
     pub hunk_count: usize,
     pub search_state: &'a SearchState,
+    /// Badge and description of the commit file history mode is showing
+    pub history: Option<(String, String)>,
     pub area_width: u16,
 }
 
-fn truncate_middle(s: &str, max_len: usize) -> String {
-    if s.len() <= max_len {
-        return s.to_string();
-    }
-    if max_len < 5 {
-        return s.chars().take(max_len).collect();
-    }
-    let half = (max_len - 3) / 2;
-    let start: String = s.chars().take(half).collect();
-    let end: String = s.chars().skip(s.len() - half).collect();
-    format!("{}...{}", start, end)
-}
-
 pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
     let t = theme::get();
     let bg = t.ui.footer_bg;
 
     if data.search_state.is_active() {
-        let prefix = match data.search_state.mode {
-            SearchMode::InputForward => "/",
+        let search = data.search_state;
+        let prefix = match search.mode {
+            SearchMode::InputForward | SearchMode::InputBackward => search.prompt(),
             SearchMode::Inactive => "",
         };
+        // The error says why nothing matches; otherwise the options and how to change them
+        let (note, note_color) = match &search.error {
+            Some(error) => (format!("  {error}"), t.ui.status_deleted),
+            None => {
+                let flags = search.flags();
+                let flags = if flags.is_empty() {
+                    String::new()
+                } else {
+                    format!("[{flags}] ")
+                };
+                (format!("  {flags}ctrl+r regex · tab case"), t.ui.text_muted)
+            }
+        };
         let search_spans = vec![
             Span::styled(prefix, Style::default().fg(t.ui.highlight).bg(bg)),
-            Span::styled(
-                &data.search_state.query,
-                Style::default().fg(t.ui.text_primary).bg(bg),
-            ),
+            Span::styled(&search.query, Style::default().fg(t.ui.text_primary).bg(bg)),
             Span::styled("_", Style::default().fg(t.ui.text_muted).bg(bg)),
+            Span::styled(note.clone(), Style::default().fg(note_color).bg(bg)),
         ];
-        let remaining_width =
-            footer_area.width as usize - prefix.len() - data.search_state.query.len() - 1;
+        let used: usize = search_spans.iter().map(Span::width).sum();
+        let remaining_width = (footer_area.width as usize).saturating_sub(used);
         let mut spans = search_spans;
         spans.push(Span::styled(
             " ".repeat(remaining_width),
@@ -61,6 +70,13 @@ pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
         frame.render_widget(footer, footer_area);
     } else {
         let watch_indicator = if data.watching { " watching" } else { "" };
+        let flow_indicator = if data.review_flow { " flow" } else { "" };
+        let read_only_indicator = if data.read_only { " read-only" } else { "" };
+        let whitespace_indicator = if data.ignore_whitespace {
+            " ignoring whitespace"
+        } else {
+            ""
+        };
         let max_filename_len = if data.search_state.has_query() {
             (data.area_width as usize).saturating_sub(80).min(40)
         } else {
@@ -78,7 +94,7 @@ pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
             let is_fork = pr
                 .head_repo_owner
                 .as_ref()
-                .map_or(true, |head_owner| head_owner != &pr.base_repo_owner);
+                .is_none_or(|head_owner| head_owner != &pr.base_repo_owner);
 
             let base_label = if is_fork {
                 format!(" {}:{} ", pr.base_repo_owner, pr.base_ref)
@@ -116,13 +132,29 @@ pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
                     Style::default().fg(t.ui.text_secondary).bg(bg),
                 ),
                 Span::styled(viewed_indicator, Style::default().fg(t.ui.viewed).bg(bg)),
+                Span::styled(flow_indicator, Style::default().fg(t.ui.viewed).bg(bg)),
+                Span::styled(
+                    read_only_indicator,
+                    Style::default().fg(t.ui.text_muted).bg(bg),
+                ),
+                Span::styled(
+                    whitespace_indicator,
+                    Style::default().fg(t.ui.text_muted).bg(bg),
+                ),
             ]
         } else {
-            // Normal diff mode: show branch name
+            // Normal diff mode: show branch name, or the commit in file history mode
+            let (badge, detail) = match &data.history {
+                Some((badge, detail)) => (
+                    badge.clone(),
+                    width::take_width(detail, max_filename_len).to_string(),
+                ),
+                None => (format!(" {} ", data.branch), String::new()),
+            };
             vec![
                 Span::styled(" ", Style::default().bg(bg)),
                 Span::styled(
-                    format!(" {} ", data.branch),
+                    badge,
                     Style::default()
                         .fg(t.ui.footer_branch_fg)
                         .bg(t.ui.footer_branch_bg),
@@ -132,8 +164,18 @@ pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
                     truncated_filename,
                     Style::default().fg(t.ui.text_secondary).bg(bg),
                 ),
+                Span::styled(detail, Style::default().fg(t.ui.text_muted).bg(bg)),
                 Span::styled(viewed_indicator, Style::default().fg(t.ui.viewed).bg(bg)),
                 Span::styled(watch_indicator, Style::default().fg(t.ui.watching).bg(bg)),
+                Span::styled(flow_indicator, Style::default().fg(t.ui.viewed).bg(bg)),
+                Span::styled(
+                    read_only_indicator,
+                    Style::default().fg(t.ui.text_muted).bg(bg),
+                ),
+                Span::styled(
+                    whitespace_indicator,
+                    Style::default().fg(t.ui.text_muted).bg(bg),
+                ),
             ]
         };
 
@@ -144,13 +186,24 @@ pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
                 .current_match_index()
                 .map(|i| i + 1)
                 .unwrap_or(0);
+            let search = data.search_state;
+            let flags = search.flags();
+            let flags = if flags.is_empty() {
+                String::new()
+            } else {
+                format!(" ({flags})")
+            };
             let search_info = if match_count > 0 {
                 format!(
-                    "[{}/{}] /{}",
-                    current_idx, match_count, data.search_state.query
+                    "[{}/{}] {}{}{}",
+                    current_idx,
+                    match_count,
+                    search.prompt(),
+                    search.query,
+                    flags
                 )
             } else {
-                format!("[0/0] /{}", data.search_state.query)
+                format!("[0/0] {}{}{}", search.prompt(), search.query, flags)
             };
             (
                 vec![Span::styled(
@@ -187,11 +240,21 @@ pub fn render_footer(frame: &mut Frame, footer_area: Rect, data: FooterData) {
                         ),
                         Style::default().fg(t.ui.text_muted).bg(bg),
                     ),
+                    Span::styled(
+                        format!(" · {}/{} viewed", data.viewed_files.len(), data.file_count),
+                        Style::default().fg(t.ui.viewed).bg(bg),
+                    ),
                 ],
-                vec![Span::styled(
-                    " ? help ",
-                    Style::default().fg(t.ui.text_muted).bg(bg),
-                )],
+                match data.status_message {
+                    Some(message) => vec![Span::styled(
+                        format!(" {message} "),
+                        Style::default().fg(t.ui.highlight).bg(bg),
+                    )],
+                    None => vec![Span::styled(
+                        " ?? help ",
+                        Style::default().fg(t.ui.text_muted).bg(bg),
+                    )],
+                },
             )
         };
 
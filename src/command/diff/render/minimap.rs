@@ -0,0 +1,171 @@
+//! The strip drawn over the right border of each diff panel: where the file's changes and
+//! search matches are, and which part of it is on screen. Clicking it jumps there.
+
+use ratatui::prelude::*;
+
+use crate::command::diff::search::{MatchPanel, SearchState};
+use crate::command::diff::theme;
+use crate::command::diff::types::{ChangeType, DiffLine};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Mark {
+    None,
+    Added,
+    Deleted,
+    Changed,
+    Match,
+}
+
+/// The strip for the panel in `area`, inside its right border.
+pub fn minimap_area(area: Rect) -> Rect {
+    Rect {
+        x: area.right().saturating_sub(1),
+        y: area.y + 1,
+        width: 1,
+        height: area.height.saturating_sub(2),
+    }
+}
+
+/// The first row of the diff that row `row` of a strip `height` rows tall stands for.
+pub fn line_at(row: usize, height: usize, total: usize) -> usize {
+    if height == 0 {
+        return 0;
+    }
+    row * total / height
+}
+
+/// The rows of the diff each row of the strip stands for, never none.
+fn rows_of(row: usize, height: usize, total: usize) -> std::ops::Range<usize> {
+    let start = line_at(row, height, total);
+    let end = line_at(row + 1, height, total).max(start + 1);
+    start..end.min(total.max(start + 1))
+}
+
+/// What each row of the strip shows. `panel` is the side the panel shows, or `None` for the
+/// unified view, which shows both. Search matches win over changes.
+fn marks(
+    lines: &[DiffLine],
+    search: &SearchState,
+    panel: Option<MatchPanel>,
+    height: usize,
+) -> Vec<Mark> {
+    let mut matched = vec![false; lines.len()];
+    for m in &search.matches {
+        if panel.is_none_or(|panel| panel == m.panel) {
+            if let Some(matched) = matched.get_mut(m.line_index) {
+                *matched = true;
+            }
+        }
+    }
+    let mark = |idx: usize| {
+        if matched[idx] {
+            return Mark::Match;
+        }
+        match (lines[idx].change_type, panel) {
+            (ChangeType::Equal, _) => Mark::None,
+            (ChangeType::Insert, Some(MatchPanel::Old))
+            | (ChangeType::Delete, Some(MatchPanel::New)) => Mark::None,
+            (ChangeType::Insert, _) | (ChangeType::Modified, Some(MatchPanel::New)) => Mark::Added,
+            (ChangeType::Delete, _) | (ChangeType::Modified, Some(MatchPanel::Old)) => {
+                Mark::Deleted
+            }
+            (ChangeType::Modified, None) => Mark::Changed,
+        }
+    };
+
+    (0..height)
+        .map(|row| {
+            let rows = rows_of(row, height, lines.len());
+            let found: Vec<Mark> = rows.filter(|&idx| idx < lines.len()).map(mark).collect();
+            [Mark::Match, Mark::Changed, Mark::Added, Mark::Deleted]
+                .into_iter()
+                .find(|mark| found.contains(mark))
+                .unwrap_or(Mark::None)
+        })
+        .collect()
+}
+
+/// Draw the strip for the panel in `area`, with rows `scroll` to `scroll + visible` of the
+/// diff on screen.
+pub fn render_minimap(
+    frame: &mut Frame,
+    area: Rect,
+    lines: &[DiffLine],
+    search: &SearchState,
+    panel: Option<MatchPanel>,
+    scroll: usize,
+    visible: usize,
+) {
+    let strip = minimap_area(area);
+    let height = strip.height as usize;
+    if height == 0 || lines.is_empty() {
+        return;
+    }
+    let t = theme::get();
+    let shown = scroll..scroll + visible.max(1);
+    let buf = frame.buffer_mut();
+    for (row, mark) in marks(lines, search, panel, height).into_iter().enumerate() {
+        let rows = rows_of(row, height, lines.len());
+        let on_screen = rows.start < shown.end && shown.start < rows.end;
+        let fg = match mark {
+            Mark::Match => t.ui.search_match_bg,
+            Mark::Added => t.ui.stats_added,
+            Mark::Deleted => t.ui.stats_removed,
+            Mark::Changed => t.ui.status_modified,
+            Mark::None if on_screen => t.ui.text_muted,
+            Mark::None => t.ui.border_unfocused,
+        };
+        let symbol = if on_screen { "┃" } else { "│" };
+        if let Some(cell) = buf.cell_mut((strip.x, strip.y + row as u16)) {
+            cell.set_symbol(symbol).set_fg(fg);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::diff::search::SearchMatch;
+
+    fn line(change_type: ChangeType) -> DiffLine {
+        DiffLine {
+            old_line: None,
+            new_line: None,
+            change_type,
+        }
+    }
+
+    #[test]
+    fn test_marks() {
+        let mut lines: Vec<DiffLine> = (0..20).map(|_| line(ChangeType::Equal)).collect();
+        lines[2] = line(ChangeType::Insert);
+        lines[9] = line(ChangeType::Delete);
+        lines[15] = line(ChangeType::Modified);
+        let mut search = SearchState::default();
+        search.matches.push(SearchMatch {
+            line_index: 18,
+            start_col: 0,
+            end_col: 1,
+            panel: MatchPanel::New,
+        });
+
+        // Two rows of the diff to each row of the strip
+        let new = marks(&lines, &search, Some(MatchPanel::New), 10);
+        assert_eq!(new[1], Mark::Added);
+        assert_eq!(new[4], Mark::None);
+        assert_eq!(new[7], Mark::Added);
+        assert_eq!(new[9], Mark::Match);
+        let old = marks(&lines, &search, Some(MatchPanel::Old), 10);
+        assert_eq!(old[1], Mark::None);
+        assert_eq!(old[4], Mark::Deleted);
+        assert_eq!(old[9], Mark::None);
+        let unified = marks(&lines, &search, None, 10);
+        assert_eq!(unified[7], Mark::Changed);
+
+        // A short diff stretches over the whole strip
+        let short = marks(&lines[..2], &search, None, 4);
+        assert_eq!(short, vec![Mark::None; 4]);
+        assert_eq!(line_at(3, 4, 2), 1);
+        assert_eq!(line_at(5, 10, 20), 10);
+    }
+}
@@ -1,9 +1,14 @@
+mod conflict_view;
 mod diff_view;
 mod footer;
+mod minimap;
 pub mod modal;
 mod sidebar;
 
-pub use diff_view::{render_diff, render_empty_state};
+pub use conflict_view::render_conflicts;
+pub use diff_view::{render_diff, render_empty_state, row_heights, DiffAreas};
+pub use minimap::line_at as minimap_line_at;
 pub use modal::{
-    FilePickerItem, FileStatus as ModalFileStatus, KeyBind, KeyBindSection, Modal, ModalResult,
+    fuzzy_match, markdown_lines, FilePickerItem, FileStatus as ModalFileStatus, KeyBind,
+    KeyBindSection, Modal, ModalResult, SuggestionItem,
 };
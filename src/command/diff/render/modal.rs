@@ -4,7 +4,13 @@ use ratatui::{
     widgets::{Block, Borders, Clear, List, ListItem, Paragraph},
 };
 
-use crate::command::diff::theme;
+use crate::command::diff::filter::FileFilter;
+use crate::command::diff::mentions;
+use crate::command::diff::overview::{sort_stats, FileStats, OverviewSort};
+use crate::command::diff::pending_review::ReviewEvent;
+use crate::command::diff::pr_details::{CheckState, PrDetails};
+use crate::command::diff::theme::{self, ThemeMode};
+use crate::command::diff::types::{DiffViewSettings, FileStatus as DiffStatus};
 
 #[derive(Clone)]
 pub struct KeyBind {
@@ -26,18 +32,30 @@ pub struct FilePickerItem {
     pub viewed: bool,
 }
 
+/// A suggested change from a PR review comment
+#[derive(Clone)]
+pub struct SuggestionItem {
+    /// Where it applies and who suggested it
+    pub heading: String,
+    pub patch: String,
+    pub applied: bool,
+}
+
 #[derive(Clone, Copy)]
 pub enum FileStatus {
     Added,
     Modified,
     Deleted,
+    Renamed,
+    Copied,
 }
 
 #[derive(Clone)]
 pub enum ModalContent {
-    #[allow(dead_code)]
-    Info { title: String, message: String },
-    #[allow(dead_code)]
+    Info {
+        title: String,
+        message: String,
+    },
     Select {
         title: String,
         items: Vec<String>,
@@ -54,6 +72,182 @@ pub enum ModalContent {
         query: String,
         selected: usize,
     },
+    /// Edits a copy of the view settings; the app applies it after every key
+    Settings {
+        settings: DiffViewSettings,
+        selected: usize,
+    },
+    /// Asks before doing something that can't be undone
+    Confirm {
+        title: String,
+        message: String,
+    },
+    /// An AI explanation that fills in as it streams
+    Explanation {
+        title: String,
+        text: String,
+        scroll: u16,
+        streaming: bool,
+    },
+    /// The PR's title, description, labels, reviewers and checks
+    PrDetails {
+        title: String,
+        details: PrDetails,
+        scroll: u16,
+    },
+    /// Suggested changes from PR reviews, one at a time
+    Suggestions {
+        items: Vec<SuggestionItem>,
+        selected: usize,
+    },
+    /// Writes a PR comment, completing `@` mentions from `candidates`
+    Comment {
+        title: String,
+        text: String,
+        candidates: Vec<String>,
+        suggestion: usize,
+        /// The summary of a review, which can also approve or request changes
+        review: bool,
+    },
+    /// Edits a drafted commit message before committing with it
+    CommitMessage {
+        text: String,
+    },
+    /// Every file with its line counts, sortable, to pick one to open
+    Overview {
+        items: Vec<FileStats>,
+        sort: OverviewSort,
+        selected: usize,
+    },
+    /// Edits the sidebar's filter; it's checked before the popup closes
+    Filter {
+        text: String,
+        error: Option<String>,
+    },
+}
+
+/// A row of the settings panel
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SettingsField {
+    TabWidth,
+    ContextLines,
+    Whitespace,
+    Theme,
+    SidebarWidth,
+    Wrap,
+    IndentGuides,
+    Layout,
+    DetectMoves,
+}
+
+const SETTINGS_FIELDS: [SettingsField; 9] = [
+    SettingsField::TabWidth,
+    SettingsField::ContextLines,
+    SettingsField::Whitespace,
+    SettingsField::Theme,
+    SettingsField::SidebarWidth,
+    SettingsField::Wrap,
+    SettingsField::IndentGuides,
+    SettingsField::Layout,
+    SettingsField::DetectMoves,
+];
+
+const MAX_TAB_WIDTH: usize = 8;
+const MAX_CONTEXT_LINES: usize = 10;
+const SIDEBAR_WIDTHS: [u16; 9] = [20, 25, 30, 35, 40, 45, 50, 55, 60];
+
+impl SettingsField {
+    fn label(self) -> &'static str {
+        match self {
+            SettingsField::TabWidth => "Tab width",
+            SettingsField::ContextLines => "Context lines",
+            SettingsField::Whitespace => "Whitespace",
+            SettingsField::Theme => "Theme",
+            SettingsField::SidebarWidth => "Sidebar width",
+            SettingsField::Wrap => "Wrap lines",
+            SettingsField::IndentGuides => "Indent guides",
+            SettingsField::Layout => "Layout",
+            SettingsField::DetectMoves => "Moved code",
+        }
+    }
+
+    fn value(self, settings: &DiffViewSettings) -> String {
+        match self {
+            SettingsField::TabWidth => settings.tab_width.to_string(),
+            SettingsField::ContextLines if !settings.context.enabled => "off".to_string(),
+            SettingsField::ContextLines => settings.context.max_lines.to_string(),
+            SettingsField::Whitespace if settings.ignore_whitespace => "ignore".to_string(),
+            SettingsField::Whitespace => "show".to_string(),
+            SettingsField::Theme => match settings.theme {
+                ThemeMode::Dark => "dark".to_string(),
+                ThemeMode::Light => "light".to_string(),
+            },
+            SettingsField::SidebarWidth => settings
+                .sidebar_width
+                .map_or("auto".to_string(), |width| width.to_string()),
+            SettingsField::Wrap if settings.wrap => "on".to_string(),
+            SettingsField::Wrap => "off".to_string(),
+            SettingsField::IndentGuides if settings.indent_guides => "on".to_string(),
+            SettingsField::IndentGuides => "off".to_string(),
+            SettingsField::Layout if settings.unified => "unified".to_string(),
+            SettingsField::Layout => "side by side".to_string(),
+            SettingsField::DetectMoves if settings.detect_moves => "detect".to_string(),
+            SettingsField::DetectMoves => "off".to_string(),
+        }
+    }
+
+    /// Step the value up (`forward`) or down; on/off values just toggle.
+    pub fn adjust(self, settings: &mut DiffViewSettings, forward: bool) {
+        match self {
+            SettingsField::TabWidth => {
+                settings.tab_width = if forward {
+                    (settings.tab_width + 1).min(MAX_TAB_WIDTH)
+                } else {
+                    settings.tab_width.saturating_sub(1).max(1)
+                };
+            }
+            SettingsField::ContextLines => {
+                let current = if settings.context.enabled {
+                    settings.context.max_lines
+                } else {
+                    0
+                };
+                let next = if forward {
+                    (current + 1).min(MAX_CONTEXT_LINES)
+                } else {
+                    current.saturating_sub(1)
+                };
+                settings.context.enabled = next > 0;
+                if next > 0 {
+                    settings.context.max_lines = next;
+                }
+            }
+            SettingsField::Whitespace => settings.ignore_whitespace = !settings.ignore_whitespace,
+            SettingsField::Theme => {
+                settings.theme = match settings.theme {
+                    ThemeMode::Dark => ThemeMode::Light,
+                    ThemeMode::Light => ThemeMode::Dark,
+                };
+            }
+            SettingsField::SidebarWidth => {
+                // Steps through auto, then the fixed widths
+                let index = settings
+                    .sidebar_width
+                    .and_then(|width| SIDEBAR_WIDTHS.iter().position(|&w| w >= width));
+                settings.sidebar_width = match (index, forward) {
+                    (None, true) => Some(SIDEBAR_WIDTHS[0]),
+                    (None, false) => None,
+                    (Some(0), false) => None,
+                    (Some(i), false) => Some(SIDEBAR_WIDTHS[i - 1]),
+                    (Some(i), true) => Some(SIDEBAR_WIDTHS[(i + 1).min(SIDEBAR_WIDTHS.len() - 1)]),
+                };
+            }
+            SettingsField::Wrap => settings.wrap = !settings.wrap,
+            SettingsField::IndentGuides => settings.indent_guides = !settings.indent_guides,
+            SettingsField::Layout => settings.unified = !settings.unified,
+            SettingsField::DetectMoves => settings.detect_moves = !settings.detect_moves,
+        }
+    }
 }
 
 pub struct Modal {
@@ -66,10 +260,25 @@ pub enum ModalResult {
     #[allow(dead_code)]
     Selected(usize, String),
     FileSelected(usize),
+    /// Save the edited settings to the config file
+    SaveSettings,
+    /// Post the written comment
+    PostComment(String),
+    /// Submit the pending review with this summary
+    SubmitReview(ReviewEvent, String),
+    /// Go ahead with what a confirmation asked about
+    Confirmed,
+    /// Apply the suggestion at this index
+    AcceptSuggestion(usize),
+    /// Put this text on the clipboard
+    Copy(String),
+    /// Filter the sidebar with these terms, which parse; empty shows every file
+    Filter(String),
+    /// Commit with this message
+    Commit(String),
 }
 
 impl Modal {
-    #[allow(dead_code)]
     pub fn info(title: impl Into<String>, message: impl Into<String>) -> Self {
         Self {
             content: ModalContent::Info {
@@ -79,7 +288,43 @@ impl Modal {
         }
     }
 
-    #[allow(dead_code)]
+    pub fn confirm(title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            content: ModalContent::Confirm {
+                title: title.into(),
+                message: message.into(),
+            },
+        }
+    }
+
+    /// An empty explanation, filled in with [`Modal::push_explanation`]
+    pub fn explanation(title: impl Into<String>) -> Self {
+        Self {
+            content: ModalContent::Explanation {
+                title: title.into(),
+                text: String::new(),
+                scroll: 0,
+                streaming: true,
+            },
+        }
+    }
+
+    pub fn pr_details(title: impl Into<String>, details: PrDetails) -> Self {
+        Self {
+            content: ModalContent::PrDetails {
+                title: title.into(),
+                details,
+                scroll: 0,
+            },
+        }
+    }
+
+    pub fn suggestions(items: Vec<SuggestionItem>) -> Self {
+        Self {
+            content: ModalContent::Suggestions { items, selected: 0 },
+        }
+    }
+
     pub fn select(title: impl Into<String>, items: Vec<String>) -> Self {
         Self {
             content: ModalContent::Select {
@@ -90,101 +335,846 @@ impl Modal {
         }
     }
 
-    pub fn keybindings(title: impl Into<String>, sections: Vec<KeyBindSection>) -> Self {
-        Self {
-            content: ModalContent::KeyBindings {
-                title: title.into(),
-                sections,
-            },
+    pub fn keybindings(title: impl Into<String>, sections: Vec<KeyBindSection>) -> Self {
+        Self {
+            content: ModalContent::KeyBindings {
+                title: title.into(),
+                sections,
+            },
+        }
+    }
+
+    pub fn file_picker(title: impl Into<String>, items: Vec<FilePickerItem>) -> Self {
+        let filtered_indices: Vec<usize> = (0..items.len()).collect();
+        Self {
+            content: ModalContent::FilePicker {
+                title: title.into(),
+                items,
+                filtered_indices,
+                query: String::new(),
+                selected: 0,
+            },
+        }
+    }
+
+    pub fn overview(mut items: Vec<FileStats>) -> Self {
+        let sort = OverviewSort::default();
+        sort_stats(&mut items, sort);
+        Self {
+            content: ModalContent::Overview {
+                items,
+                sort,
+                selected: 0,
+            },
+        }
+    }
+
+    pub fn filter(text: impl Into<String>) -> Self {
+        Self {
+            content: ModalContent::Filter {
+                text: text.into(),
+                error: None,
+            },
+        }
+    }
+
+    pub fn settings(settings: DiffViewSettings) -> Self {
+        Self {
+            content: ModalContent::Settings {
+                settings,
+                selected: 0,
+            },
+        }
+    }
+
+    pub fn comment(title: impl Into<String>, candidates: Vec<String>) -> Self {
+        Self {
+            content: ModalContent::Comment {
+                title: title.into(),
+                text: String::new(),
+                candidates,
+                suggestion: 0,
+                review: false,
+            },
+        }
+    }
+
+    /// A note of the reviewer's own, starting from what it says so far
+    pub fn note(title: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            content: ModalContent::Comment {
+                title: title.into(),
+                text: text.into(),
+                candidates: Vec::new(),
+                suggestion: 0,
+                review: false,
+            },
+        }
+    }
+
+    /// The summary of a review, sent as a comment, an approval or a request for changes
+    pub fn review(title: impl Into<String>, candidates: Vec<String>) -> Self {
+        Self {
+            content: ModalContent::Comment {
+                title: title.into(),
+                text: String::new(),
+                candidates,
+                suggestion: 0,
+                review: true,
+            },
+        }
+    }
+
+    /// A drafted commit message to edit before committing
+    pub fn commit_message(text: impl Into<String>) -> Self {
+        Self {
+            content: ModalContent::CommitMessage { text: text.into() },
+        }
+    }
+
+    /// Offer these people for `@` mentions if this is a comment being written
+    pub fn set_mention_candidates(&mut self, people: &[String]) {
+        if let ModalContent::Comment { candidates, .. } = &mut self.content {
+            *candidates = people.to_vec();
+        }
+    }
+
+    pub fn is_explanation(&self) -> bool {
+        matches!(self.content, ModalContent::Explanation { .. })
+    }
+
+    /// Add the next piece of a streaming explanation
+    pub fn push_explanation(&mut self, delta: &str) {
+        if let ModalContent::Explanation { text, .. } = &mut self.content {
+            text.push_str(delta);
+        }
+    }
+
+    /// Mark the explanation complete, noting why it stopped early if it failed
+    pub fn finish_explanation(&mut self, error: Option<&str>) {
+        if let ModalContent::Explanation {
+            text, streaming, ..
+        } = &mut self.content
+        {
+            *streaming = false;
+            if let Some(error) = error {
+                if !text.is_empty() {
+                    text.push_str("\n\n");
+                }
+                text.push_str(&format!("Explaining failed: {error}"));
+            }
+        }
+    }
+
+    /// The settings being edited, if this is the settings panel
+    pub fn edited_settings(&self) -> Option<&DiffViewSettings> {
+        match &self.content {
+            ModalContent::Settings { settings, .. } => Some(settings),
+            _ => None,
+        }
+    }
+
+    pub fn render(&self, frame: &mut Frame) {
+        let area = frame.area();
+
+        let (modal_width, modal_height) = match &self.content {
+            ModalContent::Info { message, .. } => {
+                let width = 80.min(area.width.saturating_sub(4));
+                let lines = message.lines().count() as u16;
+                let height = (lines + 4).min(area.height * 80 / 100).max(5);
+                (width, height)
+            }
+            ModalContent::Select { items, .. } => {
+                let width = 80.min(area.width.saturating_sub(4));
+                let items_count = items.len() as u16;
+                let height = (items_count + 4).min(area.height * 80 / 100).max(5);
+                (width, height)
+            }
+            ModalContent::KeyBindings { sections, .. } => {
+                let width = 60.min(area.width.saturating_sub(4));
+                let total_lines: usize = sections
+                    .iter()
+                    .map(|s| s.bindings.len() + 2) // +2 for section title and spacing
+                    .sum();
+                let height = (total_lines as u16 + 4).min(area.height * 80 / 100).max(5);
+                (width, height)
+            }
+            ModalContent::FilePicker {
+                filtered_indices, ..
+            } => {
+                let width = 80.min(area.width.saturating_sub(4));
+                let items_count = filtered_indices.len().min(15) as u16;
+                let height = (items_count + 5).min(area.height * 80 / 100).max(8);
+                (width, height)
+            }
+            ModalContent::Settings { .. } => {
+                let width = 50.min(area.width.saturating_sub(4));
+                // Fields, a blank line and the key hints, plus borders
+                let height = (SETTINGS_FIELDS.len() as u16 + 4).min(area.height);
+                (width, height)
+            }
+            ModalContent::Confirm { message, .. } => {
+                let width = 60.min(area.width.saturating_sub(4));
+                // Message, a blank line and the key hints, plus borders
+                let lines = message.lines().count() as u16;
+                let height = (lines + 4).min(area.height * 80 / 100).max(5);
+                (width, height)
+            }
+            ModalContent::Comment { .. } | ModalContent::CommitMessage { .. } => {
+                let width = 80.min(area.width.saturating_sub(4));
+                let height = 18.min(area.height.saturating_sub(2));
+                (width, height)
+            }
+            ModalContent::Suggestions { items, .. } => {
+                let width = 90.min(area.width.saturating_sub(4));
+                // The longest patch, its heading, a blank line and the key hints
+                let lines = items
+                    .iter()
+                    .map(|item| item.patch.lines().count())
+                    .max()
+                    .unwrap_or_default() as u16;
+                let height = (lines + 5).min(area.height * 80 / 100).max(8);
+                (width, height)
+            }
+            ModalContent::PrDetails { .. } => {
+                let width = 90.min(area.width.saturating_sub(4));
+                let height = (area.height * 80 / 100).max(5);
+                (width, height)
+            }
+            ModalContent::Overview { items, .. } => {
+                let width = 100.min(area.width.saturating_sub(4));
+                // Totals, largest files, a blank line, column names and the key hints
+                let height = (items.len() as u16 + 7).min(area.height * 80 / 100).max(9);
+                (width, height)
+            }
+            // The input, a blank line, the examples or error and the key hints
+            ModalContent::Filter { .. } => (70.min(area.width.saturating_sub(4)), 6),
+            // Sized up front, since the text is still growing
+            ModalContent::Explanation { .. } => {
+                let width = 90.min(area.width.saturating_sub(4));
+                let height = (area.height * 80 / 100).max(5);
+                (width, height)
+            }
+        };
+
+        let modal_x = (area.width.saturating_sub(modal_width)) / 2;
+        let modal_y = (area.height.saturating_sub(modal_height)) / 2;
+        let modal_area = Rect::new(modal_x, modal_y, modal_width, modal_height);
+
+        frame.render_widget(Clear, modal_area);
+
+        match &self.content {
+            ModalContent::Info { title, message } => {
+                self.render_info(frame, modal_area, title, message);
+            }
+            ModalContent::Select {
+                title,
+                items,
+                selected,
+            } => {
+                self.render_select(frame, modal_area, title, items, *selected);
+            }
+            ModalContent::KeyBindings { title, sections } => {
+                self.render_keybindings(frame, modal_area, title, sections);
+            }
+            ModalContent::FilePicker {
+                title,
+                items,
+                filtered_indices,
+                query,
+                selected,
+            } => {
+                self.render_file_picker(
+                    frame,
+                    modal_area,
+                    title,
+                    items,
+                    filtered_indices,
+                    query,
+                    *selected,
+                );
+            }
+            ModalContent::Settings { settings, selected } => {
+                self.render_settings(frame, modal_area, settings, *selected);
+            }
+            ModalContent::Confirm { title, message } => {
+                let hints = "y confirm · n cancel";
+                self.render_info(frame, modal_area, title, &format!("{message}\n\n{hints}"));
+            }
+            ModalContent::Suggestions { items, selected } => {
+                self.render_suggestions(frame, modal_area, items, *selected);
+            }
+            ModalContent::Explanation {
+                title,
+                text,
+                scroll,
+                streaming,
+            } => {
+                self.render_explanation(frame, modal_area, title, text, *scroll, *streaming);
+            }
+            ModalContent::PrDetails {
+                title,
+                details,
+                scroll,
+            } => {
+                self.render_pr_details(frame, modal_area, title, details, *scroll);
+            }
+            ModalContent::Filter { text, error } => {
+                self.render_filter(frame, modal_area, text, error.as_deref());
+            }
+            ModalContent::Overview {
+                items,
+                sort,
+                selected,
+            } => {
+                self.render_overview(frame, modal_area, items, *sort, *selected);
+            }
+            ModalContent::CommitMessage { text } => {
+                self.render_commit_message(frame, modal_area, text);
+            }
+            ModalContent::Comment {
+                title,
+                text,
+                candidates,
+                suggestion,
+                review,
+            } => {
+                self.render_comment(
+                    frame,
+                    modal_area,
+                    title,
+                    text,
+                    candidates,
+                    *suggestion,
+                    *review,
+                );
+            }
+        }
+    }
+
+    fn render_overview(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        items: &[FileStats],
+        sort: OverviewSort,
+        selected: usize,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(" Overview ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(3),
+                Constraint::Length(1),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .split(inner);
+
+        let muted = Style::default().fg(t.ui.text_muted);
+        let added: usize = items.iter().map(|item| item.added).sum();
+        let removed: usize = items.iter().map(|item| item.removed).sum();
+        let files = if items.len() == 1 { "file" } else { "files" };
+        let mut largest: Vec<&FileStats> = items.iter().filter(|i| i.changed() > 0).collect();
+        largest.sort_by(|a, b| b.changed().cmp(&a.changed()).then(a.name.cmp(&b.name)));
+        let mut largest_line = vec![Span::styled("Largest: ", muted)];
+        for (i, item) in largest.iter().take(3).enumerate() {
+            if i > 0 {
+                largest_line.push(Span::styled(", ", muted));
+            }
+            largest_line.push(Span::styled(
+                item.name.clone(),
+                Style::default().fg(t.ui.text_primary),
+            ));
+            largest_line.push(Span::styled(format!(" ({})", item.changed()), muted));
+        }
+        let summary = vec![
+            Line::from(vec![
+                Span::styled(
+                    format!("{} {files}  ", items.len()),
+                    Style::default().fg(t.ui.text_primary).bold(),
+                ),
+                Span::styled(format!("+{added} "), Style::default().fg(t.ui.status_added)),
+                Span::styled(
+                    format!("-{removed}"),
+                    Style::default().fg(t.ui.status_deleted),
+                ),
+            ]),
+            Line::from(largest_line),
+        ];
+        frame.render_widget(Paragraph::new(summary), chunks[0]);
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                format!("   {:>6} {:>6}  {:<11} file", "+", "-", "language"),
+                muted,
+            )),
+            chunks[1],
+        );
+
+        let visible_count = chunks[2].height as usize;
+        let scroll_offset = if selected >= visible_count {
+            selected - visible_count + 1
+        } else {
+            0
+        };
+        let list_items: Vec<ListItem> = items
+            .iter()
+            .enumerate()
+            .skip(scroll_offset)
+            .take(visible_count)
+            .map(|(i, item)| {
+                let (status_char, status_color) = match item.status {
+                    DiffStatus::Added => ("A", t.ui.status_added),
+                    DiffStatus::Modified => ("M", t.ui.status_modified),
+                    DiffStatus::Deleted => ("D", t.ui.status_deleted),
+                    DiffStatus::Renamed { .. } => ("R", t.ui.status_modified),
+                    DiffStatus::Copied { .. } => ("C", t.ui.status_added),
+                };
+                let (added, removed) = if item.binary {
+                    ("binary".to_string(), String::new())
+                } else {
+                    (format!("+{}", item.added), format!("-{}", item.removed))
+                };
+                let cells = [
+                    (
+                        format!(" {status_char} "),
+                        Style::default().fg(status_color),
+                    ),
+                    (
+                        format!("{added:>6} "),
+                        Style::default().fg(t.ui.status_added),
+                    ),
+                    (
+                        format!("{removed:>6}  "),
+                        Style::default().fg(t.ui.status_deleted),
+                    ),
+                    (format!("{:<11} ", item.language), muted),
+                    (item.name.clone(), Style::default().fg(t.ui.text_primary)),
+                ];
+                let selected_style = Style::default().fg(t.ui.selection_fg).bg(t.ui.selection_bg);
+                let spans: Vec<Span> = cells
+                    .into_iter()
+                    .map(|(text, style)| {
+                        Span::styled(text, if i == selected { selected_style } else { style })
+                    })
+                    .collect();
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        frame.render_widget(List::new(list_items), chunks[2]);
+
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                format!("s sort: {} · enter open · esc close", sort.label()),
+                muted,
+            )),
+            chunks[3],
+        );
+    }
+
+    fn render_filter(&self, frame: &mut Frame, area: Rect, text: &str, error: Option<&str>) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(" Filter files ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let (note, note_color) = match error {
+            Some(error) => (error, t.ui.status_deleted),
+            None => (
+                "*.rs  src/  !**/generated/**  :added :modified :deleted",
+                t.ui.text_secondary,
+            ),
+        };
+        let lines = vec![
+            Line::from(vec![
+                Span::styled("> ", Style::default().fg(t.ui.status_added)),
+                Span::styled(text, Style::default().fg(t.ui.text_primary)),
+                Span::styled("_", Style::default().fg(t.ui.text_muted)),
+            ]),
+            Line::default(),
+            Line::styled(note, Style::default().fg(note_color)),
+            Line::styled(
+                "enter apply · empty shows all files · esc cancel",
+                Style::default().fg(t.ui.text_muted),
+            ),
+        ];
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    fn render_suggestions(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        items: &[SuggestionItem],
+        selected: usize,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(format!(" Suggestion {}/{} ", selected + 1, items.len()))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let Some(item) = items.get(selected) else {
+            return;
+        };
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .split(inner);
+
+        let mut heading = vec![Span::styled(
+            item.heading.as_str(),
+            Style::default().fg(t.ui.text_primary).bold(),
+        )];
+        if item.applied {
+            heading.push(Span::styled(
+                "  applied",
+                Style::default().fg(t.ui.stats_added),
+            ));
+        }
+        frame.render_widget(Paragraph::new(Line::from(heading)), chunks[0]);
+
+        // Skip the file headers, the heading already names the file
+        let lines: Vec<Line> = item
+            .patch
+            .lines()
+            .skip_while(|line| !line.starts_with("@@"))
+            .map(|line| {
+                let color = if line.starts_with('+') {
+                    t.ui.stats_added
+                } else if line.starts_with('-') {
+                    t.ui.stats_removed
+                } else if line.starts_with("@@") {
+                    t.ui.highlight
+                } else {
+                    t.ui.text_secondary
+                };
+                Line::from(Span::styled(line, Style::default().fg(color)))
+            })
+            .collect();
+        frame.render_widget(Paragraph::new(lines), chunks[1]);
+
+        let hints = "a accept · n/p next/previous · esc close";
+        frame.render_widget(
+            Paragraph::new(Span::styled(hints, Style::default().fg(t.ui.text_muted))),
+            chunks[2],
+        );
+    }
+
+    fn render_explanation(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        text: &str,
+        scroll: u16,
+        streaming: bool,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(format!(" {} ", title))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(inner);
+
+        let lines: Vec<Line> = text
+            .lines()
+            .map(|line| Line::from(Span::styled(line, Style::default().fg(t.ui.text_primary))))
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(ratatui::widgets::Wrap { trim: false })
+                .scroll((scroll, 0)),
+            chunks[0],
+        );
+
+        let footer = if streaming {
+            "Explaining..."
+        } else {
+            "j/k scroll · y copy · esc close"
+        };
+        frame.render_widget(
+            Paragraph::new(Span::styled(footer, Style::default().fg(t.ui.text_muted))),
+            chunks[1],
+        );
+    }
+
+    fn render_pr_details(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        details: &PrDetails,
+        scroll: u16,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(format!(" {} ", title))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(inner);
+
+        let muted = Style::default().fg(t.ui.text_muted);
+        let primary = Style::default().fg(t.ui.text_primary);
+        let field = |name: &'static str, value: String| {
+            Line::from(vec![
+                Span::styled(format!("{name:<10}"), muted),
+                Span::styled(value, primary),
+            ])
+        };
+        let list = |items: &[String]| match items {
+            [] => "none".to_string(),
+            items => items.join(", "),
+        };
+
+        let mut lines = vec![
+            Line::from(Span::styled(details.title.clone(), primary.bold())),
+            Line::from(Span::styled(
+                format!("{} by @{} · {}", details.state, details.author, details.url),
+                muted,
+            )),
+            Line::default(),
+            field("Labels", list(&details.labels)),
+            field("Reviewers", list(&details.reviewers)),
+            field("Checks", details.checks_summary()),
+        ];
+        for check in &details.checks {
+            let color = match check.state {
+                CheckState::Passed => t.ui.stats_added,
+                CheckState::Failed => t.ui.stats_removed,
+                CheckState::Pending => t.ui.highlight,
+                CheckState::Skipped => t.ui.text_muted,
+            };
+            lines.push(Line::from(vec![
+                Span::raw(" ".repeat(10)),
+                Span::styled(check.state.symbol(), Style::default().fg(color)),
+                Span::styled(
+                    format!(" {}", check.name),
+                    Style::default().fg(t.ui.text_secondary),
+                ),
+            ]));
+        }
+        lines.push(Line::default());
+        if details.body.trim().is_empty() {
+            lines.push(Line::from(Span::styled("No description provided.", muted)));
+        } else {
+            lines.extend(markdown_lines(&details.body));
+        }
+
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(ratatui::widgets::Wrap { trim: false })
+                .scroll((scroll, 0)),
+            chunks[0],
+        );
+        frame.render_widget(
+            Paragraph::new(Span::styled("j/k scroll · I/esc close", muted)),
+            chunks[1],
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_comment(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        text: &str,
+        candidates: &[String],
+        suggestion: usize,
+        review: bool,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(format!(" {} ", title))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let suggestions = mentions::partial_mention(text)
+            .map(|partial| mentions::suggest(candidates, partial))
+            .unwrap_or_default();
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),
+                Constraint::Length(suggestions.len() as u16),
+                Constraint::Length(1),
+            ])
+            .split(inner);
+
+        let mut lines: Vec<Line> = text
+            .split('\n')
+            .map(|line| Line::from(Span::styled(line, Style::default().fg(t.ui.text_primary))))
+            .collect();
+        if let Some(last) = lines.last_mut() {
+            last.push_span(Span::styled("_", Style::default().fg(t.ui.text_muted)));
         }
+        // Keep the end of the comment, where typing happens, in view
+        let scroll = lines.len().saturating_sub(chunks[0].height as usize) as u16;
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(ratatui::widgets::Wrap { trim: false })
+                .scroll((scroll, 0)),
+            chunks[0],
+        );
+
+        let items: Vec<ListItem> = suggestions
+            .iter()
+            .enumerate()
+            .map(|(i, login)| {
+                let style = if i == suggestion {
+                    Style::default().fg(t.ui.selection_fg).bg(t.ui.selection_bg)
+                } else {
+                    Style::default().fg(t.ui.text_secondary)
+                };
+                ListItem::new(Line::from(Span::styled(format!(" @{login} "), style)))
+            })
+            .collect();
+        frame.render_widget(List::new(items), chunks[1]);
+
+        let actions = if review {
+            "ctrl+s comment · ctrl+a approve · ctrl+r request changes"
+        } else {
+            "ctrl+s post"
+        };
+        let tagged = mentions::extract_mentions(text);
+        let footer = if tagged.is_empty() {
+            format!("{actions} · @ mention · tab complete · esc cancel")
+        } else {
+            let handles: Vec<String> = tagged.iter().map(|h| format!("@{h}")).collect();
+            format!("Notifies {} · {actions} · esc cancel", handles.join(", "))
+        };
+        frame.render_widget(
+            Paragraph::new(Span::styled(footer, Style::default().fg(t.ui.text_muted))),
+            chunks[2],
+        );
     }
 
-    pub fn file_picker(title: impl Into<String>, items: Vec<FilePickerItem>) -> Self {
-        let filtered_indices: Vec<usize> = (0..items.len()).collect();
-        Self {
-            content: ModalContent::FilePicker {
-                title: title.into(),
-                items,
-                filtered_indices,
-                query: String::new(),
-                selected: 0,
-            },
+    fn render_commit_message(&self, frame: &mut Frame, area: Rect, text: &str) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(" Commit message ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(inner);
+
+        let mut lines: Vec<Line> = text
+            .split('\n')
+            .map(|line| Line::from(Span::styled(line, Style::default().fg(t.ui.text_primary))))
+            .collect();
+        if let Some(last) = lines.last_mut() {
+            last.push_span(Span::styled("_", Style::default().fg(t.ui.text_muted)));
         }
+        let scroll = lines.len().saturating_sub(chunks[0].height as usize) as u16;
+        frame.render_widget(
+            Paragraph::new(lines)
+                .wrap(ratatui::widgets::Wrap { trim: false })
+                .scroll((scroll, 0)),
+            chunks[0],
+        );
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                "ctrl+s commit · esc cancel",
+                Style::default().fg(t.ui.text_muted),
+            )),
+            chunks[1],
+        );
     }
 
-    pub fn render(&self, frame: &mut Frame) {
-        let area = frame.area();
-
-        let (modal_width, modal_height) = match &self.content {
-            ModalContent::Info { message, .. } => {
-                let width = 80.min(area.width.saturating_sub(4));
-                let lines = message.lines().count() as u16;
-                let height = (lines + 4).min(area.height * 80 / 100).max(5);
-                (width, height)
-            }
-            ModalContent::Select { items, .. } => {
-                let width = 80.min(area.width.saturating_sub(4));
-                let items_count = items.len() as u16;
-                let height = (items_count + 4).min(area.height * 80 / 100).max(5);
-                (width, height)
-            }
-            ModalContent::KeyBindings { sections, .. } => {
-                let width = 60.min(area.width.saturating_sub(4));
-                let total_lines: usize = sections
-                    .iter()
-                    .map(|s| s.bindings.len() + 2) // +2 for section title and spacing
-                    .sum();
-                let height = (total_lines as u16 + 4).min(area.height * 80 / 100).max(5);
-                (width, height)
-            }
-            ModalContent::FilePicker {
-                filtered_indices, ..
-            } => {
-                let width = 80.min(area.width.saturating_sub(4));
-                let items_count = filtered_indices.len().min(15) as u16;
-                let height = (items_count + 5).min(area.height * 80 / 100).max(8);
-                (width, height)
-            }
-        };
+    fn render_settings(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        settings: &DiffViewSettings,
+        selected: usize,
+    ) {
+        let t = theme::get();
+        let block = Block::default()
+            .title(" Settings ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
 
-        let modal_x = (area.width.saturating_sub(modal_width)) / 2;
-        let modal_y = (area.height.saturating_sub(modal_height)) / 2;
-        let modal_area = Rect::new(modal_x, modal_y, modal_width, modal_height);
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
 
-        frame.render_widget(Clear, modal_area);
+        let mut lines: Vec<Line> = SETTINGS_FIELDS
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let style = if i == selected {
+                    Style::default().fg(t.ui.selection_fg).bg(t.ui.selection_bg)
+                } else {
+                    Style::default().fg(t.ui.text_primary)
+                };
+                Line::from(Span::styled(
+                    format!("  {:<16}‹ {} ›", field.label(), field.value(settings)),
+                    style,
+                ))
+            })
+            .collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "  j/k select · h/l change · w save · esc close",
+            Style::default().fg(t.ui.text_muted),
+        )));
 
-        match &self.content {
-            ModalContent::Info { title, message } => {
-                self.render_info(frame, modal_area, title, message);
-            }
-            ModalContent::Select {
-                title,
-                items,
-                selected,
-            } => {
-                self.render_select(frame, modal_area, title, items, *selected);
-            }
-            ModalContent::KeyBindings { title, sections } => {
-                self.render_keybindings(frame, modal_area, title, sections);
-            }
-            ModalContent::FilePicker {
-                title,
-                items,
-                filtered_indices,
-                query,
-                selected,
-            } => {
-                self.render_file_picker(
-                    frame,
-                    modal_area,
-                    title,
-                    items,
-                    filtered_indices,
-                    query,
-                    *selected,
-                );
-            }
-        }
+        frame.render_widget(Paragraph::new(lines), inner);
     }
 
     fn render_info(&self, frame: &mut Frame, area: Rect, title: &str, message: &str) {
@@ -295,6 +1285,7 @@ impl Modal {
         frame.render_widget(para, inner);
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn render_file_picker(
         &self,
         frame: &mut Frame,
@@ -353,6 +1344,8 @@ impl Modal {
                     FileStatus::Added => ("A", t.ui.status_added),
                     FileStatus::Modified => ("M", t.ui.status_modified),
                     FileStatus::Deleted => ("D", t.ui.status_deleted),
+                    FileStatus::Renamed => ("R", t.ui.status_modified),
+                    FileStatus::Copied => ("C", t.ui.status_added),
                 };
 
                 let viewed_char = if item.viewed { "✓" } else { " " };
@@ -390,8 +1383,14 @@ impl Modal {
     /// Handle keyboard input for the modal.
     /// Returns Some(ModalResult) if the modal should close.
     pub fn handle_input(&mut self, key: KeyEvent) -> Option<ModalResult> {
-        // FilePicker handles its own dismiss logic (needs to allow typing 'q')
-        if !matches!(self.content, ModalContent::FilePicker { .. }) {
+        // FilePicker and Comment handle their own dismiss logic (need to allow typing 'q')
+        if !matches!(
+            self.content,
+            ModalContent::FilePicker { .. }
+                | ModalContent::Comment { .. }
+                | ModalContent::CommitMessage { .. }
+                | ModalContent::Filter { .. }
+        ) {
             // Close on Esc, q, or Ctrl+C
             if key.code == KeyCode::Esc
                 || key.code == KeyCode::Char('q')
@@ -435,6 +1434,136 @@ impl Modal {
                 }
                 None
             }
+            ModalContent::Suggestions { items, selected } => match key.code {
+                KeyCode::Char('a') | KeyCode::Enter => {
+                    Some(ModalResult::AcceptSuggestion(*selected))
+                }
+                KeyCode::Char('n') | KeyCode::Char('l') | KeyCode::Right => {
+                    *selected = (*selected + 1).min(items.len().saturating_sub(1));
+                    None
+                }
+                KeyCode::Char('p') | KeyCode::Char('h') | KeyCode::Left => {
+                    *selected = selected.saturating_sub(1);
+                    None
+                }
+                _ => None,
+            },
+            ModalContent::Explanation {
+                scroll,
+                text,
+                streaming,
+                ..
+            } => {
+                match key.code {
+                    KeyCode::Enter => return Some(ModalResult::Dismissed),
+                    KeyCode::Char('y') if !*streaming => {
+                        return Some(ModalResult::Copy(text.clone()))
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageDown | KeyCode::Char('d') => *scroll = scroll.saturating_add(10),
+                    KeyCode::PageUp | KeyCode::Char('u') => *scroll = scroll.saturating_sub(10),
+                    _ => {}
+                }
+                None
+            }
+            ModalContent::Overview {
+                items,
+                sort,
+                selected,
+            } => {
+                match key.code {
+                    KeyCode::Enter => {
+                        return items
+                            .get(*selected)
+                            .map(|item| ModalResult::FileSelected(item.file_index));
+                    }
+                    KeyCode::Char('O') => return Some(ModalResult::Dismissed),
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        *selected = (*selected + 1).min(items.len().saturating_sub(1));
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                    KeyCode::PageDown | KeyCode::Char('d') => {
+                        *selected = (*selected + 10).min(items.len().saturating_sub(1));
+                    }
+                    KeyCode::PageUp | KeyCode::Char('u') => *selected = selected.saturating_sub(10),
+                    KeyCode::Char('g') => *selected = 0,
+                    KeyCode::Char('G') => *selected = items.len().saturating_sub(1),
+                    KeyCode::Char('s') => {
+                        // Stay on the same file in its new place
+                        let current = items.get(*selected).map(|item| item.file_index);
+                        *sort = sort.next();
+                        sort_stats(items, *sort);
+                        *selected = items
+                            .iter()
+                            .position(|item| Some(item.file_index) == current)
+                            .unwrap_or_default();
+                    }
+                    _ => {}
+                }
+                None
+            }
+            ModalContent::PrDetails { scroll, .. } => {
+                match key.code {
+                    KeyCode::Enter | KeyCode::Char('I') => return Some(ModalResult::Dismissed),
+                    KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+                    KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+                    KeyCode::PageDown | KeyCode::Char('d') => *scroll = scroll.saturating_add(10),
+                    KeyCode::PageUp | KeyCode::Char('u') => *scroll = scroll.saturating_sub(10),
+                    _ => {}
+                }
+                None
+            }
+            ModalContent::Confirm { .. } => match key.code {
+                KeyCode::Char('y') | KeyCode::Enter => Some(ModalResult::Confirmed),
+                KeyCode::Char('n') => Some(ModalResult::Dismissed),
+                _ => None,
+            },
+            ModalContent::Settings { settings, selected } => {
+                match key.code {
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        *selected = (*selected + 1).min(SETTINGS_FIELDS.len() - 1);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        *selected = selected.saturating_sub(1);
+                    }
+                    KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter | KeyCode::Char(' ') => {
+                        SETTINGS_FIELDS[*selected].adjust(settings, true);
+                    }
+                    KeyCode::Left | KeyCode::Char('h') => {
+                        SETTINGS_FIELDS[*selected].adjust(settings, false);
+                    }
+                    KeyCode::Char('w') => return Some(ModalResult::SaveSettings),
+                    _ => {}
+                }
+                None
+            }
+            ModalContent::Filter { text, error } => {
+                match key.code {
+                    KeyCode::Esc => return Some(ModalResult::Dismissed),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Some(ModalResult::Dismissed)
+                    }
+                    KeyCode::Enter => match FileFilter::parse(text) {
+                        Ok(_) => return Some(ModalResult::Filter(text.trim().to_string())),
+                        Err(e) => *error = Some(e),
+                    },
+                    KeyCode::Char('u') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        text.clear();
+                        *error = None;
+                    }
+                    KeyCode::Backspace => {
+                        text.pop();
+                        *error = None;
+                    }
+                    KeyCode::Char(c) => {
+                        text.push(c);
+                        *error = None;
+                    }
+                    _ => {}
+                }
+                None
+            }
             ModalContent::FilePicker {
                 items,
                 filtered_indices,
@@ -490,6 +1619,90 @@ impl Modal {
                 }
                 _ => None,
             },
+            ModalContent::Comment {
+                text,
+                candidates,
+                suggestion,
+                review,
+                ..
+            } => {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                let suggestions: Vec<String> = mentions::partial_mention(text)
+                    .map(|partial| mentions::suggest(candidates, partial))
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect();
+                match key.code {
+                    KeyCode::Esc => return Some(ModalResult::Dismissed),
+                    KeyCode::Char('c') if ctrl => return Some(ModalResult::Dismissed),
+                    // A review's comments can speak for themselves, but a request for changes
+                    // needs a reason
+                    KeyCode::Char('s') if ctrl && *review => {
+                        let body = text.trim().to_string();
+                        return Some(ModalResult::SubmitReview(ReviewEvent::Comment, body));
+                    }
+                    KeyCode::Char('a') if ctrl && *review => {
+                        let body = text.trim().to_string();
+                        return Some(ModalResult::SubmitReview(ReviewEvent::Approve, body));
+                    }
+                    KeyCode::Char('r') if ctrl && *review && !text.trim().is_empty() => {
+                        let body = text.trim().to_string();
+                        return Some(ModalResult::SubmitReview(ReviewEvent::RequestChanges, body));
+                    }
+                    KeyCode::Char('s') if ctrl => {
+                        let body = text.trim();
+                        if !body.is_empty() {
+                            return Some(ModalResult::PostComment(body.to_string()));
+                        }
+                    }
+                    KeyCode::Down if !suggestions.is_empty() => {
+                        *suggestion = (*suggestion + 1).min(suggestions.len() - 1);
+                    }
+                    KeyCode::Char('n') if ctrl && !suggestions.is_empty() => {
+                        *suggestion = (*suggestion + 1).min(suggestions.len() - 1);
+                    }
+                    KeyCode::Up if !suggestions.is_empty() => {
+                        *suggestion = suggestion.saturating_sub(1);
+                    }
+                    KeyCode::Char('p') if ctrl && !suggestions.is_empty() => {
+                        *suggestion = suggestion.saturating_sub(1);
+                    }
+                    KeyCode::Tab | KeyCode::Enter if !suggestions.is_empty() => {
+                        let login = &suggestions[(*suggestion).min(suggestions.len() - 1)];
+                        mentions::complete(text, login);
+                        *suggestion = 0;
+                    }
+                    KeyCode::Enter => text.push('\n'),
+                    KeyCode::Backspace => {
+                        text.pop();
+                        *suggestion = 0;
+                    }
+                    KeyCode::Char(c) if !ctrl => {
+                        text.push(c);
+                        *suggestion = 0;
+                    }
+                    _ => {}
+                }
+                None
+            }
+            ModalContent::CommitMessage { text } => {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Esc => return Some(ModalResult::Dismissed),
+                    KeyCode::Char('c') if ctrl => return Some(ModalResult::Dismissed),
+                    KeyCode::Char('s') if ctrl && !text.trim().is_empty() => {
+                        return Some(ModalResult::Commit(text.trim().to_string()));
+                    }
+                    KeyCode::Enter => text.push('\n'),
+                    KeyCode::Backspace => {
+                        text.pop();
+                    }
+                    KeyCode::Char(c) if !ctrl => text.push(c),
+                    _ => {}
+                }
+                None
+            }
         }
     }
 
@@ -512,7 +1725,86 @@ impl Modal {
     }
 }
 
-fn fuzzy_match(text: &str, pattern: &str) -> bool {
+/// Whether the characters of `pattern` appear in `text` in order.
+/// Markdown like a PR description as styled lines: headings, quotes, lists and task lists, and code blocks
+/// set off from the text. HTML comments, which PR templates leave instructions in, are hidden.
+pub fn markdown_lines(markdown: &str) -> Vec<Line<'static>> {
+    let t = theme::get();
+    let mut lines = Vec::new();
+    let mut in_code = false;
+    let mut in_comment = false;
+    for line in markdown.lines() {
+        let content = line.trim_start();
+        let indent = &line[..line.len() - content.len()];
+        if in_comment {
+            in_comment = !content.contains("-->");
+            continue;
+        }
+        if content.starts_with("```") || content.starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(Line::from(Span::styled(
+                format!("  {line}"),
+                Style::default().fg(t.ui.text_secondary),
+            )));
+            continue;
+        }
+        if let Some(rest) = content.strip_prefix("<!--") {
+            in_comment = !rest.contains("-->");
+            continue;
+        }
+
+        let level = content.chars().take_while(|&c| c == '#').count();
+        if let Some(heading) = content[level..].strip_prefix(' ').filter(|_| level > 0) {
+            lines.push(Line::from(Span::styled(
+                heading.trim_end_matches('#').trim().to_string(),
+                Style::default().fg(t.ui.highlight).bold(),
+            )));
+            continue;
+        }
+        if let Some(quote) = content.strip_prefix('>') {
+            lines.push(Line::from(vec![
+                Span::styled(format!("{indent}│ "), Style::default().fg(t.ui.text_muted)),
+                Span::styled(
+                    quote.trim_start().to_string(),
+                    Style::default().fg(t.ui.text_secondary).italic(),
+                ),
+            ]));
+            continue;
+        }
+
+        let (marker, text) = match ["- ", "* ", "+ "]
+            .into_iter()
+            .find_map(|bullet| content.strip_prefix(bullet))
+        {
+            Some(item) => match (item.strip_prefix("[ ] "), item.get(..4)) {
+                (Some(task), _) => ("☐ ", task),
+                (None, Some("[x] " | "[X] ")) => ("☑ ", &item[4..]),
+                _ => ("• ", item),
+            },
+            None => ("", content),
+        };
+        let mut spans = vec![Span::styled(
+            format!("{indent}{marker}"),
+            Style::default().fg(t.ui.text_muted),
+        )];
+        // Inline code between backticks, the rest as written
+        for (i, part) in text.split('`').enumerate() {
+            let color = if i % 2 == 1 {
+                t.ui.highlight
+            } else {
+                t.ui.text_primary
+            };
+            spans.push(Span::styled(part.to_string(), Style::default().fg(color)));
+        }
+        lines.push(Line::from(spans));
+    }
+    lines
+}
+
+pub fn fuzzy_match(text: &str, pattern: &str) -> bool {
     if pattern.is_empty() {
         return true;
     }
@@ -527,3 +1819,146 @@ fn fuzzy_match(text: &str, pattern: &str) -> bool {
     }
     pattern_chars.peek().is_none()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_adjust_within_bounds() {
+        let mut settings = DiffViewSettings::default();
+
+        for _ in 0..20 {
+            SettingsField::TabWidth.adjust(&mut settings, true);
+        }
+        assert_eq!(settings.tab_width, MAX_TAB_WIDTH);
+
+        settings.context.max_lines = 1;
+        SettingsField::ContextLines.adjust(&mut settings, false);
+        assert!(!settings.context.enabled);
+        SettingsField::ContextLines.adjust(&mut settings, true);
+        assert!(settings.context.enabled);
+        assert_eq!(settings.context.max_lines, 1);
+
+        SettingsField::SidebarWidth.adjust(&mut settings, true);
+        assert_eq!(settings.sidebar_width, Some(20));
+        SettingsField::SidebarWidth.adjust(&mut settings, false);
+        assert_eq!(settings.sidebar_width, None);
+
+        SettingsField::Layout.adjust(&mut settings, true);
+        assert_eq!(SettingsField::Layout.value(&settings), "unified");
+        assert_eq!(settings.to_config().unified, Some(true));
+    }
+
+    #[test]
+    fn test_explanation_streams_and_notes_failure() {
+        let copy = KeyEvent::new(KeyCode::Char('y'), KeyModifiers::NONE);
+        let mut modal = Modal::explanation("Explain");
+        modal.push_explanation("Adds a ");
+        assert!(modal.handle_input(copy).is_none());
+        modal.push_explanation("retry.");
+        modal.finish_explanation(Some("connection reset"));
+        assert!(matches!(
+            modal.handle_input(copy),
+            Some(ModalResult::Copy(text)) if text.starts_with("Adds a retry.")
+        ));
+        let ModalContent::Explanation {
+            text, streaming, ..
+        } = &modal.content
+        else {
+            panic!("not an explanation");
+        };
+        assert_eq!(text, "Adds a retry.\n\nExplaining failed: connection reset");
+        assert!(!streaming);
+        assert!(modal.is_explanation());
+    }
+
+    #[test]
+    fn test_markdown_lines_hide_comments_and_style_lists() {
+        let text = |line: &Line| {
+            line.spans
+                .iter()
+                .map(|s| s.content.as_ref())
+                .collect::<String>()
+        };
+        let lines = markdown_lines(
+            "## Summary\n<!-- Describe\nthe change -->\nUses `retry`.\n- [x] Tests\n```\nlet a = 1;\n```",
+        );
+        assert_eq!(
+            lines.iter().map(text).collect::<Vec<_>>(),
+            vec!["Summary", "Uses retry.", "☑ Tests", "  let a = 1;"]
+        );
+    }
+
+    #[test]
+    fn test_comment_completes_mentions_and_posts() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let mut modal = Modal::comment("Comment", Vec::new());
+        modal.set_mention_candidates(&["quinn".to_string(), "qa-bot".to_string()]);
+        for c in "lgtm @q".chars() {
+            assert!(modal.handle_input(key(KeyCode::Char(c))).is_none());
+        }
+        modal.handle_input(key(KeyCode::Down));
+        modal.handle_input(key(KeyCode::Tab));
+        // Enter adds a line once there's nothing to complete, and 'q' doesn't close
+        modal.handle_input(key(KeyCode::Enter));
+        modal.handle_input(key(KeyCode::Char('q')));
+
+        let result = modal.handle_input(KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL));
+        assert!(matches!(
+            result,
+            Some(ModalResult::PostComment(body)) if body == "lgtm @qa-bot \nq"
+        ));
+    }
+
+    #[test]
+    fn test_commit_message_is_edited_before_committing() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let ctrl_s = KeyEvent::new(KeyCode::Char('s'), KeyModifiers::CONTROL);
+        let mut modal = Modal::commit_message("fix: Handle empty input");
+        modal.handle_input(key(KeyCode::Enter));
+        modal.handle_input(key(KeyCode::Enter));
+        // 'q' is typed rather than closing the popup
+        for c in "quit early".chars() {
+            assert!(modal.handle_input(key(KeyCode::Char(c))).is_none());
+        }
+        let result = modal.handle_input(ctrl_s);
+        assert!(matches!(
+            result,
+            Some(ModalResult::Commit(message)) if message == "fix: Handle empty input\n\nquit early"
+        ));
+
+        let mut empty = Modal::commit_message("");
+        assert!(empty.handle_input(ctrl_s).is_none());
+        assert!(matches!(
+            empty.handle_input(key(KeyCode::Esc)),
+            Some(ModalResult::Dismissed)
+        ));
+    }
+
+    #[test]
+    fn test_overview_keeps_the_selected_file_when_sorting() {
+        let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        let stats = |file_index: usize, name: &str, added: usize| FileStats {
+            file_index,
+            name: name.to_string(),
+            status: DiffStatus::Modified,
+            language: String::new(),
+            added,
+            removed: 0,
+            binary: false,
+        };
+        let mut modal = Modal::overview(vec![stats(0, "a.rs", 1), stats(1, "b.rs", 9)]);
+        // Most changed first, so b.rs is on top
+        modal.handle_input(key(KeyCode::Char('j')));
+        modal.handle_input(key(KeyCode::Char('s')));
+        let ModalContent::Overview { sort, selected, .. } = &modal.content else {
+            panic!("expected the overview");
+        };
+        assert_eq!((*sort, *selected), (OverviewSort::Path, 0));
+        assert!(matches!(
+            modal.handle_input(key(KeyCode::Enter)),
+            Some(ModalResult::FileSelected(0))
+        ));
+    }
+}
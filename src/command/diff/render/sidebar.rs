@@ -1,13 +1,17 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use ratatui::{
     prelude::*,
-    widgets::{Block, Borders, Paragraph},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
 };
 
+use crate::command::diff::file_summaries::FileSummaries;
+use crate::command::diff::risk::{RiskLevel, RiskScores};
+use crate::command::diff::stage::IndexState;
 use crate::command::diff::theme;
 use crate::command::diff::types::{FileStatus, SidebarItem};
 
+#[allow(clippy::too_many_arguments)]
 pub fn render_sidebar(
     frame: &mut Frame,
     area: Rect,
@@ -17,7 +21,12 @@ pub fn render_sidebar(
     sidebar_scroll: usize,
     sidebar_h_scroll: u16,
     viewed_files: &HashSet<usize>,
+    risk: Option<&RiskScores>,
+    summaries: Option<&FileSummaries>,
+    index_states: &HashMap<usize, IndexState>,
     is_focused: bool,
+    filter: Option<&str>,
+    loading: &HashSet<usize>,
 ) {
     let t = theme::get();
     let visible_height = area.height.saturating_sub(2) as usize;
@@ -25,35 +34,22 @@ pub fn render_sidebar(
         .iter()
         .enumerate()
         .map(|(i, item)| {
+            let risk_level = match item {
+                SidebarItem::File { path, .. } => risk.and_then(|risk| risk.get(path)),
+                SidebarItem::Directory { .. } => None,
+            };
             let (prefix, status_symbol, status_color, name, is_current_file, is_viewed) = match item
             {
                 SidebarItem::Directory {
-                    name, path, depth, ..
+                    name,
+                    depth,
+                    collapsed,
+                    files,
+                    ..
                 } => {
                     let indent = "  ".repeat(*depth);
-                    let all_children_viewed = sidebar_items.iter().all(|child| {
-                        if let SidebarItem::File {
-                            path: file_path,
-                            file_index,
-                            ..
-                        } = child
-                        {
-                            if file_path.starts_with(&format!("{}/", path)) {
-                                return viewed_files.contains(file_index);
-                            }
-                        }
-                        true
-                    });
-                    let has_children = sidebar_items.iter().any(|child| {
-                        if let SidebarItem::File {
-                            path: file_path, ..
-                        } = child
-                        {
-                            file_path.starts_with(&format!("{}/", path))
-                        } else {
-                            false
-                        }
-                    });
+                    let all_children_viewed = files.iter().all(|i| viewed_files.contains(i));
+                    let has_children = !files.is_empty();
                     let marker = if has_children && all_children_viewed {
                         "✓ "
                     } else {
@@ -61,7 +57,7 @@ pub fn render_sidebar(
                     };
                     (
                         format!("{}{}", indent, marker),
-                        "▼".to_string(),
+                        if *collapsed { "▶" } else { "▼" }.to_string(),
                         None,
                         format!(" {}", name),
                         false,
@@ -70,25 +66,38 @@ pub fn render_sidebar(
                 }
                 SidebarItem::File {
                     name,
+                    path,
                     file_index,
                     depth,
                     status,
-                    ..
                 } => {
                     let indent = "  ".repeat(*depth);
                     let viewed = viewed_files.contains(file_index);
                     let marker = if viewed { "✓ " } else { "  " };
+                    let loaded = !loading.contains(file_index);
                     let status_color = match status {
+                        _ if !loaded => Some(t.ui.text_muted),
                         FileStatus::Modified => Some(t.ui.status_modified),
                         FileStatus::Added => Some(t.ui.status_added),
                         FileStatus::Deleted => Some(t.ui.status_deleted),
+                        FileStatus::Renamed { .. } => Some(t.ui.status_modified),
+                        FileStatus::Copied { .. } => Some(t.ui.status_added),
+                    };
+                    // Until a file is read its status is a guess, so it gets a spinner instead
+                    let status_symbol = if loaded {
+                        status.symbol().to_string()
+                    } else {
+                        "⋯".to_string()
+                    };
+                    let name = match status.source() {
+                        Some(from) => format!(" {} → {}", moved_from_label(from, path), name),
+                        None => format!(" {}", name),
                     };
-                    let status_symbol = status.symbol().to_string();
                     (
                         format!("{}{}", indent, marker),
                         status_symbol,
                         status_color,
-                        format!(" {}", name),
+                        name,
                         *file_index == current_file,
                         viewed,
                     )
@@ -118,11 +127,78 @@ pub fn render_sidebar(
                 base_style
             };
 
-            Line::from(vec![
+            let mut spans = vec![
                 Span::styled(prefix, base_style),
                 Span::styled(status_symbol, status_style),
-                Span::styled(name, base_style),
-            ])
+            ];
+            if risk.is_some() {
+                let (marker, color) = match risk_level {
+                    Some(RiskLevel::High) => ("●", t.ui.risk_high),
+                    Some(RiskLevel::Medium) => ("●", t.ui.risk_medium),
+                    Some(RiskLevel::Low) => ("·", t.ui.risk_low),
+                    None => (" ", t.ui.risk_low),
+                };
+                let risk_style = if is_selected {
+                    base_style
+                } else {
+                    Style::default().fg(color)
+                };
+                spans.push(Span::styled(format!(" {marker}"), risk_style));
+            }
+            if !index_states.is_empty() {
+                let state = match item {
+                    SidebarItem::File { file_index, .. } => index_states.get(file_index),
+                    SidebarItem::Directory { .. } => None,
+                };
+                let (marker, color) = match state {
+                    Some(IndexState::Staged) => ("●", t.ui.status_added),
+                    Some(IndexState::PartlyStaged) => ("◐", t.ui.status_modified),
+                    None => (" ", t.ui.text_muted),
+                };
+                let staged_style = if is_selected {
+                    base_style
+                } else {
+                    Style::default().fg(color)
+                };
+                spans.push(Span::styled(format!(" {marker}"), staged_style));
+            }
+            spans.push(Span::styled(name, base_style));
+            let summary = match item {
+                SidebarItem::File { path, .. } => summaries.and_then(|s| s.get(path)),
+                SidebarItem::Directory { .. } => None,
+            };
+            if let Some(summary) = summary {
+                let summary_style = if is_selected {
+                    base_style
+                } else {
+                    Style::default().fg(t.ui.text_muted)
+                };
+                spans.push(Span::styled(format!("  {summary}"), summary_style));
+            }
+            if let SidebarItem::Directory {
+                added,
+                removed,
+                collapsed,
+                files,
+                ..
+            } = item
+            {
+                let (added_style, removed_style, muted_style) = if is_selected {
+                    (base_style, base_style, base_style)
+                } else {
+                    (
+                        Style::default().fg(t.ui.status_added),
+                        Style::default().fg(t.ui.status_deleted),
+                        Style::default().fg(t.ui.text_muted),
+                    )
+                };
+                if *collapsed {
+                    spans.push(Span::styled(format!(" ({})", files.len()), muted_style));
+                }
+                spans.push(Span::styled(format!(" +{added}"), added_style));
+                spans.push(Span::styled(format!(" -{removed}"), removed_style));
+            }
+            Line::from(spans)
         })
         .collect();
 
@@ -139,14 +215,104 @@ pub fn render_sidebar(
         .take(visible_height)
         .collect();
 
+    let mut title = match filter {
+        Some(filter) => format!(" [1] Files: {filter} "),
+        None => " [1] Files ".to_string(),
+    };
+    if !loading.is_empty() {
+        title.push_str(&format!("· loading {} ", loading.len()));
+    }
     let para = Paragraph::new(visible_lines)
         .scroll((0, sidebar_h_scroll))
         .block(
             Block::default()
-                .title(Line::styled(" [1] Files ", title_style))
+                .title(Line::styled(title, title_style))
                 .borders(Borders::ALL)
                 .border_style(border_style),
         );
 
     frame.render_widget(para, area);
 }
+
+/// Widest the box with a file's summary gets
+const TOOLTIP_WIDTH: u16 = 60;
+
+/// The selected file's whole summary in a box next to its `row` of the `sidebar`, which is
+/// usually too narrow to show it.
+pub fn render_summary_tooltip(frame: &mut Frame, sidebar: Rect, row: u16, summary: &str) {
+    let t = theme::get();
+    let screen = frame.area();
+    let x = sidebar.right().saturating_sub(1);
+    let width = TOOLTIP_WIDTH.min(screen.right().saturating_sub(x));
+    if width < 10 {
+        return;
+    }
+    let text_width = width.saturating_sub(2).max(1) as usize;
+    let height = (wrapped_line_count(summary, text_width) as u16 + 2).min(screen.height);
+    // Below the row, or above it when there's no room left underneath
+    let row_y = sidebar.y + 1 + row;
+    let y = if row_y + 1 + height <= screen.bottom() {
+        row_y + 1
+    } else {
+        row_y.saturating_sub(height)
+    };
+    let area = Rect::new(x, y, width, height).intersection(screen);
+
+    frame.render_widget(Clear, area);
+    frame.render_widget(
+        Paragraph::new(summary.to_string())
+            .style(Style::default().fg(t.ui.text_primary))
+            .wrap(Wrap { trim: true })
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::default().fg(t.ui.border_focused)),
+            ),
+        area,
+    );
+}
+
+/// Lines `text` takes up when wrapped at word boundaries to `width` columns.
+fn wrapped_line_count(text: &str, width: usize) -> usize {
+    let mut lines = 1;
+    let mut column = 0;
+    for word in text.split_whitespace() {
+        let len = word.chars().count();
+        if column > 0 && column + 1 + len > width {
+            lines += 1;
+            column = 0;
+        }
+        column += if column > 0 { 1 + len } else { len };
+        // Words longer than a line are broken up
+        while column > width {
+            lines += 1;
+            column -= width;
+        }
+    }
+    lines
+}
+
+/// The old side of a rename or copy as shown before its new name: just the file name when it
+/// stayed in the same directory, else the whole path.
+fn moved_from_label<'a>(from: &'a str, to: &str) -> &'a str {
+    let (from_dir, from_name) = from.rsplit_once('/').unwrap_or(("", from));
+    let to_dir = to.rsplit_once('/').map_or("", |(dir, _)| dir);
+    if from_dir == to_dir {
+        from_name
+    } else {
+        from
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrapped_line_count() {
+        assert_eq!(wrapped_line_count("Adds retry", 20), 1);
+        assert_eq!(wrapped_line_count("Adds retry with backoff", 12), 2);
+        assert_eq!(wrapped_line_count("a verylongidentifier b", 8), 4);
+    }
+}
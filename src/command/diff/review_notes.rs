@@ -0,0 +1,272 @@
+//! Notes of the reviewer's own, written on lines while reading a diff and exported as a
+//! markdown review summary once the review is done.
+//!
+//! They're kept in `.git/lumen/notes/` (per worktree), one file per ref range, PR or the
+//! working tree, so closing the viewer and coming back to the same comparison picks them up.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::logging;
+use crate::provider::LumenProvider;
+
+use super::pending_review::{DraftComment, Side};
+use super::risk::RiskLevel;
+use super::types::LineNote;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReviewNote {
+    pub file: String,
+    /// First line of a note on several lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<usize>,
+    pub line: usize,
+    /// Whether the lines are removed ones, numbered in the base
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub removed: bool,
+    pub text: String,
+}
+
+impl ReviewNote {
+    /// A note on the lines a review comment would go on.
+    pub fn on(target: &DraftComment, text: String) -> Self {
+        ReviewNote {
+            file: target.path.clone(),
+            start_line: target.start_line,
+            line: target.line,
+            removed: target.side == Side::Left,
+            text,
+        }
+    }
+
+    fn same_lines(&self, other: &ReviewNote) -> bool {
+        self.file == other.file
+            && self.start_line == other.start_line
+            && self.line == other.line
+            && self.removed == other.removed
+    }
+
+    /// `src/a.rs:10-12`, with `(removed)` for lines of the base
+    pub fn location(&self) -> String {
+        let lines = match self.start_line {
+            Some(start) => format!("{start}-{}", self.line),
+            None => self.line.to_string(),
+        };
+        let removed = if self.removed { " (removed)" } else { "" };
+        format!("{}:{lines}{removed}", self.file)
+    }
+}
+
+/// The notes on one comparison
+#[derive(Debug, Default)]
+pub struct ReviewNotes {
+    /// What the notes are on, like `PR #42` or `main..feature`
+    pub title: String,
+    notes: Vec<ReviewNote>,
+    /// Where they're kept, none outside a repository
+    path: Option<PathBuf>,
+}
+
+/// The file name for the notes on `title`, with anything but letters, digits, `-` and `.`
+/// made a `_`.
+fn file_name(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{stem}.json")
+}
+
+fn notes_path(title: &str) -> Option<PathBuf> {
+    let output =
+        logging::output(Command::new("git").args(["rev-parse", "--git-path", "lumen/notes"]))
+            .ok()?;
+    output.status.success().then(|| {
+        PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()).join(file_name(title))
+    })
+}
+
+impl ReviewNotes {
+    /// The notes written so far on `title`.
+    pub fn load(title: String) -> Self {
+        let path = notes_path(&title);
+        let notes = path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default();
+        ReviewNotes { title, notes, path }
+    }
+
+    pub fn len(&self) -> usize {
+        self.notes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.notes.is_empty()
+    }
+
+    /// The text of the note on `target`'s lines, to edit it.
+    pub fn text_on(&self, target: &DraftComment) -> Option<&str> {
+        let probe = ReviewNote::on(target, String::new());
+        self.notes
+            .iter()
+            .find(|note| note.same_lines(&probe))
+            .map(|note| note.text.as_str())
+    }
+
+    /// Add `note`, replacing the one on the same lines, and save them all.
+    pub fn add(&mut self, note: ReviewNote) -> Result<(), String> {
+        match self.notes.iter_mut().find(|n| n.same_lines(&note)) {
+            Some(existing) => *existing = note,
+            None => self.notes.push(note),
+        }
+        self.save()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or("notes are only kept inside a git repository")?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+        }
+        let json = serde_json::to_string_pretty(&self.notes).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Notes on the new version, shown under their last line.
+    pub fn line_notes(&self) -> Vec<LineNote> {
+        self.notes
+            .iter()
+            .filter(|note| !note.removed)
+            .map(|note| LineNote {
+                file: note.file.clone(),
+                line: note.line,
+                level: RiskLevel::Low,
+                text: format!("Note: {}", note.text),
+            })
+            .collect()
+    }
+
+    /// A markdown review summary: the notes by file, in the order of the diff's files, each
+    /// under the lines it's on.
+    pub fn to_markdown(&self, files: &[String]) -> String {
+        let mut notes: Vec<&ReviewNote> = self.notes.iter().collect();
+        let order = |file: &str| files.iter().position(|f| f == file).unwrap_or(files.len());
+        notes.sort_by_key(|note| {
+            let first = note.start_line.unwrap_or(note.line);
+            (order(&note.file), note.file.clone(), first, note.removed)
+        });
+
+        let mut markdown = format!("# Review notes: {}\n", self.title);
+        let mut file = None;
+        for note in notes {
+            if file != Some(&note.file) {
+                markdown.push_str(&format!("\n## `{}`\n\n", note.file));
+                file = Some(&note.file);
+            }
+            let mut lines = note.text.lines();
+            markdown.push_str(&format!(
+                "- `{}`: {}\n",
+                note.location(),
+                lines.next().unwrap_or_default()
+            ));
+            for line in lines {
+                markdown.push_str(&format!("  {line}\n"));
+            }
+        }
+        markdown
+    }
+}
+
+/// Have the AI tidy up the notes' markdown into a review summary (non-blocking).
+pub fn polish_async(
+    provider: Arc<LumenProvider>,
+    markdown: String,
+) -> Receiver<Result<String, String>> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime
+                    .block_on(provider.polish_review_notes(&markdown))
+                    .map_err(|e| e.to_string())
+            });
+        let _ = tx.send(result);
+    });
+    rx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(file: &str, start_line: Option<usize>, line: usize, text: &str) -> ReviewNote {
+        ReviewNote {
+            file: file.to_string(),
+            start_line,
+            line,
+            removed: false,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_file_name() {
+        assert_eq!(file_name("main..feature"), "main..feature.json");
+        assert_eq!(file_name("PR #42"), "PR__42.json");
+        assert_eq!(file_name("origin/main...HEAD"), "origin_main...HEAD.json");
+    }
+
+    #[test]
+    fn test_add_replaces_note_on_same_lines() {
+        let mut notes = ReviewNotes::default();
+        let _ = notes.add(note("a.rs", None, 3, "first"));
+        let _ = notes.add(note("a.rs", Some(1), 3, "range"));
+        let _ = notes.add(note("a.rs", None, 3, "second"));
+        assert_eq!(notes.len(), 2);
+        assert_eq!(notes.notes[0].text, "second");
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let mut notes = ReviewNotes {
+            title: "main..feature".to_string(),
+            ..ReviewNotes::default()
+        };
+        let _ = notes.add(note("b.rs", None, 9, "Why unwrap here?"));
+        let _ = notes.add(note("a.rs", Some(4), 6, "Rename this\nit shadows `len`"));
+        let _ = notes.add(ReviewNote {
+            removed: true,
+            ..note("b.rs", None, 2, "Was this check needed?")
+        });
+        let files = vec!["b.rs".to_string(), "a.rs".to_string()];
+        assert_eq!(
+            notes.to_markdown(&files),
+            "# Review notes: main..feature\n\
+             \n## `b.rs`\n\n\
+             - `b.rs:2 (removed)`: Was this check needed?\n\
+             - `b.rs:9`: Why unwrap here?\n\
+             \n## `a.rs`\n\n\
+             - `a.rs:4-6`: Rename this\n  it shadows `len`\n"
+        );
+        assert_eq!(notes.line_notes().len(), 2);
+    }
+}
@@ -0,0 +1,330 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+use lumen_core::patch::estimate_tokens;
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+
+use crate::provider::LumenProvider;
+
+use super::types::{FileDiff, FileStatus};
+
+/// How much reviewer attention a changed file deserves
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskLevel {
+    Low,
+    Medium,
+    High,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FileKind {
+    Logic,
+    Config,
+    Test,
+    Docs,
+}
+
+impl FileKind {
+    fn name(self) -> &'static str {
+        match self {
+            FileKind::Logic => "logic",
+            FileKind::Config => "config",
+            FileKind::Test => "test",
+            FileKind::Docs => "docs",
+        }
+    }
+}
+
+const CONFIG_EXTENSIONS: &[&str] = &[
+    "json", "toml", "yaml", "yml", "lock", "ini", "cfg", "conf", "env", "xml",
+];
+const CONFIG_FILES: &[&str] = &["Dockerfile", "Makefile", "Justfile", ".gitignore"];
+const DOCS_EXTENSIONS: &[&str] = &["md", "mdx", "txt", "rst", "adoc"];
+
+/// Tokens that add a branch or an early exit, a rough measure of how tricky a change is
+const BRANCH_TOKENS: &[&str] = &[
+    "if ", "else", "match ", "switch", "case ", "for ", "while ", "loop", "catch", "unsafe",
+    "return", "&&", "||", "?;", "?)",
+];
+
+fn file_kind(filename: &str) -> FileKind {
+    let lower = filename.to_lowercase();
+    let name = lower.rsplit('/').next().unwrap_or(&lower);
+    let ext = name.rsplit_once('.').map(|(_, ext)| ext).unwrap_or("");
+
+    let in_test_dir = lower
+        .split('/')
+        .any(|dir| matches!(dir, "test" | "tests" | "spec" | "__tests__"));
+    let test_name = name.starts_with("test_")
+        || name.contains("_test.")
+        || name.contains(".test.")
+        || name.contains(".spec.");
+    if in_test_dir || test_name {
+        FileKind::Test
+    } else if DOCS_EXTENSIONS.contains(&ext) || lower.starts_with("docs/") {
+        FileKind::Docs
+    } else if CONFIG_EXTENSIONS.contains(&ext)
+        || CONFIG_FILES.iter().any(|f| f.eq_ignore_ascii_case(name))
+    {
+        FileKind::Config
+    } else {
+        FileKind::Logic
+    }
+}
+
+/// Changed line count and how many of those lines branch
+fn change_stats(diff: &FileDiff) -> (usize, usize) {
+    let text_diff = TextDiff::from_lines(&diff.old_content, &diff.new_content);
+    let mut changed = 0;
+    let mut branches = 0;
+    for change in text_diff.iter_all_changes() {
+        if change.tag() == ChangeTag::Equal {
+            continue;
+        }
+        changed += 1;
+        let line = change.value().trim();
+        if BRANCH_TOKENS.iter().any(|token| line.contains(token)) {
+            branches += 1;
+        }
+    }
+    (changed, branches)
+}
+
+/// Score a file from what kind of file it is, how much changed and how much of the
+/// change is control flow.
+fn heuristic_risk(diff: &FileDiff) -> RiskLevel {
+    let kind = file_kind(&diff.filename);
+    let (changed, branches) = change_stats(diff);
+
+    let mut points = match kind {
+        FileKind::Logic => 2,
+        FileKind::Config => 1,
+        FileKind::Test | FileKind::Docs => 0,
+    };
+    points += match changed {
+        0..=50 => 0,
+        51..=200 => 1,
+        _ => 2,
+    };
+    if kind == FileKind::Logic {
+        points += match branches {
+            0..=3 => 0,
+            4..=10 => 1,
+            _ => 2,
+        };
+        // Removing code is usually safer than adding it
+        if diff.status == FileStatus::Deleted {
+            points -= 1;
+        }
+    }
+
+    match points {
+        ..=1 => RiskLevel::Low,
+        2..=3 => RiskLevel::Medium,
+        _ => RiskLevel::High,
+    }
+}
+
+/// Risk per filename: heuristic scores, overridden by AI scores once they arrive.
+#[derive(Debug, Default)]
+pub struct RiskScores {
+    heuristic: HashMap<String, RiskLevel>,
+    refined: HashMap<String, RiskLevel>,
+}
+
+impl RiskScores {
+    pub fn new(file_diffs: &[FileDiff]) -> Self {
+        let mut scores = RiskScores::default();
+        scores.update(file_diffs, None);
+        scores
+    }
+
+    /// Rescore after a reload. AI scores of files that changed since are dropped.
+    pub fn update(&mut self, file_diffs: &[FileDiff], changed_files: Option<&HashSet<String>>) {
+        self.heuristic = file_diffs
+            .iter()
+            .map(|diff| (diff.filename.clone(), heuristic_risk(diff)))
+            .collect();
+        self.refined.retain(|filename, _| {
+            self.heuristic.contains_key(filename)
+                && !changed_files.is_some_and(|changed| changed.contains(filename))
+        });
+    }
+
+    pub fn refine(&mut self, scores: HashMap<String, RiskLevel>) {
+        self.refined.extend(
+            scores
+                .into_iter()
+                .filter(|(filename, _)| self.heuristic.contains_key(filename)),
+        );
+    }
+
+    pub fn get(&self, filename: &str) -> Option<RiskLevel> {
+        self.refined
+            .get(filename)
+            .or_else(|| self.heuristic.get(filename))
+            .copied()
+    }
+}
+
+/// Past this share of the token budget, only the per-file summary is sent
+const DIFF_BUDGET_SHARE: usize = 2;
+
+/// Ask the provider to rescore the files on a background thread. The receiver gets the
+/// scores, or an error message to show in the footer.
+pub fn refine_in_background(
+    provider: Arc<LumenProvider>,
+    file_diffs: &[FileDiff],
+    scores: &RiskScores,
+) -> Receiver<Result<HashMap<String, RiskLevel>, String>> {
+    let (tx, rx) = mpsc::channel();
+
+    let summary: String = file_diffs
+        .iter()
+        .map(|diff| {
+            let (changed, branches) = change_stats(diff);
+            let level = scores.get(&diff.filename).unwrap_or(RiskLevel::Low);
+            format!(
+                "- {} ({}, {}, {changed} changed lines, {branches} branching, heuristic: {level:?})\n",
+                diff.filename,
+                diff.status.symbol(),
+                file_kind(&diff.filename).name(),
+            )
+        })
+        .collect();
+    let diff: String = file_diffs
+        .iter()
+        .map(|diff| {
+            TextDiff::from_lines(&diff.old_content, &diff.new_content)
+                .unified_diff()
+                .header(
+                    &format!("a/{}", diff.filename),
+                    &format!("b/{}", diff.filename),
+                )
+                .to_string()
+        })
+        .collect();
+
+    thread::spawn(move || {
+        let diff =
+            (estimate_tokens(&diff) <= provider.max_tokens() / DIFF_BUDGET_SHARE).then_some(diff);
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime
+                    .block_on(provider.rate_risk(&summary, diff.as_deref()))
+                    .map_err(|e| e.to_string())
+            })
+            .and_then(|response| parse_risk_response(&response));
+        let _ = tx.send(result);
+    });
+
+    rx
+}
+
+#[derive(Deserialize)]
+struct RiskResponse {
+    files: Vec<FileRisk>,
+}
+
+#[derive(Deserialize)]
+struct FileRisk {
+    path: String,
+    risk: RiskLevel,
+}
+
+fn parse_risk_response(response: &str) -> Result<HashMap<String, RiskLevel>, String> {
+    // Models sometimes wrap JSON in a code fence despite JSON mode
+    let json = response
+        .trim()
+        .trim_start_matches("```json")
+        .trim_start_matches("```")
+        .trim_end_matches("```");
+    let parsed: RiskResponse =
+        serde_json::from_str(json).map_err(|e| format!("invalid risk response: {e}"))?;
+    Ok(parsed
+        .files
+        .into_iter()
+        .map(|file| (file.path, file.risk))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(filename: &str, old: &str, new: &str) -> FileDiff {
+        FileDiff {
+            filename: filename.to_string(),
+            old_content: old.to_string(),
+            new_content: new.to_string(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_file_kind() {
+        assert_eq!(file_kind("src/main.rs"), FileKind::Logic);
+        assert_eq!(file_kind("tests/cli.rs"), FileKind::Test);
+        assert_eq!(file_kind("web/app.test.ts"), FileKind::Test);
+        assert_eq!(file_kind("Cargo.toml"), FileKind::Config);
+        assert_eq!(file_kind("README.md"), FileKind::Docs);
+    }
+
+    #[test]
+    fn test_heuristic_risk() {
+        let branchy: String = (0..12)
+            .map(|i| format!("if x == {i} {{ return; }}\n"))
+            .collect();
+        assert_eq!(
+            heuristic_risk(&file("src/auth.rs", "", &branchy)),
+            RiskLevel::High
+        );
+        assert_eq!(
+            heuristic_risk(&file("src/lib.rs", "a\n", "b\n")),
+            RiskLevel::Medium
+        );
+        assert_eq!(
+            heuristic_risk(&file("tests/auth.rs", "", &branchy)),
+            RiskLevel::Low
+        );
+    }
+
+    #[test]
+    fn test_refined_scores_win_until_file_changes() {
+        let files = vec![file("README.md", "a\n", "b\n")];
+        let mut scores = RiskScores::new(&files);
+        assert_eq!(scores.get("README.md"), Some(RiskLevel::Low));
+
+        scores.refine(HashMap::from([
+            ("README.md".to_string(), RiskLevel::High),
+            ("unknown.rs".to_string(), RiskLevel::High),
+        ]));
+        assert_eq!(scores.get("README.md"), Some(RiskLevel::High));
+        assert_eq!(scores.get("unknown.rs"), None);
+
+        let changed = HashSet::from(["README.md".to_string()]);
+        scores.update(&files, Some(&changed));
+        assert_eq!(scores.get("README.md"), Some(RiskLevel::Low));
+    }
+
+    #[test]
+    fn test_parse_risk_response() {
+        let scores = parse_risk_response(
+            "```json\n{\"files\": [{\"path\": \"src/a.rs\", \"risk\": \"high\"}]}\n```",
+        )
+        .unwrap();
+        assert_eq!(scores.get("src/a.rs"), Some(&RiskLevel::High));
+        assert!(
+            parse_risk_response("{\"files\": [{\"path\": \"a\", \"risk\": \"severe\"}]}").is_err()
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Periodic AI summaries of a `--watch` session, like a scribe pairing with you.
+//!
+//! Each run writes the working tree as a tree object (see `snapshot::working_tree`) and diffs
+//! it against the tree from the previous summary, so a summary covers exactly what changed in
+//! between, whatever the viewer itself is comparing against.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use lumen_core::patch::{estimate_tokens, GIT_DIFF_EXCLUSIONS};
+
+use crate::command::snapshot;
+use crate::provider::LumenProvider;
+use crate::vcs;
+
+/// What the viewer should show after polling the scribe
+pub enum ScribeNote {
+    /// A new summary, for a popup
+    Summary { minutes: u64, text: String },
+    /// A one-line update for the status bar
+    Status(String),
+}
+
+struct Update {
+    tree: String,
+    /// None when nothing changed, and for the first run, which only records the baseline
+    summary: Option<String>,
+}
+
+pub struct Scribe {
+    provider: Arc<LumenProvider>,
+    interval: Duration,
+    /// Append summaries here instead of showing them
+    log: Option<PathBuf>,
+    /// Working tree at the last summary, and when it was recorded
+    baseline: Option<String>,
+    baseline_at: Instant,
+    last_run: Instant,
+    job: Option<(Instant, Receiver<Result<Update, String>>)>,
+}
+
+impl Scribe {
+    /// Record the working tree as it is now; the first summary follows after `interval`.
+    pub fn start(provider: Arc<LumenProvider>, interval: Duration, log: Option<PathBuf>) -> Self {
+        let now = Instant::now();
+        let mut scribe = Self {
+            provider,
+            interval,
+            log,
+            baseline: None,
+            baseline_at: now,
+            last_run: now,
+            job: None,
+        };
+        scribe.spawn();
+        scribe
+    }
+
+    /// Collect a finished summary, or start the next one once the interval has passed.
+    /// Meant to be called on every tick of the event loop.
+    pub fn poll(&mut self) -> Option<ScribeNote> {
+        let Some((started, job)) = &self.job else {
+            if self.last_run.elapsed() >= self.interval {
+                self.spawn();
+            }
+            return None;
+        };
+
+        let result = match job.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Err("summary worker stopped".to_string()),
+        };
+        let started = *started;
+        self.job = None;
+
+        match result {
+            Ok(update) => {
+                let minutes = (started - self.baseline_at).as_secs().div_ceil(60).max(1);
+                if self.baseline.as_ref() != Some(&update.tree) {
+                    self.baseline = Some(update.tree);
+                    self.baseline_at = started;
+                }
+                update.summary.map(|text| self.deliver(minutes, text))
+            }
+            Err(e) => Some(ScribeNote::Status(format!("Session summary failed: {e}"))),
+        }
+    }
+
+    fn spawn(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        let provider = Arc::clone(&self.provider);
+        let baseline = self.baseline.clone();
+        let now = Instant::now();
+        let minutes = (now - self.baseline_at).as_secs().div_ceil(60).max(1);
+        thread::spawn(move || {
+            let _ = tx.send(summarize(&provider, baseline, minutes));
+        });
+        self.last_run = now;
+        self.job = Some((now, rx));
+    }
+
+    fn deliver(&self, minutes: u64, text: String) -> ScribeNote {
+        let Some(path) = &self.log else {
+            return ScribeNote::Summary { minutes, text };
+        };
+        let entry = format_entry(
+            &chrono::Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            minutes,
+            &text,
+        );
+        match append(path, &entry) {
+            Ok(()) => ScribeNote::Status(format!("Session summary appended to {}", path.display())),
+            Err(e) => ScribeNote::Status(format!("Could not write {}: {e}", path.display())),
+        }
+    }
+}
+
+/// Summarize the changes since `baseline`, or only record the current tree if there is none.
+fn summarize(
+    provider: &LumenProvider,
+    baseline: Option<String>,
+    minutes: u64,
+) -> Result<Update, String> {
+    let tree = snapshot::working_tree()?;
+    let baseline = match baseline {
+        Some(baseline) if baseline != tree => baseline,
+        _ => {
+            return Ok(Update {
+                tree,
+                summary: None,
+            })
+        }
+    };
+
+    let mut changes = diff_trees(&baseline, &tree, false)?;
+    if changes.trim().is_empty() {
+        // Only excluded files like lock files changed
+        return Ok(Update {
+            tree,
+            summary: None,
+        });
+    }
+    if estimate_tokens(&changes) > provider.max_tokens() {
+        changes = diff_trees(&baseline, &tree, true)?;
+    }
+
+    let summary = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?
+        .block_on(provider.summarize_session(&changes, minutes))
+        .map_err(|e| e.to_string())?;
+    Ok(Update {
+        tree,
+        summary: Some(summary.trim().to_string()),
+    })
+}
+
+/// `git diff` between two trees, or just its `--stat` when the full diff is too large.
+fn diff_trees(from: &str, to: &str, stat_only: bool) -> Result<String, String> {
+    let mut command = Command::new("git");
+    command.args(["diff", "--no-color"]);
+    if stat_only {
+        command.arg("--stat");
+    }
+    command.args([from, to]).args(GIT_DIFF_EXCLUSIONS);
+    vcs::run_git(&mut command).map_err(|e| e.to_string())
+}
+
+fn format_entry(time: &str, minutes: u64, text: &str) -> String {
+    format!("## {time} (last {minutes} min)\n\n{}\n\n", text.trim())
+}
+
+fn append(path: &Path, entry: &str) -> std::io::Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(entry.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_entry() {
+        assert_eq!(
+            format_entry(
+                "2024-05-01 10:30",
+                15,
+                "- Added retries\n- Fixed the parser\n"
+            ),
+            "## 2024-05-01 10:30 (last 15 min)\n\n- Added retries\n- Fixed the parser\n\n"
+        );
+    }
+}
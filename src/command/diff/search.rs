@@ -1,3 +1,5 @@
+use regex::{Regex, RegexBuilder};
+
 use super::types::{DiffFullscreen, DiffLine};
 
 #[derive(Default, Clone, Copy, PartialEq)]
@@ -5,6 +7,35 @@ pub enum SearchMode {
     #[default]
     Inactive,
     InputForward,
+    InputBackward,
+}
+
+/// How letter case is matched; `tab` in the search prompt cycles through them
+#[derive(Default, Clone, Copy, PartialEq, Debug)]
+pub enum CaseMode {
+    #[default]
+    Ignore,
+    /// Ignore case unless the query has an uppercase letter
+    Smart,
+    Sensitive,
+}
+
+impl CaseMode {
+    pub fn next(self) -> Self {
+        match self {
+            CaseMode::Ignore => CaseMode::Smart,
+            CaseMode::Smart => CaseMode::Sensitive,
+            CaseMode::Sensitive => CaseMode::Ignore,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            CaseMode::Ignore => "ignore case",
+            CaseMode::Smart => "smart case",
+            CaseMode::Sensitive => "match case",
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -27,27 +58,102 @@ pub struct SearchState {
     pub query: String,
     pub matches: Vec<SearchMatch>,
     pub current_match: Option<usize>,
+    /// The query is a regular expression rather than plain text; `ctrl+r` in the prompt
+    /// toggles it. Like the case mode, it's kept for later searches.
+    pub regex: bool,
+    pub case: CaseMode,
+    /// The last search went up, so `n` moves up and `N` down
+    pub backward: bool,
+    /// Why the query isn't a valid regular expression
+    pub error: Option<String>,
 }
 
 impl SearchState {
     pub fn start_forward(&mut self) {
-        self.mode = SearchMode::InputForward;
+        self.start(SearchMode::InputForward);
+    }
+
+    pub fn start_backward(&mut self) {
+        self.start(SearchMode::InputBackward);
+    }
+
+    fn start(&mut self, mode: SearchMode) {
+        self.mode = mode;
+        self.backward = mode == SearchMode::InputBackward;
         self.query.clear();
         self.matches.clear();
         self.current_match = None;
+        self.error = None;
+    }
+
+    pub fn toggle_regex(&mut self) {
+        self.regex = !self.regex;
+    }
+
+    pub fn cycle_case(&mut self) {
+        self.case = self.case.next();
+    }
+
+    /// `/` or `?`, for the direction of the search
+    pub fn prompt(&self) -> &'static str {
+        if self.backward {
+            "?"
+        } else {
+            "/"
+        }
+    }
+
+    /// The options that differ from a plain, case-insensitive search, for the footer
+    pub fn flags(&self) -> String {
+        let mut flags = Vec::new();
+        if self.regex {
+            flags.push("regex");
+        }
+        if self.case != CaseMode::Ignore {
+            flags.push(self.case.label());
+        }
+        flags.join(", ")
+    }
+
+    fn ignores_case(&self) -> bool {
+        match self.case {
+            CaseMode::Ignore => true,
+            CaseMode::Smart => !self.query.chars().any(char::is_uppercase),
+            CaseMode::Sensitive => false,
+        }
+    }
+
+    /// The query as a regex: as written in regex mode, else escaped to match literally.
+    fn pattern(&self) -> Result<Regex, String> {
+        let source = if self.regex {
+            self.query.clone()
+        } else {
+            regex::escape(&self.query)
+        };
+        RegexBuilder::new(&source)
+            .case_insensitive(self.ignores_case())
+            .build()
+            .map_err(|e| match e {
+                regex::Error::Syntax(message) => message
+                    .lines()
+                    .last()
+                    .unwrap_or_default()
+                    .trim_start_matches("error: ")
+                    .to_string(),
+                e => e.to_string(),
+            })
     }
 
     pub fn cancel(&mut self) {
         self.mode = SearchMode::Inactive;
-        self.query.clear();
-        self.matches.clear();
-        self.current_match = None;
+        self.clear();
     }
 
     pub fn clear(&mut self) {
         self.query.clear();
         self.matches.clear();
         self.current_match = None;
+        self.error = None;
     }
 
     pub fn confirm(&mut self) {
@@ -85,43 +191,38 @@ impl SearchState {
 
         self.matches.clear();
 
-        let query_lower = self.query.to_lowercase();
-        let query_len = self.query.len();
+        let pattern = match self.pattern() {
+            Ok(pattern) => {
+                self.error = None;
+                pattern
+            }
+            Err(e) => {
+                self.error = Some(e);
+                self.current_match = None;
+                return;
+            }
+        };
 
         for (i, line) in lines.iter().enumerate() {
-            // Find all occurrences in old panel
-            if !matches!(fullscreen, DiffFullscreen::NewOnly) {
-                if let Some((_, text)) = &line.old_line {
-                    let text_lower = text.to_lowercase();
-                    let mut start = 0;
-                    while let Some(pos) = text_lower[start..].find(&query_lower) {
-                        let abs_pos = start + pos;
-                        self.matches.push(SearchMatch {
-                            line_index: i,
-                            start_col: abs_pos,
-                            end_col: abs_pos + query_len,
-                            panel: MatchPanel::Old,
-                        });
-                        start = abs_pos + 1;
-                    }
+            let panels = [
+                (MatchPanel::Old, &line.old_line, DiffFullscreen::NewOnly),
+                (MatchPanel::New, &line.new_line, DiffFullscreen::OldOnly),
+            ];
+            for (panel, text, hidden) in panels {
+                let Some((_, text)) = text else {
+                    continue;
+                };
+                if fullscreen == hidden {
+                    continue;
                 }
-            }
-
-            // Find all occurrences in new panel
-            if !matches!(fullscreen, DiffFullscreen::OldOnly) {
-                if let Some((_, text)) = &line.new_line {
-                    let text_lower = text.to_lowercase();
-                    let mut start = 0;
-                    while let Some(pos) = text_lower[start..].find(&query_lower) {
-                        let abs_pos = start + pos;
-                        self.matches.push(SearchMatch {
-                            line_index: i,
-                            start_col: abs_pos,
-                            end_col: abs_pos + query_len,
-                            panel: MatchPanel::New,
-                        });
-                        start = abs_pos + 1;
-                    }
+                // A pattern like `a*` also matches nothing, which there's nothing to show of
+                for found in pattern.find_iter(text).filter(|m| !m.is_empty()) {
+                    self.matches.push(SearchMatch {
+                        line_index: i,
+                        start_col: found.start(),
+                        end_col: found.end(),
+                        panel,
+                    });
                 }
             }
         }
@@ -179,16 +280,33 @@ impl SearchState {
         Some(self.matches[prev].line_index)
     }
 
+    /// `n` continues in the search's direction, `N` (`reverse`) goes the other way.
+    pub fn repeat(&mut self, reverse: bool) -> Option<usize> {
+        if self.backward != reverse {
+            self.find_prev()
+        } else {
+            self.find_next()
+        }
+    }
+
+    /// The first match from the top of the view down, or for a backward search the first
+    /// one above it, wrapping around.
     pub fn jump_to_first_match(&mut self, current_scroll: usize) -> Option<usize> {
         if self.matches.is_empty() {
             return None;
         }
 
-        let idx = self
-            .matches
-            .iter()
-            .position(|m| m.line_index >= current_scroll)
-            .unwrap_or(0);
+        let idx = if self.backward {
+            self.matches
+                .iter()
+                .rposition(|m| m.line_index < current_scroll)
+                .unwrap_or(self.matches.len() - 1)
+        } else {
+            self.matches
+                .iter()
+                .position(|m| m.line_index >= current_scroll)
+                .unwrap_or(0)
+        };
         self.current_match = Some(idx);
         Some(self.matches[idx].line_index)
     }
@@ -217,3 +335,77 @@ impl SearchState {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::super::types::ChangeType;
+    use super::*;
+
+    fn lines() -> Vec<DiffLine> {
+        [
+            (Some("let Foo = foo();"), Some("let Foo = bar();")),
+            (None, Some("fn foo_bar() {}")),
+            (Some("// FOO"), None),
+        ]
+        .into_iter()
+        .enumerate()
+        .map(|(i, (old, new))| DiffLine {
+            old_line: old.map(|text| (i + 1, text.to_string())),
+            new_line: new.map(|text| (i + 1, text.to_string())),
+            change_type: ChangeType::Modified,
+        })
+        .collect()
+    }
+
+    fn search(query: &str, regex: bool, case: CaseMode) -> SearchState {
+        let mut state = SearchState {
+            query: query.to_string(),
+            regex,
+            case,
+            ..SearchState::default()
+        };
+        state.update_matches(&lines(), DiffFullscreen::None);
+        state
+    }
+
+    fn spans(state: &SearchState) -> Vec<(usize, usize, usize)> {
+        state
+            .matches
+            .iter()
+            .map(|m| (m.line_index, m.start_col, m.end_col))
+            .collect()
+    }
+
+    #[test]
+    fn test_case_modes() {
+        assert_eq!(search("foo", false, CaseMode::Ignore).match_count(), 5);
+        assert_eq!(search("foo", false, CaseMode::Smart).match_count(), 5);
+        assert_eq!(search("Foo", false, CaseMode::Smart).match_count(), 2);
+        assert_eq!(search("foo", false, CaseMode::Sensitive).match_count(), 2);
+        // Plain text is matched literally
+        assert_eq!(search("foo()", false, CaseMode::Ignore).match_count(), 1);
+    }
+
+    #[test]
+    fn test_regex_search() {
+        let state = search(r"\bfoo\w*", true, CaseMode::Sensitive);
+        assert_eq!(spans(&state), vec![(0, 10, 13), (1, 3, 10)]);
+
+        // Empty matches are skipped rather than highlighted
+        assert_eq!(search("x*", true, CaseMode::Ignore).match_count(), 0);
+
+        let invalid = search("foo(", true, CaseMode::Ignore);
+        assert_eq!(invalid.match_count(), 0);
+        assert!(invalid.error.is_some());
+    }
+
+    #[test]
+    fn test_backward_search() {
+        let mut state = search("foo", false, CaseMode::Sensitive);
+        state.backward = true;
+        assert_eq!(state.jump_to_first_match(1), Some(0));
+        assert_eq!(state.repeat(false), Some(1));
+        assert_eq!(state.repeat(false), Some(0));
+        assert_eq!(state.repeat(true), Some(1));
+    }
+}
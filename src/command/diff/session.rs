@@ -0,0 +1,328 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
+use serde::{Deserialize, Serialize};
+
+/// Where the viewer was when an event arrived, used to spot a replay drifting from the
+/// recording (e.g. because the diff changed since)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Position {
+    pub file: Option<String>,
+    pub scroll: u16,
+}
+
+/// One line of a recording
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    at_ms: u64,
+    #[serde(flatten)]
+    position: Position,
+    input: Input,
+}
+
+/// The subset of terminal events the viewer reacts to, in a stable serialized form
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Input {
+    Key {
+        key: String,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        modifiers: u8,
+    },
+    Mouse {
+        action: MouseAction,
+        column: u16,
+        row: u16,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MouseAction {
+    Click,
+    ScrollUp,
+    ScrollDown,
+}
+
+fn is_zero(value: &u8) -> bool {
+    *value == 0
+}
+
+impl Input {
+    fn from_event(event: &Event) -> Option<Self> {
+        match event {
+            Event::Key(key) if key.kind == KeyEventKind::Press => Some(Input::Key {
+                key: key_name(key.code)?,
+                modifiers: key.modifiers.bits(),
+            }),
+            Event::Mouse(mouse) => {
+                let action = match mouse.kind {
+                    MouseEventKind::Down(MouseButton::Left) => MouseAction::Click,
+                    MouseEventKind::ScrollUp => MouseAction::ScrollUp,
+                    MouseEventKind::ScrollDown => MouseAction::ScrollDown,
+                    _ => return None,
+                };
+                Some(Input::Mouse {
+                    action,
+                    column: mouse.column,
+                    row: mouse.row,
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn to_event(&self) -> Option<Event> {
+        match self {
+            Input::Key { key, modifiers } => Some(Event::Key(KeyEvent::new(
+                parse_key_name(key)?,
+                KeyModifiers::from_bits_truncate(*modifiers),
+            ))),
+            Input::Mouse {
+                action,
+                column,
+                row,
+            } => Some(Event::Mouse(MouseEvent {
+                kind: match action {
+                    MouseAction::Click => MouseEventKind::Down(MouseButton::Left),
+                    MouseAction::ScrollUp => MouseEventKind::ScrollUp,
+                    MouseAction::ScrollDown => MouseEventKind::ScrollDown,
+                },
+                column: *column,
+                row: *row,
+                modifiers: KeyModifiers::NONE,
+            })),
+        }
+    }
+}
+
+const NAMED_KEYS: &[(KeyCode, &str)] = &[
+    (KeyCode::Esc, "esc"),
+    (KeyCode::Enter, "enter"),
+    (KeyCode::Backspace, "backspace"),
+    (KeyCode::Tab, "tab"),
+    (KeyCode::BackTab, "backtab"),
+    (KeyCode::Up, "up"),
+    (KeyCode::Down, "down"),
+    (KeyCode::Left, "left"),
+    (KeyCode::Right, "right"),
+    (KeyCode::Home, "home"),
+    (KeyCode::End, "end"),
+    (KeyCode::PageUp, "pageup"),
+    (KeyCode::PageDown, "pagedown"),
+    (KeyCode::Delete, "delete"),
+];
+
+fn key_name(code: KeyCode) -> Option<String> {
+    match code {
+        KeyCode::Char(c) => Some(c.to_string()),
+        KeyCode::F(n) => Some(format!("f{n}")),
+        _ => NAMED_KEYS
+            .iter()
+            .find(|(named, _)| *named == code)
+            .map(|(_, name)| name.to_string()),
+    }
+}
+
+fn parse_key_name(name: &str) -> Option<KeyCode> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(KeyCode::Char(c));
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        return Some(KeyCode::F(n));
+    }
+    NAMED_KEYS
+        .iter()
+        .find(|(_, named)| *named == name)
+        .map(|(code, _)| *code)
+}
+
+/// Writes every event the viewer handles to a file as JSON lines. Each line is flushed
+/// right away so a recording of a crash is still complete.
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(Recorder {
+            writer: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+        })
+    }
+
+    fn record(&mut self, event: &Event, position: Position) -> io::Result<()> {
+        let Some(input) = Input::from_event(event) else {
+            return Ok(());
+        };
+        let entry = Entry {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            position,
+            input,
+        };
+        serde_json::to_writer(&mut self.writer, &entry)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// Plays a recording back with its original timing
+pub struct Replayer {
+    entries: std::vec::IntoIter<Entry>,
+    next: Option<Entry>,
+    started: Instant,
+}
+
+impl Replayer {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let entries = parse_recording(BufReader::new(File::open(path)?))?;
+        let mut entries = entries.into_iter();
+        Ok(Replayer {
+            next: entries.next(),
+            entries,
+            started: Instant::now(),
+        })
+    }
+}
+
+fn parse_recording(reader: impl BufRead) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid recording at line {}: {e}", i + 1),
+            )
+        })?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Where the viewer's events come from: the terminal (optionally recorded) or a recording
+pub enum EventSource {
+    Terminal(Option<Recorder>),
+    Replay(Replayer),
+}
+
+impl EventSource {
+    /// Wait up to `timeout` for the next event. `position` is the viewer's current state,
+    /// logged with recorded events and compared against the recording during a replay.
+    ///
+    /// The second value is a message to show when a replay ends.
+    pub fn next(
+        &mut self,
+        timeout: Duration,
+        position: Position,
+    ) -> io::Result<(Option<Event>, Option<String>)> {
+        match self {
+            EventSource::Terminal(recorder) => {
+                if !event::poll(timeout)? {
+                    return Ok((None, None));
+                }
+                let event = event::read()?;
+                if let Some(recorder) = recorder {
+                    recorder.record(&event, position)?;
+                }
+                Ok((Some(event), None))
+            }
+            EventSource::Replay(replayer) => {
+                let Some(entry) = replayer.next.take() else {
+                    *self = EventSource::Terminal(None);
+                    return Ok((None, Some("Replay finished".to_string())));
+                };
+
+                let due = replayer.started + Duration::from_millis(entry.at_ms);
+                let wait = due.saturating_duration_since(Instant::now()).min(timeout);
+                // Any key press hands control back to the user
+                if event::poll(wait)? {
+                    if let Event::Key(key) = event::read()? {
+                        if key.kind == KeyEventKind::Press {
+                            *self = EventSource::Terminal(None);
+                            return Ok((None, Some("Replay stopped".to_string())));
+                        }
+                    }
+                }
+                if Instant::now() < due {
+                    replayer.next = Some(entry);
+                    return Ok((None, None));
+                }
+
+                if entry.position != position {
+                    let at = Duration::from_millis(entry.at_ms).as_secs_f32();
+                    *self = EventSource::Terminal(None);
+                    return Ok((
+                        None,
+                        Some(format!(
+                            "Replay stopped at {at:.1}s: the view differs from the recording"
+                        )),
+                    ));
+                }
+                replayer.next = replayer.entries.next();
+                Ok((entry.input.to_event(), None))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_names_round_trip() {
+        for code in [
+            KeyCode::Char('j'),
+            KeyCode::Char(' '),
+            KeyCode::F(5),
+            KeyCode::PageDown,
+            KeyCode::Esc,
+        ] {
+            assert_eq!(parse_key_name(&key_name(code).unwrap()), Some(code));
+        }
+        assert_eq!(key_name(KeyCode::CapsLock), None);
+    }
+
+    #[test]
+    fn test_recording_lines_parse_back_into_events() {
+        let recording = concat!(
+            r#"{"at_ms":120,"file":"src/main.rs","scroll":0,"input":{"type":"key","key":"j"}}"#,
+            "\n\n",
+            r#"{"at_ms":400,"file":null,"scroll":3,"input":{"type":"mouse","action":"scroll_down","column":50,"row":10}}"#,
+            "\n",
+        );
+        let entries = parse_recording(recording.as_bytes()).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].position.file.as_deref(), Some("src/main.rs"));
+        assert_eq!(
+            entries[0].input.to_event(),
+            Some(Event::Key(KeyEvent::new(
+                KeyCode::Char('j'),
+                KeyModifiers::NONE
+            )))
+        );
+        assert!(matches!(
+            entries[1].input.to_event(),
+            Some(Event::Mouse(MouseEvent {
+                kind: MouseEventKind::ScrollDown,
+                column: 50,
+                row: 10,
+                ..
+            }))
+        ));
+
+        assert!(parse_recording("not json".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,434 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::process::Command;
+
+use lumen_core::side_by_side::{find_hunk_starts, hunk_at};
+use similar::{ChangeTag, TextDiff};
+
+use crate::vcs;
+
+use super::types::{ChangeType, DiffLine, FileDiff, FileStatus};
+
+/// Number of unchanged lines kept around each change, same as `git diff`
+const PATCH_CONTEXT: usize = 3;
+
+/// Old and new line numbers of the changed lines within the given side-by-side rows.
+pub fn selected_line_numbers(
+    lines: &[DiffLine],
+    rows: RangeInclusive<usize>,
+) -> (HashSet<usize>, HashSet<usize>) {
+    let mut old_selected = HashSet::new();
+    let mut new_selected = HashSet::new();
+    for line in lines.iter().skip(*rows.start()).take(rows.count()) {
+        if matches!(line.change_type, ChangeType::Equal) {
+            continue;
+        }
+        if let Some((num, _)) = line.old_line {
+            old_selected.insert(num);
+        }
+        if let Some((num, _)) = line.new_line {
+            new_selected.insert(num);
+        }
+    }
+    (old_selected, new_selected)
+}
+
+/// Build a unified patch that applies only the selected lines of `old` -> `new`.
+///
+/// `old_selected` and `new_selected` hold 1-based line numbers of deletions and insertions to
+/// keep. Unselected deletions become context and unselected insertions are dropped, the same
+/// way `git add -p`'s edit mode expects a hand-edited hunk. Returns `None` if nothing is selected.
+pub fn build_partial_patch(
+    filename: &str,
+    old: &str,
+    new: &str,
+    old_selected: &HashSet<usize>,
+    new_selected: &HashSet<usize>,
+) -> Option<String> {
+    let diff = TextDiff::from_lines(old, new);
+
+    let mut lines: Vec<(char, &str)> = Vec::new();
+    for change in diff.iter_all_changes() {
+        match change.tag() {
+            ChangeTag::Equal => lines.push((' ', change.value())),
+            ChangeTag::Delete => {
+                let selected = change
+                    .old_index()
+                    .is_some_and(|i| old_selected.contains(&(i + 1)));
+                lines.push((if selected { '-' } else { ' ' }, change.value()));
+            }
+            ChangeTag::Insert => {
+                if change
+                    .new_index()
+                    .is_some_and(|i| new_selected.contains(&(i + 1)))
+                {
+                    lines.push(('+', change.value()));
+                }
+            }
+        }
+    }
+
+    let changes: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, (tag, _))| *tag != ' ')
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return None;
+    }
+
+    // Line number each patch line starts at on the old and new side
+    let mut old_pos = Vec::with_capacity(lines.len());
+    let mut new_pos = Vec::with_capacity(lines.len());
+    let (mut old_no, mut new_no) = (1, 1);
+    for (tag, _) in &lines {
+        old_pos.push(old_no);
+        new_pos.push(new_no);
+        if *tag != '+' {
+            old_no += 1;
+        }
+        if *tag != '-' {
+            new_no += 1;
+        }
+    }
+
+    // Group changes into hunks, merging ones whose context would overlap
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &idx in &changes {
+        let start = idx.saturating_sub(PATCH_CONTEXT);
+        let end = (idx + PATCH_CONTEXT + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+
+    let mut patch =
+        format!("diff --git a/{filename} b/{filename}\n--- a/{filename}\n+++ b/{filename}\n");
+    for (start, end) in hunks {
+        let hunk = &lines[start..end];
+        let old_count = hunk.iter().filter(|(tag, _)| *tag != '+').count();
+        let new_count = hunk.iter().filter(|(tag, _)| *tag != '-').count();
+        // An empty side is addressed by the line before it
+        let old_start = if old_count == 0 {
+            old_pos[start] - 1
+        } else {
+            old_pos[start]
+        };
+        let new_start = if new_count == 0 {
+            new_pos[start] - 1
+        } else {
+            new_pos[start]
+        };
+
+        patch.push_str(&format!(
+            "@@ -{old_start},{old_count} +{new_start},{new_count} @@\n"
+        ));
+        for (tag, text) in hunk {
+            patch.push(*tag);
+            patch.push_str(text);
+            if !text.ends_with('\n') {
+                patch.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+
+    Some(patch)
+}
+
+/// Like [`build_partial_patch`], but against `index`, the file as staged, which a patch for
+/// `git apply --cached` has to match once some of its lines are staged. The selection is still
+/// of `old` -> `new`; deletions already staged are left out, as are insertions.
+pub fn build_index_patch(
+    filename: &str,
+    old: &str,
+    index: &str,
+    new: &str,
+    old_selected: &HashSet<usize>,
+    new_selected: &HashSet<usize>,
+) -> Option<String> {
+    // The selected deletions that are still in the index, by their line there
+    let index_selected: HashSet<usize> = TextDiff::from_lines(old, index)
+        .iter_all_changes()
+        .filter(|change| change.tag() == ChangeTag::Equal)
+        .filter(|change| {
+            change
+                .old_index()
+                .is_some_and(|i| old_selected.contains(&(i + 1)))
+        })
+        .filter_map(|change| change.new_index().map(|i| i + 1))
+        .collect();
+    build_partial_patch(filename, index, new, &index_selected, new_selected)
+}
+
+/// The file as it's staged, from `git show :<path>`.
+pub fn index_content(filename: &str) -> Result<String, String> {
+    vcs::git(&["show", &format!(":{filename}")]).map_err(|e| e.to_string())
+}
+
+/// How much of a file's changes against HEAD are staged
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum IndexState {
+    Staged,
+    PartlyStaged,
+}
+
+/// Files with staged changes: fully staged unless the working tree differs from the index too.
+pub fn index_states() -> HashMap<String, IndexState> {
+    let unstaged = changed_paths(&[]);
+    changed_paths(&["--cached"])
+        .into_iter()
+        .map(|path| {
+            let state = if unstaged.contains(&path) {
+                IndexState::PartlyStaged
+            } else {
+                IndexState::Staged
+            };
+            (path, state)
+        })
+        .collect()
+}
+
+fn changed_paths(extra_args: &[&str]) -> HashSet<String> {
+    vcs::run_git(
+        Command::new("git")
+            .args(["diff", "--name-only"])
+            .args(extra_args),
+    )
+    .map(|stdout| {
+        stdout
+            .lines()
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Patch with just the changes in `rows` of the file's side-by-side diff, e.g. one hunk.
+pub fn rows_patch(
+    diff: &FileDiff,
+    lines: &[DiffLine],
+    rows: RangeInclusive<usize>,
+) -> Option<String> {
+    let (old_selected, new_selected) = selected_line_numbers(lines, rows);
+    build_partial_patch(
+        &diff.filename,
+        &diff.old_content,
+        &diff.new_content,
+        &old_selected,
+        &new_selected,
+    )
+}
+
+/// Rows of the hunks whose changes are already in the index.
+pub fn staged_hunks(diff: &FileDiff, lines: &[DiffLine]) -> Vec<RangeInclusive<usize>> {
+    find_hunk_starts(lines)
+        .into_iter()
+        .filter_map(|start| hunk_at(lines, start))
+        .filter(|rows| {
+            rows_patch(diff, lines, rows.clone()).is_some_and(|patch| is_in_index(&patch))
+        })
+        .collect()
+}
+
+/// Whether the index already holds what `patch` changes, i.e. it can be taken back out.
+pub fn is_in_index(patch: &str) -> bool {
+    git_apply(&["--cached", "--check", "--reverse"], patch).is_ok()
+}
+
+/// Apply a patch to the index only, leaving the working tree untouched.
+pub fn apply_to_index(patch: &str) -> Result<(), String> {
+    git_apply(&["--cached"], patch)
+}
+
+/// Take a previously staged patch back out of the index, leaving the working tree untouched.
+pub fn remove_from_index(patch: &str) -> Result<(), String> {
+    git_apply(&["--cached", "--reverse"], patch)
+}
+
+/// Apply a patch to the working tree only, leaving the index untouched.
+pub fn apply_to_worktree(patch: &str) -> Result<(), String> {
+    git_apply(&[], patch)
+}
+
+/// Undo a patch in the working tree, leaving the index untouched.
+pub fn discard_from_worktree(patch: &str) -> Result<(), String> {
+    git_apply(&["--reverse"], patch)
+}
+
+/// Put a file back the way it is in HEAD, in both the index and the working tree. A file
+/// HEAD doesn't have is removed, and a renamed one is moved back.
+pub fn restore_file(filename: &str, status: &FileStatus) -> Result<(), String> {
+    match status {
+        FileStatus::Added | FileStatus::Copied { .. } => remove_new_file(filename),
+        FileStatus::Renamed { from, .. } => {
+            remove_new_file(filename)?;
+            git_restore(from)
+        }
+        FileStatus::Modified | FileStatus::Deleted => git_restore(filename),
+    }
+}
+
+fn git_restore(filename: &str) -> Result<(), String> {
+    git(&[
+        "restore",
+        "--source=HEAD",
+        "--staged",
+        "--worktree",
+        "--",
+        filename,
+    ])
+}
+
+fn remove_new_file(filename: &str) -> Result<(), String> {
+    git(&[
+        "rm",
+        "--cached",
+        "--quiet",
+        "--ignore-unmatch",
+        "--",
+        filename,
+    ])?;
+    std::fs::remove_file(filename).map_err(|e| format!("Failed to remove {filename}: {e}"))
+}
+
+/// Run git for its effect, with its message as the error.
+fn git(args: &[&str]) -> Result<(), String> {
+    vcs::git(args).map(|_| ()).map_err(|e| e.to_string())
+}
+
+fn git_apply(extra_args: &[&str], patch: &str) -> Result<(), String> {
+    let args = [&["apply", "--whitespace=nowarn"], extra_args, &["-"]].concat();
+    vcs::git_with_input(&args, patch)
+        .map(|_| ())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lumen_core::side_by_side::compute_side_by_side;
+    use std::io::Write;
+    use std::process::Stdio;
+
+    fn set(lines: &[usize]) -> HashSet<usize> {
+        lines.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_nothing_selected() {
+        let patch = build_partial_patch("f", "a\nb\n", "a\nc\n", &set(&[]), &set(&[]));
+        assert!(patch.is_none());
+    }
+
+    #[test]
+    fn test_select_insertion_only() {
+        let old = "a\nb\nc\n";
+        let new = "a\nx\ny\nb\nc\n";
+        let patch = build_partial_patch("f", old, new, &set(&[]), &set(&[2])).unwrap();
+        assert_eq!(
+            patch,
+            "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,3 +1,4 @@\n a\n+x\n b\n c\n"
+        );
+    }
+
+    #[test]
+    fn test_unselected_deletion_becomes_context() {
+        let old = "a\nb\nc\n";
+        let new = "a\n";
+        let patch = build_partial_patch("f", old, new, &set(&[3]), &set(&[])).unwrap();
+        assert_eq!(
+            patch,
+            "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,3 +1,2 @@\n a\n b\n-c\n"
+        );
+    }
+
+    #[test]
+    fn test_distant_changes_make_separate_hunks() {
+        let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n";
+        let new = "1x\n2\n3\n4\n5\n6\n7\n8\n9\n10x\n";
+        let patch = build_partial_patch("f", old, new, &set(&[1, 10]), &set(&[1, 10])).unwrap();
+        assert_eq!(patch.matches("@@ -").count(), 2);
+        assert!(patch.contains("@@ -1,4 +1,4 @@\n-1\n+1x\n"));
+        assert!(patch.contains("@@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+10x\n"));
+    }
+
+    #[test]
+    fn test_rows_patch_takes_one_hunk() {
+        let diff = FileDiff {
+            filename: "f".to_string(),
+            old_content: "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n".to_string(),
+            new_content: "1x\n2\n3\n4\n5\n6\n7\n8\n9\n10x\n".to_string(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        };
+        let lines = compute_side_by_side(&diff.old_content, &diff.new_content, 4, false);
+        let rows = hunk_at(&lines, lines.len() - 1).unwrap();
+        let patch = rows_patch(&diff, &lines, rows).unwrap();
+        assert_eq!(patch.matches("@@ -").count(), 1);
+        assert!(patch.contains("-10\n+10x\n"));
+    }
+
+    fn git(dir: &std::path::Path, args: &[&str], stdin: Option<&str>) -> Result<String, String> {
+        let mut child = Command::new("git")
+            .current_dir(dir)
+            .args(["-c", "user.name=Test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(stdin.unwrap_or_default().as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        if output.status.success() {
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+
+    #[test]
+    fn test_stage_lines_in_two_steps() {
+        let dir = std::env::temp_dir().join(format!("lumen-stage-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let old = "a\nb\nc\n";
+        let new = "a\nx\nb\ny\n";
+        std::fs::write(dir.join("f"), old).unwrap();
+        git(&dir, &["init", "--quiet"], None).unwrap();
+        git(&dir, &["add", "f"], None).unwrap();
+        git(&dir, &["commit", "--quiet", "-m", "init"], None).unwrap();
+        std::fs::write(dir.join("f"), new).unwrap();
+
+        // `x` first, then replacing `c` with `y`, both picked from the diff against HEAD
+        let first = build_index_patch("f", old, old, new, &set(&[]), &set(&[2])).unwrap();
+        git(&dir, &["apply", "--cached", "-"], Some(&first)).unwrap();
+        let index = git(&dir, &["show", ":f"], None).unwrap();
+        assert_eq!(index, "a\nx\nb\nc\n");
+
+        // Built against HEAD, the second patch no longer matches the index
+        let stale = build_partial_patch("f", old, new, &set(&[3]), &set(&[4])).unwrap();
+        assert!(git(&dir, &["apply", "--cached", "--check", "-"], Some(&stale)).is_err());
+
+        let second = build_index_patch("f", old, &index, new, &set(&[3]), &set(&[4])).unwrap();
+        let applied = git(&dir, &["apply", "--cached", "-"], Some(&second));
+        let index = git(&dir, &["show", ":f"], None);
+        std::fs::remove_dir_all(&dir).unwrap();
+        applied.unwrap();
+        assert_eq!(index.unwrap(), new);
+    }
+
+    #[test]
+    fn test_missing_trailing_newline() {
+        let patch = build_partial_patch("f", "a", "a\nb", &set(&[1]), &set(&[1, 2])).unwrap();
+        assert!(patch.contains("-a\n\\ No newline at end of file\n+a\n+b\n\\ No newline"));
+    }
+}
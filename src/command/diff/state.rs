@@ -1,11 +1,37 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::ops::{Range, RangeInclusive};
 
-use crate::command::diff::diff_algo::{compute_side_by_side, find_hunk_starts};
+use lumen_core::side_by_side::{compute_side_by_side, find_hunk_starts};
+
+use crate::command::diff::blame::{blame_file, BlameCommit, BlameLine, FileBlame};
+use crate::command::diff::commit_suggestion::CommitSuggestion;
+use crate::command::diff::file_summaries::FileSummaries;
+use crate::command::diff::filter::FileFilter;
+use crate::command::diff::git::DiffRefs;
+use crate::command::diff::highlight::{self, Highlighted};
+use crate::command::diff::history::{FileHistory, HistoryCommit};
+use crate::command::diff::moves::{find_moves, MovedBlock};
+use crate::command::diff::overview::line_counts;
+use crate::command::diff::prefetch::{ViewCache, ViewKey};
+use crate::command::diff::risk::RiskScores;
 use crate::command::diff::search::SearchState;
+use crate::command::diff::stage::{index_states, staged_hunks, IndexState};
+use crate::command::diff::theme;
 use crate::command::diff::types::{
-    build_file_tree, DiffFullscreen, DiffViewSettings, FileDiff, FocusedPanel, SidebarItem,
+    build_file_tree, DiffFullscreen, DiffLine, DiffViewSettings, FileDiff, FileStatus,
+    FocusedPanel, SidebarItem,
 };
 
+/// Where [`AppState::jump_to_hunk`] went
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HunkJump {
+    SameFile,
+    /// The first hunk of the next file, or the last of the previous one
+    OtherFile,
+    /// Nowhere, this was the last hunk that way
+    NoneLeft,
+}
+
 #[derive(Default, Clone, Copy, PartialEq)]
 pub enum PendingKey {
     #[default]
@@ -13,9 +39,98 @@ pub enum PendingKey {
     G,
 }
 
+/// The current file's side-by-side rows and their syntax highlighting, kept between frames
+/// so drawing one only has to put together the rows on screen.
+#[derive(Default)]
+pub struct FileView {
+    /// File, tab width, whitespace and moved code settings the rows were computed for
+    key: Option<(usize, usize, bool, bool)>,
+    /// What the rows were computed from, to keep them in the cache by; `None` when held back
+    id: Option<ViewKey>,
+    pub rows: Vec<DiffLine>,
+    /// Definitions the rows show as deleted in one place and added in another, when
+    /// moved code is detected
+    pub moves: Vec<MovedBlock>,
+    /// Highlighted old and new text of each row, filled in as the rows come on screen
+    old: Vec<Option<Highlighted>>,
+    new: Vec<Option<Highlighted>>,
+    /// Lines of a large file that hasn't been asked for yet, left undiffed until it is
+    pub held_back: Option<usize>,
+    /// Large files are shown without syntax highlighting
+    pub plain: bool,
+}
+
+impl FileView {
+    /// Work out the rows of `diff` for the settings in `id`, highlighting none yet.
+    pub fn compute(diff: &FileDiff, id: &ViewKey, plain: bool) -> Self {
+        let rows = compute_side_by_side(
+            &diff.old_content,
+            &diff.new_content,
+            id.tab_width,
+            id.ignore_whitespace,
+        );
+        let moves = if id.detect_moves {
+            find_moves(&diff.filename, &diff.old_content, &diff.new_content, &rows)
+        } else {
+            Vec::new()
+        };
+        FileView {
+            key: None,
+            id: Some(id.clone()),
+            old: vec![None; rows.len()],
+            new: vec![None; rows.len()],
+            rows,
+            moves,
+            held_back: None,
+            plain,
+        }
+    }
+
+    /// Highlight the text of `rows` that hasn't been already.
+    pub fn highlight(&mut self, rows: Range<usize>, filename: &str) {
+        let end = rows.end.min(self.rows.len());
+        for row in rows.start.min(end)..end {
+            let line = &self.rows[row];
+            for (text, highlighted) in [
+                (&line.old_line, &mut self.old[row]),
+                (&line.new_line, &mut self.new[row]),
+            ] {
+                if highlighted.is_none() {
+                    *highlighted = text.as_ref().map(|(_, text)| {
+                        if self.plain {
+                            vec![(text.clone(), None)]
+                        } else {
+                            highlight::highlight_code(text, filename)
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    /// The highlighted text on the old or new side of `row`, if it has been on screen.
+    pub fn highlighted(&self, row: usize, old: bool) -> Option<&Highlighted> {
+        let side = if old { &self.old } else { &self.new };
+        side.get(row)?.as_ref()
+    }
+
+    /// Whether line `number` on the old or new side is part of a moved definition.
+    pub fn is_moved(&self, number: usize, old: bool) -> bool {
+        self.moves
+            .iter()
+            .any(|m| if old { &m.old } else { &m.new }.contains(&number))
+    }
+}
+
 pub struct AppState {
     pub file_diffs: Vec<FileDiff>,
     pub sidebar_items: Vec<SidebarItem>,
+    /// Limits the sidebar, and the files moved through with ctrl+j/k, to those it matches
+    pub sidebar_filter: Option<FileFilter>,
+    /// Sidebar directories whose files are hidden, and skipped by ctrl+j/k, by path
+    pub collapsed_dirs: HashSet<String>,
+    /// Lines added and removed per file index, for the sidebar's directory totals
+    pub line_counts: HashMap<usize, (usize, usize)>,
     pub current_file: usize,
     pub sidebar_selected: usize,
     pub sidebar_scroll: usize,
@@ -30,11 +145,48 @@ pub struct AppState {
     pub search_state: SearchState,
     pub pending_key: PendingKey,
     pub needs_reload: bool,
+    /// Last (scroll, h_scroll) per filename, so returning to a file resumes where it was left
+    pub reading_positions: HashMap<String, (u16, u16)>,
+    /// Visual line selection as (anchor, cursor) rows of the side-by-side diff
+    pub line_selection: Option<(usize, usize)>,
+    /// One-off message shown in the footer until the next key press
+    pub status_message: Option<String>,
+    /// Review risk markers for the sidebar, with `--risk`
+    pub risk: Option<RiskScores>,
+    /// One-line AI summaries of the files for the sidebar, with `--file-summaries`
+    pub file_summaries: Option<FileSummaries>,
+    /// AI commit subject for the staged hunks, shown in the footer while it's turned on
+    pub commit_suggestion: Option<CommitSuggestion>,
+    /// Files with staged changes, by index; only kept up to date where staging is possible
+    pub index_states: HashMap<usize, IndexState>,
+    /// Rows of the current file's staged hunks, with the file and whitespace setting they
+    /// were worked out for
+    pub staged_hunks: Option<(usize, bool, Vec<RangeInclusive<usize>>)>,
+    /// Whether the blame gutter is on; it follows the current file
+    pub show_blame: bool,
+    /// Blame of the file the gutter was last loaded for
+    pub blame: Option<FileBlame>,
+    /// File history mode, showing one past commit's change to the current file at a time
+    pub file_history: Option<FileHistory>,
+    /// Rows and highlighting of the current file
+    pub view: FileView,
+    /// Large files that were asked for with enter, by name
+    pub loaded_large_files: HashSet<String>,
+    /// Files whose diffs are still being read in the background, by index
+    pub loading: HashSet<usize>,
+    /// Views of files shown before or next to the current one
+    views: ViewCache,
 }
 
 impl AppState {
-    pub fn new(file_diffs: Vec<FileDiff>) -> Self {
-        let sidebar_items = build_file_tree(&file_diffs);
+    pub fn new(file_diffs: Vec<FileDiff>, settings: DiffViewSettings) -> Self {
+        sniff_languages(&file_diffs);
+        let line_counts: HashMap<usize, (usize, usize)> = file_diffs
+            .iter()
+            .enumerate()
+            .map(|(idx, diff)| (idx, line_counts(diff)))
+            .collect();
+        let sidebar_items = build_file_tree(&file_diffs, |_| true, &HashSet::new(), &line_counts);
         let sidebar_selected = sidebar_items
             .iter()
             .position(|item| matches!(item, SidebarItem::File { .. }))
@@ -49,9 +201,8 @@ impl AppState {
                 }
             })
             .unwrap_or(0);
-        let settings = DiffViewSettings::default();
         let scroll = if !file_diffs.is_empty() && current_file < file_diffs.len() {
-            calc_initial_scroll(&file_diffs[current_file], settings.tab_width)
+            calc_initial_scroll(&file_diffs[current_file], &settings)
         } else {
             0
         };
@@ -59,6 +210,9 @@ impl AppState {
         Self {
             file_diffs,
             sidebar_items,
+            sidebar_filter: None,
+            collapsed_dirs: HashSet::new(),
+            line_counts,
             current_file,
             sidebar_selected,
             sidebar_scroll: 0,
@@ -73,12 +227,384 @@ impl AppState {
             search_state: SearchState::default(),
             pending_key: PendingKey::default(),
             needs_reload: false,
+            reading_positions: HashMap::new(),
+            line_selection: None,
+            status_message: None,
+            risk: None,
+            file_summaries: None,
+            commit_suggestion: None,
+            index_states: HashMap::new(),
+            staged_hunks: None,
+            show_blame: false,
+            blame: None,
+            file_history: None,
+            view: FileView::default(),
+            loaded_large_files: HashSet::new(),
+            loading: HashSet::new(),
+            views: ViewCache::default(),
+        }
+    }
+
+    /// Put diffs read in the background in place of the files' placeholders, keeping the
+    /// sidebar selection. Diffs for files that were reloaded in the meantime are dropped.
+    pub fn fill_file_diffs(&mut self, diffs: Vec<(usize, FileDiff)>) {
+        let mut filled = false;
+        for (idx, diff) in diffs {
+            let placeholder = self.file_diffs.get(idx);
+            if !self.loading.remove(&idx) || placeholder.is_none_or(|f| f.filename != diff.filename)
+            {
+                continue;
+            }
+            sniff_languages(std::slice::from_ref(&diff));
+            self.file_diffs[idx] = diff;
+            self.line_counts.remove(&idx);
+            if idx == self.current_file {
+                self.view = FileView::default();
+                self.scroll = calc_initial_scroll(&self.file_diffs[idx], &self.settings);
+            }
+            filled = true;
+        }
+        if !filled {
+            return;
+        }
+        // Statuses may have changed from the placeholders'
+        let selected = match self.sidebar_items.get(self.sidebar_selected) {
+            Some(SidebarItem::File { file_index, .. }) => Some(*file_index),
+            _ => None,
+        };
+        self.rebuild_sidebar();
+        if let Some(idx) = self.sidebar_items.iter().position(|item| {
+            matches!(item, SidebarItem::File { file_index, .. } if Some(*file_index) == selected)
+        }) {
+            self.sidebar_selected = idx;
+        }
+    }
+
+    /// Work out the current file's rows, unless they're known already for its settings.
+    pub fn update_view(&mut self) {
+        let Some(diff) = self.file_diffs.get(self.current_file) else {
+            self.view = FileView::default();
+            return;
+        };
+        let tab_width = self.settings.tab_width_for(&diff.filename);
+        let key = (
+            self.current_file,
+            tab_width,
+            self.settings.ignore_whitespace,
+            self.settings.detect_moves,
+        );
+        if self.view.key == Some(key) {
+            return;
+        }
+        // The view being left is kept, with what's highlighted of it, for coming back
+        let previous = std::mem::take(&mut self.view);
+        if let Some(id) = previous.id.clone() {
+            self.views.put(id, previous);
+        }
+        let large = self.settings.is_large(diff);
+        if large && !self.loaded_large_files.contains(&diff.filename) {
+            self.view = FileView {
+                key: Some(key),
+                held_back: Some(diff.line_count()),
+                ..FileView::default()
+            };
+            return;
+        }
+        let id = ViewKey::new(diff, &self.settings);
+        self.view = self
+            .views
+            .take(&id)
+            .unwrap_or_else(|| FileView::compute(diff, &id, large));
+        self.view.key = Some(key);
+        self.prefetch_neighbours();
+    }
+
+    /// Work out the views of the files before and after the current one in the sidebar on
+    /// threads, so moving to either doesn't wait for them.
+    fn prefetch_neighbours(&mut self) {
+        let files: Vec<usize> = self
+            .sidebar_items
+            .iter()
+            .filter_map(|item| match item {
+                SidebarItem::File { file_index, .. } => Some(*file_index),
+                _ => None,
+            })
+            .collect();
+        let Some(position) = files.iter().position(|&file| file == self.current_file) else {
+            return;
+        };
+        let neighbours = [position.checked_sub(1), Some(position + 1)];
+        for &file in neighbours
+            .into_iter()
+            .flatten()
+            .filter_map(|p| files.get(p))
+        {
+            let Some(diff) = self.file_diffs.get(file) else {
+                continue;
+            };
+            let large = self.settings.is_large(diff);
+            if self.loading.contains(&file)
+                || (large && !self.loaded_large_files.contains(&diff.filename))
+            {
+                continue;
+            }
+            let scroll = self
+                .reading_positions
+                .get(&diff.filename)
+                .map(|&(scroll, _)| scroll as usize);
+            self.views
+                .prefetch(ViewKey::new(diff, &self.settings), diff, large, scroll);
+        }
+    }
+
+    /// Where the current file opens: at its first hunk, found from its cached rows if it
+    /// was prefetched.
+    fn initial_scroll(&mut self) -> u16 {
+        let diff = &self.file_diffs[self.current_file];
+        if self.settings.is_large(diff) {
+            return 0;
+        }
+        match self.views.rows(&ViewKey::new(diff, &self.settings)) {
+            Some(rows) => find_hunk_starts(rows)
+                .first()
+                .map_or(0, |&h| (h as u16).saturating_sub(5)),
+            None => calc_initial_scroll(diff, &self.settings),
+        }
+    }
+
+    /// Diff the current file if it was held back for being large, starting at its first hunk.
+    pub fn load_large_file(&mut self) {
+        let Some(diff) = self.file_diffs.get(self.current_file) else {
+            return;
+        };
+        self.loaded_large_files.insert(diff.filename.clone());
+        self.view = FileView::default();
+        self.update_view();
+        self.scroll = find_hunk_starts(&self.view.rows)
+            .first()
+            .map_or(0, |&h| h.saturating_sub(5) as u16);
+    }
+
+    /// Highlight the text of `rows` of the current file that hasn't been already.
+    pub fn highlight_rows(&mut self, rows: Range<usize>) {
+        let Some(diff) = self.file_diffs.get(self.current_file) else {
+            return;
+        };
+        self.view.highlight(rows, &diff.filename);
+    }
+
+    /// Re-read which files are staged, after the index or the set of files changed.
+    pub fn refresh_index(&mut self) {
+        let states = index_states();
+        self.index_states = self
+            .file_diffs
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| states.get(&f.filename).map(|state| (i, *state)))
+            .collect();
+        self.staged_hunks = None;
+        if let Some(suggestion) = self.commit_suggestion.as_mut() {
+            suggestion.index_changed();
+        }
+    }
+
+    /// Work out which hunks of the current file are staged, unless that's known already.
+    pub fn update_staged_hunks(&mut self) {
+        let key = (self.current_file, self.settings.ignore_whitespace);
+        if self
+            .staged_hunks
+            .as_ref()
+            .is_some_and(|(file, ignore_whitespace, _)| (*file, *ignore_whitespace) == key)
+        {
+            return;
+        }
+        let rows = match self.file_diffs.get(self.current_file) {
+            // Only files with something staged need checking hunk by hunk
+            Some(diff) if self.index_states.contains_key(&self.current_file) => {
+                let side_by_side = compute_side_by_side(
+                    &diff.old_content,
+                    &diff.new_content,
+                    self.settings.tab_width_for(&diff.filename),
+                    self.settings.ignore_whitespace,
+                );
+                staged_hunks(diff, &side_by_side)
+            }
+            _ => Vec::new(),
+        };
+        self.staged_hunks = Some((key.0, key.1, rows));
+    }
+
+    /// Whether `rows` are one of the current file's staged hunks
+    pub fn is_hunk_staged(&self, rows: &RangeInclusive<usize>) -> bool {
+        self.staged_hunks
+            .as_ref()
+            .is_some_and(|(file, _, hunks)| *file == self.current_file && hunks.contains(rows))
+    }
+
+    /// Rows of the current file's staged hunks, as far as they're known
+    pub fn staged_rows(&self) -> &[RangeInclusive<usize>] {
+        match &self.staged_hunks {
+            Some((file, _, hunks)) if *file == self.current_file => hunks,
+            _ => &[],
+        }
+    }
+
+    /// Blame both sides of the current file for the gutter, unless that's done already.
+    /// Returns the error for a side that couldn't be blamed, like a file that isn't in git.
+    pub fn update_blame(&mut self, refs: &DiffRefs) -> Option<String> {
+        if !self.show_blame
+            || self
+                .blame
+                .as_ref()
+                .is_some_and(|blame| blame.file_index == self.current_file)
+        {
+            return None;
+        }
+        let diff = self.file_diffs.get(self.current_file)?;
+        let mut error = None;
+        let mut side = |present: bool, revision: Option<&str>, filename: &str| {
+            if !present {
+                return None;
+            }
+            blame_file(revision, filename)
+                .map_err(|e| error = Some(e))
+                .ok()
+        };
+        let old_name = match &diff.status {
+            FileStatus::Renamed { from, .. } | FileStatus::Copied { from, .. } => from,
+            _ => &diff.filename,
+        };
+        let old = side(
+            !diff.old_content.is_empty(),
+            Some(&refs.old_revision()),
+            old_name,
+        );
+        let new = side(
+            !diff.new_content.is_empty(),
+            refs.new_revision().as_deref(),
+            &diff.filename,
+        );
+        self.blame = Some(FileBlame {
+            file_index: self.current_file,
+            old,
+            new,
+        });
+        error
+    }
+
+    /// Blame of the line at `row` of the current file: its new version, or the old one for
+    /// a removed line.
+    pub fn blamed_at(&self, row: usize) -> Option<(&BlameCommit, &BlameLine)> {
+        let blame = self
+            .blame
+            .as_ref()
+            .filter(|blame| blame.file_index == self.current_file)?;
+        let diff = self.file_diffs.get(self.current_file)?;
+        let side_by_side = compute_side_by_side(
+            &diff.old_content,
+            &diff.new_content,
+            self.settings.tab_width_for(&diff.filename),
+            self.settings.ignore_whitespace,
+        );
+        let line = side_by_side.get(row)?;
+        let new = line
+            .new_line
+            .as_ref()
+            .zip(blame.new.as_ref())
+            .and_then(|((num, _), blame)| blame.commit_of(*num));
+        new.or_else(|| {
+            line.old_line
+                .as_ref()
+                .zip(blame.old.as_ref())
+                .and_then(|((num, _), blame)| blame.commit_of(*num))
+        })
+    }
+
+    /// Step through `commits` in place of the current file's diff, starting at the newest.
+    pub fn enter_history(&mut self, commits: Vec<HistoryCommit>) {
+        self.leave_history();
+        let Some(original) = self.file_diffs.get(self.current_file).cloned() else {
+            return;
+        };
+        if commits.is_empty() {
+            return;
+        }
+        self.file_history = Some(FileHistory {
+            file_index: self.current_file,
+            commits,
+            index: 0,
+            original,
+        });
+        // Blame and staged hunks are for the diff being replaced
+        self.show_blame = false;
+        self.blame = None;
+        self.show_history_commit();
+    }
+
+    /// Show the next older or newer commit in file history mode. Returns false at either end.
+    pub fn step_history(&mut self, older: bool) -> bool {
+        let stepped = self
+            .file_history
+            .as_mut()
+            .is_some_and(|history| history.step(older));
+        if stepped {
+            self.show_history_commit();
+        }
+        stepped
+    }
+
+    fn show_history_commit(&mut self) {
+        let Some(history) = &self.file_history else {
+            return;
+        };
+        let diff = history.diff();
+        self.file_diffs[history.file_index] = diff;
+        self.view = FileView::default();
+        self.scroll = calc_initial_scroll(&self.file_diffs[self.current_file], &self.settings);
+        self.h_scroll = 0;
+        self.line_selection = None;
+        self.staged_hunks = None;
+    }
+
+    /// Put the file's own diff back if file history mode is on.
+    pub fn leave_history(&mut self) {
+        let Some(history) = self.file_history.take() else {
+            return;
+        };
+        if let Some(diff) = self.file_diffs.get_mut(history.file_index) {
+            *diff = history.original;
+        }
+        self.view = FileView::default();
+        if history.file_index == self.current_file {
+            self.scroll = calc_initial_scroll(&self.file_diffs[self.current_file], &self.settings);
+            self.h_scroll = 0;
+            self.line_selection = None;
+        }
+        self.staged_hunks = None;
+    }
+
+    /// Switch to new view settings, dropping state tied to the old row layout.
+    pub fn apply_settings(&mut self, settings: DiffViewSettings) {
+        let rows_changed = settings.tab_width != self.settings.tab_width
+            || settings.ignore_whitespace != self.settings.ignore_whitespace;
+        theme::set_mode(settings.theme);
+        if settings.large_file_lines != self.settings.large_file_lines {
+            self.view = FileView::default();
+        }
+        self.settings = settings;
+        if rows_changed {
+            // Whitespace-only rows may have collapsed, so row-based state no longer lines up
+            self.line_selection = None;
+            self.reading_positions.clear();
         }
     }
 
     /// Reload file diffs, optionally unmarking changed files from viewed set.
     /// Preserves scroll position and current file when possible.
     pub fn reload(&mut self, file_diffs: Vec<FileDiff>, changed_files: Option<&HashSet<String>>) {
+        sniff_languages(&file_diffs);
+        // The reloaded diffs replace the one file history mode stood in for
+        self.file_history = None;
         // Store current state to preserve
         let old_filename = self
             .file_diffs
@@ -101,8 +627,17 @@ impl AppState {
             }
         }
 
+        if let Some(risk) = self.risk.as_mut() {
+            risk.update(&file_diffs, changed_files);
+        }
+        if let Some(summaries) = self.file_summaries.as_mut() {
+            summaries.update(&file_diffs, changed_files);
+        }
         self.file_diffs = file_diffs;
-        self.sidebar_items = build_file_tree(&self.file_diffs);
+        self.view = FileView::default();
+        self.loading.clear();
+        self.line_counts.clear();
+        self.rebuild_sidebar();
 
         // Convert viewed filenames back to indices in the new file_diffs
         self.viewed_files = self
@@ -138,57 +673,576 @@ impl AppState {
                 .unwrap_or(0);
         }
 
-        // Preserve scroll position instead of resetting
-        if !self.file_diffs.is_empty() {
+        // Preserve scroll position instead of resetting; large files aren't diffed for it
+        if !self.file_diffs.is_empty()
+            && !self.settings.is_large(&self.file_diffs[self.current_file])
+        {
             // Keep the old scroll position, but clamp to valid range
             let diff = &self.file_diffs[self.current_file];
             let side_by_side = compute_side_by_side(
                 &diff.old_content,
                 &diff.new_content,
-                self.settings.tab_width,
+                self.settings.tab_width_for(&diff.filename),
+                self.settings.ignore_whitespace,
             );
             let max_scroll = side_by_side.len().saturating_sub(10);
             self.scroll = old_scroll.min(max_scroll as u16);
             self.h_scroll = old_h_scroll;
         }
 
+        self.line_selection = None;
+        // Lines may have moved or been committed since the blame was loaded
+        self.blame = None;
         self.needs_reload = false;
     }
 
+    /// Show only the files `filter` matches in the sidebar, moving to the first of them if
+    /// the current file isn't one. Returns how many files are shown.
+    pub fn set_sidebar_filter(&mut self, filter: Option<FileFilter>) -> usize {
+        self.sidebar_filter = filter.filter(|filter| !filter.is_empty());
+        self.rebuild_sidebar();
+        self.sidebar_scroll = 0;
+
+        let files: Vec<(usize, usize)> = self
+            .sidebar_items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| match item {
+                SidebarItem::File { file_index, .. } => Some((idx, *file_index)),
+                _ => None,
+            })
+            .collect();
+        match files.iter().find(|(_, file)| *file == self.current_file) {
+            Some(&(idx, _)) => self.sidebar_selected = idx,
+            None => {
+                self.sidebar_selected = files.first().map_or(0, |&(idx, _)| idx);
+                if let Some(&(_, file_index)) = files.first() {
+                    self.select_file_at_first_hunk(file_index);
+                }
+            }
+        }
+        files.len()
+    }
+
+    /// Switch to a file, resuming at its last reading position if it was opened before.
     pub fn select_file(&mut self, file_index: usize) {
+        self.reveal(file_index);
+        self.leave_history();
+        self.remember_position();
+        self.current_file = file_index;
+        self.diff_fullscreen = DiffFullscreen::None;
+        self.line_selection = None;
+        let filename = &self.file_diffs[self.current_file].filename;
+        if let Some(&(scroll, h_scroll)) = self.reading_positions.get(filename) {
+            self.scroll = scroll;
+            self.h_scroll = h_scroll;
+        } else {
+            self.scroll = self.initial_scroll();
+            self.h_scroll = 0;
+        }
+    }
+
+    /// Switch to a file and jump to its first hunk, ignoring any remembered position.
+    pub fn select_file_at_first_hunk(&mut self, file_index: usize) {
+        self.reveal(file_index);
+        self.leave_history();
+        self.remember_position();
         self.current_file = file_index;
         self.diff_fullscreen = DiffFullscreen::None;
-        self.scroll =
-            calc_initial_scroll(&self.file_diffs[self.current_file], self.settings.tab_width);
+        self.line_selection = None;
+        self.scroll = self.initial_scroll();
         self.h_scroll = 0;
     }
+
+    /// Open `file` with `line` of its new version near the top, selected with `select`, or
+    /// at its first hunk without a line. Returns false if the file isn't in the diff or the
+    /// line isn't shown.
+    pub fn focus(&mut self, file: &str, line: Option<usize>, select: bool) -> bool {
+        let Some(file_index) = self
+            .file_diffs
+            .iter()
+            .position(|diff| diff.filename == file)
+        else {
+            return false;
+        };
+        if let Some(idx) = self.sidebar_items.iter().position(
+            |item| matches!(item, SidebarItem::File { file_index: fi, .. } if *fi == file_index),
+        ) {
+            self.sidebar_selected = idx;
+        }
+        self.select_file_at_first_hunk(file_index);
+        let Some(line) = line else {
+            return true;
+        };
+
+        let diff = &self.file_diffs[file_index];
+        let side_by_side = compute_side_by_side(
+            &diff.old_content,
+            &diff.new_content,
+            self.settings.tab_width_for(&diff.filename),
+            self.settings.ignore_whitespace,
+        );
+        let row = side_by_side
+            .iter()
+            .position(|row| row.new_line.as_ref().is_some_and(|(n, _)| *n == line));
+        match row {
+            Some(row) => {
+                self.scroll = row.saturating_sub(5) as u16;
+                if select {
+                    self.line_selection = Some((row, row));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn remember_position(&mut self) {
+        if let Some(diff) = self.file_diffs.get(self.current_file) {
+            self.reading_positions
+                .insert(diff.filename.clone(), (self.scroll, self.h_scroll));
+        }
+    }
+
+    /// Find the next unviewed file in sidebar order after the current selection, wrapping
+    /// around to the top. Returns the sidebar index and the file index.
+    pub fn next_unviewed_file(&self) -> Option<(usize, usize)> {
+        let after = self
+            .sidebar_items
+            .iter()
+            .enumerate()
+            .skip(self.sidebar_selected + 1);
+        let before = self
+            .sidebar_items
+            .iter()
+            .enumerate()
+            .take(self.sidebar_selected);
+
+        after.chain(before).find_map(|(idx, item)| match item {
+            SidebarItem::File { file_index, .. } if !self.viewed_files.contains(file_index) => {
+                Some((idx, *file_index))
+            }
+            _ => None,
+        })
+    }
+
+    /// Scroll to the next hunk, or with `forward` false the previous one. Past the current
+    /// file's last (or first) hunk, carry on into the next (or previous) unviewed file in
+    /// sidebar order.
+    pub fn jump_to_hunk(&mut self, forward: bool) -> HunkJump {
+        let Some(diff) = self.file_diffs.get(self.current_file) else {
+            return HunkJump::NoneLeft;
+        };
+        let hunks = hunk_starts(diff, &self.settings);
+        let target = if forward {
+            hunks.iter().find(|&&h| h > self.scroll as usize + 5)
+        } else {
+            hunks
+                .iter()
+                .rev()
+                .find(|&&h| (h as u16) < self.scroll.saturating_sub(5))
+        };
+        if let Some(&row) = target {
+            self.scroll = (row as u16).saturating_sub(5);
+            return HunkJump::SameFile;
+        }
+
+        let current = self.sidebar_items.iter().position(
+            |item| matches!(item, SidebarItem::File { file_index, .. } if *file_index == self.current_file),
+        );
+        let unviewed = |(idx, item): (usize, &SidebarItem)| match item {
+            SidebarItem::File { file_index, .. } if !self.viewed_files.contains(file_index) => {
+                Some((idx, *file_index))
+            }
+            _ => None,
+        };
+        let items = self.sidebar_items.iter().enumerate();
+        let adjacent = match (forward, current) {
+            (true, Some(current)) => items.skip(current + 1).find_map(unviewed),
+            (true, None) => items.skip(self.sidebar_selected).find_map(unviewed),
+            (false, Some(current)) => items.take(current).rev().find_map(unviewed),
+            (false, None) => items.take(self.sidebar_selected).rev().find_map(unviewed),
+        };
+        let Some((idx, file_index)) = adjacent else {
+            return HunkJump::NoneLeft;
+        };
+        self.sidebar_selected = idx;
+        self.select_file_at_first_hunk(file_index);
+        let diff = &self.file_diffs[file_index];
+        if !forward && !self.settings.is_large(diff) {
+            if let Some(&last) = hunk_starts(diff, &self.settings).last() {
+                self.scroll = (last as u16).saturating_sub(5);
+            }
+        }
+        HunkJump::OtherFile
+    }
+
+    /// Open the next unviewed file at its first hunk. Returns false when every file is viewed.
+    pub fn advance_to_next_unviewed(&mut self) -> bool {
+        match self.next_unviewed_file() {
+            Some((idx, file_index)) => {
+                self.sidebar_selected = idx;
+                self.select_file_at_first_hunk(file_index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Selected rows in ascending order, if a line selection is active.
+    pub fn selected_rows(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        self.line_selection
+            .map(|(anchor, cursor)| anchor.min(cursor)..=anchor.max(cursor))
+    }
+
+    /// Build the sidebar again from the files, the filter and the collapsed directories,
+    /// counting lines only of files that changed since it was last built.
+    fn rebuild_sidebar(&mut self) {
+        for (idx, diff) in self.file_diffs.iter().enumerate() {
+            self.line_counts
+                .entry(idx)
+                .or_insert_with(|| line_counts(diff));
+        }
+        let filter = self.sidebar_filter.as_ref();
+        self.sidebar_items = build_file_tree(
+            &self.file_diffs,
+            |diff| filter.is_none_or(|filter| filter.matches(diff)),
+            &self.collapsed_dirs,
+            &self.line_counts,
+        );
+    }
+
+    /// Collapse the directory at `path` in the sidebar, or expand it if it's collapsed,
+    /// keeping it selected.
+    pub fn toggle_directory(&mut self, path: &str) {
+        if !self.collapsed_dirs.remove(path) {
+            self.collapsed_dirs.insert(path.to_string());
+        }
+        self.rebuild_sidebar();
+        if let Some(idx) = self
+            .sidebar_items
+            .iter()
+            .position(|item| matches!(item, SidebarItem::Directory { path: p, .. } if p == path))
+        {
+            self.sidebar_selected = idx;
+        }
+    }
+
+    /// Expand the directories hiding `file_index`, so it can be selected in the sidebar.
+    fn reveal(&mut self, file_index: usize) {
+        let Some(diff) = self.file_diffs.get(file_index) else {
+            return;
+        };
+        let before = self.collapsed_dirs.len();
+        self.collapsed_dirs
+            .retain(|dir| !diff.filename.starts_with(&format!("{dir}/")));
+        if self.collapsed_dirs.len() == before {
+            return;
+        }
+        self.rebuild_sidebar();
+        if let Some(idx) = self.sidebar_items.iter().position(
+            |item| matches!(item, SidebarItem::File { file_index: fi, .. } if *fi == file_index),
+        ) {
+            self.sidebar_selected = idx;
+        }
+    }
+
+    /// Indices of the files in the sidebar's `visible_height` rows, leaving out those still
+    /// being read.
+    pub fn files_in_view(&self, visible_height: usize) -> Vec<usize> {
+        self.sidebar_items
+            .iter()
+            .skip(self.sidebar_scroll)
+            .take(visible_height)
+            .filter_map(|item| match item {
+                SidebarItem::File { file_index, .. } if !self.loading.contains(file_index) => {
+                    Some(*file_index)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Scroll the sidebar so the selected item is within the visible rows.
+    pub fn ensure_sidebar_visible(&mut self, visible_height: usize) {
+        if self.sidebar_selected >= self.sidebar_scroll + visible_height {
+            self.sidebar_scroll = self.sidebar_selected.saturating_sub(visible_height) + 1;
+        } else if self.sidebar_selected < self.sidebar_scroll {
+            self.sidebar_scroll = self.sidebar_selected;
+        }
+    }
 }
 
-pub fn calc_initial_scroll(diff: &FileDiff, tab_width: usize) -> u16 {
-    let side_by_side = compute_side_by_side(&diff.old_content, &diff.new_content, tab_width);
-    let hunks = find_hunk_starts(&side_by_side);
-    hunks
+/// Let the highlighter recognize scripts by their shebang, from whichever side has content
+fn sniff_languages(file_diffs: &[FileDiff]) {
+    highlight::sniff_languages(file_diffs.iter().map(|diff| {
+        let content = if diff.new_content.is_empty() {
+            &diff.old_content
+        } else {
+            &diff.new_content
+        };
+        (diff.filename.as_str(), content.as_str())
+    }));
+}
+
+pub fn calc_initial_scroll(diff: &FileDiff, settings: &DiffViewSettings) -> u16 {
+    // Large files open at the top rather than being diffed to find their first hunk
+    if settings.is_large(diff) {
+        return 0;
+    }
+    hunk_starts(diff, settings)
         .first()
         .map(|&h| (h as u16).saturating_sub(5))
         .unwrap_or(0)
 }
 
+/// Rows of the side-by-side diff of `diff` where its hunks start
+fn hunk_starts(diff: &FileDiff, settings: &DiffViewSettings) -> Vec<usize> {
+    let side_by_side = compute_side_by_side(
+        &diff.old_content,
+        &diff.new_content,
+        settings.tab_width_for(&diff.filename),
+        settings.ignore_whitespace,
+    );
+    find_hunk_starts(&side_by_side)
+}
+
+/// The furthest the diff can scroll while still filling all but `padding` lines of the
+/// screen, given the screen lines each of its rows takes.
+pub fn scroll_limit(heights: &[usize], visible_height: usize, padding: usize) -> usize {
+    first_fitting(
+        heights,
+        heights.len(),
+        visible_height.saturating_sub(padding),
+    )
+}
+
+/// The first row from which the rows up to `end` fit in `room` screen lines.
+fn first_fitting(heights: &[usize], end: usize, room: usize) -> usize {
+    let mut first = end;
+    let mut used = 0;
+    while first > 0 && used + heights.get(first - 1).copied().unwrap_or(1) <= room {
+        first -= 1;
+        used += heights.get(first).copied().unwrap_or(1);
+    }
+    first
+}
+
+/// Scroll so `line` is on screen with some margin around it, if it isn't already.
+/// `heights` are the screen lines each row takes, which is more than one for wrapped rows.
 pub fn adjust_scroll_to_line(
     line: usize,
     scroll: u16,
     visible_height: usize,
     max_scroll: usize,
+    heights: &[usize],
 ) -> u16 {
     let margin = 10usize;
     let scroll_usize = scroll as usize;
     let content_height = visible_height.saturating_sub(2);
+    let room = content_height.saturating_sub(margin);
+    let below: usize = (scroll_usize..=line)
+        .map(|row| heights.get(row).copied().unwrap_or(1))
+        .sum();
 
     let new_scroll = if line < scroll_usize + margin {
-        line.saturating_sub(margin) as u16
-    } else if line >= scroll_usize + content_height.saturating_sub(margin) {
-        (line.saturating_sub(content_height.saturating_sub(margin).saturating_sub(1))) as u16
+        line.saturating_sub(margin)
+    } else if below > room {
+        first_fitting(heights, line + 1, room).min(line)
     } else {
-        scroll
+        scroll_usize
     };
-    new_scroll.min(max_scroll as u16)
+    new_scroll.min(max_scroll) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_view_is_kept_until_the_diff_changes() {
+        let diff = FileDiff {
+            filename: "notes.txt".to_string(),
+            old_content: "a\nb\n".to_string(),
+            new_content: "a\nc\nd\n".to_string(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        };
+        let mut state = AppState::new(vec![diff.clone()], DiffViewSettings::default());
+        state.update_view();
+        let rows = state.view.rows.len();
+        assert!(rows >= 3);
+        state.highlight_rows(1..rows + 10);
+        assert!(state.view.highlighted(0, false).is_none());
+        assert!(state.view.highlighted(rows - 1, false).is_some());
+
+        state.update_view();
+        assert!(state.view.highlighted(rows - 1, false).is_some());
+        state.reload(vec![diff], None);
+        assert!(state.view.rows.is_empty());
+        state.update_view();
+        assert_eq!(state.view.rows.len(), rows);
+        assert!(state.view.highlighted(rows - 1, false).is_none());
+    }
+
+    #[test]
+    fn test_hunk_jumps_carry_on_into_unviewed_files() {
+        let diff = |filename: &str| FileDiff {
+            filename: filename.to_string(),
+            old_content: "x\n".to_string(),
+            new_content: "y\n".to_string(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        };
+        let mut state = AppState::new(
+            vec![diff("a.rs"), diff("b.rs"), diff("c.rs")],
+            DiffViewSettings::default(),
+        );
+        state.viewed_files.insert(1);
+        assert_eq!(state.jump_to_hunk(true), HunkJump::OtherFile);
+        assert_eq!(state.current_file, 2);
+        assert_eq!(state.jump_to_hunk(true), HunkJump::NoneLeft);
+        assert_eq!(state.jump_to_hunk(false), HunkJump::OtherFile);
+        assert_eq!(state.current_file, 0);
+        assert_eq!(state.jump_to_hunk(false), HunkJump::NoneLeft);
+    }
+
+    fn modified(filename: &str, lines: usize) -> FileDiff {
+        let old: String = (0..lines).map(|i| format!("line {i}\n")).collect();
+        FileDiff {
+            filename: filename.to_string(),
+            new_content: old.replace("line 0\n", "first\n"),
+            old_content: old,
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        }
+    }
+
+    #[test]
+    fn test_advance_to_next_unviewed_wraps_around() {
+        let mut state = AppState::new(
+            vec![
+                modified("a.rs", 1),
+                modified("b.rs", 1),
+                modified("c.rs", 1),
+            ],
+            DiffViewSettings::default(),
+        );
+        state.viewed_files.insert(1);
+        assert!(state.advance_to_next_unviewed());
+        assert_eq!((state.current_file, state.sidebar_selected), (2, 2));
+
+        state.viewed_files.insert(2);
+        assert!(state.advance_to_next_unviewed());
+        assert_eq!(state.current_file, 0);
+
+        state.viewed_files.insert(0);
+        assert!(!state.advance_to_next_unviewed());
+        assert_eq!(state.current_file, 0);
+    }
+
+    #[test]
+    fn test_reading_positions_are_restored() {
+        let mut state = AppState::new(
+            vec![modified("a.rs", 50), modified("b.rs", 50)],
+            DiffViewSettings::default(),
+        );
+        state.scroll = 30;
+        state.h_scroll = 4;
+        state.select_file(1);
+        assert_eq!((state.scroll, state.h_scroll), (0, 0));
+        state.scroll = 12;
+
+        state.select_file(0);
+        assert_eq!((state.scroll, state.h_scroll), (30, 4));
+        state.select_file(1);
+        assert_eq!(state.scroll, 12);
+
+        // Jumping to a file's first hunk doesn't resume it
+        state.select_file_at_first_hunk(0);
+        assert_eq!((state.scroll, state.h_scroll), (0, 0));
+
+        // Nor does it survive a change to the row layout
+        let settings = DiffViewSettings {
+            ignore_whitespace: true,
+            ..DiffViewSettings::default()
+        };
+        state.apply_settings(settings);
+        assert!(state.reading_positions.is_empty());
+    }
+
+    #[test]
+    fn test_ensure_sidebar_visible() {
+        let files = (0..20)
+            .map(|i| modified(&format!("{i:02}.rs"), 1))
+            .collect();
+        let mut state = AppState::new(files, DiffViewSettings::default());
+        state.sidebar_selected = 12;
+        state.ensure_sidebar_visible(5);
+        assert_eq!(state.sidebar_scroll, 8);
+        // Already on screen
+        state.sidebar_selected = 9;
+        state.ensure_sidebar_visible(5);
+        assert_eq!(state.sidebar_scroll, 8);
+        state.sidebar_selected = 3;
+        state.ensure_sidebar_visible(5);
+        assert_eq!(state.sidebar_scroll, 3);
+    }
+
+    #[test]
+    fn test_fill_file_diffs_replaces_placeholders() {
+        let diff = |filename: &str, new_content: &str, status| FileDiff {
+            filename: filename.to_string(),
+            old_content: String::new(),
+            new_content: new_content.to_string(),
+            status,
+            binary: None,
+            submodule: None,
+        };
+        let mut state = AppState::new(
+            vec![
+                diff("a.rs", "", FileStatus::Modified),
+                diff("b.rs", "", FileStatus::Modified),
+            ],
+            DiffViewSettings::default(),
+        );
+        state.loading = HashSet::from([0, 1]);
+        state.fill_file_diffs(vec![
+            (1, diff("b.rs", "fn b() {}\n", FileStatus::Added)),
+            // Meant for a list of files that has since been reloaded
+            (0, diff("c.rs", "fn c() {}\n", FileStatus::Added)),
+        ]);
+        assert_eq!(state.loading, HashSet::new());
+        assert_eq!(state.file_diffs[0].filename, "a.rs");
+        assert_eq!(state.file_diffs[1].status, FileStatus::Added);
+        assert!(state.sidebar_items.iter().any(|item| matches!(
+            item,
+            SidebarItem::File {
+                file_index: 1,
+                status: FileStatus::Added,
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn test_scroll_with_wrapped_rows() {
+        let single = vec![1; 100];
+        assert_eq!(scroll_limit(&single, 40, 5), 65);
+        assert_eq!(adjust_scroll_to_line(50, 0, 40, 65, &single), 23);
+        assert_eq!(adjust_scroll_to_line(20, 0, 40, 65, &single), 0);
+
+        // Every other row wraps onto three lines
+        let wrapped: Vec<usize> = (0..100).map(|row| 1 + row % 2 * 2).collect();
+        assert_eq!(scroll_limit(&wrapped, 40, 5), 83);
+        let scroll = adjust_scroll_to_line(50, 0, 40, 83, &wrapped);
+        assert_eq!(scroll, 37);
+        let shown: usize = wrapped[scroll as usize..=50].iter().sum();
+        assert!(shown <= 28);
+        // A row that's already well on screen doesn't move it
+        assert_eq!(adjust_scroll_to_line(48, 37, 40, 83, &wrapped), 37);
+    }
 }
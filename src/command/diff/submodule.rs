@@ -0,0 +1,144 @@
+//! Submodules whose recorded commit changed. git records a submodule as a commit id, a
+//! gitlink, rather than as a file, so there are no lines to diff, only the two commits, which
+//! the submodule's own checkout can say more about.
+
+use std::path::Path;
+use std::process::Command;
+
+use crate::vcs;
+
+use super::types::{SubmoduleChange, SubmoduleCommit};
+
+/// What git prints in a patch in place of a submodule's contents
+const PATCH_PREFIX: &str = "Subproject commit ";
+
+/// The commit recorded for the submodule at `path` in `revision`, or checked out in the
+/// working tree when that's `None`. `None` when `path` isn't a submodule there.
+pub fn gitlink(revision: Option<&str>, path: &str) -> Option<String> {
+    match revision {
+        Some(revision) => parse_ls_tree(&git(".", &["ls-tree", revision, "--", path])?),
+        None if is_checked_out(path) => git(path, &["rev-parse", "HEAD"]),
+        None => None,
+    }
+}
+
+/// The change to the submodule at `path` from commit `old` to `new`, `None` when it's on
+/// neither side. Subjects and counts come from its checkout, if it has the commits.
+pub fn change(path: &str, old: Option<String>, new: Option<String>) -> Option<SubmoduleChange> {
+    if old.is_none() && new.is_none() {
+        return None;
+    }
+    let checked_out = is_checked_out(path);
+    let commit = |sha: String| SubmoduleCommit {
+        subject: checked_out
+            .then(|| git(path, &["log", "-1", "--format=%s", &sha, "--"]))
+            .flatten(),
+        sha,
+    };
+    let behind_ahead = match (&old, &new) {
+        (Some(old), Some(new)) if checked_out => git(
+            path,
+            &[
+                "rev-list",
+                "--left-right",
+                "--count",
+                &format!("{old}...{new}"),
+            ],
+        )
+        .and_then(|counts| parse_counts(&counts)),
+        _ => None,
+    };
+    Some(SubmoduleChange {
+        old: old.map(commit),
+        new: new.map(commit),
+        behind_ahead,
+    })
+}
+
+/// The commits a patch records for a submodule, read from its `Subproject commit` lines.
+/// `None` unless each side is one of those lines or nothing.
+pub fn from_patch(path: &str, old: &str, new: &str) -> Option<SubmoduleChange> {
+    let side = |content: &str| -> Option<Option<String>> {
+        match content.lines().collect::<Vec<_>>()[..] {
+            [] => Some(None),
+            [line] => Some(Some(line.strip_prefix(PATCH_PREFIX)?.trim().to_string())),
+            _ => None,
+        }
+    };
+    change(path, side(old)?, side(new)?)
+}
+
+/// The patch lines git writes for a submodule moving from `old` to `new`
+pub fn patch_lines(change: &SubmoduleChange) -> (Option<String>, Option<String>) {
+    let line = |commit: &SubmoduleCommit| format!("{PATCH_PREFIX}{}\n", commit.sha);
+    (change.old.as_ref().map(line), change.new.as_ref().map(line))
+}
+
+fn is_checked_out(path: &str) -> bool {
+    Path::new(path).join(".git").exists()
+}
+
+/// What git prints for `args` run in `dir`, trimmed, `None` when it fails or prints nothing.
+fn git(dir: &str, args: &[&str]) -> Option<String> {
+    let stdout = vcs::run_git(Command::new("git").arg("-C").arg(dir).args(args)).ok()?;
+    Some(stdout.trim().to_string()).filter(|stdout| !stdout.is_empty())
+}
+
+/// The commit of a `git ls-tree` entry, if it's a gitlink (mode 160000).
+fn parse_ls_tree(output: &str) -> Option<String> {
+    let (meta, _path) = output.lines().next()?.split_once('\t')?;
+    match meta.split_whitespace().collect::<Vec<_>>()[..] {
+        ["160000", "commit", sha] => Some(sha.to_string()),
+        _ => None,
+    }
+}
+
+/// The two counts `git rev-list --left-right --count` prints.
+fn parse_counts(output: &str) -> Option<(usize, usize)> {
+    let mut counts = output.split_whitespace().map(str::parse);
+    Some((counts.next()?.ok()?, counts.next()?.ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_gitlinks_are_submodules() {
+        assert_eq!(
+            parse_ls_tree("160000 commit 6df2f32eb5316ddaeb554099e6eec05953a7f7cf\tlib\n"),
+            Some("6df2f32eb5316ddaeb554099e6eec05953a7f7cf".to_string())
+        );
+        assert_eq!(parse_ls_tree("100644 blob 8ab686e\tlib\n"), None);
+        assert_eq!(parse_ls_tree(""), None);
+        assert_eq!(parse_counts("2\t5\n"), Some((2, 5)));
+    }
+
+    #[test]
+    fn test_submodule_read_from_patch() {
+        // Not checked out here, so only the ids are known
+        let change = from_patch(
+            "vendor/missing",
+            "Subproject commit 6df2f32\n",
+            "Subproject commit e4c3baa\n",
+        )
+        .expect("a submodule");
+        assert_eq!(change.old.as_ref().unwrap().sha, "6df2f32");
+        assert_eq!(change.new.as_ref().unwrap().sha, "e4c3baa");
+        assert_eq!(
+            patch_lines(&change),
+            (
+                Some("Subproject commit 6df2f32\n".to_string()),
+                Some("Subproject commit e4c3baa\n".to_string())
+            )
+        );
+
+        let added = from_patch("vendor/missing", "", "Subproject commit e4c3baa\n").unwrap();
+        assert_eq!(added.old, None);
+        assert_eq!(
+            from_patch("notes.txt", "a\n", "Subproject commit e4c3baa\n"),
+            None
+        );
+        assert_eq!(from_patch("notes.txt", "", ""), None);
+    }
+}
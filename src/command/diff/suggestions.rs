@@ -0,0 +1,192 @@
+//! Suggested changes from PR review comments, turned into patches the author can accept
+//! without leaving the terminal.
+//!
+//! GitHub has no API for its "Commit suggestion" button, so accepting applies the patch to
+//! the local checkout of the PR branch, ready to be committed and pushed.
+
+use std::collections::HashSet;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde_json::Value;
+
+use super::stage::build_partial_patch;
+use super::threads::fetch_review_comments;
+use super::PrInfo;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    pub path: String,
+    pub author: String,
+    /// 1-based lines of the PR's head version that get replaced
+    pub start_line: usize,
+    pub end_line: usize,
+    /// Lines to put in their place, empty to delete them
+    pub replacement: String,
+}
+
+/// Load the suggestions on the PR in the background, see [`fetch_suggestions`].
+pub fn load_suggestions_async(pr: &PrInfo) -> Receiver<Result<Vec<Suggestion>, String>> {
+    let pr = pr.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(fetch_suggestions(&pr));
+    });
+    rx
+}
+
+/// Suggestions in the PR's review comments, oldest first. Comments on outdated code and on
+/// the base side of the diff are left out, since their lines no longer say where to apply them.
+fn fetch_suggestions(pr: &PrInfo) -> Result<Vec<Suggestion>, String> {
+    parse_suggestions(&fetch_review_comments(pr)?)
+}
+
+fn parse_suggestions(json: &str) -> Result<Vec<Suggestion>, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let mut suggestions = Vec::new();
+    for comment in value.as_array().into_iter().flatten() {
+        let (Some(path), Some(line), Some(body)) = (
+            comment["path"].as_str(),
+            comment["line"].as_u64(),
+            comment["body"].as_str(),
+        ) else {
+            continue;
+        };
+        if comment["side"].as_str() == Some("LEFT") {
+            continue;
+        }
+        let end_line = line as usize;
+        let start_line = comment["start_line"]
+            .as_u64()
+            .map_or(end_line, |start| start as usize);
+        for replacement in suggestion_blocks(body) {
+            suggestions.push(Suggestion {
+                path: path.to_string(),
+                author: comment["user"]["login"]
+                    .as_str()
+                    .unwrap_or("ghost")
+                    .to_string(),
+                start_line,
+                end_line,
+                replacement,
+            });
+        }
+    }
+    Ok(suggestions)
+}
+
+/// Contents of the ```` ```suggestion ```` blocks in a comment body.
+fn suggestion_blocks(body: &str) -> Vec<String> {
+    let body = body.replace("\r\n", "\n");
+    let mut blocks = Vec::new();
+    // Backticks of the open fence and the lines inside it
+    let mut open: Option<(usize, Vec<&str>)> = None;
+    for line in body.lines() {
+        let trimmed = line.trim();
+        let ticks = trimmed.chars().take_while(|&c| c == '`').count();
+        match &mut open {
+            Some((fence, lines)) => {
+                if ticks >= *fence && trimmed.len() == ticks {
+                    let text = lines.iter().map(|line| format!("{line}\n")).collect();
+                    blocks.push(text);
+                    open = None;
+                } else {
+                    lines.push(line);
+                }
+            }
+            None if ticks >= 3 && trimmed[ticks..].trim() == "suggestion" => {
+                open = Some((ticks, Vec::new()));
+            }
+            None => {}
+        }
+    }
+    blocks
+}
+
+/// Patch replacing the suggestion's lines of `content`, the file as the PR has it. `None`
+/// when the lines are out of range or the suggestion changes nothing.
+pub fn suggestion_patch(suggestion: &Suggestion, content: &str) -> Option<String> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let Suggestion {
+        start_line,
+        end_line,
+        ..
+    } = *suggestion;
+    if start_line == 0 || start_line > end_line || end_line > lines.len() {
+        return None;
+    }
+
+    let mut replacement = suggestion.replacement.clone();
+    // Keep a missing newline at the end of the file missing
+    if end_line == lines.len() && !content.ends_with('\n') {
+        replacement.pop();
+    }
+    let suggested: String = lines[..start_line - 1]
+        .iter()
+        .copied()
+        .chain(std::iter::once(replacement.as_str()))
+        .chain(lines[end_line..].iter().copied())
+        .collect();
+
+    let old_selected: HashSet<usize> = (start_line..=end_line).collect();
+    let added = suggestion.replacement.lines().count();
+    let new_selected: HashSet<usize> = (start_line..start_line + added).collect();
+    build_partial_patch(
+        &suggestion.path,
+        content,
+        &suggested,
+        &old_selected,
+        &new_selected,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn suggestion(start_line: usize, end_line: usize, replacement: &str) -> Suggestion {
+        Suggestion {
+            path: "f".to_string(),
+            author: "ada".to_string(),
+            start_line,
+            end_line,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_suggestions_skips_outdated_and_plain_comments() {
+        let json = r#"[
+            {"id":1,"path":"src/a.rs","line":4,"start_line":2,"side":"RIGHT","user":{"login":"ada"},
+             "body":"Simpler:\r\n```suggestion\r\nlet x = 1;\r\n```"},
+            {"id":2,"path":"src/a.rs","line":null,"side":"RIGHT","user":{"login":"bob"},
+             "body":"```suggestion\nold\n```"},
+            {"id":3,"path":"src/b.rs","line":7,"side":"RIGHT","user":{"login":"bob"},
+             "body":"Looks good"},
+            {"id":4,"path":"src/b.rs","line":9,"start_line":null,"side":"RIGHT","user":{"login":"cyd"},
+             "body":"Drop it\n````suggestion\n````"}
+        ]"#;
+        let suggestions = parse_suggestions(json).unwrap();
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].replacement, "let x = 1;\n");
+        assert_eq!((suggestions[0].start_line, suggestions[0].end_line), (2, 4));
+        assert_eq!(suggestions[1].author, "cyd");
+        assert_eq!(suggestions[1].start_line, 9);
+        assert_eq!(suggestions[1].replacement, "");
+    }
+
+    #[test]
+    fn test_suggestion_patch_replaces_lines() {
+        let patch = suggestion_patch(&suggestion(2, 3, "x\n"), "a\nb\nc\nd\n").unwrap();
+        assert_eq!(
+            patch,
+            "diff --git a/f b/f\n--- a/f\n+++ b/f\n@@ -1,4 +1,3 @@\n a\n-b\n-c\n+x\n d\n"
+        );
+    }
+
+    #[test]
+    fn test_suggestion_patch_rejects_no_ops_and_stale_lines() {
+        assert!(suggestion_patch(&suggestion(2, 2, "b\n"), "a\nb\n").is_none());
+        assert!(suggestion_patch(&suggestion(3, 4, "x\n"), "a\nb\n").is_none());
+    }
+}
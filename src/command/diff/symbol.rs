@@ -0,0 +1,270 @@
+//! `diff --symbol`: one function or type, found by name with tree-sitter in both revisions,
+//! so the viewer shows only how it changed.
+
+use std::path::Path;
+use std::process::Command;
+
+use once_cell::sync::Lazy;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{Language, Parser, Query, QueryCursor};
+
+use crate::logging;
+
+use super::encoding::decode;
+use super::git::{get_new_content, get_old_content, DiffRefs};
+use super::types::{FileDiff, FileStatus};
+
+// Named definitions for each supported language: `@symbol` is the whole definition and
+// `@name` the name it's looked up by
+
+const RUST_SYMBOL_QUERY: &str = r#"
+(function_item name: (identifier) @name) @symbol
+(function_signature_item name: (identifier) @name) @symbol
+(struct_item name: (type_identifier) @name) @symbol
+(enum_item name: (type_identifier) @name) @symbol
+(trait_item name: (type_identifier) @name) @symbol
+(type_item name: (type_identifier) @name) @symbol
+(mod_item name: (identifier) @name) @symbol
+(macro_definition name: (identifier) @name) @symbol
+"#;
+
+const TYPESCRIPT_SYMBOL_QUERY: &str = r#"
+(function_declaration name: (identifier) @name) @symbol
+(generator_function_declaration name: (identifier) @name) @symbol
+(method_definition name: (property_identifier) @name) @symbol
+(class_declaration name: (type_identifier) @name) @symbol
+(abstract_class_declaration name: (type_identifier) @name) @symbol
+(interface_declaration name: (type_identifier) @name) @symbol
+(type_alias_declaration name: (type_identifier) @name) @symbol
+(enum_declaration name: (identifier) @name) @symbol
+(variable_declarator name: (identifier) @name value: [(arrow_function) (function_expression)]) @symbol
+"#;
+
+const JAVASCRIPT_SYMBOL_QUERY: &str = r#"
+(function_declaration name: (identifier) @name) @symbol
+(generator_function_declaration name: (identifier) @name) @symbol
+(method_definition name: (property_identifier) @name) @symbol
+(class_declaration name: (identifier) @name) @symbol
+(variable_declarator name: (identifier) @name value: [(arrow_function) (function_expression)]) @symbol
+"#;
+
+const PYTHON_SYMBOL_QUERY: &str = r#"
+(function_definition name: (identifier) @name) @symbol
+(class_definition name: (identifier) @name) @symbol
+"#;
+
+const GO_SYMBOL_QUERY: &str = r#"
+(function_declaration name: (identifier) @name) @symbol
+(method_declaration name: (field_identifier) @name) @symbol
+(type_spec name: (type_identifier) @name) @symbol
+"#;
+
+struct SymbolLanguage {
+    extensions: &'static [&'static str],
+    language: Language,
+    query: Query,
+}
+
+static SYMBOL_LANGUAGES: Lazy<Vec<SymbolLanguage>> = Lazy::new(|| {
+    let languages: [(&'static [&'static str], Language, &str); 6] = [
+        (
+            &["rs"],
+            tree_sitter_rust::LANGUAGE.into(),
+            RUST_SYMBOL_QUERY,
+        ),
+        (
+            &["ts", "mts", "cts"],
+            tree_sitter_typescript::LANGUAGE_TYPESCRIPT.into(),
+            TYPESCRIPT_SYMBOL_QUERY,
+        ),
+        (
+            &["tsx"],
+            tree_sitter_typescript::LANGUAGE_TSX.into(),
+            TYPESCRIPT_SYMBOL_QUERY,
+        ),
+        (
+            &["js", "jsx", "mjs", "cjs"],
+            tree_sitter_javascript::LANGUAGE.into(),
+            JAVASCRIPT_SYMBOL_QUERY,
+        ),
+        (
+            &["py"],
+            tree_sitter_python::LANGUAGE.into(),
+            PYTHON_SYMBOL_QUERY,
+        ),
+        (&["go"], tree_sitter_go::LANGUAGE.into(), GO_SYMBOL_QUERY),
+    ];
+    languages
+        .into_iter()
+        .filter_map(|(extensions, language, query)| {
+            let query = Query::new(&language, query).ok()?;
+            Some(SymbolLanguage {
+                extensions,
+                language,
+                query,
+            })
+        })
+        .collect()
+});
+
+fn symbol_language(filename: &str) -> Option<&'static SymbolLanguage> {
+    let ext = Path::new(filename).extension().and_then(|e| e.to_str())?;
+    SYMBOL_LANGUAGES
+        .iter()
+        .find(|lang| lang.extensions.contains(&ext))
+}
+
+/// Every named definition in `source` as its name and first and last line, counted from 0,
+/// or nothing when the language has no symbol query.
+pub fn definitions(filename: &str, source: &str) -> Vec<(String, usize, usize)> {
+    let Some(lang) = symbol_language(filename) else {
+        return Vec::new();
+    };
+    let mut parser = Parser::new();
+    if parser.set_language(&lang.language).is_err() {
+        return Vec::new();
+    }
+    let Some(tree) = parser.parse(source, None) else {
+        return Vec::new();
+    };
+    let (Some(name_index), Some(symbol_index)) = (
+        lang.query.capture_index_for_name("name"),
+        lang.query.capture_index_for_name("symbol"),
+    ) else {
+        return Vec::new();
+    };
+
+    let mut found = Vec::new();
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&lang.query, tree.root_node(), source.as_bytes());
+    while let Some(m) = matches.next() {
+        let node = |index| m.captures.iter().find(|c| c.index == index).map(|c| c.node);
+        let (Some(name_node), Some(symbol_node)) = (node(name_index), node(symbol_index)) else {
+            continue;
+        };
+        if let Ok(name) = name_node.utf8_text(source.as_bytes()) {
+            found.push((
+                name.to_string(),
+                symbol_node.start_position().row,
+                symbol_node.end_position().row,
+            ));
+        }
+    }
+    found
+}
+
+/// The lines of every definition of `name` in `source`, in order and separated by a blank
+/// line, or `None` when the file has none. Definitions nested in one already taken are
+/// part of it and not repeated.
+pub fn find_symbol(filename: &str, source: &str, name: &str) -> Option<String> {
+    let mut ranges: Vec<(usize, usize)> = definitions(filename, source)
+        .into_iter()
+        .filter(|(found, _, _)| found == name)
+        .map(|(_, start, end)| (start, end))
+        .collect();
+    ranges.sort();
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut definitions: Vec<String> = Vec::new();
+    let mut taken_until = None;
+    for (start, end) in ranges {
+        if taken_until.is_some_and(|until| start <= until) {
+            continue;
+        }
+        taken_until = Some(end);
+        definitions.push(lines[start..=end.min(lines.len() - 1)].join("\n") + "\n");
+    }
+    (!definitions.is_empty()).then(|| definitions.join("\n"))
+}
+
+/// Files that mention `name` as a word at `revision`, or in the working tree for `None`.
+fn files_mentioning(name: &str, revision: Option<&str>) -> Result<Vec<String>, String> {
+    let mut args = vec!["grep", "-l", "-w", "-F", "-e", name];
+    args.extend(revision);
+    let output = logging::output(Command::new("git").args(&args))
+        .map_err(|e| format!("Failed to run git grep: {e}"))?;
+    // Exit code 1 only means nothing matched
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+    let prefix = revision.map(|rev| format!("{rev}:")).unwrap_or_default();
+    Ok(decode(&output.stdout, None)
+        .lines()
+        .map(|line| line.strip_prefix(&prefix).unwrap_or(line).to_string())
+        .collect())
+}
+
+/// The definitions of `name` on both sides of `refs`, one diff per file that has it on
+/// either side.
+pub fn load_symbol_diffs(name: &str, refs: &DiffRefs) -> Result<Vec<FileDiff>, String> {
+    let old_revision = refs.old_revision();
+    let mut files = files_mentioning(name, Some(&old_revision))?;
+    files.extend(files_mentioning(name, refs.new_revision().as_deref())?);
+    files.retain(|file| symbol_language(file).is_some());
+    files.sort();
+    files.dedup();
+
+    let file_diffs: Vec<FileDiff> = files
+        .into_iter()
+        .filter_map(|filename| {
+            let old = find_symbol(&filename, &get_old_content(&filename, refs), name);
+            let new = find_symbol(&filename, &get_new_content(&filename, refs), name);
+            let status = match (&old, &new) {
+                (None, None) => return None,
+                (None, Some(_)) => FileStatus::Added,
+                (Some(_), None) => FileStatus::Deleted,
+                (Some(_), Some(_)) => FileStatus::Modified,
+            };
+            Some(FileDiff {
+                filename,
+                old_content: old.unwrap_or_default(),
+                new_content: new.unwrap_or_default(),
+                status,
+                binary: None,
+                submodule: None,
+            })
+        })
+        .collect();
+
+    if file_diffs.is_empty() {
+        return Err(format!(
+            "No function or type named `{name}` in either revision"
+        ));
+    }
+    Ok(file_diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_rust_symbol() {
+        let source = "use std::io;\n\nfn parse() -> u8 {\n    1\n}\n\nstruct Parser;\n\nimpl Parser {\n    fn parse(&self) {}\n}\n";
+        assert_eq!(
+            find_symbol("lib.rs", source, "parse").unwrap(),
+            "fn parse() -> u8 {\n    1\n}\n\n    fn parse(&self) {}\n"
+        );
+        assert_eq!(
+            find_symbol("lib.rs", source, "Parser").unwrap(),
+            "struct Parser;\n"
+        );
+        assert!(find_symbol("lib.rs", source, "io").is_none());
+        assert!(find_symbol("notes.txt", source, "parse").is_none());
+    }
+
+    #[test]
+    fn test_nested_definitions_are_not_repeated() {
+        let source = "class Lexer:\n    def Lexer(self):\n        pass\n";
+        assert_eq!(find_symbol("lex.py", source, "Lexer").unwrap(), source);
+    }
+
+    #[test]
+    fn test_find_arrow_function() {
+        let source = "const a = 1;\nexport const render = (x) => {\n  return x;\n};\n";
+        assert_eq!(
+            find_symbol("view.ts", source, "render").unwrap(),
+            "export const render = (x) => {\n  return x;\n};\n"
+        );
+    }
+}
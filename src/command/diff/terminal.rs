@@ -0,0 +1,110 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::io;
+use std::panic;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crossterm::{
+    cursor::Show,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    ExecutableCommand,
+};
+
+/// Whether the TUI currently owns the terminal, so restoring never writes escape codes to
+/// the output of a non-interactive command
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Puts the terminal in TUI mode and restores it when dropped, including on early returns
+pub struct TerminalGuard;
+
+impl TerminalGuard {
+    pub fn enter() -> io::Result<Self> {
+        watch_signals();
+        resume()?;
+        Ok(TerminalGuard)
+    }
+}
+
+/// Put the terminal back in TUI mode for a guard that's still held, after a nested viewer
+/// restored it on the way out.
+pub fn resume() -> io::Result<()> {
+    enable_raw_mode()?;
+    ACTIVE.store(true, Ordering::SeqCst);
+    io::stdout().execute(EnterAlternateScreen)?;
+    io::stdout().execute(EnableMouseCapture)?;
+    Ok(())
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// Leave the alternate screen, disable raw mode and show the cursor. Does nothing unless
+/// the TUI is active, and is safe to call more than once.
+pub fn restore() {
+    if !ACTIVE.swap(false, Ordering::SeqCst) {
+        return;
+    }
+    // Best effort: there's nowhere to report a failure while tearing down
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(DisableMouseCapture);
+    let _ = stdout.execute(LeaveAlternateScreen);
+    let _ = stdout.execute(Show);
+    let _ = disable_raw_mode();
+}
+
+/// Restore the terminal before reporting a panic, so the message isn't lost on the
+/// alternate screen and the shell isn't left in raw mode
+pub fn install_panic_hook(backtrace: bool) {
+    panic::set_hook(Box::new(move |info| {
+        restore();
+        eprintln!("\x1b[91m\rerror:\x1b[0m lumen crashed: {info}");
+
+        // --backtrace always captures one; otherwise RUST_BACKTRACE decides
+        let trace = if backtrace {
+            Backtrace::force_capture()
+        } else {
+            Backtrace::capture()
+        };
+        if trace.status() == BacktraceStatus::Captured {
+            eprintln!("{trace}");
+        } else {
+            eprintln!("note: run with --backtrace to see where it happened");
+        }
+    }));
+}
+
+/// Restore the terminal and exit when the process is terminated or its terminal hangs up.
+/// Ctrl+C arrives as a key event in raw mode, so SIGINT only matters outside of it.
+fn watch_signals() {
+    #[cfg(unix)]
+    {
+        use std::sync::Once;
+        use tokio::signal::unix::{signal, SignalKind};
+
+        static SIGNALS: Once = Once::new();
+        SIGNALS.call_once(|| {
+            // The TUI is driven synchronously, so this needs the caller's runtime to run on
+            let Ok(handle) = tokio::runtime::Handle::try_current() else {
+                return;
+            };
+            for (kind, code) in [
+                (SignalKind::interrupt(), 130),
+                (SignalKind::hangup(), 129),
+                (SignalKind::terminate(), 143),
+            ] {
+                handle.spawn(async move {
+                    let Ok(mut stream) = signal(kind) else {
+                        return;
+                    };
+                    if stream.recv().await.is_some() {
+                        restore();
+                        std::process::exit(code);
+                    }
+                });
+            }
+        });
+    }
+}
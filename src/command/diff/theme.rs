@@ -1,17 +1,46 @@
-use once_cell::sync::OnceCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+use once_cell::sync::Lazy;
 use ratatui::prelude::Color;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+use super::background;
+use crate::config::configuration::toml_to_json;
 
-static THEME: OnceCell<Theme> = OnceCell::new();
+static DARK: Lazy<Theme> = Lazy::new(Theme::dark);
+static LIGHT: Lazy<Theme> = Lazy::new(Theme::light);
+/// Switchable at runtime from the settings panel
+static LIGHT_MODE: AtomicBool = AtomicBool::new(false);
+/// A theme file or named built-in theme, used in place of the default theme of its mode
+static CUSTOM: OnceLock<Theme> = OnceLock::new();
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
 pub enum ThemeMode {
     Dark,
     Light,
 }
 
 impl ThemeMode {
+    /// The mode that suits the terminal: `LUMEN_THEME` when it's `dark` or `light`, else the
+    /// terminal's background, else dark. The terminal is only asked once per run.
     pub fn detect() -> Self {
-        ThemeMode::Dark
+        static DETECTED: OnceLock<ThemeMode> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            let forced = std::env::var("LUMEN_THEME")
+                .ok()
+                .and_then(|name| <ThemeMode as ValueEnum>::from_str(&name, true).ok());
+            forced.unwrap_or(match background::is_light() {
+                Some(true) => ThemeMode::Light,
+                _ => ThemeMode::Dark,
+            })
+        })
     }
 }
 
@@ -43,6 +72,8 @@ pub struct DiffColors {
     pub deleted_bg: Color,
     pub deleted_gutter_bg: Color,
     pub deleted_gutter_fg: Color,
+    /// Both ends of a definition that was moved rather than changed
+    pub moved_bg: Color,
     pub context_bg: Color,
     pub empty_placeholder_fg: Color,
 }
@@ -68,6 +99,9 @@ pub struct UiColors {
     pub highlight: Color,
     pub viewed: Color,
     pub watching: Color,
+    pub risk_low: Color,
+    pub risk_medium: Color,
+    pub risk_high: Color,
     pub search_match_bg: Color,
     pub search_match_fg: Color,
     pub search_current_bg: Color,
@@ -76,7 +110,6 @@ pub struct UiColors {
 
 #[derive(Debug, Clone)]
 pub struct Theme {
-    #[allow(dead_code)]
     pub mode: ThemeMode,
     pub syntax: SyntaxColors,
     pub diff: DiffColors,
@@ -112,6 +145,7 @@ impl Theme {
                 deleted_bg: Color::Rgb(60, 30, 30),
                 deleted_gutter_bg: Color::Rgb(60, 30, 30),
                 deleted_gutter_fg: Color::DarkGray,
+                moved_bg: Color::Rgb(35, 45, 70),
                 context_bg: Color::Rgb(40, 40, 50),
                 empty_placeholder_fg: Color::DarkGray,
             },
@@ -135,6 +169,9 @@ impl Theme {
                 highlight: Color::Yellow,
                 viewed: Color::Green,
                 watching: Color::Yellow,
+                risk_low: Color::DarkGray,
+                risk_medium: Color::Rgb(230, 180, 60),
+                risk_high: Color::Rgb(240, 80, 80),
                 search_match_bg: Color::Rgb(100, 80, 20),
                 search_match_fg: Color::Rgb(255, 220, 120),
                 search_current_bg: Color::Rgb(255, 165, 0),
@@ -171,6 +208,7 @@ impl Theme {
                 deleted_bg: Color::Rgb(255, 245, 243),
                 deleted_gutter_bg: Color::Rgb(255, 210, 205),
                 deleted_gutter_fg: Color::Rgb(140, 60, 60),
+                moved_bg: Color::Rgb(225, 232, 250),
                 context_bg: Color::Rgb(246, 248, 250),
                 empty_placeholder_fg: Color::Rgb(200, 205, 212),
             },
@@ -194,6 +232,9 @@ impl Theme {
                 highlight: Color::Rgb(154, 103, 0),
                 viewed: Color::Rgb(26, 127, 55),
                 watching: Color::Rgb(154, 103, 0),
+                risk_low: Color::Rgb(175, 184, 193),
+                risk_medium: Color::Rgb(191, 135, 0),
+                risk_high: Color::Rgb(207, 34, 46),
                 search_match_bg: Color::Rgb(255, 235, 150),
                 search_match_fg: Color::Black,
                 search_current_bg: Color::Rgb(255, 140, 0),
@@ -201,20 +242,498 @@ impl Theme {
             },
         }
     }
+}
+
+/// Names of the built-in themes, for `diff.custom_theme`, `--theme` and a theme file's `base`
+pub const BUILTIN_THEMES: [&str; 8] = [
+    "dark",
+    "light",
+    "solarized-dark",
+    "solarized-light",
+    "gruvbox-dark",
+    "gruvbox-light",
+    "catppuccin-mocha",
+    "catppuccin-latte",
+];
+
+const fn hex(rgb: u32) -> Color {
+    Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+}
+
+/// The handful of colors a built-in theme is made of, each filling in several slots
+struct Palette {
+    mode: ThemeMode,
+    /// Context lines and the footer
+    background: Color,
+    /// The footer's branch, search matches
+    surface: Color,
+    /// Line numbers, gutters and unfocused borders
+    overlay: Color,
+    comment: Color,
+    text: Color,
+    subtext: Color,
+    red: Color,
+    orange: Color,
+    yellow: Color,
+    green: Color,
+    cyan: Color,
+    blue: Color,
+    purple: Color,
+    added_bg: Color,
+    deleted_bg: Color,
+}
+
+impl Palette {
+    fn theme(&self) -> Theme {
+        Theme {
+            mode: self.mode,
+            syntax: SyntaxColors {
+                comment: self.comment,
+                keyword: self.purple,
+                string: self.green,
+                number: self.orange,
+                function: self.blue,
+                function_macro: self.cyan,
+                r#type: self.yellow,
+                variable_builtin: self.red,
+                variable_member: self.cyan,
+                module: self.yellow,
+                operator: self.cyan,
+                tag: self.red,
+                attribute: self.yellow,
+                label: self.orange,
+                punctuation: self.subtext,
+                default_text: self.text,
+            },
+            diff: DiffColors {
+                added_bg: self.added_bg,
+                added_gutter_bg: self.added_bg,
+                added_gutter_fg: self.overlay,
+                deleted_bg: self.deleted_bg,
+                deleted_gutter_bg: self.deleted_bg,
+                deleted_gutter_fg: self.overlay,
+                moved_bg: self.surface,
+                context_bg: self.background,
+                empty_placeholder_fg: self.overlay,
+            },
+            ui: UiColors {
+                border_focused: self.blue,
+                border_unfocused: self.overlay,
+                text_primary: self.text,
+                text_secondary: self.subtext,
+                text_muted: self.comment,
+                line_number: self.overlay,
+                footer_bg: self.background,
+                footer_branch_bg: self.surface,
+                footer_branch_fg: self.blue,
+                status_added: self.green,
+                status_modified: self.yellow,
+                status_deleted: self.red,
+                stats_added: self.green,
+                stats_removed: self.red,
+                selection_bg: self.blue,
+                selection_fg: self.background,
+                highlight: self.yellow,
+                viewed: self.green,
+                watching: self.yellow,
+                risk_low: self.overlay,
+                risk_medium: self.orange,
+                risk_high: self.red,
+                search_match_bg: self.surface,
+                search_match_fg: self.yellow,
+                search_current_bg: self.orange,
+                search_current_fg: self.background,
+            },
+        }
+    }
+}
+
+const SOLARIZED_DARK: Palette = Palette {
+    mode: ThemeMode::Dark,
+    background: hex(0x002b36),
+    surface: hex(0x073642),
+    overlay: hex(0x586e75),
+    comment: hex(0x657b83),
+    text: hex(0x93a1a1),
+    subtext: hex(0x839496),
+    red: hex(0xdc322f),
+    orange: hex(0xcb4b16),
+    yellow: hex(0xb58900),
+    green: hex(0x859900),
+    cyan: hex(0x2aa198),
+    blue: hex(0x268bd2),
+    purple: hex(0x6c71c4),
+    added_bg: hex(0x0b3b2a),
+    deleted_bg: hex(0x3b1f2b),
+};
+
+const SOLARIZED_LIGHT: Palette = Palette {
+    mode: ThemeMode::Light,
+    background: hex(0xfdf6e3),
+    surface: hex(0xeee8d5),
+    overlay: hex(0x93a1a1),
+    comment: hex(0x839496),
+    text: hex(0x586e75),
+    subtext: hex(0x657b83),
+    added_bg: hex(0xe4ecc8),
+    deleted_bg: hex(0xf6dcd0),
+    ..SOLARIZED_DARK
+};
+
+const GRUVBOX_DARK: Palette = Palette {
+    mode: ThemeMode::Dark,
+    background: hex(0x282828),
+    surface: hex(0x3c3836),
+    overlay: hex(0x665c54),
+    comment: hex(0x928374),
+    text: hex(0xebdbb2),
+    subtext: hex(0xd5c4a1),
+    red: hex(0xfb4934),
+    orange: hex(0xfe8019),
+    yellow: hex(0xfabd2f),
+    green: hex(0xb8bb26),
+    cyan: hex(0x8ec07c),
+    blue: hex(0x83a598),
+    purple: hex(0xd3869b),
+    added_bg: hex(0x32361a),
+    deleted_bg: hex(0x3c1f1e),
+};
+
+const GRUVBOX_LIGHT: Palette = Palette {
+    mode: ThemeMode::Light,
+    background: hex(0xfbf1c7),
+    surface: hex(0xebdbb2),
+    overlay: hex(0xa89984),
+    comment: hex(0x928374),
+    text: hex(0x3c3836),
+    subtext: hex(0x504945),
+    red: hex(0x9d0006),
+    orange: hex(0xaf3a03),
+    yellow: hex(0xb57614),
+    green: hex(0x79740e),
+    cyan: hex(0x427b58),
+    blue: hex(0x076678),
+    purple: hex(0x8f3f71),
+    added_bg: hex(0xe3e6b8),
+    deleted_bg: hex(0xf5d3c4),
+};
+
+const CATPPUCCIN_MOCHA: Palette = Palette {
+    mode: ThemeMode::Dark,
+    background: hex(0x181825),
+    surface: hex(0x313244),
+    overlay: hex(0x6c7086),
+    comment: hex(0x7f849c),
+    text: hex(0xcdd6f4),
+    subtext: hex(0xbac2de),
+    red: hex(0xf38ba8),
+    orange: hex(0xfab387),
+    yellow: hex(0xf9e2af),
+    green: hex(0xa6e3a1),
+    cyan: hex(0x94e2d5),
+    blue: hex(0x89b4fa),
+    purple: hex(0xcba6f7),
+    added_bg: hex(0x2b3a2f),
+    deleted_bg: hex(0x3d2a35),
+};
+
+const CATPPUCCIN_LATTE: Palette = Palette {
+    mode: ThemeMode::Light,
+    background: hex(0xe6e9ef),
+    surface: hex(0xccd0da),
+    overlay: hex(0x9ca0b0),
+    comment: hex(0x8c8fa1),
+    text: hex(0x4c4f69),
+    subtext: hex(0x5c5f77),
+    red: hex(0xd20f39),
+    orange: hex(0xfe640b),
+    yellow: hex(0xdf8e1d),
+    green: hex(0x40a02b),
+    cyan: hex(0x179299),
+    blue: hex(0x1e66f5),
+    purple: hex(0x8839ef),
+    added_bg: hex(0xd5ecd0),
+    deleted_bg: hex(0xf4d2da),
+};
+
+/// A color of a theme, by the section and name it has in a theme file
+pub struct Slot {
+    pub section: &'static str,
+    name: &'static str,
+    pub get: fn(&Theme) -> Color,
+    pub set: fn(&mut Theme, Color),
+}
 
-    pub fn from_mode(mode: ThemeMode) -> Self {
+impl Slot {
+    pub fn name(&self) -> &'static str {
+        self.name.trim_start_matches("r#")
+    }
+}
+
+macro_rules! slots {
+    ($($section:ident . $name:ident),* $(,)?) => {
+        &[$(Slot {
+            section: stringify!($section),
+            name: stringify!($name),
+            get: |theme| theme.$section.$name,
+            set: |theme, color| theme.$section.$name = color,
+        }),*]
+    };
+}
+
+/// Every color of a theme, in the order `lumen theme edit` lists them
+pub const SLOTS: &[Slot] = slots![
+    syntax.comment,
+    syntax.keyword,
+    syntax.string,
+    syntax.number,
+    syntax.function,
+    syntax.function_macro,
+    syntax.r#type,
+    syntax.variable_builtin,
+    syntax.variable_member,
+    syntax.module,
+    syntax.operator,
+    syntax.tag,
+    syntax.attribute,
+    syntax.label,
+    syntax.punctuation,
+    syntax.default_text,
+    diff.added_bg,
+    diff.added_gutter_bg,
+    diff.added_gutter_fg,
+    diff.deleted_bg,
+    diff.deleted_gutter_bg,
+    diff.deleted_gutter_fg,
+    diff.moved_bg,
+    diff.context_bg,
+    diff.empty_placeholder_fg,
+    ui.border_focused,
+    ui.border_unfocused,
+    ui.text_primary,
+    ui.text_secondary,
+    ui.text_muted,
+    ui.line_number,
+    ui.footer_bg,
+    ui.footer_branch_bg,
+    ui.footer_branch_fg,
+    ui.status_added,
+    ui.status_modified,
+    ui.status_deleted,
+    ui.stats_added,
+    ui.stats_removed,
+    ui.selection_bg,
+    ui.selection_fg,
+    ui.highlight,
+    ui.viewed,
+    ui.watching,
+    ui.risk_low,
+    ui.risk_medium,
+    ui.risk_high,
+    ui.search_match_bg,
+    ui.search_match_fg,
+    ui.search_current_bg,
+    ui.search_current_fg,
+];
+
+impl Theme {
+    pub fn for_mode(mode: ThemeMode) -> Self {
         match mode {
             ThemeMode::Dark => Self::dark(),
             ThemeMode::Light => Self::light(),
         }
     }
+
+    /// A theme file: the built-in theme it's based on, and every color as `#rrggbb` or a
+    /// color name, by section.
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({ "base": self.mode });
+        for slot in SLOTS {
+            value[slot.section][slot.name()] = json!((slot.get)(self).to_string());
+        }
+        value
+    }
+
+    /// Read a theme file. Colors it leaves out keep the base theme's, which can be any
+    /// built-in theme.
+    pub fn from_json(value: &Value) -> Result<Self, String> {
+        let mut theme = match value.get("base") {
+            Some(base) => base.as_str().and_then(Self::builtin).ok_or_else(|| {
+                format!(
+                    "unknown base theme {base}, expected one of: {}",
+                    BUILTIN_THEMES.join(", ")
+                )
+            })?,
+            None => Self::dark(),
+        };
+        let empty = Map::new();
+        for (section, colors) in value.as_object().unwrap_or(&empty) {
+            let Some(colors) = colors.as_object() else {
+                continue;
+            };
+            for (name, color) in colors {
+                let slot = SLOTS
+                    .iter()
+                    .find(|slot| slot.section == section && slot.name() == name)
+                    .ok_or_else(|| format!("unknown color {section}.{name}"))?;
+                let color = color
+                    .as_str()
+                    .and_then(|color| Color::from_str(color).ok())
+                    .ok_or_else(|| format!("invalid color for {section}.{name}: {color}"))?;
+                (slot.set)(&mut theme, color);
+            }
+        }
+        Ok(theme)
+    }
+
+    /// The built-in theme `name`, one of [`BUILTIN_THEMES`].
+    pub fn builtin(name: &str) -> Option<Self> {
+        let palette = match name {
+            "dark" => return Some(Self::dark()),
+            "light" => return Some(Self::light()),
+            "solarized-dark" => &SOLARIZED_DARK,
+            "solarized-light" => &SOLARIZED_LIGHT,
+            "gruvbox-dark" => &GRUVBOX_DARK,
+            "gruvbox-light" => &GRUVBOX_LIGHT,
+            "catppuccin-mocha" => &CATPPUCCIN_MOCHA,
+            "catppuccin-latte" => &CATPPUCCIN_LATTE,
+            _ => return None,
+        };
+        Some(palette.theme())
+    }
+
+    /// Load a theme by name from the themes directory, or from a path to its file. A name
+    /// with no file falls back to the built-in theme of that name.
+    pub fn load(name: &str) -> Result<Self, String> {
+        let path = theme_path(name).ok_or("no home directory to find themes in")?;
+        if !path.exists() {
+            if let Some(theme) = Self::builtin(name) {
+                return Ok(theme);
+            }
+        }
+        let text = fs::read_to_string(&path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let value = if is_toml(&path) {
+            toml_to_json(&text)
+        } else {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        };
+        value
+            .and_then(|value| Self::from_json(&value))
+            .map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "toml")
+}
+
+/// Where themes from `lumen theme edit` are kept
+fn themes_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("lumen").join("themes"))
+}
+
+/// File of the theme `name`: `<themes dir>/<name>.json`, or `<name>.toml` when only that
+/// exists, or `name` itself when it's a path.
+pub fn theme_path(name: &str) -> Option<PathBuf> {
+    if name.contains('/') || name.ends_with(".json") || name.ends_with(".toml") {
+        return Some(PathBuf::from(name));
+    }
+    let dir = themes_dir()?;
+    let toml = dir.join(format!("{name}.toml"));
+    let json = dir.join(format!("{name}.json"));
+    Some(if toml.exists() && !json.exists() {
+        toml
+    } else {
+        json
+    })
+}
+
+/// Use the theme `name` in place of the default theme of its mode, and return that mode. Only
+/// the first custom theme of a run is used.
+pub fn use_custom(name: &str) -> Result<ThemeMode, String> {
+    let theme = Theme::load(name)?;
+    let mode = theme.mode;
+    let _ = CUSTOM.set(theme);
+    Ok(CUSTOM.get().map_or(mode, |theme| theme.mode))
 }
 
-pub fn init() {
-    let mode = ThemeMode::detect();
-    let _ = THEME.set(Theme::from_mode(mode));
+pub fn set_mode(mode: ThemeMode) {
+    LIGHT_MODE.store(mode == ThemeMode::Light, Ordering::Relaxed);
 }
 
 pub fn get() -> &'static Theme {
-    THEME.get_or_init(|| Theme::from_mode(ThemeMode::detect()))
+    let light = LIGHT_MODE.load(Ordering::Relaxed);
+    let mode = if light {
+        ThemeMode::Light
+    } else {
+        ThemeMode::Dark
+    };
+    match CUSTOM.get() {
+        Some(custom) if custom.mode == mode => custom,
+        _ if light => &LIGHT,
+        _ => &DARK,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_file_round_trips() {
+        let mut theme = Theme::light();
+        theme.ui.highlight = Color::Rgb(1, 2, 3);
+        let value = theme.to_json();
+        assert_eq!(value["base"], "light");
+        assert_eq!(value["ui"]["highlight"], "#010203");
+        assert_eq!(value["syntax"]["type"], "#953800");
+
+        let loaded = Theme::from_json(&value).unwrap();
+        assert_eq!(loaded.to_json(), value);
+    }
+
+    #[test]
+    fn test_theme_file_overrides_base() {
+        let theme = Theme::from_json(&json!({"ui": {"selection_bg": "magenta"}})).unwrap();
+        assert_eq!(theme.mode, ThemeMode::Dark);
+        assert_eq!(theme.ui.selection_bg, Color::Magenta);
+        assert_eq!(theme.ui.highlight, Theme::dark().ui.highlight);
+
+        assert!(Theme::from_json(&json!({"ui": {"nope": "red"}})).is_err());
+        assert!(Theme::from_json(&json!({"ui": {"highlight": "#12"}})).is_err());
+        assert!(Theme::from_json(&json!({"base": "sepia"})).is_err());
+    }
+
+    #[test]
+    fn test_builtin_themes() {
+        for name in BUILTIN_THEMES {
+            let theme = Theme::builtin(name).unwrap();
+            let light = name.ends_with("light") || name.ends_with("latte");
+            assert_eq!(theme.mode == ThemeMode::Light, light, "{name}");
+            assert_eq!(Theme::load(name).unwrap().to_json(), theme.to_json());
+        }
+        assert!(Theme::builtin("sepia").is_none());
+        assert!(Theme::load("sepia").is_err());
+
+        let theme = Theme::from_json(&json!({"base": "gruvbox-light"})).unwrap();
+        assert_eq!(theme.mode, ThemeMode::Light);
+        assert_eq!(theme.ui.status_deleted, Color::Rgb(0x9d, 0x00, 0x06));
+    }
+
+    #[test]
+    fn test_toml_theme_file() {
+        let path = std::env::temp_dir().join(format!("lumen-theme-{}.toml", std::process::id()));
+        fs::write(
+            &path,
+            "base = \"catppuccin-mocha\"\n\n[ui]\nhighlight = \"#010203\"\n",
+        )
+        .unwrap();
+        let theme = Theme::load(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+
+        let theme = theme.unwrap();
+        assert_eq!(theme.mode, ThemeMode::Dark);
+        assert_eq!(theme.ui.highlight, Color::Rgb(1, 2, 3));
+        assert_eq!(theme.syntax.keyword, CATPPUCCIN_MOCHA.purple);
+    }
 }
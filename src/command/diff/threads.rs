@@ -0,0 +1,207 @@
+//! Review comment threads on a PR, shown under the lines they were left on.
+//!
+//! Comments come from the REST API and are grouped into threads by `in_reply_to_id`. Only
+//! threads on the PR's head version can be placed, so comments on removed lines and on
+//! outdated code are counted but not shown.
+
+use std::collections::HashMap;
+use std::process::Command;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+use serde_json::Value;
+
+use super::risk::RiskLevel;
+use super::types::{FileDiff, LineNote};
+use super::PrInfo;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadComment {
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReviewThread {
+    pub path: String,
+    /// 1-based line of the PR's head version
+    pub line: usize,
+    /// The first comment, then the replies, oldest first
+    pub comments: Vec<ThreadComment>,
+}
+
+/// Threads that can be shown, and how many couldn't be placed
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReviewThreads {
+    pub threads: Vec<ReviewThread>,
+    pub unplaced: usize,
+}
+
+/// The PR's review comments as the JSON array `gh api` returns.
+pub fn fetch_review_comments(pr: &PrInfo) -> Result<String, String> {
+    let endpoint = format!(
+        "repos/{}/{}/pulls/{}/comments?per_page=100",
+        pr.repo_owner, pr.repo_name, pr.number
+    );
+    let output = Command::new("gh")
+        .args(["api", &endpoint])
+        .output()
+        .map_err(|e| format!("Failed to run gh api: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("gh api failed: {}", stderr.trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Load the PR's comment threads in the background.
+pub fn load_threads_async(pr: &PrInfo) -> Receiver<Result<ReviewThreads, String>> {
+    let pr = pr.clone();
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(fetch_review_comments(&pr).and_then(|json| parse_threads(&json)));
+    });
+    rx
+}
+
+fn parse_threads(json: &str) -> Result<ReviewThreads, String> {
+    let value: Value = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    let comments = value.as_array().map(Vec::as_slice).unwrap_or_default();
+
+    let mut result = ReviewThreads::default();
+    // For each top-level comment, the thread it started (if it could be placed), found by
+    // its id when a reply comes along
+    let mut by_id: HashMap<u64, usize> = HashMap::new();
+    let mut placed: Vec<Option<usize>> = Vec::new();
+    for comment in comments {
+        let text = ThreadComment {
+            author: comment["user"]["login"]
+                .as_str()
+                .unwrap_or("ghost")
+                .to_string(),
+            body: comment["body"]
+                .as_str()
+                .unwrap_or_default()
+                .replace("\r\n", "\n"),
+        };
+        if let Some(parent) = comment["in_reply_to_id"].as_u64() {
+            if let Some(Some(index)) = by_id.get(&parent).map(|&i| placed[i]) {
+                result.threads[index].comments.push(text);
+            }
+            continue;
+        }
+
+        let line = comment["line"].as_u64().filter(|&line| line > 0);
+        let path = comment["path"].as_str();
+        let index = match (path, line) {
+            (Some(path), Some(line)) if comment["side"].as_str() != Some("LEFT") => {
+                result.threads.push(ReviewThread {
+                    path: path.to_string(),
+                    line: line as usize,
+                    comments: vec![text],
+                });
+                Some(result.threads.len() - 1)
+            }
+            _ => {
+                result.unplaced += 1;
+                None
+            }
+        };
+        if let Some(id) = comment["id"].as_u64() {
+            by_id.insert(id, placed.len());
+        }
+        placed.push(index);
+    }
+    Ok(result)
+}
+
+impl ReviewThreads {
+    /// Keep only threads on files in the diff, ordered by file and line, for jumping
+    /// between them.
+    pub fn sort_for(&mut self, file_diffs: &[FileDiff]) {
+        let file_index = |path: &str| file_diffs.iter().position(|d| d.filename == path);
+        let before = self.threads.len();
+        self.threads
+            .retain(|thread| file_index(&thread.path).is_some());
+        self.unplaced += before - self.threads.len();
+        self.threads
+            .sort_by_key(|thread| (file_index(&thread.path), thread.line));
+    }
+
+    /// The threads as notes under their lines: the first comment's opening line and a reply
+    /// count, or with `expanded`, every comment in full.
+    pub fn notes(&self, expanded: bool) -> Vec<LineNote> {
+        self.threads
+            .iter()
+            .map(|thread| {
+                let first = &thread.comments[0];
+                let headline = format!(
+                    "{}: {}",
+                    first.author,
+                    first.body.lines().next().unwrap_or_default()
+                );
+                let replies = thread.comments.len() - 1;
+                let mut text = headline;
+                if expanded {
+                    for line in first.body.lines().skip(1) {
+                        text.push_str(&format!("\n{line}"));
+                    }
+                    for reply in &thread.comments[1..] {
+                        let mut lines = reply.body.lines();
+                        let opening = lines.next().unwrap_or_default();
+                        text.push_str(&format!("\n↳ {}: {opening}", reply.author));
+                        for line in lines {
+                            text.push_str(&format!("\n  {line}"));
+                        }
+                    }
+                } else if replies > 0 || first.body.lines().count() > 1 {
+                    let replies = match replies {
+                        0 => String::new(),
+                        1 => "1 reply · ".to_string(),
+                        n => format!("{n} replies · "),
+                    };
+                    text.push_str(&format!("\n{replies}z to expand"));
+                }
+                LineNote {
+                    file: thread.path.clone(),
+                    line: thread.line,
+                    level: RiskLevel::Low,
+                    text,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_threads_groups_replies() {
+        let json = r#"[
+            {"id": 1, "path": "src/a.rs", "line": 12, "side": "RIGHT", "body": "Why unwrap?", "user": {"login": "alice"}},
+            {"id": 2, "path": "src/a.rs", "line": 3, "side": "LEFT", "body": "Keep this", "user": {"login": "bob"}},
+            {"id": 3, "path": "src/a.rs", "line": 12, "in_reply_to_id": 1, "body": "It can't fail", "user": {"login": "carol"}},
+            {"id": 4, "path": "src/b.rs", "line": null, "body": "Outdated", "user": {"login": "bob"}},
+            {"id": 5, "in_reply_to_id": 2, "body": "Agreed", "user": {"login": "alice"}}
+        ]"#;
+        let threads = parse_threads(json).unwrap();
+        assert_eq!(threads.unplaced, 2);
+        assert_eq!(threads.threads.len(), 1);
+        assert_eq!(threads.threads[0].line, 12);
+        assert_eq!(threads.threads[0].comments.len(), 2);
+
+        let collapsed = threads.notes(false);
+        assert_eq!(
+            collapsed[0].text,
+            "alice: Why unwrap?\n1 reply · z to expand"
+        );
+        let expanded = threads.notes(true);
+        assert_eq!(
+            expanded[0].text,
+            "alice: Why unwrap?\n↳ carol: It can't fail"
+        );
+    }
+}
@@ -1,31 +1,45 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
 use super::context::ContextConfig;
+use super::risk::RiskLevel;
+use super::theme::ThemeMode;
+use crate::config::configuration::DiffConfig;
+pub use lumen_core::side_by_side::{expand_tabs, ChangeType, DiffLine};
+
+/// A note pinned below a line of the new version, such as a review finding
+#[derive(Clone, Debug)]
+pub struct LineNote {
+    pub file: String,
+    pub line: usize,
+    pub level: RiskLevel,
+    /// First line is the headline, the rest is shown dimmed under it
+    pub text: String,
+}
 
-pub fn expand_tabs(s: &str, tab_width: usize) -> String {
-    if tab_width == 0 {
-        return s.replace('\t', "");
-    }
-    let mut result = String::with_capacity(s.len());
-    let mut col = 0;
-    for c in s.chars() {
-        if c == '\t' {
-            let spaces = tab_width - (col % tab_width);
-            for _ in 0..spaces {
-                result.push(' ');
-            }
-            col += spaces;
-        } else {
-            result.push(c);
-            col += 1;
-        }
-    }
-    result
+/// Where to open the viewer, such as the line a permalink points at
+#[derive(Clone, Debug)]
+pub struct Focus {
+    pub file: String,
+    /// Line of the new version; the file's first hunk when `None`
+    pub line: Option<usize>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum FileStatus {
     Added,
     Modified,
     Deleted,
+    /// Moved from `from`, possibly with changes. The old content is read from `from`.
+    Renamed {
+        from: String,
+        to: String,
+    },
+    /// A new file that started as a copy of `from`, which is left as it was
+    Copied {
+        from: String,
+        to: String,
+    },
 }
 
 impl FileStatus {
@@ -34,15 +48,152 @@ impl FileStatus {
             FileStatus::Added => "A",
             FileStatus::Modified => "M",
             FileStatus::Deleted => "D",
+            FileStatus::Renamed { .. } => "R",
+            FileStatus::Copied { .. } => "C",
+        }
+    }
+
+    /// Path of the file on the old side of the diff, `None` when it's the same as the new side.
+    pub fn source(&self) -> Option<&str> {
+        match self {
+            FileStatus::Renamed { from, .. } | FileStatus::Copied { from, .. } => Some(from),
+            _ => None,
         }
     }
 }
 
+#[derive(Clone)]
 pub struct FileDiff {
     pub filename: String,
     pub old_content: String,
     pub new_content: String,
     pub status: FileStatus,
+    /// Set for binary files, whose contents are left empty
+    pub binary: Option<BinaryChange>,
+    /// Set for submodules, which have no contents, only the commit they point at
+    pub submodule: Option<SubmoduleChange>,
+}
+
+impl FileDiff {
+    /// Lines in the longer of the two versions
+    pub fn line_count(&self) -> usize {
+        self.old_content
+            .lines()
+            .count()
+            .max(self.new_content.lines().count())
+    }
+}
+
+/// What's known about a changed binary file, shown in place of its lines
+#[derive(Clone, Debug, PartialEq)]
+pub struct BinaryChange {
+    /// Sizes in bytes, `None` on a side the file doesn't exist on or when unknown
+    pub old_size: Option<usize>,
+    pub new_size: Option<usize>,
+    /// The newest version of a PNG, for terminals that can draw images
+    pub preview: Option<Vec<u8>>,
+}
+
+impl BinaryChange {
+    pub fn new(filename: &str, old: Option<&[u8]>, new: Option<&[u8]>) -> Self {
+        let is_png = Path::new(filename)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("png"));
+        Self {
+            old_size: old.map(<[u8]>::len),
+            new_size: new.map(<[u8]>::len),
+            preview: new.or(old).filter(|_| is_png).map(<[u8]>::to_vec),
+        }
+    }
+
+    /// A binary file in a patch, which only says that it changed
+    pub fn unknown() -> Self {
+        Self {
+            old_size: None,
+            new_size: None,
+            preview: None,
+        }
+    }
+
+    pub fn describe(&self) -> String {
+        match (self.old_size, self.new_size) {
+            (Some(old), Some(new)) => format!(
+                "binary file changed (size {} → {})",
+                format_size(old),
+                format_size(new)
+            ),
+            (None, Some(new)) => format!("binary file added ({})", format_size(new)),
+            (Some(old), None) => format!("binary file deleted ({})", format_size(old)),
+            (None, None) => "binary file changed".to_string(),
+        }
+    }
+}
+
+/// A submodule whose recorded commit changed, shown in place of lines
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubmoduleChange {
+    /// The commit recorded on each side, `None` on a side the submodule isn't on
+    pub old: Option<SubmoduleCommit>,
+    pub new: Option<SubmoduleCommit>,
+    /// Commits only the old side has and only the new side has, known when the submodule
+    /// is checked out with both
+    pub behind_ahead: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubmoduleCommit {
+    pub sha: String,
+    /// Read from the submodule's checkout, when it has the commit
+    pub subject: Option<String>,
+}
+
+impl SubmoduleChange {
+    /// What happened, then a line for each side like `- 6df2f32 Add parser`
+    pub fn describe(&self) -> Vec<String> {
+        let count = |n: usize| format!("{n} commit{}", if n == 1 { "" } else { "s" });
+        let heading = match (&self.old, &self.new, self.behind_ahead) {
+            (None, _, _) => "submodule added".to_string(),
+            (_, None, _) => "submodule removed".to_string(),
+            (_, _, Some((0, ahead))) => format!("submodule moved forward {}", count(ahead)),
+            (_, _, Some((behind, 0))) => format!("submodule moved back {}", count(behind)),
+            (_, _, Some((behind, ahead))) => format!(
+                "submodule moved to another branch ({} dropped, {} added)",
+                count(behind),
+                count(ahead)
+            ),
+            _ => "submodule changed".to_string(),
+        };
+        let side = |sign: char, commit: &SubmoduleCommit| {
+            let short = &commit.sha[..commit.sha.len().min(7)];
+            match &commit.subject {
+                Some(subject) => format!("{sign} {short} {subject}"),
+                None => format!("{sign} {short}"),
+            }
+        };
+        let mut lines = vec![heading];
+        lines.extend(self.old.iter().map(|commit| side('-', commit)));
+        lines.extend(self.new.iter().map(|commit| side('+', commit)));
+        lines
+    }
+
+    /// The old and new commits, when the submodule's checkout has both to diff
+    pub fn range(&self) -> Option<(&str, &str)> {
+        self.behind_ahead?;
+        Some((&self.old.as_ref()?.sha, &self.new.as_ref()?.sha))
+    }
+}
+
+/// `512 B`, `1.5 KB` or `2.0 MB`
+fn format_size(bytes: usize) -> String {
+    const KB: f64 = 1024.0;
+    let size = bytes as f64;
+    if size < KB {
+        format!("{bytes} B")
+    } else if size < KB * KB {
+        format!("{:.1} KB", size / KB)
+    } else {
+        format!("{:.1} MB", size / (KB * KB))
+    }
 }
 
 /// Settings for the diff view UI. Designed to be easily extended
@@ -51,6 +202,29 @@ pub struct FileDiff {
 pub struct DiffViewSettings {
     pub context: ContextConfig,
     pub tab_width: usize,
+    /// Tab widths by file extension or file name, taking precedence over `tab_width`
+    pub tab_widths: HashMap<String, usize>,
+    /// Marking a file viewed from the sidebar also advances to the next unviewed file
+    pub review_flow: bool,
+    /// Disables every action that changes the repository or the PR
+    pub read_only: bool,
+    /// Lines that differ only in whitespace are shown as unchanged
+    pub ignore_whitespace: bool,
+    pub theme: ThemeMode,
+    /// Theme from `lumen theme edit` that replaces the built-in theme of its mode
+    pub custom_theme: Option<String>,
+    /// Fixed sidebar width; `None` sizes it to the terminal
+    pub sidebar_width: Option<u16>,
+    /// Wrap long lines instead of scrolling horizontally
+    pub wrap: bool,
+    /// Mark each indentation level of a line with a faint guide
+    pub indent_guides: bool,
+    /// Interleave old and new lines in one panel instead of showing them side by side
+    pub unified: bool,
+    /// Set apart definitions that were moved or reindented rather than changed
+    pub detect_moves: bool,
+    /// Files with more lines than this are only diffed on request, and never highlighted
+    pub large_file_lines: usize,
 }
 
 impl Default for DiffViewSettings {
@@ -58,37 +232,106 @@ impl Default for DiffViewSettings {
         Self {
             context: ContextConfig::default(),
             tab_width: 4,
+            review_flow: false,
+            read_only: false,
+            ignore_whitespace: false,
+            // Detected in `from_config`, so plain defaults never query the terminal
+            theme: ThemeMode::Dark,
+            custom_theme: None,
+            sidebar_width: None,
+            wrap: false,
+            tab_widths: HashMap::new(),
+            indent_guides: false,
+            unified: false,
+            detect_moves: false,
+            large_file_lines: 10_000,
         }
     }
 }
 
-pub struct DiffLine {
-    pub old_line: Option<(usize, String)>,
-    pub new_line: Option<(usize, String)>,
-    pub change_type: ChangeType,
-}
+impl DiffViewSettings {
+    pub fn from_config(config: &DiffConfig) -> Self {
+        let defaults = Self::default();
+        let context = match config.context_lines {
+            Some(0) => ContextConfig {
+                enabled: false,
+                ..defaults.context
+            },
+            Some(max_lines) => ContextConfig {
+                enabled: true,
+                max_lines,
+            },
+            None => defaults.context,
+        };
+        Self {
+            context,
+            tab_width: config.tab_width.unwrap_or(defaults.tab_width),
+            ignore_whitespace: config
+                .ignore_whitespace
+                .unwrap_or(defaults.ignore_whitespace),
+            theme: config.theme.unwrap_or_else(ThemeMode::detect),
+            custom_theme: config.custom_theme.clone(),
+            sidebar_width: config.sidebar_width.or(defaults.sidebar_width),
+            wrap: config.wrap.unwrap_or(defaults.wrap),
+            tab_widths: config.tab_widths.clone(),
+            indent_guides: config.indent_guides.unwrap_or(defaults.indent_guides),
+            unified: config.unified.unwrap_or(defaults.unified),
+            detect_moves: config.detect_moves.unwrap_or(defaults.detect_moves),
+            large_file_lines: config.large_file_lines.unwrap_or(defaults.large_file_lines),
+            ..defaults
+        }
+    }
 
-#[derive(Clone, Copy)]
-pub enum ChangeType {
-    Equal,
-    Delete,
-    Insert,
-    /// A paired delete+insert, shown on the same row (GitHub-style)
-    Modified,
+    pub fn to_config(&self) -> DiffConfig {
+        DiffConfig {
+            tab_width: Some(self.tab_width),
+            context_lines: Some(if self.context.enabled {
+                self.context.max_lines
+            } else {
+                0
+            }),
+            ignore_whitespace: Some(self.ignore_whitespace),
+            theme: Some(self.theme),
+            custom_theme: self.custom_theme.clone(),
+            sidebar_width: self.sidebar_width,
+            wrap: Some(self.wrap),
+            tab_widths: self.tab_widths.clone(),
+            indent_guides: Some(self.indent_guides),
+            unified: Some(self.unified),
+            detect_moves: Some(self.detect_moves),
+            large_file_lines: Some(self.large_file_lines),
+        }
+    }
+
+    /// Tab width for `filename`: its extension's or name's override, else the default.
+    pub fn tab_width_for(&self, filename: &str) -> usize {
+        let path = Path::new(filename);
+        [path.extension(), path.file_name()]
+            .into_iter()
+            .flatten()
+            .find_map(|key| self.tab_widths.get(key.to_str()?))
+            .copied()
+            .unwrap_or(self.tab_width)
+    }
+
+    /// Whether `diff` is too long to diff and highlight without being asked to.
+    pub fn is_large(&self, diff: &FileDiff) -> bool {
+        diff.line_count() > self.large_file_lines
+    }
+
+    pub fn sidebar_width(&self, area_width: u16) -> u16 {
+        self.sidebar_width
+            .unwrap_or_else(|| (area_width / 4).clamp(20, 35))
+    }
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Default)]
 pub enum FocusedPanel {
     Sidebar,
+    #[default]
     DiffView,
 }
 
-impl Default for FocusedPanel {
-    fn default() -> Self {
-        FocusedPanel::DiffView
-    }
-}
-
 #[derive(Clone, Copy, PartialEq, Default)]
 pub enum DiffFullscreen {
     #[default]
@@ -103,6 +346,13 @@ pub enum SidebarItem {
         name: String,
         path: String,
         depth: usize,
+        /// Whether its contents are hidden
+        collapsed: bool,
+        /// Indexes of the files under it, shown or not
+        files: Vec<usize>,
+        /// Lines added and removed in those files
+        added: usize,
+        removed: usize,
     },
     File {
         name: String,
@@ -113,19 +363,27 @@ pub enum SidebarItem {
     },
 }
 
-pub fn build_file_tree(file_diffs: &[FileDiff]) -> Vec<SidebarItem> {
+/// The sidebar's tree of the files that pass `show`, keeping their indexes in `file_diffs`.
+/// The contents of directories in `collapsed` are left out, and each directory adds up the
+/// `line_counts` of the files under it.
+pub fn build_file_tree(
+    file_diffs: &[FileDiff],
+    show: impl Fn(&FileDiff) -> bool,
+    collapsed: &HashSet<String>,
+    line_counts: &HashMap<usize, (usize, usize)>,
+) -> Vec<SidebarItem> {
     use std::collections::{BTreeMap, BTreeSet};
 
-    if file_diffs.is_empty() {
-        return Vec::new();
-    }
-
     let mut file_paths: Vec<(String, usize, FileStatus)> = file_diffs
         .iter()
         .enumerate()
-        .map(|(idx, diff)| (diff.filename.clone(), idx, diff.status))
+        .filter(|(_, diff)| show(diff))
+        .map(|(idx, diff)| (diff.filename.clone(), idx, diff.status.clone()))
         .collect();
     file_paths.sort_by(|a, b| a.0.cmp(&b.0));
+    if file_paths.is_empty() {
+        return Vec::new();
+    }
 
     // Count children for each directory path
     let mut dir_children: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
@@ -139,7 +397,7 @@ pub fn build_file_tree(file_diffs: &[FileDiff]) -> Vec<SidebarItem> {
             let parent_path = parts[..parts.len() - 1].join("/");
             dir_children
                 .entry(parent_path)
-                .or_insert_with(BTreeSet::new)
+                .or_default()
                 .insert(path.clone());
         } else {
             // File at root level
@@ -155,12 +413,10 @@ pub fn build_file_tree(file_diffs: &[FileDiff]) -> Vec<SidebarItem> {
                 parts[..i].join("/")
             };
 
-            dir_children
-                .entry(dir_path.clone())
-                .or_insert_with(BTreeSet::new);
+            dir_children.entry(dir_path.clone()).or_default();
             dir_children
                 .entry(parent_path)
-                .or_insert_with(BTreeSet::new)
+                .or_default()
                 .insert(dir_path);
         }
     }
@@ -258,10 +514,24 @@ pub fn build_file_tree(file_diffs: &[FileDiff]) -> Vec<SidebarItem> {
                     }
                 };
 
+                let prefix = format!("{collapsed_path}/");
+                let files: Vec<usize> = file_paths
+                    .iter()
+                    .filter(|(path, _, _)| path.starts_with(&prefix))
+                    .map(|(_, idx, _)| *idx)
+                    .collect();
+                let (added, removed) = files
+                    .iter()
+                    .filter_map(|idx| line_counts.get(idx))
+                    .fold((0, 0), |(a, r), (added, removed)| (a + added, r + removed));
                 items.push(SidebarItem::Directory {
                     name: display_name,
                     path: collapsed_path.clone(),
                     depth,
+                    collapsed: collapsed.contains(&collapsed_path),
+                    files,
+                    added,
+                    removed,
                 });
 
                 // Skip to the end of the collapsed path
@@ -295,9 +565,152 @@ pub fn build_file_tree(file_diffs: &[FileDiff]) -> Vec<SidebarItem> {
             path: path.clone(),
             file_index: *file_idx,
             depth: file_depth,
-            status: *status,
+            status: status.clone(),
         });
     }
 
+    // Only directories in the tree hide anything, not ones a filter has since reshaped
+    let hidden_under: Vec<String> = items
+        .iter()
+        .filter_map(|item| match item {
+            SidebarItem::Directory {
+                path,
+                collapsed: true,
+                ..
+            } => Some(format!("{path}/")),
+            _ => None,
+        })
+        .collect();
+    items.retain(|item| {
+        let path = match item {
+            SidebarItem::Directory { path, .. } | SidebarItem::File { path, .. } => path,
+        };
+        !hidden_under.iter().any(|dir| path.starts_with(dir))
+    });
     items
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_tree_collapses_directories_and_adds_up_lines() {
+        let file = |filename: &str| FileDiff {
+            filename: filename.to_string(),
+            old_content: String::new(),
+            new_content: String::new(),
+            status: FileStatus::Modified,
+            binary: None,
+            submodule: None,
+        };
+        let diffs = [
+            file("src/command/diff/app.rs"),
+            file("src/command/diff/state.rs"),
+            file("src/main.rs"),
+            file("README.md"),
+        ];
+        let counts = HashMap::from([(0, (3, 1)), (1, (2, 0)), (2, (1, 1)), (3, (5, 5))]);
+        let rows = |collapsed: &[&str]| -> Vec<String> {
+            let collapsed = collapsed.iter().map(|dir| dir.to_string()).collect();
+            build_file_tree(&diffs, |_| true, &collapsed, &counts)
+                .iter()
+                .map(|item| match item {
+                    SidebarItem::Directory {
+                        name,
+                        depth,
+                        collapsed,
+                        added,
+                        removed,
+                        ..
+                    } => format!(
+                        "{}{name}/ {collapsed} +{added} -{removed}",
+                        "  ".repeat(*depth)
+                    ),
+                    SidebarItem::File { name, depth, .. } => {
+                        format!("{}{name}", "  ".repeat(*depth))
+                    }
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            rows(&[]),
+            [
+                "README.md",
+                "src/ false +6 -2",
+                "  command/diff/ false +5 -1",
+                "    app.rs",
+                "    state.rs",
+                "  main.rs",
+            ]
+        );
+        assert_eq!(
+            rows(&["src/command/diff"]),
+            [
+                "README.md",
+                "src/ false +6 -2",
+                "  command/diff/ true +5 -1",
+                "  main.rs"
+            ]
+        );
+        assert_eq!(rows(&["src", "src/gone"]), ["README.md", "src/ true +6 -2"]);
+    }
+
+    #[test]
+    fn test_binary_change_description() {
+        let png = b"\x89PNG\r\n".as_slice();
+        let change = BinaryChange::new("logo.png", Some(&[0; 512]), Some(&[0; 3 * 1024 / 2]));
+        assert_eq!(
+            change.describe(),
+            "binary file changed (size 512 B → 1.5 KB)"
+        );
+        assert!(change.preview.is_some());
+        assert_eq!(
+            BinaryChange::new("app.bin", None, Some(png)).describe(),
+            "binary file added (6 B)"
+        );
+        assert_eq!(BinaryChange::new("app.bin", None, Some(png)).preview, None);
+        assert_eq!(BinaryChange::unknown().describe(), "binary file changed");
+    }
+
+    #[test]
+    fn test_submodule_change_description() {
+        let commit = |sha: &str, subject: Option<&str>| SubmoduleCommit {
+            sha: sha.to_string(),
+            subject: subject.map(str::to_string),
+        };
+        let bumped = SubmoduleChange {
+            old: Some(commit(
+                "6df2f32eb5316ddaeb554099e6eec05953a7f7cf",
+                Some("Initial lib"),
+            )),
+            new: Some(commit(
+                "e4c3baabcd6f3cc5424b7c62ceddefefcfaba5f8",
+                Some("Add parser"),
+            )),
+            behind_ahead: Some((0, 1)),
+        };
+        assert_eq!(
+            bumped.describe(),
+            [
+                "submodule moved forward 1 commit",
+                "- 6df2f32 Initial lib",
+                "+ e4c3baa Add parser"
+            ]
+        );
+        assert!(bumped.range().is_some());
+
+        // Without a checkout only the ids are known, and there's nothing to open
+        let unknown = SubmoduleChange {
+            old: Some(commit("6df2f32", None)),
+            new: Some(commit("e4c3baa", None)),
+            behind_ahead: None,
+        };
+        assert_eq!(
+            unknown.describe(),
+            ["submodule changed", "- 6df2f32", "+ e4c3baa"]
+        );
+        assert_eq!(unknown.range(), None);
+    }
+}
@@ -1,16 +1,48 @@
 use std::collections::HashSet;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::mpsc::{self, Receiver};
+use std::thread;
 use std::time::Duration;
 
+use notify::RecursiveMode;
 use notify_debouncer_mini::{new_debouncer, DebouncedEventKind};
 
+use crate::logging;
+
 /// Changed files from file system watcher
 pub struct WatchEvent {
     pub changed_files: HashSet<String>,
 }
 
-pub fn setup_watcher() -> Option<Receiver<WatchEvent>> {
+impl WatchEvent {
+    /// Whether only files inside `.git` changed, as when the index is written
+    pub fn is_git_internal(&self) -> bool {
+        self.changed_files.iter().all(|path| is_git_path(path))
+    }
+
+    /// Fold a later event into this one so no changed file is lost while a reload waits.
+    pub fn merge(&mut self, other: WatchEvent) {
+        self.changed_files.extend(other.changed_files);
+    }
+
+    /// How many files outside `.git` changed
+    pub fn file_count(&self) -> usize {
+        self.changed_files
+            .iter()
+            .filter(|path| !is_git_path(path))
+            .count()
+    }
+}
+
+fn is_git_path(path: &str) -> bool {
+    path == ".git" || path.starts_with(".git/")
+}
+
+/// Watch `paths`, or the whole working tree when there are none. Changes to files
+/// `.gitignore` leaves out, like build output, are dropped, so they don't reload the diff.
+pub fn setup_watcher(paths: &[PathBuf]) -> Result<Receiver<WatchEvent>, String> {
     let (tx, rx) = mpsc::channel();
 
     // Get current directory for making paths relative
@@ -40,20 +72,112 @@ pub fn setup_watcher() -> Option<Receiver<WatchEvent>> {
                         changed_files.insert(normalized.to_string());
                     }
                 }
+                let ignored = ignored(&changed_files);
+                changed_files.retain(|path| !ignored.contains(path));
                 if !changed_files.is_empty() {
                     let _ = tx.send(WatchEvent { changed_files });
                 }
             }
         },
     )
-    .ok()?;
+    .map_err(|e| e.to_string())?;
 
-    debouncer
-        .watcher()
-        .watch(Path::new("."), notify::RecursiveMode::Recursive)
-        .ok()?;
+    let watcher = debouncer.watcher();
+    if paths.is_empty() {
+        watcher
+            .watch(Path::new("."), RecursiveMode::Recursive)
+            .map_err(|e| e.to_string())?;
+    } else {
+        for path in paths {
+            watcher
+                .watch(path, RecursiveMode::Recursive)
+                .map_err(|e| format!("{}: {e}", path.display()))?;
+        }
+        // Staging or committing from elsewhere still reloads
+        let _ = watcher.watch(Path::new(".git"), RecursiveMode::NonRecursive);
+    }
 
     std::mem::forget(debouncer);
 
-    Some(rx)
+    Ok(rx)
+}
+
+/// Which of `paths` the ignore files leave out. Files inside `.git` never are.
+fn ignored(paths: &HashSet<String>) -> HashSet<String> {
+    let paths: HashSet<String> = paths
+        .iter()
+        .filter(|path| !is_git_path(path))
+        .cloned()
+        .collect();
+    if paths.is_empty() {
+        return HashSet::new();
+    }
+    #[cfg(feature = "libgit2")]
+    if let Some(ignored) = crate::libgit::ignored(&paths) {
+        return ignored;
+    }
+
+    let child = logging::spawn(
+        Command::new("git")
+            .args(["check-ignore", "--stdin", "-z"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null()),
+    );
+    let Ok(mut child) = child else {
+        return HashSet::new();
+    };
+    // Written from a thread, since git answers while it reads and could fill its output first
+    if let Some(mut stdin) = child.stdin.take() {
+        let input: Vec<u8> = paths
+            .iter()
+            .flat_map(|path| path.bytes().chain([0]))
+            .collect();
+        thread::spawn(move || stdin.write_all(&input));
+    }
+    // It exits with 1 when nothing is ignored
+    child
+        .wait_with_output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .split('\0')
+                .filter(|path| !path.is_empty())
+                .map(String::from)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(paths: &[&str]) -> WatchEvent {
+        WatchEvent {
+            changed_files: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_git_internal_events() {
+        assert!(event(&[".git/index", ".git/index.lock"]).is_git_internal());
+        assert!(!event(&[".git/index", "src/main.rs"]).is_git_internal());
+        assert!(!event(&[".github/workflows/ci.yml"]).is_git_internal());
+    }
+
+    #[test]
+    fn test_merge_keeps_all_files() {
+        let mut pending = event(&["a.rs"]);
+        pending.merge(event(&["b.rs", "a.rs"]));
+        assert_eq!(pending.changed_files.len(), 2);
+    }
+
+    #[test]
+    fn test_file_count_leaves_out_git_internals() {
+        assert_eq!(
+            event(&[".git/index", "src/main.rs", "a.rs"]).file_count(),
+            2
+        );
+        assert_eq!(event(&[".git/HEAD"]).file_count(), 0);
+    }
 }
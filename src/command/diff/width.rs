@@ -0,0 +1,104 @@
+//! Measuring and cutting text by the columns it takes on screen rather than by bytes or
+//! chars, so CJK text and emoji, which take two columns, line up like everything else.
+
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+/// Columns `c` takes; control characters take none.
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(0)
+}
+
+/// Columns `s` takes.
+pub fn width(s: &str) -> usize {
+    s.width()
+}
+
+/// The longest start of `s` that fits in `max` columns.
+pub fn take_width(s: &str, max: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in s.char_indices() {
+        used += char_width(c);
+        if used > max {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// `s` cut or padded with spaces to exactly `columns` wide.
+pub fn fit(s: &str, columns: usize) -> String {
+    let head = take_width(s, columns);
+    format!("{head}{}", " ".repeat(columns - width(head)))
+}
+
+/// `s` with its middle replaced by `...` when it's wider than `max` columns.
+pub fn truncate_middle(s: &str, max: usize) -> String {
+    if width(s) <= max {
+        return s.to_string();
+    }
+    if max < 5 {
+        return take_width(s, max).to_string();
+    }
+    let half = (max - 3) / 2;
+    let start = take_width(s, half);
+    // The end is taken the same way, from the back
+    let mut used = 0;
+    let end_at = s
+        .char_indices()
+        .rev()
+        .find(|&(_, c)| {
+            used += char_width(c);
+            used > half
+        })
+        .map_or(0, |(i, c)| i + c.len_utf8());
+    format!("{start}...{}", &s[end_at..])
+}
+
+/// Byte offset `i` of `s` moved back to the start of the char it falls in.
+pub fn floor_char_boundary(s: &str, i: usize) -> usize {
+    let mut i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+/// Byte offset `i` of `s` moved on to the end of the char it falls in.
+pub fn ceil_char_boundary(s: &str, i: usize) -> usize {
+    let mut i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i += 1;
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wide_characters_take_two_columns() {
+        assert_eq!(width("日本語"), 6);
+        assert_eq!(take_width("日本語", 5), "日本");
+        assert_eq!(fit("日本語", 5), "日本 ");
+        assert_eq!(fit("ab", 4), "ab  ");
+    }
+
+    #[test]
+    fn test_truncate_middle_counts_columns() {
+        assert_eq!(truncate_middle("src/main.rs", 20), "src/main.rs");
+        assert_eq!(truncate_middle("abcdefghijkl", 9), "abc...jkl");
+        let cut = truncate_middle("ドキュメント/説明書.md", 13);
+        assert!(width(&cut) <= 13, "{cut}");
+        assert!(cut.starts_with("ドキ") && cut.ends_with(".md"));
+    }
+
+    #[test]
+    fn test_char_boundaries() {
+        let s = "aé日";
+        assert_eq!(floor_char_boundary(s, 2), 1);
+        assert_eq!(ceil_char_boundary(s, 2), 3);
+        assert_eq!(ceil_char_boundary(s, 4), 6);
+        assert_eq!(floor_char_boundary(s, 10), 6);
+    }
+}
@@ -0,0 +1,306 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use serde_json::json;
+use spinoff::{spinners, Color, Spinner};
+
+use super::git_style::print_paged;
+use super::output::{Format, Report};
+
+use crate::{error::LumenError, provider::LumenProvider, vcs, webhook::WebhookConfig};
+
+pub struct DigestCommand {
+    pub since: String,
+    pub author: Option<String>,
+    pub branch: Option<String>,
+    pub all: bool,
+    pub format: Format,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+}
+
+/// Markup the digest is written in
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DigestFormat {
+    Markdown,
+    Slack,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DigestCommit {
+    pub sha: String,
+    pub author: String,
+    pub subject: String,
+    pub is_merge: bool,
+    pub files: Vec<String>,
+}
+
+impl DigestCommit {
+    /// Top-level directories the commit touched, or `(root)` for files at the top.
+    fn areas(&self) -> Vec<String> {
+        let mut areas: Vec<String> = self
+            .files
+            .iter()
+            .map(|file| match file.split_once('/') {
+                Some((dir, _)) => dir.to_string(),
+                None => "(root)".to_string(),
+            })
+            .collect();
+        areas.sort();
+        areas.dedup();
+        areas
+    }
+}
+
+// Separators that can't appear in names or subjects
+const RECORD_SEP: char = '\x1e';
+const FIELD_SEP: char = '\x1f';
+
+/// Parse `git log --format=%x1e%h%x1f%an%x1f%s%x1f%P --name-only` output.
+pub fn parse_log(output: &str) -> Vec<DigestCommit> {
+    output
+        .split(RECORD_SEP)
+        .filter_map(|record| {
+            let mut lines = record.lines();
+            let mut fields = lines.next()?.split(FIELD_SEP);
+            let sha = fields.next()?.to_string();
+            let author = fields.next()?.to_string();
+            let subject = fields.next()?.to_string();
+            let parents = fields.next().unwrap_or_default();
+            Some(DigestCommit {
+                sha,
+                author,
+                subject,
+                is_merge: parents.split_whitespace().count() > 1,
+                files: lines
+                    .filter(|l| !l.trim().is_empty())
+                    .map(String::from)
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+/// Render the digest. `highlights` is the AI-written part, as `- ` bullets.
+pub fn render_digest(
+    title: &str,
+    commits: &[DigestCommit],
+    highlights: &str,
+    format: DigestFormat,
+) -> String {
+    let heading = |s: &str| match format {
+        DigestFormat::Markdown => format!("## {s}"),
+        DigestFormat::Slack => format!("*{s}*"),
+    };
+    let bullet = match format {
+        DigestFormat::Markdown => "-",
+        DigestFormat::Slack => "•",
+    };
+    let code = |s: &str| format!("`{s}`");
+
+    let mut out = match format {
+        DigestFormat::Markdown => format!("# {title}\n"),
+        DigestFormat::Slack => format!("*{title}*\n"),
+    };
+
+    let highlights = highlights.trim();
+    if !highlights.is_empty() {
+        out.push_str(&format!("\n{}\n", heading("Highlights")));
+        for line in highlights.lines() {
+            let line = match line.trim_start().strip_prefix("- ") {
+                Some(rest) => format!("{bullet} {rest}"),
+                None => line.to_string(),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+
+    let mut by_author: BTreeMap<&str, Vec<&DigestCommit>> = BTreeMap::new();
+    for commit in commits.iter().filter(|c| !c.is_merge) {
+        by_author.entry(&commit.author).or_default().push(commit);
+    }
+    if !by_author.is_empty() {
+        out.push_str(&format!("\n{}\n", heading("By author")));
+        for (author, commits) in &by_author {
+            out.push_str(&format!("{bullet} {author} ({})\n", commits.len()));
+            for commit in commits {
+                out.push_str(&format!(
+                    "    {bullet} {} {}\n",
+                    code(&commit.sha),
+                    commit.subject
+                ));
+            }
+        }
+    }
+
+    let mut by_area: BTreeMap<String, usize> = BTreeMap::new();
+    for commit in commits.iter().filter(|c| !c.is_merge) {
+        for area in commit.areas() {
+            *by_area.entry(area).or_default() += 1;
+        }
+    }
+    if !by_area.is_empty() {
+        let mut areas: Vec<_> = by_area.into_iter().collect();
+        areas.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        out.push_str(&format!("\n{}\n", heading("By area")));
+        for (area, count) in areas {
+            let noun = if count == 1 { "commit" } else { "commits" };
+            out.push_str(&format!("{bullet} {} ({count} {noun})\n", code(&area)));
+        }
+    }
+
+    let merges: Vec<_> = commits.iter().filter(|c| c.is_merge).collect();
+    if !merges.is_empty() {
+        out.push_str(&format!("\n{}\n", heading("Notable merges")));
+        for merge in merges {
+            out.push_str(&format!(
+                "{bullet} {} {} ({})\n",
+                code(&merge.sha),
+                merge.subject,
+                merge.author
+            ));
+        }
+    }
+
+    out
+}
+
+/// Commit list in a compact form for the highlights prompt.
+pub fn format_commits_for_prompt(commits: &[DigestCommit]) -> String {
+    commits
+        .iter()
+        .map(|c| {
+            let kind = if c.is_merge { " [merge]" } else { "" };
+            format!(
+                "{} {}{kind} ({}) [{}]",
+                c.sha,
+                c.subject,
+                c.author,
+                c.areas().join(", ")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl DigestCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let commits = self.collect_commits()?;
+        let scope = match (&self.branch, self.all) {
+            (_, true) => "all branches".to_string(),
+            (Some(branch), false) => branch.clone(),
+            (None, false) => "HEAD".to_string(),
+        };
+        let title = format!("Digest for {scope} since {}", self.since);
+
+        if commits.is_empty() {
+            println!("No commits on {scope} since {}.", self.since);
+            return Ok(());
+        }
+
+        let mut spinner = Spinner::new(spinners::Dots, "Writing highlights...", Color::Blue);
+        let highlights = provider
+            .digest(&format_commits_for_prompt(&commits))
+            .await?;
+        spinner.clear();
+
+        let markdown = render_digest(&title, &commits, &highlights, DigestFormat::Markdown);
+        if self.format.interactive() {
+            print_paged(&markdown)?;
+            return Ok(());
+        }
+        let commits_json: Vec<_> = commits
+            .iter()
+            .map(|c| {
+                json!({
+                    "sha": c.sha,
+                    "author": c.author,
+                    "subject": c.subject,
+                    "merge": c.is_merge,
+                    "files": c.files,
+                })
+            })
+            .collect();
+        let data = json!({
+            "title": title,
+            "since": self.since,
+            "highlights": highlights.trim(),
+            "commits": commits_json,
+        });
+        let slack = render_digest(&title, &commits, &highlights, DigestFormat::Slack);
+        let report = Report::new("digest", title, markdown, data).with_slack(slack);
+        self.format.emit(&report, self.webhook.as_ref()).await
+    }
+
+    fn collect_commits(&self) -> Result<Vec<DigestCommit>, LumenError> {
+        let mut command = Command::new("git");
+        command.args([
+            "log",
+            "--no-color",
+            &format!("--since={}", self.since),
+            &format!("--format={RECORD_SEP}%h{FIELD_SEP}%an{FIELD_SEP}%s{FIELD_SEP}%P"),
+            "--name-only",
+        ]);
+        if let Some(author) = &self.author {
+            command.arg(format!("--author={author}"));
+        }
+        if self.all {
+            command.arg("--all");
+        } else if let Some(branch) = &self.branch {
+            command.arg(branch);
+        }
+
+        Ok(parse_log(&vcs::run_git(&mut command)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commit(sha: &str, author: &str, is_merge: bool, files: &[&str]) -> DigestCommit {
+        DigestCommit {
+            sha: sha.to_string(),
+            author: author.to_string(),
+            subject: format!("subject {sha}"),
+            is_merge,
+            files: files.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_log() {
+        let output = "\x1eabc1234\x1fAda\x1ffeat: add x\x1fp1\n\nsrc/a.rs\nREADME.md\n\
+                      \x1edef5678\x1fBob\x1fMerge branch 'x'\x1fp1 p2\n";
+        let commits = parse_log(output);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].files, vec!["src/a.rs", "README.md"]);
+        assert_eq!(commits[0].areas(), vec!["(root)", "src"]);
+        assert!(!commits[0].is_merge);
+        assert!(commits[1].is_merge);
+        assert!(commits[1].files.is_empty());
+    }
+
+    #[test]
+    fn test_render_markdown_groups() {
+        let commits = vec![
+            commit("a1", "Bob", false, &["src/x.rs"]),
+            commit("a2", "Ada", false, &["src/y.rs", "docs/z.md"]),
+            commit("m1", "Ada", true, &[]),
+        ];
+        let out = render_digest("Digest", &commits, "- Shipped x", DigestFormat::Markdown);
+        assert!(out.starts_with("# Digest\n\n## Highlights\n- Shipped x\n"));
+        assert!(out.contains("- Ada (1)\n    - `a2` subject a2\n- Bob (1)\n"));
+        assert!(out.contains("## By area\n- `src` (2 commits)\n- `docs` (1 commit)\n"));
+        assert!(out.contains("## Notable merges\n- `m1` subject m1 (Ada)\n"));
+    }
+
+    #[test]
+    fn test_render_slack() {
+        let commits = vec![commit("a1", "Bob", false, &["src/x.rs"])];
+        let out = render_digest("Digest", &commits, "- Shipped x", DigestFormat::Slack);
+        assert!(out.starts_with("*Digest*\n\n*Highlights*\n• Shipped x\n"));
+        assert!(!out.contains("##"));
+    }
+}
@@ -0,0 +1,239 @@
+use std::io::IsTerminal;
+use std::process::Command;
+use std::time::Instant;
+
+use crate::command::diff::highlight;
+use crate::config::cli::{Cli, ProviderType};
+use crate::config::{LumenConfig, ProviderInfo};
+use crate::error::LumenError;
+use crate::provider::LumenProvider;
+
+/// Outcome of a single diagnostic check.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: Status, detail: impl Into<String>) -> Self {
+        Check {
+            name,
+            status,
+            detail: detail.into(),
+        }
+    }
+}
+
+pub struct DoctorCommand;
+
+impl DoctorCommand {
+    /// Run every diagnostic and print a pass/fail report. Fails if any required check fails,
+    /// so the exit code can be used in scripts.
+    pub async fn execute(cli: &Cli) -> Result<(), LumenError> {
+        let mut checks = vec![Self::check_git(), Self::check_repo()];
+        checks.extend(Self::check_config_and_provider(cli).await);
+        checks.push(Self::check_gh());
+        checks.push(Self::check_tool(
+            "fzf",
+            &["--version"],
+            "fallback for `explain --list` when output is piped",
+        ));
+        checks.extend(Self::check_terminal());
+        checks.push(Self::check_highlighting());
+
+        for check in &checks {
+            let (icon, color) = match check.status {
+                Status::Pass => ("✓", "32"),
+                Status::Warn => ("!", "33"),
+                Status::Fail => ("✗", "91"),
+            };
+            println!(
+                "\x1b[{color}m{icon}\x1b[0m {:<12} {}",
+                check.name, check.detail
+            );
+        }
+
+        let failed = checks.iter().filter(|c| c.status == Status::Fail).count();
+        if failed > 0 {
+            return Err(LumenError::CommandError(format!(
+                "{failed} check(s) failed"
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_git() -> Check {
+        match run("git", &["--version"]) {
+            Some(version) => Check::new("git", Status::Pass, version),
+            None => Check::new("git", Status::Fail, "git not found in PATH"),
+        }
+    }
+
+    fn check_repo() -> Check {
+        if run("git", &["rev-parse", "--is-inside-work-tree"]).is_none() {
+            return Check::new("repository", Status::Fail, "not inside a git work tree");
+        }
+
+        let branch = run("git", &["branch", "--show-current"])
+            .filter(|b| !b.is_empty())
+            .unwrap_or_else(|| "detached HEAD".to_string());
+
+        // An unfinished merge/rebase/cherry-pick changes what `diff` and `draft` see
+        let in_progress = ["MERGE_HEAD", "REBASE_HEAD", "CHERRY_PICK_HEAD"]
+            .into_iter()
+            .find(|head| run("git", &["rev-parse", "-q", "--verify", head]).is_some());
+
+        match in_progress {
+            Some(head) => Check::new(
+                "repository",
+                Status::Warn,
+                format!("on {branch}, {head} present (operation in progress)"),
+            ),
+            None => Check::new("repository", Status::Pass, format!("on {branch}")),
+        }
+    }
+
+    async fn check_config_and_provider(cli: &Cli) -> Vec<Check> {
+        let config = match LumenConfig::build(cli) {
+            Ok(config) => config,
+            Err(e) => {
+                return vec![
+                    Check::new("config", Status::Fail, e.to_string()),
+                    Check::new("provider", Status::Fail, "skipped: config is invalid"),
+                ]
+            }
+        };
+
+        let info = ProviderInfo::for_provider(config.provider);
+        let model = config
+            .model
+            .clone()
+            .unwrap_or_else(|| info.default_model.to_string());
+        let mut checks = vec![Check::new(
+            "config",
+            Status::Pass,
+            format!("{} / {model}", info.display_name),
+        )];
+
+        let has_key = config.api_key.is_some()
+            || info.env_key.is_empty()
+            || config.provider == ProviderType::CustomOpenai
+            || std::env::var(info.env_key).is_ok_and(|k| !k.is_empty());
+        if !has_key {
+            checks.push(Check::new(
+                "auth",
+                Status::Fail,
+                format!("no API key (set {} or run `lumen configure`)", info.env_key),
+            ));
+            return checks;
+        }
+
+        let provider = match LumenProvider::new(
+            config.provider,
+            config.api_key,
+            config.model,
+            config.api_base_url,
+        )
+        .map(|provider| provider.with_ai_config(config.ai))
+        {
+            Ok(provider) => provider,
+            Err(e) => {
+                checks.push(Check::new("provider", Status::Fail, e.to_string()));
+                return checks;
+            }
+        };
+
+        let start = Instant::now();
+        match provider.health_check().await {
+            Ok(()) => checks.push(Check::new(
+                "provider",
+                Status::Pass,
+                format!("reachable in {} ms", start.elapsed().as_millis()),
+            )),
+            Err(e) => checks.push(Check::new("provider", Status::Fail, e.to_string())),
+        }
+        checks
+    }
+
+    fn check_gh() -> Check {
+        if run("gh", &["--version"]).is_none() {
+            return Check::new("gh", Status::Warn, "not installed (needed for `diff --pr`)");
+        }
+        match run("gh", &["auth", "status"]) {
+            Some(_) => Check::new("gh", Status::Pass, "installed and authenticated"),
+            None => Check::new("gh", Status::Warn, "installed but not authenticated"),
+        }
+    }
+
+    fn check_tool(name: &'static str, args: &[&str], missing_hint: &str) -> Check {
+        match run(name, args) {
+            Some(version) => Check::new(name, Status::Pass, version),
+            None => Check::new(
+                name,
+                Status::Warn,
+                format!("not installed ({missing_hint})"),
+            ),
+        }
+    }
+
+    fn check_terminal() -> Vec<Check> {
+        let term = std::env::var("TERM").unwrap_or_default();
+        let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+
+        let tty = if std::io::stdout().is_terminal() {
+            Check::new("terminal", Status::Pass, format!("TERM={term}"))
+        } else {
+            Check::new("terminal", Status::Warn, "stdout is not a terminal")
+        };
+
+        let truecolor = if matches!(colorterm.as_str(), "truecolor" | "24bit") {
+            Check::new("truecolor", Status::Pass, format!("COLORTERM={colorterm}"))
+        } else {
+            Check::new(
+                "truecolor",
+                Status::Warn,
+                "COLORTERM not set to truecolor, diff colors may be approximated",
+            )
+        };
+
+        // Mouse reporting can't be queried without taking over the terminal, so infer it
+        let mouse = if term.is_empty() || term == "dumb" {
+            Check::new("mouse", Status::Warn, "unlikely to be supported")
+        } else {
+            Check::new("mouse", Status::Pass, "likely supported")
+        };
+
+        vec![tty, truecolor, mouse]
+    }
+
+    fn check_highlighting() -> Check {
+        let disabled = highlight::disabled_languages();
+        if disabled.is_empty() {
+            return Check::new("highlighting", Status::Pass, "all grammars loaded");
+        }
+        let names: Vec<&str> = disabled.iter().map(|(name, _)| name.as_str()).collect();
+        Check::new(
+            "highlighting",
+            Status::Warn,
+            format!("disabled for {}, shown as plain text", names.join(", ")),
+        )
+    }
+}
+
+/// Run a command and return the first line of its stdout if it succeeded.
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Some(stdout.lines().next().unwrap_or_default().trim().to_string())
+}
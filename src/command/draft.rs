@@ -1,27 +1,910 @@
-use std::io::{IsTerminal, Write};
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use inquire::Select;
+use lumen_core::prompt::{AIPromptError, DraftInput};
+use lumen_core::response::json_object;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::{json, Value};
 
 use crate::{
-    config::configuration::DraftConfig, error::LumenError, git_entity::GitEntity,
+    config::configuration::{DraftConfig, TicketConfig, TicketPlacement},
+    error::LumenError,
+    git_entity::{diff::Diff, GitEntity},
+    hooks::{run_hook, HookOutcome},
+    logging,
     provider::LumenProvider,
+    vcs::git,
+    webhook::WebhookConfig,
 };
 
+use super::conventions::{self, Remark};
+use super::output::{Format, Report};
+use super::LumenCommand;
+
+/// Limit for the subject line unless `draft.subject_max_length` says otherwise, the 50 of
+/// the 50/72 rule
+pub const SUBJECT_MAX_LEN: usize = 50;
+/// Column the body is wrapped at
+const BODY_WRAP: usize = 72;
+
 pub struct DraftCommand {
     pub git_entity: GitEntity,
     pub context: Option<String>,
     pub draft_config: DraftConfig,
+    pub subject_only: bool,
+    /// Run `git commit` with the message after confirmation
+    pub commit: bool,
+    /// Open the message in the editor before committing
+    pub edit: bool,
+    /// How many alternative messages to generate; more than one opens a picker
+    pub count: usize,
+    /// Formats other than `markdown` write out every candidate instead of picking one
+    pub format: Format,
+    /// With `--squash`, the branch the message covers instead of the staged changes
+    pub squash: Option<SquashBranch>,
+    /// With `--amend`, the message of the commit the staged changes are added to
+    pub amend: Option<String>,
+    /// Copy the message to the clipboard once it's printed
+    pub copy: bool,
+    /// With `--hook`: the commit message file of a `prepare-commit-msg` hook
+    pub message_file: Option<PathBuf>,
+    /// Shell command each message is piped through; messages it rejects are dropped
+    pub hook: Option<String>,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+}
+
+/// A branch about to be squash-merged, whose own commit messages the merge discards
+#[derive(Debug)]
+pub struct SquashBranch {
+    /// Subject and indented body of each commit on the branch, oldest first
+    pub log: String,
+    /// `Name <email>` of everyone else who authored or co-authored a commit on the branch
+    pub co_authors: Vec<String>,
+}
+
+impl SquashBranch {
+    /// Read the commits on HEAD since it left `base`.
+    pub fn load(base: &str) -> Result<Self, LumenError> {
+        let range = format!("{base}..HEAD");
+        let log = git(&[
+            "log",
+            "--reverse",
+            "--no-merges",
+            "--format=- %s%n%w(0,2,2)%b",
+            &range,
+        ])?;
+        if log.trim().is_empty() {
+            return Err(LumenError::CommandError(format!(
+                "no commits on the current branch since {base}"
+            )));
+        }
+        let authors = git(&[
+            "log",
+            "--no-merges",
+            "--format=%an <%ae>%n%(trailers:key=Co-authored-by,valueonly)",
+            &range,
+        ])?;
+        let own_email = git(&["config", "user.email"]).unwrap_or_default();
+        Ok(SquashBranch {
+            log: log.trim_end().to_string(),
+            co_authors: co_authors(&authors, own_email.trim()),
+        })
+    }
+}
+
+/// The message of the commit at HEAD, for `--amend`.
+pub fn head_message() -> Result<String, LumenError> {
+    let message = git(&["log", "-1", "--format=%B", "HEAD"])
+        .map_err(|_| LumenError::CommandError("there's no commit to amend yet".to_string()))?;
+    Ok(message.trim().to_string())
+}
+
+/// The current branch, `None` when HEAD is detached.
+fn current_branch() -> Option<String> {
+    git(&["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .ok()
+        .map(|branch| branch.trim().to_string())
+        .filter(|branch| !branch.is_empty())
+}
+
+/// A ticket found in the branch name, and how it's referenced in the message
+#[derive(Debug, PartialEq)]
+pub struct Ticket {
+    pub id: String,
+    pub reference: String,
+    pub placement: TicketPlacement,
+}
+
+impl Ticket {
+    /// The ticket in `branch`, like `PROJ-123` in `feature/PROJ-123-login`, if there's one.
+    pub fn from_branch(config: &TicketConfig, branch: &str) -> Result<Option<Self>, LumenError> {
+        let pattern = Regex::new(&config.pattern)
+            .map_err(|e| LumenError::InvalidConfiguration(format!("draft.ticket.pattern: {e}")))?;
+        let Some(captures) = pattern.captures(branch) else {
+            return Ok(None);
+        };
+        let id = captures
+            .get(1)
+            .or_else(|| captures.get(0))
+            .map_or("", |m| m.as_str())
+            .to_string();
+        let format = config.format.as_deref().unwrap_or(match config.placement {
+            TicketPlacement::Prefix => "{ticket}:",
+            TicketPlacement::Suffix => "({ticket})",
+            TicketPlacement::Trailer => "Refs: {ticket}",
+        });
+        Ok(Some(Ticket {
+            reference: format.replace("{ticket}", &id),
+            id,
+            placement: config.placement,
+        }))
+    }
+}
+
+/// Distinct `Name <email>` lines, by email and in order, leaving out `own_email`.
+fn co_authors(lines: &str, own_email: &str) -> Vec<String> {
+    let mut seen: Vec<String> = vec![own_email.to_lowercase()];
+    let mut authors = Vec::new();
+    for line in lines.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let email = line
+            .rsplit_once('<')
+            .map_or(line, |(_, email)| email.trim_end_matches('>'))
+            .to_lowercase();
+        if !seen.contains(&email) {
+            seen.push(email);
+            authors.push(line.to_string());
+        }
+    }
+    authors
+}
+
+#[derive(Debug, PartialEq)]
+pub struct DraftMessage {
+    /// Conventional commit type and scope the header was rendered from
+    pub kind: String,
+    pub scope: Option<String>,
+    pub subject: String,
+    pub body: Option<String>,
+    /// What the convention checks fixed or found
+    pub remarks: Vec<Remark>,
+}
+
+impl DraftMessage {
+    /// Full commit message in the format git expects: subject, blank line, body.
+    pub fn to_commit_message(&self) -> String {
+        match &self.body {
+            Some(body) => format!("{}\n\n{}", self.subject, body),
+            None => self.subject.clone(),
+        }
+    }
+
+    /// Put the ticket reference in the header, unless the message mentions the ticket
+    /// already. A trailer is returned instead, to go with the others.
+    fn add_ticket(&mut self, ticket: &Ticket) -> Option<String> {
+        if self.to_commit_message().contains(&ticket.id) {
+            return None;
+        }
+        match ticket.placement {
+            TicketPlacement::Prefix => {
+                self.subject = format!("{} {}", ticket.reference, self.subject)
+            }
+            TicketPlacement::Suffix => {
+                self.subject = format!("{} {}", self.subject, ticket.reference)
+            }
+            TicketPlacement::Trailer => return Some(ticket.reference.clone()),
+        }
+        None
+    }
+
+    /// Append git trailers such as `Co-authored-by: ...` after the body.
+    fn add_trailers(&mut self, trailers: &[String]) {
+        if trailers.is_empty() {
+            return;
+        }
+        let trailers = trailers.join("\n");
+        self.body = Some(match self.body.take() {
+            Some(body) => format!("{body}\n\n{trailers}"),
+            None => trailers,
+        });
+    }
+
+    /// The message and its parts, for `--format json`. `subject` is the rendered header line.
+    pub fn to_json(&self) -> Value {
+        let mut value = json!({
+            "type": self.kind,
+            "scope": self.scope,
+            "subject": self.subject,
+            "body": self.body,
+            "message": self.to_commit_message(),
+        });
+        if !self.remarks.is_empty() {
+            value["remarks"] = self.remarks.iter().map(ToString::to_string).collect();
+        }
+        value
+    }
+
+    /// Point out the subject's length and any `forbidden_words`, after trailers are added.
+    fn annotate(&mut self, forbidden_words: &[String]) {
+        let remarks = conventions::annotate(&self.subject, self.body.as_deref(), forbidden_words);
+        self.remarks.extend(remarks);
+    }
+
+    /// Print the remarks to stderr, so they stay out of a piped message.
+    fn print_remarks(&self) {
+        let styled = io::stderr().is_terminal();
+        for remark in &self.remarks {
+            if styled {
+                eprintln!("\x1b[2m{remark}\x1b[0m");
+            } else {
+                eprintln!("{remark}");
+            }
+        }
+    }
+}
+
+/// Attempts before giving up on responses that don't validate
+const MAX_ATTEMPTS: usize = 3;
+
+/// Gitmoji for the default commit types, from gitmoji.dev
+const GITMOJIS: [(&str, &str); 11] = [
+    ("feat", "✨"),
+    ("fix", "🐛"),
+    ("docs", "📝"),
+    ("style", "🎨"),
+    ("refactor", "♻️"),
+    ("perf", "⚡️"),
+    ("test", "✅"),
+    ("build", "📦️"),
+    ("ci", "👷"),
+    ("chore", "🔧"),
+    ("revert", "⏪️"),
+];
+
+/// What a drafted header may contain and how it's decorated, from the draft config
+#[derive(Debug)]
+pub struct Taxonomy {
+    /// Allowed types; any type when empty
+    pub types: Vec<String>,
+    /// Allowed scopes; any scope when empty
+    pub scopes: Vec<String>,
+    /// Emoji the header starts with, by type; empty without gitmoji
+    pub emojis: HashMap<String, String>,
+    /// Headers are cut at a word boundary to fit
+    pub subject_max_len: usize,
+}
+
+impl Default for Taxonomy {
+    fn default() -> Self {
+        Taxonomy {
+            types: Vec::new(),
+            scopes: Vec::new(),
+            emojis: HashMap::new(),
+            subject_max_len: SUBJECT_MAX_LEN,
+        }
+    }
+}
+
+impl Taxonomy {
+    pub fn new(config: &DraftConfig) -> Self {
+        let mut emojis = HashMap::new();
+        if config.gitmoji {
+            for (kind, emoji) in GITMOJIS {
+                emojis.insert(kind.to_string(), emoji.to_string());
+            }
+            emojis.extend(config.gitmojis.clone());
+        }
+        Taxonomy {
+            types: serde_json::from_str::<HashMap<String, String>>(&config.commit_types)
+                .map(|types| types.into_keys().collect())
+                .unwrap_or_default(),
+            scopes: config.scopes.clone(),
+            emojis,
+            subject_max_len: config.subject_limit(),
+        }
+    }
+}
+
+/// One message as the model returns it, before it's rendered into a header and body.
+#[derive(Debug, Deserialize)]
+struct StructuredDraft {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    scope: Option<String>,
+    subject: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct StructuredResponse {
+    messages: Vec<StructuredDraft>,
+}
+
+impl StructuredDraft {
+    /// Check the message follows the conventional commit format with a type and scope the
+    /// taxonomy allows.
+    fn validate(&self, taxonomy: &Taxonomy) -> Result<(), String> {
+        let kind = self.kind.trim();
+        if kind.is_empty() {
+            return Err("missing type".to_string());
+        }
+        if !taxonomy.types.is_empty() && !taxonomy.types.iter().any(|t| t == kind) {
+            return Err(format!("unknown type `{kind}`"));
+        }
+        if let Some(scope) = self.scope.as_deref().map(str::trim) {
+            if scope.contains(|c: char| c.is_whitespace() || c == '(' || c == ')') {
+                return Err(format!("invalid scope `{scope}`"));
+            }
+            if !scope.is_empty()
+                && !taxonomy.scopes.is_empty()
+                && !taxonomy.scopes.iter().any(|s| s == scope)
+            {
+                return Err(format!(
+                    "unknown scope `{scope}`, expected one of: {}",
+                    taxonomy.scopes.join(", ")
+                ));
+            }
+        }
+
+        let subject = self.subject.trim();
+        if subject.is_empty() {
+            return Err("empty subject".to_string());
+        }
+        if subject.contains('\n') {
+            return Err("subject spans several lines".to_string());
+        }
+        // The model repeating the header in the subject would render as `feat: feat: ...`
+        if subject.starts_with(&format!("{kind}:")) || subject.starts_with(&format!("{kind}(")) {
+            return Err("subject repeats the type".to_string());
+        }
+        Ok(())
+    }
+
+    fn into_message(self, taxonomy: &Taxonomy) -> DraftMessage {
+        let kind = self.kind.trim().to_string();
+        let scope = self
+            .scope
+            .as_deref()
+            .map(str::trim)
+            .filter(|scope| !scope.is_empty())
+            .map(str::to_string);
+        let emoji = taxonomy
+            .emojis
+            .get(&kind)
+            .map_or(String::new(), |emoji| format!("{emoji} "));
+        let header = match &scope {
+            Some(scope) => format!("{emoji}{kind}({scope}): {}", self.subject.trim()),
+            None => format!("{emoji}{kind}: {}", self.subject.trim()),
+        };
+        let body = wrap_body(self.body.unwrap_or_default().trim(), BODY_WRAP);
+        let (subject, remarks) =
+            conventions::fix_subject(&normalize_subject(&header, taxonomy.subject_max_len));
+        DraftMessage {
+            kind,
+            scope,
+            subject,
+            body: (!body.is_empty()).then_some(body),
+            remarks,
+        }
+    }
+}
+
+/// Parse and validate the `{"messages": [...]}` object from the AI response, ignoring any
+/// prose or code fences around it.
+pub fn parse_draft_response(
+    ai_response: &str,
+    taxonomy: &Taxonomy,
+) -> Result<Vec<DraftMessage>, String> {
+    let json = json_object(ai_response).ok_or("no JSON object in response")?;
+    let response: StructuredResponse = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    if response.messages.is_empty() {
+        return Err("no messages in response".to_string());
+    }
+
+    response
+        .messages
+        .into_iter()
+        .map(|draft| {
+            draft.validate(taxonomy)?;
+            Ok(draft.into_message(taxonomy))
+        })
+        .collect()
+}
+
+/// Collapse the subject onto one line and cut it at a word boundary if it's over `max_len`.
+fn normalize_subject(subject: &str, max_len: usize) -> String {
+    let subject = subject.split_whitespace().collect::<Vec<_>>().join(" ");
+    if subject.chars().count() <= max_len {
+        return subject.to_string();
+    }
+
+    let mut result = String::new();
+    for word in subject.split(' ') {
+        if result.chars().count() + word.chars().count() + 1 > max_len {
+            break;
+        }
+        if !result.is_empty() {
+            result.push(' ');
+        }
+        result.push_str(word);
+    }
+    if result.is_empty() {
+        subject.chars().take(max_len).collect()
+    } else {
+        result
+    }
+}
+
+/// Re-wrap each paragraph of the body at `width`, keeping list markers as hanging indents.
+fn wrap_body(body: &str, width: usize) -> String {
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        let line = line.trim_end();
+        if line.chars().count() <= width {
+            lines.push(line.to_string());
+            continue;
+        }
+
+        let content = line.trim_start();
+        let indent = &line[..line.len() - content.len()];
+        let is_bullet = content.starts_with("- ") || content.starts_with("* ");
+        let continuation = if is_bullet {
+            format!("{indent}  ")
+        } else {
+            indent.to_string()
+        };
+
+        let mut current = indent.to_string();
+        for word in content.split_whitespace() {
+            let at_line_start = current.trim().is_empty();
+            if !at_line_start && current.chars().count() + 1 + word.chars().count() > width {
+                lines.push(current);
+                current = continuation.clone();
+            } else if !at_line_start {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        lines.push(current);
+    }
+    lines.join("\n")
 }
 
 impl DraftCommand {
+    /// What the draft prompt is built from: the staged diff, or a branch's with `--squash`.
+    pub fn prompt_input(&self) -> Result<DraftInput<'_>, AIPromptError> {
+        let diff = match (&self.git_entity, &self.squash) {
+            (GitEntity::Diff(Diff::WorkingTree { diff, .. }), None)
+            | (GitEntity::Diff(Diff::CommitsRange { diff, .. }), Some(_)) => diff,
+            _ => {
+                return Err(AIPromptError(
+                    "`draft` is only supported for working tree diffs and squashed branches".into(),
+                ))
+            }
+        };
+        Ok(DraftInput {
+            diff,
+            context: self.context.as_deref(),
+            squash: self.squash.as_ref().map(|branch| branch.log.as_str()),
+            amend: self.amend.as_deref(),
+            count: self.count,
+            commit_types: &self.draft_config.commit_types,
+            scopes: &self.draft_config.scopes,
+            gitmoji: self.draft_config.gitmoji,
+            subject_limit: self.draft_config.subject_limit(),
+        })
+    }
+
     pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        let result = provider.draft(self).await?;
+        if !self.format.interactive() {
+            let messages = self.generate(provider).await?;
+            let markdown = messages
+                .iter()
+                .map(|message| format!("```\n{}\n```", message.to_commit_message()))
+                .collect::<Vec<_>>()
+                .join("\n\n");
+            let report = Report::new(
+                "draft",
+                "Commit message",
+                markdown,
+                json!({
+                    "messages": messages.iter().map(DraftMessage::to_json).collect::<Vec<_>>(),
+                }),
+            );
+            return self.format.emit(&report, self.webhook.as_ref()).await;
+        }
+
+        let message = if self.count > 1 {
+            self.pick_candidate(provider).await?
+        } else {
+            self.generate(provider).await?.swap_remove(0)
+        };
+
+        if self.commit {
+            return self.commit_with(&message);
+        }
+        if let Some(path) = &self.message_file {
+            return self.write_to(path, &message);
+        }
+
+        let is_terminal = std::io::stdout().is_terminal();
+        let output = if self.subject_only {
+            message.subject.clone()
+        } else if is_terminal {
+            // Bold subject and dimmed body so the two parts read distinctly
+            match &message.body {
+                Some(body) => format!("\x1b[1m{}\x1b[0m\n\n\x1b[2m{body}\x1b[0m", message.subject),
+                None => format!("\x1b[1m{}\x1b[0m", message.subject),
+            }
+        } else {
+            message.to_commit_message()
+        };
 
         // Only add newline when outputting to terminal, not when piped (e.g., `lumen draft | pbcopy`)
-        if std::io::stdout().is_terminal() {
-            println!("{result}");
+        if is_terminal {
+            println!("{output}");
         } else {
-            print!("{result}");
+            print!("{output}");
         }
         std::io::stdout().flush()?;
+        message.print_remarks();
+        if self.copy {
+            let plain = if self.subject_only {
+                message.subject.clone()
+            } else {
+                message.to_commit_message()
+            };
+            LumenCommand::copy_to_clipboard(&plain);
+        }
+        Ok(())
+    }
+
+    /// Request messages until the response validates and the hook accepts at least one of
+    /// them, up to `MAX_ATTEMPTS` times. Always returns at least one message.
+    pub async fn generate(
+        &self,
+        provider: &LumenProvider,
+    ) -> Result<Vec<DraftMessage>, LumenError> {
+        let taxonomy = Taxonomy::new(&self.draft_config);
+        let ticket = match (&self.draft_config.ticket, current_branch()) {
+            (Some(config), Some(branch)) => Ticket::from_branch(config, &branch)?,
+            _ => None,
+        };
+
+        let mut last_error = String::new();
+        for _ in 0..MAX_ATTEMPTS {
+            let response = provider.draft(self).await?;
+            match parse_draft_response(&response, &taxonomy) {
+                Ok(mut messages) => {
+                    // Credits and the ticket are added here rather than trusted to the model
+                    let co_authors: Vec<String> = self
+                        .squash
+                        .iter()
+                        .flat_map(|branch| &branch.co_authors)
+                        .map(|author| format!("Co-authored-by: {author}"))
+                        .collect();
+                    for message in &mut messages {
+                        let mut trailers = co_authors.clone();
+                        if let Some(trailer) = ticket.as_ref().and_then(|t| message.add_ticket(t)) {
+                            trailers.insert(0, trailer);
+                        }
+                        message.add_trailers(&trailers);
+                    }
+                    for message in &mut messages {
+                        message.annotate(&self.draft_config.forbidden_words);
+                    }
+                    if let Some(hook) = &self.hook {
+                        let mut accepted = Vec::new();
+                        for message in messages {
+                            match run_hook(hook, &message.to_commit_message())? {
+                                HookOutcome::Accepted => accepted.push(message),
+                                HookOutcome::Rejected(reason) => last_error = reason,
+                            }
+                        }
+                        if accepted.is_empty() {
+                            eprintln!(
+                                "Hook rejected the message, generating another: {last_error}"
+                            );
+                            continue;
+                        }
+                        messages = accepted;
+                    }
+                    return Ok(messages);
+                }
+                Err(e) => last_error = e,
+            }
+        }
+        Err(LumenError::CommandError(format!(
+            "no valid commit message after {MAX_ATTEMPTS} attempts: {last_error}"
+        )))
+    }
+
+    /// Generate candidates and let the user pick one, or ask for a fresh batch.
+    async fn pick_candidate(&self, provider: &LumenProvider) -> Result<DraftMessage, LumenError> {
+        const REGENERATE: &str = "↻ Regenerate";
+
+        if !io::stdin().is_terminal() {
+            return Err(LumenError::InvalidArguments(
+                "--count needs an interactive terminal to pick a message".to_string(),
+            ));
+        }
+
+        loop {
+            let mut candidates = self.generate(provider).await?;
+            let mut options: Vec<String> = candidates.iter().map(|c| c.subject.clone()).collect();
+            options.push(REGENERATE.to_string());
+
+            let choice = Select::new("Pick a commit message:", options)
+                .raw_prompt()
+                .map_err(|e| LumenError::CommandError(e.to_string()))?;
+            if choice.index < candidates.len() {
+                return Ok(candidates.swap_remove(choice.index));
+            }
+        }
+    }
+
+    /// Put the message above what git already wrote to the file, its template and comments.
+    fn write_to(&self, path: &Path, message: &DraftMessage) -> Result<(), LumenError> {
+        message.print_remarks();
+        let message = if self.subject_only {
+            message.subject.clone()
+        } else {
+            message.to_commit_message()
+        };
+        let existing = fs::read_to_string(path).unwrap_or_default();
+        fs::write(path, format!("{message}\n{existing}"))?;
         Ok(())
     }
+
+    fn commit_with(&self, message: &DraftMessage) -> Result<(), LumenError> {
+        message.print_remarks();
+        let message = if self.subject_only {
+            message.subject.clone()
+        } else {
+            message.to_commit_message()
+        };
+
+        // The editor is the confirmation step when editing: git aborts on an empty message
+        if !self.edit {
+            println!("{message}\n");
+            if self.amend.is_some() {
+                print!("Amend HEAD with the staged changes and this message? [y/N] ");
+            } else {
+                print!("Commit with this message? [y/N] ");
+            }
+            io::stdout().flush()?;
+
+            let mut input = String::new();
+            io::stdin().read_line(&mut input)?;
+            if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+                println!("Commit aborted.");
+                return Ok(());
+            }
+        }
+
+        let mut command = Command::new("git");
+        command.args(["commit", "-m", &message]);
+        if self.amend.is_some() {
+            command.arg("--amend");
+        }
+        if self.edit {
+            command.arg("--edit");
+        }
+        let status = logging::status(&mut command)?;
+        if !status.success() {
+            return Err(LumenError::CommandError("git commit failed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types() -> Taxonomy {
+        Taxonomy {
+            types: vec!["feat".to_string(), "fix".to_string()],
+            ..Taxonomy::default()
+        }
+    }
+
+    #[test]
+    fn test_parse_with_scope_and_body() {
+        let messages = parse_draft_response(
+            r#"{"messages": [{"type": "feat", "scope": "cli", "subject": "add draft flag.", "body": "Explain why."}]}"#,
+            &types(),
+        )
+        .unwrap();
+        assert_eq!(
+            messages,
+            vec![DraftMessage {
+                kind: "feat".to_string(),
+                scope: Some("cli".to_string()),
+                subject: "feat(cli): add draft flag".to_string(),
+                body: Some("Explain why.".to_string()),
+                remarks: vec![Remark {
+                    fixed: true,
+                    text: "dropped the period at the end of the subject".to_string(),
+                }],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_ignores_surrounding_prose() {
+        let messages = parse_draft_response(
+            "Here you go:\n```json\n{\"messages\": [{\"type\": \"fix\", \"scope\": null, \"subject\": \"handle empty diff\", \"body\": null}]}\n```",
+            &types(),
+        )
+        .unwrap();
+        assert_eq!(messages[0].subject, "fix: handle empty diff");
+        assert_eq!(messages[0].body, None);
+        assert_eq!(messages[0].to_commit_message(), "fix: handle empty diff");
+        assert_eq!(
+            messages[0].to_json(),
+            json!({
+                "type": "fix",
+                "scope": null,
+                "subject": "fix: handle empty diff",
+                "body": null,
+                "message": "fix: handle empty diff",
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_several_candidates() {
+        let messages = parse_draft_response(
+            r#"{"messages": [{"type": "feat", "subject": "a"}, {"type": "fix", "subject": "b"}]}"#,
+            &types(),
+        )
+        .unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1].subject, "fix: b");
+    }
+
+    #[test]
+    fn test_invalid_responses_are_rejected() {
+        let parse = |json: &str| parse_draft_response(json, &types());
+        assert!(parse("feat: not json").is_err());
+        assert!(parse("}x{").is_err());
+        assert!(parse(r#"{"messages": []}"#).is_err());
+        assert!(parse(r#"{"messages": [{"type": "wip", "subject": "x"}]}"#).is_err());
+        assert!(parse(r#"{"messages": [{"type": "feat", "subject": ""}]}"#).is_err());
+        assert!(parse(r#"{"messages": [{"type": "feat", "subject": "feat: x"}]}"#).is_err());
+        assert!(
+            parse(r#"{"messages": [{"type": "feat", "scope": "a b", "subject": "x"}]}"#).is_err()
+        );
+        // No configured types means any type is accepted
+        assert!(parse_draft_response(
+            r#"{"messages": [{"type": "wip", "subject": "x"}]}"#,
+            &Taxonomy::default()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_custom_scopes_and_gitmoji() {
+        let config = DraftConfig {
+            commit_types: r#"{"feat": "A feature", "deps": "Dependency updates"}"#.to_string(),
+            scopes: vec!["api".to_string(), "ui".to_string()],
+            gitmoji: true,
+            gitmojis: HashMap::from([("deps".to_string(), "⬆️".to_string())]),
+            ..DraftConfig::default()
+        };
+        let taxonomy = Taxonomy::new(&config);
+        let parse = |json: &str| parse_draft_response(json, &taxonomy);
+
+        let messages = parse(
+            r#"{"messages": [{"type": "feat", "scope": "api", "subject": "add paging"},
+                {"type": "deps", "subject": "bump serde"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(messages[0].subject, "✨ feat(api): add paging");
+        assert_eq!(messages[1].subject, "⬆️ deps: bump serde");
+        assert!(
+            parse(r#"{"messages": [{"type": "feat", "scope": "db", "subject": "x"}]}"#)
+                .unwrap_err()
+                .contains("expected one of: api, ui")
+        );
+        assert!(parse(r#"{"messages": [{"type": "fix", "subject": "x"}]}"#).is_err());
+    }
+
+    #[test]
+    fn test_co_authors_are_distinct_and_leave_you_out() {
+        let lines = "Ada <ada@x.dev>\n\nMe <me@x.dev>\nBob <bob@x.dev>\nAda L <ADA@x.dev>\n";
+        assert_eq!(
+            co_authors(lines, "me@x.dev"),
+            vec!["Ada <ada@x.dev>", "Bob <bob@x.dev>"]
+        );
+
+        let mut message = DraftMessage {
+            kind: "feat".to_string(),
+            scope: None,
+            subject: "feat: add squash drafts".to_string(),
+            body: None,
+            remarks: Vec::new(),
+        };
+        message.add_trailers(&["Co-authored-by: Ada <ada@x.dev>".to_string()]);
+        assert_eq!(
+            message.to_commit_message(),
+            "feat: add squash drafts\n\nCo-authored-by: Ada <ada@x.dev>"
+        );
+    }
+
+    #[test]
+    fn test_ticket_from_branch_name() {
+        let config = |placement, format: Option<&str>| TicketConfig {
+            pattern: r"[A-Z][A-Z0-9]+-\d+".to_string(),
+            placement,
+            format: format.map(str::to_string),
+        };
+        let ticket = |config: &TicketConfig, branch| Ticket::from_branch(config, branch).unwrap();
+        let message = || DraftMessage {
+            kind: "feat".to_string(),
+            scope: None,
+            subject: "feat: add login".to_string(),
+            body: None,
+            remarks: Vec::new(),
+        };
+
+        let prefix = config(TicketPlacement::Prefix, None);
+        assert_eq!(ticket(&prefix, "main"), None);
+        let mut drafted = message();
+        assert_eq!(
+            drafted.add_ticket(&ticket(&prefix, "feature/PROJ-123-login").unwrap()),
+            None
+        );
+        assert_eq!(drafted.subject, "PROJ-123: feat: add login");
+
+        let suffix = config(TicketPlacement::Suffix, Some("[{ticket}]"));
+        let mut drafted = message();
+        drafted.add_ticket(&ticket(&suffix, "PROJ-7").unwrap());
+        assert_eq!(drafted.subject, "feat: add login [PROJ-7]");
+
+        let issue = TicketConfig {
+            pattern: r"^(\d+)-".to_string(),
+            ..config(TicketPlacement::Trailer, Some("Refs #{ticket}"))
+        };
+        let found = ticket(&issue, "42-fix-login").unwrap();
+        assert_eq!(message().add_ticket(&found), Some("Refs #42".to_string()));
+        let mut mentioned = DraftMessage {
+            body: Some("Closes 42".to_string()),
+            ..message()
+        };
+        assert_eq!(mentioned.add_ticket(&found), None);
+    }
+
+    #[test]
+    fn test_subject_is_single_line_and_limited() {
+        let subject = normalize_subject(
+            &format!("feat: {}\nmore", "word ".repeat(30)),
+            SUBJECT_MAX_LEN,
+        );
+        assert!(!subject.contains('\n'));
+        assert_eq!(subject.chars().count(), SUBJECT_MAX_LEN);
+        assert!(subject.ends_with("word"));
+        assert_eq!(
+            normalize_subject("fix: a longer subject", 14),
+            "fix: a longer"
+        );
+    }
+
+    #[test]
+    fn test_body_wrapped_with_hanging_indent() {
+        let body = format!("- {}", "lorem ".repeat(20));
+        let wrapped = wrap_body(body.trim(), 30);
+        let lines: Vec<&str> = wrapped.lines().collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.chars().count() <= 30));
+        assert!(lines[1].starts_with("  lorem"));
+    }
 }
@@ -1,19 +1,283 @@
+use std::process::Command;
+
+use lumen_core::patch::parse_hunks;
+use lumen_core::prompt::{ExplainInput, Explained};
+use lumen_core::response::{json_array, json_object};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use spinoff::{spinners, Color, Spinner};
 
-use crate::{error::LumenError, git_entity::GitEntity, provider::LumenProvider};
+use crate::{
+    error::LumenError,
+    git_entity::{commit::Commit, diff::Diff, GitEntity},
+    hooks::{run_hook, HookOutcome, MAX_HOOK_ATTEMPTS},
+    logging,
+    provider::LumenProvider,
+    webhook::WebhookConfig,
+};
 
+use super::diff::RiskLevel;
+use super::git_style::{print_paged, DiffColors};
+use super::output::{Annotation, Format, Report};
 use super::LumenCommand;
 
 pub struct ExplainCommand {
     pub git_entity: GitEntity,
+    /// The Cargo crates the change touches and their public API changes, see
+    /// [`super::cargo_workspace::api_section`]
+    pub crates: Option<String>,
     pub query: Option<String>,
+    /// For `--branch`: the branch's commits, oldest first, each with its short SHA, see
+    /// [`branch_log`]
+    pub commits: Option<String>,
+    pub annotate: bool,
+    /// Assess the risk of the change as a checklist instead of explaining it, see
+    /// [`ImpactReport`]
+    pub impact: bool,
+    pub cache: bool,
+    pub format: Format,
+    /// Copy the explanation to the clipboard once it's printed
+    pub copy: bool,
+    /// Shell command the explanation is piped through before it's printed
+    pub hook: Option<String>,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct HunkAnnotation {
+    pub hunk: usize,
+    pub note: String,
+}
+
+/// The structured answer asked for by structured formats like `--format json`.
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct ExplainReport {
+    pub summary: String,
+    #[serde(default)]
+    pub files: Vec<FileNote>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct FileNote {
+    pub path: String,
+    pub note: String,
+}
+
+/// The risk assessment asked for by `--impact`.
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ImpactReport {
+    pub risk: RiskLevel,
+    #[serde(default)]
+    pub summary: String,
+    /// Public functions, types, endpoints, flags or formats the change affects
+    #[serde(default)]
+    pub apis: Vec<ApiChange>,
+    /// Code that depends on the change and is likely to break
+    #[serde(default)]
+    pub callers: Vec<Caller>,
+    /// Configuration or migration steps for users and deployers
+    #[serde(default)]
+    pub migration: Vec<String>,
+    #[serde(default)]
+    pub tests: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct ApiChange {
+    pub item: String,
+    pub change: String,
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct Caller {
+    /// `file:line`, or a module when the model can't point at a line
+    pub location: String,
+    pub reason: String,
+}
+
+impl ImpactReport {
+    /// The assessment as a markdown checklist, one section per kind of follow-up.
+    pub fn to_markdown(&self) -> String {
+        let risk = match self.risk {
+            RiskLevel::Low => "low",
+            RiskLevel::Medium => "medium",
+            RiskLevel::High => "high",
+        };
+        let mut markdown = format!("## Impact: {risk} risk");
+        if !self.summary.trim().is_empty() {
+            markdown.push_str(&format!("\n\n{}", self.summary.trim()));
+        }
+        let sections = [
+            (
+                "Public APIs affected",
+                self.apis
+                    .iter()
+                    .map(|api| format!("`{}`: {}", api.item, api.change))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "Callers likely to break",
+                self.callers
+                    .iter()
+                    .map(|caller| format!("`{}`: {}", caller.location, caller.reason))
+                    .collect(),
+            ),
+            ("Configuration and migration", self.migration.clone()),
+            ("Tests to add", self.tests.clone()),
+        ];
+        for (title, items) in sections {
+            markdown.push_str(&format!("\n\n### {title}\n"));
+            if items.is_empty() {
+                markdown.push_str("\nNone found.");
+            }
+            for item in items {
+                markdown.push_str(&format!("\n- [ ] {item}"));
+            }
+        }
+        markdown
+    }
+
+    /// The callers as findings for formats like SARIF, at the risk of the whole change.
+    fn findings(&self) -> Vec<Annotation> {
+        self.callers
+            .iter()
+            .map(|caller| {
+                let (file, line) = match caller.location.rsplit_once(':') {
+                    Some((file, line)) => match line.parse() {
+                        Ok(line) => (file, Some(line)),
+                        Err(_) => (caller.location.as_str(), None),
+                    },
+                    None => (caller.location.as_str(), None),
+                };
+                Annotation {
+                    level: self.risk,
+                    file: file.to_string(),
+                    line,
+                    message: caller.reason.clone(),
+                    category: None,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Pull the impact assessment out of the AI response, ignoring any surrounding prose or
+/// code fences.
+pub fn extract_impact(ai_response: &str) -> Result<ImpactReport, LumenError> {
+    let json = json_object(ai_response).ok_or_else(|| {
+        LumenError::CommandError("AI response did not contain an impact assessment".to_string())
+    })?;
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Pull the JSON array of annotations out of the AI response, ignoring any surrounding
+/// prose or code fences.
+pub fn extract_annotations(ai_response: &str) -> Result<Vec<HunkAnnotation>, LumenError> {
+    let json = json_array(ai_response).ok_or_else(|| {
+        LumenError::CommandError("AI response did not contain hunk annotations".to_string())
+    })?;
+    Ok(serde_json::from_str(json)?)
+}
+
+/// Pull the JSON report out of the AI response. A response that isn't JSON at all is kept
+/// as the summary, so a model ignoring the format still produces usable output.
+pub fn extract_report(ai_response: &str) -> ExplainReport {
+    if let Some(Ok(report)) = json_object(ai_response).map(serde_json::from_str) {
+        return report;
+    }
+    ExplainReport {
+        summary: ai_response.trim().to_string(),
+        files: Vec::new(),
+    }
+}
+
+/// The commits on HEAD since it left `base`, oldest first: each one's short SHA and subject,
+/// with its body indented below. Merge commits are left out.
+pub fn branch_log(base: &str) -> Result<String, LumenError> {
+    let output = logging::output(Command::new("git").args([
+        "log",
+        "--reverse",
+        "--no-merges",
+        "--format=- %h %s%n%w(0,2,2)%b",
+        &format!("{base}..HEAD"),
+    ]))?;
+    if !output.status.success() {
+        return Err(LumenError::CommandError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    let log = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if log.is_empty() {
+        return Err(LumenError::InvalidArguments(format!(
+            "HEAD has no commits since {base}"
+        )));
+    }
+    Ok(log)
+}
+
+/// The SHAs in a list of commits, one per line as `git rev-list` prints them. Only the first
+/// word of a line counts, so `git log --oneline` works too; blank lines are skipped.
+pub fn parse_sha_list(text: &str) -> Vec<String> {
+    text.lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
 }
 
 impl ExplainCommand {
+    /// Commit SHA to cache responses under. Only single commits are cached: they can't change,
+    /// while working tree diffs and ranges of branch names can.
+    pub fn cache_key(&self) -> Option<&str> {
+        match &self.git_entity {
+            GitEntity::Commit(commit) if self.cache => Some(&commit.full_hash),
+            _ => None,
+        }
+    }
+
+    /// What the explain prompts are built from.
+    pub fn prompt_input(&self) -> ExplainInput<'_> {
+        let explained = match &self.git_entity {
+            GitEntity::Commit(commit) => Explained::Commit {
+                message: &commit.message,
+            },
+            GitEntity::Diff(Diff::WorkingTree { .. }) => Explained::WorkingTree,
+            GitEntity::Diff(Diff::CommitsRange { .. }) => Explained::Range,
+            GitEntity::Diff(Diff::Patch { .. }) => Explained::Patch,
+        };
+        ExplainInput {
+            explained,
+            diff: self.git_entity.diff(),
+            commits: self.commits.as_deref(),
+            query: self.query.as_deref(),
+            structured: self.format.structured(),
+            crates: self.crates.as_deref(),
+        }
+    }
+
     pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        LumenCommand::print_with_mdcat(self.git_entity.format_static_details(provider))?;
+        if !self.format.interactive() {
+            let report = self.report(provider).await?;
+            return self.format.emit(&report, self.webhook.as_ref()).await;
+        }
+
+        LumenCommand::print_markdown(&self.git_entity.format_static_details(provider));
+        if self.annotate {
+            return self.execute_annotate(provider).await;
+        }
         if let Some(query) = &self.query {
-            LumenCommand::print_with_mdcat(format!("`query`: {query}"))?;
+            LumenCommand::print_markdown(&format!("`query`: {query}"));
+        }
+        if self.impact {
+            let mut spinner = Spinner::new(spinners::Dots, "Assessing impact...", Color::Blue);
+            let report = extract_impact(&provider.impact(self).await?)?;
+            spinner.success("Done");
+            let markdown = report.to_markdown();
+            LumenCommand::print_markdown(&markdown);
+            if self.copy {
+                LumenCommand::copy_to_clipboard(&markdown);
+            }
+            return Ok(());
         }
 
         let spinner_text = match &self.query {
@@ -25,7 +289,375 @@ impl ExplainCommand {
         let result = provider.explain(self).await?;
         spinner.success("Done");
 
-        LumenCommand::print_with_mdcat(result)?;
+        let result = match &self.hook {
+            Some(hook) => self.run_hook(provider, hook, result).await?,
+            None => result,
+        };
+        LumenCommand::print_markdown(&result);
+        if self.copy {
+            LumenCommand::copy_to_clipboard(&result);
+        }
         Ok(())
     }
+
+    /// Pipe `result` through `hook`, generating a fresh explanation each time it's rejected.
+    async fn run_hook(
+        &self,
+        provider: &LumenProvider,
+        hook: &str,
+        mut result: String,
+    ) -> Result<String, LumenError> {
+        // Retries skip the cache, which would only hand back the rejected answer
+        let uncached = ExplainCommand {
+            git_entity: self.git_entity.clone(),
+            crates: self.crates.clone(),
+            query: self.query.clone(),
+            commits: self.commits.clone(),
+            annotate: self.annotate,
+            impact: self.impact,
+            cache: false,
+            format: self.format,
+            copy: false,
+            hook: None,
+            webhook: None,
+        };
+        let mut attempts = 1;
+        loop {
+            match run_hook(hook, &result)? {
+                HookOutcome::Accepted => return Ok(result),
+                HookOutcome::Rejected(reason) if attempts == MAX_HOOK_ATTEMPTS => {
+                    return Err(LumenError::CommandError(format!(
+                        "hook rejected {MAX_HOOK_ATTEMPTS} explanations: {reason}"
+                    )))
+                }
+                HookOutcome::Rejected(reason) => {
+                    eprintln!("Hook rejected the explanation, generating another: {reason}");
+                    let mut spinner = Spinner::new(spinners::Dots, "Regenerating...", Color::Blue);
+                    result = provider.explain(&uncached).await?;
+                    spinner.success("Done");
+                    attempts += 1;
+                }
+            }
+        }
+    }
+
+    /// The explanation for formats other than the terminal's, without spinners.
+    async fn report(&self, provider: &LumenProvider) -> Result<Report, LumenError> {
+        let mut data = json!({
+            "entity": self.git_entity.to_json(),
+            "provider": provider.to_string(),
+            "query": self.query,
+        });
+        let mut markdown = self.git_entity.format_static_details(provider);
+        if let Some(query) = &self.query {
+            markdown.push_str(&format!("\n\n`query`: {query}"));
+        }
+        let mut findings = Vec::new();
+
+        if self.annotate {
+            let hunks = parse_hunks(self.git_entity.diff());
+            let annotations = if hunks.is_empty() {
+                Vec::new()
+            } else {
+                extract_annotations(&provider.annotate(self, &hunks).await?)?
+            };
+            let mut entries = Vec::new();
+            for (i, hunk) in hunks.iter().enumerate() {
+                let note = annotations.iter().find(|a| a.hunk == i + 1);
+                markdown.push_str(&format!(
+                    "\n\n## {}\n```diff\n{}```",
+                    hunk.location(),
+                    hunk.text
+                ));
+                if let Some(note) = note {
+                    markdown.push_str(&format!("\n{}", note.note.trim()));
+                    findings.push(Annotation {
+                        level: RiskLevel::Low,
+                        file: hunk.file.clone(),
+                        line: Some(hunk.new_start),
+                        message: note.note.clone(),
+                        category: None,
+                    });
+                }
+                entries.push(json!({
+                    "file": hunk.file,
+                    "location": hunk.location(),
+                    "start": hunk.new_start,
+                    "lines": hunk.new_count,
+                    "note": note.map(|a| a.note.as_str()),
+                }));
+            }
+            data["hunks"] = entries.into();
+        } else if self.impact {
+            let report = extract_impact(&provider.impact(self).await?)?;
+            markdown.push_str(&format!("\n\n{}", report.to_markdown()));
+            findings = report.findings();
+            data["impact"] = serde_json::to_value(&report)?;
+        } else {
+            let response = provider.explain(self).await?;
+            let report = if self.format.structured() {
+                extract_report(&response)
+            } else {
+                // Prose, as the terminal gets it, so the hook sees the same thing
+                let response = match &self.hook {
+                    Some(hook) => self.run_hook(provider, hook, response).await?,
+                    None => response,
+                };
+                ExplainReport {
+                    summary: response.trim().to_string(),
+                    files: Vec::new(),
+                }
+            };
+            markdown.push_str(&format!("\n\n{}", report.summary));
+            for file in &report.files {
+                markdown.push_str(&format!("\n- `{}`: {}", file.path, file.note));
+                findings.push(Annotation {
+                    level: RiskLevel::Low,
+                    file: file.path.clone(),
+                    line: None,
+                    message: file.note.clone(),
+                    category: None,
+                });
+            }
+            data["summary"] = report.summary.into();
+            data["files"] = report
+                .files
+                .iter()
+                .map(|file| json!({ "path": file.path, "note": file.note }))
+                .collect::<Vec<Value>>()
+                .into();
+        }
+
+        Ok(Report::new("explain", "Explanation", markdown, data).with_findings(findings))
+    }
+
+    /// Print every hunk followed by its explanation.
+    async fn execute_annotate(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let hunks = parse_hunks(self.git_entity.diff());
+        if hunks.is_empty() {
+            println!("No changes to annotate.");
+            return Ok(());
+        }
+
+        let mut spinner = Spinner::new(spinners::Dots, "Annotating hunks...", Color::Blue);
+        let result = provider.annotate(self, &hunks).await?;
+        spinner.success("Done");
+        let annotations = extract_annotations(&result)?;
+
+        let colors = DiffColors::load();
+        let mut output = String::new();
+        for (i, hunk) in hunks.iter().enumerate() {
+            output.push_str(&format!(
+                "{}── {}{}\n",
+                colors.meta,
+                hunk.location(),
+                colors.reset
+            ));
+            for line in hunk.text.lines() {
+                let color = match line.chars().next() {
+                    Some('+') => &colors.new,
+                    Some('-') => &colors.old,
+                    Some('@') => &colors.frag,
+                    _ => "",
+                };
+                let reset = if color.is_empty() { "" } else { &colors.reset };
+                output.push_str(&format!("{color}{line}{reset}\n"));
+            }
+            if let Some(note) = annotations.iter().find(|a| a.hunk == i + 1) {
+                for line in note.note.lines() {
+                    output.push_str(&format!("{}▎ {line}{}\n", colors.note, colors.reset));
+                }
+            }
+            output.push('\n');
+        }
+        print_paged(&output)?;
+        Ok(())
+    }
+}
+
+/// `lumen explain` given several commits: each is explained on its own, then all of them
+/// together.
+pub struct ExplainCommitsCommand {
+    pub commits: Vec<Commit>,
+    /// Asked of the commits as a whole, in place of the overall summary
+    pub query: Option<String>,
+    pub cache: bool,
+    pub format: Format,
+    /// Copy the overall explanation to the clipboard once it's printed
+    pub copy: bool,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+}
+
+impl ExplainCommitsCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let interactive = self.format.interactive();
+        let total = self.commits.len();
+        let mut explained = Vec::with_capacity(total);
+        for (i, commit) in self.commits.iter().enumerate() {
+            // Each commit on its own is a plain summary, cached under its SHA like a single
+            // `lumen explain <sha>`
+            let single = ExplainCommand {
+                git_entity: GitEntity::Commit(commit.clone()),
+                crates: None,
+                query: None,
+                commits: None,
+                annotate: false,
+                impact: false,
+                cache: self.cache,
+                format: Format::default(),
+                copy: false,
+                hook: None,
+                webhook: None,
+            };
+            let summary = if interactive {
+                let mut spinner = Spinner::new(
+                    spinners::Dots,
+                    format!("Explaining commit {} of {total}...", i + 1),
+                    Color::Blue,
+                );
+                let summary = provider.explain(&single).await?;
+                spinner.success("Done");
+                LumenCommand::print_markdown(&format!("## {}\n\n{summary}", heading(commit)));
+                summary
+            } else {
+                provider.explain(&single).await?
+            };
+            explained.push((commit, summary.trim().to_string()));
+        }
+
+        let overview = explained
+            .iter()
+            .map(|(commit, summary)| format!("### {}\n\n{summary}", heading(commit)))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        let synthesis = if interactive {
+            let mut spinner = Spinner::new(spinners::Dots, "Tying them together...", Color::Blue);
+            let synthesis = provider
+                .explain_commits(&overview, self.query.as_deref())
+                .await?;
+            spinner.success("Done");
+            synthesis
+        } else {
+            provider
+                .explain_commits(&overview, self.query.as_deref())
+                .await?
+        };
+        let title = match &self.query {
+            Some(query) => format!("## {query}"),
+            None => "## Overall".to_string(),
+        };
+
+        if interactive {
+            LumenCommand::print_markdown(&format!("{title}\n\n{synthesis}"));
+            if self.copy {
+                LumenCommand::copy_to_clipboard(&synthesis);
+            }
+            return Ok(());
+        }
+
+        let mut markdown = format!("# Provider: {provider}");
+        for (commit, summary) in &explained {
+            markdown.push_str(&format!("\n\n## {}\n\n{summary}", heading(commit)));
+        }
+        markdown.push_str(&format!("\n\n{title}\n\n{}", synthesis.trim()));
+        let data = json!({
+            "provider": provider.to_string(),
+            "query": self.query,
+            "commits": explained
+                .iter()
+                .map(|(commit, summary)| json!({
+                    "sha": commit.full_hash,
+                    "subject": commit.message.lines().next().unwrap_or_default(),
+                    "summary": summary,
+                }))
+                .collect::<Vec<Value>>(),
+            "summary": synthesis.trim(),
+        });
+        let report = Report::new("explain", "Explanation", markdown, data);
+        self.format.emit(&report, self.webhook.as_ref()).await
+    }
+}
+
+/// `` `abc1234` subject `` for a commit's section.
+fn heading(commit: &Commit) -> String {
+    let short: String = commit.full_hash.chars().take(7).collect();
+    let subject = commit.message.lines().next().unwrap_or_default();
+    format!("`{short}` {subject}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_annotations_ignores_fences() {
+        let response = "```json\n[{\"hunk\": 1, \"note\": \"Adds a line\"}]\n```";
+        assert_eq!(
+            extract_annotations(response).unwrap(),
+            vec![HunkAnnotation {
+                hunk: 1,
+                note: "Adds a line".to_string()
+            }]
+        );
+        assert!(extract_annotations("no json here").is_err());
+    }
+
+    #[test]
+    fn test_extract_report() {
+        let response = "```json\n{\"summary\": \"Adds a flag\", \"files\": [{\"path\": \"src/cli.rs\", \"note\": \"New arg\"}]}\n```";
+        assert_eq!(
+            extract_report(response),
+            ExplainReport {
+                summary: "Adds a flag".to_string(),
+                files: vec![FileNote {
+                    path: "src/cli.rs".to_string(),
+                    note: "New arg".to_string()
+                }]
+            }
+        );
+        // Plain markdown is kept as the summary rather than failing
+        let report = extract_report("## Changes\nSomething {odd}\n");
+        assert_eq!(report.summary, "## Changes\nSomething {odd}");
+        assert!(report.files.is_empty());
+        // So is prose with a stray brace before the first opening one
+        assert_eq!(extract_report("a } then {b").summary, "a } then {b");
+    }
+
+    #[test]
+    fn test_impact_checklist() {
+        let report = extract_impact(
+            r#"```json
+            {"risk": "high", "summary": "Renames a public function.",
+             "apis": [{"item": "parse_config", "change": "renamed to load_config, breaking"}],
+             "callers": [{"location": "src/main.rs:42", "reason": "calls parse_config"},
+                         {"location": "the cli module", "reason": "re-exports it"}],
+             "tests": ["Load a config with unknown keys"]}
+            ```"#,
+        )
+        .unwrap();
+        assert_eq!(report.risk, RiskLevel::High);
+        assert!(report.migration.is_empty());
+
+        let markdown = report.to_markdown();
+        assert!(markdown.starts_with("## Impact: high risk\n\nRenames a public function."));
+        assert!(markdown.contains("- [ ] `parse_config`: renamed to load_config, breaking"));
+        assert!(markdown.contains("### Configuration and migration\n\nNone found."));
+        assert!(markdown.contains("- [ ] Load a config with unknown keys"));
+
+        let findings = report.findings();
+        assert_eq!(findings[0].file, "src/main.rs");
+        assert_eq!(findings[0].line, Some(42));
+        assert_eq!(findings[1].file, "the cli module");
+        assert_eq!(findings[1].line, None);
+
+        assert!(extract_impact("The change looks safe.").is_err());
+    }
+
+    #[test]
+    fn test_parse_sha_list() {
+        let text = "3f2a9c1e\n\nb41d07a Fix the parser\n  9e0c55d\n";
+        assert_eq!(parse_sha_list(text), ["3f2a9c1e", "b41d07a", "9e0c55d"]);
+        assert!(parse_sha_list("\n \n").is_empty());
+    }
 }
@@ -0,0 +1,406 @@
+//! `lumen explain --watch`: the explanation of the working tree in a panel that's redone
+//! whenever files change, to follow along with a long edit or an agent at work.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Local};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Paragraph, Wrap},
+};
+
+use super::diff::terminal::TerminalGuard;
+use super::diff::watcher::{setup_watcher, WatchEvent};
+use super::diff::{markdown_lines, theme};
+use super::{cargo_workspace, explain::ExplainCommand, output::Format};
+use crate::{
+    error::LumenError,
+    git_entity::{diff::Diff, GitEntity},
+    provider::LumenProvider,
+};
+
+/// How long the files have to stay unchanged before they're explained again, so a burst of
+/// saves costs one request
+const SETTLE: Duration = Duration::from_secs(2);
+/// How long after a run a change inside `.git` is taken to be `git diff` refreshing the index
+const ECHO_WINDOW: Duration = Duration::from_secs(1);
+/// How often the panel checks for finished explanations between key presses
+const TICK: Duration = Duration::from_millis(200);
+
+pub struct WatchOptions {
+    /// Explain the staged changes instead of the unstaged ones
+    pub staged: bool,
+    /// Only explain and watch these files or directories
+    pub paths: Vec<String>,
+    /// A question to ask about the changes instead of summarizing them
+    pub query: Option<String>,
+    pub provider: Arc<LumenProvider>,
+}
+
+/// How a run in the background ended
+enum Outcome {
+    Explained {
+        diff: String,
+        text: String,
+    },
+    /// The diff is the one that's already explained
+    Unchanged,
+    /// There's no diff to explain, with the reason, like "no changes in the working tree"
+    NoChanges(String),
+    Failed(String),
+}
+
+/// What the panel shows, and when to explain again.
+struct Watch {
+    /// The latest explanation, kept on screen while the next one is on its way
+    summary: Option<String>,
+    /// The diff `summary` explains, so changes that don't touch it aren't explained again
+    explained_diff: Option<String>,
+    files: usize,
+    updated: Option<DateTime<Local>>,
+    /// Why there's nothing new to show, shown in place of the status
+    problem: Option<String>,
+    /// When the latest change not explained yet was seen
+    changed_at: Option<Instant>,
+    running: bool,
+    finished_at: Option<Instant>,
+    scroll: u16,
+}
+
+impl Watch {
+    /// A panel that explains the working tree right away.
+    fn new(now: Instant) -> Self {
+        Watch {
+            summary: None,
+            explained_diff: None,
+            files: 0,
+            updated: None,
+            problem: None,
+            changed_at: Some(now.checked_sub(SETTLE).unwrap_or(now)),
+            running: false,
+            finished_at: None,
+            scroll: 0,
+        }
+    }
+
+    /// Note that files changed. Index writes right after a run are our own `git diff`
+    /// refreshing it and are left out, unless staged changes are explained.
+    fn changed(&mut self, event: &WatchEvent, staged: bool, now: Instant) {
+        let echo = self
+            .finished_at
+            .is_some_and(|finished| now.duration_since(finished) < ECHO_WINDOW);
+        if event.is_git_internal() && !staged && (self.running || echo) {
+            return;
+        }
+        self.changed_at = Some(now);
+    }
+
+    /// Explain again even if the diff is the same.
+    fn refresh(&mut self, now: Instant) {
+        self.explained_diff = None;
+        self.changed_at = Some(now.checked_sub(SETTLE).unwrap_or(now));
+    }
+
+    /// Whether the changes have settled and nothing is being explained.
+    fn due(&self, now: Instant) -> bool {
+        !self.running
+            && self
+                .changed_at
+                .is_some_and(|changed| now.duration_since(changed) >= SETTLE)
+    }
+
+    fn start(&mut self) {
+        self.running = true;
+        self.changed_at = None;
+    }
+
+    fn finish(&mut self, outcome: Outcome, now: Instant) {
+        self.running = false;
+        self.finished_at = Some(now);
+        match outcome {
+            Outcome::Explained { diff, text } => {
+                self.files = changed_files(&diff);
+                self.explained_diff = Some(diff);
+                self.summary = Some(text);
+                self.updated = Some(Local::now());
+                self.problem = None;
+                self.scroll = 0;
+            }
+            Outcome::Unchanged => {}
+            Outcome::NoChanges(reason) => {
+                self.summary = None;
+                self.explained_diff = None;
+                self.files = 0;
+                self.problem = Some(reason);
+            }
+            // The last explanation stays up, it's only out of date
+            Outcome::Failed(error) => self.problem = Some(error),
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let t = theme::get();
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(frame.area());
+
+        let block = Block::default()
+            .title(" Working tree ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+        let body = match &self.summary {
+            Some(summary) => Text::from(markdown_lines(summary)),
+            None => Text::styled(
+                if self.running || self.problem.is_none() {
+                    "Explaining the changes…"
+                } else {
+                    "Waiting for changes…"
+                },
+                Style::default().fg(t.ui.text_muted),
+            ),
+        };
+        frame.render_widget(
+            Paragraph::new(body)
+                .block(block)
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll, 0)),
+            rows[0],
+        );
+
+        let status = if self.running {
+            Span::styled(" explaining…", Style::default().fg(t.ui.watching))
+        } else if let Some(problem) = &self.problem {
+            Span::styled(format!(" {problem}"), Style::default().fg(t.ui.risk_high))
+        } else if self.changed_at.is_some() {
+            Span::styled(
+                " changes seen, waiting for them to settle",
+                Style::default().fg(t.ui.watching),
+            )
+        } else {
+            let updated = self
+                .updated
+                .map_or(String::new(), |at| at.format("%H:%M:%S").to_string());
+            let files = if self.files == 1 { "file" } else { "files" };
+            Span::styled(
+                format!(" updated {updated} · {} {files} changed", self.files),
+                Style::default().fg(t.ui.text_secondary),
+            )
+        };
+        let footer = Line::from(vec![
+            status,
+            Span::styled(
+                "  ·  j/k scroll · r refresh · q quit",
+                Style::default().fg(t.ui.text_muted),
+            ),
+        ]);
+        frame.render_widget(Paragraph::new(footer), rows[1]);
+    }
+}
+
+/// How many files `diff` changes.
+fn changed_files(diff: &str) -> usize {
+    diff.lines()
+        .filter(|line| line.starts_with("diff --git "))
+        .count()
+}
+
+/// Explain the working tree in the background unless its diff is still `previous`.
+fn explain_in_background(options: &WatchOptions, previous: Option<String>) -> Receiver<Outcome> {
+    let (tx, rx) = mpsc::channel();
+    let provider = Arc::clone(&options.provider);
+    let (staged, paths, query) = (options.staged, options.paths.clone(), options.query.clone());
+    thread::spawn(move || {
+        let diff = match Diff::from_working_tree(staged, &paths) {
+            Ok(diff) => diff,
+            Err(e) => {
+                let _ = tx.send(Outcome::NoChanges(e.to_string()));
+                return;
+            }
+        };
+        let git_entity = GitEntity::Diff(diff);
+        let text = git_entity.diff().to_string();
+        if previous.as_deref() == Some(text.as_str()) {
+            let _ = tx.send(Outcome::Unchanged);
+            return;
+        }
+
+        let command = ExplainCommand {
+            crates: cargo_workspace::api_section(&git_entity),
+            git_entity,
+            query,
+            commits: None,
+            annotate: false,
+            impact: false,
+            cache: false,
+            format: Format::default(),
+            copy: false,
+            hook: None,
+            webhook: None,
+        };
+        let result = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| e.to_string())
+            .and_then(|runtime| {
+                runtime
+                    .block_on(provider.explain(&command))
+                    .map_err(|e| e.to_string())
+            });
+        let _ = tx.send(match result {
+            Ok(explanation) => Outcome::Explained {
+                diff: text,
+                text: explanation,
+            },
+            Err(e) => Outcome::Failed(e),
+        });
+    });
+    rx
+}
+
+/// Explain the working tree and keep the explanation current until the user quits.
+pub fn run(options: WatchOptions) -> Result<(), LumenError> {
+    let watch_paths: Vec<PathBuf> = options.paths.iter().map(PathBuf::from).collect();
+    let events = setup_watcher(&watch_paths).map_err(LumenError::CommandError)?;
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    let mut watch = Watch::new(Instant::now());
+    let mut job: Option<Receiver<Outcome>> = None;
+    loop {
+        while let Ok(event) = events.try_recv() {
+            watch.changed(&event, options.staged, Instant::now());
+        }
+        if let Some(rx) = &job {
+            let outcome = match rx.try_recv() {
+                Ok(outcome) => Some(outcome),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => {
+                    Some(Outcome::Failed("the explanation stopped".to_string()))
+                }
+            };
+            if let Some(outcome) = outcome {
+                watch.finish(outcome, Instant::now());
+                job = None;
+            }
+        }
+        if watch.due(Instant::now()) {
+            job = Some(explain_in_background(
+                &options,
+                watch.explained_diff.clone(),
+            ));
+            watch.start();
+        }
+
+        terminal.draw(|frame| watch.render(frame))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if ctrl => return Ok(()),
+            KeyCode::Char('r') => watch.refresh(Instant::now()),
+            KeyCode::Char('j') | KeyCode::Down => watch.scroll = watch.scroll.saturating_add(1),
+            KeyCode::Char('k') | KeyCode::Up => watch.scroll = watch.scroll.saturating_sub(1),
+            KeyCode::Char('d') if ctrl => watch.scroll = watch.scroll.saturating_add(10),
+            KeyCode::Char('u') if ctrl => watch.scroll = watch.scroll.saturating_sub(10),
+            KeyCode::PageDown => watch.scroll = watch.scroll.saturating_add(10),
+            KeyCode::PageUp => watch.scroll = watch.scroll.saturating_sub(10),
+            KeyCode::Char('g') | KeyCode::Home => watch.scroll = 0,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(paths: &[&str]) -> WatchEvent {
+        WatchEvent {
+            changed_files: paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_changes_are_explained_once_they_settle() {
+        let start = Instant::now();
+        let mut watch = Watch::new(start);
+        assert!(watch.due(start), "the first explanation starts right away");
+        watch.start();
+        assert!(!watch.due(start + SETTLE));
+
+        let diff = "diff --git a/a.rs b/a.rs\n+x\ndiff --git a/b.rs b/b.rs\n+y\n".to_string();
+        let done = start + Duration::from_secs(5);
+        watch.finish(
+            Outcome::Explained {
+                diff: diff.clone(),
+                text: "Adds x and y".to_string(),
+            },
+            done,
+        );
+        assert_eq!(watch.files, 2);
+        assert_eq!(watch.explained_diff, Some(diff));
+
+        // `git diff` refreshing the index right after a run isn't a change
+        watch.changed(&event(&[".git/index"]), false, done);
+        assert!(watch.changed_at.is_none());
+        watch.changed(&event(&[".git/index"]), true, done);
+        assert!(watch.changed_at.is_some());
+
+        let saved = done + Duration::from_secs(3);
+        watch.changed(&event(&["src/a.rs"]), false, saved);
+        assert!(!watch.due(saved + Duration::from_secs(1)));
+        assert!(watch.due(saved + SETTLE));
+    }
+
+    #[test]
+    fn test_failures_keep_the_last_explanation() {
+        let now = Instant::now();
+        let mut watch = Watch::new(now);
+        watch.start();
+        watch.finish(
+            Outcome::Explained {
+                diff: "diff --git a/a.rs b/a.rs\n".to_string(),
+                text: "Adds a".to_string(),
+            },
+            now,
+        );
+
+        watch.start();
+        watch.finish(Outcome::Failed("rate limited".to_string()), now);
+        assert_eq!(watch.summary.as_deref(), Some("Adds a"));
+        assert_eq!(watch.problem.as_deref(), Some("rate limited"));
+
+        watch.start();
+        watch.finish(Outcome::Unchanged, now);
+        assert_eq!(watch.summary.as_deref(), Some("Adds a"));
+
+        watch.refresh(now);
+        assert!(watch.explained_diff.is_none());
+        assert!(watch.due(now));
+
+        watch.start();
+        watch.finish(
+            Outcome::NoChanges("no changes in the working tree".to_string()),
+            now,
+        );
+        assert!(watch.summary.is_none());
+        assert_eq!(watch.files, 0);
+    }
+}
@@ -0,0 +1,116 @@
+//! A `prepare-commit-msg` hook that fills in the commit message with `lumen draft`, so a
+//! plain `git commit` opens the editor with a drafted message.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::error::LumenError;
+use crate::logging;
+
+/// Marks a hook as written by lumen, so it's only ever replaced or removed by lumen
+const MARKER: &str = "# Installed by `lumen hook install`";
+
+/// Drafts only for a plain `git commit`: git passes a second argument when the message
+/// comes from `-m`, `-F`, a template, a merge, a squash or an amended commit. A failed
+/// draft never blocks the commit.
+const SCRIPT: &str = r#"#!/bin/sh
+# Installed by `lumen hook install`; remove it with `lumen hook uninstall`
+[ -z "$2" ] || exit 0
+lumen draft --hook "$1" || echo "lumen: couldn't draft a commit message" >&2
+exit 0
+"#;
+
+pub enum HookAction {
+    Install { force: bool },
+    Uninstall,
+}
+
+pub struct HookCommand {
+    pub action: HookAction,
+}
+
+/// Where git looks for the hook, following `core.hooksPath`.
+fn hook_path() -> Result<PathBuf, String> {
+    let output = logging::output(Command::new("git").args([
+        "rev-parse",
+        "--git-path",
+        "hooks/prepare-commit-msg",
+    ]))
+    .map_err(|e| format!("failed to run git: {e}"))?;
+    if output.status.success() {
+        Ok(PathBuf::from(
+            String::from_utf8_lossy(&output.stdout).trim(),
+        ))
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+fn is_ours(script: &str) -> bool {
+    script.lines().any(|line| line.starts_with(MARKER))
+}
+
+impl HookCommand {
+    pub fn execute(&self) -> Result<(), LumenError> {
+        match &self.action {
+            HookAction::Install { force } => Self::install(*force),
+            HookAction::Uninstall => Self::uninstall(),
+        }
+        .map_err(LumenError::CommandError)
+    }
+
+    fn install(force: bool) -> Result<(), String> {
+        let path = hook_path()?;
+        if let Ok(existing) = fs::read_to_string(&path) {
+            if !force && !is_ours(&existing) {
+                return Err(format!(
+                    "{} already exists (use --force to replace it)",
+                    path.display()
+                ));
+            }
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+        }
+        fs::write(&path, SCRIPT).map_err(|e| format!("failed to write {}: {e}", path.display()))?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                .map_err(|e| format!("failed to make {} executable: {e}", path.display()))?;
+        }
+        println!("Installed {}.", path.display());
+        println!("`git commit` now opens the editor with a drafted message.");
+        Ok(())
+    }
+
+    fn uninstall() -> Result<(), String> {
+        let path = hook_path()?;
+        let Ok(existing) = fs::read_to_string(&path) else {
+            println!("No prepare-commit-msg hook to remove.");
+            return Ok(());
+        };
+        if !is_ours(&existing) {
+            return Err(format!(
+                "{} wasn't installed by lumen; leaving it alone",
+                path.display()
+            ));
+        }
+        fs::remove_file(&path).map_err(|e| format!("failed to remove {}: {e}", path.display()))?;
+        println!("Removed {}.", path.display());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_our_hook_is_ours() {
+        assert!(is_ours(SCRIPT));
+        assert!(!is_ours("#!/bin/sh\nexec commitizen --hook\n"));
+    }
+}
@@ -0,0 +1,101 @@
+//! Match the user's git setup for plain (non-TUI) output: `color.*` settings and the pager.
+
+use std::io::{self, IsTerminal, Write};
+use std::process::Stdio;
+
+use crate::shell;
+use crate::vcs::git;
+
+/// ANSI sequences for each part of a diff, as configured with `color.diff.<slot>`.
+/// All empty when color is off.
+pub struct DiffColors {
+    pub meta: String,
+    pub frag: String,
+    pub old: String,
+    pub new: String,
+    /// `color.lumen.note`, for AI annotations shown alongside the diff
+    pub note: String,
+    pub reset: String,
+}
+
+impl DiffColors {
+    /// Colors for output written to stdout, honoring `color.ui` / `color.diff`.
+    pub fn load() -> Self {
+        let enabled = git(&[
+            "config",
+            "--get-colorbool",
+            "color.diff",
+            if io::stdout().is_terminal() {
+                "true"
+            } else {
+                "false"
+            },
+        ])
+        .is_ok_and(|value| value.trim() == "true");
+
+        if !enabled {
+            return DiffColors {
+                meta: String::new(),
+                frag: String::new(),
+                old: String::new(),
+                new: String::new(),
+                note: String::new(),
+                reset: String::new(),
+            };
+        }
+
+        DiffColors {
+            meta: color("color.diff.meta", "bold"),
+            frag: color("color.diff.frag", "cyan"),
+            old: color("color.diff.old", "red"),
+            new: color("color.diff.new", "green"),
+            note: color("color.lumen.note", "yellow"),
+            reset: "\x1b[m".to_string(),
+        }
+    }
+}
+
+fn color(slot: &str, default: &str) -> String {
+    git(&["config", "--get-color", slot, default]).unwrap_or_default()
+}
+
+/// Print `content` through git's pager (`GIT_PAGER`, `core.pager`, `PAGER`, then `less`) when
+/// stdout is a terminal, with the same `LESS` defaults git uses.
+pub fn print_paged(content: &str) -> io::Result<()> {
+    let pager = if io::stdout().is_terminal() {
+        git(&["var", "GIT_PAGER"])
+            .ok()
+            .map(|pager| pager.trim().to_string())
+            .filter(|pager| !pager.is_empty() && pager != "cat")
+    } else {
+        None
+    };
+
+    let Some(pager) = pager else {
+        print!("{content}");
+        return io::stdout().flush();
+    };
+
+    let mut command = shell::command(&pager);
+    command.stdin(Stdio::piped());
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    if std::env::var_os("LV").is_none() {
+        command.env("LV", "-c");
+    }
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(_) => {
+            print!("{content}");
+            return io::stdout().flush();
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // The pager may exit before reading everything (e.g. `q` in less)
+        let _ = stdin.write_all(content.as_bytes());
+    }
+    child.wait()?;
+    Ok(())
+}
@@ -0,0 +1,430 @@
+//! `lumen lint-msg`: commit messages checked against the configured conventions, e.g. from a
+//! `commit-msg` hook.
+//!
+//! Local rules need no provider: the conventional `type(scope): description` header with the
+//! types and scopes `draft` is configured with, the subject length, the imperative mood, a
+//! blank line before the body and `draft.forbidden_words`. `--ai` adds a style pass on top,
+//! whose remarks are warnings.
+
+use std::fmt;
+use std::process::Command;
+
+use lumen_core::response::json_object;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use spinoff::{spinners, Color, Spinner};
+
+use crate::config::configuration::{DraftConfig, TicketPlacement};
+use crate::error::LumenError;
+use crate::logging;
+use crate::provider::LumenProvider;
+
+use super::conventions::{imperative, looks_inflected, uses_word, SUBJECT_RECOMMENDED_LEN};
+use super::draft::Taxonomy;
+
+static HEADER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^(?P<type>[A-Za-z][\w-]*)(?:\((?P<scope>[^()]*)\))?!?: (?P<description>.*)$")
+        .expect("header pattern is valid")
+});
+
+/// Subjects longer than this fail; drafts are held to `draft.subject_max_length` instead
+const SUBJECT_MAX_LEN: usize = 72;
+
+/// The line `git commit --verbose` puts above the diff; everything from it on is dropped
+const SCISSORS: &str = "# ------------------------ >8 ------------------------";
+
+/// Subjects git writes itself, which don't follow the conventions
+const GENERATED: [&str; 5] = ["Merge ", "Revert \"", "fixup! ", "squash! ", "amend! "];
+
+/// Where the messages to check come from
+pub enum MessageSource {
+    /// A message given on the command line or stdin
+    Text(String),
+    /// The file git passes a `commit-msg` hook
+    File(std::path::PathBuf),
+    /// The commits of a revision range, like `main..HEAD`
+    Range(String),
+}
+
+pub struct LintMsgCommand {
+    pub source: MessageSource,
+    pub draft_config: DraftConfig,
+    /// Fail on warnings too, not only errors
+    pub strict: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Warning,
+    Error,
+}
+
+/// Something a message does against the conventions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub level: Level,
+    pub text: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.level {
+            Level::Error => "error",
+            Level::Warning => "warning",
+        };
+        write!(f, "{label}: {}", self.text)
+    }
+}
+
+fn error(text: String) -> Violation {
+    Violation {
+        level: Level::Error,
+        text,
+    }
+}
+
+fn warning(text: String) -> Violation {
+    Violation {
+        level: Level::Warning,
+        text,
+    }
+}
+
+/// The conventions from the `draft` config, which is what drafted messages follow too
+pub struct Rules {
+    taxonomy: Taxonomy,
+    forbidden_words: Vec<String>,
+    /// Finds a ticket written before the header, which the header rules skip
+    ticket_prefix: Option<Regex>,
+}
+
+impl Rules {
+    pub fn new(config: &DraftConfig) -> Self {
+        let ticket_prefix = config
+            .ticket
+            .as_ref()
+            .filter(|ticket| ticket.placement == TicketPlacement::Prefix)
+            .and_then(|ticket| Regex::new(&format!(r"^(?:{}):?\s+", ticket.pattern)).ok());
+        Rules {
+            taxonomy: Taxonomy::new(config),
+            forbidden_words: config.forbidden_words.clone(),
+            ticket_prefix,
+        }
+    }
+
+    /// Check one message, as cleaned up by [`clean`].
+    pub fn lint(&self, message: &str) -> Vec<Violation> {
+        let mut lines = message.lines();
+        let subject = lines.next().unwrap_or_default().trim_end();
+        if subject.trim().is_empty() {
+            return vec![error("the message is empty".to_string())];
+        }
+        if GENERATED.iter().any(|prefix| subject.starts_with(prefix)) {
+            return Vec::new();
+        }
+
+        let mut violations = Vec::new();
+        let length = subject.chars().count();
+        if length > SUBJECT_MAX_LEN {
+            violations.push(error(format!(
+                "the subject is {length} characters, over the limit of {SUBJECT_MAX_LEN}"
+            )));
+        } else if length > SUBJECT_RECOMMENDED_LEN {
+            violations.push(warning(format!(
+                "the subject is {length} characters; {SUBJECT_RECOMMENDED_LEN} or fewer reads best in `git log --oneline`"
+            )));
+        }
+        violations.extend(self.lint_header(subject));
+
+        if lines.next().is_some_and(|line| !line.trim().is_empty()) {
+            violations.push(error(
+                "the subject needs a blank line between it and the body".to_string(),
+            ));
+        }
+
+        let body = message.split_once('\n').map_or("", |(_, body)| body);
+        for word in self
+            .forbidden_words
+            .iter()
+            .map(|w| w.trim())
+            .filter(|w| !w.is_empty())
+        {
+            for (part, text) in [("subject", subject), ("body", body)] {
+                if uses_word(text, word) {
+                    violations.push(error(format!(
+                        "the {part} uses \"{word}\", which `draft.forbidden_words` rules out"
+                    )));
+                }
+            }
+        }
+        violations
+    }
+
+    fn lint_header(&self, subject: &str) -> Vec<Violation> {
+        let mut header = subject;
+        if let Some(prefix) = self.ticket_prefix.as_ref().and_then(|t| t.find(header)) {
+            header = &header[prefix.end()..];
+        }
+        if let Some(rest) = self
+            .taxonomy
+            .emojis
+            .values()
+            .find_map(|emoji| header.strip_prefix(emoji.as_str()))
+        {
+            header = rest.trim_start();
+        }
+
+        let Some(captures) = HEADER.captures(header) else {
+            return vec![error(
+                "the subject doesn't follow `type(scope): description`".to_string(),
+            )];
+        };
+        let mut violations = Vec::new();
+        let kind = &captures["type"];
+        let types = &self.taxonomy.types;
+        if !types.is_empty() && !types.iter().any(|t| t == kind) {
+            let mut types = types.clone();
+            types.sort();
+            violations.push(error(format!(
+                "unknown type `{kind}`, expected one of: {}",
+                types.join(", ")
+            )));
+        }
+        if let Some(scope) = captures.name("scope").map(|scope| scope.as_str()) {
+            let scopes = &self.taxonomy.scopes;
+            if scope.trim().is_empty() || scope.contains(char::is_whitespace) {
+                violations.push(error(format!("invalid scope `{scope}`")));
+            } else if !scopes.is_empty() && !scopes.iter().any(|s| s == scope) {
+                violations.push(error(format!(
+                    "unknown scope `{scope}`, expected one of: {}",
+                    scopes.join(", ")
+                )));
+            }
+        }
+
+        let description = &captures["description"];
+        if description.trim().is_empty() {
+            violations.push(error("the subject has no description".to_string()));
+            return violations;
+        }
+        if description.ends_with('.') && !description.ends_with("..") {
+            violations.push(error(
+                "the subject ends with a period; leave it out".to_string(),
+            ));
+        }
+        let first = description.split(' ').next().unwrap_or_default();
+        if let Some(verb) = imperative(first) {
+            violations.push(error(format!(
+                "\"{first}\" should be \"{verb}\" (subjects use the imperative mood)"
+            )));
+        } else if looks_inflected(first) {
+            violations.push(warning(format!(
+                "\"{first}\" may not be in the imperative mood (\"add\", not \"added\" or \"adding\")"
+            )));
+        }
+        violations
+    }
+}
+
+/// A message as git would store it: without comment lines, anything below the scissors line
+/// of `git commit --verbose`, or trailing blank lines.
+pub fn clean(message: &str) -> String {
+    let mut lines = Vec::new();
+    for line in message.lines() {
+        if line == SCISSORS {
+            break;
+        }
+        if !line.starts_with('#') {
+            lines.push(line.trim_end());
+        }
+    }
+    let start = lines
+        .iter()
+        .position(|line| !line.is_empty())
+        .unwrap_or(lines.len());
+    lines[start..].join("\n").trim_end().to_string()
+}
+
+/// Each commit of `range` with its message, oldest first. Merges are left out; their
+/// messages are git's.
+fn range_messages(range: &str) -> Result<Vec<(String, String)>, LumenError> {
+    let output = logging::output(Command::new("git").args([
+        "log",
+        "--no-merges",
+        "--reverse",
+        "--format=%H%x1f%B%x1e",
+        range,
+        "--",
+    ]))?;
+    if !output.status.success() {
+        return Err(LumenError::CommandError(format!(
+            "couldn't read the commits of {range}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8(output.stdout)?
+        .split('\x1e')
+        .filter_map(|record| {
+            let (sha, message) = record.trim_start_matches('\n').split_once('\x1f')?;
+            Some((sha.to_string(), message.to_string()))
+        })
+        .collect())
+}
+
+#[derive(Debug, Deserialize)]
+struct StyleReview {
+    #[serde(default)]
+    problems: Vec<String>,
+}
+
+/// The style remarks in the AI response, ignoring any surrounding prose or code fences.
+fn extract_problems(ai_response: &str) -> Result<Vec<String>, LumenError> {
+    let json = json_object(ai_response).ok_or_else(|| {
+        LumenError::CommandError("AI response did not contain a style review".to_string())
+    })?;
+    Ok(serde_json::from_str::<StyleReview>(json)?.problems)
+}
+
+impl LintMsgCommand {
+    /// Check the messages, asking `provider` for a style review of each too when given one.
+    pub async fn execute(&self, provider: Option<&LumenProvider>) -> Result<(), LumenError> {
+        let messages = match &self.source {
+            MessageSource::Text(text) => vec![(None, clean(text))],
+            MessageSource::File(path) => vec![(None, clean(&std::fs::read_to_string(path)?))],
+            MessageSource::Range(range) => range_messages(range)?
+                .into_iter()
+                .map(|(sha, message)| (Some(sha), clean(&message)))
+                .collect(),
+        };
+        if messages.is_empty() {
+            println!("No commits to check.");
+            return Ok(());
+        }
+
+        let rules = Rules::new(&self.draft_config);
+        let threshold = if self.strict {
+            Level::Warning
+        } else {
+            Level::Error
+        };
+        let mut found = 0;
+        for (sha, message) in &messages {
+            let mut violations = rules.lint(message);
+            if let Some(provider) = provider.filter(|_| !message.is_empty()) {
+                let mut spinner =
+                    Spinner::new(spinners::Dots, "Reviewing message style...", Color::Blue);
+                let response = provider.lint_message(message, sha.as_deref()).await?;
+                spinner.clear();
+                violations.extend(extract_problems(&response)?.into_iter().map(warning));
+            }
+            found += violations.iter().filter(|v| v.level >= threshold).count();
+
+            let indent = match sha {
+                Some(sha) if !violations.is_empty() => {
+                    let subject = message.lines().next().unwrap_or_default();
+                    println!("{} {subject}", &sha[..sha.len().min(7)]);
+                    "  "
+                }
+                _ => "",
+            };
+            for violation in &violations {
+                println!("{indent}{violation}");
+            }
+        }
+
+        if found > 0 {
+            return Err(LumenError::LintFailed { found });
+        }
+        match messages.len() {
+            1 => println!("The commit message follows the conventions."),
+            count => println!("All {count} commit messages follow the conventions."),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::configuration::TicketConfig;
+
+    fn rules() -> Rules {
+        Rules::new(&DraftConfig {
+            commit_types: r#"{"feat": "", "fix": "", "docs": ""}"#.to_string(),
+            scopes: vec!["cli".to_string(), "diff".to_string()],
+            forbidden_words: vec!["WIP".to_string()],
+            ticket: Some(TicketConfig {
+                pattern: r"[A-Z]+-\d+".to_string(),
+                placement: TicketPlacement::Prefix,
+                format: None,
+            }),
+            ..DraftConfig::default()
+        })
+    }
+
+    fn lint(message: &str) -> Vec<String> {
+        rules()
+            .lint(message)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_conforming_messages_pass() {
+        assert!(lint("feat(cli): add lint-msg command").is_empty());
+        assert!(lint("fix!: handle empty diff\n\nThe viewer crashed.").is_empty());
+        assert!(lint("PROJ-12: docs: describe hooks").is_empty());
+        assert!(lint("Merge branch 'main' into topic").is_empty());
+        assert!(lint("fixup! feat: whatever").is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        assert_eq!(
+            lint("feat(core): Added parser.\nWIP"),
+            vec![
+                "error: unknown scope `core`, expected one of: cli, diff",
+                "error: the subject ends with a period; leave it out",
+                "error: \"Added\" should be \"add\" (subjects use the imperative mood)",
+                "error: the subject needs a blank line between it and the body",
+                "error: the body uses \"WIP\", which `draft.forbidden_words` rules out",
+            ]
+        );
+        assert_eq!(
+            lint("update readme"),
+            vec!["error: the subject doesn't follow `type(scope): description`"]
+        );
+        assert_eq!(
+            lint("chore: tweaked colors"),
+            vec![
+                "error: unknown type `chore`, expected one of: docs, feat, fix",
+                "warning: \"tweaked\" may not be in the imperative mood (\"add\", not \"added\" or \"adding\")",
+            ]
+        );
+        let long = format!("feat: {}", "x".repeat(60));
+        assert_eq!(rules().lint(&long)[0].level, Level::Warning);
+        let longer = format!("feat: {}", "x".repeat(70));
+        assert_eq!(rules().lint(&longer)[0].level, Level::Error);
+        assert_eq!(lint(""), vec!["error: the message is empty"]);
+    }
+
+    #[test]
+    fn test_clean() {
+        let message = "\n# Please enter the commit message\nfeat: add x\n\nBody\n\n\
+                       # On branch main\n# ------------------------ >8 ------------------------\n\
+                       diff --git a/x b/x\n";
+        assert_eq!(clean(message), "feat: add x\n\nBody");
+    }
+
+    #[test]
+    fn test_extract_problems() {
+        let response = "```json\n{\"problems\": [\"The subject is vague\"]}\n```";
+        assert_eq!(
+            extract_problems(response).unwrap(),
+            vec!["The subject is vague"]
+        );
+        assert!(extract_problems("{}").unwrap().is_empty());
+    }
+}
@@ -4,17 +4,28 @@ use crate::{
     provider::LumenProvider,
 };
 
-use super::{explain::ExplainCommand, LumenCommand};
+use super::{cargo_workspace, explain::ExplainCommand, output::Format, oversized, LumenCommand};
 
 pub struct ListCommand;
 
 impl ListCommand {
     pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        let sha = LumenCommand::get_sha_from_fzf()?;
-        let git_entity = GitEntity::Commit(Commit::new(sha)?);
+        let sha = LumenCommand::pick_commit()?;
+        let mut git_entity = GitEntity::Commit(Commit::new(sha)?);
+        oversized::confirm_exclusions(&mut git_entity)?;
+        let crates = cargo_workspace::api_section(&git_entity);
         ExplainCommand {
             git_entity,
+            crates,
             query: None,
+            commits: None,
+            annotate: false,
+            impact: false,
+            cache: true,
+            format: Format::default(),
+            copy: false,
+            hook: None,
+            webhook: None,
         }
         .execute(provider)
         .await
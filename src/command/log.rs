@@ -0,0 +1,503 @@
+//! `lumen log`: the commit history with its graph, filterable, opening commits in the diff
+//! viewer or explaining them without leaving the browser.
+
+use std::io::{self, BufRead, Write};
+use std::process::Command;
+use std::sync::Arc;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{prelude::*, widgets::Paragraph};
+
+use super::diff::terminal::TerminalGuard;
+use super::diff::{self, fuzzy_match, theme, DiffOptions};
+use super::{cargo_workspace, explain::ExplainCommand, output::Format, oversized};
+use crate::{
+    commit_reference::CommitReference,
+    config::configuration::{DiffConfig, DraftConfig},
+    error::LumenError,
+    git_entity::{commit::Commit, GitEntity},
+    logging,
+    provider::LumenProvider,
+};
+
+/// Commits loaded into the browser, newest first
+const MAX_COMMITS: usize = 5000;
+/// Separates the graph and the fields of each `git log` line
+const FIELD_SEP: char = '\x1f';
+
+pub struct LogOptions {
+    /// Revisions to show the history of, HEAD when empty
+    pub revisions: Vec<String>,
+    /// Show every branch and tag instead
+    pub all: bool,
+    pub provider: Arc<LumenProvider>,
+    /// View settings for the diff viewer
+    pub settings: DiffConfig,
+    /// Shell hook explanations are piped through
+    pub hook: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+struct LogCommit {
+    sha: String,
+    short: String,
+    subject: String,
+    author: String,
+    date: String,
+    /// Branches and tags pointing at the commit, as `git log --format=%D` lists them
+    refs: String,
+}
+
+/// A line of `git log --graph`: a commit, or only graph edges between commits
+#[derive(Debug, PartialEq)]
+struct Row {
+    graph: String,
+    commit: Option<LogCommit>,
+}
+
+/// Parse `git log --graph` output whose format starts each commit's fields with `\x1f`.
+fn parse_graph(output: &str) -> Vec<Row> {
+    output
+        .lines()
+        .map(|line| {
+            let Some((graph, fields)) = line.split_once(FIELD_SEP) else {
+                return Row {
+                    graph: line.trim_end().to_string(),
+                    commit: None,
+                };
+            };
+            let mut fields = fields.split(FIELD_SEP).map(str::to_string);
+            let mut next = || fields.next().unwrap_or_default();
+            Row {
+                graph: graph.to_string(),
+                commit: Some(LogCommit {
+                    sha: next(),
+                    short: next(),
+                    subject: next(),
+                    author: next(),
+                    date: next(),
+                    refs: next(),
+                }),
+            }
+        })
+        .collect()
+}
+
+fn load_graph(revisions: &[String], all: bool) -> Result<Vec<Row>, LumenError> {
+    let mut args = vec![
+        "log".to_string(),
+        "--graph".to_string(),
+        "--date=short".to_string(),
+        "--no-color".to_string(),
+        format!("--max-count={MAX_COMMITS}"),
+        "--format=%x1f%H%x1f%h%x1f%s%x1f%an%x1f%ad%x1f%D".to_string(),
+    ];
+    if all {
+        args.push("--all".to_string());
+    }
+    args.extend(revisions.iter().cloned());
+    args.push("--".to_string());
+
+    let output = logging::output(Command::new("git").args(&args))?;
+    if !output.status.success() {
+        return Err(LumenError::CommandError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(parse_graph(&String::from_utf8_lossy(&output.stdout)))
+}
+
+enum Action {
+    None,
+    Open,
+    Explain,
+    Quit,
+}
+
+struct LogBrowser {
+    rows: Vec<Row>,
+    /// Lowercased text of each row that the query is matched against
+    haystacks: Vec<String>,
+    query: String,
+    /// Whether keys go to the query instead of moving around
+    filtering: bool,
+    /// Indices into `rows` that are shown: every row without a query, matching commits with one
+    visible: Vec<usize>,
+    /// Index into `visible`, always on a commit
+    selected: usize,
+}
+
+impl LogBrowser {
+    fn new(rows: Vec<Row>) -> Self {
+        let haystacks = rows
+            .iter()
+            .map(|row| {
+                row.commit.as_ref().map_or(String::new(), |c| {
+                    format!("{} {} {} {}", c.short, c.subject, c.author, c.refs).to_lowercase()
+                })
+            })
+            .collect();
+        let mut browser = LogBrowser {
+            rows,
+            haystacks,
+            query: String::new(),
+            filtering: false,
+            visible: Vec::new(),
+            selected: 0,
+        };
+        browser.filter();
+        browser
+    }
+
+    fn filter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.visible = (0..self.rows.len())
+            .filter(|&i| {
+                if query.is_empty() {
+                    return true;
+                }
+                self.rows[i].commit.is_some() && fuzzy_match(&self.haystacks[i], &query)
+            })
+            .collect();
+        self.selected = 0;
+        self.move_selection(0);
+    }
+
+    fn is_commit(&self, index: usize) -> bool {
+        self.rows[self.visible[index]].commit.is_some()
+    }
+
+    fn current(&self) -> Option<&LogCommit> {
+        self.visible
+            .get(self.selected)
+            .and_then(|&i| self.rows[i].commit.as_ref())
+    }
+
+    /// Move by `delta` commits, skipping graph-only rows. Zero snaps to the nearest commit.
+    fn move_selection(&mut self, delta: isize) {
+        let Some(last) = self.visible.len().checked_sub(1) else {
+            return;
+        };
+        let mut target = self.selected.saturating_add_signed(delta).min(last);
+        if delta < 0 {
+            while target > 0 && !self.is_commit(target) {
+                target -= 1;
+            }
+        }
+        while target < last && !self.is_commit(target) {
+            target += 1;
+        }
+        if self.is_commit(target) {
+            self.selected = target;
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        if ctrl && key.code == KeyCode::Char('c') {
+            return Action::Quit;
+        }
+        if self.filtering {
+            match key.code {
+                KeyCode::Esc => {
+                    self.filtering = false;
+                    self.query.clear();
+                    self.filter();
+                }
+                KeyCode::Enter => self.filtering = false,
+                KeyCode::Up => self.move_selection(-1),
+                KeyCode::Down => self.move_selection(1),
+                KeyCode::Backspace if self.query.pop().is_some() => self.filter(),
+                KeyCode::Char(c) if !ctrl => {
+                    self.query.push(c);
+                    self.filter();
+                }
+                _ => {}
+            }
+            return Action::None;
+        }
+
+        match key.code {
+            KeyCode::Char('q') => return Action::Quit,
+            KeyCode::Esc if self.query.is_empty() => return Action::Quit,
+            KeyCode::Esc => {
+                self.query.clear();
+                self.filter();
+            }
+            KeyCode::Enter if self.current().is_some() => return Action::Open,
+            KeyCode::Char('e') if self.current().is_some() => return Action::Explain,
+            KeyCode::Char('/') => self.filtering = true,
+            KeyCode::Char('k') | KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('j') | KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('u') if ctrl => self.move_selection(-10),
+            KeyCode::Char('d') if ctrl => self.move_selection(10),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Char('g') | KeyCode::Home => {
+                self.selected = 0;
+                self.move_selection(0);
+            }
+            KeyCode::Char('G') | KeyCode::End => {
+                self.selected = self.visible.len().saturating_sub(1);
+                while self.selected > 0 && !self.is_commit(self.selected) {
+                    self.selected -= 1;
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let t = theme::get();
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(frame.area());
+
+        let list = rows[0];
+        let lines: Vec<Line> = self
+            .visible
+            .iter()
+            .enumerate()
+            .map(|(row, &i)| {
+                let Row { graph, commit } = &self.rows[i];
+                // The graph doesn't connect once rows are filtered out
+                let graph = if self.query.is_empty() {
+                    graph.as_str()
+                } else {
+                    ""
+                };
+                let mut spans = vec![Span::styled(
+                    format!(" {graph}"),
+                    Style::default().fg(t.ui.border_unfocused),
+                )];
+                let Some(commit) = commit else {
+                    return Line::from(spans);
+                };
+                spans.push(Span::styled(
+                    format!("{} ", commit.short),
+                    Style::default().fg(t.ui.highlight),
+                ));
+                if !commit.refs.is_empty() {
+                    spans.push(Span::styled(
+                        format!("({}) ", commit.refs),
+                        Style::default().fg(t.ui.stats_added).bold(),
+                    ));
+                }
+                spans.push(Span::styled(
+                    commit.subject.clone(),
+                    Style::default().fg(t.ui.text_primary),
+                ));
+                spans.push(Span::styled(
+                    format!("  {}, {}", commit.author, commit.date),
+                    Style::default().fg(t.ui.text_muted),
+                ));
+                let line = Line::from(spans);
+                if row == self.selected {
+                    line.style(Style::default().bg(t.ui.selection_bg))
+                } else {
+                    line
+                }
+            })
+            .collect();
+        // Keep the selected row in view
+        let scroll = self
+            .selected
+            .saturating_sub(list.height.saturating_sub(1) as usize);
+        frame.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), list);
+
+        let footer = if self.filtering || !self.query.is_empty() {
+            let cursor = if self.filtering { "█" } else { "" };
+            let commits = self.visible.len();
+            Line::from(vec![
+                Span::styled(" / ", Style::default().fg(t.ui.border_focused).bold()),
+                Span::styled(
+                    format!("{}{cursor}", self.query),
+                    Style::default().fg(t.ui.text_primary),
+                ),
+                Span::styled(
+                    format!("  {commits} matching · esc clear"),
+                    Style::default().fg(t.ui.text_muted),
+                ),
+            ])
+        } else {
+            Line::from(Span::styled(
+                " j/k move · / filter · enter open diff · e explain · q quit",
+                Style::default().fg(t.ui.text_muted),
+            ))
+        };
+        frame.render_widget(Paragraph::new(footer), rows[1]);
+    }
+}
+
+/// Explain a commit on the regular screen, then wait for enter to go back to the log.
+async fn explain(sha: &str, options: &LogOptions) -> Result<(), LumenError> {
+    let result = async {
+        let mut git_entity = GitEntity::Commit(Commit::new(sha.to_string())?);
+        oversized::confirm_exclusions(&mut git_entity)?;
+        let crates = cargo_workspace::api_section(&git_entity);
+        ExplainCommand {
+            git_entity,
+            crates,
+            query: None,
+            commits: None,
+            annotate: false,
+            impact: false,
+            cache: true,
+            format: Format::default(),
+            copy: false,
+            hook: options.hook.clone(),
+            webhook: None,
+        }
+        .execute(&options.provider)
+        .await
+    }
+    .await;
+    // A failed explanation shouldn't close the browser
+    if let Err(e) = result {
+        eprintln!("\x1b[91m\rerror:\x1b[0m {e}");
+    }
+
+    print!("\nPress enter to go back to the log ");
+    io::stdout().flush()?;
+    io::stdin().lock().read_line(&mut String::new())?;
+    Ok(())
+}
+
+/// Browse the history until the user quits.
+pub async fn run(options: LogOptions) -> Result<(), LumenError> {
+    let rows = load_graph(&options.revisions, options.all)?;
+    if rows.iter().all(|row| row.commit.is_none()) {
+        return Err(LumenError::CommandError("no commits to show".to_string()));
+    }
+
+    let mut browser = LogBrowser::new(rows);
+    loop {
+        let action = {
+            let _guard = TerminalGuard::enter()?;
+            let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+            loop {
+                terminal.draw(|frame| browser.render(frame))?;
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match browser.handle_key(key) {
+                    Action::None => {}
+                    action => break action,
+                }
+            }
+        };
+
+        let Some(sha) = browser.current().map(|commit| commit.sha.clone()) else {
+            return Ok(());
+        };
+        match action {
+            Action::None => {}
+            Action::Quit => return Ok(()),
+            Action::Open => diff::run_diff_ui(DiffOptions {
+                reference: Some(CommitReference::Single(sha)),
+                pr: None,
+                file: None,
+                watch: false,
+                watch_paths: Vec::new(),
+                review_flow: false,
+                read_only: false,
+                record: None,
+                replay: None,
+                risk: None,
+                provider: Some(Arc::clone(&options.provider)),
+                provider_error: None,
+                summarize_every: None,
+                summary_log: None,
+                file_summaries: false,
+                preview: false,
+                snapshot: None,
+                notes: Vec::new(),
+                focus: None,
+                settings: options.settings.clone(),
+                draft: DraftConfig::default(),
+            })?,
+            Action::Explain => explain(&sha, &options).await?,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn browser() -> LogBrowser {
+        LogBrowser::new(parse_graph(
+            "*   \x1faaa111\x1faaa\x1fMerge branch 'lexer'\x1fAda\x1f2024-03-02\x1fHEAD -> main\n\
+             |\\  \n\
+             | * \x1fbbb222\x1fbbb\x1fFix the lexer\x1fBob\x1f2024-03-01\x1f\n\
+             |/  \n\
+             * \x1fccc333\x1fccc\x1fAdd parser\x1fAda\x1f2024-02-28\x1ftag: v1.0\n",
+        ))
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_parse_graph() {
+        let browser = browser();
+        assert_eq!(browser.rows.len(), 5);
+        assert_eq!(browser.rows[1].graph, "|\\");
+        assert!(browser.rows[1].commit.is_none());
+        let commit = browser.rows[2].commit.as_ref().unwrap();
+        assert_eq!(browser.rows[2].graph, "| * ");
+        assert_eq!(commit.subject, "Fix the lexer");
+        assert_eq!(commit.date, "2024-03-01");
+        assert_eq!(browser.rows[4].commit.as_ref().unwrap().refs, "tag: v1.0");
+    }
+
+    #[test]
+    fn test_selection_skips_graph_rows() {
+        let mut browser = browser();
+        assert_eq!(browser.current().unwrap().short, "aaa");
+        browser.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(browser.current().unwrap().short, "bbb");
+        browser.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(browser.current().unwrap().short, "ccc");
+        browser.handle_key(key(KeyCode::Char('j')));
+        assert_eq!(browser.current().unwrap().short, "ccc");
+        browser.handle_key(key(KeyCode::Char('k')));
+        assert_eq!(browser.current().unwrap().short, "bbb");
+        browser.handle_key(key(KeyCode::Char('g')));
+        assert_eq!(browser.current().unwrap().short, "aaa");
+        browser.handle_key(key(KeyCode::Char('G')));
+        assert_eq!(browser.current().unwrap().short, "ccc");
+    }
+
+    #[test]
+    fn test_filter_keeps_matching_commits() {
+        let mut browser = browser();
+        browser.handle_key(key(KeyCode::Char('/')));
+        for c in "v1".chars() {
+            browser.handle_key(key(KeyCode::Char(c)));
+        }
+        // Typed keys go to the query, not to the commands
+        assert!(matches!(
+            browser.handle_key(key(KeyCode::Enter)),
+            Action::None
+        ));
+        assert_eq!(browser.visible, vec![4]);
+        assert!(matches!(
+            browser.handle_key(key(KeyCode::Char('e'))),
+            Action::Explain
+        ));
+
+        browser.handle_key(key(KeyCode::Esc));
+        assert_eq!(browser.visible.len(), 5);
+        assert!(matches!(
+            browser.handle_key(key(KeyCode::Esc)),
+            Action::Quit
+        ));
+    }
+}
@@ -0,0 +1,315 @@
+//! Render markdown as ANSI-styled text for the terminal: headings, emphasis, inline code,
+//! links, lists, quotes, rules, and fenced code blocks highlighted like the diff viewer.
+//!
+//! Line based and forgiving rather than a full CommonMark parser. It only has to read well
+//! for AI answers, and anything it doesn't recognize is printed as written.
+
+use std::io::{self, IsTerminal};
+
+use ratatui::style::Color;
+
+use super::diff::highlight;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const ITALIC: &str = "\x1b[3m";
+const UNDERLINE: &str = "\x1b[4m";
+const HEADING: &str = "\x1b[1;34m";
+const CODE: &str = "\x1b[36m";
+const ADDED: &str = "\x1b[32m";
+const REMOVED: &str = "\x1b[31m";
+
+/// Fence names that differ from the file extension the highlighter knows the language by
+const FENCE_LANGUAGES: &[(&str, &str)] = &[
+    ("rust", "rs"),
+    ("python", "py"),
+    ("javascript", "js"),
+    ("jsx", "js"),
+    ("typescript", "ts"),
+    ("golang", "go"),
+    ("bash", "sh"),
+    ("shell", "sh"),
+    ("zsh", "sh"),
+    ("console", "sh"),
+    ("markdown", "md"),
+];
+
+/// Indent of code block lines
+const CODE_INDENT: &str = "    ";
+
+enum Fence {
+    /// `diff` and `patch` blocks, colored by line like git does
+    Diff,
+    /// Highlighted as if it were a file with this name
+    Code(String),
+}
+
+/// Whether markdown written to stdout should be styled: only on a terminal, and not when
+/// `NO_COLOR` is set.
+pub fn use_styles() -> bool {
+    io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+/// Render `markdown` with ANSI styles, one output line per input line (minus code fences).
+pub fn render(markdown: &str) -> String {
+    let mut out = String::new();
+    // Fence marker and language of the open code block
+    let mut open: Option<(&str, Fence)> = None;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if let Some((marker, fence)) = &open {
+            if trimmed.starts_with(marker) && trimmed.trim_start_matches(&marker[..1]).is_empty() {
+                open = None;
+            } else {
+                out.push_str(&code_line(line, fence));
+                out.push('\n');
+            }
+            continue;
+        }
+
+        let marker = ["```", "~~~"]
+            .into_iter()
+            .find(|marker| trimmed.starts_with(marker));
+        if let Some(marker) = marker {
+            let info = trimmed.trim_start_matches(&marker[..1]).trim();
+            open = Some((marker, fence(info)));
+            continue;
+        }
+
+        out.push_str(&block_line(line));
+        out.push('\n');
+    }
+    out
+}
+
+fn fence(info: &str) -> Fence {
+    let language = info
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    if matches!(language.as_str(), "diff" | "patch") {
+        return Fence::Diff;
+    }
+    let extension = FENCE_LANGUAGES
+        .iter()
+        .find(|(name, _)| *name == language)
+        .map_or(language.as_str(), |(_, extension)| *extension);
+    Fence::Code(format!("code.{extension}"))
+}
+
+fn code_line(line: &str, fence: &Fence) -> String {
+    match fence {
+        Fence::Diff => {
+            let color = match line.chars().next() {
+                Some('+') => ADDED,
+                Some('-') => REMOVED,
+                Some('@') => CODE,
+                _ => "",
+            };
+            format!("{CODE_INDENT}{color}{line}{RESET}")
+        }
+        Fence::Code(filename) => {
+            let highlighted: String = highlight::highlight_colored(line, filename)
+                .into_iter()
+                .map(|(text, color)| format!("{}{text}", foreground(color)))
+                .collect();
+            format!("{CODE_INDENT}{highlighted}{RESET}")
+        }
+    }
+}
+
+fn block_line(line: &str) -> String {
+    let content = line.trim_start();
+    let indent = &line[..line.len() - content.len()];
+
+    let level = content.chars().take_while(|&c| c == '#').count();
+    if level <= 6 {
+        if let Some(text) = content[level..].strip_prefix(' ').filter(|_| level > 0) {
+            let text = text.trim_end().trim_end_matches('#').trim_end();
+            let style = if level == 1 {
+                format!("{HEADING}{UNDERLINE}")
+            } else {
+                HEADING.to_string()
+            };
+            return format!("{}{RESET}", inline(text, &style));
+        }
+    }
+
+    if is_rule(content) {
+        return format!("{DIM}{}{RESET}", "─".repeat(40));
+    }
+    if let Some(text) = content.strip_prefix('>') {
+        return format!(
+            "{indent}{DIM}│{RESET} {}{RESET}",
+            inline(text.trim_start(), ITALIC)
+        );
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(text) = content.strip_prefix(bullet) {
+            return format!("{indent}• {}", inline(text, ""));
+        }
+    }
+    format!("{indent}{}", inline(content, ""))
+}
+
+/// `---`, `***` or `___`, optionally spaced out.
+fn is_rule(content: &str) -> bool {
+    let marks: Vec<char> = content.chars().filter(|c| !c.is_whitespace()).collect();
+    marks.len() >= 3 && matches!(marks[0], '-' | '*' | '_') && marks.iter().all(|&c| c == marks[0])
+}
+
+/// Style inline code, emphasis and links, going back to `base` after each.
+fn inline(text: &str, base: &str) -> String {
+    let mut out = String::from(base);
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some((code, after)) = rest
+            .strip_prefix('`')
+            .and_then(|inner| inner.split_once('`'))
+            .filter(|(code, _)| !code.is_empty())
+        {
+            out.push_str(&format!("{CODE}{code}{RESET}{base}"));
+            rest = after;
+        } else if let Some((strong, after)) = delimited(rest, "**") {
+            let style = format!("{base}{BOLD}");
+            out.push_str(&format!("{}{RESET}{base}", inline(strong, &style)));
+            rest = after;
+        } else if let Some((emphasis, after)) = delimited(rest, "*") {
+            let style = format!("{base}{ITALIC}");
+            out.push_str(&format!("{}{RESET}{base}", inline(emphasis, &style)));
+            rest = after;
+        } else if let Some((label, url, after)) = link(rest) {
+            if label == url {
+                out.push_str(&format!("{UNDERLINE}{url}{RESET}{base}"));
+            } else {
+                out.push_str(&format!(
+                    "{UNDERLINE}{label}{RESET}{base} {DIM}({url}){RESET}{base}"
+                ));
+            }
+            rest = after;
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+/// Text between a pair of `marker`s at the start of `text`, and what follows. Like markdown,
+/// the text must not start or end with a space, so `2 * 3 * 4` stays as written.
+fn delimited<'a>(text: &'a str, marker: &str) -> Option<(&'a str, &'a str)> {
+    let inner = text.strip_prefix(marker)?;
+    let end = inner.find(marker)?;
+    let content = &inner[..end];
+    let valid = !content.is_empty() && !content.starts_with(' ') && !content.ends_with(' ');
+    valid.then_some((content, &inner[end + marker.len()..]))
+}
+
+/// `[label](url)` at the start of `text`: the label, the url, and what follows.
+fn link(text: &str) -> Option<(&str, &str, &str)> {
+    let inner = text.strip_prefix('[')?;
+    let (label, after) = inner.split_once("](")?;
+    let (url, after) = after.split_once(')')?;
+    (!label.contains('[') && !url.contains(char::is_whitespace)).then_some((label, url, after))
+}
+
+fn foreground(color: Color) -> String {
+    let code = match color {
+        Color::Rgb(r, g, b) => return format!("\x1b[38;2;{r};{g};{b}m"),
+        Color::Indexed(i) => return format!("\x1b[38;5;{i}m"),
+        Color::Reset => 39,
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::Gray => 37,
+        Color::DarkGray => 90,
+        Color::LightRed => 91,
+        Color::LightGreen => 92,
+        Color::LightYellow => 93,
+        Color::LightBlue => 94,
+        Color::LightMagenta => 95,
+        Color::LightCyan => 96,
+        Color::White => 97,
+    };
+    format!("\x1b[{code}m")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `text` without its escape sequences
+    fn plain(text: &str) -> String {
+        let mut out = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find('\x1b') {
+            out.push_str(&rest[..start]);
+            let end = rest[start..]
+                .find('m')
+                .map_or(rest.len(), |m| start + m + 1);
+            rest = &rest[end..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    #[test]
+    fn test_blocks() {
+        let rendered = render("# Title ##\n- one\n  * two\n> quoted\n-----\n1. first");
+        assert_eq!(
+            plain(&rendered),
+            format!(
+                "Title\n• one\n  • two\n│ quoted\n{}\n1. first\n",
+                "─".repeat(40)
+            )
+        );
+        assert!(rendered.starts_with(HEADING));
+        // Not a heading without the space
+        assert_eq!(plain(&render("#hashtag")), "#hashtag\n");
+    }
+
+    #[test]
+    fn test_inline_styles() {
+        let rendered = render("Use `cargo test` and **really** *read* [docs](https://x.dev)");
+        assert_eq!(
+            plain(&rendered),
+            "Use cargo test and really read docs (https://x.dev)\n"
+        );
+        assert!(rendered.contains(&format!("{CODE}cargo test{RESET}")));
+        assert!(rendered.contains(&format!("{BOLD}really")));
+        assert!(rendered.contains(&format!("{ITALIC}read")));
+    }
+
+    #[test]
+    fn test_text_that_only_looks_like_markup_is_kept() {
+        for text in [
+            "2 * 3 * 4",
+            "snake_case_name",
+            "a ** b",
+            "[not a link]",
+            "`unclosed",
+        ] {
+            assert_eq!(plain(&render(text)), format!("{text}\n"));
+        }
+    }
+
+    #[test]
+    fn test_code_blocks_drop_fences() {
+        let rendered =
+            render("Before\n```rust\nfn main() {}\n```\n```diff\n-old\n+new\n```\nAfter");
+        assert_eq!(
+            plain(&rendered),
+            "Before\n    fn main() {}\n    -old\n    +new\nAfter\n"
+        );
+        assert!(rendered.contains(&format!("{REMOVED}-old")));
+        assert!(rendered.contains(&format!("{ADDED}+new")));
+        // Markup inside code is left alone
+        assert_eq!(plain(&render("~~~\n**x**\n~~~")), "    **x**\n");
+    }
+}
@@ -1,32 +1,160 @@
-use draft::DraftCommand;
-use explain::ExplainCommand;
+use changelog::ChangelogCommand;
+use cherry_pick::CherryPickCommand;
+use digest::DigestCommand;
+use draft::{DraftCommand, SquashBranch};
+use explain::{ExplainCommand, ExplainCommitsCommand};
 use list::ListCommand;
 use operate::OperateCommand;
+use output::Format;
+use pr::PrCommand;
+use review::{Gate, ReviewCommand};
+use safety::Policy;
+use serve::ServeCommand;
+use std::io::IsTerminal;
+use std::path::PathBuf;
 use std::process::Stdio;
+use suppress::Suppressions;
 
-use crate::config::configuration::DraftConfig;
+use crate::commit_reference::CommitReference;
+use crate::config::configuration::{DiffConfig, DraftConfig, OperateConfig, ReviewConfig};
 use crate::error::LumenError;
+use crate::git_entity::commit::Commit;
 use crate::git_entity::diff::Diff;
 use crate::git_entity::GitEntity;
+use crate::logging;
 use crate::provider::LumenProvider;
+use crate::webhook::WebhookConfig;
+use lumen_core::git::RangeOptions;
 
+pub mod apply;
+pub mod cargo_workspace;
+pub mod changelog;
+pub mod check;
+pub mod cherry_pick;
+pub mod completions;
 pub mod configure;
+pub mod conventions;
 pub mod diff;
+pub mod digest;
+pub mod doctor;
 pub mod draft;
 pub mod explain;
+pub mod explain_watch;
+pub mod git_hook;
+pub mod git_style;
+pub mod lint_msg;
 pub mod list;
+pub mod log;
+pub mod markdown;
 pub mod operate;
+pub mod output;
+pub mod oversized;
+pub mod picker;
+pub mod pr;
+pub mod review;
+pub mod reword;
+pub mod safety;
+pub mod serve;
+pub mod snapshot;
+pub mod split;
+pub mod suppress;
+pub mod theme_editor;
+pub mod usage;
 
 #[derive(Debug)]
 pub enum CommandType {
     Explain {
         git_entity: GitEntity,
         query: Option<String>,
+        /// The branch's commits, for `--branch`
+        commits: Option<String>,
+        annotate: bool,
+        /// Assess the risk of the change instead of explaining it
+        impact: bool,
+        cache: bool,
+        format: Format,
+        /// Copy the explanation to the clipboard
+        copy: bool,
+        /// Shell hook the explanation is piped through
+        hook: Option<String>,
+        webhook: Option<WebhookConfig>,
+    },
+    /// `lumen explain` given several commits
+    ExplainCommits {
+        commits: Vec<Commit>,
+        query: Option<String>,
+        cache: bool,
+        format: Format,
+        copy: bool,
+        webhook: Option<WebhookConfig>,
     },
     List,
-    Draft(Option<String>, DraftConfig),
+    Draft {
+        context: Option<String>,
+        draft_config: DraftConfig,
+        subject_only: bool,
+        commit: bool,
+        edit: bool,
+        count: usize,
+        format: Format,
+        /// Base of the branch to draft a squash-merge message for
+        squash: Option<String>,
+        /// Update HEAD's message for the staged changes instead of writing a new one
+        amend: bool,
+        /// Copy the message to the clipboard
+        copy: bool,
+        /// Commit message file to write the message to, from a `prepare-commit-msg` hook
+        message_file: Option<PathBuf>,
+        /// Shell hook each message is piped through
+        hook: Option<String>,
+        webhook: Option<WebhookConfig>,
+    },
+    Pr {
+        base: Option<String>,
+        context: Option<String>,
+        create: bool,
+        format: Format,
+        webhook: Option<WebhookConfig>,
+    },
     Operate {
         query: String,
+        dry_run: bool,
+        policy: OperateConfig,
+        allow_destructive: bool,
+    },
+    Review {
+        git_entity: GitEntity,
+        reference: Option<CommitReference>,
+        tui: bool,
+        format: Format,
+        settings: DiffConfig,
+        gate: Option<Gate>,
+        webhook: Option<WebhookConfig>,
+        ignore: ReviewConfig,
+    },
+    CherryPick {
+        shas: Vec<String>,
+    },
+    Digest {
+        since: String,
+        author: Option<String>,
+        branch: Option<String>,
+        all: bool,
+        format: Format,
+        webhook: Option<WebhookConfig>,
+    },
+    Changelog {
+        from: String,
+        to: String,
+        version: String,
+        range_options: RangeOptions,
+        template: Option<String>,
+        format: Format,
+        prepend: Option<PathBuf>,
+        webhook: Option<WebhookConfig>,
+    },
+    Serve {
+        draft_config: DraftConfig,
     },
 }
 
@@ -39,93 +167,326 @@ impl LumenCommand {
         LumenCommand { provider }
     }
 
+    /// Hand over the provider to code that runs outside `execute`, like the diff viewer.
+    pub fn into_provider(self) -> LumenProvider {
+        self.provider
+    }
+
     pub async fn execute(&self, command_type: CommandType) -> Result<(), LumenError> {
         match command_type {
-            CommandType::Explain { git_entity, query } => {
-                ExplainCommand { git_entity, query }.execute(&self.provider).await
+            CommandType::Explain {
+                mut git_entity,
+                query,
+                commits,
+                annotate,
+                impact,
+                cache,
+                format,
+                copy,
+                hook,
+                webhook,
+            } => {
+                oversized::confirm_exclusions(&mut git_entity)?;
+                let crates = cargo_workspace::api_section(&git_entity);
+                ExplainCommand {
+                    git_entity,
+                    crates,
+                    query,
+                    commits,
+                    annotate,
+                    impact,
+                    cache,
+                    format,
+                    copy,
+                    hook,
+                    webhook,
+                }
+                .execute(&self.provider)
+                .await
+            }
+            CommandType::ExplainCommits {
+                commits,
+                query,
+                cache,
+                format,
+                copy,
+                webhook,
+            } => {
+                ExplainCommitsCommand {
+                    commits,
+                    query,
+                    cache,
+                    format,
+                    copy,
+                    webhook,
+                }
+                .execute(&self.provider)
+                .await
             }
             CommandType::List => ListCommand.execute(&self.provider).await,
-            CommandType::Draft(context, draft_config) => {
+            CommandType::Draft {
+                context,
+                draft_config,
+                subject_only,
+                commit,
+                edit,
+                count,
+                format,
+                squash,
+                amend,
+                copy,
+                message_file,
+                hook,
+                webhook,
+            } => {
+                let (mut git_entity, squash) = match squash {
+                    Some(base) => (
+                        GitEntity::Diff(Diff::from_commits_range(
+                            &base,
+                            "HEAD",
+                            true,
+                            RangeOptions::default(),
+                            &[],
+                        )?),
+                        Some(SquashBranch::load(&base)?),
+                    ),
+                    None => (GitEntity::Diff(Diff::from_working_tree(true, &[])?), None),
+                };
+                let amend = if amend {
+                    Some(draft::head_message()?)
+                } else {
+                    None
+                };
+                oversized::confirm_exclusions(&mut git_entity)?;
+                oversized::fit_to_context(&mut git_entity, self.provider.max_tokens());
                 DraftCommand {
-                    git_entity: GitEntity::Diff(Diff::from_working_tree(true)?),
+                    git_entity,
                     draft_config,
                     context,
+                    subject_only,
+                    commit,
+                    edit,
+                    count,
+                    format,
+                    squash,
+                    amend,
+                    copy,
+                    message_file,
+                    hook,
+                    webhook,
+                }
+                .execute(&self.provider)
+                .await
+            }
+            CommandType::Pr {
+                base,
+                context,
+                create,
+                format,
+                webhook,
+            } => {
+                let base = match base {
+                    Some(base) => base,
+                    None => pr::default_base()?,
+                };
+                let commits = SquashBranch::load(&base)?.log;
+                let mut git_entity = GitEntity::Diff(Diff::from_commits_range(
+                    &base,
+                    "HEAD",
+                    true,
+                    RangeOptions::default(),
+                    &[],
+                )?);
+                oversized::confirm_exclusions(&mut git_entity)?;
+                oversized::fit_to_context(&mut git_entity, self.provider.max_tokens());
+                PrCommand {
+                    git_entity,
+                    base,
+                    commits,
+                    context,
+                    create,
+                    format,
+                    webhook,
+                }
+                .execute(&self.provider)
+                .await
+            }
+            CommandType::Operate {
+                query,
+                dry_run,
+                policy,
+                allow_destructive,
+            } => {
+                OperateCommand {
+                    query,
+                    dry_run,
+                    policy: Policy::new(&policy),
+                    allow_destructive,
+                }
+                .execute(&self.provider)
+                .await
+            }
+            CommandType::Review {
+                mut git_entity,
+                reference,
+                tui,
+                format,
+                settings,
+                gate,
+                webhook,
+                ignore,
+            } => {
+                oversized::confirm_exclusions(&mut git_entity)?;
+                oversized::fit_to_context(&mut git_entity, self.provider.max_tokens());
+                ReviewCommand {
+                    git_entity,
+                    reference,
+                    tui,
+                    format,
+                    settings,
+                    gate,
+                    webhook,
+                    suppressions: Suppressions::load(&ignore)?,
                 }
                 .execute(&self.provider)
                 .await
             }
-            CommandType::Operate { query } => {
-                OperateCommand { query }.execute(&self.provider).await
+            CommandType::CherryPick { shas } => {
+                CherryPickCommand { shas }.execute(&self.provider).await
+            }
+            CommandType::Digest {
+                since,
+                author,
+                branch,
+                all,
+                format,
+                webhook,
+            } => {
+                DigestCommand {
+                    since,
+                    author,
+                    branch,
+                    all,
+                    format,
+                    webhook,
+                }
+                .execute(&self.provider)
+                .await
             }
+            CommandType::Changelog {
+                from,
+                to,
+                version,
+                range_options,
+                template,
+                format,
+                prepend,
+                webhook,
+            } => {
+                ChangelogCommand {
+                    from,
+                    to,
+                    version,
+                    range_options,
+                    template,
+                    format,
+                    prepend,
+                    webhook,
+                }
+                .execute(&self.provider)
+                .await
+            }
+            CommandType::Serve { draft_config } => {
+                ServeCommand { draft_config }.execute(&self.provider).await
+            }
+        }
+    }
+
+    /// Let the user pick a commit in the built-in picker, or with fzf when stdout isn't a
+    /// terminal the picker could draw on.
+    pub(crate) fn pick_commit() -> Result<String, LumenError> {
+        if std::io::stdin().is_terminal() && std::io::stdout().is_terminal() {
+            picker::pick_commit()
+        } else {
+            Self::get_sha_from_fzf()
         }
     }
 
-    pub(crate) fn get_sha_from_fzf() -> Result<String, LumenError> {
-        let command = "git log --color=always --format='%C(auto)%h%d %s %C(black)%C(bold)%cr' | fzf --ansi --reverse --bind='enter:become(echo {1})'";
+    fn get_sha_from_fzf() -> Result<String, LumenError> {
+        // git's output is piped into fzf directly, so no shell is needed on any platform
+        let mut log = logging::spawn(
+            std::process::Command::new("git")
+                .args([
+                    "log",
+                    "--color=always",
+                    "--format=%C(auto)%h%d %s %C(black)%C(bold)%cr",
+                ])
+                .stdin(Stdio::null())
+                .stdout(Stdio::piped()),
+        )?;
+        let commits = log
+            .stdout
+            .take()
+            .map(Stdio::from)
+            .unwrap_or_else(Stdio::null);
 
-        let output = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .stdin(Stdio::null())
+        let fzf = std::process::Command::new("fzf")
+            .args(["--ansi", "--reverse"])
+            .stdin(commits)
             .stdout(Stdio::piped())
             .stderr(Stdio::piped())
-            .output()?;
+            .spawn();
+        let output = match fzf {
+            Ok(fzf) => fzf.wait_with_output(),
+            Err(e) => Err(e),
+        };
+        // fzf stops reading once a commit is picked, so git may be left writing to a closed pipe
+        let _ = log.kill();
+        let _ = log.wait();
+        let output = match output {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Err(LumenError::CommandError(
+                    "fzf not found (hint: picking a commit without a terminal requires fzf)"
+                        .to_string(),
+                ));
+            }
+            output => output?,
+        };
 
         if !output.status.success() {
-            let mut stderr = String::from_utf8(output.stderr)?;
-            stderr.pop();
-
-            let hint = match &stderr {
-                stderr if stderr.contains("fzf: command not found") => {
-                    Some("`list` command requires fzf")
-                }
-                _ => None,
-            };
-
-            let hint = match hint {
-                Some(hint) => format!("(hint: {})", hint),
-                None => String::new(),
-            };
-
-            return Err(LumenError::CommandError(format!("{} {}", stderr, hint)));
+            let stderr = String::from_utf8(output.stderr)?;
+            return Err(LumenError::CommandError(stderr.trim_end().to_string()));
         }
 
-        let mut sha = String::from_utf8(output.stdout)?;
-        sha.pop(); // remove trailing newline from echo
+        // fzf prints the picked line without its colors; the short hash comes first
+        let line = String::from_utf8(output.stdout)?;
+        line.split_whitespace()
+            .next()
+            .map(str::to_string)
+            .ok_or_else(|| LumenError::CommandError("no commit picked".to_string()))
+    }
 
-        Ok(sha)
+    /// Print markdown styled for the terminal, or as written when piped.
+    fn print_markdown(content: &str) {
+        if markdown::use_styles() {
+            println!("{}", markdown::render(content));
+        } else {
+            println!("{content}");
+        }
     }
 
-    fn print_with_mdcat(content: String) -> Result<(), LumenError> {
-        match std::process::Command::new("mdcat")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-        {
-            Ok(mut mdcat) => {
-                if let Some(stdin) = mdcat.stdin.take() {
-                    std::process::Command::new("echo")
-                        .arg(&content)
-                        .stdout(stdin)
-                        .spawn()?
-                        .wait()?;
-                }
-                let output = mdcat.wait_with_output()?;
-                println!("{}", String::from_utf8(output.stdout)?);
-            }
-            Err(_) => {
-                println!("{}", content);
-            }
+    /// Copy `text` for `--copy`, reporting on stderr so piped output stays clean. Copying
+    /// is a convenience, so failing to only warns.
+    fn copy_to_clipboard(text: &str) {
+        match crate::clipboard::copy(text) {
+            Ok(()) => eprintln!("Copied to the clipboard."),
+            Err(e) => eprintln!("Warning: {e}"),
         }
-        Ok(())
     }
 
     #[allow(dead_code)]
     fn execute_bash_command(command: &str) -> Result<(), LumenError> {
-        let output = std::process::Command::new("sh")
-            .arg("-c")
-            .arg(command)
-            .output()?;
+        let output = crate::shell::command(command).output()?;
 
         if !output.status.success() {
             let mut stderr = String::from_utf8(output.stderr)?;
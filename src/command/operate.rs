@@ -1,5 +1,8 @@
+use genai::chat::Tool;
+use serde_json::{json, Value};
 use spinoff::{spinners, Color, Spinner};
 use std::io::{self, Write};
+use std::process::Command;
 use thiserror::Error;
 use xml::reader::{EventReader, XmlEvent};
 
@@ -8,6 +11,15 @@ pub struct OperateResult {
     pub command: String,
     pub explanation: String,
     pub warning: Option<String>,
+    /// Each command of the chain with its effect and risk, asked for with `--dry-run`
+    pub steps: Vec<OperateStep>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct OperateStep {
+    pub run: String,
+    pub effect: String,
+    pub risk: String,
 }
 
 #[derive(Error, Debug)]
@@ -17,12 +29,19 @@ pub struct ExtractError {
     message: String,
 }
 
-use crate::{error::LumenError, provider::LumenProvider};
+use crate::{error::LumenError, logging, provider::LumenProvider, shell};
 
+use super::safety::Policy;
 use super::LumenCommand;
 
 pub struct OperateCommand {
     pub query: String,
+    /// Explain the command step by step instead of offering to run it
+    pub dry_run: bool,
+    /// Checked before the command is offered to run
+    pub policy: Policy,
+    /// Offer to run commands the policy blocks
+    pub allow_destructive: bool,
 }
 
 pub fn extract_operate_response(ai_response: &str) -> Result<OperateResult, ExtractError> {
@@ -30,6 +49,8 @@ pub fn extract_operate_response(ai_response: &str) -> Result<OperateResult, Extr
     let mut command = None;
     let mut explanation = None;
     let mut warning = None;
+    let mut steps = Vec::new();
+    let mut step = OperateStep::default();
     let mut current_element = None;
     let mut current_text = String::new();
 
@@ -39,12 +60,13 @@ pub fn extract_operate_response(ai_response: &str) -> Result<OperateResult, Extr
                 current_element = Some(name.local_name.clone());
                 current_text.clear();
             }
-            Ok(XmlEvent::Characters(text)) => {
-                if current_element.is_some() {
-                    current_text.push_str(&text);
-                }
+            Ok(XmlEvent::Characters(text)) if current_element.is_some() => {
+                current_text.push_str(&text);
             }
             Ok(XmlEvent::EndElement { name }) => {
+                if name.local_name == "step" {
+                    steps.push(std::mem::take(&mut step));
+                }
                 if let Some(element) = &current_element {
                     if element == &name.local_name {
                         match element.as_str() {
@@ -56,6 +78,9 @@ pub fn extract_operate_response(ai_response: &str) -> Result<OperateResult, Extr
                                     warning = Some(trimmed.to_string());
                                 }
                             }
+                            "run" => step.run = current_text.trim().to_string(),
+                            "effect" => step.effect = current_text.trim().to_string(),
+                            "risk" => step.risk = current_text.trim().to_string(),
                             _ => {}
                         }
                     }
@@ -87,9 +112,126 @@ pub fn extract_operate_response(ai_response: &str) -> Result<OperateResult, Extr
         command,
         explanation,
         warning,
+        steps,
     })
 }
 
+/// Tool output is cut to this many characters so a huge log can't fill the context
+const MAX_TOOL_OUTPUT: usize = 8_000;
+
+/// Read-only git tools the provider can call to look at the repository before answering.
+pub fn tools() -> Vec<Tool> {
+    vec![
+        Tool::new("git_status")
+            .with_description("Show the current branch, its upstream and changed files (`git status --short --branch`)")
+            .with_schema(json!({ "type": "object", "properties": {} })),
+        Tool::new("git_log")
+            .with_description("List recent commits, one per line with refs (`git log --oneline --decorate`)")
+            .with_schema(json!({
+                "type": "object",
+                "properties": {
+                    "revision": { "type": "string", "description": "Branch, ref or range to list, eg: main..HEAD (defaults to HEAD)" },
+                    "count": { "type": "integer", "description": "How many commits to list (default 20, at most 100)" }
+                }
+            })),
+        Tool::new("git_branches")
+            .with_description("List branches with their upstream and last commit (`git branch -vv`)")
+            .with_schema(json!({
+                "type": "object",
+                "properties": {
+                    "all": { "type": "boolean", "description": "Include remote-tracking branches" },
+                    "merged_into": { "type": "string", "description": "Only list branches merged into this ref, eg: main" }
+                }
+            })),
+    ]
+}
+
+/// Refs come from the model, so anything that could be read as an option is refused
+fn ref_arg(args: &Value, key: &str) -> Result<Option<String>, String> {
+    match args.get(key).and_then(Value::as_str).map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) if value.starts_with('-') => Err(format!("invalid {key}: {value}")),
+        Some(value) => Ok(Some(value.to_string())),
+    }
+}
+
+fn git_args(name: &str, args: &Value) -> Result<Vec<String>, String> {
+    let mut git: Vec<String> = match name {
+        "git_status" => vec!["status".into(), "--short".into(), "--branch".into()],
+        "git_log" => {
+            let count = args
+                .get("count")
+                .and_then(Value::as_u64)
+                .unwrap_or(20)
+                .clamp(1, 100);
+            let mut git = vec![
+                "log".into(),
+                "--oneline".into(),
+                "--decorate".into(),
+                format!("-n{count}"),
+            ];
+            git.extend(ref_arg(args, "revision")?);
+            git
+        }
+        "git_branches" => {
+            let mut git = vec!["branch".into(), "-vv".into()];
+            if args.get("all").and_then(Value::as_bool).unwrap_or(false) {
+                git.push("--all".into());
+            }
+            if let Some(base) = ref_arg(args, "merged_into")? {
+                git.push(format!("--merged={base}"));
+            }
+            git
+        }
+        _ => return Err(format!("unknown tool: {name}")),
+    };
+    git.insert(0, "--no-pager".into());
+    Ok(git)
+}
+
+/// Run one of `tools()` and return its output, or the error, as text for the model.
+pub fn run_tool(name: &str, args: &Value) -> String {
+    let git = match git_args(name, args) {
+        Ok(git) => git,
+        Err(e) => return format!("error: {e}"),
+    };
+    let output = match logging::output(Command::new("git").args(&git)) {
+        Ok(output) => output,
+        Err(e) => return format!("error: failed to run git: {e}"),
+    };
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() {
+        text.push_str(&String::from_utf8_lossy(&output.stderr));
+    }
+    if text.trim().is_empty() {
+        return "(no output)".to_string();
+    }
+    if let Some((cut, _)) = text.char_indices().nth(MAX_TOOL_OUTPUT) {
+        text.truncate(cut);
+        text.push_str("\n... (truncated)");
+    }
+    text
+}
+
+/// What `--dry-run` prints: the command, then each step with its effect and risk.
+pub fn dry_run_report(result: &OperateResult) -> String {
+    let mut out = format!("{}\n\n--- What this will do ---\n", result.command);
+    if result.steps.is_empty() {
+        out.push_str(&format!("{}\n", result.explanation));
+    }
+    for (i, step) in result.steps.iter().enumerate() {
+        out.push_str(&format!("{}. {}\n   {}\n", i + 1, step.run, step.effect));
+        if !step.risk.is_empty() && !step.risk.eq_ignore_ascii_case("none") {
+            out.push_str(&format!("   Risk: {}\n", step.risk));
+        }
+    }
+    if let Some(warning) = &result.warning {
+        out.push_str(&format!("\nWarning: {warning}\n"));
+    }
+    out.push_str("\nDry run: nothing was run.");
+    out
+}
+
 pub fn process_operation(result: OperateResult) -> Result<(), io::Error> {
     // Display the explanation
     println!("\n--- What this will do ---");
@@ -113,18 +255,8 @@ pub fn process_operation(result: OperateResult) -> Result<(), io::Error> {
         return Ok(());
     }
 
-    // Using a shell to execute the git command
-    #[cfg(target_family = "unix")]
-    let output = std::process::Command::new("sh")
-        .arg("-c")
-        .arg(&result.command)
-        .output()?;
-
-    #[cfg(target_family = "windows")]
-    let output = std::process::Command::new("cmd")
-        .arg("/C")
-        .arg(&result.command)
-        .output()?;
+    // The suggestion can chain commands with `&&`, which takes a shell
+    let output = shell::command(&result.command).output()?;
 
     // Print command output
     if !output.stdout.is_empty() {
@@ -147,7 +279,7 @@ pub fn process_operation(result: OperateResult) -> Result<(), io::Error> {
 
 impl OperateCommand {
     pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
-        LumenCommand::print_with_mdcat(format!("`query`: {}", &self.query))?;
+        LumenCommand::print_markdown(&format!("`query`: {}", &self.query));
 
         let spinner_text = "Generating answer...".to_string();
 
@@ -157,7 +289,89 @@ impl OperateCommand {
             .map_err(|e| LumenError::CommandError(e.to_string()))?;
         spinner.success("Done");
 
+        let violation = if self.allow_destructive {
+            None
+        } else {
+            self.policy.check(&operate_result.command)
+        };
+        if self.dry_run {
+            println!("\n{}", dry_run_report(&operate_result));
+            if let Some(violation) = violation {
+                println!(
+                    "`{}` is blocked by `{}`; it would need --allow-destructive.",
+                    violation.command, violation.pattern
+                );
+            }
+            return Ok(());
+        }
+        if let Some(violation) = violation {
+            println!("\n{}", operate_result.command);
+            return Err(LumenError::CommandError(format!(
+                "`{}` is blocked by the safety policy (`{}`); pass --allow-destructive to run it",
+                violation.command, violation.pattern
+            )));
+        }
         process_operation(operate_result)?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_git_args() {
+        assert_eq!(
+            git_args(
+                "git_log",
+                &json!({ "revision": "main..HEAD", "count": 500 })
+            )
+            .unwrap(),
+            [
+                "--no-pager",
+                "log",
+                "--oneline",
+                "--decorate",
+                "-n100",
+                "main..HEAD"
+            ]
+        );
+        assert_eq!(
+            git_args("git_branches", &json!({ "merged_into": "main" })).unwrap(),
+            ["--no-pager", "branch", "-vv", "--merged=main"]
+        );
+    }
+
+    #[test]
+    fn test_dry_run_steps() {
+        let response = "<response><command>git stash; git reset --hard HEAD~1</command>\
+            <explanation>Drop the last commit</explanation>\
+            <warning>Discards the commit</warning>\
+            <steps>\
+            <step><run>git stash</run><effect>Sets local changes aside</effect><risk>none</risk></step>\
+            <step><run>git reset --hard HEAD~1</run><effect>Moves the branch back</effect>\
+            <risk>The commit is only left in the reflog</risk></step>\
+            </steps></response>";
+        let result = extract_operate_response(response).unwrap();
+        assert_eq!(result.steps.len(), 2);
+        assert_eq!(
+            result.steps[1].risk,
+            "The commit is only left in the reflog"
+        );
+        assert_eq!(
+            dry_run_report(&result),
+            "git stash; git reset --hard HEAD~1\n\n--- What this will do ---\n\
+             1. git stash\n   Sets local changes aside\n\
+             2. git reset --hard HEAD~1\n   Moves the branch back\n   \
+             Risk: The commit is only left in the reflog\n\n\
+             Warning: Discards the commit\n\nDry run: nothing was run."
+        );
+    }
+
+    #[test]
+    fn test_git_args_refuse_options() {
+        assert!(git_args("git_log", &json!({ "revision": "--output=/tmp/x" })).is_err());
+        assert!(git_args("git_push", &json!({})).is_err());
+    }
+}
@@ -0,0 +1,555 @@
+//! Output formats for command results, chosen with `--format`.
+//!
+//! Commands describe what they found as a [`Report`] and leave the writing to a
+//! [`Formatter`]. Adding a format means implementing the trait and listing it in [`FORMATS`];
+//! every command that takes `--format` picks it up from there.
+
+use std::fmt;
+
+use clap::builder::PossibleValue;
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::error::LumenError;
+use crate::webhook::WebhookConfig;
+
+use super::diff::RiskLevel;
+use super::markdown;
+
+/// The result of a command, in the shapes the formats need
+pub struct Report {
+    /// The command it came from, e.g. `review`
+    pub command: &'static str,
+    /// Page title for formats that have one
+    pub title: String,
+    /// The result for people, as the terminal shows it
+    pub markdown: String,
+    /// Slack's markup of it, for commands that write one
+    pub slack: Option<String>,
+    /// The result for programs
+    pub data: Value,
+    /// Remarks on a file (and line), for formats that annotate code
+    pub findings: Vec<Annotation>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub level: RiskLevel,
+    pub file: String,
+    pub line: Option<usize>,
+    pub message: String,
+    /// Narrows the SARIF rule to `lumen/<command>/<category>`, so scanners can filter by it
+    pub category: Option<String>,
+}
+
+impl Report {
+    pub fn new(
+        command: &'static str,
+        title: impl Into<String>,
+        markdown: impl Into<String>,
+        data: Value,
+    ) -> Self {
+        Report {
+            command,
+            title: title.into(),
+            markdown: markdown.into(),
+            slack: None,
+            data,
+            findings: Vec::new(),
+        }
+    }
+
+    pub fn with_findings(mut self, findings: Vec<Annotation>) -> Self {
+        self.findings = findings;
+        self
+    }
+
+    pub fn with_slack(mut self, slack: String) -> Self {
+        self.slack = Some(slack);
+        self
+    }
+
+    /// The text to send to chat: Slack's markup where the target renders it
+    fn chat_text(&self, slack_markup: bool) -> &str {
+        match &self.slack {
+            Some(slack) if slack_markup => slack,
+            _ => &self.markdown,
+        }
+    }
+}
+
+/// Where the output is going, for formats that adapt to it
+pub struct Target<'a> {
+    /// Whether stdout takes ANSI styles
+    pub styled: bool,
+    pub webhook: Option<&'a WebhookConfig>,
+}
+
+pub enum Rendered {
+    /// Write to stdout
+    Print(String),
+    /// Send to the configured webhook
+    Post(String),
+}
+
+pub trait Formatter: Sync {
+    /// The value `--format` takes
+    fn name(&self) -> &'static str;
+
+    /// One line for `--help`
+    fn help(&self) -> &'static str;
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Whether the command should ask the provider for a structured answer (a summary and
+    /// notes per file) instead of prose
+    fn structured(&self) -> bool {
+        false
+    }
+
+    /// Whether the command prints as it goes, with spinners and prompts, rather than
+    /// handing over a [`Report`] at the end
+    fn interactive(&self) -> bool {
+        false
+    }
+
+    fn render(&self, report: &Report, target: &Target) -> Result<Rendered, LumenError>;
+}
+
+struct Markdown;
+struct Json;
+struct Sarif;
+struct Html;
+struct Slack;
+struct Webhook;
+
+/// Every format `--format` accepts, the first being the default
+const FORMATS: &[Format] = &[
+    Format(&Markdown),
+    Format(&Json),
+    Format(&Sarif),
+    Format(&Html),
+    Format(&Slack),
+    Format(&Webhook),
+];
+
+impl Formatter for Markdown {
+    fn name(&self) -> &'static str {
+        "markdown"
+    }
+
+    fn help(&self) -> &'static str {
+        "Rendered markdown for reading in the terminal"
+    }
+
+    fn aliases(&self) -> &'static [&'static str] {
+        &["text"]
+    }
+
+    fn interactive(&self) -> bool {
+        true
+    }
+
+    fn render(&self, report: &Report, target: &Target) -> Result<Rendered, LumenError> {
+        Ok(Rendered::Print(if target.styled {
+            markdown::render(&report.markdown)
+        } else {
+            report.markdown.clone()
+        }))
+    }
+}
+
+impl Formatter for Json {
+    fn name(&self) -> &'static str {
+        "json"
+    }
+
+    fn help(&self) -> &'static str {
+        "A single JSON document, for scripts and editor plugins"
+    }
+
+    fn structured(&self) -> bool {
+        true
+    }
+
+    fn render(&self, report: &Report, _: &Target) -> Result<Rendered, LumenError> {
+        Ok(Rendered::Print(serde_json::to_string_pretty(&report.data)?))
+    }
+}
+
+impl Formatter for Sarif {
+    fn name(&self) -> &'static str {
+        "sarif"
+    }
+
+    fn help(&self) -> &'static str {
+        "SARIF 2.1.0, for code scanning dashboards"
+    }
+
+    fn structured(&self) -> bool {
+        true
+    }
+
+    fn render(&self, report: &Report, _: &Target) -> Result<Rendered, LumenError> {
+        Ok(Rendered::Print(serde_json::to_string_pretty(&sarif_log(
+            report,
+        ))?))
+    }
+}
+
+impl Formatter for Html {
+    fn name(&self) -> &'static str {
+        "html"
+    }
+
+    fn help(&self) -> &'static str {
+        "A standalone HTML page"
+    }
+
+    fn render(&self, report: &Report, _: &Target) -> Result<Rendered, LumenError> {
+        Ok(Rendered::Print(html_page(&report.title, &report.markdown)))
+    }
+}
+
+impl Formatter for Slack {
+    fn name(&self) -> &'static str {
+        "slack"
+    }
+
+    fn help(&self) -> &'static str {
+        "Slack's markup, for pasting into a message"
+    }
+
+    fn render(&self, report: &Report, _: &Target) -> Result<Rendered, LumenError> {
+        Ok(Rendered::Print(report.chat_text(true).to_string()))
+    }
+}
+
+impl Formatter for Webhook {
+    fn name(&self) -> &'static str {
+        "webhook"
+    }
+
+    fn help(&self) -> &'static str {
+        "Post to the webhook configured in `webhook.url`"
+    }
+
+    fn render(&self, report: &Report, target: &Target) -> Result<Rendered, LumenError> {
+        let webhook = target.webhook.ok_or_else(missing_webhook)?;
+        Ok(Rendered::Post(
+            report
+                .chat_text(webhook.preset.uses_slack_markup())
+                .to_string(),
+        ))
+    }
+}
+
+fn missing_webhook() -> LumenError {
+    LumenError::InvalidConfiguration(
+        "`--format webhook` needs a `webhook` with a `url` in the config file".to_string(),
+    )
+}
+
+/// A registered format, as `--format` takes it
+#[derive(Clone, Copy)]
+pub struct Format(&'static dyn Formatter);
+
+impl Default for Format {
+    fn default() -> Self {
+        FORMATS[0]
+    }
+}
+
+impl fmt::Debug for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.name())
+    }
+}
+
+impl PartialEq for Format {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.name() == other.0.name()
+    }
+}
+
+impl ValueEnum for Format {
+    fn value_variants<'a>() -> &'a [Self] {
+        FORMATS
+    }
+
+    fn to_possible_value(&self) -> Option<PossibleValue> {
+        Some(
+            PossibleValue::new(self.0.name())
+                .help(self.0.help())
+                .aliases(self.0.aliases().iter().copied()),
+        )
+    }
+}
+
+impl Format {
+    pub fn json() -> Self {
+        Format(&Json)
+    }
+
+    pub fn webhook() -> Self {
+        Format(&Webhook)
+    }
+
+    pub fn structured(self) -> bool {
+        self.0.structured()
+    }
+
+    pub fn interactive(self) -> bool {
+        self.0.interactive()
+    }
+
+    /// Fail early, before any tokens are spent, when the format can't be delivered.
+    pub fn check(self, webhook: Option<&WebhookConfig>) -> Result<(), LumenError> {
+        if self == Format::webhook() && webhook.is_none() {
+            return Err(missing_webhook());
+        }
+        Ok(())
+    }
+
+    /// Write `report` out in this format.
+    pub async fn emit(
+        self,
+        report: &Report,
+        webhook: Option<&WebhookConfig>,
+    ) -> Result<(), LumenError> {
+        let target = Target {
+            styled: markdown::use_styles(),
+            webhook,
+        };
+        match self.0.render(report, &target)? {
+            Rendered::Print(text) => println!("{}", text.trim_end()),
+            Rendered::Post(text) => {
+                webhook.ok_or_else(missing_webhook)?.post(&text).await?;
+                println!("Posted {} to webhook.", report.command);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sarif_level(level: RiskLevel) -> &'static str {
+    match level {
+        RiskLevel::High => "error",
+        RiskLevel::Medium => "warning",
+        RiskLevel::Low => "note",
+    }
+}
+
+fn sarif_log(report: &Report) -> Value {
+    let results: Vec<Value> = report
+        .findings
+        .iter()
+        .map(|finding| {
+            let mut location = json!({
+                "physicalLocation": { "artifactLocation": { "uri": finding.file } },
+            });
+            if let Some(line) = finding.line {
+                location["physicalLocation"]["region"] = json!({ "startLine": line });
+            }
+            let rule = match &finding.category {
+                Some(category) => format!("lumen/{}/{category}", report.command),
+                None => format!("lumen/{}", report.command),
+            };
+            json!({
+                "ruleId": rule,
+                "level": sarif_level(finding.level),
+                "message": { "text": finding.message },
+                "locations": [location],
+            })
+        })
+        .collect();
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "lumen",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Inline code and bold, on escaped text.
+fn inline_html(text: &str) -> String {
+    let mut out = String::new();
+    for (i, part) in escape_html(text).split('`').enumerate() {
+        if i % 2 == 1 {
+            out.push_str(&format!("<code>{part}</code>"));
+            continue;
+        }
+        for (j, piece) in part.split("**").enumerate() {
+            if j % 2 == 1 {
+                out.push_str(&format!("<strong>{piece}</strong>"));
+            } else {
+                out.push_str(piece);
+            }
+        }
+    }
+    out
+}
+
+/// The subset of markdown commands write (headings, lists, fenced code and paragraphs) as
+/// HTML. Line based like [`markdown::render`], and as forgiving.
+fn markdown_to_html(text: &str) -> String {
+    let mut out = String::new();
+    let mut in_code = false;
+    let mut in_list = false;
+    let mut paragraph: Vec<String> = Vec::new();
+    let flush = |out: &mut String, paragraph: &mut Vec<String>| {
+        if !paragraph.is_empty() {
+            out.push_str(&format!("<p>{}</p>\n", paragraph.join("<br>\n")));
+            paragraph.clear();
+        }
+    };
+
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if let Some(info) = trimmed.strip_prefix("```") {
+            if in_code {
+                out.push_str("</code></pre>\n");
+            } else {
+                flush(&mut out, &mut paragraph);
+                match info.trim() {
+                    "" => out.push_str("<pre><code>"),
+                    lang => out.push_str(&format!(
+                        "<pre><code class=\"language-{}\">",
+                        escape_html(lang)
+                    )),
+                }
+            }
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            out.push_str(&escape_html(line));
+            out.push('\n');
+            continue;
+        }
+
+        let item = trimmed
+            .strip_prefix("- ")
+            .or_else(|| trimmed.strip_prefix("* "));
+        if in_list && item.is_none() {
+            out.push_str("</ul>\n");
+            in_list = false;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if let Some(item) = item {
+            flush(&mut out, &mut paragraph);
+            if !in_list {
+                out.push_str("<ul>\n");
+                in_list = true;
+            }
+            out.push_str(&format!("<li>{}</li>\n", inline_html(item)));
+        } else if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            flush(&mut out, &mut paragraph);
+            out.push_str(&format!(
+                "<h{level}>{}</h{level}>\n",
+                inline_html(trimmed[level..].trim())
+            ));
+        } else if trimmed.is_empty() {
+            flush(&mut out, &mut paragraph);
+        } else {
+            paragraph.push(inline_html(trimmed));
+        }
+    }
+    if in_code {
+        out.push_str("</code></pre>\n");
+    }
+    if in_list {
+        out.push_str("</ul>\n");
+    }
+    flush(&mut out, &mut paragraph);
+    out
+}
+
+fn html_page(title: &str, markdown: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>body {{ font-family: sans-serif; max-width: 50rem; margin: 2rem auto; }} \
+         pre {{ background: #f6f8fa; padding: 0.75rem; overflow-x: auto; }}</style>\n\
+         </head>\n<body>\n{}</body>\n</html>",
+        escape_html(title),
+        markdown_to_html(markdown)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_names() {
+        assert_eq!(Format::from_str("text", false), Ok(Format::default()));
+        assert_eq!(Format::from_str("webhook", false), Ok(Format::webhook()));
+        assert!(Format::from_str("sarif", false).unwrap().structured());
+        assert!(Format::default().interactive());
+        assert!(Format::from_str("xml", false).is_err());
+    }
+
+    #[test]
+    fn test_sarif_log() {
+        let report = Report::new("review", "Review", "", json!({})).with_findings(vec![
+            Annotation {
+                level: RiskLevel::High,
+                file: "src/a.rs".to_string(),
+                line: Some(10),
+                message: "Unchecked index".to_string(),
+                category: Some("bug".to_string()),
+            },
+            Annotation {
+                level: RiskLevel::Low,
+                file: "README.md".to_string(),
+                line: None,
+                message: "Typo".to_string(),
+                category: None,
+            },
+        ]);
+        let log = sarif_log(&report);
+        let results = &log["runs"][0]["results"];
+        assert_eq!(results[0]["ruleId"], "lumen/review/bug");
+        assert_eq!(results[1]["ruleId"], "lumen/review");
+        assert_eq!(results[0]["level"], "error");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["region"]["startLine"],
+            10
+        );
+        assert_eq!(results[1]["level"], "note");
+        assert!(results[1]["locations"][0]["physicalLocation"]
+            .get("region")
+            .is_none());
+    }
+
+    #[test]
+    fn test_markdown_to_html() {
+        let html = markdown_to_html(
+            "## High: Unchecked `index`\n\nIt can <panic>.\n- one\n- **two**\n\n```diff\n-a\n+b\n```",
+        );
+        assert_eq!(
+            html,
+            "<h2>High: Unchecked <code>index</code></h2>\n\
+             <p>It can &lt;panic&gt;.</p>\n\
+             <ul>\n<li>one</li>\n<li><strong>two</strong></li>\n</ul>\n\
+             <pre><code class=\"language-diff\">-a\n+b\n</code></pre>\n"
+        );
+    }
+}
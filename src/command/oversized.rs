@@ -0,0 +1,138 @@
+//! Point out files that take up most of a diff before it's sent to the provider, so a
+//! lock file or generated code doesn't silently crowd out the changes that matter, and cut
+//! what still doesn't fit the model at file and hunk boundaries.
+
+use std::io::{self, IsTerminal};
+
+use inquire::Confirm;
+use lumen_core::patch::{diff_files, estimate_tokens, omission_note};
+
+use crate::error::LumenError;
+use crate::git_entity::GitEntity;
+
+/// Diffs smaller than this are cheap enough to send as they are
+const MIN_TOTAL_TOKENS: usize = 2_000;
+/// Share of the diff's tokens that makes a single file worth asking about
+const MIN_SHARE_PERCENT: usize = 30;
+
+#[derive(Debug, PartialEq)]
+pub struct Offender {
+    pub path: String,
+    pub tokens: usize,
+    pub percent: usize,
+}
+
+/// Files holding at least `MIN_SHARE_PERCENT` of a large diff, largest first. Empty when
+/// leaving them out would leave nothing to send.
+pub fn find_offenders(diff: &str) -> Vec<Offender> {
+    let files: Vec<(&str, usize)> = diff_files(diff)
+        .into_iter()
+        .map(|(path, part)| (path, estimate_tokens(part)))
+        .collect();
+    let total: usize = files.iter().map(|(_, tokens)| tokens).sum();
+    if total < MIN_TOTAL_TOKENS {
+        return Vec::new();
+    }
+
+    let mut offenders: Vec<Offender> = files
+        .into_iter()
+        .map(|(path, tokens)| Offender {
+            path: path.to_string(),
+            tokens,
+            percent: tokens * 100 / total,
+        })
+        .filter(|offender| offender.percent >= MIN_SHARE_PERCENT)
+        .collect();
+    if offenders.iter().map(|o| o.tokens).sum::<usize>() >= total {
+        return Vec::new();
+    }
+    offenders.sort_by_key(|offender| std::cmp::Reverse(offender.tokens));
+    offenders
+}
+
+/// Warn about files that dominate the diff and, on a terminal, offer to leave each one out.
+pub fn confirm_exclusions(git_entity: &mut GitEntity) -> Result<(), LumenError> {
+    let offenders = find_offenders(git_entity.diff());
+    if offenders.is_empty() {
+        return Ok(());
+    }
+
+    let interactive = io::stdin().is_terminal() && io::stderr().is_terminal();
+    let mut excluded = Vec::new();
+    for offender in offenders {
+        let message = format!(
+            "{} contributes {}% of the diff (~{} tokens)",
+            offender.path,
+            offender.percent,
+            format_tokens(offender.tokens)
+        );
+        if !interactive {
+            eprintln!("\x1b[33mwarning:\x1b[0m {message}");
+            continue;
+        }
+        let exclude = Confirm::new(&format!("{message}. Exclude it?"))
+            .with_default(true)
+            .with_help_message("Large generated or vendored files crowd out the real changes")
+            .prompt()
+            .map_err(|e| LumenError::CommandError(e.to_string()))?;
+        if exclude {
+            excluded.push(offender.path);
+        }
+    }
+
+    git_entity.exclude_files(&excluded);
+    Ok(())
+}
+
+/// Cut a diff that's too large for one request down to `max_tokens`, saying which files were
+/// left out. The prompt carries the same note, so the model knows it sees only part of it.
+pub fn fit_to_context(git_entity: &mut GitEntity, max_tokens: usize) {
+    let omitted = git_entity.fit_to(max_tokens);
+    if !omitted.is_empty() {
+        eprintln!("\x1b[33mwarning:\x1b[0m {}", omission_note(&omitted));
+    }
+}
+
+/// `850` or `12.3k`
+fn format_tokens(tokens: usize) -> String {
+    if tokens < 1_000 {
+        tokens.to_string()
+    } else {
+        format!("{:.1}k", tokens as f64 / 1_000.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, lines: usize) -> String {
+        format!(
+            "diff --git a/{path} b/{path}\n--- a/{path}\n+++ b/{path}\n@@ -1 +1,{lines} @@\n{}",
+            "+some added line of content\n".repeat(lines)
+        )
+    }
+
+    #[test]
+    fn test_finds_dominant_file() {
+        let diff = format!("{}{}", file("Cargo.lock", 1000), file("src/main.rs", 100));
+        let offenders = find_offenders(&diff);
+        assert_eq!(offenders.len(), 1);
+        assert_eq!(offenders[0].path, "Cargo.lock");
+        assert!(offenders[0].percent >= 85);
+    }
+
+    #[test]
+    fn test_small_or_single_file_diffs_are_left_alone() {
+        let small = format!("{}{}", file("Cargo.lock", 50), file("src/main.rs", 5));
+        assert!(find_offenders(&small).is_empty());
+        // Excluding the only file would leave nothing to explain
+        assert!(find_offenders(&file("src/main.rs", 1000)).is_empty());
+    }
+
+    #[test]
+    fn test_format_tokens() {
+        assert_eq!(format_tokens(850), "850");
+        assert_eq!(format_tokens(12_345), "12.3k");
+    }
+}
@@ -0,0 +1,389 @@
+//! Built-in commit picker for `explain --list`: the log with a fuzzy filter and a preview
+//! of the selected commit.
+
+use std::collections::HashMap;
+use std::io;
+use std::process::Command;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+use super::diff::fuzzy_match;
+use super::diff::terminal::TerminalGuard;
+use super::diff::theme;
+use crate::error::LumenError;
+use crate::logging;
+
+/// Commits loaded into the picker, newest first
+const MAX_COMMITS: usize = 2000;
+/// Lines of `git show` kept for the preview
+const MAX_PREVIEW_LINES: usize = 1000;
+/// Terminal width from which the preview goes beside the list instead of below it
+const SIDE_BY_SIDE_WIDTH: u16 = 120;
+
+#[derive(Debug, PartialEq)]
+struct LogEntry {
+    sha: String,
+    short: String,
+    subject: String,
+    author: String,
+    age: String,
+}
+
+/// Parse `git log` output with fields separated by `\x1f`.
+fn parse_log(output: &str) -> Vec<LogEntry> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\x1f').map(str::to_string);
+            Some(LogEntry {
+                sha: fields.next()?,
+                short: fields.next()?,
+                subject: fields.next()?,
+                author: fields.next()?,
+                age: fields.next()?,
+            })
+        })
+        .collect()
+}
+
+fn load_log() -> Result<Vec<LogEntry>, LumenError> {
+    let output = logging::output(Command::new("git").args([
+        "log",
+        &format!("--max-count={MAX_COMMITS}"),
+        "--format=%H%x1f%h%x1f%s%x1f%an%x1f%cr",
+    ]))?;
+    if !output.status.success() {
+        return Err(LumenError::CommandError(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(parse_log(&String::from_utf8_lossy(&output.stdout)))
+}
+
+fn load_preview(sha: &str) -> Vec<String> {
+    let output = logging::output(Command::new("git").args([
+        "show",
+        "--stat",
+        "--patch",
+        "--no-color",
+        "--format=fuller",
+        sha,
+    ]));
+    match output {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .take(MAX_PREVIEW_LINES)
+            .map(str::to_string)
+            .collect(),
+        Ok(output) => vec![String::from_utf8_lossy(&output.stderr).trim().to_string()],
+        Err(e) => vec![e.to_string()],
+    }
+}
+
+enum Action {
+    None,
+    Select,
+    Cancel,
+}
+
+struct Picker {
+    entries: Vec<LogEntry>,
+    /// Lowercased text of each entry that the query is matched against
+    haystacks: Vec<String>,
+    query: String,
+    /// Indices into `entries` that match the query
+    filtered: Vec<usize>,
+    selected: usize,
+    preview_scroll: u16,
+    previews: HashMap<String, Vec<String>>,
+}
+
+impl Picker {
+    fn new(entries: Vec<LogEntry>) -> Self {
+        let haystacks = entries
+            .iter()
+            .map(|e| format!("{} {} {}", e.short, e.subject, e.author).to_lowercase())
+            .collect();
+        let mut picker = Picker {
+            entries,
+            haystacks,
+            query: String::new(),
+            filtered: Vec::new(),
+            selected: 0,
+            preview_scroll: 0,
+            previews: HashMap::new(),
+        };
+        picker.filter();
+        picker
+    }
+
+    fn filter(&mut self) {
+        let query = self.query.to_lowercase();
+        self.filtered = (0..self.entries.len())
+            .filter(|&i| fuzzy_match(&self.haystacks[i], &query))
+            .collect();
+        self.selected = 0;
+        self.preview_scroll = 0;
+    }
+
+    fn current(&self) -> Option<&LogEntry> {
+        self.filtered.get(self.selected).map(|&i| &self.entries[i])
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let Some(last) = self.filtered.len().checked_sub(1) else {
+            return;
+        };
+        self.selected = self.selected.saturating_add_signed(delta).min(last);
+        self.preview_scroll = 0;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Esc => return Action::Cancel,
+            KeyCode::Char('c') if ctrl => return Action::Cancel,
+            KeyCode::Enter if self.current().is_some() => return Action::Select,
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Char('p' | 'k') if ctrl => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::Char('n' | 'j') if ctrl => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-10),
+            KeyCode::PageDown => self.move_selection(10),
+            KeyCode::Char('u') if ctrl => {
+                self.preview_scroll = self.preview_scroll.saturating_sub(10);
+            }
+            KeyCode::Char('d') if ctrl => {
+                self.preview_scroll = self.preview_scroll.saturating_add(10);
+            }
+            KeyCode::Backspace if self.query.pop().is_some() => self.filter(),
+            KeyCode::Char(c) if !ctrl => {
+                self.query.push(c);
+                self.filter();
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    /// Run `git show` for the selected commit unless it's been loaded before.
+    fn load_current_preview(&mut self) {
+        if let Some(sha) = self.current().map(|entry| entry.sha.clone()) {
+            self.previews
+                .entry(sha)
+                .or_insert_with_key(|sha| load_preview(sha));
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let t = theme::get();
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Min(1),
+                Constraint::Length(1),
+            ])
+            .split(frame.area());
+
+        let prompt = Line::from(vec![
+            Span::styled(" > ", Style::default().fg(t.ui.border_focused).bold()),
+            Span::styled(
+                format!("{}█", self.query),
+                Style::default().fg(t.ui.text_primary),
+            ),
+            Span::styled(
+                format!("  {}/{}", self.filtered.len(), self.entries.len()),
+                Style::default().fg(t.ui.text_muted),
+            ),
+        ]);
+        frame.render_widget(Paragraph::new(prompt), rows[0]);
+
+        let direction = if rows[1].width >= SIDE_BY_SIDE_WIDTH {
+            Direction::Horizontal
+        } else {
+            Direction::Vertical
+        };
+        let body = Layout::default()
+            .direction(direction)
+            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+            .split(rows[1]);
+        self.render_list(frame, body[0]);
+        self.render_preview(frame, body[1]);
+
+        let hints =
+            " type to filter · ↑/↓ select · ctrl-u/ctrl-d scroll preview · enter pick · esc cancel";
+        frame.render_widget(
+            Paragraph::new(Span::styled(hints, Style::default().fg(t.ui.text_muted))),
+            rows[2],
+        );
+    }
+
+    fn render_list(&self, frame: &mut Frame, area: Rect) {
+        let t = theme::get();
+        let lines: Vec<Line> = self
+            .filtered
+            .iter()
+            .enumerate()
+            .map(|(row, &i)| {
+                let entry = &self.entries[i];
+                let line = Line::from(vec![
+                    Span::styled(
+                        format!(" {} ", entry.short),
+                        Style::default().fg(t.ui.highlight),
+                    ),
+                    Span::styled(
+                        entry.subject.clone(),
+                        Style::default().fg(t.ui.text_primary),
+                    ),
+                    Span::styled(
+                        format!("  {}, {}", entry.author, entry.age),
+                        Style::default().fg(t.ui.text_muted),
+                    ),
+                ]);
+                if row == self.selected {
+                    line.style(Style::default().bg(t.ui.selection_bg))
+                } else {
+                    line
+                }
+            })
+            .collect();
+        // Keep the selected row in view
+        let scroll = self
+            .selected
+            .saturating_sub(area.height.saturating_sub(1) as usize);
+        frame.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), area);
+    }
+
+    fn render_preview(&self, frame: &mut Frame, area: Rect) {
+        let t = theme::get();
+        let current = self.current();
+        let block = Block::default()
+            .title(current.map_or(String::new(), |entry| format!(" {} ", entry.short)))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+        let preview = current
+            .and_then(|entry| self.previews.get(&entry.sha))
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let lines: Vec<Line> = preview
+            .iter()
+            .map(|line| {
+                let color = if line.starts_with("+++") || line.starts_with("---") {
+                    t.ui.text_secondary
+                } else if line.starts_with('+') {
+                    t.ui.stats_added
+                } else if line.starts_with('-') {
+                    t.ui.stats_removed
+                } else if line.starts_with("@@") || line.starts_with("diff --git") {
+                    t.ui.highlight
+                } else {
+                    t.ui.text_primary
+                };
+                Line::from(Span::styled(line.clone(), Style::default().fg(color)))
+            })
+            .collect();
+        frame.render_widget(
+            Paragraph::new(lines)
+                .block(block)
+                .scroll((self.preview_scroll, 0)),
+            area,
+        );
+    }
+}
+
+/// Show the picker and return the full SHA of the chosen commit.
+pub fn pick_commit() -> Result<String, LumenError> {
+    let entries = load_log()?;
+    if entries.is_empty() {
+        return Err(LumenError::CommandError(
+            "no commits to pick from".to_string(),
+        ));
+    }
+
+    let mut picker = Picker::new(entries);
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    loop {
+        picker.load_current_preview();
+        terminal.draw(|frame| picker.render(frame))?;
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match picker.handle_key(key) {
+            Action::None => {}
+            Action::Select => {
+                if let Some(entry) = picker.current() {
+                    return Ok(entry.sha.clone());
+                }
+            }
+            Action::Cancel => {
+                return Err(LumenError::CommandError("no commit selected".to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> Picker {
+        Picker::new(parse_log(
+            "aaa111\x1faaa\x1fAdd parser\x1fAda\x1f2 days ago\n\
+             bbb222\x1fbbb\x1fFix the lexer\x1fBob\x1f3 days ago\n\
+             malformed line\n",
+        ))
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_parse_log_skips_malformed_lines() {
+        let picker = picker();
+        assert_eq!(picker.entries.len(), 2);
+        assert_eq!(picker.entries[1].subject, "Fix the lexer");
+        assert_eq!(picker.entries[1].age, "3 days ago");
+    }
+
+    #[test]
+    fn test_typing_filters_and_selection_stays_in_range() {
+        let mut picker = picker();
+        for c in "flx".chars() {
+            picker.handle_key(key(KeyCode::Char(c)));
+        }
+        assert_eq!(picker.filtered, vec![1]);
+        picker.handle_key(key(KeyCode::Down));
+        assert_eq!(picker.current().unwrap().sha, "bbb222");
+
+        picker.handle_key(key(KeyCode::Char('z')));
+        assert!(picker.current().is_none());
+        assert!(matches!(
+            picker.handle_key(key(KeyCode::Enter)),
+            Action::None
+        ));
+        picker.handle_key(key(KeyCode::Backspace));
+        assert!(matches!(
+            picker.handle_key(key(KeyCode::Enter)),
+            Action::Select
+        ));
+    }
+
+    #[test]
+    fn test_filter_matches_author() {
+        let mut picker = picker();
+        picker.query = "ada".to_string();
+        picker.filter();
+        assert_eq!(picker.filtered, vec![0]);
+    }
+}
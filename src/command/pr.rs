@@ -0,0 +1,224 @@
+//! `lumen pr`: a pull request title and description for the current branch, written from its
+//! diff and commits, and optionally opened with `gh pr create`.
+
+use std::io::{self, Write};
+use std::process::Command;
+
+use lumen_core::response::json_object;
+use serde::Deserialize;
+use serde_json::json;
+use spinoff::{spinners, Color, Spinner};
+
+use super::output::{Format, Report};
+use super::LumenCommand;
+
+use crate::{
+    error::LumenError, git_entity::GitEntity, provider::LumenProvider, vcs::git,
+    webhook::WebhookConfig,
+};
+
+/// Times to ask again when the response isn't the JSON object we asked for
+const MAX_ATTEMPTS: usize = 3;
+
+pub struct PrCommand {
+    /// The branch's changes since it left `base`
+    pub git_entity: GitEntity,
+    pub base: String,
+    /// Subject and indented body of each commit on the branch, oldest first
+    pub commits: String,
+    pub context: Option<String>,
+    /// Open the PR with `gh pr create` after confirmation
+    pub create: bool,
+    pub format: Format,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+}
+
+#[derive(Debug, PartialEq, Deserialize)]
+pub struct PrDescription {
+    pub title: String,
+    pub summary: String,
+    #[serde(default)]
+    pub changes: Vec<String>,
+    /// How the change was or can be tested
+    #[serde(default)]
+    pub testing: Vec<String>,
+}
+
+impl PrDescription {
+    /// The PR body in GitHub markdown, leaving out empty sections.
+    pub fn body(&self) -> String {
+        let mut body = format!("## Summary\n\n{}\n", self.summary.trim());
+        for (heading, items) in [
+            ("Notable changes", &self.changes),
+            ("Testing", &self.testing),
+        ] {
+            if items.is_empty() {
+                continue;
+            }
+            body.push_str(&format!("\n## {heading}\n\n"));
+            for item in items {
+                body.push_str(&format!("- {}\n", item.trim()));
+            }
+        }
+        body
+    }
+}
+
+/// Parse the `{"title": ..., "summary": ..., ...}` object from the AI response, ignoring any
+/// prose or code fences around it.
+pub fn parse_pr_response(ai_response: &str) -> Result<PrDescription, String> {
+    let json = json_object(ai_response).ok_or("no JSON object in response")?;
+    let mut description: PrDescription = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    description.title = description
+        .title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    if description.title.is_empty() {
+        return Err("empty title in response".to_string());
+    }
+    Ok(description)
+}
+
+/// What to compare the branch against without `--base`: the default branch of the remote the
+/// branch tracks (or `origin`), or else a local `main` or `master`.
+pub fn default_base() -> Result<String, LumenError> {
+    let remote = git(&["rev-parse", "--abbrev-ref", "@{upstream}"])
+        .ok()
+        .and_then(|upstream| upstream.trim().split_once('/').map(|(r, _)| r.to_string()))
+        .unwrap_or_else(|| "origin".to_string());
+
+    let remote_head = git(&[
+        "symbolic-ref",
+        "--quiet",
+        "--short",
+        &format!("refs/remotes/{remote}/HEAD"),
+    ])
+    .ok()
+    .map(|head| head.trim().to_string())
+    .filter(|head| !head.is_empty());
+    let candidates = [
+        format!("{remote}/main"),
+        format!("{remote}/master"),
+        "main".to_string(),
+        "master".to_string(),
+    ];
+    remote_head
+        .into_iter()
+        .chain(candidates)
+        .find(|candidate| git(&["rev-parse", "--verify", "--quiet", candidate.as_str()]).is_ok())
+        .ok_or_else(|| {
+            LumenError::InvalidArguments(
+                "couldn't tell which branch the PR would merge into; pass it with --base"
+                    .to_string(),
+            )
+        })
+}
+
+/// `base` as the branch name `gh pr create --base` takes, without the remote in front.
+fn branch_name<'a>(base: &'a str, remotes: &[&str]) -> &'a str {
+    match base.split_once('/') {
+        Some((remote, branch)) if remotes.contains(&remote) => branch,
+        _ => base,
+    }
+}
+
+impl PrCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let mut spinner = Spinner::new(
+            spinners::Dots,
+            "Writing the pull request description...",
+            Color::Blue,
+        );
+        let description = self.generate(provider).await;
+        spinner.clear();
+        let description = description?;
+
+        let body = description.body();
+        let markdown = format!("# {}\n\n{body}", description.title);
+        if !self.format.interactive() {
+            let data = json!({
+                "base": self.base,
+                "title": description.title,
+                "body": body,
+                "summary": description.summary.trim(),
+                "changes": description.changes,
+                "testing": description.testing,
+            });
+            let report = Report::new("pr", description.title.clone(), markdown, data);
+            return self.format.emit(&report, self.webhook.as_ref()).await;
+        }
+
+        LumenCommand::print_markdown(&markdown);
+        if self.create {
+            self.create_with(&description.title, &body)?;
+        }
+        Ok(())
+    }
+
+    async fn generate(&self, provider: &LumenProvider) -> Result<PrDescription, LumenError> {
+        let mut last_error = String::new();
+        for _ in 0..MAX_ATTEMPTS {
+            let response = provider.pr(self).await?;
+            match parse_pr_response(&response) {
+                Ok(description) => return Ok(description),
+                Err(e) => last_error = e,
+            }
+        }
+        Err(LumenError::CommandError(format!(
+            "no valid PR description after {MAX_ATTEMPTS} attempts: {last_error}"
+        )))
+    }
+
+    fn create_with(&self, title: &str, body: &str) -> Result<(), LumenError> {
+        print!("\nCreate the pull request with gh? [y/N] ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Pull request not created.");
+            return Ok(());
+        }
+
+        let remotes = git(&["remote"]).unwrap_or_default();
+        let remotes: Vec<&str> = remotes.lines().collect();
+        // Like `gh pr create --fill`, but with the generated title and body; gh still asks
+        // where to push the branch if it isn't pushed yet
+        let status = Command::new("gh")
+            .args(["pr", "create", "--title", title, "--body", body, "--base"])
+            .arg(branch_name(&self.base, &remotes))
+            .status()
+            .map_err(|e| LumenError::CommandError(format!("Failed to run gh pr create: {e}")))?;
+        if !status.success() {
+            return Err(LumenError::CommandError("gh pr create failed".to_string()));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_render_description() {
+        let response = "```json\n{\"title\": \"Retry failed\\n uploads\", \"summary\": \"Uploads are retried.\", \"changes\": [\"Add a retry loop\"], \"testing\": []}\n```";
+        let description = parse_pr_response(response).unwrap();
+        assert_eq!(description.title, "Retry failed uploads");
+        assert_eq!(
+            description.body(),
+            "## Summary\n\nUploads are retried.\n\n## Notable changes\n\n- Add a retry loop\n"
+        );
+        assert!(parse_pr_response(r#"{"title": " ", "summary": "x"}"#).is_err());
+        assert!(parse_pr_response("}x{").is_err());
+    }
+
+    #[test]
+    fn test_branch_name() {
+        let remotes = ["origin", "upstream"];
+        assert_eq!(branch_name("origin/main", &remotes), "main");
+        assert_eq!(branch_name("upstream/release/2.0", &remotes), "release/2.0");
+        assert_eq!(branch_name("feature/x", &remotes), "feature/x");
+    }
+}
@@ -0,0 +1,417 @@
+//! `lumen review`: findings on a diff with a severity, a location and a suggested fix,
+//! written out in any `--format`, or pinned under their lines in the diff viewer.
+
+use std::time::Duration;
+
+use lumen_core::patch::is_ci_config;
+use lumen_core::response::json_object;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use spinoff::{spinners, Color, Spinner};
+
+use crate::commit_reference::CommitReference;
+use crate::config::cli::Severity;
+use crate::config::configuration::{DiffConfig, DraftConfig};
+use crate::error::LumenError;
+use crate::git_entity::GitEntity;
+use crate::provider::LumenProvider;
+use crate::webhook::WebhookConfig;
+
+use super::diff::{run_diff_ui, DiffOptions, LineNote, RiskLevel};
+use super::output::{Annotation, Format, Report};
+use super::suppress::Suppressions;
+use super::LumenCommand;
+
+pub struct ReviewCommand {
+    pub git_entity: GitEntity,
+    /// What the diff viewer compares with `--tui`, `None` for the working tree
+    pub reference: Option<CommitReference>,
+    pub tui: bool,
+    pub format: Format,
+    pub settings: DiffConfig,
+    /// With `--quiet`: print only a verdict and fail when it doesn't pass
+    pub gate: Option<Gate>,
+    /// Where `--format webhook` posts to
+    pub webhook: Option<WebhookConfig>,
+    /// Findings dropped before they're counted or rendered
+    pub suppressions: Suppressions,
+}
+
+/// Pass/fail rule of `lumen review --quiet`, for gating pushes in a hook
+#[derive(Debug)]
+pub struct Gate {
+    pub max_findings: usize,
+    /// Findings below this don't count
+    pub severity: Severity,
+    pub timeout: Duration,
+}
+
+/// The finding level a `--severity` flag stands for
+pub fn severity_level(severity: Severity) -> RiskLevel {
+    match severity {
+        Severity::Low => RiskLevel::Low,
+        Severity::Medium => RiskLevel::Medium,
+        Severity::High => RiskLevel::High,
+    }
+}
+
+impl Gate {
+    fn threshold(&self) -> RiskLevel {
+        severity_level(self.severity)
+    }
+
+    /// The findings that count against the limit, and the single line to print for them:
+    /// `verdict=fail findings=2 severity=high max=0 top="src/a.rs:10 Unchecked index"`.
+    fn verdict<'a>(&self, findings: &'a [Finding]) -> (Vec<&'a Finding>, String) {
+        let counted: Vec<&Finding> = findings
+            .iter()
+            .filter(|f| f.severity >= self.threshold())
+            .collect();
+        let verdict = if counted.len() > self.max_findings {
+            "fail"
+        } else {
+            "pass"
+        };
+        let mut line = format!(
+            "verdict={verdict} findings={} severity={} max={}",
+            counted.len(),
+            severity_label(self.threshold()).to_lowercase(),
+            self.max_findings
+        );
+        if let Some(top) = counted.first() {
+            let top = format!("{} {}", top.location(), top.title).replace('"', "'");
+            line.push_str(&format!(" top=\"{top}\""));
+        }
+        (counted, line)
+    }
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+pub struct Finding {
+    pub severity: RiskLevel,
+    pub file: String,
+    /// Line in the new version of the file
+    #[serde(default)]
+    pub line: Option<usize>,
+    pub title: String,
+    #[serde(default)]
+    pub detail: String,
+    /// Like `bug`, `security` or `style`, for suppressing a kind of finding
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Unified diff that fixes it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl Finding {
+    /// `file:line`, or just the file when the provider gave no line
+    fn location(&self) -> String {
+        match self.line {
+            Some(line) => format!("{}:{line}", self.file),
+            None => self.file.clone(),
+        }
+    }
+
+    fn to_annotation(&self) -> Annotation {
+        let message = match self.detail.trim() {
+            "" => self.title.clone(),
+            detail => format!("{}\n\n{detail}", self.title),
+        };
+        Annotation {
+            level: self.severity,
+            file: self.file.clone(),
+            line: self.line,
+            message,
+            category: self.category.clone(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReviewReport {
+    #[serde(default)]
+    findings: Vec<Finding>,
+}
+
+/// Pull the findings out of the AI response, most severe first, ignoring any surrounding
+/// prose or code fences.
+pub fn extract_findings(ai_response: &str) -> Result<Vec<Finding>, LumenError> {
+    let json = json_object(ai_response).ok_or_else(|| {
+        LumenError::CommandError("AI response did not contain review findings".to_string())
+    })?;
+    let mut findings = serde_json::from_str::<ReviewReport>(json)?.findings;
+    // Stable, so the provider's order holds within a severity
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    Ok(findings)
+}
+
+pub fn severity_label(severity: RiskLevel) -> &'static str {
+    match severity {
+        RiskLevel::High => "High",
+        RiskLevel::Medium => "Medium",
+        RiskLevel::Low => "Low",
+    }
+}
+
+/// The findings as markdown, one section each with its fix as a `diff` block that
+/// `lumen apply` can pick up. Findings on CI configuration come last, under their own heading.
+pub fn to_markdown(findings: &[Finding]) -> String {
+    if findings.is_empty() {
+        return "Nothing in the diff needs attention.\n".to_string();
+    }
+    let (ci, code): (Vec<&Finding>, Vec<&Finding>) =
+        findings.iter().partition(|f| is_ci_config(&f.file));
+    let mut out = String::new();
+    push_findings(&mut out, &code);
+    if !ci.is_empty() {
+        out.push_str("# CI pipeline changes\n\n");
+        push_findings(&mut out, &ci);
+    }
+    out
+}
+
+fn push_findings(out: &mut String, findings: &[&Finding]) {
+    for finding in findings {
+        out.push_str(&format!(
+            "## {}: {}\n`{}`\n\n",
+            severity_label(finding.severity),
+            finding.title,
+            finding.location()
+        ));
+        if !finding.detail.is_empty() {
+            out.push_str(&format!("{}\n\n", finding.detail.trim()));
+        }
+        if let Some(fix) = finding.fix.as_deref().map(str::trim) {
+            if fix.starts_with("```") {
+                out.push_str(&format!("{fix}\n\n"));
+            } else if !fix.is_empty() {
+                out.push_str(&format!("```diff\n{fix}\n```\n\n"));
+            }
+        }
+    }
+}
+
+impl ReviewCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        if let Some(gate) = &self.gate {
+            return self.execute_gate(provider, gate).await;
+        }
+        let diff = self.git_entity.diff();
+        if diff.trim().is_empty() {
+            println!("No changes to review.");
+            return Ok(());
+        }
+
+        let findings = if self.format.interactive() {
+            let mut spinner = Spinner::new(spinners::Dots, "Reviewing changes...", Color::Blue);
+            let findings = extract_findings(&provider.review(diff).await?)?;
+            spinner.success("Done");
+            findings
+        } else {
+            extract_findings(&provider.review(diff).await?)?
+        };
+        let (findings, suppressed) = self.suppressions.apply(findings);
+
+        if !self.format.interactive() {
+            let report = Report::new(
+                "review",
+                "Review",
+                format!(
+                    "{}\n\n{}",
+                    self.git_entity.format_static_details(provider),
+                    to_markdown(&findings)
+                ),
+                json!({
+                    "entity": self.git_entity.to_json(),
+                    "provider": provider.to_string(),
+                    "findings": findings,
+                    "suppressed": suppressed,
+                }),
+            )
+            .with_findings(findings.iter().map(Finding::to_annotation).collect());
+            return self.format.emit(&report, self.webhook.as_ref()).await;
+        }
+
+        if self.tui {
+            self.show_in_viewer(&findings)?;
+        }
+        // Also printed after the viewer, for the fixes and findings on files it doesn't show
+        LumenCommand::print_markdown(&self.git_entity.format_static_details(provider));
+        LumenCommand::print_markdown(&to_markdown(&findings));
+        if suppressed > 0 {
+            println!("{suppressed} suppressed by ignore rules");
+        }
+        Ok(())
+    }
+
+    /// No spinner or markdown, just the verdict on stdout; what went wrong goes to stderr.
+    async fn execute_gate(&self, provider: &LumenProvider, gate: &Gate) -> Result<(), LumenError> {
+        let diff = self.git_entity.diff();
+        let findings = if diff.trim().is_empty() {
+            Vec::new()
+        } else {
+            match tokio::time::timeout(gate.timeout, provider.review(diff)).await {
+                Ok(Ok(response)) => {
+                    let findings = extract_findings(&response).inspect_err(|_| {
+                        println!("verdict=error");
+                    })?;
+                    self.suppressions.apply(findings).0
+                }
+                Ok(Err(e)) => {
+                    println!("verdict=error");
+                    return Err(e.into());
+                }
+                Err(_) => {
+                    println!("verdict=timeout seconds={}", gate.timeout.as_secs());
+                    return Err(LumenError::ReviewTimeout(gate.timeout.as_secs()));
+                }
+            }
+        };
+
+        let (counted, line) = gate.verdict(&findings);
+        println!("{line}");
+        if counted.len() > gate.max_findings {
+            return Err(LumenError::ReviewFailed {
+                found: counted.len(),
+                max: gate.max_findings,
+                severity: severity_label(gate.threshold()),
+            });
+        }
+        Ok(())
+    }
+
+    fn show_in_viewer(&self, findings: &[Finding]) -> Result<(), LumenError> {
+        let notes = findings
+            .iter()
+            .map(|finding| LineNote {
+                file: finding.file.clone(),
+                // Findings without a line go at the top of their file
+                line: finding.line.unwrap_or(1),
+                level: finding.severity,
+                text: format!("{}\n{}", finding.title, finding.detail.trim()),
+            })
+            .collect();
+        run_diff_ui(DiffOptions {
+            reference: self.reference.clone(),
+            pr: None,
+            file: None,
+            watch: false,
+            watch_paths: Vec::new(),
+            review_flow: false,
+            read_only: false,
+            record: None,
+            replay: None,
+            risk: None,
+            provider: None,
+            provider_error: None,
+            summarize_every: None,
+            summary_log: None,
+            file_summaries: false,
+            preview: false,
+            snapshot: None,
+            notes,
+            focus: None,
+            settings: self.settings.clone(),
+            draft: DraftConfig::default(),
+        })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract_findings_sorts_by_severity() {
+        let response = r#"```json
+{"findings": [
+  {"severity": "low", "file": "src/a.rs", "line": 3, "title": "Unclear name"},
+  {"severity": "high", "file": "src/b.rs", "line": 10, "title": "Unchecked index", "detail": "Panics on empty input"},
+  {"severity": "low", "file": "src/c.rs", "title": "Dead code"}
+]}
+```"#;
+        let findings = extract_findings(response).unwrap();
+        let titles: Vec<&str> = findings.iter().map(|f| f.title.as_str()).collect();
+        assert_eq!(titles, vec!["Unchecked index", "Unclear name", "Dead code"]);
+        assert_eq!(findings[2].line, None);
+        assert!(extract_findings("Looks fine to me").is_err());
+    }
+
+    #[test]
+    fn test_gate_verdict() {
+        let finding = |severity, title: &str| Finding {
+            severity,
+            file: "src/a.rs".to_string(),
+            line: Some(10),
+            title: title.to_string(),
+            detail: String::new(),
+            category: None,
+            fix: None,
+        };
+        let findings = vec![
+            finding(RiskLevel::High, "Unchecked \"index\""),
+            finding(RiskLevel::Medium, "Error is dropped"),
+        ];
+        let gate = |max_findings, severity| Gate {
+            max_findings,
+            severity,
+            timeout: Duration::from_secs(60),
+        };
+
+        let (counted, line) = gate(0, Severity::High).verdict(&findings);
+        assert_eq!(counted.len(), 1);
+        assert_eq!(
+            line,
+            "verdict=fail findings=1 severity=high max=0 top=\"src/a.rs:10 Unchecked 'index'\""
+        );
+        let (_, line) = gate(2, Severity::Medium).verdict(&findings);
+        assert!(line.starts_with("verdict=pass findings=2 severity=medium max=2 "));
+        assert_eq!(
+            gate(0, Severity::Low).verdict(&[]).1,
+            "verdict=pass findings=0 severity=low max=0"
+        );
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let findings = vec![Finding {
+            severity: RiskLevel::Medium,
+            file: "src/a.rs".to_string(),
+            line: Some(4),
+            title: "Error is dropped".to_string(),
+            detail: "The write can fail silently.".to_string(),
+            category: None,
+            fix: Some(
+                "--- a/src/a.rs\n+++ b/src/a.rs\n@@ -4 +4 @@\n-write();\n+write()?;".to_string(),
+            ),
+        }];
+        assert_eq!(
+            to_markdown(&findings),
+            "## Medium: Error is dropped\n`src/a.rs:4`\n\nThe write can fail silently.\n\n```diff\n--- a/src/a.rs\n+++ b/src/a.rs\n@@ -4 +4 @@\n-write();\n+write()?;\n```\n\n"
+        );
+        assert_eq!(to_markdown(&[]), "Nothing in the diff needs attention.\n");
+    }
+
+    #[test]
+    fn test_ci_findings_get_their_own_section() {
+        let finding = |file: &str, title: &str| Finding {
+            severity: RiskLevel::High,
+            file: file.to_string(),
+            line: None,
+            title: title.to_string(),
+            detail: String::new(),
+            category: None,
+            fix: None,
+        };
+        let findings = vec![
+            finding(".github/workflows/ci.yml", "Unpinned action"),
+            finding("src/a.rs", "Unchecked index"),
+        ];
+        assert_eq!(
+            to_markdown(&findings),
+            "## High: Unchecked index\n`src/a.rs`\n\n# CI pipeline changes\n\n## High: Unpinned action\n`.github/workflows/ci.yml`\n\n"
+        );
+    }
+}
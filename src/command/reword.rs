@@ -0,0 +1,272 @@
+//! `lumen reword FROM..TO`: draft better messages for a range of commits and rewrite them
+//! in place, by running `git rebase -i` with a todo list written up front.
+
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use spinoff::{spinners, Color, Spinner};
+
+use crate::config::configuration::DraftConfig;
+use crate::error::LumenError;
+use crate::git_entity::commit::{Commit, CommitError};
+use crate::git_entity::diff::Diff;
+use crate::git_entity::GitEntity;
+use crate::logging;
+use crate::provider::LumenProvider;
+use crate::vcs::git;
+
+use super::draft::DraftCommand;
+use super::output::Format;
+
+pub struct RewordCommand {
+    pub from: String,
+    pub to: String,
+    pub context: Option<String>,
+    pub draft_config: DraftConfig,
+    /// Shell hook each drafted message is piped through
+    pub hook: Option<String>,
+}
+
+/// A commit of the range with its message before and after
+#[derive(Debug, PartialEq)]
+pub struct Reworded {
+    pub sha: String,
+    pub old: String,
+    pub new: String,
+}
+
+fn subject(message: &str) -> &str {
+    message.lines().next().unwrap_or_default()
+}
+
+/// Quote `path` for the shell that runs `exec` lines.
+fn shell_quote(path: &Path) -> String {
+    format!("'{}'", path.display().to_string().replace('\'', r"'\''"))
+}
+
+/// The rebase todo list: each commit picked as is, then amended with the message in
+/// `dir`, named after its SHA.
+pub fn build_todo(commits: &[Reworded], dir: &Path) -> String {
+    commits
+        .iter()
+        .map(|commit| {
+            let file = shell_quote(&dir.join(&commit.sha));
+            let amend = if commit.old == commit.new {
+                String::new()
+            } else {
+                format!("exec git commit --amend --quiet --no-verify --allow-empty -F {file}\n")
+            };
+            format!("pick {} {}\n{amend}", commit.sha, subject(&commit.old))
+        })
+        .collect()
+}
+
+/// Each commit's subject line before and after, for approval.
+pub fn comparison(commits: &[Reworded]) -> String {
+    commits
+        .iter()
+        .map(|commit| {
+            let short = &commit.sha[..7.min(commit.sha.len())];
+            if commit.old == commit.new {
+                format!("{short}  {}  (unchanged)\n", subject(&commit.old))
+            } else {
+                format!(
+                    "{short}  {}\n      → {}\n",
+                    subject(&commit.old),
+                    subject(&commit.new)
+                )
+            }
+        })
+        .collect()
+}
+
+impl RewordCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        // The rebase rewrites the current branch, so the range has to end there
+        if git(&["rev-parse", &self.to])? != git(&["rev-parse", "HEAD"])? {
+            return Err(LumenError::InvalidArguments(format!(
+                "{} isn't HEAD; check out the branch to reword first",
+                self.to
+            )));
+        }
+        let range = format!("{}..{}", self.from, self.to);
+        if !git(&["rev-list", "--merges", &range])?.is_empty() {
+            return Err(LumenError::InvalidArguments(format!(
+                "{range} has merge commits, which reword can't rewrite"
+            )));
+        }
+        let shas = git(&["rev-list", "--reverse", &range])?;
+        if shas.is_empty() {
+            return Err(LumenError::InvalidArguments(format!(
+                "no commits in {range}"
+            )));
+        }
+
+        let shas: Vec<&str> = shas.lines().collect();
+        let mut commits = Vec::new();
+        for (i, sha) in shas.iter().enumerate() {
+            let mut spinner = Spinner::new(
+                spinners::Dots,
+                format!("Drafting message {}/{}...", i + 1, shas.len()),
+                Color::Blue,
+            );
+            let reworded = self.draft(provider, sha).await?;
+            spinner.clear();
+            commits.push(reworded);
+        }
+
+        print!("{}", comparison(&commits));
+        if commits.iter().all(|commit| commit.old == commit.new) {
+            println!("Nothing to reword.");
+            return Ok(());
+        }
+        if !io::stdin().is_terminal() {
+            return Err(LumenError::InvalidArguments(
+                "reword needs an interactive terminal to confirm the new messages".to_string(),
+            ));
+        }
+        print!("\nRewrite these commits? [y/N] ");
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Reword aborted.");
+            return Ok(());
+        }
+
+        self.rewrite(&commits)
+    }
+
+    /// Draft a new message for one commit. Commits with nothing but excluded files, like
+    /// lock files, keep theirs.
+    async fn draft(&self, provider: &LumenProvider, sha: &str) -> Result<Reworded, LumenError> {
+        let commit = match Commit::new(sha.to_string()) {
+            Ok(commit) => commit,
+            Err(LumenError::GitCommitError(CommitError::EmptyDiff(_))) => {
+                let message = git(&["log", "-1", "--format=%B", sha])?.trim().to_string();
+                return Ok(Reworded {
+                    sha: sha.to_string(),
+                    old: message.clone(),
+                    new: message,
+                });
+            }
+            Err(e) => return Err(e),
+        };
+        let old = commit.message.trim().to_string();
+        let intent = format!("The commit's current message, to improve on:\n{old}");
+        let context = match &self.context {
+            Some(context) => format!("{context}\n\n{intent}"),
+            None => intent,
+        };
+        let mut git_entity = GitEntity::Diff(Diff::WorkingTree {
+            staged: true,
+            diff: commit.diff,
+        });
+        super::oversized::fit_to_context(&mut git_entity, provider.max_tokens());
+        let command = DraftCommand {
+            git_entity,
+            context: Some(context),
+            draft_config: self.draft_config.clone(),
+            subject_only: false,
+            commit: false,
+            edit: false,
+            count: 1,
+            format: Format::default(),
+            squash: None,
+            amend: None,
+            copy: false,
+            message_file: None,
+            hook: self.hook.clone(),
+            webhook: None,
+        };
+        let new = command
+            .generate(provider)
+            .await?
+            .swap_remove(0)
+            .to_commit_message();
+        Ok(Reworded {
+            sha: sha.to_string(),
+            old,
+            new,
+        })
+    }
+
+    /// Run the rebase with the todo list in place of the editor, amending each commit's
+    /// message as it's picked.
+    fn rewrite(&self, commits: &[Reworded]) -> Result<(), LumenError> {
+        let dir = PathBuf::from(git(&["rev-parse", "--git-path", "lumen-reword"])?.trim());
+        fs::create_dir_all(&dir)?;
+        // `exec` lines run from the top of the work tree, wherever lumen was started
+        let dir = fs::canonicalize(&dir)?;
+        for commit in commits {
+            fs::write(dir.join(&commit.sha), &commit.new)?;
+        }
+        let todo = dir.join("todo");
+        fs::write(&todo, build_todo(commits, &dir))?;
+
+        let status = logging::status(
+            Command::new("git")
+                .args(["rebase", "--interactive", "--autostash", &self.from])
+                .env("GIT_SEQUENCE_EDITOR", format!("cp {}", shell_quote(&todo))),
+        )
+        .inspect_err(|_| {
+            let _ = fs::remove_dir_all(&dir);
+        })?;
+        if !status.success() {
+            // The rebase's remaining `exec` lines still read the messages, so they stay until
+            // it's continued or aborted
+            return Err(LumenError::CommandError(format!(
+                "git rebase failed; see `git status`, and `git rebase --abort` to undo. The new \
+                 messages are kept in {}",
+                dir.display()
+            )));
+        }
+        let _ = fs::remove_dir_all(&dir);
+        println!(
+            "Reworded {} commit(s).",
+            commits.iter().filter(|c| c.old != c.new).count()
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commits() -> Vec<Reworded> {
+        vec![
+            Reworded {
+                sha: "aaaaaaaaaa".to_string(),
+                old: "wip".to_string(),
+                new: "feat: Add the parser\n\nHandles nested lists.".to_string(),
+            },
+            Reworded {
+                sha: "bbbbbbbbbb".to_string(),
+                old: "fix: Keep this".to_string(),
+                new: "fix: Keep this".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_build_todo_amends_changed_messages() {
+        let todo = build_todo(&commits(), Path::new("/repo/.git/it's here"));
+        assert_eq!(
+            todo,
+            "pick aaaaaaaaaa wip\n\
+             exec git commit --amend --quiet --no-verify --allow-empty -F '/repo/.git/it'\\''s here/aaaaaaaaaa'\n\
+             pick bbbbbbbbbb fix: Keep this\n"
+        );
+    }
+
+    #[test]
+    fn test_comparison() {
+        assert_eq!(
+            comparison(&commits()),
+            "aaaaaaa  wip\n      → feat: Add the parser\nbbbbbbb  fix: Keep this  (unchanged)\n"
+        );
+    }
+}
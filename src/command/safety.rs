@@ -0,0 +1,415 @@
+//! The safety policy `lumen operate` checks a proposed command against before running it.
+//!
+//! The command line is cut into its simple commands the way a shell would, and each one is
+//! matched against deny patterns like `git reset --hard`: the pattern's words have to start
+//! the command and its flags have to appear anywhere in it, so `git -C repo clean -dfx` is
+//! caught by `git clean -f`.
+
+use crate::config::configuration::OperateConfig;
+
+/// Blocked unless they're allowed in the config or `--allow-destructive` is passed
+pub const DEFAULT_DENY: [&str; 13] = [
+    "git push --force",
+    "git push -f",
+    "git push --mirror",
+    "git reset --hard",
+    "git clean -f",
+    "git clean --force",
+    "git checkout -f",
+    "git checkout --force",
+    "git branch -D",
+    "git stash clear",
+    "git stash drop",
+    "git filter-branch",
+    "git update-ref -d",
+];
+
+/// Words that run the command after them, with their options that take the next word as
+/// their value
+const WRAPPERS: [(&str, &[&str]); 6] = [
+    (
+        "sudo",
+        &[
+            "-u",
+            "-g",
+            "-h",
+            "-p",
+            "-C",
+            "-D",
+            "-r",
+            "-t",
+            "-T",
+            "-U",
+            "--user",
+            "--group",
+            "--host",
+            "--prompt",
+            "--close-from",
+            "--chdir",
+            "--role",
+            "--type",
+            "--command-timeout",
+            "--other-user",
+        ],
+    ),
+    (
+        "env",
+        &["-u", "-C", "-S", "--unset", "--chdir", "--split-string"],
+    ),
+    ("command", &[]),
+    ("exec", &["-a"]),
+    ("nohup", &[]),
+    ("time", &["-f", "-o", "--format", "--output"]),
+];
+
+/// Git options before the subcommand that take the next word as their value
+const GIT_OPTIONS_WITH_VALUE: [&str; 5] = ["-C", "-c", "--git-dir", "--work-tree", "--namespace"];
+
+/// Split a shell command line into its simple commands, each as words with the quoting
+/// removed. Operators, newlines, parentheses and backticks all separate commands, so the
+/// commands of `a && b`, `a | b` and `$(a)` are each seen; so are substitutions inside
+/// double quotes.
+pub fn commands(line: &str) -> Vec<Vec<String>> {
+    let mut found = Vec::new();
+    let mut nested = Vec::new();
+    let mut command: Vec<String> = Vec::new();
+    let mut word = String::new();
+    // A word was started, even if it's empty like `""`
+    let mut in_word = false;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some(next) = chars.next() {
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            '\'' => {
+                for next in chars.by_ref() {
+                    if next == '\'' {
+                        break;
+                    }
+                    word.push(next);
+                }
+                in_word = true;
+            }
+            '"' => {
+                let mut quoted = String::new();
+                while let Some(next) = chars.next() {
+                    match next {
+                        '"' => break,
+                        '\\' => quoted.extend(chars.next()),
+                        _ => quoted.push(next),
+                    }
+                }
+                let start = [quoted.find("$("), quoted.find('`')]
+                    .into_iter()
+                    .flatten()
+                    .min();
+                if let Some(start) = start {
+                    nested.extend(commands(&quoted[start + 1..]));
+                }
+                word.push_str(&quoted);
+                in_word = true;
+            }
+            ' ' | '\t' | ';' | '&' | '|' | '\n' | '(' | ')' | '`' => {
+                if in_word {
+                    command.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+                if !matches!(c, ' ' | '\t') && !command.is_empty() {
+                    found.push(std::mem::take(&mut command));
+                }
+            }
+            _ => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        command.push(word);
+    }
+    if !command.is_empty() {
+        found.push(command);
+    }
+    found.extend(nested);
+    found
+}
+
+/// A simple command as the words that name what it does, and its flags
+#[derive(Debug, Default, PartialEq)]
+struct Invocation {
+    words: Vec<String>,
+    flags: Vec<String>,
+}
+
+impl Invocation {
+    /// Leading variable assignments, wrappers like `sudo` with their options and git's own
+    /// options are skipped, so what's left starts with the program and its subcommand.
+    /// Spellings git takes for the same thing are normalized: a `+` refspec to `git push` is
+    /// a `--force`, and `git branch -d -f` is a `-D`.
+    fn parse(command: &[String]) -> Self {
+        let mut args = command.iter().map(String::as_str).peekable();
+        while let Some(&word) = args.peek() {
+            if let Some((_, with_value)) = WRAPPERS.iter().find(|(wrapper, _)| *wrapper == word) {
+                args.next();
+                while let Some(option) = args.next_if(|arg| arg.starts_with('-') && *arg != "--") {
+                    if with_value.contains(&option) {
+                        args.next();
+                    }
+                }
+                args.next_if_eq(&"--");
+            } else if word.contains('=') && !word.starts_with('-') {
+                args.next();
+            } else {
+                break;
+            }
+        }
+        let mut invocation = Invocation::default();
+        let Some(program) = args.next() else {
+            return invocation;
+        };
+        invocation.words.push(program.to_string());
+        if program == "git" {
+            while let Some(option) = args.next_if(|arg| arg.starts_with('-')) {
+                if GIT_OPTIONS_WITH_VALUE.contains(&option) {
+                    args.next();
+                }
+            }
+        }
+
+        let mut options_done = false;
+        for arg in args {
+            if options_done || arg == "-" || !arg.starts_with('-') {
+                invocation.words.push(arg.to_string());
+            } else if arg == "--" {
+                options_done = true;
+            } else {
+                invocation.flags.push(arg.to_string());
+            }
+        }
+        if program == "git" {
+            invocation.normalize_git();
+        }
+        invocation
+    }
+
+    fn normalize_git(&mut self) {
+        let force = self.has_flag("-f") || self.has_flag("--force");
+        match self.words.get(1).map(String::as_str) {
+            Some("push") if !force && self.words[2..].iter().any(|word| word.starts_with('+')) => {
+                self.flags.push("--force".to_string());
+            }
+            Some("branch") if force && (self.has_flag("-d") || self.has_flag("--delete")) => {
+                self.flags.push("-D".to_string());
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether `flag` was passed: long flags by name, with or without a value, and short
+    /// ones letter by letter, so `-f` is found in `-fd`.
+    fn has_flag(&self, flag: &str) -> bool {
+        if flag.starts_with("--") {
+            return self.flags.iter().any(|f| {
+                f == flag
+                    || f.strip_prefix(flag)
+                        .is_some_and(|value| value.starts_with('='))
+            });
+        }
+        flag.chars().skip(1).all(|letter| {
+            self.flags
+                .iter()
+                .filter(|f| !f.starts_with("--"))
+                .any(|f| f[1..].contains(letter))
+        })
+    }
+}
+
+/// A deny or allow rule, like `git push --force`
+#[derive(Debug)]
+struct Pattern {
+    text: String,
+    invocation: Invocation,
+}
+
+impl Pattern {
+    fn new(text: &str) -> Self {
+        let words: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        Pattern {
+            text: words.join(" "),
+            invocation: Invocation::parse(&words),
+        }
+    }
+
+    fn matches(&self, invocation: &Invocation) -> bool {
+        !self.invocation.words.is_empty()
+            && invocation.words.starts_with(&self.invocation.words)
+            && self
+                .invocation
+                .flags
+                .iter()
+                .all(|flag| invocation.has_flag(flag))
+    }
+}
+
+/// A command of the proposed line that the policy blocks
+#[derive(Debug, PartialEq)]
+pub struct Violation {
+    pub command: String,
+    pub pattern: String,
+}
+
+#[derive(Debug)]
+pub struct Policy {
+    deny: Vec<Pattern>,
+    allow: Vec<Pattern>,
+}
+
+impl Policy {
+    /// The built-in deny list with the configured patterns added, and the configured
+    /// allow patterns taking precedence over both.
+    pub fn new(config: &OperateConfig) -> Self {
+        Policy {
+            deny: DEFAULT_DENY
+                .iter()
+                .copied()
+                .chain(config.deny.iter().map(String::as_str))
+                .map(Pattern::new)
+                .collect(),
+            allow: config.allow.iter().map(|p| Pattern::new(p)).collect(),
+        }
+    }
+
+    /// The first command of `line` that's denied and not allowed, if any.
+    pub fn check(&self, line: &str) -> Option<Violation> {
+        commands(line).into_iter().find_map(|command| {
+            let invocation = Invocation::parse(&command);
+            if self.allow.iter().any(|p| p.matches(&invocation)) {
+                return None;
+            }
+            let pattern = self.deny.iter().find(|p| p.matches(&invocation))?;
+            Some(Violation {
+                command: command.join(" "),
+                pattern: pattern.text.clone(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(deny: &[&str], allow: &[&str]) -> Policy {
+        Policy::new(&OperateConfig {
+            deny: deny.iter().map(|p| p.to_string()).collect(),
+            allow: allow.iter().map(|p| p.to_string()).collect(),
+        })
+    }
+
+    fn blocked_by(line: &str) -> Option<String> {
+        policy(&[], &[]).check(line).map(|v| v.pattern)
+    }
+
+    #[test]
+    fn test_commands_split_like_a_shell() {
+        assert_eq!(
+            commands(r#"git stash && git commit -m "fix; it" | tee log; echo "$(git clean -fd)""#),
+            vec![
+                vec!["git", "stash"],
+                vec!["git", "commit", "-m", "fix; it"],
+                vec!["tee", "log"],
+                vec!["echo", "$(git clean -fd)"],
+                vec!["git", "clean", "-fd"],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_deny_list() {
+        assert_eq!(
+            blocked_by("git fetch && git reset --hard origin/main").as_deref(),
+            Some("git reset --hard")
+        );
+        assert_eq!(
+            blocked_by("git -C ../app push origin +main -f").as_deref(),
+            Some("git push -f")
+        );
+        assert_eq!(
+            blocked_by("git clean -dfx").as_deref(),
+            Some("git clean -f")
+        );
+        assert_eq!(
+            blocked_by("(cd repo; git push --force)").as_deref(),
+            Some("git push --force")
+        );
+        assert_eq!(blocked_by("git push --force-with-lease"), None);
+        assert_eq!(blocked_by("git clean -n -d"), None);
+        assert_eq!(
+            blocked_by("git reset --soft HEAD~3 && git commit -m 'git reset --hard'"),
+            None
+        );
+        assert_eq!(blocked_by("git branch -d merged"), None);
+    }
+
+    #[test]
+    fn test_force_push_by_refspec() {
+        assert_eq!(
+            blocked_by("git push origin +main").as_deref(),
+            Some("git push --force")
+        );
+        assert_eq!(
+            blocked_by("git push origin +HEAD:refs/heads/main").as_deref(),
+            Some("git push --force")
+        );
+        assert_eq!(blocked_by("git push origin main"), None);
+    }
+
+    #[test]
+    fn test_forced_branch_delete() {
+        assert_eq!(
+            blocked_by("git branch -d -f old").as_deref(),
+            Some("git branch -D")
+        );
+        assert_eq!(
+            blocked_by("git branch --delete --force old").as_deref(),
+            Some("git branch -D")
+        );
+        assert_eq!(blocked_by("git branch -f topic main"), None);
+    }
+
+    #[test]
+    fn test_wrapper_options() {
+        assert_eq!(
+            blocked_by("sudo -u root git reset --hard").as_deref(),
+            Some("git reset --hard")
+        );
+        assert_eq!(
+            blocked_by("env -u GIT_DIR LANG=C nohup git clean -fd").as_deref(),
+            Some("git clean -f")
+        );
+        assert_eq!(
+            blocked_by("sudo --user=root -- git stash clear").as_deref(),
+            Some("git stash clear")
+        );
+    }
+
+    #[test]
+    fn test_configured_patterns() {
+        let policy = policy(&["git rebase"], &["git branch -D", "git push -f origin"]);
+        assert_eq!(
+            policy.check("git rebase -i main"),
+            Some(Violation {
+                command: "git rebase -i main".to_string(),
+                pattern: "git rebase".to_string(),
+            })
+        );
+        assert_eq!(policy.check("git branch -D old"), None);
+        assert_eq!(policy.check("git push -f origin topic"), None);
+        assert!(policy.check("git push -f upstream topic").is_some());
+    }
+}
@@ -0,0 +1,416 @@
+//! JSON-RPC 2.0 server over stdio for editor integrations.
+//!
+//! Messages are newline-delimited JSON. Requests are handled concurrently; while one runs, the
+//! server sends `stream` notifications with `{"id", "delta"}` as text arrives, then the
+//! response with the full result.
+//!
+//! `initialize` answers with [`PROTOCOL_VERSION`], which goes up whenever a method is removed
+//! or a field of a result changes meaning or goes away. New methods and fields don't bump it,
+//! so clients should ignore fields they don't know.
+
+use std::io::Write;
+
+use futures::stream::{FuturesUnordered, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use lumen_core::git::RangeOptions;
+use lumen_core::patch::diff_files;
+use lumen_core::prompt::AIPrompt;
+
+use crate::{
+    commit_reference::CommitReference,
+    config::configuration::DraftConfig,
+    error::LumenError,
+    git_entity::{commit::Commit, diff::Diff, GitEntity},
+    provider::LumenProvider,
+};
+
+use super::cargo_workspace;
+use super::draft::DraftCommand;
+use super::explain::{extract_report, ExplainCommand};
+use super::output::Format;
+
+/// Version of the methods and result shapes below, see the module docs
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Every method `handle` answers, listed by `initialize`
+const METHODS: &[&str] = &[
+    "initialize",
+    "explain",
+    "explain_hunk",
+    "draft_message",
+    "review_diff",
+    "diff_files",
+    "shutdown",
+];
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+pub struct ServeCommand {
+    pub draft_config: DraftConfig,
+}
+
+#[derive(Deserialize)]
+struct Request {
+    /// Absent for notifications, which get no response
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct ExplainHunkParams {
+    hunk: String,
+    file: Option<String>,
+    query: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct DraftParams {
+    /// Staged changes are used when no diff is given
+    diff: Option<String>,
+    context: Option<String>,
+}
+
+/// The changes a request is about: a commit, a range like `main..HEAD`, or the working tree
+/// (staged or not) when there's no `reference`. `paths` limits them like `lumen explain -- PATH`.
+#[derive(Deserialize, Default)]
+struct TargetParams {
+    reference: Option<String>,
+    #[serde(default)]
+    staged: bool,
+    #[serde(default)]
+    paths: Vec<String>,
+}
+
+impl TargetParams {
+    fn git_entity(&self) -> Result<GitEntity, LumenError> {
+        let reference =
+            match &self.reference {
+                Some(reference) => Some(reference.parse::<CommitReference>().map_err(|e| {
+                    LumenError::InvalidArguments(format!("invalid reference: {e}"))
+                })?),
+                None => None,
+            };
+        let options = RangeOptions::default();
+        Ok(match reference {
+            Some(CommitReference::Single(sha)) => {
+                let mut commit = Commit::new(sha)?;
+                commit.limit_to_paths(&self.paths)?;
+                GitEntity::Commit(commit)
+            }
+            Some(CommitReference::Range { from, to }) => GitEntity::Diff(Diff::from_commits_range(
+                &from,
+                &to,
+                false,
+                options,
+                &self.paths,
+            )?),
+            Some(CommitReference::TripleDots { from, to }) => GitEntity::Diff(
+                Diff::from_commits_range(&from, &to, true, options, &self.paths)?,
+            ),
+            None => GitEntity::Diff(Diff::from_working_tree(self.staged, &self.paths)?),
+        })
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct ExplainParams {
+    #[serde(flatten)]
+    target: TargetParams,
+    query: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct ReviewParams {
+    /// Unstaged changes are used when no diff is given
+    diff: Option<String>,
+}
+
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<LumenError> for RpcError {
+    fn from(e: LumenError) -> Self {
+        RpcError::new(INTERNAL_ERROR, e.to_string())
+    }
+}
+
+/// Write one message per line. Requests only interleave at await points, so lines never mix.
+fn send(message: Value) {
+    let mut stdout = std::io::stdout().lock();
+    let _ = writeln!(stdout, "{message}");
+    let _ = stdout.flush();
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": e.code, "message": e.message },
+        }),
+    }
+}
+
+fn params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, RpcError> {
+    let params = if params.is_null() { json!({}) } else { params };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+impl ServeCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut pending = FuturesUnordered::new();
+
+        loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let Some(line) = line? else { break };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let request: Request = match serde_json::from_str(&line) {
+                        Ok(request) => request,
+                        Err(e) => {
+                            send(response(Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string()))));
+                            continue;
+                        }
+                    };
+                    if request.method == "shutdown" {
+                        if let Some(id) = request.id {
+                            send(response(id, Ok(Value::Null)));
+                        }
+                        break;
+                    }
+                    pending.push(self.handle(provider, request));
+                }
+                Some(()) = pending.next(), if !pending.is_empty() => {}
+            }
+        }
+
+        // Let requests already in flight finish before exiting
+        while pending.next().await.is_some() {}
+        Ok(())
+    }
+
+    async fn handle(&self, provider: &LumenProvider, request: Request) {
+        let id = request.id.clone().unwrap_or(Value::Null);
+        let result = match request.method.as_str() {
+            "initialize" => Ok(json!({
+                "protocol": PROTOCOL_VERSION,
+                "version": env!("CARGO_PKG_VERSION"),
+                "methods": METHODS,
+            })),
+            "explain" => self.explain(provider, request.params).await,
+            "explain_hunk" => self.explain_hunk(provider, &id, request.params).await,
+            "draft_message" => self.draft_message(provider, request.params).await,
+            "review_diff" => self.review_diff(provider, &id, request.params).await,
+            "diff_files" => self::params(request.params)
+                .and_then(|target: TargetParams| Ok(changed_files(&target.git_entity()?))),
+            method => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method: {method}"),
+            )),
+        };
+        if request.id.is_some() {
+            send(response(id, result));
+        }
+    }
+
+    /// Run a prompt, streaming deltas tagged with the request id.
+    async fn stream(
+        provider: &LumenProvider,
+        id: &Value,
+        prompt: AIPrompt,
+        command: &str,
+    ) -> Result<String, RpcError> {
+        provider
+            .complete_stream(prompt, command, |delta| {
+                send(json!({
+                    "jsonrpc": "2.0",
+                    "method": "stream",
+                    "params": { "id": id, "delta": delta },
+                }))
+            })
+            .await
+            .map_err(|e| RpcError::from(LumenError::from(e)))
+    }
+
+    /// A commit, range or the working tree explained as `lumen explain --format json` would:
+    /// a markdown `summary` and a `note` per changed file.
+    async fn explain(&self, provider: &LumenProvider, params: Value) -> Result<Value, RpcError> {
+        let params: ExplainParams = self::params(params)?;
+        let git_entity = params.target.git_entity()?;
+        let command = ExplainCommand {
+            crates: cargo_workspace::api_section(&git_entity),
+            git_entity,
+            query: params.query,
+            commits: None,
+            annotate: false,
+            impact: false,
+            cache: true,
+            format: Format::json(),
+            copy: false,
+            hook: None,
+            webhook: None,
+        };
+        // Not streamed: the structured answer is JSON, which is only useful once it's complete
+        let response = provider.explain(&command).await.map_err(LumenError::from)?;
+        let report = extract_report(&response);
+        Ok(json!({
+            "entity": command.git_entity.to_json(),
+            "summary": report.summary,
+            "files": report
+                .files
+                .iter()
+                .map(|file| json!({ "path": file.path, "note": file.note }))
+                .collect::<Vec<Value>>(),
+        }))
+    }
+
+    async fn explain_hunk(
+        &self,
+        provider: &LumenProvider,
+        id: &Value,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        let params: ExplainHunkParams = self::params(params)?;
+        let prompt = AIPrompt::build_explain_hunk_prompt(
+            params.file.as_deref(),
+            &params.hunk,
+            params.query.as_deref(),
+        )
+        .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+        let text = Self::stream(provider, id, prompt, "explain").await?;
+        Ok(json!({ "text": text }))
+    }
+
+    async fn draft_message(
+        &self,
+        provider: &LumenProvider,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        let params: DraftParams = self::params(params)?;
+        let diff = match params.diff {
+            Some(diff) => Diff::WorkingTree { staged: true, diff },
+            None => Diff::from_working_tree(true, &[])?,
+        };
+        let command = DraftCommand {
+            git_entity: GitEntity::Diff(diff),
+            context: params.context,
+            draft_config: self.draft_config.clone(),
+            subject_only: false,
+            commit: false,
+            edit: false,
+            count: 1,
+            format: Format::default(),
+            squash: None,
+            amend: None,
+            copy: false,
+            message_file: None,
+            hook: None,
+            webhook: None,
+        };
+        // Not streamed: the JSON response is only useful once it's complete and validated
+        let message = command.generate(provider).await?.swap_remove(0);
+        Ok(json!({
+            "message": message.to_commit_message(),
+            "subject": message.subject,
+            "body": message.body,
+        }))
+    }
+
+    async fn review_diff(
+        &self,
+        provider: &LumenProvider,
+        id: &Value,
+        params: Value,
+    ) -> Result<Value, RpcError> {
+        let params: ReviewParams = self::params(params)?;
+        let diff = match params.diff {
+            Some(diff) => diff,
+            None => match Diff::from_working_tree(false, &[])? {
+                Diff::WorkingTree { diff, .. }
+                | Diff::CommitsRange { diff, .. }
+                | Diff::Patch { diff } => diff,
+            },
+        };
+        let prompt = AIPrompt::build_review_prompt(&diff)
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?;
+        let text = Self::stream(provider, id, prompt, "review").await?;
+        Ok(json!({ "text": text }))
+    }
+}
+
+/// Each file the changes touch, with its added and removed line counts.
+fn changed_files(git_entity: &GitEntity) -> Value {
+    let files = diff_files(git_entity.diff())
+        .into_iter()
+        .map(|(path, part)| {
+            let (mut added, mut removed) = (0, 0);
+            for line in part.lines().skip_while(|line| !line.starts_with("@@")) {
+                if line.starts_with('+') {
+                    added += 1;
+                } else if line.starts_with('-') {
+                    removed += 1;
+                }
+            }
+            json!({ "path": path, "added": added, "removed": removed })
+        })
+        .collect::<Vec<Value>>();
+    json!({ "files": files })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_response_shape() {
+        let value = response(json!(1), Err(RpcError::new(METHOD_NOT_FOUND, "nope")));
+        assert_eq!(
+            value,
+            json!({ "jsonrpc": "2.0", "id": 1, "error": { "code": -32601, "message": "nope" } })
+        );
+    }
+
+    #[test]
+    fn test_missing_params_use_defaults() {
+        let params: DraftParams = params(Value::Null).unwrap();
+        assert!(params.diff.is_none());
+        assert!(self::params::<ExplainHunkParams>(json!({})).is_err());
+    }
+
+    #[test]
+    fn test_changed_files_counts_lines() {
+        let diff = "diff --git a/src/a.rs b/src/a.rs\n--- a/src/a.rs\n+++ b/src/a.rs\n\
+                    @@ -1,2 +1,2 @@\n-old\n+new\n+more\n keep\n";
+        let entity = GitEntity::Diff(Diff::Patch {
+            diff: diff.to_string(),
+        });
+        assert_eq!(
+            changed_files(&entity),
+            json!({ "files": [{ "path": "src/a.rs", "added": 2, "removed": 1 }] })
+        );
+    }
+}
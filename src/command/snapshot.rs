@@ -0,0 +1,150 @@
+//! Personal checkpoints of the working tree, independent of commits.
+//!
+//! A snapshot is a commit of the whole working tree (tracked and untracked files, minus
+//! ignored ones) stored under `refs/lumen/snapshots/<name>`. It's written through a
+//! temporary index, so the real index and the working tree are never touched.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::error::LumenError;
+use crate::vcs;
+
+const SNAPSHOT_REFS: &str = "refs/lumen/snapshots";
+
+pub enum SnapshotAction {
+    Save { name: String, force: bool },
+    List,
+    Delete { name: String },
+}
+
+pub struct SnapshotCommand {
+    pub action: SnapshotAction,
+}
+
+fn snapshot_ref(name: &str) -> String {
+    format!("{SNAPSHOT_REFS}/{name}")
+}
+
+/// git's trimmed stdout, using `index` instead of the real index when given.
+fn git(args: &[&str], index: Option<&PathBuf>) -> Result<String, String> {
+    let mut command = Command::new("git");
+    command.args(args);
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    vcs::run_git(&mut command)
+        .map(|stdout| stdout.trim().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Write the current working tree as a tree object and return its id.
+pub fn working_tree() -> Result<String, String> {
+    let index = PathBuf::from(git(
+        &["rev-parse", "--git-path", "lumen-snapshot.index"],
+        None,
+    )?);
+    // Starting from the real index lets git skip rehashing files whose stat info is unchanged
+    let real_index = PathBuf::from(git(&["rev-parse", "--git-path", "index"], None)?);
+    if real_index.exists() {
+        fs::copy(&real_index, &index).map_err(|e| format!("failed to copy the index: {e}"))?;
+    }
+    let tree = git(&["add", "--all", "--", ":/"], Some(&index))
+        .and_then(|_| git(&["write-tree"], Some(&index)));
+    let _ = fs::remove_file(&index);
+    tree
+}
+
+/// The commit a snapshot points at, by name.
+pub fn resolve(name: &str) -> Result<String, String> {
+    git(
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{}^{{commit}}", snapshot_ref(name)),
+        ],
+        None,
+    )
+    .map_err(|_| format!("no snapshot named {name} (see `lumen snapshot list`)"))
+}
+
+impl SnapshotCommand {
+    pub fn execute(&self) -> Result<(), LumenError> {
+        match &self.action {
+            SnapshotAction::Save { name, force } => Self::save(name, *force),
+            SnapshotAction::List => Self::list(),
+            SnapshotAction::Delete { name } => Self::delete(name),
+        }
+        .map_err(LumenError::CommandError)
+    }
+
+    fn save(name: &str, force: bool) -> Result<(), String> {
+        let reference = snapshot_ref(name);
+        git(&["check-ref-format", &reference], None)
+            .map_err(|_| format!("{name} is not a valid snapshot name"))?;
+        if !force && resolve(name).is_ok() {
+            return Err(format!(
+                "snapshot {name} already exists (use --force to replace it)"
+            ));
+        }
+
+        let tree = working_tree()?;
+        let message = format!("lumen snapshot: {name}");
+        let mut args = vec!["commit-tree", &tree, "-m", &message];
+        // Unborn branches have no HEAD to hang the snapshot on
+        let head = git(&["rev-parse", "--verify", "--quiet", "HEAD"], None).ok();
+        if let Some(head) = &head {
+            args.extend(["-p", head.as_str()]);
+        }
+        let commit = git(&args, None)?;
+        git(&["update-ref", &reference, &commit], None)?;
+
+        let changes = match &head {
+            Some(head) => git(&["diff", "--shortstat", head, &commit], None)?,
+            None => String::new(),
+        };
+        let changes = if changes.is_empty() {
+            "no changes since HEAD".to_string()
+        } else {
+            format!("{changes} since HEAD")
+        };
+        println!("Saved snapshot {name} ({changes}).");
+        println!("Review what changed since with `lumen diff --snapshot {name}`.");
+        Ok(())
+    }
+
+    fn list() -> Result<(), String> {
+        let snapshots = git(
+            &[
+                "for-each-ref",
+                "--sort=-creatordate",
+                "--format=%(refname:lstrip=3)\t%(creatordate:relative)\t%(objectname:short)",
+                SNAPSHOT_REFS,
+            ],
+            None,
+        )?;
+        if snapshots.is_empty() {
+            println!("No snapshots yet. Save one with `lumen snapshot save <name>`.");
+            return Ok(());
+        }
+        for line in snapshots.lines() {
+            let mut fields = line.split('\t');
+            let (name, age, id) = (
+                fields.next().unwrap_or_default(),
+                fields.next().unwrap_or_default(),
+                fields.next().unwrap_or_default(),
+            );
+            println!("{name:<24} {id}  {age}");
+        }
+        Ok(())
+    }
+
+    fn delete(name: &str) -> Result<(), String> {
+        resolve(name)?;
+        git(&["update-ref", "-d", &snapshot_ref(name)], None)?;
+        println!("Deleted snapshot {name}.");
+        Ok(())
+    }
+}
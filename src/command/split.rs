@@ -0,0 +1,353 @@
+//! `lumen split`: turn a working tree full of unrelated changes into a series of commits.
+//!
+//! The diff is cut into hunks, the provider groups them by concern, and each group is
+//! staged on its own with `git apply --cached` and committed with a drafted message.
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+use lumen_core::patch::diff_files;
+use lumen_core::response::json_object;
+use serde::Deserialize;
+use spinoff::{spinners, Color, Spinner};
+
+use crate::config::configuration::DraftConfig;
+use crate::error::LumenError;
+use crate::git_entity::diff::Diff;
+use crate::git_entity::GitEntity;
+use crate::provider::LumenProvider;
+use crate::vcs::{git, git_with_input};
+
+use super::draft::DraftCommand;
+use super::output::Format;
+
+/// Lines of a hunk shown to the provider; the rest only matter once it's applied
+const HUNK_PREVIEW_LINES: usize = 60;
+
+pub struct SplitCommand {
+    pub context: Option<String>,
+    /// Only show the plan
+    pub dry_run: bool,
+    pub draft_config: DraftConfig,
+    /// Shell hook each drafted message is piped through
+    pub hook: Option<String>,
+}
+
+/// One hunk of the diff, with its file's header so it can be applied on its own
+#[derive(Debug, PartialEq)]
+pub struct Hunk {
+    pub file: String,
+    /// `diff --git` line and the lines after it up to the first hunk
+    header: String,
+    /// The `@@` line and its content; empty for changes without hunks, like binary files
+    text: String,
+}
+
+impl Hunk {
+    /// The `@@` line, or what the header says happened to the file when there's none.
+    pub fn describe(&self) -> String {
+        match self.text.lines().next() {
+            Some(range) => format!("{} {range}", self.file),
+            None => format!("{} (whole file)", self.file),
+        }
+    }
+}
+
+/// Split a unified diff into its hunks, in order.
+pub fn split_hunks(diff: &str) -> Vec<Hunk> {
+    let mut hunks = Vec::new();
+    for (file, part) in diff_files(diff) {
+        let mut starts: Vec<usize> = Vec::new();
+        let mut offset = 0;
+        for line in part.split_inclusive('\n') {
+            if line.starts_with("@@ ") {
+                starts.push(offset);
+            }
+            offset += line.len();
+        }
+        let header = &part[..starts.first().copied().unwrap_or(part.len())];
+        if starts.is_empty() {
+            hunks.push(Hunk {
+                file: file.to_string(),
+                header: header.to_string(),
+                text: String::new(),
+            });
+        }
+        for (i, &start) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).copied().unwrap_or(part.len());
+            hunks.push(Hunk {
+                file: file.to_string(),
+                header: header.to_string(),
+                text: part[start..end].to_string(),
+            });
+        }
+    }
+    hunks
+}
+
+/// A patch of just the hunks at `indices`, each file's header written once.
+pub fn group_patch(hunks: &[Hunk], indices: &[usize]) -> String {
+    let mut sorted = indices.to_vec();
+    sorted.sort_unstable();
+    let mut patch = String::new();
+    let mut file: Option<&str> = None;
+    for hunk in sorted.iter().filter_map(|&i| hunks.get(i)) {
+        if file != Some(hunk.file.as_str()) {
+            patch.push_str(&hunk.header);
+            file = Some(&hunk.file);
+        }
+        patch.push_str(&hunk.text);
+    }
+    patch
+}
+
+/// The hunks numbered from 1, as the provider sees them; long ones are cut short.
+fn hunk_listing(hunks: &[Hunk]) -> String {
+    hunks
+        .iter()
+        .enumerate()
+        .map(|(i, hunk)| {
+            let lines: Vec<&str> = hunk.text.lines().collect();
+            let mut text = lines
+                .iter()
+                .take(HUNK_PREVIEW_LINES)
+                .copied()
+                .collect::<Vec<_>>()
+                .join("\n");
+            if lines.len() > HUNK_PREVIEW_LINES {
+                text.push_str(&format!(
+                    "\n... {} more lines",
+                    lines.len() - HUNK_PREVIEW_LINES
+                ));
+            }
+            if text.is_empty() {
+                // Binary patches say what happened in the header, then the data
+                text = hunk
+                    .header
+                    .lines()
+                    .take_while(|line| !line.starts_with("GIT binary patch"))
+                    .collect::<Vec<_>>()
+                    .join("\n");
+            }
+            format!("Hunk {}: {}\n```diff\n{text}\n```", i + 1, hunk.file)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// One commit of the plan, with indices into the hunks
+#[derive(Debug, PartialEq)]
+pub struct PlannedCommit {
+    pub title: String,
+    pub hunks: Vec<usize>,
+}
+
+#[derive(Deserialize)]
+struct PlanResponse {
+    commits: Vec<PlannedGroup>,
+}
+
+#[derive(Deserialize)]
+struct PlannedGroup {
+    title: String,
+    hunks: Vec<usize>,
+}
+
+/// Pull the plan out of the AI response. Hunks are numbered from 1 there; unknown numbers
+/// and repeats are dropped, and hunks left out go into a final commit of their own, so
+/// every hunk is committed exactly once.
+pub fn parse_plan(ai_response: &str, hunk_count: usize) -> Result<Vec<PlannedCommit>, LumenError> {
+    let json = json_object(ai_response).ok_or_else(|| {
+        LumenError::CommandError("AI response did not contain a commit plan".to_string())
+    })?;
+    let response: PlanResponse = serde_json::from_str(json)?;
+
+    let mut seen = HashSet::new();
+    let mut plan: Vec<PlannedCommit> = response
+        .commits
+        .into_iter()
+        .map(|group| PlannedCommit {
+            title: group.title,
+            hunks: group
+                .hunks
+                .into_iter()
+                .filter(|&n| (1..=hunk_count).contains(&n) && seen.insert(n))
+                .map(|n| n - 1)
+                .collect(),
+        })
+        .filter(|commit| !commit.hunks.is_empty())
+        .collect();
+    let rest: Vec<usize> = (0..hunk_count)
+        .filter(|i| !seen.contains(&(i + 1)))
+        .collect();
+    if !rest.is_empty() {
+        plan.push(PlannedCommit {
+            title: "Remaining changes".to_string(),
+            hunks: rest,
+        });
+    }
+    Ok(plan)
+}
+
+impl SplitCommand {
+    pub async fn execute(&self, provider: &LumenProvider) -> Result<(), LumenError> {
+        // Whatever is staged would end up in the first commit
+        if !git(&["diff", "--cached", "--name-only"])?.trim().is_empty() {
+            return Err(LumenError::InvalidArguments(
+                "there are staged changes; commit or unstage them before splitting".to_string(),
+            ));
+        }
+        let diff = git(&["diff", "--binary", "--no-color", "--no-ext-diff"])?;
+        let hunks = split_hunks(&diff);
+        if hunks.is_empty() {
+            println!("No unstaged changes to split. Untracked files need a `git add -N` first.");
+            return Ok(());
+        }
+
+        let mut spinner = Spinner::new(spinners::Dots, "Planning commits...", Color::Blue);
+        let response = provider
+            .split(&hunk_listing(&hunks), self.context.as_deref())
+            .await?;
+        let plan = parse_plan(&response, hunks.len())?;
+        spinner.success("Done");
+
+        for (i, commit) in plan.iter().enumerate() {
+            println!("{}. {}", i + 1, commit.title);
+            for &hunk in &commit.hunks {
+                println!("     {}", hunks[hunk].describe());
+            }
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+
+        print!("\nCreate these {} commits? [y/N] ", plan.len());
+        io::stdout().flush()?;
+        let mut input = String::new();
+        io::stdin().read_line(&mut input)?;
+        if !matches!(input.trim().to_lowercase().as_str(), "y" | "yes") {
+            println!("Split aborted.");
+            return Ok(());
+        }
+
+        for (i, commit) in plan.iter().enumerate() {
+            let patch = group_patch(&hunks, &commit.hunks);
+            // Earlier commits may have shifted the hunks, which --recount and git's offset
+            // search take care of
+            git_with_input(&["apply", "--cached", "--recount", "-"], &patch).map_err(|e| {
+                LumenError::CommandError(format!(
+                    "couldn't stage \"{}\", stopping after {i} commit(s): {e}",
+                    commit.title
+                ))
+            })?;
+            let message = self.draft(provider, &commit.title, patch).await?;
+            git(&["commit", "--quiet", "-m", &message])?;
+            let subject = message.lines().next().unwrap_or_default();
+            println!("[{}/{}] {subject}", i + 1, plan.len());
+        }
+        Ok(())
+    }
+
+    /// A commit message for one group, with its title from the plan as the intent.
+    async fn draft(
+        &self,
+        provider: &LumenProvider,
+        title: &str,
+        patch: String,
+    ) -> Result<String, LumenError> {
+        let context = match &self.context {
+            Some(context) => format!("{title}. {context}"),
+            None => title.to_string(),
+        };
+        let command = DraftCommand {
+            git_entity: GitEntity::Diff(Diff::WorkingTree {
+                staged: true,
+                diff: patch,
+            }),
+            context: Some(context),
+            draft_config: self.draft_config.clone(),
+            subject_only: false,
+            commit: false,
+            edit: false,
+            count: 1,
+            format: Format::default(),
+            squash: None,
+            amend: None,
+            copy: false,
+            message_file: None,
+            hook: self.hook.clone(),
+            webhook: None,
+        };
+        Ok(command
+            .generate(provider)
+            .await?
+            .swap_remove(0)
+            .to_commit_message())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "\
+diff --git a/src/a.rs b/src/a.rs
+index 1111111..2222222 100644
+--- a/src/a.rs
++++ b/src/a.rs
+@@ -1,2 +1,2 @@
+-one
++ONE
+ two
+@@ -10,2 +10,2 @@
+ ten
+-eleven
++ELEVEN
+diff --git a/logo.png b/logo.png
+index 3333333..4444444 100644
+Binary files a/logo.png and b/logo.png differ
+";
+
+    #[test]
+    fn test_split_hunks_keeps_headers() {
+        let hunks = split_hunks(DIFF);
+        assert_eq!(hunks.len(), 3);
+        assert_eq!(hunks[1].describe(), "src/a.rs @@ -10,2 +10,2 @@");
+        assert_eq!(hunks[2].describe(), "logo.png (whole file)");
+
+        let patch = group_patch(&hunks, &[2, 1]);
+        assert!(patch.starts_with("diff --git a/src/a.rs b/src/a.rs\n"));
+        assert!(patch.contains("+ELEVEN\ndiff --git a/logo.png"));
+        assert!(!patch.contains("+ONE"));
+        assert_eq!(group_patch(&hunks, &[0, 1, 2]), DIFF);
+    }
+
+    #[test]
+    fn test_parse_plan_commits_every_hunk_once() {
+        let response = r#"Here you go: {"commits": [
+            {"title": "Rename", "hunks": [1, 3]},
+            {"title": "Again", "hunks": [3, 9]},
+            {"title": "Other", "hunks": [2]}
+        ]}"#;
+        let plan = parse_plan(response, 4).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                PlannedCommit {
+                    title: "Rename".to_string(),
+                    hunks: vec![0, 2],
+                },
+                PlannedCommit {
+                    title: "Other".to_string(),
+                    hunks: vec![1],
+                },
+                PlannedCommit {
+                    title: "Remaining changes".to_string(),
+                    hunks: vec![3],
+                },
+            ]
+        );
+        assert!(parse_plan("no plan", 1).is_err());
+        assert!(parse_plan("} no plan {", 1).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! Rules that drop `lumen review` findings before they're rendered, so generated or vendored
+//! code doesn't fail CI on findings nobody will act on.
+//!
+//! Rules come from `.lumenignore-findings` at the repository root and the `review.ignore`
+//! config, one per line: a path glob as `--file` takes it, then the categories and severities
+//! it applies to. Without any, every finding under the path is dropped.
+//!
+//! ```text
+//! # Style findings in generated code
+//! generated/ style
+//! vendor/
+//! * low
+//! ```
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::config::configuration::ReviewConfig;
+use crate::error::LumenError;
+use crate::logging;
+
+use super::diff::{FileFilter, RiskLevel};
+use super::review::Finding;
+
+/// Suppression file at the repository root
+pub const IGNORE_FILE: &str = ".lumenignore-findings";
+
+#[derive(Debug)]
+struct Rule {
+    paths: FileFilter,
+    categories: Vec<String>,
+    severities: Vec<RiskLevel>,
+}
+
+impl Rule {
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let mut words = line.split_whitespace();
+        let Some(glob) = words.next() else {
+            return Ok(None);
+        };
+        let mut rule = Rule {
+            paths: FileFilter::from_terms([glob])?,
+            categories: Vec::new(),
+            severities: Vec::new(),
+        };
+        for word in words.map(str::to_lowercase) {
+            match serde_json::from_value(word.clone().into()) {
+                Ok(severity) => rule.severities.push(severity),
+                Err(_) => rule.categories.push(word),
+            }
+        }
+        Ok(Some(rule))
+    }
+
+    fn matches(&self, finding: &Finding) -> bool {
+        let category = finding.category.as_deref().map(str::to_lowercase);
+        self.paths.matches_path(&finding.file)
+            && (self.categories.is_empty()
+                || category.is_some_and(|c| self.categories.contains(&c)))
+            && (self.severities.is_empty() || self.severities.contains(&finding.severity))
+    }
+}
+
+#[derive(Debug)]
+pub struct Suppressions {
+    rules: Vec<Rule>,
+}
+
+impl Suppressions {
+    /// Rules one per line, skipping blank lines and `#` comments.
+    pub fn parse<'a>(lines: impl IntoIterator<Item = &'a str>) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        for (i, line) in lines.into_iter().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            if let Some(rule) = Rule::parse(line).map_err(|e| format!("line {}: {e}", i + 1))? {
+                rules.push(rule);
+            }
+        }
+        Ok(Suppressions { rules })
+    }
+
+    /// The configured rules and those of the repository's `.lumenignore-findings`, if any.
+    pub fn load(config: &ReviewConfig) -> Result<Self, LumenError> {
+        let mut suppressions = Self::parse(config.ignore.iter().map(String::as_str))
+            .map_err(|e| LumenError::InvalidConfiguration(format!("review.ignore: {e}")))?;
+        let path = repo_root().join(IGNORE_FILE);
+        if path.is_file() {
+            let contents = std::fs::read_to_string(&path)?;
+            let file = Self::parse(contents.lines()).map_err(|e| invalid(&path, e))?;
+            suppressions.rules.extend(file.rules);
+        }
+        Ok(suppressions)
+    }
+
+    /// `findings` without the suppressed ones, and how many were dropped.
+    pub fn apply(&self, findings: Vec<Finding>) -> (Vec<Finding>, usize) {
+        let total = findings.len();
+        let kept: Vec<Finding> = findings
+            .into_iter()
+            .filter(|finding| !self.rules.iter().any(|rule| rule.matches(finding)))
+            .collect();
+        let dropped = total - kept.len();
+        (kept, dropped)
+    }
+}
+
+fn invalid(path: &Path, e: String) -> LumenError {
+    LumenError::InvalidConfiguration(format!("{}: {e}", path.display()))
+}
+
+fn repo_root() -> PathBuf {
+    logging::output(Command::new("git").args(["rev-parse", "--show-toplevel"]))
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()))
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finding(file: &str, severity: RiskLevel, category: Option<&str>) -> Finding {
+        Finding {
+            severity,
+            file: file.to_string(),
+            line: None,
+            title: format!("{file} {category:?}"),
+            detail: String::new(),
+            category: category.map(str::to_string),
+            fix: None,
+        }
+    }
+
+    fn kept(rules: &str, findings: Vec<Finding>) -> Vec<String> {
+        let suppressions = Suppressions::parse(rules.lines()).unwrap();
+        suppressions
+            .apply(findings)
+            .0
+            .into_iter()
+            .map(|f| f.title)
+            .collect()
+    }
+
+    #[test]
+    fn test_rules_by_path_and_category() {
+        let findings = || {
+            vec![
+                finding("src/generated/api.rs", RiskLevel::Low, Some("style")),
+                finding("src/generated/api.rs", RiskLevel::High, Some("bug")),
+                finding("vendor/lib.js", RiskLevel::High, Some("security")),
+                finding("src/main.rs", RiskLevel::Medium, None),
+            ]
+        };
+        assert_eq!(
+            kept(
+                "# noise\ngenerated/ Style\n\nvendor/  # not ours\n",
+                findings()
+            ),
+            ["src/generated/api.rs Some(\"bug\")", "src/main.rs None"]
+        );
+        // A category doesn't match findings without one
+        assert_eq!(
+            kept("* bug style security", findings()),
+            ["src/main.rs None"]
+        );
+    }
+
+    #[test]
+    fn test_rules_by_severity() {
+        let findings = vec![
+            finding("src/a.rs", RiskLevel::Low, Some("style")),
+            finding("docs/b.md", RiskLevel::Low, Some("bug")),
+            finding("docs/c.md", RiskLevel::Medium, Some("style")),
+        ];
+        let suppressions = Suppressions::parse(["docs/ low", "* medium style"]).unwrap();
+        let (kept, dropped) = suppressions.apply(findings);
+        assert_eq!(dropped, 2);
+        assert_eq!(kept[0].file, "src/a.rs");
+    }
+
+    #[test]
+    fn test_invalid_rule() {
+        assert_eq!(
+            Suppressions::parse(["src/", "/ style"]).unwrap_err(),
+            "line 2: empty pattern `/`"
+        );
+    }
+}
@@ -0,0 +1,729 @@
+//! `lumen theme edit`: pick each color of a theme against a preview of a sample diff, and
+//! save it as a theme file for the diff viewer's `diff.custom_theme`.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{
+    prelude::*,
+    widgets::{Block, BorderType, Borders, Paragraph},
+};
+
+use crate::command::diff::terminal::TerminalGuard;
+use crate::command::diff::theme::{self, theme_path, Theme, ThemeMode, BUILTIN_THEMES, SLOTS};
+use crate::error::LumenError;
+
+/// Width of the slot list
+const LIST_WIDTH: u16 = 46;
+/// Channel step for `H`/`L`
+const COARSE_STEP: i16 = 16;
+
+enum Action {
+    None,
+    Quit,
+}
+
+struct ThemeEditor {
+    name: String,
+    path: PathBuf,
+    theme: Theme,
+    selected: usize,
+    /// Red, green or blue, for the arrow keys
+    channel: usize,
+    /// A color being typed after `#`
+    input: Option<String>,
+    dirty: bool,
+    saved: bool,
+    /// Quitting with unsaved changes asks to be confirmed by pressing q again
+    quit_warned: bool,
+    /// Message and whether it's an error
+    message: Option<(String, bool)>,
+}
+
+/// Red, green and blue of a color, approximating named and indexed colors with the usual
+/// xterm palette.
+fn rgb(color: Color) -> (u8, u8, u8) {
+    const NAMED: [(u8, u8, u8); 16] = [
+        (0, 0, 0),
+        (128, 0, 0),
+        (0, 128, 0),
+        (128, 128, 0),
+        (0, 0, 128),
+        (128, 0, 128),
+        (0, 128, 128),
+        (192, 192, 192),
+        (128, 128, 128),
+        (255, 0, 0),
+        (0, 255, 0),
+        (255, 255, 0),
+        (0, 0, 255),
+        (255, 0, 255),
+        (0, 255, 255),
+        (255, 255, 255),
+    ];
+    let index = match color {
+        Color::Rgb(r, g, b) => return (r, g, b),
+        Color::Reset => return NAMED[8],
+        Color::Black => 0,
+        Color::Red => 1,
+        Color::Green => 2,
+        Color::Yellow => 3,
+        Color::Blue => 4,
+        Color::Magenta => 5,
+        Color::Cyan => 6,
+        Color::Gray => 7,
+        Color::DarkGray => 8,
+        Color::LightRed => 9,
+        Color::LightGreen => 10,
+        Color::LightYellow => 11,
+        Color::LightBlue => 12,
+        Color::LightMagenta => 13,
+        Color::LightCyan => 14,
+        Color::White => 15,
+        Color::Indexed(index) => index,
+    };
+    match index {
+        0..=15 => NAMED[index as usize],
+        16..=231 => {
+            let level = |n: u8| if n == 0 { 0 } else { 55 + n * 40 };
+            let n = index - 16;
+            (level(n / 36), level(n / 6 % 6), level(n % 6))
+        }
+        _ => {
+            let gray = 8 + (index - 232) * 10;
+            (gray, gray, gray)
+        }
+    }
+}
+
+/// `color` with one channel moved by `delta`, as an RGB color.
+fn adjusted(color: Color, channel: usize, delta: i16) -> Color {
+    let (r, g, b) = rgb(color);
+    let mut channels = [r, g, b];
+    channels[channel] = (channels[channel] as i16 + delta).clamp(0, 255) as u8;
+    Color::Rgb(channels[0], channels[1], channels[2])
+}
+
+impl ThemeEditor {
+    fn new(name: &str, path: PathBuf, theme: Theme) -> Self {
+        Self {
+            name: name.to_string(),
+            path,
+            theme,
+            selected: 0,
+            channel: 0,
+            input: None,
+            dirty: false,
+            saved: false,
+            quit_warned: false,
+            message: None,
+        }
+    }
+
+    fn color(&self) -> Color {
+        (SLOTS[self.selected].get)(&self.theme)
+    }
+
+    fn set_color(&mut self, color: Color) {
+        (SLOTS[self.selected].set)(&mut self.theme, color);
+        self.dirty = true;
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        if let Some(input) = self.input.as_mut() {
+            match key.code {
+                KeyCode::Esc => self.input = None,
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Enter => {
+                    let text = self.input.take().unwrap_or_default();
+                    match Color::from_str(text.trim()) {
+                        Ok(color) => self.set_color(color),
+                        Err(_) => {
+                            self.message = Some((format!("Not a color: {text}"), true));
+                        }
+                    }
+                }
+                _ => {}
+            }
+            return Action::None;
+        }
+
+        let quit_warned = std::mem::take(&mut self.quit_warned);
+        self.message = None;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.dirty && !quit_warned {
+                    self.quit_warned = true;
+                    self.message = Some((
+                        "Unsaved changes: press s to save, or q again to quit".to_string(),
+                        true,
+                    ));
+                } else {
+                    return Action::Quit;
+                }
+            }
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return Action::Quit;
+            }
+            KeyCode::Char('j') | KeyCode::Down => {
+                self.selected = (self.selected + 1).min(SLOTS.len() - 1);
+            }
+            KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Tab => self.channel = (self.channel + 1) % 3,
+            KeyCode::BackTab => self.channel = (self.channel + 2) % 3,
+            KeyCode::Char('l') | KeyCode::Right => {
+                self.set_color(adjusted(self.color(), self.channel, 1))
+            }
+            KeyCode::Char('h') | KeyCode::Left => {
+                self.set_color(adjusted(self.color(), self.channel, -1))
+            }
+            KeyCode::Char('L') => self.set_color(adjusted(self.color(), self.channel, COARSE_STEP)),
+            KeyCode::Char('H') => {
+                self.set_color(adjusted(self.color(), self.channel, -COARSE_STEP))
+            }
+            KeyCode::Char('#') | KeyCode::Enter => self.input = Some(String::new()),
+            KeyCode::Char('u') => {
+                let base = Theme::for_mode(self.theme.mode);
+                self.set_color((SLOTS[self.selected].get)(&base));
+            }
+            KeyCode::Char('m') => {
+                self.theme.mode = match self.theme.mode {
+                    ThemeMode::Dark => ThemeMode::Light,
+                    ThemeMode::Light => ThemeMode::Dark,
+                };
+                theme::set_mode(self.theme.mode);
+                self.dirty = true;
+            }
+            KeyCode::Char('s') => self.save(),
+            _ => {}
+        }
+        Action::None
+    }
+
+    fn save(&mut self) {
+        let json = serde_json::to_string_pretty(&self.theme.to_json()).unwrap_or_default();
+        let result = self
+            .path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|()| fs::write(&self.path, format!("{json}\n")));
+        match result {
+            Ok(()) => {
+                self.dirty = false;
+                self.saved = true;
+                self.message = Some((format!("Saved to {}", self.path.display()), false));
+            }
+            Err(e) => {
+                self.message = Some((format!("Saving failed: {e}"), true));
+            }
+        }
+    }
+
+    fn render(&self, frame: &mut Frame) {
+        let t = theme::get();
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(frame.area());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Length(LIST_WIDTH), Constraint::Min(1)])
+            .split(rows[0]);
+
+        self.render_slots(frame, columns[0]);
+        let block = Block::default()
+            .title(" Preview ")
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_unfocused));
+        let inner = block.inner(columns[1]);
+        frame.render_widget(block, columns[1]);
+        render_preview(frame, inner, &self.theme);
+
+        let status = match (&self.input, &self.message) {
+            (Some(input), _) => Line::from(vec![
+                Span::styled(" color: ", Style::default().fg(t.ui.text_muted)),
+                Span::styled(
+                    format!("{input}_"),
+                    Style::default().fg(t.ui.text_primary),
+                ),
+                Span::styled(
+                    "  #rrggbb or a name like cyan · enter apply · esc cancel",
+                    Style::default().fg(t.ui.text_muted),
+                ),
+            ]),
+            (None, Some((message, error))) => {
+                let color = if *error {
+                    t.ui.stats_removed
+                } else {
+                    t.ui.stats_added
+                };
+                Line::from(Span::styled(
+                    format!(" {message}"),
+                    Style::default().fg(color),
+                ))
+            }
+            (None, None) => Line::from(Span::styled(
+                " j/k color · h/l adjust (H/L ×16) · tab channel · # type · u reset · m base · s save · q quit",
+                Style::default().fg(t.ui.text_muted),
+            )),
+        };
+        frame.render_widget(Paragraph::new(status), rows[1]);
+    }
+
+    fn render_slots(&self, frame: &mut Frame, area: Rect) {
+        let t = theme::get();
+        let mode = match self.theme.mode {
+            ThemeMode::Dark => "dark",
+            ThemeMode::Light => "light",
+        };
+        let block = Block::default()
+            .title(format!(" {} (for {mode}) ", self.name))
+            .title_style(Style::default().fg(t.ui.border_focused).bold())
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .border_style(Style::default().fg(t.ui.border_focused));
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(4)])
+            .split(inner);
+
+        let mut lines = Vec::new();
+        let mut selected_line = 0;
+        let mut section = "";
+        for (index, slot) in SLOTS.iter().enumerate() {
+            if slot.section != section {
+                section = slot.section;
+                lines.push(Line::from(Span::styled(
+                    section,
+                    Style::default().fg(t.ui.text_muted).bold(),
+                )));
+            }
+            let color = (slot.get)(&self.theme);
+            let style = if index == self.selected {
+                selected_line = lines.len();
+                Style::default().fg(t.ui.selection_fg).bg(t.ui.selection_bg)
+            } else {
+                Style::default().fg(t.ui.text_primary)
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!(" {:<24}", slot.name()), style),
+                Span::styled("    ", Style::default().bg(color)),
+                Span::styled(
+                    format!(" {color}"),
+                    Style::default().fg(t.ui.text_secondary),
+                ),
+            ]));
+        }
+        // Keep the selected slot in view
+        let height = parts[0].height as usize;
+        let scroll = selected_line.saturating_sub(height.saturating_sub(2));
+        frame.render_widget(Paragraph::new(lines).scroll((scroll as u16, 0)), parts[0]);
+
+        let (r, g, b) = rgb(self.color());
+        let mut picker = vec![Line::default()];
+        for (index, (label, value)) in [("R", r), ("G", g), ("B", b)].into_iter().enumerate() {
+            let filled = value as usize * 24 / 255;
+            let marker = if index == self.channel { "▸" } else { " " };
+            picker.push(Line::from(vec![
+                Span::styled(
+                    format!("{marker}{label} "),
+                    Style::default().fg(t.ui.text_primary),
+                ),
+                Span::styled("█".repeat(filled), Style::default().fg(t.ui.highlight)),
+                Span::styled(
+                    "░".repeat(24 - filled),
+                    Style::default().fg(t.ui.text_muted),
+                ),
+                Span::styled(
+                    format!(" {value:>3}"),
+                    Style::default().fg(t.ui.text_secondary),
+                ),
+            ]));
+        }
+        frame.render_widget(Paragraph::new(picker), parts[1]);
+    }
+}
+
+/// Kind of a row of the sample diff
+#[derive(Clone, Copy, PartialEq)]
+enum Row {
+    Context,
+    Removed,
+    Added,
+    Changed,
+}
+
+/// The sample diff's old and new lines as colored tokens, and its rows as indexes into them.
+#[allow(clippy::type_complexity)]
+fn sample(
+    theme: &Theme,
+) -> (
+    Vec<Vec<(Color, &'static str)>>,
+    Vec<(Row, Option<usize>, Option<usize>)>,
+) {
+    let s = &theme.syntax;
+    let lines = vec![
+        vec![(s.comment, "// Retry failed requests")],
+        vec![(s.attribute, "#[derive(Debug)]")],
+        vec![
+            (s.keyword, "pub struct "),
+            (s.r#type, "Client"),
+            (s.punctuation, " {"),
+        ],
+        vec![
+            (s.variable_member, "    retries"),
+            (s.punctuation, ": "),
+            (s.r#type, "u32"),
+            (s.punctuation, ","),
+        ],
+        vec![
+            (s.variable_member, "    retries"),
+            (s.punctuation, ": "),
+            (s.r#type, "usize"),
+            (s.punctuation, ","),
+        ],
+        vec![
+            (s.variable_member, "    name"),
+            (s.punctuation, ": &"),
+            (s.label, "'static "),
+            (s.r#type, "str"),
+            (s.punctuation, ","),
+        ],
+        vec![(s.punctuation, "}")],
+        vec![
+            (s.keyword, "fn "),
+            (s.function, "connect"),
+            (s.punctuation, "(&"),
+            (s.variable_builtin, "self"),
+            (s.punctuation, ") {"),
+        ],
+        vec![
+            (s.function_macro, "    println!"),
+            (s.punctuation, "("),
+            (s.string, "\"try {}\""),
+            (s.punctuation, ", "),
+            (s.variable_builtin, "self"),
+            (s.punctuation, "."),
+            (s.variable_member, "retries"),
+            (s.punctuation, ");"),
+        ],
+        vec![
+            (s.keyword, "    let "),
+            (s.default_text, "limit "),
+            (s.operator, "= "),
+            (s.number, "3 "),
+            (s.operator, "* "),
+            (s.default_text, "BASE"),
+            (s.punctuation, ";"),
+        ],
+        vec![
+            (s.module, "    net"),
+            (s.punctuation, "::"),
+            (s.function, "open"),
+            (s.punctuation, "("),
+            (s.default_text, "limit"),
+            (s.punctuation, ")"),
+        ],
+        vec![(s.tag, "    <retry/>")],
+    ];
+    let rows = vec![
+        (Row::Context, Some(0), Some(0)),
+        (Row::Context, Some(1), Some(1)),
+        (Row::Context, Some(2), Some(2)),
+        (Row::Changed, Some(3), Some(4)),
+        (Row::Added, None, Some(5)),
+        (Row::Context, Some(6), Some(6)),
+        (Row::Context, Some(7), Some(7)),
+        (Row::Context, Some(8), Some(8)),
+        (Row::Removed, Some(11), None),
+        (Row::Added, None, Some(9)),
+        (Row::Context, Some(10), Some(10)),
+    ];
+    (lines, rows)
+}
+
+/// A miniature diff viewer in `theme`'s colors: sidebar, both panes and footer.
+fn render_preview(frame: &mut Frame, area: Rect, theme: &Theme) {
+    let ui = &theme.ui;
+    let diff = &theme.diff;
+    let rows = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
+        .split(area);
+    let columns = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Length(24), Constraint::Min(1)])
+        .split(rows[0]);
+
+    let sidebar = Block::default()
+        .title(" Files ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(ui.border_unfocused))
+        .title_style(Style::default().fg(ui.text_muted));
+    let files = vec![
+        Line::from(Span::styled(
+            "▾ src",
+            Style::default().fg(ui.text_secondary),
+        )),
+        Line::from(vec![
+            Span::styled(
+                "  M ",
+                Style::default().fg(ui.selection_fg).bg(ui.selection_bg),
+            ),
+            Span::styled(
+                "client.rs      ",
+                Style::default().fg(ui.selection_fg).bg(ui.selection_bg),
+            ),
+            Span::styled(" ●", Style::default().fg(ui.risk_high)),
+        ]),
+        Line::from(vec![
+            Span::styled("  A ", Style::default().fg(ui.status_added)),
+            Span::styled("retry.rs ", Style::default().fg(ui.text_primary)),
+            Span::styled("✓", Style::default().fg(ui.viewed)),
+            Span::styled("      ●", Style::default().fg(ui.risk_medium)),
+        ]),
+        Line::from(vec![
+            Span::styled("  D ", Style::default().fg(ui.status_deleted)),
+            Span::styled("legacy.rs      ", Style::default().fg(ui.text_primary)),
+            Span::styled(" ●", Style::default().fg(ui.risk_low)),
+        ]),
+        Line::from(vec![
+            Span::styled("M ", Style::default().fg(ui.status_modified)),
+            Span::styled("README.md", Style::default().fg(ui.text_primary)),
+        ]),
+        Line::default(),
+        Line::from(Span::styled("4 files", Style::default().fg(ui.text_muted))),
+    ];
+    frame.render_widget(Paragraph::new(files).block(sidebar), columns[0]);
+
+    let pane = Block::default()
+        .title(" src/client.rs ")
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(Style::default().fg(ui.border_focused))
+        .title_style(Style::default().fg(ui.border_focused).bold());
+    let inner = pane.inner(columns[1]);
+    frame.render_widget(pane, columns[1]);
+    let half = inner.width / 2;
+
+    let (lines, sample_rows) = sample(theme);
+    let side = |line: Option<usize>, kind: Row, old: bool| -> Vec<Span<'static>> {
+        let Some(index) = line else {
+            return vec![Span::styled(
+                "╱".repeat(half as usize),
+                Style::default().fg(diff.empty_placeholder_fg),
+            )];
+        };
+        let changed = match kind {
+            Row::Context => false,
+            Row::Changed => true,
+            Row::Removed => old,
+            Row::Added => !old,
+        };
+        let (gutter, bg) = match (changed, old) {
+            (false, _) => (Style::default().fg(ui.line_number), None),
+            (true, true) => (
+                Style::default()
+                    .fg(diff.deleted_gutter_fg)
+                    .bg(diff.deleted_gutter_bg),
+                Some(diff.deleted_bg),
+            ),
+            (true, false) => (
+                Style::default()
+                    .fg(diff.added_gutter_fg)
+                    .bg(diff.added_gutter_bg),
+                Some(diff.added_bg),
+            ),
+        };
+        let with_bg = |style: Style| match bg {
+            Some(bg) => style.bg(bg),
+            None => style,
+        };
+        let mut spans = vec![Span::styled(format!("{:>3} ", index + 1), gutter)];
+        let mut width = 4;
+        for &(color, text) in &lines[index] {
+            width += text.chars().count();
+            spans.push(Span::styled(text, with_bg(Style::default().fg(color))));
+        }
+        spans.push(Span::styled(
+            " ".repeat((half as usize).saturating_sub(width)),
+            with_bg(Style::default()),
+        ));
+        spans
+    };
+
+    let mut diff_lines = vec![Line::from(Span::styled(
+        format!(
+            "{:<width$}",
+            "  impl Client {",
+            width = inner.width as usize
+        ),
+        Style::default().fg(ui.text_muted).bg(diff.context_bg),
+    ))];
+    for &(kind, old, new) in &sample_rows {
+        let mut spans = side(old, kind, true);
+        spans.extend(side(new, kind, false));
+        diff_lines.push(Line::from(spans));
+    }
+    diff_lines.push(Line::from(Span::styled(
+        "    ▲ high: callers still pass a u32",
+        Style::default().fg(ui.risk_high),
+    )));
+    diff_lines.push(Line::default());
+    diff_lines.push(Line::from(vec![
+        Span::styled("  search: ", Style::default().fg(ui.text_muted)),
+        Span::styled(
+            "retries",
+            Style::default()
+                .fg(ui.search_current_fg)
+                .bg(ui.search_current_bg),
+        ),
+        Span::styled(" ", Style::default()),
+        Span::styled(
+            "retries",
+            Style::default()
+                .fg(ui.search_match_fg)
+                .bg(ui.search_match_bg),
+        ),
+    ]));
+    frame.render_widget(Paragraph::new(diff_lines), inner);
+
+    let bg = ui.footer_bg;
+    let footer = Line::from(vec![
+        Span::styled(" ", Style::default().bg(bg)),
+        Span::styled(
+            " main ",
+            Style::default()
+                .fg(ui.footer_branch_fg)
+                .bg(ui.footer_branch_bg),
+        ),
+        Span::styled(
+            " src/client.rs",
+            Style::default().fg(ui.text_secondary).bg(bg),
+        ),
+        Span::styled(" watching", Style::default().fg(ui.watching).bg(bg)),
+        Span::styled("   +2", Style::default().fg(ui.stats_added).bg(bg)),
+        Span::styled(" -1", Style::default().fg(ui.stats_removed).bg(bg)),
+        Span::styled(" (2 hunks)", Style::default().fg(ui.text_muted).bg(bg)),
+        Span::styled(
+            "   File marked viewed",
+            Style::default().fg(ui.highlight).bg(bg),
+        ),
+        Span::styled(" ".repeat(rows[1].width as usize), Style::default().bg(bg)),
+    ]);
+    frame.render_widget(Paragraph::new(footer), rows[1]);
+}
+
+/// Edit the theme `name` (a name in the themes directory or a path), starting from the saved
+/// theme if there is one, or from the built-in theme `from`.
+pub fn run(name: &str, from: Option<&str>) -> Result<(), LumenError> {
+    let path = theme_path(name).ok_or_else(|| {
+        LumenError::ConfigurationError("no home directory to keep themes in".to_string())
+    })?;
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        return Err(LumenError::InvalidConfiguration(format!(
+            "{} is a TOML theme, which the editor can't save; edit it by hand or pick a .json name",
+            path.display()
+        )));
+    }
+    let theme = match from {
+        Some(base) => Theme::builtin(base).ok_or_else(|| {
+            LumenError::InvalidConfiguration(format!(
+                "unknown built-in theme `{base}`, expected one of: {}",
+                BUILTIN_THEMES.join(", ")
+            ))
+        })?,
+        None if path.exists() => Theme::load(name).map_err(LumenError::InvalidConfiguration)?,
+        None => Theme::builtin(name).unwrap_or_else(|| Theme::for_mode(ThemeMode::detect())),
+    };
+    theme::set_mode(theme.mode);
+
+    let mut editor = ThemeEditor::new(name, path, theme);
+    {
+        let _guard = TerminalGuard::enter()?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        loop {
+            terminal.draw(|frame| editor.render(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Action::Quit = editor.handle_key(key) {
+                break;
+            }
+        }
+    }
+
+    if editor.saved {
+        println!(
+            "Saved {}. Use it in the diff viewer with \"diff\": {{\"custom_theme\": \"{}\"}} in your config.",
+            editor.path.display(),
+            name
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    #[test]
+    fn test_rgb_of_named_and_indexed_colors() {
+        assert_eq!(rgb(Color::Cyan), (0, 128, 128));
+        assert_eq!(rgb(Color::Indexed(196)), (255, 0, 0));
+        assert_eq!(rgb(Color::Indexed(244)), (128, 128, 128));
+        assert_eq!(
+            adjusted(Color::Rgb(250, 0, 0), 0, 16),
+            Color::Rgb(255, 0, 0)
+        );
+        assert_eq!(adjusted(Color::Black, 2, -1), Color::Rgb(0, 0, 0));
+    }
+
+    #[test]
+    fn test_keys_adjust_and_reset_the_selected_color() {
+        let mut editor = ThemeEditor::new("test", PathBuf::from("test.json"), Theme::dark());
+        editor.handle_key(key(KeyCode::Tab));
+        editor.handle_key(key(KeyCode::Char('L')));
+        // comment is (106, 115, 125); tab moved to green
+        assert_eq!(editor.theme.syntax.comment, Color::Rgb(106, 131, 125));
+
+        editor.handle_key(key(KeyCode::Char('#')));
+        for c in "#ff0000".chars() {
+            editor.handle_key(key(KeyCode::Char(c)));
+        }
+        editor.handle_key(key(KeyCode::Enter));
+        assert_eq!(editor.theme.syntax.comment, Color::Rgb(255, 0, 0));
+
+        editor.handle_key(key(KeyCode::Char('u')));
+        assert_eq!(editor.theme.syntax.comment, Theme::dark().syntax.comment);
+
+        // Unsaved changes take a second q to quit
+        assert!(matches!(
+            editor.handle_key(key(KeyCode::Char('q'))),
+            Action::None
+        ));
+        assert!(matches!(
+            editor.handle_key(key(KeyCode::Char('q'))),
+            Action::Quit
+        ));
+    }
+}
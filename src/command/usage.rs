@@ -0,0 +1,168 @@
+use std::collections::BTreeMap;
+
+use crate::error::LumenError;
+use crate::provider::usage::{read_log, usage_log_path, UsageRecord};
+
+pub struct UsageCommand {
+    /// Only include requests from the last this many days
+    pub days: u32,
+}
+
+/// Totals for one group of requests
+#[derive(Debug, Default, PartialEq)]
+struct Totals {
+    requests: usize,
+    input_tokens: u64,
+    output_tokens: u64,
+    cost: f64,
+    /// Some requests used a model without a known price
+    unpriced: bool,
+}
+
+impl Totals {
+    fn add(&mut self, record: &UsageRecord) {
+        self.requests += 1;
+        self.input_tokens += record.input_tokens;
+        self.output_tokens += record.output_tokens;
+        match record.cost {
+            Some(cost) => self.cost += cost,
+            None => self.unpriced = true,
+        }
+    }
+}
+
+/// 1234 -> `1.2k`, 3_400_000 -> `3.4M`
+fn format_tokens(tokens: u64) -> String {
+    match tokens {
+        0..=999 => tokens.to_string(),
+        1_000..=999_999 => format!("{:.1}k", tokens as f64 / 1_000.0),
+        _ => format!("{:.1}M", tokens as f64 / 1_000_000.0),
+    }
+}
+
+fn format_row(label: &str, totals: &Totals) -> String {
+    // A `+` marks a lower bound, since some requests couldn't be priced
+    let cost = format!(
+        "${:.4}{}",
+        totals.cost,
+        if totals.unpriced { "+" } else { "" }
+    );
+    format!(
+        "  {label:<24} {:>6} req {:>8} in {:>8} out {cost:>11}",
+        totals.requests,
+        format_tokens(totals.input_tokens),
+        format_tokens(totals.output_tokens),
+    )
+}
+
+/// Summarize records by day, command and provider, followed by the overall total.
+fn render_summary(records: &[UsageRecord]) -> String {
+    let mut by_day: BTreeMap<&str, Totals> = BTreeMap::new();
+    let mut by_command: BTreeMap<&str, Totals> = BTreeMap::new();
+    let mut by_provider: BTreeMap<String, Totals> = BTreeMap::new();
+    let mut total = Totals::default();
+    for record in records {
+        by_day.entry(record.day()).or_default().add(record);
+        by_command.entry(&record.command).or_default().add(record);
+        by_provider
+            .entry(format!("{} ({})", record.provider, record.model))
+            .or_default()
+            .add(record);
+        total.add(record);
+    }
+
+    let mut out = String::from("By day\n");
+    // Most recent first
+    for (day, totals) in by_day.iter().rev() {
+        out.push_str(&format_row(day, totals));
+        out.push('\n');
+    }
+    out.push_str("\nBy command\n");
+    for (command, totals) in &by_command {
+        out.push_str(&format_row(command, totals));
+        out.push('\n');
+    }
+    out.push_str("\nBy provider\n");
+    for (provider, totals) in &by_provider {
+        out.push_str(&format_row(provider, totals));
+        out.push('\n');
+    }
+    out.push('\n');
+    out.push_str(&format_row("Total", &total));
+    out.push('\n');
+    if total.unpriced {
+        out.push_str("\n+ includes models without a known price, whose cost isn't counted\n");
+    }
+    out
+}
+
+impl UsageCommand {
+    pub fn execute(&self) -> Result<(), LumenError> {
+        let since = (chrono::Local::now() - chrono::Duration::days(i64::from(self.days)))
+            .format("%Y-%m-%d")
+            .to_string();
+        let records: Vec<UsageRecord> = read_log()?
+            .into_iter()
+            .filter(|record| record.day() > since.as_str())
+            .collect();
+
+        if records.is_empty() {
+            let location = usage_log_path()
+                .map(|path| format!(" in {}", path.display()))
+                .unwrap_or_default();
+            println!(
+                "No AI requests recorded in the last {} day(s){location}.",
+                self.days
+            );
+            return Ok(());
+        }
+
+        println!(
+            "AI usage in the last {} day(s), estimated from list prices\n",
+            self.days
+        );
+        print!("{}", render_summary(&records));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: &str, command: &str, cost: Option<f64>) -> UsageRecord {
+        UsageRecord {
+            timestamp: timestamp.to_string(),
+            command: command.to_string(),
+            provider: "openai".to_string(),
+            model: "gpt-5-mini".to_string(),
+            input_tokens: 1_500,
+            output_tokens: 200,
+            cached_tokens: 0,
+            cost,
+        }
+    }
+
+    #[test]
+    fn test_format_tokens() {
+        assert_eq!(format_tokens(999), "999");
+        assert_eq!(format_tokens(1_240), "1.2k");
+        assert_eq!(format_tokens(3_400_000), "3.4M");
+    }
+
+    #[test]
+    fn test_summary_groups_and_totals() {
+        let records = [
+            record("2026-10-14T09:00:00+02:00", "draft", Some(0.01)),
+            record("2026-10-15T10:00:00+02:00", "draft", Some(0.02)),
+            record("2026-10-15T11:00:00+02:00", "explain", None),
+        ];
+        let summary = render_summary(&records);
+        let day_15 = summary.find("2026-10-15").unwrap();
+        let day_14 = summary.find("2026-10-14").unwrap();
+        assert!(day_15 < day_14, "most recent day comes first");
+        assert!(summary.contains("openai (gpt-5-mini)"));
+        assert!(summary.contains("$0.0300+"));
+        assert!(summary.contains("includes models without a known price"));
+    }
+}
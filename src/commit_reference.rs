@@ -1,3 +1,4 @@
+use std::fmt;
 use std::str::FromStr;
 use thiserror::Error;
 
@@ -45,6 +46,88 @@ impl FromStr for CommitReference {
     }
 }
 
+impl CommitReference {
+    /// The same reference with each revision in it replaced by what `f` makes of it.
+    pub fn map_revisions(self, f: impl Fn(&str) -> String) -> Self {
+        match self {
+            CommitReference::Single(sha) => CommitReference::Single(f(&sha)),
+            CommitReference::Range { from, to } => CommitReference::Range {
+                from: f(&from),
+                to: f(&to),
+            },
+            CommitReference::TripleDots { from, to } => CommitReference::TripleDots {
+                from: f(&from),
+                to: f(&to),
+            },
+        }
+    }
+}
+
+/// Written back in the form `FromStr` reads, so a reference can be stored and parsed again
+impl fmt::Display for CommitReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommitReference::Single(sha) => write!(f, "{sha}"),
+            CommitReference::Range { from, to } => write!(f, "{from}..{to}"),
+            CommitReference::TripleDots { from, to } => write!(f, "{from}...{to}"),
+        }
+    }
+}
+
+/// Split a revision into the name it starts with and what git applies to that name, like
+/// `main` and `~2` for `main~2`, so a mistyped name can be matched against the real ones.
+pub fn split_revision(revision: &str) -> (&str, &str) {
+    let end = [revision.find(['~', '^', ':']), revision.find("@{")]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(revision.len());
+    revision.split_at(end)
+}
+
+/// The `names` that `name` is probably a typo of, closest first, at most three.
+pub fn similar_names<'a>(name: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    if name.is_empty() {
+        return Vec::new();
+    }
+    let max_distance = if name.chars().count() <= 4 { 1 } else { 2 };
+    let mut close: Vec<(usize, &str)> = names
+        .into_iter()
+        .filter_map(|candidate| {
+            // `feature` is close to `origin/feature` too
+            let short = candidate.rsplit('/').next().unwrap_or(candidate);
+            let distance = edit_distance(name, candidate).min(edit_distance(name, short));
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+    close.into_iter().take(3).map(|(_, name)| name).collect()
+}
+
+/// Edits that turn `a` into `b`: inserting, removing or replacing a character, or swapping
+/// two neighbouring ones, the most common typo.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    // rows[i][j]: edits between the first i characters of `a` and the first j of `b`
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    rows[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replace = rows[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut best = replace.min(rows[i - 1][j] + 1).min(rows[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                best = best.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = best;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -99,7 +182,7 @@ mod tests {
     #[test]
     fn test_clap_integration() {
         // Test full range
-        let cli = TestCli::try_parse_from(&["test", "main..feature"]).unwrap();
+        let cli = TestCli::try_parse_from(["test", "main..feature"]).unwrap();
         assert!(matches!(
             cli.reference,
             CommitReference::Range { from, to }
@@ -107,7 +190,7 @@ mod tests {
         ));
 
         // Test from-only range
-        let cli = TestCli::try_parse_from(&["test", "develop.."]).unwrap();
+        let cli = TestCli::try_parse_from(["test", "develop.."]).unwrap();
         assert!(matches!(
             cli.reference,
             CommitReference::Range { from, to }
@@ -115,7 +198,7 @@ mod tests {
         ));
 
         // Test to-only range
-        let cli = TestCli::try_parse_from(&["test", "..feature"]).unwrap();
+        let cli = TestCli::try_parse_from(["test", "..feature"]).unwrap();
         assert!(matches!(
             cli.reference,
             CommitReference::Range { from, to }
@@ -123,6 +206,14 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_display_round_trip() {
+        for input in ["HEAD~2", "main..feature", "main...feature"] {
+            let reference = input.parse::<CommitReference>().unwrap();
+            assert_eq!(reference.to_string(), input);
+        }
+    }
+
     #[test]
     fn test_empty_reference() {
         assert!(matches!(
@@ -130,4 +221,23 @@ mod tests {
             Err(ReferenceParseError::Empty)
         ));
     }
+
+    #[test]
+    fn test_split_revision() {
+        assert_eq!(split_revision("main~2"), ("main", "~2"));
+        assert_eq!(split_revision("v1.0^{commit}"), ("v1.0", "^{commit}"));
+        assert_eq!(split_revision("HEAD@{yesterday}"), ("HEAD", "@{yesterday}"));
+        assert_eq!(split_revision("@{upstream}"), ("", "@{upstream}"));
+        assert_eq!(split_revision("feature/login"), ("feature/login", ""));
+    }
+
+    #[test]
+    fn test_similar_names() {
+        let refs = ["main", "master", "origin/feature", "v1.0.0", "develop"];
+        assert_eq!(similar_names("mian", refs), ["main"]);
+        assert_eq!(similar_names("featrue", refs), ["origin/feature"]);
+        assert_eq!(similar_names("v1.0.1", refs), ["v1.0.0"]);
+        assert!(similar_names("nothing-like-it", refs).is_empty());
+        assert!(similar_names("", refs).is_empty());
+    }
 }
@@ -1,6 +1,10 @@
-use clap::{Parser, Subcommand, ValueEnum};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::{ArgValueCandidates, Shell};
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::command::completions::profile_candidates;
+use crate::command::output::Format;
 use crate::commit_reference::CommitReference;
 
 #[derive(Parser)]
@@ -21,6 +25,47 @@ pub struct Cli {
     #[arg(short = 'm', long = "model")]
     pub model: Option<String>,
 
+    /// Base URL for the custom-openai provider eg: http://localhost:1234/v1
+    #[arg(long = "api-base-url")]
+    pub api_base_url: Option<String>,
+
+    /// Sampling temperature for every command, overriding the config (0-2)
+    #[arg(long = "temperature", global = true)]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling for every command, overriding the config (0-1)
+    #[arg(long, global = true)]
+    pub top_p: Option<f64>,
+
+    /// Longest answer the model may give, in tokens, overriding the config
+    #[arg(long, global = true)]
+    pub max_output_tokens: Option<u32>,
+
+    /// How hard reasoning models think first, overriding the config: minimal, low, medium,
+    /// high, or a token budget
+    #[arg(long, global = true, value_name = "EFFORT")]
+    pub reasoning_effort: Option<String>,
+
+    /// Language to answer in for every command, overriding the config, eg: Japanese
+    #[arg(long, global = true, value_name = "LANGUAGE")]
+    pub lang: Option<String>,
+
+    /// Named profile from the config file to use, eg: work
+    #[arg(long, global = true, env = "LUMEN_PROFILE", add = ArgValueCandidates::new(profile_candidates))]
+    pub profile: Option<String>,
+
+    /// Print a backtrace if lumen crashes
+    #[arg(long, global = true)]
+    pub backtrace: bool,
+
+    /// Log git commands and provider requests, with timing and token counts, to stderr
+    #[arg(short = 'v', long, global = true)]
+    pub verbose: bool,
+
+    /// Append the `--verbose` or `LUMEN_LOG` log to this file instead of stderr
+    #[arg(long, global = true, env = "LUMEN_LOG_FILE", value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
@@ -36,6 +81,8 @@ pub enum ProviderType {
     Gemini,
     Xai,
     Vercel,
+    CustomOpenai,
+    Bedrock,
 }
 
 impl FromStr for ProviderType {
@@ -52,6 +99,8 @@ impl FromStr for ProviderType {
             "gemini" => Ok(ProviderType::Gemini),
             "xai" => Ok(ProviderType::Xai),
             "vercel" => Ok(ProviderType::Vercel),
+            "custom-openai" => Ok(ProviderType::CustomOpenai),
+            "bedrock" => Ok(ProviderType::Bedrock),
             _ => Err(format!("Unknown provider: {}", s)),
         }
     }
@@ -65,6 +114,18 @@ pub enum Commands {
         #[arg(value_parser = clap::value_parser!(CommitReference))]
         reference: Option<CommitReference>,
 
+        /// More commits to explain along with REFERENCE: each is explained on its own, then
+        /// all of them together
+        #[arg(value_name = "MORE", requires = "reference")]
+        more: Vec<String>,
+
+        /// Explain the commits listed on stdin, one SHA per line, e.g. from `git rev-list`
+        #[arg(
+            long,
+            conflicts_with_all = ["reference", "staged", "list", "branch", "patch", "annotate", "impact"]
+        )]
+        from_stdin: bool,
+
         /// Use staged diff only (when showing uncommitted changes)
         #[arg(long)]
         staged: bool,
@@ -76,39 +137,584 @@ pub enum Commands {
         /// Select commit interactively using fuzzy finder
         #[arg(long)]
         list: bool,
+
+        /// Explain the current branch since it left BASE (defaults to the default branch of
+        /// the upstream's remote), then each of its commits in a line
+        #[arg(
+            long,
+            value_name = "BASE",
+            num_args = 0..=1,
+            default_missing_value = "",
+            conflicts_with_all = ["reference", "staged", "list"]
+        )]
+        branch: Option<String>,
+
+        /// Explain a unified diff read from FILE (`-` for stdin), e.g. from `gh pr diff`
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["reference", "staged", "list", "branch"]
+        )]
+        patch: Option<PathBuf>,
+
+        /// For ranges: follow only the first parent of merge commits
+        #[arg(long)]
+        first_parent: bool,
+
+        /// For ranges: leave merge commits out
+        #[arg(long)]
+        no_merges: bool,
+
+        /// Explain each hunk separately, printed inline with the diff
+        #[arg(long)]
+        annotate: bool,
+
+        /// Assess the risk instead: affected public APIs, callers likely to break,
+        /// configuration and migration steps, and tests to add, as a checklist
+        #[arg(long, conflicts_with = "annotate")]
+        impact: bool,
+
+        /// Always ask the provider instead of reusing a cached answer for this commit
+        #[arg(long)]
+        no_cache: bool,
+
+        /// How to write out the result
+        #[arg(long, alias = "output", value_enum, default_value = "markdown")]
+        format: Format,
+
+        /// Also copy the explanation to the clipboard, over SSH too
+        #[arg(long, conflicts_with_all = ["annotate", "format"])]
+        copy: bool,
+
+        /// Keep explaining the working tree in a panel, again each time files change
+        #[arg(
+            long,
+            conflicts_with_all = ["reference", "from_stdin", "list", "branch", "patch", "annotate", "impact", "copy", "format"]
+        )]
+        watch: bool,
+
+        /// Only explain the changes to these files or directories, given after `--`
+        #[arg(last = true, value_name = "PATH", conflicts_with = "patch")]
+        paths: Vec<String>,
     },
     /// List all commits in an interactive fuzzy-finder, and summarize the changes
     List,
+    /// Browse the commit history, opening commits in the diff viewer or explaining them
+    Log {
+        /// Branches, tags or ranges to show the history of [default: HEAD]
+        revisions: Vec<String>,
+
+        /// Show the history of every branch and tag
+        #[arg(long, conflicts_with = "revisions")]
+        all: bool,
+    },
     /// Generate a commit message for the staged changes
     Draft {
         /// Add context to communicate intent
         #[arg(short, long)]
         context: Option<String>,
+
+        /// Print only the subject line
+        #[arg(long)]
+        subject_only: bool,
+
+        /// Commit the staged changes with the message, after confirmation
+        #[arg(long)]
+        commit: bool,
+
+        /// With --commit: open the message in your editor before committing
+        #[arg(long, requires = "commit")]
+        edit: bool,
+
+        /// Generate this many alternatives and pick one interactively
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=5))]
+        count: u8,
+
+        /// How to write out the result; anything but `markdown` includes every generated message
+        #[arg(
+            long,
+            alias = "output",
+            value_enum,
+            default_value = "markdown",
+            conflicts_with = "commit"
+        )]
+        format: Format,
+
+        /// Draft one message covering every commit since BASE, for squash-merging the branch
+        #[arg(long, value_name = "BASE", conflicts_with = "commit")]
+        squash: Option<String>,
+
+        /// Write the plain message to the top of FILE, for a `prepare-commit-msg` hook
+        #[arg(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = ["commit", "count", "format"]
+        )]
+        hook: Option<PathBuf>,
+
+        /// Update HEAD's message for the staged changes; with --commit, amend HEAD with it
+        #[arg(long, conflicts_with_all = ["squash", "hook"])]
+        amend: bool,
+
+        /// Also copy the message to the clipboard, over SSH too
+        #[arg(long, conflicts_with_all = ["commit", "hook", "format"])]
+        copy: bool,
+    },
+    /// Split the unstaged changes into logical commits, each with a drafted message
+    Split {
+        /// Add context to communicate intent
+        #[arg(short, long)]
+        context: Option<String>,
+
+        /// Only show how the changes would be split
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Draft better messages for a range of commits and rewrite them, after approval
+    Reword {
+        /// Commits to reword, ending at the current branch: main..HEAD, HEAD~3..
+        #[arg(value_parser = clap::value_parser!(CommitReference))]
+        range: CommitReference,
+
+        /// Add context to communicate intent
+        #[arg(short, long)]
+        context: Option<String>,
+    },
+    /// Write a pull request title and description for the current branch, and optionally open it
+    Pr {
+        /// Branch the PR merges into (defaults to the default branch of the upstream's remote)
+        #[arg(long)]
+        base: Option<String>,
+
+        /// Add context to communicate intent
+        #[arg(short, long)]
+        context: Option<String>,
+
+        /// Open the PR with `gh pr create` using the title and description, after confirmation
+        #[arg(long)]
+        create: bool,
+
+        /// How to write out the result
+        #[arg(
+            long,
+            value_enum,
+            default_value = "markdown",
+            conflicts_with = "create"
+        )]
+        format: Format,
     },
 
     Operate {
         #[arg()]
         query: String,
+
+        /// Only show the command, what each step of it does and its risks, without running it
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Run commands the safety policy blocks, like `git reset --hard` or `git push --force`
+        #[arg(long)]
+        allow_destructive: bool,
     },
     /// Launch interactive side-by-side diff viewer
-    Diff {
+    Diff(Box<DiffArgs>),
+    /// Compare files for `git difftool`, or resolve a conflicted file for `git mergetool`
+    Difftool {
+        /// The old version, or directory with `git difftool --dir-diff` ($LOCAL)
+        local: PathBuf,
+
+        /// The new version, or directory ($REMOTE)
+        remote: PathBuf,
+
+        /// The common ancestor, when resolving a merge ($BASE)
+        #[arg(requires = "merged")]
+        base: Option<PathBuf>,
+
+        /// The path being compared; with BASE, the conflicted file to resolve ($MERGED)
+        #[arg(long, value_name = "PATH")]
+        merged: Option<PathBuf>,
+    },
+    /// Review a commit, range or the current diff: findings with severity, location and fixes
+    Review {
         /// Commit reference: SHA, HEAD, HEAD~3..HEAD, main..feature, main...feature
-        /// Can also be a PR number or URL (e.g., 123 or https://github.com/owner/repo/pull/123)
         #[arg(value_parser = clap::value_parser!(CommitReference))]
         reference: Option<CommitReference>,
 
-        /// View a GitHub pull request (number or URL)
+        /// Use staged diff only (when reviewing uncommitted changes)
+        #[arg(long, conflicts_with = "tui")]
+        staged: bool,
+
+        /// Show the findings under their lines in the diff viewer
+        #[arg(long, conflicts_with = "format")]
+        tui: bool,
+
+        /// How to write out the findings
+        #[arg(long, alias = "output", value_enum, default_value = "markdown")]
+        format: Format,
+
+        /// For hooks: print one `verdict=...` line and exit non-zero when the review fails
+        #[arg(long, conflicts_with_all = ["tui", "format"])]
+        quiet: bool,
+
+        /// With --quiet: how many findings at --severity or above still pass
+        #[arg(long, value_name = "N", default_value_t = 0, requires = "quiet")]
+        max_findings: usize,
+
+        /// With --quiet: the least severe finding that counts against --max-findings
+        #[arg(long, value_enum, default_value_t = Severity::High, requires = "quiet")]
+        severity: Severity,
+
+        /// With --quiet: give up after SECONDS, exiting with code 5
+        #[arg(long, value_name = "SECONDS", default_value_t = 120, requires = "quiet", value_parser = clap::value_parser!(u64).range(1..))]
+        timeout: u64,
+    },
+    /// Check the staged changes for likely mistakes before committing, e.g. from a pre-commit hook
+    Check {
+        /// Also ask the provider to review the changes
+        #[arg(long)]
+        ai: bool,
+
+        /// The least severe finding that fails the check with a non-zero exit code
+        #[arg(long, value_enum, default_value_t = Severity::Medium)]
+        severity: Severity,
+    },
+    /// Check commit messages against the configured conventions, e.g. from a commit-msg hook
+    LintMsg {
+        /// The message to check; read from stdin without it, --file or --range
+        #[arg(conflicts_with_all = ["file", "range"])]
+        message: Option<String>,
+
+        /// Read the message from FILE, like the one git passes a commit-msg hook
+        #[arg(long, value_name = "FILE", conflicts_with = "range")]
+        file: Option<PathBuf>,
+
+        /// Check the message of every commit in RANGE, e.g. main..HEAD
+        #[arg(long, value_name = "RANGE")]
+        range: Option<String>,
+
+        /// Also ask the provider to review the style of each message
         #[arg(long)]
-        pr: Option<String>,
+        ai: bool,
 
-        /// Filter to specific files
+        /// Fail on warnings too, like a subject over 50 characters
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Edit settings (provider, model, AI, draft and diff view defaults) in a full-screen editor
+    Configure {
+        /// Set up provider, API key and model with step-by-step prompts instead
+        #[arg(long)]
+        wizard: bool,
+    },
+    /// Check git, config, provider connectivity, optional tools and terminal support
+    Doctor,
+    /// Preview commits, flag likely conflicts with AI, and cherry-pick them one by one
+    CherryPick {
+        /// Commits to cherry-pick, applied in the given order
+        #[arg(required = true)]
+        shas: Vec<String>,
+    },
+    /// Run a server for editor plugins (explain hunks, draft messages, review diffs)
+    Serve {
+        /// Speak newline-delimited JSON-RPC 2.0 over stdin/stdout
+        #[arg(long, alias = "stdio", required = true)]
+        json_rpc: bool,
+    },
+    /// Save working tree checkpoints to review changes against with `diff --snapshot`
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Draft commit messages from a `prepare-commit-msg` git hook
+    Hook {
+        #[command(subcommand)]
+        action: HookAction,
+    },
+    /// Design color themes for the diff viewer
+    Theme {
+        #[command(subcommand)]
+        action: ThemeAction,
+    },
+    /// Manage cached AI responses
+    Cache {
+        #[command(subcommand)]
+        action: CacheAction,
+    },
+    /// Print a shell completion script, eg: `source <(lumen completions bash)`
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Print the man page
+    Man {
+        /// Write a page for every command into this directory instead, eg: lumen-explain.1
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+    /// Summarize AI token usage and estimated cost by day, command and provider
+    Usage {
+        /// How many days back to include
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Preview code suggestions from AI output and apply the accepted ones to the working tree
+    Apply {
+        /// File with ```diff or SEARCH/REPLACE blocks (reads stdin when omitted or `-`)
+        input: Option<PathBuf>,
+
+        /// Apply every suggestion that applies cleanly without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Skip the diff viewer preview
+        #[arg(long)]
+        no_preview: bool,
+    },
+    /// Summarize recent activity for standups: commits by author and area, merges, AI highlights
+    Digest {
+        /// Branch to summarize (defaults to the current branch)
+        branch: Option<String>,
+
+        /// Include commits since this date (anything `git log --since` accepts)
+        #[arg(long, default_value = "1 day ago")]
+        since: String,
+
+        /// Only include commits by this author
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Summarize all branches instead of one
+        #[arg(long, conflicts_with = "branch")]
+        all: bool,
+
+        /// How to write out the digest
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: Format,
+
+        /// Post the digest to the webhook configured in `webhook.url`, like `--format webhook`
+        #[arg(long, conflicts_with = "format")]
+        post: bool,
+    },
+    /// Write a changelog section for the commits in a range, eg: v1.2.0..v1.3.0
+    Changelog {
+        /// Commits to cover, as FROM..TO; a single ref means from it to HEAD
+        range: String,
+
+        /// Version the section is for (defaults to TO, or `Unreleased` when it's HEAD)
+        #[arg(long, value_name = "VERSION")]
+        release: Option<String>,
+
+        /// Follow only the first parent of merge commits, so each merged branch is one entry
+        #[arg(long)]
+        first_parent: bool,
+
+        /// Leave merge commits out
+        #[arg(long)]
+        no_merges: bool,
+
+        /// Markdown file with the section layout to follow instead of Keep a Changelog;
+        /// `{version}` and `{date}` in it are filled in
+        #[arg(long)]
+        template: Option<PathBuf>,
+
+        /// How to write out the section
+        #[arg(long, alias = "output", value_enum, default_value = "markdown")]
+        format: Format,
+
+        /// Add the section to the top of a changelog file instead of printing it
+        #[arg(
+            long,
+            value_name = "FILE",
+            num_args = 0..=1,
+            default_missing_value = "CHANGELOG.md",
+            conflicts_with = "format"
+        )]
+        prepend: Option<PathBuf>,
+    },
+}
+
+/// Arguments of `lumen diff`, boxed in [`Commands`] as there are so many of them
+#[derive(Args)]
+pub struct DiffArgs {
+    /// Commit reference: SHA, HEAD, HEAD~3..HEAD, main..feature, main...feature
+    /// Can also be a PR number or URL (e.g., 123 or https://github.com/owner/repo/pull/123)
+    #[arg(value_parser = clap::value_parser!(CommitReference))]
+    pub reference: Option<CommitReference>,
+
+    /// View a GitHub pull request (number or URL)
+    #[arg(long)]
+    pub pr: Option<String>,
+
+    /// Reopen the last ref range or PR viewed in this repository
+    #[arg(long, conflicts_with_all = ["reference", "pr", "snapshot", "symbol"])]
+    pub last: bool,
+
+    /// Only show files matching these globs (`*.rs`, `src/`, `!**/generated/**`) or
+    /// statuses (`:added`, `:modified`, `:deleted`, `:renamed`, `:copied`)
+    #[arg(short, long, value_name = "PATTERN")]
+    pub file: Option<Vec<String>>,
+
+    /// Watch for file changes and auto-reload
+    #[arg(short, long)]
+    pub watch: bool,
+
+    /// With --watch: only watch these files or directories instead of the whole repository
+    #[arg(long, value_name = "PATH", requires = "watch")]
+    pub watch_path: Vec<PathBuf>,
+
+    /// Review flow: marking a file viewed advances to the next unviewed file
+    #[arg(long)]
+    pub review_flow: bool,
+
+    /// Disable staging, editing and PR updates (for demos and shared sessions)
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// Record key and mouse events with file and scroll positions to a file
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Play back a session saved with --record (read-only; press any key to take over)
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Mark each file's review risk in the sidebar; `ai` refines the heuristic scores
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "heuristic")]
+    pub risk: Option<RiskMode>,
+
+    /// Compare the working tree against a snapshot saved with `lumen snapshot save`
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["reference", "pr"])]
+    pub snapshot: Option<String>,
+
+    /// Show lines that differ only in whitespace as unchanged (toggle with `w`)
+    #[arg(long)]
+    pub ignore_whitespace: bool,
+
+    /// Color theme: a built-in one (dark, light, solarized-dark, gruvbox-dark,
+    /// catppuccin-mocha, ...), a theme in ~/.config/lumen/themes, or a path to a theme file
+    #[arg(long, value_name = "NAME")]
+    pub theme: Option<String>,
+
+    /// Show only how the function or type NAME changed, found with tree-sitter in both revisions
+    #[arg(long, value_name = "NAME", conflicts_with_all = ["pr", "snapshot", "file", "watch"])]
+    pub symbol: Option<String>,
+
+    /// With --watch: summarize what changed every MINUTES minutes with the configured provider
+    #[arg(long, value_name = "MINUTES", requires = "watch", value_parser = clap::value_parser!(u64).range(1..))]
+    pub summarize_every: Option<u64>,
+
+    /// Append those summaries to FILE instead of showing them in a popup
+    #[arg(long, value_name = "FILE", requires = "summarize_every")]
+    pub summary_log: Option<PathBuf>,
+
+    /// Show a one-line AI summary of each file in the sidebar, asked for as files come
+    /// into view and cached by their diffs (read them whole with the sidebar focused)
+    #[arg(long)]
+    pub file_summaries: bool,
+
+    /// Compare two files, or two directories recursively, instead of git revisions
+    #[arg(
+        long,
+        num_args = 2,
+        value_names = ["OLD", "NEW"],
+        conflicts_with_all = ["reference", "pr", "last", "snapshot", "symbol", "watch", "export_html", "export_patch"]
+    )]
+    pub files: Option<Vec<PathBuf>>,
+
+    /// Write a standalone HTML report of the diff to FILE instead of opening the viewer
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["export_patch", "watch", "replay", "symbol"])]
+    pub export_html: Option<PathBuf>,
+
+    /// Write the diff to FILE as a patch `git apply` takes, instead of opening the viewer
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["watch", "replay", "symbol"])]
+    pub export_patch: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum SnapshotAction {
+    /// Save the working tree (including untracked files) without touching the index
+    Save {
+        name: String,
+
+        /// Replace an existing snapshot with the same name
         #[arg(short, long)]
-        file: Option<Vec<String>>,
+        force: bool,
+    },
+    /// List saved snapshots, newest first
+    List,
+    /// Delete a snapshot
+    Delete { name: String },
+}
 
-        /// Watch for file changes and auto-reload
+#[derive(Subcommand)]
+pub enum HookAction {
+    /// Install the hook, so `git commit` opens the editor with a drafted message
+    Install {
+        /// Replace a `prepare-commit-msg` hook that lumen didn't install
         #[arg(short, long)]
-        watch: bool,
+        force: bool,
     },
-    /// Interactively configure Lumen (provider, API key)
-    Configure,
+    /// Remove the hook, if lumen installed it
+    Uninstall,
+}
+
+#[derive(Subcommand)]
+pub enum ThemeAction {
+    /// Edit a theme against a preview of a sample diff, creating it if it doesn't exist
+    Edit {
+        /// Theme name, saved under ~/.config/lumen/themes, or a path to a theme file
+        name: String,
+
+        /// Start over from a built-in theme (dark, light, solarized-dark, gruvbox-light, ...)
+        /// instead of the saved one
+        #[arg(long, value_name = "THEME")]
+        from: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheAction {
+    /// Delete all cached responses
+    Clear,
+}
+
+/// Severity of a review finding, as `lumen review --severity` takes it
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum Severity {
+    #[value(alias = "info")]
+    Low,
+    #[value(alias = "warning")]
+    Medium,
+    #[value(alias = "error")]
+    High,
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum RiskMode {
+    /// Score files by kind, size and control flow of the change
+    Heuristic,
+    /// Also ask the configured provider to rescore them
+    Ai,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_global_flags_after_the_command() {
+        let cli = Cli::try_parse_from([
+            "lumen",
+            "explain",
+            "--lang",
+            "Japanese",
+            "--reasoning-effort",
+            "high",
+            "HEAD",
+        ])
+        .unwrap();
+        assert_eq!(cli.lang.as_deref(), Some("Japanese"));
+        assert_eq!(cli.reasoning_effort.as_deref(), Some("high"));
+        assert!(matches!(cli.command, Commands::Explain { .. }));
+    }
 }
@@ -1,16 +1,25 @@
+use crate::command::diff::theme::ThemeMode;
+use crate::command::draft::SUBJECT_MAX_LEN;
 use crate::config::cli::ProviderType;
+use crate::config::schema::config_error;
+use crate::config::ALL_PROVIDERS;
 use crate::error::LumenError;
+use crate::privacy::SecretPolicy;
+use crate::webhook::WebhookConfig;
 use dirs::home_dir;
 use indoc::indoc;
-use serde::{Deserialize, Deserializer};
-use serde_json::from_reader;
+use lumen_core::prompt::{template_placeholders, PromptTemplate};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use toml_edit::DocumentMut;
 
 use crate::Cli;
 
 #[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct LumenConfig {
     #[serde(
         default = "default_ai_provider",
@@ -24,17 +33,414 @@ pub struct LumenConfig {
     #[serde(default = "default_api_key")]
     pub api_key: Option<String>,
 
+    #[serde(default = "default_api_base_url")]
+    pub api_base_url: Option<String>,
+
     #[serde(default = "default_draft_config")]
     pub draft: DraftConfig,
+
+    #[serde(default)]
+    pub ai: AiConfig,
+
+    /// Where `--post` sends generated reports
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
+
+    /// Shell commands that generated output is piped through, keyed by command name
+    /// (`explain`, `draft`); a failing hook rejects the output
+    #[serde(default)]
+    pub hooks: HashMap<String, String>,
+
+    #[serde(default)]
+    pub diff: DiffConfig,
+
+    #[serde(default)]
+    pub operate: OperateConfig,
+
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    #[serde(default)]
+    pub review: ReviewConfig,
+}
+
+/// Defaults for the diff viewer, which its settings panel can also save
+#[derive(Debug, Deserialize, Serialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct DiffConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tab_width: Option<usize>,
+
+    /// Tab width by file extension (or file name), overriding `tab_width`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tab_widths: HashMap<String, usize>,
+
+    /// Enclosing scope lines pinned above the diff; 0 turns them off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_lines: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ignore_whitespace: Option<bool>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme: Option<ThemeMode>,
+
+    /// A built-in theme by name, or a theme file by name or path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_theme: Option<String>,
+
+    /// Sidebar width in columns; sized to the terminal when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sidebar_width: Option<u16>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wrap: Option<bool>,
+
+    /// Faint guides at each indentation level of the diff panes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indent_guides: Option<bool>,
+
+    /// Old and new lines interleaved in one panel instead of side by side
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unified: Option<bool>,
+
+    /// Functions and other definitions that were moved or reindented get their own color
+    /// instead of showing as deleted and added
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detect_moves: Option<bool>,
+
+    /// Files with more lines than this wait for enter before they're diffed, and are shown
+    /// without syntax highlighting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub large_file_lines: Option<usize>,
+}
+
+/// Generation settings for AI requests, with optional per-command overrides
+/// keyed by command name (`explain`, `draft`, `operate`, `cherry-pick`, `digest`, `review`).
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AiConfig {
+    #[serde(default)]
+    pub temperature: Option<f64>,
+
+    /// Nucleus sampling: only the most likely tokens making up this probability mass (0-1)
+    #[serde(default)]
+    pub top_p: Option<f64>,
+
+    /// Longest answer the model may give, in tokens
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
+
+    /// How hard reasoning models think first: `minimal`, `low`, `medium`, `high`, or a token
+    /// budget for models that take one, like Claude
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+
+    /// Language to write explanations, reviews and commit messages in, e.g. `Japanese`
+    #[serde(default)]
+    pub language: Option<String>,
+
+    #[serde(default)]
+    pub per_command: HashMap<String, GenerationSettings>,
+
+    /// Token budget for a diff sent in one request; larger diffs are chunked
+    #[serde(default)]
+    pub max_tokens: Option<usize>,
+
+    /// How many times to retry a rate-limited or transient provider error
+    #[serde(default)]
+    pub max_retries: Option<u32>,
+
+    /// Custom prompts keyed by command name (`explain`, `draft`, `operate`)
+    #[serde(default)]
+    pub prompts: HashMap<String, PromptTemplate>,
+
+    /// Whether likely secrets in a prompt are redacted or fail the request
+    #[serde(default)]
+    pub secrets: SecretPolicy,
+
+    /// Send diffs in a shorter form: one header line per file and less unchanged context
+    #[serde(default)]
+    pub compact_diff: bool,
 }
 
-#[derive(Debug, Deserialize, Default)]
+/// Placeholders each customizable prompt can use
+const PROMPT_PLACEHOLDERS: &[(&str, &[&str])] = &[
+    ("explain", &["diff", "message", "query"]),
+    (
+        "draft",
+        &["diff", "context", "commit_types", "commits", "format"],
+    ),
+    ("operate", &["query", "format"]),
+];
+
+/// Fits comfortably in the context window of every supported provider's default model
+const DEFAULT_MAX_TOKENS: usize = 64_000;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+#[derive(Debug, Deserialize, Default, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct GenerationSettings {
+    #[serde(default)]
+    pub temperature: Option<f64>,
+
+    #[serde(default)]
+    pub top_p: Option<f64>,
+
+    #[serde(default)]
+    pub max_output_tokens: Option<u32>,
+
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+
+    #[serde(default)]
+    pub language: Option<String>,
+
+    /// Model to use instead of the configured one, like a cheaper one for a frequent command
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+/// Keywords `reasoning_effort` takes besides a token budget
+const REASONING_EFFORTS: &[&str] = &["minimal", "low", "medium", "high"];
+
+impl AiConfig {
+    /// Resolve the settings for a command; per-command values win over global ones.
+    pub fn for_command(&self, command: &str) -> GenerationSettings {
+        let specific = self.per_command.get(command);
+        GenerationSettings {
+            temperature: specific.and_then(|s| s.temperature).or(self.temperature),
+            top_p: specific.and_then(|s| s.top_p).or(self.top_p),
+            max_output_tokens: specific
+                .and_then(|s| s.max_output_tokens)
+                .or(self.max_output_tokens),
+            reasoning_effort: specific
+                .and_then(|s| s.reasoning_effort.clone())
+                .or_else(|| self.reasoning_effort.clone()),
+            language: specific
+                .and_then(|s| s.language.clone())
+                .or_else(|| self.language.clone())
+                .filter(|language| !language.trim().is_empty()),
+            model: specific
+                .and_then(|s| s.model.clone())
+                .filter(|model| !model.trim().is_empty()),
+        }
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS)
+    }
+
+    pub fn max_retries(&self) -> u32 {
+        self.max_retries.unwrap_or(DEFAULT_MAX_RETRIES)
+    }
+
+    pub fn prompt(&self, command: &str) -> Option<&PromptTemplate> {
+        self.prompts.get(command)
+    }
+
+    fn validate(&self) -> Result<(), LumenError> {
+        if self.max_tokens == Some(0) {
+            return Err(LumenError::InvalidConfiguration(
+                "max_tokens must be greater than 0".to_string(),
+            ));
+        }
+
+        let temperatures = std::iter::once(self.temperature)
+            .chain(self.per_command.values().map(|s| s.temperature))
+            .flatten();
+        for temperature in temperatures {
+            if !(0.0..=2.0).contains(&temperature) {
+                return Err(LumenError::InvalidConfiguration(format!(
+                    "temperature must be between 0 and 2, got {temperature}"
+                )));
+            }
+        }
+
+        let settings = std::iter::once(self.for_command(""))
+            .chain(self.per_command.values().cloned())
+            .collect::<Vec<_>>();
+        for top_p in settings.iter().filter_map(|s| s.top_p) {
+            if !(0.0..=1.0).contains(&top_p) {
+                return Err(LumenError::InvalidConfiguration(format!(
+                    "top_p must be between 0 and 1, got {top_p}"
+                )));
+            }
+        }
+        if settings.iter().any(|s| s.max_output_tokens == Some(0)) {
+            return Err(LumenError::InvalidConfiguration(
+                "max_output_tokens must be greater than 0".to_string(),
+            ));
+        }
+        for effort in settings
+            .iter()
+            .filter_map(|s| s.reasoning_effort.as_deref())
+        {
+            if !REASONING_EFFORTS.contains(&effort) && effort.parse::<u32>().is_err() {
+                return Err(LumenError::InvalidConfiguration(format!(
+                    "reasoning_effort must be one of {} or a token budget, got `{effort}`",
+                    REASONING_EFFORTS.join(", ")
+                )));
+            }
+        }
+
+        for (command, template) in &self.prompts {
+            let Some((_, allowed)) = PROMPT_PLACEHOLDERS.iter().find(|(name, _)| name == command)
+            else {
+                return Err(LumenError::InvalidConfiguration(format!(
+                    "prompts can only be customized for explain, draft and operate, not `{command}`"
+                )));
+            };
+            let templates = template.system.iter().chain(template.user.iter());
+            for placeholder in templates.flat_map(|t| template_placeholders(t)) {
+                if !allowed.contains(&placeholder) {
+                    return Err(LumenError::InvalidConfiguration(format!(
+                        "unknown placeholder {{{{{placeholder}}}}} in the {command} prompt, expected one of: {}",
+                        allowed.join(", ")
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
 pub struct DraftConfig {
     #[serde(
         default = "default_commit_types",
         deserialize_with = "deserialize_commit_types"
     )]
     pub commit_types: String,
+    /// Words drafted messages are checked for, ignoring case
+    #[serde(default)]
+    pub forbidden_words: Vec<String>,
+    /// Scopes a drafted message may use; any scope when empty
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Start each header with its type's gitmoji, like `✨ feat: Add login`
+    #[serde(default)]
+    pub gitmoji: bool,
+    /// Emoji by commit type, over the built-in gitmoji ones
+    #[serde(default)]
+    pub gitmojis: HashMap<String, String>,
+    /// Ticket reference taken from the branch name and added to every message
+    #[serde(default)]
+    pub ticket: Option<TicketConfig>,
+    /// Longest header a drafted message may have; 50 by default
+    #[serde(default)]
+    pub subject_max_length: Option<usize>,
+}
+
+impl DraftConfig {
+    pub fn subject_limit(&self) -> usize {
+        self.subject_max_length.unwrap_or(SUBJECT_MAX_LEN)
+    }
+}
+
+/// Where the ticket reference goes in a drafted message
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TicketPlacement {
+    /// Before the header: `PROJ-123: feat: Add login`
+    Prefix,
+    /// After the header: `feat: Add login (PROJ-123)`
+    Suffix,
+    /// A trailer at the end of the message: `Refs: PROJ-123`
+    #[default]
+    Trailer,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TicketConfig {
+    /// Finds the ticket in the branch name; its first group, if it has one, is the ticket
+    #[serde(default = "default_ticket_pattern")]
+    pub pattern: String,
+    #[serde(default)]
+    pub placement: TicketPlacement,
+    /// How the reference is written, with `{ticket}` for the ticket; defaults by placement
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+fn default_ticket_pattern() -> String {
+    r"[A-Z][A-Z0-9]+-\d+".to_string()
+}
+
+/// Safety policy for commands proposed by `lumen operate`, on top of the built-in deny list
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct OperateConfig {
+    /// Patterns blocked unless `--allow-destructive` is passed, like `git rebase`
+    #[serde(default)]
+    pub deny: Vec<String>,
+    /// Patterns run even when denied, built-in or not
+    #[serde(default)]
+    pub allow: Vec<String>,
+}
+
+/// Settings for `lumen review`
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ReviewConfig {
+    /// Findings to drop, one rule each as in `.lumenignore-findings`, like `generated/ style`
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+/// How requests to providers and webhooks reach the network. `HTTPS_PROXY`, `HTTP_PROXY` and
+/// `NO_PROXY` are honored without any of this.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct HttpConfig {
+    /// Proxy for every request, like `http://proxy.corp:3128`, instead of the environment's
+    #[serde(default)]
+    pub proxy: Option<String>,
+    /// PEM file of CA certificates to trust on top of the system's, for TLS-inspecting proxies
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+    /// Seconds a request may take before it's abandoned; no limit by default
+    #[serde(default)]
+    pub timeout: Option<u64>,
+}
+
+impl DiffConfig {
+    /// Save as the `diff` section of the global config file, keeping the rest of the file.
+    pub fn save(&self) -> Result<PathBuf, LumenError> {
+        let (path, mut config) = read_global_config()?;
+        config["diff"] = serde_json::to_value(self)?;
+        write_global_config(&path, &config)?;
+        Ok(path)
+    }
+}
+
+/// The global config file's path and contents, or an empty object if it doesn't exist yet.
+pub fn read_global_config() -> Result<(PathBuf, Value), LumenError> {
+    let path = global_config_path().ok_or_else(|| {
+        LumenError::ConfigurationError("Could not determine home directory".to_string())
+    })?;
+    let config = if path.exists() {
+        read_config_value(&path)?
+    } else {
+        Value::Object(Default::default())
+    };
+    if !config.is_object() {
+        return Err(LumenError::InvalidConfiguration(format!(
+            "{}: expected a JSON object",
+            path.display()
+        )));
+    }
+    Ok((path, config))
+}
+
+pub fn write_global_config(path: &Path, config: &Value) -> Result<(), LumenError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(config)?)?;
+    Ok(())
 }
 
 fn default_ai_provider() -> ProviderType {
@@ -49,10 +455,14 @@ where
     D: Deserializer<'de>,
 {
     let s = String::deserialize(deserializer)?;
-    s.parse().map_err(serde::de::Error::custom)
+    s.parse()
+        .map_err(|_| serde::de::Error::unknown_variant(&s, &PROVIDER_IDS))
 }
 
-fn default_commit_types() -> String {
+static PROVIDER_IDS: Lazy<Vec<&'static str>> =
+    Lazy::new(|| ALL_PROVIDERS.iter().map(|provider| provider.id).collect());
+
+pub fn default_commit_types() -> String {
     indoc! {r#"
     {
         "docs": "Documentation only changes",
@@ -79,6 +489,10 @@ fn default_api_key() -> Option<String> {
     std::env::var("LUMEN_API_KEY").ok()
 }
 
+fn default_api_base_url() -> Option<String> {
+    std::env::var("LUMEN_API_BASE_URL").ok()
+}
+
 fn deserialize_commit_types<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: Deserializer<'de>,
@@ -90,53 +504,300 @@ where
 fn default_draft_config() -> DraftConfig {
     DraftConfig {
         commit_types: default_commit_types(),
+        forbidden_words: Vec::new(),
+        scopes: Vec::new(),
+        gitmoji: false,
+        gitmojis: HashMap::new(),
+        ticket: None,
+        subject_max_length: None,
     }
 }
 
-fn default_config_path() -> Option<String> {
-    home_dir().and_then(|mut path| {
-        path.push(".config/lumen/lumen.config.json");
-        path.exists()
-            .then(|| path)
-            .and_then(|p| p.to_str().map(|s| s.to_string()))
-    })
+fn global_config_path() -> Option<PathBuf> {
+    home_dir().map(|path| path.join(".config").join("lumen").join("lumen.config.json"))
+}
+
+/// The global config files that exist, lowest priority first: `lumen.config.toml` for
+/// writing by hand, then `lumen.config.json`, which `lumen configure` writes.
+fn global_config_paths() -> Vec<PathBuf> {
+    let Some(json) = global_config_path() else {
+        return Vec::new();
+    };
+    [json.with_extension("toml"), json]
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect()
+}
+
+/// Names of the profiles in the global and repository config files, sorted, for completing
+/// `--profile`. Files that can't be read are skipped.
+pub fn profile_names() -> Vec<String> {
+    let mut names: Vec<String> = global_config_paths()
+        .into_iter()
+        .chain(repo_config_path())
+        .filter_map(|path| read_config_value(&path).ok())
+        .filter_map(|config| match config.get("profiles") {
+            Some(Value::Object(profiles)) => Some(profiles.keys().cloned().collect::<Vec<_>>()),
+            _ => None,
+        })
+        .flatten()
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Config files a repository can commit, in order of preference
+const REPO_CONFIG_FILES: &[&str] = &[".lumen.toml", "lumen.config.toml", "lumen.config.json"];
+
+/// Settings a repository config can't override, so a cloned repo can't send your API key
+/// to an endpoint of its choosing or run commands of its choosing. Nested keys are dotted.
+const PERSONAL_KEYS: &[&str] = &[
+    "api_key",
+    "api_base_url",
+    "hooks",
+    "operate.allow",
+    "http.proxy",
+    "http.ca_cert",
+];
+
+/// Find the repository's config file by walking up from the current directory, stopping
+/// at the repository root.
+fn repo_config_path() -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+    for dir in cwd.ancestors() {
+        let found = REPO_CONFIG_FILES
+            .iter()
+            .map(|name| dir.join(name))
+            .find(|path| path.is_file());
+        if found.is_some() {
+            return found;
+        }
+        if dir.join(".git").exists() {
+            break;
+        }
+    }
+    None
+}
+
+/// Read a JSON or TOML config file into a JSON value so layers can be merged before
+/// deserializing.
+fn read_config_value(path: &Path) -> Result<Value, LumenError> {
+    let contents = std::fs::read_to_string(path)?;
+    let invalid = |e: String| LumenError::InvalidConfiguration(format!("{}: {e}", path.display()));
+
+    if path.extension().is_some_and(|ext| ext == "toml") {
+        toml_to_json(&contents).map_err(invalid)
+    } else {
+        serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))
+    }
+}
+
+/// Parse a TOML document into the JSON value of its top-level table.
+pub fn toml_to_json(contents: &str) -> Result<Value, String> {
+    let document: DocumentMut = contents.parse().map_err(|e| format!("{e}"))?;
+    Ok(toml_table_to_json(document.as_table()))
+}
+
+fn toml_table_to_json(table: &toml_edit::Table) -> Value {
+    Value::Object(
+        table
+            .iter()
+            .filter_map(|(key, item)| Some((key.to_string(), toml_item_to_json(item)?)))
+            .collect(),
+    )
+}
+
+fn toml_item_to_json(item: &toml_edit::Item) -> Option<Value> {
+    match item {
+        toml_edit::Item::None => None,
+        toml_edit::Item::Value(value) => Some(toml_value_to_json(value)),
+        toml_edit::Item::Table(table) => Some(toml_table_to_json(table)),
+        toml_edit::Item::ArrayOfTables(tables) => Some(Value::Array(
+            tables.iter().map(toml_table_to_json).collect(),
+        )),
+    }
+}
+
+fn toml_value_to_json(value: &toml_edit::Value) -> Value {
+    match value {
+        toml_edit::Value::String(s) => Value::from(s.value().as_str()),
+        toml_edit::Value::Integer(i) => Value::from(*i.value()),
+        toml_edit::Value::Float(f) => Value::from(*f.value()),
+        toml_edit::Value::Boolean(b) => Value::from(*b.value()),
+        toml_edit::Value::Datetime(d) => Value::from(d.value().to_string()),
+        toml_edit::Value::Array(array) => {
+            Value::Array(array.iter().map(toml_value_to_json).collect())
+        }
+        toml_edit::Value::InlineTable(table) => Value::Object(
+            table
+                .iter()
+                .map(|(key, value)| (key.to_string(), toml_value_to_json(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Drop the personal settings from a repository config, including inside its profiles.
+fn strip_personal_keys(config: &mut Value, path: &Path) {
+    let remove = |section: &mut Value, prefix: &str| {
+        let Some(section) = section.as_object_mut() else {
+            return;
+        };
+        for key in PERSONAL_KEYS {
+            let removed = match key.split_once('.') {
+                Some((parent, child)) => section
+                    .get_mut(parent)
+                    .and_then(Value::as_object_mut)
+                    .and_then(|parent| parent.remove(child)),
+                None => section.remove(*key),
+            };
+            if removed.is_some() {
+                eprintln!(
+                    "Warning: ignoring `{prefix}{key}` in {}; set it in your global config or environment instead",
+                    path.display()
+                );
+            }
+        }
+    };
+
+    if let Some(profiles) = config.get_mut("profiles").and_then(Value::as_object_mut) {
+        for (name, profile) in profiles {
+            remove(profile, &format!("profiles.{name}."));
+        }
+    }
+    remove(config, "");
+}
+
+/// Merge the selected profile over the rest of the config and drop the `profiles` table,
+/// so a profile can swap provider, model, draft and AI settings at once.
+fn apply_profile(config: &mut Value, profile: Option<&str>) -> Result<(), LumenError> {
+    let profiles = config
+        .as_object_mut()
+        .and_then(|config| config.remove("profiles"));
+    let Some(name) = profile else {
+        return Ok(());
+    };
+
+    let mut profiles = match profiles {
+        Some(Value::Object(profiles)) => profiles,
+        _ => serde_json::Map::new(),
+    };
+    match profiles.remove(name) {
+        Some(selected) => {
+            merge_config(config, selected);
+            Ok(())
+        }
+        None => {
+            let available: Vec<_> = profiles.keys().map(String::as_str).collect();
+            Err(LumenError::InvalidConfiguration(if available.is_empty() {
+                format!("profile `{name}` not found: no profiles are configured")
+            } else {
+                format!(
+                    "profile `{name}` not found, expected one of: {}",
+                    available.join(", ")
+                )
+            }))
+        }
+    }
+}
+
+/// Merge `overlay` into `base`: objects are merged key by key, anything else is replaced.
+/// Commit types are replaced as a whole so a repository's list is exactly what it says.
+fn merge_config(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) if key != "commit_types" => merge_config(existing, value),
+                    _ => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
 }
 
 impl LumenConfig {
     pub fn build(cli: &Cli) -> Result<Self, LumenError> {
-        let config = if let Some(config_path) = &cli.config {
-            LumenConfig::from_file(config_path)?
-        } else {
-            match default_config_path() {
-                Some(path) => LumenConfig::from_file(&path)?,
-                None => LumenConfig::default(),
-            }
-        };
+        // Layered from lowest to highest priority: global, repository, --config
+        let mut layers = Vec::new();
+        for path in global_config_paths() {
+            layers.push((path.clone(), read_config_value(&path)?));
+        }
+        if let Some(path) = repo_config_path() {
+            let mut repo = read_config_value(&path)?;
+            strip_personal_keys(&mut repo, &path);
+            layers.push((path, repo));
+        }
+        if let Some(path) = &cli.config {
+            let path = PathBuf::from(path);
+            layers.push((path.clone(), read_config_value(&path)?));
+        }
+        let mut value = Value::Object(Default::default());
+        for (_, layer) in &layers {
+            merge_config(&mut value, layer.clone());
+        }
+        apply_profile(&mut value, cli.profile.as_deref())?;
+        let config: LumenConfig =
+            serde_json::from_value(value).map_err(|e| config_error(&e, &layers))?;
 
         let provider = cli.provider.as_ref().cloned().unwrap_or(config.provider);
         let api_key = cli.api_key.clone().or(config.api_key);
         let model = cli.model.clone().or(config.model);
+        let api_base_url = cli.api_base_url.clone().or(config.api_base_url);
+
+        // Generation settings given on the command line apply to every command
+        let mut ai = config.ai;
+        if let Some(temperature) = cli.temperature {
+            ai.temperature = Some(temperature);
+            for settings in ai.per_command.values_mut() {
+                settings.temperature = None;
+            }
+        }
+        if let Some(top_p) = cli.top_p {
+            ai.top_p = Some(top_p);
+            for settings in ai.per_command.values_mut() {
+                settings.top_p = None;
+            }
+        }
+        if let Some(max_output_tokens) = cli.max_output_tokens {
+            ai.max_output_tokens = Some(max_output_tokens);
+            for settings in ai.per_command.values_mut() {
+                settings.max_output_tokens = None;
+            }
+        }
+        if let Some(effort) = &cli.reasoning_effort {
+            ai.reasoning_effort = Some(effort.clone());
+            for settings in ai.per_command.values_mut() {
+                settings.reasoning_effort = None;
+            }
+        }
+        if let Some(language) = &cli.lang {
+            ai.language = Some(language.clone());
+            for settings in ai.per_command.values_mut() {
+                settings.language = None;
+            }
+        }
+        ai.validate()?;
 
         Ok(LumenConfig {
             provider,
             model,
             api_key,
+            api_base_url,
             draft: config.draft,
+            ai,
+            webhook: config.webhook,
+            hooks: config.hooks,
+            diff: config.diff,
+            operate: config.operate,
+            http: config.http,
+            review: config.review,
         })
     }
-
-    pub fn from_file(file_path: &str) -> Result<Self, LumenError> {
-        let file = File::open(file_path)?;
-        let reader = BufReader::new(file);
-
-        // Deserialize JSON data into the LumenConfig struct
-        let config: LumenConfig = match from_reader(reader) {
-            Ok(config) => config,
-            Err(e) => return Err(LumenError::InvalidConfiguration(e.to_string())),
-        };
-
-        Ok(config)
-    }
 }
 
 impl Default for LumenConfig {
@@ -145,7 +806,208 @@ impl Default for LumenConfig {
             provider: default_ai_provider(),
             model: default_model(),
             api_key: default_api_key(),
+            api_base_url: default_api_base_url(),
             draft: default_draft_config(),
+            ai: AiConfig::default(),
+            webhook: None,
+            hooks: HashMap::new(),
+            diff: DiffConfig::default(),
+            operate: OperateConfig::default(),
+            http: HttpConfig::default(),
+            review: ReviewConfig::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_command_temperature_overrides_global() {
+        let ai: AiConfig = serde_json::from_str(
+            r#"{"temperature": 0.7, "per_command": {"draft": {"temperature": 0.1}}}"#,
+        )
+        .unwrap();
+        assert_eq!(ai.for_command("draft").temperature, Some(0.1));
+        assert_eq!(ai.for_command("explain").temperature, Some(0.7));
+    }
+
+    #[test]
+    fn test_language_per_command() {
+        let ai: AiConfig = serde_json::from_str(
+            r#"{"language": "Japanese", "per_command": {"draft": {"language": "English"}}}"#,
+        )
+        .unwrap();
+        assert_eq!(ai.for_command("draft").language.as_deref(), Some("English"));
+        assert_eq!(
+            ai.for_command("review").language.as_deref(),
+            Some("Japanese")
+        );
+    }
+
+    #[test]
+    fn test_temperature_out_of_range() {
+        let ai: AiConfig =
+            serde_json::from_str(r#"{"per_command": {"explain": {"temperature": 3.0}}}"#).unwrap();
+        assert!(ai.validate().is_err());
+    }
+
+    #[test]
+    fn test_generation_settings_are_merged_and_checked() {
+        let ai: AiConfig = serde_json::from_str(
+            r#"{"reasoning_effort": "low", "max_output_tokens": 800,
+                "per_command": {"review": {"reasoning_effort": "16000", "top_p": 0.5}}}"#,
+        )
+        .unwrap();
+        assert!(ai.validate().is_ok());
+        let review = ai.for_command("review");
+        assert_eq!(review.reasoning_effort.as_deref(), Some("16000"));
+        assert_eq!(review.max_output_tokens, Some(800));
+        assert_eq!(review.top_p, Some(0.5));
+        assert_eq!(ai.for_command("draft").top_p, None);
+
+        for invalid in [
+            r#"{"reasoning_effort": "extreme"}"#,
+            r#"{"per_command": {"draft": {"top_p": 1.5}}}"#,
+            r#"{"max_output_tokens": 0}"#,
+        ] {
+            let ai: AiConfig = serde_json::from_str(invalid).unwrap();
+            assert!(ai.validate().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_prompt_placeholders_are_checked() {
+        let ai: AiConfig = serde_json::from_str(
+            r#"{"prompts": {"draft": {"user": "Diff: {{diff}} {{context}}"}}}"#,
+        )
+        .unwrap();
+        assert!(ai.validate().is_ok());
+        assert!(ai.prompt("draft").is_some());
+
+        let ai: AiConfig =
+            serde_json::from_str(r#"{"prompts": {"operate": {"user": "{{diff}}"}}}"#).unwrap();
+        assert!(ai.validate().is_err());
+
+        let ai: AiConfig =
+            serde_json::from_str(r#"{"prompts": {"digest": {"system": "x"}}}"#).unwrap();
+        assert!(ai.validate().is_err());
+    }
+
+    #[test]
+    fn test_repo_config_merges_over_global() {
+        let mut config = serde_json::json!({
+            "provider": "openai",
+            "api_key": "personal",
+            "ai": {"temperature": 0.7, "max_retries": 5},
+            "draft": {"commit_types": {"feat": "A feature", "fix": "A fix"}}
+        });
+        merge_config(
+            &mut config,
+            serde_json::json!({
+                "model": "gpt-5-mini",
+                "ai": {"temperature": 0.2},
+                "draft": {"commit_types": {"change": "Any change"}}
+            }),
+        );
+        assert_eq!(
+            config,
+            serde_json::json!({
+                "provider": "openai",
+                "api_key": "personal",
+                "model": "gpt-5-mini",
+                "ai": {"temperature": 0.2, "max_retries": 5},
+                "draft": {"commit_types": {"change": "Any change"}}
+            })
+        );
+    }
+
+    #[test]
+    fn test_repo_config_cant_allow_operate_commands() {
+        let mut repo = serde_json::json!({
+            "hooks": {"draft": "true"},
+            "operate": {"deny": ["git rebase"], "allow": ["git reset --hard"]},
+            "profiles": {"ci": {"operate": {"allow": ["git clean -f"]}}}
+        });
+        strip_personal_keys(&mut repo, Path::new("lumen.config.json"));
+        assert_eq!(
+            repo,
+            serde_json::json!({
+                "operate": {"deny": ["git rebase"]},
+                "profiles": {"ci": {"operate": {}}}
+            })
+        );
+    }
+
+    #[test]
+    fn test_repo_config_cant_redirect_requests() {
+        let mut repo = serde_json::json!({
+            "api_base_url": "https://attacker.example",
+            "http": {"proxy": "http://attacker.example:3128", "ca_cert": "certs/ca.pem", "timeout": 30},
+            "profiles": {"ci": {"http": {"proxy": "http://attacker.example:3128"}}}
+        });
+        strip_personal_keys(&mut repo, Path::new(".lumen.toml"));
+        assert_eq!(
+            repo,
+            serde_json::json!({
+                "http": {"timeout": 30},
+                "profiles": {"ci": {"http": {}}}
+            })
+        );
+    }
+
+    #[test]
+    fn test_profile_overrides_base_settings() {
+        let base = serde_json::json!({
+            "provider": "openai",
+            "model": "gpt-5-mini",
+            "profiles": {
+                "personal": {"provider": "openrouter", "api_key": "sk-or"},
+                "work": {}
+            }
+        });
+
+        let mut config = base.clone();
+        apply_profile(&mut config, Some("personal")).unwrap();
+        assert_eq!(
+            config,
+            serde_json::json!({"provider": "openrouter", "model": "gpt-5-mini", "api_key": "sk-or"})
+        );
+
+        let mut config = base.clone();
+        apply_profile(&mut config, None).unwrap();
+        assert_eq!(
+            config,
+            serde_json::json!({"provider": "openai", "model": "gpt-5-mini"})
+        );
+
+        let mut config = base;
+        let err = apply_profile(&mut config, Some("home")).unwrap_err();
+        assert!(err.to_string().contains("personal, work"));
+    }
+
+    #[test]
+    fn test_toml_config_converts_to_json() {
+        let document: DocumentMut = indoc! {r#"
+            model = "gpt-5-mini"
+
+            [ai]
+            temperature = 0.2
+            max_retries = 2
+
+            [draft.commit_types]
+            feat = "A new feature"
+        "#}
+        .parse()
+        .unwrap();
+        assert_eq!(
+            toml_table_to_json(document.as_table()),
+            serde_json::json!({
+                "model": "gpt-5-mini",
+                "ai": {"temperature": 0.2, "max_retries": 2},
+                "draft": {"commit_types": {"feat": "A new feature"}}
+            })
+        );
+    }
+}
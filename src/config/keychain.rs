@@ -0,0 +1,65 @@
+//! API keys kept in the system keychain (macOS Keychain, Secret Service on Linux,
+//! Windows Credential Manager) instead of the config file.
+//!
+//! The config stores a `keyring:<name>` reference in place of the key, which is looked
+//! up when the provider is created.
+
+use crate::error::LumenError;
+use keyring::Entry;
+
+/// Service name the keys are stored under
+const SERVICE: &str = "lumen";
+
+const REFERENCE_PREFIX: &str = "keyring:";
+
+/// The reference to write to the config for a key stored under `name`
+pub fn reference(name: &str) -> String {
+    format!("{REFERENCE_PREFIX}{name}")
+}
+
+/// Store `secret` in the keychain under `name`, replacing any previous value.
+pub fn store(name: &str, secret: &str) -> Result<(), LumenError> {
+    Entry::new(SERVICE, name)
+        .and_then(|entry| entry.set_password(secret))
+        .map_err(|e| {
+            LumenError::ConfigurationError(format!(
+                "could not store the API key in the system keychain: {e}"
+            ))
+        })
+}
+
+/// Resolve an API key from the config: `keyring:<name>` is read from the keychain,
+/// anything else is a literal key.
+pub fn resolve(api_key: String) -> Result<String, LumenError> {
+    let Some(name) = api_key.strip_prefix(REFERENCE_PREFIX) else {
+        return Ok(api_key);
+    };
+    if name.is_empty() {
+        return Err(LumenError::InvalidConfiguration(
+            "`keyring:` reference needs a name, e.g. `keyring:openai`".to_string(),
+        ));
+    }
+
+    Entry::new(SERVICE, name)
+        .and_then(|entry| entry.get_password())
+        .map_err(|e| match e {
+            keyring::Error::NoEntry => LumenError::ConfigurationError(format!(
+                "no API key named `{name}` in the system keychain (run `lumen configure` to store one)"
+            )),
+            e => LumenError::ConfigurationError(format!(
+                "could not read API key `{name}` from the system keychain: {e}"
+            )),
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_literal_keys_pass_through() {
+        assert_eq!(resolve("sk-xxxx".to_string()).unwrap(), "sk-xxxx");
+        assert_eq!(reference("openai"), "keyring:openai");
+        assert!(resolve("keyring:".to_string()).is_err());
+    }
+}
@@ -1,6 +1,8 @@
 pub mod cli;
 pub mod configuration;
+pub mod keychain;
 pub mod providers;
+pub mod schema;
 
 pub use configuration::LumenConfig;
 pub use providers::{ProviderInfo, ALL_PROVIDERS};
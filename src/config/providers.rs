@@ -1,8 +1,8 @@
-/// Single source of truth for all provider configurations.
-/// 
-/// Add new providers here - they will automatically appear in:
-/// - The `lumen configure` interactive prompt
-/// - The provider initialization in provider/mod.rs
+//! Single source of truth for all provider configurations.
+//!
+//! Add new providers here - they will automatically appear in:
+//! - The `lumen configure` interactive prompt
+//! - The provider initialization in provider/mod.rs
 
 use crate::config::cli::ProviderType;
 
@@ -13,6 +13,8 @@ pub struct ProviderInfo {
     pub display_name: &'static str,
     pub default_model: &'static str,
     pub env_key: &'static str,
+    /// Reliable function calling, which lets `operate` look at the repository first
+    pub supports_tools: bool,
 }
 
 /// All supported providers - single source of truth.
@@ -24,6 +26,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "OpenAI",
         default_model: "gpt-5-mini",
         env_key: "OPENAI_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "groq",
@@ -31,6 +34,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "Groq",
         default_model: "llama-3.3-70b-versatile",
         env_key: "GROQ_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "claude",
@@ -38,6 +42,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "Claude (Anthropic)",
         default_model: "claude-sonnet-4-5-20250930",
         env_key: "ANTHROPIC_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "ollama",
@@ -45,6 +50,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "Ollama (local)",
         default_model: "llama3.2",
         env_key: "",
+        supports_tools: false,
     },
     ProviderInfo {
         id: "openrouter",
@@ -52,6 +58,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "OpenRouter",
         default_model: "anthropic/claude-sonnet-4.5",
         env_key: "OPENROUTER_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "deepseek",
@@ -59,6 +66,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "DeepSeek",
         default_model: "deepseek-chat",
         env_key: "DEEPSEEK_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "gemini",
@@ -66,6 +74,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "Gemini (Google)",
         default_model: "gemini-2.5-flash",
         env_key: "GEMINI_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "xai",
@@ -73,6 +82,7 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "xAI (Grok)",
         default_model: "grok-4-mini-fast",
         env_key: "XAI_API_KEY",
+        supports_tools: true,
     },
     ProviderInfo {
         id: "vercel",
@@ -80,6 +90,24 @@ pub const ALL_PROVIDERS: &[ProviderInfo] = &[
         display_name: "Vercel AI Gateway",
         default_model: "anthropic/claude-sonnet-4.5",
         env_key: "VERCEL_API_KEY",
+        supports_tools: false,
+    },
+    ProviderInfo {
+        id: "custom-openai",
+        provider_type: ProviderType::CustomOpenai,
+        display_name: "OpenAI-compatible (custom URL)",
+        default_model: "",
+        env_key: "OPENAI_COMPATIBLE_API_KEY",
+        supports_tools: false,
+    },
+    ProviderInfo {
+        id: "bedrock",
+        provider_type: ProviderType::Bedrock,
+        display_name: "AWS Bedrock",
+        default_model: "us.anthropic.claude-sonnet-4-5-20250929-v1:0",
+        // Uses the AWS credential chain instead of an API key
+        env_key: "",
+        supports_tools: false,
     },
 ];
 
@@ -0,0 +1,163 @@
+//! Precise errors for config files that don't match what lumen reads.
+//!
+//! Every config section rejects keys it doesn't know, so a typo like `provder` fails instead
+//! of silently leaving the default in place. serde names the unknown key or value and what it
+//! expected, but not where it is; that's looked up in the config files here, so the error
+//! gives the file, the dotted key and the closest accepted name.
+
+use std::path::{Path, PathBuf};
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::commit_reference::similar_names;
+use crate::error::LumenError;
+
+static UNKNOWN: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"^unknown (field|variant) `([^`]*)`, (?:expected (.*)|there are no \w+)$")
+        .expect("unknown pattern is valid")
+});
+
+static NAME: Lazy<Regex> = Lazy::new(|| Regex::new(r"`([^`]*)`").expect("name pattern is valid"));
+
+/// The error for `error`, from deserializing the config merged from `layers` (each file's
+/// path and contents, lowest priority first).
+pub fn config_error(error: &serde_json::Error, layers: &[(PathBuf, Value)]) -> LumenError {
+    let message = error.to_string();
+    let Some(captures) = UNKNOWN.captures(&message) else {
+        return LumenError::InvalidConfiguration(message);
+    };
+    let is_key = &captures[1] == "field";
+    let name = &captures[2];
+    let accepted: Vec<&str> = captures
+        .get(3)
+        .map(|expected| {
+            NAME.captures_iter(expected.as_str())
+                .map(|name| name.get(1).map_or("", |m| m.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    // The highest priority file is the one whose value was read
+    let found = layers.iter().rev().find_map(|(path, value)| {
+        let mut keys = Vec::new();
+        locate(value, name, is_key, &mut keys).then(|| (path.as_path(), keys.join(".")))
+    });
+    LumenError::InvalidConfiguration(describe(name, is_key, &accepted, found))
+}
+
+fn describe(name: &str, is_key: bool, accepted: &[&str], found: Option<(&Path, String)>) -> String {
+    let mut message = match (&found, is_key) {
+        (Some((path, key)), true) => format!("{}: unknown key `{key}`", path.display()),
+        (Some((path, key)), false) => format!("{}: `{key}` can't be `{name}`", path.display()),
+        (None, true) => format!("unknown key `{name}`"),
+        (None, false) => format!("unknown value `{name}`"),
+    };
+    match similar_names(name, accepted.iter().copied()).first() {
+        Some(suggestion) => message.push_str(&format!("; did you mean `{suggestion}`?")),
+        None => message.push('.'),
+    }
+    if accepted.is_empty() {
+        message.push_str(" This section takes no keys.");
+    } else {
+        message.push_str(&format!(" Expected one of: {}", accepted.join(", ")));
+    }
+    message
+}
+
+/// Whether `value` has a key named `name` (or, if not `is_key`, a string equal to it), with
+/// `keys` set to the keys leading there.
+fn locate(value: &Value, name: &str, is_key: bool, keys: &mut Vec<String>) -> bool {
+    match value {
+        Value::Object(object) => {
+            if is_key && object.contains_key(name) {
+                keys.push(name.to_string());
+                return true;
+            }
+            for (key, child) in object {
+                keys.push(key.clone());
+                if locate(child, name, is_key, keys) {
+                    return true;
+                }
+                keys.pop();
+            }
+            false
+        }
+        Value::Array(items) => items.iter().enumerate().any(|(i, item)| {
+            keys.push(i.to_string());
+            let found = locate(item, name, is_key, keys);
+            if !found {
+                keys.pop();
+            }
+            found
+        }),
+        Value::String(text) => !is_key && text.eq_ignore_ascii_case(name),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::LumenConfig;
+    use serde_json::json;
+
+    /// The error for `layers` merged into `merged`.
+    fn error_for(layers: &[(PathBuf, Value)], merged: Value) -> String {
+        let error = serde_json::from_value::<LumenConfig>(merged).unwrap_err();
+        config_error(&error, layers).to_string()
+    }
+
+    fn error_in(file: &str, config: Value) -> String {
+        error_for(&[(PathBuf::from(file), config.clone())], config)
+    }
+
+    #[test]
+    fn test_unknown_key_is_located() {
+        let layers = [
+            (PathBuf::from("global.json"), json!({"model": "gpt-5"})),
+            (
+                PathBuf::from(".lumen.toml"),
+                json!({"ai": {"per_command": {"draft": {"temprature": 0.2}}}}),
+            ),
+        ];
+        let merged =
+            json!({"model": "gpt-5", "ai": {"per_command": {"draft": {"temprature": 0.2}}}});
+        let error = error_for(&layers, merged);
+        assert!(
+            error.contains(
+                ".lumen.toml: unknown key `ai.per_command.draft.temprature`; did you mean `temperature`?"
+            ),
+            "{error}"
+        );
+        assert!(
+            error.contains("Expected one of: temperature, top_p"),
+            "{error}"
+        );
+
+        let error = error_in("lumen.config.json", json!({"provder": "claude"}));
+        assert!(
+            error.contains("lumen.config.json: unknown key `provder`; did you mean `provider`?"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn test_invalid_value_is_located() {
+        let error = error_in("lumen.config.json", json!({"ai": {"secrets": "redcat"}}));
+        assert!(
+            error.contains(
+                "lumen.config.json: `ai.secrets` can't be `redcat`; did you mean `redact`? Expected one of: redact, fail"
+            ),
+            "{error}"
+        );
+
+        let error = error_in("c.json", json!({"provider": "antropic"}));
+        assert!(
+            error.contains("c.json: `provider` can't be `antropic`"),
+            "{error}"
+        );
+        assert!(error.contains("claude"), "{error}");
+    }
+}
@@ -13,7 +13,6 @@ pub enum LumenError {
     #[error("{0}")]
     GitDiffError(#[from] DiffError),
 
-    #[allow(dead_code)]
     #[error("Invalid arguments: {0}")]
     InvalidArguments(String),
 
@@ -35,6 +34,71 @@ pub enum LumenError {
     #[error(transparent)]
     ProviderError(#[from] ProviderError),
 
+    #[error("Webhook delivery failed: {0}")]
+    WebhookError(String),
+
     #[error("JSON error: {0}")]
     JsonError(#[from] serde_json::Error),
+
+    #[error("review found {found} finding(s) at {severity} or above, more than the {max} allowed")]
+    ReviewFailed {
+        found: usize,
+        max: usize,
+        severity: &'static str,
+    },
+
+    #[error("review timed out after {0}s")]
+    ReviewTimeout(u64),
+
+    #[error("check found {found} finding(s) at {severity} or above")]
+    CheckFailed {
+        found: usize,
+        severity: &'static str,
+    },
+
+    #[error("found {found} problem(s) in commit messages")]
+    LintFailed { found: usize },
+
+    #[error(
+        "git isn't installed or on PATH\n(hint: install it from https://git-scm.com/downloads)"
+    )]
+    GitMissing,
+
+    #[error("not inside a git repository\n(hint: run lumen from a repository's directory, or `git init` to start one)")]
+    NotARepo,
+
+    #[error("HEAD is detached, so there's no branch to {0}\n(hint: `git switch -c <name>` puts the commits on a new branch)")]
+    DetachedHead(&'static str),
+
+    #[error("gh isn't installed or on PATH, and lumen talks to GitHub through it\n(hint: install it from https://cli.github.com, then run `gh auth login`)")]
+    GhMissing,
+
+    #[error("gh isn't signed in to GitHub: {0}\n(hint: run `gh auth login`, or set GH_TOKEN)")]
+    GhNotAuthenticated(String),
+}
+
+/// Exit code when there were no changes to work on, so scripts can tell it apart from a
+/// failure. Clap already uses 2 for usage errors.
+pub const EXIT_NO_CHANGES: i32 = 3;
+
+/// Exit code of `lumen review --quiet` when more findings than allowed reach the severity, of
+/// `lumen check` when any does, and of `lumen lint-msg` when a message breaks the conventions
+pub const EXIT_REVIEW_FAILED: i32 = 4;
+
+/// Exit code of `lumen review --quiet` when the review didn't finish within `--timeout`
+pub const EXIT_REVIEW_TIMEOUT: i32 = 5;
+
+impl LumenError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            LumenError::GitDiffError(_) | LumenError::GitCommitError(CommitError::EmptyDiff(_)) => {
+                EXIT_NO_CHANGES
+            }
+            LumenError::ReviewFailed { .. }
+            | LumenError::CheckFailed { .. }
+            | LumenError::LintFailed { .. } => EXIT_REVIEW_FAILED,
+            LumenError::ReviewTimeout(_) => EXIT_REVIEW_TIMEOUT,
+            _ => 1,
+        }
+    }
 }
@@ -1,8 +1,12 @@
+use crate::commit_reference::{similar_names, split_revision};
 use crate::error::LumenError;
+use crate::logging;
+use crate::vcs::Vcs;
+use lumen_core::git::DiffSource;
 use std::process::Command;
 use thiserror::Error;
 
-use super::GIT_DIFF_EXCLUSIONS;
+use super::diff::DiffError;
 
 /// Errors that can occur when resolving commit metadata or diffs.
 #[derive(Error, Debug, Clone)]
@@ -10,7 +14,25 @@ pub enum CommitError {
     #[error("Commit '{0}' not found")]
     InvalidCommit(String),
 
-    #[error("Diff for commit '{0}' is empty")]
+    #[error("Commit '{reference}' not found; did you mean {}?", .suggestions.join(", "))]
+    UnknownRevision {
+        reference: String,
+        suggestions: Vec<String>,
+    },
+
+    #[error("short SHA '{reference}' is ambiguous, it could be:\n{}", .candidates.join("\n"))]
+    AmbiguousSha {
+        reference: String,
+        /// A line for each matching commit: its hash, date and subject
+        candidates: Vec<String>,
+    },
+
+    /// git's own explanation, for revisions that name something that doesn't exist, like
+    /// `@{upstream}` on a branch without one
+    #[error("can't resolve '{reference}': {reason}")]
+    Unresolvable { reference: String, reason: String },
+
+    #[error("commit '{0}' has no changes outside lockfiles and other excluded files")]
     EmptyDiff(String),
 }
 
@@ -28,56 +50,139 @@ pub struct Commit {
 impl Commit {
     /// Build a commit object from a SHA or ref.
     pub fn new(sha: String) -> Result<Self, LumenError> {
-        let sha = sha.trim().to_string();
+        let sha = Vcs::detect().git_revision(sha.trim());
         Self::is_valid_commit(&sha)?;
 
+        #[cfg(feature = "libgit2")]
+        if let Some(info) = crate::libgit::commit_info(&sha) {
+            return Ok(Commit {
+                full_hash: info.full_hash,
+                message: info.message,
+                diff: Self::get_diff(&sha, &[])?,
+                author_name: info.author_name,
+                author_email: info.author_email,
+                date: info.date,
+            });
+        }
+
         Ok(Commit {
             full_hash: Self::get_full_hash(&sha)?,
             message: Self::get_message(&sha)?,
-            diff: Self::get_diff(&sha)?,
+            diff: Self::get_diff(&sha, &[])?,
             author_name: Self::get_author_name(&sha)?,
             author_email: Self::get_author_email(&sha)?,
             date: Self::get_date(&sha)?,
         })
     }
 
-    /// Validate that a SHA or ref resolves to a commit object.
+    /// Limit the commit's diff to `paths`, files, directories or any other git pathspec.
+    pub fn limit_to_paths(&mut self, paths: &[String]) -> Result<(), LumenError> {
+        if !paths.is_empty() {
+            self.diff = Self::get_diff(&self.full_hash, paths)?;
+        }
+        Ok(())
+    }
+
+    /// Validate that a SHA or ref resolves to a commit object. Anything `git rev-parse`
+    /// understands works, like `HEAD~3`, `v1.2^`, `@{upstream}` or `main@{yesterday}`; when it
+    /// doesn't resolve, the error lists the commits a short SHA could be or refs a typo is close to.
     pub fn is_valid_commit(sha: &str) -> Result<(), LumenError> {
         let sha = sha.trim();
-        let output = Command::new("git").args(["cat-file", "-t", sha]).output()?;
-        let output_str = String::from_utf8(output.stdout)?;
-
-        if output_str.trim() == "commit" {
+        #[cfg(feature = "libgit2")]
+        if crate::libgit::is_commit(sha) == Some(true) {
+            return Ok(());
+        }
+        // git knows more revision syntax than libgit2, so it gets the final say
+        let output = logging::output(Command::new("git").args([
+            "rev-parse",
+            "--verify",
+            "--end-of-options",
+            &format!("{sha}^{{commit}}"),
+        ]))?;
+        if output.status.success() {
             return Ok(());
         }
 
-        Err(CommitError::InvalidCommit(sha.to_string()).into())
+        let reason = String::from_utf8_lossy(&output.stderr)
+            .lines()
+            .map(|line| line.trim_start_matches("fatal: ").trim())
+            .find(|line| !line.is_empty() && !line.starts_with("Needed a single revision"))
+            .map(str::to_string);
+        Err(Self::diagnose(sha, reason).into())
+    }
+
+    /// Why `reference` didn't resolve to a commit, as helpfully as can be told.
+    fn diagnose(reference: &str, reason: Option<String>) -> CommitError {
+        let candidates = Self::sha_candidates(reference);
+        if candidates.len() > 1 {
+            return CommitError::AmbiguousSha {
+                reference: reference.to_string(),
+                candidates,
+            };
+        }
+
+        let (name, suffix) = split_revision(reference);
+        let refs = git_lines(&[
+            "for-each-ref",
+            "--format=%(refname:short)",
+            "refs/heads",
+            "refs/tags",
+            "refs/remotes",
+        ]);
+        let suggestions: Vec<String> = similar_names(name, refs.iter().map(String::as_str))
+            .into_iter()
+            .map(|name| format!("{name}{suffix}"))
+            .collect();
+        if !suggestions.is_empty() {
+            return CommitError::UnknownRevision {
+                reference: reference.to_string(),
+                suggestions,
+            };
+        }
+        match reason {
+            // git says why a ref that exists can't be followed, like a missing upstream
+            Some(reason) if !reason.contains("unknown revision") => CommitError::Unresolvable {
+                reference: reference.to_string(),
+                reason,
+            },
+            _ => CommitError::InvalidCommit(reference.to_string()),
+        }
+    }
+
+    /// The commits a short SHA could mean, as `<hash> <date> <subject>` lines.
+    fn sha_candidates(reference: &str) -> Vec<String> {
+        let is_short_sha =
+            (4..40).contains(&reference.len()) && reference.chars().all(|c| c.is_ascii_hexdigit());
+        if !is_short_sha {
+            return Vec::new();
+        }
+        git_lines(&["rev-parse", &format!("--disambiguate={reference}")])
+            .iter()
+            .filter_map(|hash| {
+                // Blobs and trees share the prefix too, but `git log` only shows commits
+                git_lines(&["log", "-1", "--no-walk", "--format=%h %cs %s", hash])
+                    .into_iter()
+                    .next()
+            })
+            .collect()
     }
 
     /// Resolve the full commit hash for a ref.
     fn get_full_hash(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git").args(["rev-parse", sha]).output()?;
+        let output = logging::output(Command::new("git").args(["rev-parse", sha]))?;
 
         let full_hash = String::from_utf8(output.stdout)?.trim_end().to_string();
         Ok(full_hash)
     }
 
     /// Get the commit diff content.
-    fn get_diff(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args([
-                "diff-tree",
-                "-p",
-                "--root",
-                "--binary",
-                "--no-color",
-                "--compact-summary",
-                sha,
-            ])
-            .args(GIT_DIFF_EXCLUSIONS)
-            .output()?;
+    fn get_diff(sha: &str, paths: &[String]) -> Result<String, LumenError> {
+        let output = logging::output(&mut DiffSource::Commit { revision: sha }.command(paths))?;
 
         let diff = String::from_utf8(output.stdout)?;
+        if diff.is_empty() && !paths.is_empty() {
+            return Err(DiffError::Paths(paths.to_vec()).into());
+        }
         if diff.is_empty() {
             return Err(CommitError::EmptyDiff(sha.to_string()).into());
         }
@@ -87,9 +192,8 @@ impl Commit {
 
     /// Get the commit message body.
     fn get_message(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args(["log", "--format=%B", "-n", "1", sha])
-            .output()?;
+        let output =
+            logging::output(Command::new("git").args(["log", "--format=%B", "-n", "1", sha]))?;
 
         let message = String::from_utf8(output.stdout)?
             .trim_end_matches('\n')
@@ -99,9 +203,8 @@ impl Commit {
 
     /// Get the commit author name.
     fn get_author_name(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args(["log", "--format=%an", "-n", "1", sha])
-            .output()?;
+        let output =
+            logging::output(Command::new("git").args(["log", "--format=%an", "-n", "1", sha]))?;
 
         let name = String::from_utf8(output.stdout)?.trim_end().to_string();
         Ok(name)
@@ -109,9 +212,8 @@ impl Commit {
 
     /// Get the commit author email.
     fn get_author_email(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args(["log", "--format=%ae", "-n", "1", sha])
-            .output()?;
+        let output =
+            logging::output(Command::new("git").args(["log", "--format=%ae", "-n", "1", sha]))?;
 
         let email = String::from_utf8(output.stdout)?.trim_end().to_string();
         Ok(email)
@@ -119,22 +221,31 @@ impl Commit {
 
     /// Get the commit timestamp formatted for display.
     fn get_date(sha: &str) -> Result<String, LumenError> {
-        let output = Command::new("git")
-            .args([
-                "log",
-                "--format=%cd",
-                "--date=format:%Y-%m-%d %H:%M:%S",
-                "-n",
-                "1",
-                sha,
-            ])
-            .output()?;
+        let output = logging::output(Command::new("git").args([
+            "log",
+            "--format=%cd",
+            "--date=format:%Y-%m-%d %H:%M:%S",
+            "-n",
+            "1",
+            sha,
+        ]))?;
 
         let date = String::from_utf8(output.stdout)?.trim_end().to_string();
         Ok(date)
     }
 }
 
+/// The lines git prints for `args`, none when it fails.
+fn git_lines(args: &[&str]) -> Vec<String> {
+    match logging::output(Command::new("git").args(args)) {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::Commit;
@@ -220,4 +331,32 @@ mod tests {
         let commit = Commit::new("HEAD".to_string()).expect("root commit should load");
         assert!(!commit.diff.trim().is_empty());
     }
+
+    #[test]
+    fn metadata_should_match_git_log() {
+        let _repo = RepoGuard::new();
+
+        let commit = Commit::new("HEAD".to_string()).expect("root commit should load");
+        assert_eq!(commit.full_hash, Commit::get_full_hash("HEAD").unwrap());
+        assert_eq!(commit.message, "init");
+        assert_eq!(commit.author_name, "Test User");
+        assert_eq!(commit.author_email, "test@example.com");
+        assert_eq!(commit.date, Commit::get_date("HEAD").unwrap());
+    }
+
+    #[test]
+    fn invalid_commit_should_suggest_similar_refs() {
+        let _repo = RepoGuard::new();
+        git(Path::new("."), &["branch", "feature"]);
+
+        assert!(Commit::is_valid_commit("feature~0").is_ok());
+        let error = Commit::is_valid_commit("featrue~0")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("did you mean feature~0?"), "{error}");
+        let error = Commit::is_valid_commit("no-such-thing")
+            .unwrap_err()
+            .to_string();
+        assert_eq!(error, "Commit 'no-such-thing' not found");
+    }
 }
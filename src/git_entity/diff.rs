@@ -1,12 +1,41 @@
 use crate::error::LumenError;
+use crate::logging;
+use crate::vcs::Vcs;
+use lumen_core::git::{DiffSource, RangeOptions};
+use lumen_core::patch::diff_files;
 use thiserror::Error;
 
-use super::{commit::Commit, GIT_DIFF_EXCLUSIONS};
+use super::commit::Commit;
 
+/// Why there's nothing to diff
 #[derive(Error, Debug)]
 pub enum DiffError {
-    #[error("diff{} is empty", if *staged { " (staged)" } else { "" })]
-    EmptyDiff { staged: bool },
+    /// `other_side` is whether the working tree has changes on the other side of the index,
+    /// which is usually what the user meant
+    #[error("{}", empty_working_tree_message(*staged, *other_side))]
+    WorkingTree { staged: bool, other_side: bool },
+
+    #[error("no changes between {from} and {to}")]
+    Range { from: String, to: String },
+
+    #[error("no changes in {}", .0.join(", "))]
+    Paths(Vec<String>),
+
+    #[error("no file changes in the patch; expected a unified diff like `git diff` prints")]
+    Patch,
+}
+
+fn empty_working_tree_message(staged: bool, other_side: bool) -> String {
+    match (staged, other_side) {
+        (true, true) => {
+            "no staged changes, only unstaged ones. Stage them with `git add`".to_string()
+        }
+        (true, false) => "no staged changes, and no unstaged ones either".to_string(),
+        (false, true) => {
+            "no unstaged changes, only staged ones. Did you mean --staged?".to_string()
+        }
+        (false, false) => "no changes in the working tree".to_string(),
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -20,45 +49,75 @@ pub enum Diff {
         to: String,
         diff: String,
     },
+    /// A diff given as text, e.g. piped in from `git diff`, `gh pr diff` or a mailed patch
+    Patch {
+        diff: String,
+    },
 }
 
 impl Diff {
-    pub fn from_working_tree(staged: bool) -> Result<Self, LumenError> {
-        let args = if staged {
-            vec!["diff", "--staged"]
-        } else {
-            vec!["diff"]
-        };
-
-        let output = std::process::Command::new("git")
-            .args(args)
-            .args(GIT_DIFF_EXCLUSIONS)
-            .output()?;
-
-        let diff = String::from_utf8(output.stdout)?;
+    /// The working tree's diff, or the index's with `staged`, limited to `paths` when there
+    /// are any.
+    pub fn from_working_tree(staged: bool, paths: &[String]) -> Result<Self, LumenError> {
+        if let Some(diff) = Vcs::detect().working_copy_diff(paths)? {
+            if diff.is_empty() {
+                return Err(DiffError::WorkingTree {
+                    staged: false,
+                    other_side: false,
+                }
+                .into());
+            }
+            return Ok(Diff::WorkingTree { staged, diff });
+        }
+        let diff = Self::working_tree_diff(staged, paths)?;
         if diff.is_empty() {
-            return Err(DiffError::EmptyDiff { staged }.into());
+            if !paths.is_empty() {
+                return Err(DiffError::Paths(paths.to_vec()).into());
+            }
+            // Checked before anything is sent, so an empty diff never costs a provider call
+            let other_side = !Self::working_tree_diff(!staged, paths)?.is_empty();
+            return Err(DiffError::WorkingTree { staged, other_side }.into());
         }
 
         Ok(Diff::WorkingTree { staged, diff })
     }
 
-    pub fn from_commits_range(from: &str, to: &str, triple_dot: bool) -> Result<Self, LumenError> {
-        let _ = Commit::is_valid_commit(from)?;
-        let _ = Commit::is_valid_commit(to)?;
-
-        let separator = if triple_dot { "..." } else { ".." };
-        let range = format!("{}{}{}", from, separator, to);
+    fn working_tree_diff(staged: bool, paths: &[String]) -> Result<String, LumenError> {
+        let output = logging::output(&mut DiffSource::WorkingTree { staged }.command(paths))?;
+        Ok(String::from_utf8(output.stdout)?)
+    }
 
-        let output = std::process::Command::new("git")
-            .args(["diff", &range])
-            .args(GIT_DIFF_EXCLUSIONS)
-            .output()?;
+    pub fn from_commits_range(
+        from: &str,
+        to: &str,
+        triple_dot: bool,
+        options: RangeOptions,
+        paths: &[String],
+    ) -> Result<Self, LumenError> {
+        // Kept as git names them, since later steps hand them to git
+        let vcs = Vcs::detect();
+        let (from, to) = (&vcs.git_revision(from), &vcs.git_revision(to));
+        Commit::is_valid_commit(from)?;
+        Commit::is_valid_commit(to)?;
 
+        let source = DiffSource::Range {
+            from,
+            to,
+            triple_dot,
+            options,
+        };
+        let output = logging::output(&mut source.command(paths))?;
         let diff = String::from_utf8(output.stdout)?;
 
+        if diff.is_empty() && !paths.is_empty() {
+            return Err(DiffError::Paths(paths.to_vec()).into());
+        }
         if diff.is_empty() {
-            return Err(DiffError::EmptyDiff { staged: false }.into());
+            return Err(DiffError::Range {
+                from: from.to_string(),
+                to: to.to_string(),
+            }
+            .into());
         }
 
         Ok(Diff::CommitsRange {
@@ -67,4 +126,45 @@ impl Diff {
             diff,
         })
     }
+
+    /// A unified diff given as text. Anything before the first file, like the headers of a
+    /// mailed patch, is kept as context.
+    pub fn from_patch(diff: String) -> Result<Self, LumenError> {
+        if diff_files(&diff).is_empty() {
+            return Err(DiffError::Patch.into());
+        }
+        Ok(Diff::Patch { diff })
+    }
+
+    /// Whether `text` looks like a unified diff rather than, say, a commit SHA.
+    pub fn is_patch(text: &str) -> bool {
+        text.lines().any(|line| line.starts_with("diff --git "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_diff_points_at_the_other_side() {
+        let message =
+            |staged, other_side| DiffError::WorkingTree { staged, other_side }.to_string();
+        assert!(message(true, true).contains("git add"));
+        assert!(message(false, true).contains("--staged"));
+        assert_eq!(message(false, false), "no changes in the working tree");
+    }
+
+    #[test]
+    fn test_from_patch_needs_a_file() {
+        let mailed = "From: Ada <ada@example.com>\nSubject: [PATCH] Fix it\n\n\
+                      diff --git a/f.rs b/f.rs\n--- a/f.rs\n+++ b/f.rs\n@@ -1 +1 @@\n-a\n+b\n";
+        assert!(Diff::is_patch(mailed));
+        assert!(matches!(
+            Diff::from_patch(mailed.to_string()),
+            Ok(Diff::Patch { diff }) if diff.starts_with("From: Ada")
+        ));
+        assert!(!Diff::is_patch("abc123f\n"));
+        assert!(Diff::from_patch("abc123f\n".to_string()).is_err());
+    }
 }
@@ -1,6 +1,8 @@
 use commit::Commit;
 use diff::Diff;
 use indoc::formatdoc;
+use lumen_core::patch::{diff_files, fit_diff};
+use serde_json::{json, Value};
 
 use crate::provider::LumenProvider;
 
@@ -13,17 +15,50 @@ pub enum GitEntity {
     Diff(Diff),
 }
 
-pub const GIT_DIFF_EXCLUSIONS: [&str; 7] = [
-    "--", // Separator for pathspecs
-    ".",  // Include everything
-    ":(exclude)package-lock.json",
-    ":(exclude)yarn.lock",
-    ":(exclude)pnpm-lock.yaml",
-    ":(exclude)Cargo.lock",
-    ":(exclude)node_modules/**",
-];
-
 impl GitEntity {
+    /// Raw diff text of the entity, whichever kind it is.
+    pub fn diff(&self) -> &str {
+        match self {
+            GitEntity::Commit(commit) => &commit.diff,
+            GitEntity::Diff(
+                Diff::WorkingTree { diff, .. }
+                | Diff::CommitsRange { diff, .. }
+                | Diff::Patch { diff },
+            ) => diff,
+        }
+    }
+
+    /// Leave `paths` out of the diff, e.g. files too large to be worth sending.
+    fn diff_mut(&mut self) -> &mut String {
+        match self {
+            GitEntity::Commit(commit) => &mut commit.diff,
+            GitEntity::Diff(
+                Diff::WorkingTree { diff, .. }
+                | Diff::CommitsRange { diff, .. }
+                | Diff::Patch { diff },
+            ) => diff,
+        }
+    }
+
+    pub fn exclude_files(&mut self, paths: &[String]) {
+        let diff = self.diff_mut();
+        let kept: String = diff_files(diff)
+            .into_iter()
+            .filter(|(path, _)| !paths.iter().any(|p| p == path))
+            .map(|(_, part)| part)
+            .collect();
+        *diff = kept;
+    }
+
+    /// Cut the diff down to about `max_tokens` at file and hunk boundaries, returning the
+    /// files left out entirely or in part.
+    pub fn fit_to(&mut self, max_tokens: usize) -> Vec<String> {
+        let diff = self.diff_mut();
+        let (fitted, omitted) = fit_diff(diff, max_tokens);
+        *diff = fitted;
+        omitted
+    }
+
     pub fn format_static_details(&self, provider: &LumenProvider) -> String {
         match self {
             GitEntity::Commit(commit) => formatdoc! {"
@@ -50,6 +85,36 @@ impl GitEntity {
                 `{from}` -> `{to}`
                 # Provider: {provider}
             "},
+            GitEntity::Diff(Diff::Patch { .. }) => formatdoc! {"
+                # Entity: Patch
+                # Provider: {provider}"
+            },
+        }
+    }
+
+    /// What the entity is, as plain fields for `--format json`.
+    pub fn to_json(&self) -> Value {
+        match self {
+            GitEntity::Commit(commit) => json!({
+                "kind": "commit",
+                "sha": commit.full_hash,
+                "author": commit.author_name,
+                "email": commit.author_email,
+                "date": commit.date,
+                "message": commit.message,
+            }),
+            GitEntity::Diff(Diff::WorkingTree { staged, .. }) => json!({
+                "kind": "working_tree",
+                "staged": staged,
+            }),
+            GitEntity::Diff(Diff::CommitsRange { from, to, .. }) => json!({
+                "kind": "range",
+                "from": from,
+                "to": to,
+            }),
+            GitEntity::Diff(Diff::Patch { .. }) => json!({
+                "kind": "patch",
+            }),
         }
     }
 }
@@ -71,3 +136,32 @@ impl AsRef<Diff> for GitEntity {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFF: &str = "diff --git a/Cargo.lock b/Cargo.lock\n\
+        --- a/Cargo.lock\n\
+        +++ b/Cargo.lock\n\
+        @@ -1 +1 @@\n\
+        -old\n\
+        +new\n\
+        diff --git a/src/main.rs b/src/main.rs\n\
+        --- a/src/main.rs\n\
+        +++ b/src/main.rs\n\
+        @@ -1 +1 @@\n\
+        -a\n\
+        +b\n";
+
+    #[test]
+    fn test_exclude_files() {
+        let mut entity = GitEntity::Diff(Diff::WorkingTree {
+            staged: true,
+            diff: DIFF.to_string(),
+        });
+        entity.exclude_files(&["Cargo.lock".to_string()]);
+        assert!(entity.diff().starts_with("diff --git a/src/main.rs"));
+        assert!(!entity.diff().contains("Cargo.lock"));
+    }
+}
@@ -0,0 +1,71 @@
+//! Shell hooks that generated output is piped through before it's used, such as a commit
+//! message linter or a note-taking tool. A hook that exits with an error rejects the
+//! output, which is then generated again.
+
+use std::io::Write;
+use std::process::Stdio;
+
+use crate::error::LumenError;
+use crate::shell;
+
+/// How many times output is generated before giving up when the hook keeps rejecting it
+pub const MAX_HOOK_ATTEMPTS: usize = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum HookOutcome {
+    Accepted,
+    /// The hook's explanation: its stderr, else its stdout, else the exit status
+    Rejected(String),
+}
+
+/// Run `command` in the shell with `output` on its stdin.
+pub fn run_hook(command: &str, output: &str) -> Result<HookOutcome, LumenError> {
+    let mut child = shell::command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| LumenError::CommandError(format!("failed to run hook `{command}`: {e}")))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // A hook that exits without reading all of its input closes the pipe early,
+        // which isn't an error in itself
+        let _ = stdin.write_all(output.as_bytes());
+    }
+    let result = child.wait_with_output()?;
+
+    if result.status.success() {
+        return Ok(HookOutcome::Accepted);
+    }
+    let stderr = String::from_utf8_lossy(&result.stderr).trim().to_string();
+    let stdout = String::from_utf8_lossy(&result.stdout).trim().to_string();
+    let reason = [stderr, stdout]
+        .into_iter()
+        .find(|text| !text.is_empty())
+        .unwrap_or_else(|| format!("hook `{command}` exited with {}", result.status));
+    Ok(HookOutcome::Rejected(reason))
+}
+
+#[cfg(all(test, target_family = "unix"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hook_sees_output_and_can_reject_it() {
+        assert_eq!(
+            run_hook("grep -q '^feat'", "feat: add hooks\n").unwrap(),
+            HookOutcome::Accepted
+        );
+        assert_eq!(
+            run_hook(
+                "grep -q '^feat' || { echo 'type must be feat' >&2; exit 1; }",
+                "wip\n"
+            )
+            .unwrap(),
+            HookOutcome::Rejected("type must be feat".to_string())
+        );
+        assert!(matches!(
+            run_hook("exit 3", "").unwrap(),
+            HookOutcome::Rejected(reason) if reason.contains("exit")
+        ));
+    }
+}
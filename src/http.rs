@@ -0,0 +1,76 @@
+//! The HTTP client that requests to providers, Ollama and webhooks go through, set up once
+//! from the `http` section of the config.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::config::configuration::HttpConfig;
+use crate::error::LumenError;
+
+static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+
+/// Set up the shared client from `config`. Only the first call counts.
+pub fn init(config: &HttpConfig) -> Result<(), LumenError> {
+    let client = build(config)?;
+    let _ = CLIENT.set(client);
+    Ok(())
+}
+
+/// The shared client, or reqwest's defaults (which honor the proxy environment variables)
+/// before [`init`].
+pub fn client() -> reqwest::Client {
+    CLIENT.get().cloned().unwrap_or_default()
+}
+
+fn build(config: &HttpConfig) -> Result<reqwest::Client, LumenError> {
+    let invalid = |what: &str, e: &dyn std::fmt::Display| {
+        LumenError::InvalidConfiguration(format!("http.{what}: {e}"))
+    };
+    let mut builder = reqwest::Client::builder();
+    if let Some(proxy) = &config.proxy {
+        // `NO_PROXY` still applies, so internal hosts can be reached directly
+        let proxy = reqwest::Proxy::all(proxy)
+            .map_err(|e| invalid("proxy", &e))?
+            .no_proxy(reqwest::NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+    if let Some(path) = &config.ca_cert {
+        let pem = std::fs::read(path)
+            .map_err(|e| invalid("ca_cert", &format!("{}: {e}", path.display())))?;
+        let certificates =
+            reqwest::Certificate::from_pem_bundle(&pem).map_err(|e| invalid("ca_cert", &e))?;
+        if certificates.is_empty() {
+            return Err(invalid(
+                "ca_cert",
+                &format!("{} has no PEM certificates", path.display()),
+            ));
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    if let Some(seconds) = config.timeout {
+        builder = builder.timeout(Duration::from_secs(seconds));
+    }
+    builder.build().map_err(|e| invalid("proxy", &e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bad_settings_are_reported() {
+        let config = |json| serde_json::from_str::<HttpConfig>(json).unwrap();
+        assert!(build(&config(
+            r#"{"proxy": "http://proxy.corp:3128", "timeout": 30}"#
+        ))
+        .is_ok());
+
+        let error = build(&config(r#"{"proxy": "not a url"}"#)).unwrap_err();
+        assert!(error.to_string().contains("http.proxy"));
+
+        let error = build(&config(r#"{"ca_cert": "/nonexistent/ca.pem"}"#)).unwrap_err();
+        assert!(error.to_string().contains("/nonexistent/ca.pem"));
+    }
+}
@@ -0,0 +1,145 @@
+//! Read the repository in process through libgit2, rather than running `git` for every
+//! question. Each function answers `None` when libgit2 can't, say outside a repository or for
+//! a revision it doesn't understand, and callers then run `git` instead.
+//!
+//! Diffs meant for prompts still come from `git`, since libgit2 doesn't know the `:(exclude)`
+//! pathspecs that leave lockfiles out of them.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use git2::{ObjectType, Repository, StatusOptions};
+
+thread_local! {
+    /// A `Repository` can't be shared between threads, so each opens its own, once
+    static REPO: Option<Repository> = Repository::open_from_env().ok();
+}
+
+fn with_repo<T>(f: impl FnOnce(&Repository) -> Option<T>) -> Option<T> {
+    REPO.with(|repo| repo.as_ref().and_then(f))
+}
+
+/// The checked out branch, or `HEAD` when it's detached, like `git rev-parse --abbrev-ref HEAD`.
+pub fn current_branch() -> Option<String> {
+    with_repo(|repo| {
+        let head = repo.head().ok()?;
+        if repo.head_detached().ok()? {
+            return Some("HEAD".to_string());
+        }
+        head.shorthand().map(String::from)
+    })
+}
+
+/// The best common ancestor of two revisions, `Some(None)` when they share no history.
+pub fn merge_base(from: &str, to: &str) -> Option<Option<String>> {
+    with_repo(|repo| {
+        let commit = |revision: &str| repo.revparse_single(revision).ok()?.peel_to_commit().ok();
+        let (from, to) = (commit(from)?, commit(to)?);
+        let base = repo.merge_base(from.id(), to.id()).ok();
+        Some(base.map(|oid| oid.to_string()))
+    })
+}
+
+/// The contents of `path` at `revision`, `Some(None)` when it isn't there.
+pub fn blob(revision: &str, path: &str) -> Option<Option<Vec<u8>>> {
+    with_repo(|repo| {
+        let tree = repo.revparse_single(revision).ok()?.peel_to_tree().ok()?;
+        let Ok(entry) = tree.get_path(Path::new(path)) else {
+            return Some(None);
+        };
+        let blob = entry.to_object(repo).ok()?.into_blob().ok();
+        Some(blob.map(|blob| blob.content().to_vec()))
+    })
+}
+
+/// The paths that differ between the trees of two revisions, like `git diff --name-only`.
+pub fn changed_between(from: &str, to: &str) -> Option<Vec<String>> {
+    with_repo(|repo| {
+        let tree = |revision: &str| repo.revparse_single(revision).ok()?.peel_to_tree().ok();
+        let (old, new) = (tree(from)?, tree(to)?);
+        let diff = repo.diff_tree_to_tree(Some(&old), Some(&new), None).ok()?;
+        Some(
+            diff.deltas()
+                .filter_map(|delta| delta.new_file().path().or(delta.old_file().path()))
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect(),
+        )
+    })
+}
+
+/// Files with staged, unstaged or untracked changes, leaving out ignored ones.
+pub fn working_tree_changes() -> Option<Vec<String>> {
+    with_repo(|repo| {
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .recurse_untracked_dirs(true)
+            .include_ignored(false);
+        let statuses = repo.statuses(Some(&mut options)).ok()?;
+        Some(
+            statuses
+                .iter()
+                .filter_map(|entry| entry.path().map(String::from))
+                .collect(),
+        )
+    })
+}
+
+/// Which of `paths`, relative to the current directory, the ignore files leave out.
+pub fn ignored(paths: &HashSet<String>) -> Option<HashSet<String>> {
+    with_repo(|repo| {
+        let workdir = repo.workdir()?;
+        let cwd = std::env::current_dir().ok()?;
+        Some(
+            paths
+                .iter()
+                .filter(|path| {
+                    let full = cwd.join(path);
+                    let relative = full.strip_prefix(workdir).unwrap_or(Path::new(path));
+                    repo.is_path_ignored(relative).unwrap_or(false)
+                })
+                .cloned()
+                .collect(),
+        )
+    })
+}
+
+/// Whether `revision` names a commit object, like `git cat-file -t` answering `commit`.
+pub fn is_commit(revision: &str) -> Option<bool> {
+    with_repo(|repo| {
+        let object = repo.revparse_single(revision).ok()?;
+        Some(object.kind() == Some(ObjectType::Commit))
+    })
+}
+
+/// What `lumen` shows of a commit besides its diff.
+pub struct CommitInfo {
+    pub full_hash: String,
+    pub message: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// When it was committed, in the committer's time zone
+    pub date: String,
+}
+
+pub fn commit_info(revision: &str) -> Option<CommitInfo> {
+    with_repo(|repo| {
+        let commit = repo.revparse_single(revision).ok()?.peel_to_commit().ok()?;
+        let author = commit.author();
+        let time = commit.committer().when();
+        let offset = chrono::FixedOffset::east_opt(time.offset_minutes() * 60)?;
+        let date = chrono::DateTime::from_timestamp(time.seconds(), 0)?
+            .with_timezone(&offset)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        Some(CommitInfo {
+            full_hash: commit.id().to_string(),
+            message: String::from_utf8_lossy(commit.message_bytes())
+                .trim_end_matches('\n')
+                .to_string(),
+            author_name: String::from_utf8_lossy(author.name_bytes()).into_owned(),
+            author_email: String::from_utf8_lossy(author.email_bytes()).into_owned(),
+            date,
+        })
+    })
+}
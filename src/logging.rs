@@ -0,0 +1,153 @@
+//! Debug logging for `--verbose` and `LUMEN_LOG`.
+//!
+//! Nothing is logged by default. `--verbose` logs lumen's own debug events: the git commands
+//! it runs, provider requests with their timing and token counts. `LUMEN_LOG` takes a filter
+//! in `RUST_LOG` syntax instead, e.g. `lumen=trace` to also log full prompts and responses.
+//! Logs go to stderr, or are appended to `--log-file`, which keeps them out of the diff viewer.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::Mutex;
+use std::time::Instant;
+
+use tracing_subscriber::EnvFilter;
+
+use crate::error::LumenError;
+
+/// Start logging as `--verbose`, `--log-file` and `LUMEN_LOG` ask.
+pub fn init(verbose: bool, file: Option<&Path>) -> Result<(), LumenError> {
+    let filter = match std::env::var("LUMEN_LOG") {
+        Ok(directives) => EnvFilter::try_new(&directives)
+            .map_err(|e| LumenError::InvalidArguments(format!("LUMEN_LOG `{directives}`: {e}")))?,
+        Err(_) if verbose => EnvFilter::new("lumen=debug"),
+        Err(_) => return Ok(()),
+    };
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_target(false);
+    match file {
+        Some(path) => {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            builder
+                .with_ansi(false)
+                .with_writer(Mutex::new(file))
+                .init();
+        }
+        None => builder.with_writer(io::stderr).init(),
+    }
+    Ok(())
+}
+
+/// `key` with all but its last four characters hidden, for logging which key is in use.
+pub fn mask_key(key: &str) -> String {
+    let count = key.chars().count();
+    if count <= 8 {
+        return "*".repeat(count);
+    }
+    let tail: String = key.chars().skip(count - 4).collect();
+    format!("{}{tail}", "*".repeat(count - 4))
+}
+
+/// Run `command` for its output like `Command::output`, logging the command line, how long it
+/// took and how it exited.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    logged(command, Command::output)
+}
+
+/// [`output`] with `input` written to the command's stdin.
+pub fn output_with_input(command: &mut Command, input: &[u8]) -> io::Result<Output> {
+    logged(command, |command| {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(input)?;
+        child.wait_with_output()
+    })
+}
+
+/// Run `command` like `Command::status`, for commands that take over the terminal, logged
+/// like [`output`].
+pub fn status(command: &mut Command) -> io::Result<ExitStatus> {
+    logged(command, Command::status)
+}
+
+/// Start `command` like `Command::spawn`, for output that's read as it comes, logging the
+/// command line when it starts.
+pub fn spawn(command: &mut Command) -> io::Result<Child> {
+    let line = command_line(command);
+    let child = command.spawn();
+    match &child {
+        Ok(child) => tracing::debug!(command = %line, pid = child.id(), "started"),
+        Err(error) => tracing::debug!(command = %line, %error, "failed to run"),
+    }
+    child
+}
+
+fn logged<T: Exited>(
+    command: &mut Command,
+    run: impl FnOnce(&mut Command) -> io::Result<T>,
+) -> io::Result<T> {
+    let line = command_line(command);
+    let started = Instant::now();
+    let result = run(command);
+    let elapsed_ms = started.elapsed().as_millis() as u64;
+    match &result {
+        Ok(done) => {
+            tracing::debug!(command = %line, elapsed_ms, status = %done.exit_status(), "ran")
+        }
+        Err(error) => tracing::debug!(command = %line, elapsed_ms, %error, "failed to run"),
+    }
+    result
+}
+
+/// What a finished command is logged with
+trait Exited {
+    fn exit_status(&self) -> ExitStatus;
+}
+
+impl Exited for Output {
+    fn exit_status(&self) -> ExitStatus {
+        self.status
+    }
+}
+
+impl Exited for ExitStatus {
+    fn exit_status(&self) -> ExitStatus {
+        *self
+    }
+}
+
+fn command_line(command: &Command) -> String {
+    std::iter::once(command.get_program())
+        .chain(command.get_args())
+        .map(|part| part.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_all_but_the_end_of_a_key() {
+        assert_eq!(mask_key("sk-abcdefgh1234"), "***********1234");
+        assert_eq!(mask_key("short"), "*****");
+        assert_eq!(mask_key(""), "");
+    }
+
+    #[test]
+    fn command_line_joins_program_and_args() {
+        let mut command = Command::new("git");
+        command.args(["diff", "--staged"]);
+        assert_eq!(command_line(&command), "git diff --staged");
+    }
+}
@@ -1,118 +1,839 @@
-use clap::Parser;
+use clap::{CommandFactory, Parser};
+use command::diff::theme::ThemeMode;
 use command::LumenCommand;
 use commit_reference::CommitReference;
-use config::cli::{Cli, Commands};
+use config::cli::{CacheAction, Cli, Commands, DiffArgs, HookAction, SnapshotAction, ThemeAction};
 use config::LumenConfig;
 use error::LumenError;
 use git_entity::{commit::Commit, diff::Diff, GitEntity};
+use lumen_core::git::RangeOptions;
 use std::io::Read;
 use std::process;
+use std::sync::Arc;
+use std::time::Duration;
 
-mod ai_prompt;
+mod clipboard;
 mod command;
 mod commit_reference;
 mod config;
 mod error;
 mod git_entity;
+mod hooks;
+mod http;
+#[cfg(feature = "libgit2")]
+mod libgit;
+mod logging;
+mod permalink;
+mod preflight;
+mod privacy;
 mod provider;
+mod shell;
+mod vcs;
+mod webhook;
 
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
         eprintln!("\x1b[91m\rerror:\x1b[0m {e}");
-        process::exit(1);
+        process::exit(e.exit_code());
     }
 }
 
 async fn run() -> Result<(), LumenError> {
+    // A completion script asking what to offer; answers and exits
+    clap_complete::CompleteEnv::with_factory(Cli::command).complete();
     let cli = Cli::parse();
+    command::diff::terminal::install_panic_hook(cli.backtrace);
+    logging::init(cli.verbose, cli.log_file.as_deref())?;
+
+    // A missing repository or gh is reported up front, not by whatever fails first without it
+    preflight::check(&cli.command)?;
+
+    // Doctor reports config errors itself instead of failing before it can run
+    if let Commands::Doctor = cli.command {
+        return command::doctor::DoctorCommand::execute(&cli).await;
+    }
+
+    // Clearing the cache needs no provider, so it works even with a broken config
+    if let Commands::Cache {
+        action: CacheAction::Clear,
+    } = cli.command
+    {
+        let count = provider::cache::clear()?;
+        println!("Cleared cached responses for {count} commit(s).");
+        return Ok(());
+    }
+
+    // Completions and man pages only describe the command line
+    if let Commands::Completions { shell } = cli.command {
+        return command::completions::print_completions(shell);
+    }
+    if let Commands::Man { dir } = &cli.command {
+        return command::completions::print_man(dir.as_deref());
+    }
+
+    // The usage log is local, so reading it needs no provider either
+    if let Commands::Usage { days } = cli.command {
+        return command::usage::UsageCommand { days }.execute();
+    }
+
+    // Without --ai, checking is local heuristics only
+    if let Commands::Check {
+        ai: false,
+        severity,
+    } = cli.command
+    {
+        return command::check::CheckCommand { severity }
+            .execute(None)
+            .await;
+    }
+
+    // Snapshots are plain git objects and refs
+    if let Commands::Snapshot { action } = cli.command {
+        let action = match action {
+            SnapshotAction::Save { name, force } => {
+                command::snapshot::SnapshotAction::Save { name, force }
+            }
+            SnapshotAction::List => command::snapshot::SnapshotAction::List,
+            SnapshotAction::Delete { name } => command::snapshot::SnapshotAction::Delete { name },
+        };
+        return command::snapshot::SnapshotCommand { action }.execute();
+    }
+
+    // Installing the hook only writes a script; the hook itself runs `lumen draft`
+    if let Commands::Hook { action } = cli.command {
+        let action = match action {
+            HookAction::Install { force } => command::git_hook::HookAction::Install { force },
+            HookAction::Uninstall => command::git_hook::HookAction::Uninstall,
+        };
+        return command::git_hook::HookCommand { action }.execute();
+    }
+
+    // Themes are files of their own, picked in the config by name
+    if let Commands::Theme { action } = cli.command {
+        let ThemeAction::Edit { name, from } = action;
+        return command::theme_editor::run(&name, from.as_deref());
+    }
+
+    // Configure is how a broken config gets fixed, so it must not need a valid one
+    if let Commands::Configure { wizard } = cli.command {
+        // ... but its test request should still go through the configured proxy
+        if let Ok(config) = LumenConfig::build(&cli) {
+            let _ = http::init(&config.http);
+        }
+        return command::configure::ConfigureCommand { wizard }
+            .execute()
+            .await;
+    }
+
+    // Applying suggestions is local too; the config only supplies preview settings
+    if let Commands::Apply {
+        ref input,
+        yes,
+        no_preview,
+    } = cli.command
+    {
+        return command::apply::ApplyCommand {
+            input: input.clone(),
+            yes,
+            no_preview,
+            settings: LumenConfig::build(&cli)
+                .map(|config| config.diff)
+                .unwrap_or_default(),
+        }
+        .execute();
+    }
+
+    // git runs the difftool with versions it has written out; only the view settings matter
+    if let Commands::Difftool {
+        ref local,
+        ref remote,
+        ref base,
+        ref merged,
+    } = cli.command
+    {
+        let settings = LumenConfig::build(&cli)
+            .map(|config| config.diff)
+            .unwrap_or_default();
+        let result = match (base, merged) {
+            (Some(base), Some(merged)) => command::diff::run_merge_tool(
+                merged,
+                local.clone(),
+                base.clone(),
+                remote.clone(),
+                settings,
+            ),
+            _ => {
+                let name = merged.as_deref().map(|path| path.to_string_lossy());
+                command::diff::run_path_diff(local, remote, name.as_deref(), None, settings)
+            }
+        };
+        return result.map_err(LumenError::CommandError);
+    }
 
     let config = match LumenConfig::build(&cli) {
         Ok(config) => config,
         Err(e) => return Err(e),
     };
+    http::init(&config.http)?;
 
-    let provider =
-        provider::LumenProvider::new(config.provider, config.api_key, config.model)?;
+    // Without --ai, commit messages are checked with local rules only
+    if let Commands::LintMsg {
+        ref message,
+        ref file,
+        ref range,
+        ai: false,
+        strict,
+    } = cli.command
+    {
+        return command::lint_msg::LintMsgCommand {
+            source: message_source(message, file, range)?,
+            draft_config: config.draft,
+            strict,
+        }
+        .execute(None)
+        .await;
+    }
+
+    // The viewer is local; its AI features only report a missing provider when used
+    if let Commands::Diff(args) = cli.command {
+        let DiffArgs {
+            reference,
+            pr,
+            last,
+            file,
+            watch,
+            watch_path,
+            review_flow,
+            read_only,
+            record,
+            replay,
+            risk,
+            snapshot,
+            ignore_whitespace,
+            theme,
+            symbol,
+            files,
+            summarize_every,
+            summary_log,
+            file_summaries,
+            export_html,
+            export_patch,
+        } = *args;
+        let mut settings = config.diff;
+        if ignore_whitespace {
+            settings.ignore_whitespace = Some(true);
+        }
+        if let Some(name) = theme {
+            settings.custom_theme = None;
+            match name.as_str() {
+                "dark" => settings.theme = Some(ThemeMode::Dark),
+                "light" => settings.theme = Some(ThemeMode::Light),
+                _ => settings.custom_theme = Some(name),
+            }
+        }
+        if let Some(name) = symbol {
+            command::diff::run_symbol_diff(&name, reference.as_ref(), settings)
+                .map_err(LumenError::CommandError)?;
+            return Ok(());
+        }
+        if let Some([old, new]) = files.as_deref() {
+            let filter = file
+                .map(|terms| {
+                    command::diff::FileFilter::from_terms(terms.iter().map(String::as_str))
+                })
+                .transpose()
+                .map_err(LumenError::CommandError)?;
+            command::diff::run_path_diff(old, new, None, filter.as_ref(), settings)
+                .map_err(LumenError::CommandError)?;
+            return Ok(());
+        }
+        let link = reference.as_ref().and_then(linked_target).transpose()?;
+        let (reference, focus) = match link {
+            Some(target) => (
+                Some(CommitReference::Single(target.commit)),
+                target.file.map(|file| command::diff::Focus {
+                    file,
+                    line: target.line,
+                }),
+            ),
+            None => (reference, None),
+        };
+        let bare = reference.is_none()
+            && pr.is_none()
+            && snapshot.is_none()
+            && file.is_none()
+            && replay.is_none()
+            && export_html.is_none()
+            && export_patch.is_none();
+        let previous = if last {
+            Some(command::diff::last::load().ok_or_else(|| {
+                LumenError::CommandError(
+                    "no earlier comparison in this repository; open one with `lumen diff <ref>` or `--pr` first"
+                        .to_string(),
+                )
+            })?)
+        } else if bare {
+            command::diff::range_picker::pick()
+        } else {
+            None
+        };
+        let (reference, pr) = match previous {
+            Some(previous) => (previous.reference(), previous.pr),
+            None => (reference, pr),
+        };
+        command::diff::last::save(reference.as_ref(), pr.as_deref());
+        // jj keeps the working copy in a commit of its own, shown like any other commit
+        let vcs = vcs::Vcs::detect();
+        let reference = match reference {
+            None if vcs == vcs::Vcs::Jujutsu && pr.is_none() && snapshot.is_none() => {
+                Some(CommitReference::Single(vcs.git_revision("@")))
+            }
+            reference => reference
+                .map(|reference| reference.map_revisions(|revision| vcs.git_revision(revision))),
+        };
+        let snapshot = snapshot
+            .map(|name| command::snapshot::resolve(&name))
+            .transpose()
+            .map_err(LumenError::CommandError)?;
+        let file = file
+            .map(|terms| command::diff::FileFilter::from_terms(terms.iter().map(String::as_str)))
+            .transpose()
+            .map_err(LumenError::CommandError)?;
+        let provider = provider::LumenProvider::new(
+            config.provider,
+            config.api_key,
+            config.model,
+            config.api_base_url,
+        )
+        .map(|provider| Arc::new(provider.with_ai_config(config.ai)));
+        let options = command::diff::DiffOptions {
+            reference,
+            pr,
+            file,
+            watch,
+            watch_paths: watch_path,
+            review_flow,
+            // A replay must not stage, edit or post anything the recording did
+            read_only: read_only || replay.is_some(),
+            record,
+            replay,
+            risk,
+            provider: provider.as_ref().ok().cloned(),
+            provider_error: provider.err().map(|e| e.to_string()),
+            summarize_every: summarize_every.map(|minutes| Duration::from_secs(minutes * 60)),
+            summary_log,
+            file_summaries,
+            preview: false,
+            snapshot,
+            notes: Vec::new(),
+            focus,
+            settings,
+            draft: config.draft,
+        };
+        let export = match (export_html, export_patch) {
+            (Some(path), _) => Some((command::diff::ExportFormat::Html, path)),
+            (None, Some(path)) => Some((command::diff::ExportFormat::Patch, path)),
+            (None, None) => None,
+        };
+        if let Some((format, path)) = export {
+            command::diff::export_diff(&options, format, &path)
+                .map_err(LumenError::CommandError)?;
+            println!("Wrote {}", path.display());
+            return Ok(());
+        }
+        command::diff::run_diff_ui(options)?;
+        return Ok(());
+    }
+
+    let provider = provider::LumenProvider::new(
+        config.provider,
+        config.api_key,
+        config.model,
+        config.api_base_url,
+    )?
+    .with_ai_config(config.ai);
     let command = command::LumenCommand::new(provider);
 
     match cli.command {
         Commands::Explain {
             reference,
+            more,
+            from_stdin,
             staged,
             query,
             list,
+            branch,
+            patch,
+            first_parent,
+            no_merges,
+            annotate,
+            impact,
+            no_cache,
+            format,
+            copy,
+            watch,
+            paths,
         } => {
-            let git_entity = if list {
-                let sha = LumenCommand::get_sha_from_fzf()?;
-                GitEntity::Commit(Commit::new(sha)?)
-            } else {
-                match reference {
-                    Some(CommitReference::Single(input)) => {
-                        let sha = if input == "-" {
-                            read_from_stdin()?
-                        } else {
-                            input
-                        };
-                        GitEntity::Commit(Commit::new(sha)?)
-                    }
-                    Some(CommitReference::Range { from, to }) => {
-                        GitEntity::Diff(Diff::from_commits_range(&from, &to, false)?)
-                    }
-                    Some(CommitReference::TripleDots { from, to }) => {
-                        GitEntity::Diff(Diff::from_commits_range(&from, &to, true)?)
-                    }
-                    None => {
-                        // Default: show uncommitted diff
-                        GitEntity::Diff(Diff::from_working_tree(staged)?)
-                    }
+            if watch {
+                return command::explain_watch::run(command::explain_watch::WatchOptions {
+                    staged,
+                    paths,
+                    query,
+                    provider: Arc::new(command.into_provider()),
+                });
+            }
+            format.check(config.webhook.as_ref())?;
+            if from_stdin || !more.is_empty() {
+                let shas = if from_stdin {
+                    command::explain::parse_sha_list(&read_stdin()?)
+                } else {
+                    let first = match reference {
+                        Some(CommitReference::Single(sha)) => sha,
+                        _ => {
+                            return Err(LumenError::InvalidArguments(
+                                "only single commits can be explained together, not ranges"
+                                    .to_string(),
+                            ))
+                        }
+                    };
+                    std::iter::once(first).chain(more).collect()
+                };
+                if shas.is_empty() {
+                    return Err(LumenError::InvalidArguments(
+                        "no commits on stdin".to_string(),
+                    ));
+                }
+                if annotate || impact || first_parent || no_merges {
+                    return Err(LumenError::InvalidArguments(
+                        "--annotate, --impact, --first-parent and --no-merges apply to a single commit or range"
+                            .to_string(),
+                    ));
                 }
+                let commits = shas
+                    .into_iter()
+                    .map(|sha| commit_in(sha, &paths))
+                    .collect::<Result<Vec<_>, _>>()?;
+                command
+                    .execute(command::CommandType::ExplainCommits {
+                        commits,
+                        query,
+                        cache: !no_cache,
+                        format,
+                        copy,
+                        webhook: config.webhook,
+                    })
+                    .await?;
+                return Ok(());
+            }
+            let range_options = RangeOptions {
+                first_parent,
+                no_merges,
+            };
+            if (first_parent || no_merges)
+                && branch.is_none()
+                && !matches!(
+                    reference,
+                    Some(CommitReference::Range { .. } | CommitReference::TripleDots { .. })
+                )
+            {
+                return Err(LumenError::InvalidArguments(
+                    "--first-parent and --no-merges only apply to commit ranges and --branch"
+                        .to_string(),
+                ));
+            }
+
+            let link = reference.as_ref().and_then(linked_target).transpose()?;
+            let mut commits = None;
+            let git_entity = if let Some(base) = branch {
+                let base = if base.is_empty() {
+                    command::pr::default_base().map_err(|_| {
+                        LumenError::InvalidArguments(
+                            "couldn't find the default branch; pass the base with --branch BASE"
+                                .to_string(),
+                        )
+                    })?
+                } else {
+                    base
+                };
+                commits = Some(command::explain::branch_log(&base)?);
+                GitEntity::Diff(Diff::from_commits_range(
+                    &base,
+                    "HEAD",
+                    true,
+                    range_options,
+                    &paths,
+                )?)
+            } else if let Some(path) = patch {
+                let text = if path.as_os_str() == "-" {
+                    read_stdin()?
+                } else {
+                    std::fs::read_to_string(&path)?
+                };
+                GitEntity::Diff(Diff::from_patch(text)?)
+            } else if list {
+                let sha = LumenCommand::pick_commit()?;
+                GitEntity::Commit(commit_in(sha, &paths)?)
+            } else if let Some(target) = &link {
+                GitEntity::Commit(commit_in(target.commit.clone(), &paths)?)
+            } else {
+                git_entity_for(reference, staged, range_options, &paths)?
             };
 
             command
-                .execute(command::CommandType::Explain { git_entity, query })
+                .execute(command::CommandType::Explain {
+                    git_entity,
+                    // A link to a line is a question about that line
+                    query: query.or_else(|| {
+                        let target = link?;
+                        Some(format!(
+                            "What does this change do at line {} of {}, and why was it made?",
+                            target.line?, target.file?
+                        ))
+                    }),
+                    commits,
+                    annotate,
+                    impact,
+                    cache: !no_cache,
+                    format,
+                    copy,
+                    hook: config.hooks.get("explain").cloned(),
+                    webhook: config.webhook,
+                })
                 .await?;
         }
         Commands::List => {
             eprintln!("Warning: 'lumen list' is deprecated. Use 'lumen explain --list' instead.");
             command.execute(command::CommandType::List).await?
         }
-        Commands::Draft { context } => {
+        Commands::Check { severity, .. } => {
+            command::check::CheckCommand { severity }
+                .execute(Some(&command.into_provider()))
+                .await?;
+        }
+        Commands::LintMsg {
+            message,
+            file,
+            range,
+            strict,
+            ..
+        } => {
+            command::lint_msg::LintMsgCommand {
+                source: message_source(&message, &file, &range)?,
+                draft_config: config.draft,
+                strict,
+            }
+            .execute(Some(&command.into_provider()))
+            .await?;
+        }
+        Commands::Log { revisions, all } => {
+            command::log::run(command::log::LogOptions {
+                revisions,
+                all,
+                provider: Arc::new(command.into_provider()),
+                settings: config.diff,
+                hook: config.hooks.get("explain").cloned(),
+            })
+            .await?;
+        }
+        Commands::Draft {
+            context,
+            subject_only,
+            commit,
+            edit,
+            count,
+            format,
+            squash,
+            hook,
+            amend,
+            copy,
+        } => {
+            format.check(config.webhook.as_ref())?;
             command
-                .execute(command::CommandType::Draft(context, config.draft))
+                .execute(command::CommandType::Draft {
+                    context,
+                    draft_config: config.draft,
+                    subject_only,
+                    commit,
+                    edit,
+                    count: count.into(),
+                    format,
+                    squash,
+                    amend,
+                    copy,
+                    message_file: hook,
+                    hook: config.hooks.get("draft").cloned(),
+                    webhook: config.webhook,
+                })
                 .await?
         }
-        Commands::Operate { query } => {
+        Commands::Split { context, dry_run } => {
+            command::split::SplitCommand {
+                context,
+                dry_run,
+                draft_config: config.draft,
+                hook: config.hooks.get("draft").cloned(),
+            }
+            .execute(&command.into_provider())
+            .await?;
+        }
+        Commands::Reword { range, context } => {
+            let CommitReference::Range { from, to } = range else {
+                return Err(LumenError::InvalidArguments(
+                    "reword takes a range of commits, like main..HEAD".to_string(),
+                ));
+            };
+            command::reword::RewordCommand {
+                from,
+                to,
+                context,
+                draft_config: config.draft,
+                hook: config.hooks.get("draft").cloned(),
+            }
+            .execute(&command.into_provider())
+            .await?;
+        }
+        Commands::Pr {
+            base,
+            context,
+            create,
+            format,
+        } => {
+            format.check(config.webhook.as_ref())?;
             command
-                .execute(command::CommandType::Operate { query })
+                .execute(command::CommandType::Pr {
+                    base,
+                    context,
+                    create,
+                    format,
+                    webhook: config.webhook,
+                })
                 .await?;
         }
-        Commands::Diff {
+        Commands::Operate {
+            query,
+            dry_run,
+            allow_destructive,
+        } => {
+            command
+                .execute(command::CommandType::Operate {
+                    query,
+                    dry_run,
+                    policy: config.operate,
+                    allow_destructive,
+                })
+                .await?;
+        }
+        Commands::Review {
             reference,
-            pr,
-            file,
-            watch,
+            staged,
+            tui,
+            format,
+            quiet,
+            max_findings,
+            severity,
+            timeout,
+        } => {
+            format.check(config.webhook.as_ref())?;
+            let git_entity =
+                git_entity_for(reference.clone(), staged, RangeOptions::default(), &[])?;
+            if tui && matches!(git_entity, GitEntity::Diff(Diff::Patch { .. })) {
+                return Err(LumenError::InvalidArguments(
+                    "--tui can't show a patch read from stdin".to_string(),
+                ));
+            }
+            // A SHA read from stdin only resolves here, so hand the viewer the commit itself
+            let reference = match &git_entity {
+                GitEntity::Commit(commit) => {
+                    Some(CommitReference::Single(commit.full_hash.clone()))
+                }
+                GitEntity::Diff(_) => reference,
+            };
+            command
+                .execute(command::CommandType::Review {
+                    git_entity,
+                    reference,
+                    tui,
+                    format,
+                    settings: config.diff,
+                    gate: quiet.then_some(command::review::Gate {
+                        max_findings,
+                        severity,
+                        timeout: Duration::from_secs(timeout),
+                    }),
+                    webhook: config.webhook,
+                    ignore: config.review,
+                })
+                .await?;
+        }
+        Commands::Serve { .. } => {
+            command
+                .execute(command::CommandType::Serve {
+                    draft_config: config.draft,
+                })
+                .await?;
+        }
+        Commands::Doctor
+        | Commands::Cache { .. }
+        | Commands::Usage { .. }
+        | Commands::Completions { .. }
+        | Commands::Man { .. }
+        | Commands::Apply { .. }
+        | Commands::Difftool { .. }
+        | Commands::Configure { .. }
+        | Commands::Snapshot { .. }
+        | Commands::Hook { .. }
+        | Commands::Theme { .. } => {
+            unreachable!("handled before config is built")
+        }
+        Commands::Diff(_) => unreachable!("handled before the provider is built"),
+        Commands::CherryPick { shas } => {
+            command
+                .execute(command::CommandType::CherryPick { shas })
+                .await?;
+        }
+        Commands::Digest {
+            branch,
+            since,
+            author,
+            all,
+            format,
+            post,
         } => {
-            let options = command::diff::DiffOptions {
-                reference,
-                pr,
-                file,
-                watch,
+            let format = if post {
+                command::output::Format::webhook()
+            } else {
+                format
             };
-            command::diff::run_diff_ui(options)?;
+            format.check(config.webhook.as_ref())?;
+            command
+                .execute(command::CommandType::Digest {
+                    since,
+                    author,
+                    branch,
+                    all,
+                    format,
+                    webhook: config.webhook,
+                })
+                .await?;
         }
-        Commands::Configure => {
-            command::configure::ConfigureCommand::execute()?;
+        Commands::Changelog {
+            range,
+            release,
+            first_parent,
+            no_merges,
+            template,
+            format,
+            prepend,
+        } => {
+            format.check(config.webhook.as_ref())?;
+            let (from, to) = match range.split_once("..") {
+                Some((from, to)) => (from, to.trim_start_matches('.')),
+                None => (range.as_str(), ""),
+            };
+            let to = if to.is_empty() { "HEAD" } else { to };
+            for reference in [from, to] {
+                Commit::is_valid_commit(reference)?;
+            }
+            let version = release.unwrap_or_else(|| match to {
+                "HEAD" => "Unreleased".to_string(),
+                to => to.to_string(),
+            });
+            let template = template
+                .map(|path| {
+                    std::fs::read_to_string(&path).map_err(|e| {
+                        LumenError::InvalidArguments(format!(
+                            "can't read template {}: {e}",
+                            path.display()
+                        ))
+                    })
+                })
+                .transpose()?;
+            command
+                .execute(command::CommandType::Changelog {
+                    from: from.to_string(),
+                    to: to.to_string(),
+                    version,
+                    range_options: RangeOptions {
+                        first_parent,
+                        no_merges,
+                    },
+                    template,
+                    format,
+                    prepend,
+                    webhook: config.webhook,
+                })
+                .await?;
         }
     }
 
     Ok(())
 }
 
-fn read_from_stdin() -> Result<String, LumenError> {
+/// Where a GitHub commit or file link given as the reference leads, if it is one.
+fn linked_target(reference: &CommitReference) -> Option<Result<permalink::Target, LumenError>> {
+    // Written back out, in case a `..` in the link was taken for a range
+    let link = permalink::Permalink::parse(&reference.to_string())?;
+    Some(link.resolve().map_err(LumenError::CommandError))
+}
+
+/// The commit, range or uncommitted diff (the default) a reference names.
+fn git_entity_for(
+    reference: Option<CommitReference>,
+    staged: bool,
+    range_options: RangeOptions,
+    paths: &[String],
+) -> Result<GitEntity, LumenError> {
+    Ok(match reference {
+        Some(CommitReference::Single(input)) if input == "-" => {
+            // Either a SHA or a whole diff piped in
+            let text = read_stdin()?;
+            if Diff::is_patch(&text) {
+                GitEntity::Diff(Diff::from_patch(text)?)
+            } else {
+                eprintln!("Reading commit SHA from stdin: '{}'", text.trim());
+                GitEntity::Commit(commit_in(text, paths)?)
+            }
+        }
+        Some(CommitReference::Single(sha)) => GitEntity::Commit(commit_in(sha, paths)?),
+        Some(CommitReference::Range { from, to }) => GitEntity::Diff(Diff::from_commits_range(
+            &from,
+            &to,
+            false,
+            range_options,
+            paths,
+        )?),
+        Some(CommitReference::TripleDots { from, to }) => GitEntity::Diff(
+            Diff::from_commits_range(&from, &to, true, range_options, paths)?,
+        ),
+        None => GitEntity::Diff(Diff::from_working_tree(staged, paths)?),
+    })
+}
+
+/// The commit `sha`, with its diff limited to `paths` when there are any.
+fn commit_in(sha: String, paths: &[String]) -> Result<Commit, LumenError> {
+    let mut commit = Commit::new(sha)?;
+    commit.limit_to_paths(paths)?;
+    Ok(commit)
+}
+
+/// Where `lumen lint-msg` reads messages from: the argument, a file, a range, or stdin.
+fn message_source(
+    message: &Option<String>,
+    file: &Option<std::path::PathBuf>,
+    range: &Option<String>,
+) -> Result<command::lint_msg::MessageSource, LumenError> {
+    use command::lint_msg::MessageSource;
+    Ok(match (message, file, range) {
+        (Some(message), _, _) => MessageSource::Text(message.clone()),
+        (_, Some(file), _) => MessageSource::File(file.clone()),
+        (_, _, Some(range)) => MessageSource::Range(range.clone()),
+        (None, None, None) => MessageSource::Text(read_stdin()?),
+    })
+}
+
+fn read_stdin() -> Result<String, LumenError> {
     let mut buffer = String::new();
     std::io::stdin().read_to_string(&mut buffer)?;
-
-    eprintln!("Reading commit SHA from stdin: '{}'", buffer.trim());
     Ok(buffer)
 }
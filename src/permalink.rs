@@ -0,0 +1,196 @@
+//! GitHub links to a commit or to lines of a file, so a link pasted from chat can be opened
+//! with `lumen diff` or `lumen explain`.
+//!
+//! A file link is traced back with `git blame` to the commit that last changed the linked
+//! line (or the file), since that's the change worth looking at.
+
+use crate::vcs::git;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Permalink {
+    pub owner: String,
+    pub repo: String,
+    /// Commit SHA, or the branch or tag of a non-permanent link
+    pub rev: String,
+    /// File of a `blob` link
+    pub path: Option<String>,
+    /// First linked line, from `#L12` or `#L12-L20`
+    pub line: Option<usize>,
+}
+
+/// The commit a link leads to and, for a file link, where in it to look
+#[derive(Debug, Clone, PartialEq)]
+pub struct Target {
+    pub commit: String,
+    pub file: Option<String>,
+    /// Line of `file` in the new version of `commit`
+    pub line: Option<usize>,
+}
+
+impl Permalink {
+    /// Parse `https://github.com/owner/repo/commit/<sha>` or
+    /// `https://github.com/owner/repo/blob/<sha>/<path>#L<line>`.
+    pub fn parse(input: &str) -> Option<Self> {
+        let rest = input
+            .trim()
+            .trim_start_matches("https://")
+            .trim_start_matches("http://")
+            .trim_start_matches("www.");
+        let rest = rest.strip_prefix("github.com/")?;
+        let (rest, fragment) = rest.split_once('#').unwrap_or((rest, ""));
+        let rest = rest.split_once('?').map_or(rest, |(path, _)| path);
+
+        let mut parts = rest.splitn(4, '/');
+        let owner = parts.next().filter(|s| !s.is_empty())?;
+        let repo = parts.next().filter(|s| !s.is_empty())?;
+        let kind = parts.next()?;
+        let target = parts.next().filter(|s| !s.is_empty())?;
+        let (rev, path) = match kind {
+            "commit" => (target.trim_end_matches('/'), None),
+            "blob" => {
+                let (rev, path) = target.split_once('/')?;
+                (rev, Some(path.trim_end_matches('/').to_string()))
+            }
+            _ => return None,
+        };
+        let line = fragment
+            .strip_prefix('L')
+            .and_then(|lines| lines.split('-').next())
+            .and_then(|line| line.parse().ok())
+            .filter(|_| path.is_some());
+
+        Some(Permalink {
+            owner: owner.to_string(),
+            repo: repo.trim_end_matches(".git").to_string(),
+            rev: rev.to_string(),
+            path,
+            line,
+        })
+    }
+
+    /// Find the commit to show, fetching it from GitHub if it isn't here yet.
+    pub fn resolve(&self) -> Result<Target, String> {
+        let commit = self.ensure_commit()?;
+        let Some(path) = &self.path else {
+            return Ok(Target {
+                commit,
+                file: None,
+                line: None,
+            });
+        };
+
+        match self.line {
+            Some(line) => {
+                let range = format!("{line},{line}");
+                let output = git(&["blame", "--porcelain", "-L", &range, &commit, "--", path])
+                    .map_err(|e| format!("couldn't trace {path}:{line} at {}: {e}", self.rev))?;
+                let (commit, file, line) = parse_blame(&output)
+                    .ok_or_else(|| format!("unexpected `git blame` output for {path}:{line}"))?;
+                Ok(Target {
+                    commit,
+                    file: Some(file),
+                    line: Some(line),
+                })
+            }
+            None => {
+                let last = git(&["log", "-1", "--format=%H", &commit, "--", path])
+                    .map_err(|e| e.to_string())?
+                    .trim()
+                    .to_string();
+                if last.is_empty() {
+                    return Err(format!("{path} doesn't exist at {}", self.rev));
+                }
+                Ok(Target {
+                    commit: last,
+                    file: Some(path.clone()),
+                    line: None,
+                })
+            }
+        }
+    }
+
+    fn ensure_commit(&self) -> Result<String, String> {
+        let verify = |rev: &str| {
+            git(&[
+                "rev-parse",
+                "--verify",
+                "--quiet",
+                &format!("{rev}^{{commit}}"),
+            ])
+            .map(|sha| sha.trim().to_string())
+        };
+        if let Ok(sha) = verify(&self.rev) {
+            return Ok(sha);
+        }
+        let url = format!("https://github.com/{}/{}.git", self.owner, self.repo);
+        eprintln!(
+            "Fetching {} from github.com/{}/{}...",
+            self.rev, self.owner, self.repo
+        );
+        git(&["fetch", "--quiet", "--no-tags", &url, &self.rev]).map_err(|e| {
+            format!(
+                "{} isn't in this repository and couldn't be fetched from github.com/{}/{}: {e}",
+                self.rev, self.owner, self.repo
+            )
+        })?;
+        verify(&self.rev)
+            .or_else(|_| verify("FETCH_HEAD"))
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Commit, original file name and original line number from `git blame --porcelain` of one
+/// line.
+fn parse_blame(output: &str) -> Option<(String, String, usize)> {
+    let mut lines = output.lines();
+    let mut header = lines.next()?.split_whitespace();
+    let commit = header.next()?.to_string();
+    let line = header.next()?.parse().ok()?;
+    let file = lines.find_map(|line| line.strip_prefix("filename "))?;
+    Some((commit, file.to_string(), line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_blob_link() {
+        let link =
+            Permalink::parse("https://github.com/owner/repo/blob/1a2b3c/src/main.rs#L12-L20")
+                .unwrap();
+        assert_eq!(
+            link,
+            Permalink {
+                owner: "owner".to_string(),
+                repo: "repo".to_string(),
+                rev: "1a2b3c".to_string(),
+                path: Some("src/main.rs".to_string()),
+                line: Some(12),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_commit_link() {
+        let link = Permalink::parse("github.com/owner/repo/commit/1a2b3c?diff=split").unwrap();
+        assert_eq!(link.rev, "1a2b3c");
+        assert_eq!(link.path, None);
+        assert_eq!(link.line, None);
+
+        assert_eq!(
+            Permalink::parse("https://github.com/owner/repo/pull/12"),
+            None
+        );
+        assert_eq!(Permalink::parse("main..feature"), None);
+    }
+
+    #[test]
+    fn test_parse_blame() {
+        let output = "9f8e7d 40 12 1\nauthor Someone\nfilename src/old_name.rs\n\tlet x = 1;";
+        assert_eq!(
+            parse_blame(output),
+            Some(("9f8e7d".to_string(), "src/old_name.rs".to_string(), 40))
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! Checks that what a command needs is there before it starts: git and a repository for most
+//! commands, a branch to open a PR from, and `gh` for anything that talks to GitHub.
+//!
+//! Without them a command would fail somewhere in the middle with whatever git or gh printed;
+//! checking first gives one error saying what's missing and how to get it.
+
+use std::io::ErrorKind;
+use std::process::Command;
+
+use crate::commit_reference::CommitReference;
+use crate::config::cli::{Commands, DiffArgs};
+use crate::error::LumenError;
+use crate::logging;
+
+/// What a command needs from its surroundings
+#[derive(Debug, Default, PartialEq)]
+struct Needs {
+    repo: bool,
+    /// A branch checked out, rather than a detached HEAD
+    branch: bool,
+    gh: bool,
+}
+
+fn is_pr_url(input: &str) -> bool {
+    input.contains("/pull/")
+}
+
+fn needs(command: &Commands) -> Needs {
+    match command {
+        Commands::Diff(args) => {
+            let DiffArgs {
+                reference,
+                pr,
+                files,
+                ..
+            } = args.as_ref();
+            let url = match (pr, reference) {
+                (Some(pr), _) => is_pr_url(pr),
+                (None, Some(CommitReference::Single(input))) => is_pr_url(input),
+                _ => false,
+            };
+            Needs {
+                // A PR's URL names its repository, and two paths are compared as they are
+                repo: !url && files.is_none(),
+                branch: false,
+                gh: pr.is_some() || url,
+            }
+        }
+        Commands::Pr { create, .. } => Needs {
+            repo: true,
+            branch: *create,
+            gh: *create,
+        },
+        // A message given as text, a file or on stdin is checked without a repository
+        Commands::LintMsg { range, .. } => Needs {
+            repo: range.is_some(),
+            ..Needs::default()
+        },
+        Commands::Explain { .. }
+        | Commands::List
+        | Commands::Log { .. }
+        | Commands::Draft { .. }
+        | Commands::Split { .. }
+        | Commands::Reword { .. }
+        | Commands::Review { .. }
+        | Commands::Check { .. }
+        | Commands::CherryPick { .. }
+        | Commands::Snapshot { .. }
+        | Commands::Hook { .. }
+        | Commands::Digest { .. }
+        | Commands::Changelog { .. } => Needs {
+            repo: true,
+            ..Needs::default()
+        },
+        _ => Needs::default(),
+    }
+}
+
+/// Fail with what's missing when `command` can't run here.
+pub fn check(command: &Commands) -> Result<(), LumenError> {
+    let needs = needs(command);
+    if needs.repo {
+        repo()?;
+    }
+    if needs.branch {
+        branch("open a pull request from")?;
+    }
+    if needs.gh {
+        gh()?;
+    }
+    Ok(())
+}
+
+/// git is installed and the current directory is inside a repository's work tree.
+pub fn repo() -> Result<(), LumenError> {
+    match logging::output(Command::new("git").args(["rev-parse", "--is-inside-work-tree"])) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(LumenError::GitMissing),
+        Err(e) => Err(LumenError::CommandError(format!("couldn't run git: {e}"))),
+        Ok(output) if output.status.success() => Ok(()),
+        Ok(_) => Err(LumenError::NotARepo),
+    }
+}
+
+/// A branch is checked out; `action` is what it's needed for.
+pub fn branch(action: &'static str) -> Result<(), LumenError> {
+    let checked_out =
+        logging::output(Command::new("git").args(["symbolic-ref", "--quiet", "HEAD"]))
+            .is_ok_and(|output| output.status.success());
+    if checked_out {
+        Ok(())
+    } else {
+        Err(LumenError::DetachedHead(action))
+    }
+}
+
+/// gh is installed. Whether it's signed in shows in what its first request returns, see
+/// [`gh_failure`].
+pub fn gh() -> Result<(), LumenError> {
+    match Command::new("gh").arg("--version").output() {
+        Err(e) if e.kind() == ErrorKind::NotFound => Err(LumenError::GhMissing),
+        Err(e) => Err(LumenError::CommandError(format!("couldn't run gh: {e}"))),
+        Ok(_) => Ok(()),
+    }
+}
+
+/// The error for a gh command that failed with `stderr`, telling sign-in problems apart from
+/// the rest.
+pub fn gh_failure(action: &str, stderr: &str) -> LumenError {
+    let stderr = stderr.trim();
+    let lower = stderr.to_lowercase();
+    let auth = [
+        "gh auth login",
+        "not logged in",
+        "authentication",
+        "bad credentials",
+        "http 401",
+    ]
+    .iter()
+    .any(|sign| lower.contains(sign));
+    if auth {
+        let reason = stderr.lines().next().unwrap_or_default().to_string();
+        LumenError::GhNotAuthenticated(reason)
+    } else {
+        LumenError::CommandError(format!("{action} failed: {stderr}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::cli::Cli;
+    use clap::Parser;
+
+    fn needs_of(args: &[&str]) -> Needs {
+        let cli =
+            Cli::try_parse_from(std::iter::once("lumen").chain(args.iter().copied())).unwrap();
+        needs(&cli.command)
+    }
+
+    #[test]
+    fn test_needs() {
+        let repo = Needs {
+            repo: true,
+            ..Needs::default()
+        };
+        assert_eq!(needs_of(&["diff"]), repo);
+        assert_eq!(needs_of(&["draft"]), repo);
+        assert_eq!(
+            needs_of(&["diff", "--pr", "42"]),
+            Needs {
+                repo: true,
+                branch: false,
+                gh: true,
+            }
+        );
+        assert_eq!(
+            needs_of(&["diff", "https://github.com/o/r/pull/7"]),
+            Needs {
+                gh: true,
+                ..Needs::default()
+            }
+        );
+        assert_eq!(
+            needs_of(&["pr", "--create"]),
+            Needs {
+                repo: true,
+                branch: true,
+                gh: true,
+            }
+        );
+        assert_eq!(needs_of(&["lint-msg", "feat: add x"]), Needs::default());
+        assert_eq!(needs_of(&["doctor"]), Needs::default());
+    }
+
+    #[test]
+    fn test_gh_failure() {
+        assert!(matches!(
+            gh_failure(
+                "gh api graphql",
+                "To get started with GitHub CLI, please run:  gh auth login\n"
+            ),
+            LumenError::GhNotAuthenticated(_)
+        ));
+        assert!(matches!(
+            gh_failure(
+                "gh api graphql",
+                "HTTP 401: Bad credentials (https://api.github.com/graphql)"
+            ),
+            LumenError::GhNotAuthenticated(_)
+        ));
+        assert_eq!(
+            gh_failure("gh api graphql", "Could not resolve to a PullRequest\n").to_string(),
+            "gh api graphql failed: Could not resolve to a PullRequest"
+        );
+    }
+}
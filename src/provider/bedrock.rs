@@ -0,0 +1,437 @@
+//! AWS Bedrock backend: Converse API requests signed with SigV4.
+//!
+//! Credentials and region come from the standard AWS sources, in order:
+//! environment variables, then the shared `~/.aws/credentials` and `~/.aws/config` files
+//! (honouring `AWS_PROFILE`).
+
+use std::collections::HashMap;
+use std::fs;
+
+use hmac::{Hmac, Mac};
+use lumen_core::prompt::AIPrompt;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::usage::TokenUsage;
+use super::ProviderError;
+use crate::config::configuration::GenerationSettings;
+
+const SERVICE: &str = "bedrock";
+
+pub struct AwsCredentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    pub fn resolve() -> Option<Self> {
+        if let (Ok(access_key_id), Ok(secret_access_key)) = (
+            std::env::var("AWS_ACCESS_KEY_ID"),
+            std::env::var("AWS_SECRET_ACCESS_KEY"),
+        ) {
+            return Some(AwsCredentials {
+                access_key_id,
+                secret_access_key,
+                session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            });
+        }
+
+        let mut section = read_aws_file("credentials", &profile())?;
+        Some(AwsCredentials {
+            access_key_id: section.remove("aws_access_key_id")?,
+            secret_access_key: section.remove("aws_secret_access_key")?,
+            session_token: section.remove("aws_session_token"),
+        })
+    }
+}
+
+pub fn resolve_region() -> Option<String> {
+    std::env::var("AWS_REGION")
+        .or_else(|_| std::env::var("AWS_DEFAULT_REGION"))
+        .ok()
+        .or_else(|| {
+            let profile = profile();
+            // The config file prefixes every section except `default` with "profile "
+            let section = if profile == "default" {
+                profile
+            } else {
+                format!("profile {profile}")
+            };
+            read_aws_file("config", &section)?.remove("region")
+        })
+}
+
+fn profile() -> String {
+    std::env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string())
+}
+
+/// Read one `[section]` of an INI-style file in `~/.aws`.
+fn read_aws_file(name: &str, section: &str) -> Option<HashMap<String, String>> {
+    let path = dirs::home_dir()?.join(".aws").join(name);
+    let content = fs::read_to_string(path).ok()?;
+
+    let mut in_section = false;
+    let mut values = HashMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') && line.ends_with(']') {
+            in_section = line[1..line.len() - 1].trim() == section;
+        } else if in_section {
+            if let Some((key, value)) = line.split_once('=') {
+                values.insert(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+    }
+    (!values.is_empty()).then_some(values)
+}
+
+pub struct BedrockClient {
+    http: reqwest::Client,
+    region: String,
+    credentials: AwsCredentials,
+}
+
+impl BedrockClient {
+    pub fn new(region: String, credentials: AwsCredentials) -> Self {
+        BedrockClient {
+            http: crate::http::client(),
+            region,
+            credentials,
+        }
+    }
+
+    pub async fn converse(
+        &self,
+        model: &str,
+        prompt: &AIPrompt,
+        settings: &GenerationSettings,
+    ) -> Result<(String, TokenUsage), ProviderError> {
+        let mut body = json!({
+            "system": [{ "text": prompt.system_prompt_in(settings.language.as_deref()) }],
+            "messages": [{ "role": "user", "content": user_content(model, prompt) }],
+        });
+        add_settings(&mut body, model, settings);
+        let body = body.to_string();
+
+        let host = format!("bedrock-runtime.{}.amazonaws.com", self.region);
+        let path = format!("/model/{}/converse", uri_encode(model));
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut headers = vec![
+            ("content-type", "application/json".to_string()),
+            ("host", host.clone()),
+            ("x-amz-date", amz_date.clone()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token", token.clone()));
+        }
+
+        // Non-S3 services sign the path with every segment encoded a second time
+        let canonical_uri = path
+            .split('/')
+            .map(uri_encode)
+            .collect::<Vec<_>>()
+            .join("/");
+        let authorization = sign(
+            &SigningRequest {
+                method: "POST",
+                canonical_uri: &canonical_uri,
+                headers: &headers,
+                payload: body.as_bytes(),
+                region: &self.region,
+                service: SERVICE,
+                amz_date: &amz_date,
+            },
+            &self.credentials,
+        );
+
+        let mut request = self
+            .http
+            .post(format!("https://{host}{path}"))
+            .header("authorization", authorization)
+            .body(body);
+        for (name, value) in &headers {
+            if *name != "host" {
+                request = request.header(*name, value);
+            }
+        }
+
+        let response = request.send().await?;
+        let status = response.status();
+        let retry_after = response
+            .headers()
+            .get("retry-after")
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+        let json: Value = response.json().await?;
+        if !status.is_success() {
+            return Err(ProviderError::BedrockError {
+                status: status.as_u16(),
+                message: json["message"]
+                    .as_str()
+                    .unwrap_or("request failed")
+                    .to_string(),
+                retry_after,
+            });
+        }
+
+        let text = json["output"]["message"]["content"]
+            .as_array()
+            .and_then(|content| content.iter().find_map(|block| block["text"].as_str()))
+            .map(String::from)
+            .ok_or(ProviderError::NoCompletionChoice)?;
+        // Tokens read from or written to the cache aren't part of `inputTokens`
+        let tokens = |name: &str| json["usage"][name].as_u64().unwrap_or(0);
+        let usage = TokenUsage {
+            input: tokens("inputTokens")
+                + tokens("cacheReadInputTokens")
+                + tokens("cacheWriteInputTokens"),
+            output: tokens("outputTokens"),
+            cached: tokens("cacheReadInputTokens"),
+        };
+        Ok((text, usage))
+    }
+}
+
+/// The user message's content blocks. For Claude models, a cache point follows the stable
+/// start of the prompt so the next request about the same changes reads it from the cache.
+fn user_content(model: &str, prompt: &AIPrompt) -> Value {
+    match prompt.split_user_prompt() {
+        (stable, rest) if model.contains("anthropic.") && !stable.is_empty() => {
+            let mut content = vec![
+                json!({ "text": stable }),
+                json!({ "cachePoint": { "type": "default" } }),
+            ];
+            if !rest.is_empty() {
+                content.push(json!({ "text": rest }));
+            }
+            Value::Array(content)
+        }
+        _ => json!([{ "text": prompt.user_prompt }]),
+    }
+}
+
+/// Token budgets for Claude's extended thinking at each `reasoning_effort`, as genai uses
+/// for Anthropic's own API
+fn thinking_budget(effort: &str) -> Option<u32> {
+    match effort {
+        "low" => Some(1024),
+        "medium" => Some(8000),
+        "high" => Some(24000),
+        budget => budget.parse().ok(),
+    }
+}
+
+/// Put the generation settings into a Converse request `body`. Reasoning effort only applies
+/// to Claude models, as extended thinking, which doesn't allow setting the temperature or
+/// top_p and needs room for the answer on top of the thinking budget.
+fn add_settings(body: &mut Value, model: &str, settings: &GenerationSettings) {
+    let budget = settings
+        .reasoning_effort
+        .as_deref()
+        .and_then(thinking_budget)
+        .filter(|_| model.contains("anthropic."));
+
+    let mut inference = serde_json::Map::new();
+    if budget.is_none() {
+        if let Some(temperature) = settings.temperature {
+            inference.insert("temperature".into(), json!(temperature));
+        }
+        if let Some(top_p) = settings.top_p {
+            inference.insert("topP".into(), json!(top_p));
+        }
+    }
+    let max_tokens = match (settings.max_output_tokens, budget) {
+        (Some(max), Some(budget)) => Some(max.max(budget + 1024)),
+        (None, Some(budget)) => Some(budget + 4096),
+        (max, None) => max,
+    };
+    if let Some(max_tokens) = max_tokens {
+        inference.insert("maxTokens".into(), json!(max_tokens));
+    }
+    if !inference.is_empty() {
+        body["inferenceConfig"] = Value::Object(inference);
+    }
+    if let Some(budget) = budget {
+        body["additionalModelRequestFields"] =
+            json!({ "thinking": { "type": "enabled", "budget_tokens": budget } });
+    }
+}
+
+struct SigningRequest<'a> {
+    method: &'a str,
+    canonical_uri: &'a str,
+    /// Lowercase header names, sorted
+    headers: &'a [(&'a str, String)],
+    payload: &'a [u8],
+    region: &'a str,
+    service: &'a str,
+    amz_date: &'a str,
+}
+
+/// Build the SigV4 `Authorization` header value for a request without query parameters.
+fn sign(request: &SigningRequest, credentials: &AwsCredentials) -> String {
+    let date_stamp = &request.amz_date[..8];
+    let scope = format!(
+        "{date_stamp}/{}/{}/aws4_request",
+        request.region, request.service
+    );
+
+    let canonical_headers: String = request
+        .headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{}\n", value.trim()))
+        .collect();
+    let signed_headers = request
+        .headers
+        .iter()
+        .map(|(name, _)| *name)
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let canonical_request = format!(
+        "{}\n{}\n\n{canonical_headers}\n{signed_headers}\n{}",
+        request.method,
+        request.canonical_uri,
+        hex_sha256(request.payload)
+    );
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{scope}\n{}",
+        request.amz_date,
+        hex_sha256(canonical_request.as_bytes())
+    );
+
+    let key = [date_stamp, request.region, request.service, "aws4_request"]
+        .iter()
+        .fold(
+            format!("AWS4{}", credentials.secret_access_key).into_bytes(),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+    let signature: String = hmac_sha256(&key, string_to_sign.as_bytes())
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex_sha256(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// Percent-encode everything except RFC 3986 unreserved characters.
+fn uri_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_in_request() {
+        let settings = GenerationSettings {
+            temperature: Some(0.2),
+            top_p: Some(0.9),
+            max_output_tokens: Some(500),
+            reasoning_effort: Some("medium".to_string()),
+            language: None,
+            model: None,
+        };
+        let mut body = json!({});
+        add_settings(&mut body, "meta.llama3-70b-instruct-v1:0", &settings);
+        assert_eq!(
+            body,
+            json!({ "inferenceConfig": { "temperature": 0.2, "topP": 0.9, "maxTokens": 500 } })
+        );
+
+        // Claude thinks instead, with room to answer after the budget
+        let mut body = json!({});
+        add_settings(&mut body, "us.anthropic.claude-sonnet-4-5-v1:0", &settings);
+        assert_eq!(
+            body,
+            json!({
+                "inferenceConfig": { "maxTokens": 9024 },
+                "additionalModelRequestFields": {
+                    "thinking": { "type": "enabled", "budget_tokens": 8000 }
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_cache_point_after_stable_prefix() {
+        let prompt = AIPrompt {
+            system_prompt: String::new(),
+            user_prompt: "diff\nQuestion".to_string(),
+            stable_prefix: 5,
+        };
+        assert_eq!(
+            user_content("anthropic.claude-haiku-4-5-v1:0", &prompt),
+            json!([
+                { "text": "diff\n" },
+                { "cachePoint": { "type": "default" } },
+                { "text": "Question" },
+            ])
+        );
+        assert_eq!(
+            user_content("meta.llama3-70b-instruct-v1:0", &prompt),
+            json!([{ "text": "diff\nQuestion" }])
+        );
+    }
+
+    #[test]
+    fn test_uri_encode() {
+        assert_eq!(
+            uri_encode("anthropic.claude-v2:1"),
+            "anthropic.claude-v2%3A1"
+        );
+        assert_eq!(uri_encode("a b/c"), "a%20b%2Fc");
+    }
+
+    #[test]
+    fn test_sign_matches_aws_test_suite() {
+        // "get-vanilla" from the AWS SigV4 test suite
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let headers = [
+            ("host", "example.amazonaws.com".to_string()),
+            ("x-amz-date", "20150830T123600Z".to_string()),
+        ];
+        let authorization = sign(
+            &SigningRequest {
+                method: "GET",
+                canonical_uri: "/",
+                headers: &headers,
+                payload: b"",
+                region: "us-east-1",
+                service: "service",
+                amz_date: "20150830T123600Z",
+            },
+            &credentials,
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, SignedHeaders=host;x-amz-date, Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+}
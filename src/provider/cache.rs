@@ -0,0 +1,106 @@
+//! On-disk cache of AI responses for immutable inputs such as commits.
+//!
+//! Entries live in `~/.cache/lumen/<sha>/<key>`, where the key hashes the model, the generation
+//! settings and the full prompt, so any change to them misses the cache.
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use lumen_core::prompt::AIPrompt;
+use sha2::{Digest, Sha256};
+
+use crate::config::configuration::GenerationSettings;
+
+pub fn cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join("lumen"))
+}
+
+/// Cache slot for one prompt sent about one commit.
+pub struct CacheEntry {
+    path: PathBuf,
+}
+
+impl CacheEntry {
+    /// `model` should identify the provider too, since model names are not unique across them.
+    pub fn new(
+        sha: &str,
+        model: &str,
+        settings: &GenerationSettings,
+        prompt: &AIPrompt,
+    ) -> Option<Self> {
+        let mut hasher = Sha256::new();
+        for part in [
+            model,
+            &format!("{settings:?}"),
+            &prompt.system_prompt,
+            &prompt.user_prompt,
+        ] {
+            hasher.update(part.as_bytes());
+            // Separator so ("ab", "c") and ("a", "bc") hash differently
+            hasher.update([0]);
+        }
+        let key = format!("{:x}", hasher.finalize());
+        Some(CacheEntry {
+            path: cache_dir()?.join(sha).join(key),
+        })
+    }
+
+    pub fn get(&self) -> Option<String> {
+        fs::read_to_string(&self.path).ok()
+    }
+
+    /// Store a response. Failures are ignored since the cache is only an optimization.
+    pub fn put(&self, response: &str) {
+        if let Some(parent) = self.path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let _ = fs::write(&self.path, response);
+    }
+}
+
+/// Remove every cached response, returning how many commits had entries.
+pub fn clear() -> io::Result<usize> {
+    let Some(dir) = cache_dir() else {
+        return Ok(0);
+    };
+    if !dir.exists() {
+        return Ok(0);
+    }
+    let count = fs::read_dir(&dir)?.count();
+    fs::remove_dir_all(&dir)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(user: &str) -> AIPrompt {
+        AIPrompt {
+            system_prompt: "system".to_string(),
+            user_prompt: user.to_string(),
+            stable_prefix: 0,
+        }
+    }
+
+    fn path(sha: &str, model: &str, temperature: Option<f64>, user: &str) -> PathBuf {
+        let settings = GenerationSettings {
+            temperature,
+            ..Default::default()
+        };
+        CacheEntry::new(sha, model, &settings, &prompt(user))
+            .unwrap()
+            .path
+    }
+
+    #[test]
+    fn test_key_depends_on_every_input() {
+        let base = path("abc", "m", None, "u");
+        assert!(base.parent().unwrap().ends_with("lumen/abc"));
+        assert_eq!(base, path("abc", "m", None, "u"));
+        assert_ne!(base, path("abc", "m2", None, "u"));
+        assert_ne!(base, path("abc", "m", Some(0.2), "u"));
+        assert_ne!(base, path("abc", "m", None, "u2"));
+    }
+}
@@ -1,14 +1,40 @@
+use std::time::Instant;
+
+use futures::StreamExt;
 use genai::adapter::AdapterKind;
-use genai::chat::{ChatMessage, ChatRequest};
+use genai::chat::{
+    CacheControl, ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, ChatStreamEvent, Tool,
+    ToolResponse,
+};
 use genai::resolver::{AuthData, Endpoint, ServiceTargetResolver};
 use genai::{Client, ClientBuilder, ModelIden, ServiceTarget};
+use lumen_core::patch::{compact_diff, estimate_tokens, split_diff, DiffHunk};
+use lumen_core::prompt::{AIPrompt, AIPromptError};
 use thiserror::Error;
 
-use crate::ai_prompt::{AIPrompt, AIPromptError};
-use crate::command::{draft::DraftCommand, explain::ExplainCommand, operate::OperateCommand};
+use crate::command::{
+    draft::DraftCommand,
+    explain::ExplainCommand,
+    operate::{self, OperateCommand},
+    pr::PrCommand,
+};
 use crate::config::cli::ProviderType;
-use crate::config::ProviderInfo;
+use crate::config::configuration::{AiConfig, GenerationSettings};
+use crate::config::{keychain, ProviderInfo};
 use crate::error::LumenError;
+use crate::git_entity::commit::Commit;
+use crate::http;
+use crate::logging;
+use crate::privacy::{self, SecretPolicy};
+use bedrock::{AwsCredentials, BedrockClient};
+use cache::CacheEntry;
+use usage::{TokenUsage, UsageRecord};
+
+mod bedrock;
+pub mod cache;
+pub mod ollama;
+mod retry;
+pub mod usage;
 
 #[derive(Error, Debug)]
 pub enum ProviderError {
@@ -23,17 +49,48 @@ pub enum ProviderError {
 
     #[error(transparent)]
     AIPromptError(#[from] AIPromptError),
+
+    #[error("AWS Bedrock request failed: {status}: {message}")]
+    BedrockError {
+        status: u16,
+        message: String,
+        retry_after: Option<String>,
+    },
+
+    #[error("{0}")]
+    Ollama(String),
+
+    #[error("Refusing to send likely secrets to the provider ({0}); set `ai.secrets` to \"redact\" to send them redacted")]
+    SecretsDetected(String),
 }
 
 enum ProviderBackend {
-    GenAI { client: Client, model: String },
+    GenAI {
+        client: Client,
+        model: String,
+    },
+    Bedrock {
+        client: BedrockClient,
+        model: String,
+    },
 }
 
 pub struct LumenProvider {
     backend: ProviderBackend,
     provider_name: String,
+    provider_id: &'static str,
+    supports_tools: bool,
+    /// Whether to mark the stable start of prompts for Anthropic's prompt caching, which
+    /// unlike OpenAI's has to be asked for
+    cache_control: bool,
+    /// The server, for Ollama, to find out why a request failed
+    ollama_host: Option<String>,
+    ai: AiConfig,
 }
 
+/// Rounds of tool calls before the model has to answer with what it has
+const MAX_TOOL_ROUNDS: usize = 6;
+
 /// Provider configuration for custom endpoint providers (OpenRouter, Vercel)
 struct CustomProviderConfig {
     endpoint: &'static str,
@@ -46,8 +103,88 @@ impl LumenProvider {
         provider_type: ProviderType,
         api_key: Option<String>,
         model: Option<String>,
+        api_base_url: Option<String>,
     ) -> Result<Self, LumenError> {
+        // `keyring:<name>` references are only read once a provider actually needs the key
+        let api_key = api_key.map(keychain::resolve).transpose()?;
+        let masked_key = api_key.as_deref().map(logging::mask_key);
+        let ollama_host =
+            (provider_type == ProviderType::Ollama).then(|| ollama::host(api_base_url.as_deref()));
         let (backend, provider_name) = match provider_type {
+            // Bedrock isn't supported by genai, so requests are signed and sent directly
+            ProviderType::Bedrock => {
+                let defaults = ProviderInfo::for_provider(provider_type);
+                let region = bedrock::resolve_region().ok_or_else(|| {
+                    LumenError::ConfigurationError(
+                        "AWS region not found (set AWS_REGION or `region` in ~/.aws/config)"
+                            .to_string(),
+                    )
+                })?;
+                let credentials = AwsCredentials::resolve().ok_or_else(|| {
+                    LumenError::ConfigurationError(
+                        "AWS credentials not found (set AWS_ACCESS_KEY_ID/AWS_SECRET_ACCESS_KEY or configure ~/.aws/credentials)"
+                            .to_string(),
+                    )
+                })?;
+                let model = model.unwrap_or_else(|| defaults.default_model.to_string());
+
+                (
+                    ProviderBackend::Bedrock {
+                        client: BedrockClient::new(region, credentials),
+                        model,
+                    },
+                    defaults.display_name.to_string(),
+                )
+            }
+            // Any OpenAI-compatible server (LM Studio, vLLM, ...) at a user-provided URL
+            ProviderType::CustomOpenai => {
+                let defaults = ProviderInfo::for_provider(provider_type);
+
+                let base_url = api_base_url.ok_or_else(|| {
+                    LumenError::ConfigurationError(
+                        "custom-openai requires `api_base_url` (config, --api-base-url or LUMEN_API_BASE_URL)"
+                            .to_string(),
+                    )
+                })?;
+                // Trailing slash is required for URL joining to work correctly
+                let base_url = if base_url.ends_with('/') {
+                    base_url
+                } else {
+                    format!("{base_url}/")
+                };
+                let model = model.filter(|m| !m.is_empty()).ok_or_else(|| {
+                    LumenError::ConfigurationError(
+                        "custom-openai requires a model name (config, --model or LUMEN_AI_MODEL)"
+                            .to_string(),
+                    )
+                })?;
+
+                // Local servers usually don't check the key, so an empty one is fine
+                let api_key = api_key
+                    .or_else(|| std::env::var(defaults.env_key).ok())
+                    .unwrap_or_default();
+
+                let target_resolver = ServiceTargetResolver::from_resolver_fn(
+                    move |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
+                        let ServiceTarget { model, .. } = service_target;
+                        Ok(ServiceTarget {
+                            endpoint: Endpoint::from_owned(base_url.clone()),
+                            auth: AuthData::from_single(api_key.clone()),
+                            model: ModelIden::new(AdapterKind::OpenAI, model.model_name),
+                        })
+                    },
+                );
+
+                let client = ClientBuilder::default()
+                    .with_reqwest(http::client())
+                    .with_service_target_resolver(target_resolver)
+                    .build();
+
+                (
+                    ProviderBackend::GenAI { client, model },
+                    defaults.display_name.to_string(),
+                )
+            }
             // Custom endpoint providers (OpenRouter, Vercel) - use ServiceTargetResolver
             ProviderType::Openrouter | ProviderType::Vercel => {
                 let defaults = ProviderInfo::for_provider(provider_type);
@@ -90,6 +227,7 @@ impl LumenProvider {
                 );
 
                 let client = ClientBuilder::default()
+                    .with_reqwest(http::client())
                     .with_service_target_resolver(target_resolver)
                     .build();
 
@@ -101,6 +239,33 @@ impl LumenProvider {
                     defaults.display_name.to_string(),
                 )
             }
+            // Ollama's OpenAI-compatible API, on whichever host it runs
+            ProviderType::Ollama => {
+                let defaults = ProviderInfo::for_provider(provider_type);
+                let model = model.unwrap_or_else(|| defaults.default_model.to_string());
+                let endpoint = format!("{}/v1/", ollama_host.clone().unwrap_or_default());
+
+                let target_resolver = ServiceTargetResolver::from_resolver_fn(
+                    move |service_target: ServiceTarget| -> Result<ServiceTarget, genai::resolver::Error> {
+                        let ServiceTarget { model, auth, .. } = service_target;
+                        Ok(ServiceTarget {
+                            endpoint: Endpoint::from_owned(endpoint.clone()),
+                            auth,
+                            model: ModelIden::new(AdapterKind::Ollama, model.model_name),
+                        })
+                    },
+                );
+
+                let client = ClientBuilder::default()
+                    .with_reqwest(http::client())
+                    .with_service_target_resolver(target_resolver)
+                    .build();
+
+                (
+                    ProviderBackend::GenAI { client, model },
+                    defaults.display_name.to_string(),
+                )
+            }
             // Native genai providers
             _ => {
                 let defaults = ProviderInfo::for_provider(provider_type);
@@ -116,7 +281,9 @@ impl LumenProvider {
 
                 (
                     ProviderBackend::GenAI {
-                        client: Client::default(),
+                        client: ClientBuilder::default()
+                            .with_reqwest(http::client())
+                            .build(),
                         model,
                     },
                     defaults.display_name.to_string(),
@@ -124,48 +291,595 @@ impl LumenProvider {
             }
         };
 
-        Ok(Self {
+        let provider = Self {
             backend,
             provider_name,
-        })
+            provider_id: ProviderInfo::for_provider(provider_type).id,
+            supports_tools: ProviderInfo::for_provider(provider_type).supports_tools,
+            cache_control: provider_type == ProviderType::Claude,
+            ollama_host,
+            ai: AiConfig::default(),
+        };
+        tracing::debug!(
+            provider = provider.provider_id,
+            model = %provider.get_model(),
+            api_key = masked_key.as_deref().unwrap_or("from environment"),
+            "provider set up"
+        );
+        Ok(provider)
+    }
+
+    /// Use the given generation settings for subsequent requests.
+    pub fn with_ai_config(mut self, ai: AiConfig) -> Self {
+        self.ai = ai;
+        self
     }
 
-    async fn complete(&self, prompt: AIPrompt) -> Result<String, ProviderError> {
+    /// Send a prompt using the generation settings configured for `command`, retrying
+    /// rate-limited and transient failures.
+    async fn complete(&self, prompt: AIPrompt, command: &str) -> Result<String, ProviderError> {
+        self.complete_with(prompt, command, false).await
+    }
+
+    /// Like `complete`, but asks for JSON output on providers that support a JSON mode
+    /// (OpenAI-compatible APIs and Gemini). The prompt must still describe the format.
+    async fn complete_json(
+        &self,
+        prompt: AIPrompt,
+        command: &str,
+    ) -> Result<String, ProviderError> {
+        self.complete_with(prompt, command, true).await
+    }
+
+    async fn complete_with(
+        &self,
+        prompt: AIPrompt,
+        command: &str,
+        json: bool,
+    ) -> Result<String, ProviderError> {
+        let prompt = self.redact_secrets(self.compact_diffs(prompt))?;
+        let max_retries = self.ai.max_retries();
+        let mut attempt = 0;
+        loop {
+            let error = match self.send(&prompt, command, json).await {
+                Ok((response, usage)) => {
+                    self.record_usage(command, usage);
+                    return Ok(response);
+                }
+                Err(error) => error,
+            };
+            let Some(retry_after) = retry::retry_hint(&error).filter(|_| attempt < max_retries)
+            else {
+                return Err(self.diagnose(error).await);
+            };
+
+            let delay = retry_after.unwrap_or_else(|| retry::backoff_delay(attempt));
+            attempt += 1;
+            eprintln!(
+                "\r\x1b[33mProvider is rate limiting or unavailable, retrying in {:.1}s ({attempt}/{max_retries})\x1b[0m",
+                delay.as_secs_f64()
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    async fn send(
+        &self,
+        prompt: &AIPrompt,
+        command: &str,
+        json: bool,
+    ) -> Result<(String, TokenUsage), ProviderError> {
+        let settings = self.ai.for_command(command);
+        tracing::debug!(
+            command,
+            model = settings.model.as_deref().unwrap_or(&self.get_model()),
+            system_bytes = prompt.system_prompt.len(),
+            user_bytes = prompt.user_prompt.len(),
+            temperature = ?settings.temperature,
+            json,
+            "sending request"
+        );
+        tracing::trace!(system = %prompt.system_prompt, user = %prompt.user_prompt, "prompt");
+        let started = Instant::now();
+        let result = self.send_to_backend(prompt, &settings, json).await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok((text, _)) => {
+                tracing::debug!(command, elapsed_ms, "response received");
+                tracing::trace!(response = %text, "response");
+            }
+            Err(error) => tracing::debug!(command, elapsed_ms, %error, "request failed"),
+        }
+        result
+    }
+
+    async fn send_to_backend(
+        &self,
+        prompt: &AIPrompt,
+        settings: &GenerationSettings,
+        json: bool,
+    ) -> Result<(String, TokenUsage), ProviderError> {
         match &self.backend {
             ProviderBackend::GenAI { client, model } => {
-                let chat_req = ChatRequest::new(vec![
-                    ChatMessage::system(prompt.system_prompt),
-                    ChatMessage::user(prompt.user_prompt),
-                ]);
-
-                let response = client.exec_chat(model, chat_req, None).await?;
+                let (chat_req, mut options) = self.chat_request(prompt, settings);
+                if json {
+                    options = options.with_response_format(ChatResponseFormat::JsonMode);
+                }
+                let model = settings.model.as_deref().unwrap_or(model);
+                let response = client.exec_chat(model, chat_req, Some(&options)).await?;
 
-                response
+                let text = response
                     .first_text()
                     .map(|s| s.to_string())
-                    .ok_or(ProviderError::NoCompletionChoice)
+                    .ok_or(ProviderError::NoCompletionChoice)?;
+                Ok((text, Self::token_usage(&response.usage)))
+            }
+            ProviderBackend::Bedrock { client, model } => {
+                let model = settings.model.as_deref().unwrap_or(model);
+                client.converse(model, prompt, settings).await
             }
         }
     }
 
+    /// Like `complete`, but calls `on_delta` with each piece of text as it arrives, and returns
+    /// the full text at the end. Bedrock answers arrive in one piece. Not retried, since part of
+    /// the answer may already have been handed out.
+    pub async fn complete_stream(
+        &self,
+        prompt: AIPrompt,
+        command: &str,
+        mut on_delta: impl FnMut(&str),
+    ) -> Result<String, ProviderError> {
+        match &self.backend {
+            ProviderBackend::GenAI { client, model } => {
+                let prompt = self.redact_secrets(self.compact_diffs(prompt))?;
+                let settings = self.ai.for_command(command);
+                let (chat_req, options) = self.chat_request(&prompt, &settings);
+                let options = options.with_capture_usage(true);
+                let model = settings.model.as_deref().unwrap_or(model);
+                tracing::debug!(command, model = %model, "streaming request");
+                tracing::trace!(system = %prompt.system_prompt, user = %prompt.user_prompt, "prompt");
+                let started = Instant::now();
+                let mut stream = match client
+                    .exec_chat_stream(model, chat_req, Some(&options))
+                    .await
+                {
+                    Ok(response) => response.stream,
+                    Err(e) => return Err(self.diagnose(e.into()).await),
+                };
+
+                let mut text = String::new();
+                let mut usage = TokenUsage::default();
+                while let Some(event) = stream.next().await {
+                    match event? {
+                        ChatStreamEvent::Chunk(chunk) => {
+                            on_delta(&chunk.content);
+                            text.push_str(&chunk.content);
+                        }
+                        ChatStreamEvent::End(end) => {
+                            if let Some(captured) = &end.captured_usage {
+                                usage = Self::token_usage(captured);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                tracing::debug!(
+                    command,
+                    elapsed_ms = started.elapsed().as_millis() as u64,
+                    "stream finished"
+                );
+                tracing::trace!(response = %text, "response");
+                self.record_usage(command, usage);
+                Ok(text)
+            }
+            ProviderBackend::Bedrock { .. } => {
+                let text = self.complete(prompt, command).await?;
+                on_delta(&text);
+                Ok(text)
+            }
+        }
+    }
+
+    /// A clearer error than the HTTP one for requests that fail because of the setup, like
+    /// an Ollama model that isn't installed.
+    async fn diagnose(&self, error: ProviderError) -> ProviderError {
+        match &self.ollama_host {
+            Some(host) => ollama::diagnose(host, &self.get_model(), error).await,
+            None => error,
+        }
+    }
+
+    /// Like `complete`, but lets the model call `tools` (run by `run_tool`) and see their
+    /// output before it answers. Only for providers with `supports_tools`; not retried.
+    async fn complete_with_tools(
+        &self,
+        prompt: AIPrompt,
+        command: &str,
+        tools: Vec<Tool>,
+        run_tool: impl Fn(&str, &serde_json::Value) -> String,
+    ) -> Result<String, ProviderError> {
+        let ProviderBackend::GenAI { client, model } = &self.backend else {
+            return self.complete(prompt, command).await;
+        };
+        let prompt = self.redact_secrets(self.compact_diffs(prompt))?;
+        let settings = self.ai.for_command(command);
+        let (chat_req, options) = self.chat_request(&prompt, &settings);
+        let mut chat_req = chat_req.with_tools(tools);
+        let model = settings.model.as_deref().unwrap_or(model);
+
+        for round in 0..=MAX_TOOL_ROUNDS {
+            if round == MAX_TOOL_ROUNDS {
+                chat_req.tools = None;
+            }
+            let response = client
+                .exec_chat(model, chat_req.clone(), Some(&options))
+                .await?;
+            self.record_usage(command, Self::token_usage(&response.usage));
+
+            let text = response.first_text().map(|s| s.to_string());
+            let tool_calls = response.into_tool_calls();
+            if tool_calls.is_empty() {
+                return text.ok_or(ProviderError::NoCompletionChoice);
+            }
+
+            let mut results = Vec::new();
+            for call in &tool_calls {
+                tracing::debug!(command, round, tool = %call.fn_name, "tool call");
+                eprintln!("\r\x1b[2m  {} {}\x1b[0m", call.fn_name, call.fn_arguments);
+                // Tool output goes to the provider too, so it gets the same redaction
+                let output = privacy::redact(&run_tool(&call.fn_name, &call.fn_arguments)).text;
+                results.push(ToolResponse::new(call.call_id.clone(), output));
+            }
+            chat_req = chat_req.append_message(tool_calls);
+            for result in results {
+                chat_req = chat_req.append_message(result);
+            }
+        }
+        Err(ProviderError::NoCompletionChoice)
+    }
+
+    fn token_usage(usage: &genai::chat::Usage) -> TokenUsage {
+        let count = |tokens: Option<i32>| tokens.and_then(|t| u64::try_from(t).ok()).unwrap_or(0);
+        TokenUsage {
+            input: count(usage.prompt_tokens),
+            output: count(usage.completion_tokens),
+            cached: count(
+                usage
+                    .prompt_tokens_details
+                    .as_ref()
+                    .and_then(|details| details.cached_tokens),
+            ),
+        }
+    }
+
+    /// Add a request to the local usage log that `lumen usage` reads.
+    fn record_usage(&self, command: &str, usage: TokenUsage) {
+        tracing::debug!(
+            command,
+            input_tokens = usage.input,
+            output_tokens = usage.output,
+            cached_tokens = usage.cached,
+            "token usage"
+        );
+        let model = self
+            .ai
+            .for_command(command)
+            .model
+            .unwrap_or_else(|| self.get_model());
+        UsageRecord::new(command, self.provider_id, &model, usage).append();
+    }
+
+    /// Shorten the diffs in the user prompt when `ai.compact_diff` is set.
+    fn compact_diffs(&self, mut prompt: AIPrompt) -> AIPrompt {
+        if self.ai.compact_diff {
+            prompt.rewrite_user_prompt(compact_diff);
+        }
+        prompt
+    }
+
+    /// Replace likely secrets in the user prompt with placeholders, or refuse to send it
+    /// when `ai.secrets` is `fail`.
+    fn redact_secrets(&self, mut prompt: AIPrompt) -> Result<AIPrompt, ProviderError> {
+        let mut findings = Vec::new();
+        prompt.rewrite_user_prompt(|text| {
+            let redacted = privacy::redact(text);
+            findings.extend(redacted.findings);
+            redacted.text
+        });
+        if findings.is_empty() {
+            return Ok(prompt);
+        }
+        let summary = privacy::summary(&findings);
+        match self.ai.secrets {
+            SecretPolicy::Fail => Err(ProviderError::SecretsDetected(summary)),
+            SecretPolicy::Redact => {
+                eprintln!(
+                    "\r\x1b[33mRedacted likely secrets before sending to {self}: {summary}\x1b[0m"
+                );
+                Ok(prompt)
+            }
+        }
+    }
+
+    fn chat_request(
+        &self,
+        prompt: &AIPrompt,
+        settings: &GenerationSettings,
+    ) -> (ChatRequest, ChatOptions) {
+        let chat_req = ChatRequest::new(self.chat_messages(prompt, settings));
+
+        let mut options = ChatOptions::default();
+        if let Some(temperature) = settings.temperature {
+            options = options.with_temperature(temperature);
+        }
+        if let Some(top_p) = settings.top_p {
+            options = options.with_top_p(top_p);
+        }
+        if let Some(max_tokens) = settings.max_output_tokens {
+            options = options.with_max_tokens(max_tokens);
+        }
+        // Checked when the config is read
+        if let Some(effort) = settings
+            .reasoning_effort
+            .as_deref()
+            .and_then(|effort| effort.parse().ok())
+        {
+            options = options.with_reasoning_effort(effort);
+        }
+        (chat_req, options)
+    }
+
+    /// The system and user messages for `prompt`, in the configured language. For Anthropic, the stable start of the user
+    /// prompt goes in a message of its own marked for caching, so the next request about the
+    /// same changes reads it from the cache. OpenAI and others cache a repeated start of the
+    /// prompt by themselves.
+    fn chat_messages(&self, prompt: &AIPrompt, settings: &GenerationSettings) -> Vec<ChatMessage> {
+        let system = ChatMessage::system(prompt.system_prompt_in(settings.language.as_deref()));
+        match prompt.split_user_prompt() {
+            (stable, rest) if self.cache_control && !stable.is_empty() => {
+                let mut messages = vec![
+                    system,
+                    ChatMessage::user(stable).with_options(CacheControl::Ephemeral),
+                ];
+                if !rest.is_empty() {
+                    messages.push(ChatMessage::user(rest));
+                }
+                messages
+            }
+            _ => vec![system, ChatMessage::user(prompt.user_prompt.clone())],
+        }
+    }
+
+    /// Like `complete` (or `complete_json` if `json` is set), but reuses a stored response when
+    /// the prompt is about an immutable commit (`sha` is set) and was answered before with the
+    /// same model and settings.
+    async fn complete_cached(
+        &self,
+        prompt: AIPrompt,
+        command: &str,
+        sha: Option<&str>,
+        json: bool,
+    ) -> Result<String, ProviderError> {
+        let settings = self.ai.for_command(command);
+        let entry = sha.and_then(|sha| CacheEntry::new(sha, &self.to_string(), &settings, &prompt));
+        if let Some(cached) = entry.as_ref().and_then(CacheEntry::get) {
+            tracing::debug!(command, "using cached response");
+            return Ok(cached);
+        }
+
+        let response = self.complete_with(prompt, command, json).await?;
+        if let Some(entry) = entry {
+            entry.put(&response);
+        }
+        Ok(response)
+    }
+
     pub async fn explain(&self, command: &ExplainCommand) -> Result<String, ProviderError> {
-        let prompt = AIPrompt::build_explain_prompt(command)?;
-        self.complete(prompt).await
+        let sha = command.cache_key();
+        // Structured formats like `--format json` get a JSON report instead of markdown
+        let json = command.format.structured();
+        let max_tokens = self.ai.max_tokens();
+        let diff = command.git_entity.diff();
+        if estimate_tokens(diff) <= max_tokens {
+            let prompt =
+                AIPrompt::build_explain_prompt(&command.prompt_input(), self.ai.prompt("explain"))?;
+            return self.complete_cached(prompt, "explain", sha, json).await;
+        }
+
+        // Too large for one request: summarize each chunk, then explain from the summaries
+        let chunks = split_diff(diff, max_tokens);
+        let mut summaries = Vec::with_capacity(chunks.len());
+        for (i, chunk) in chunks.iter().enumerate() {
+            let prompt = AIPrompt::build_diff_chunk_prompt(chunk, i + 1, chunks.len())?;
+            summaries.push(self.complete_cached(prompt, "explain", sha, false).await?);
+        }
+        let prompt = AIPrompt::build_explain_synthesis_prompt(
+            &command.prompt_input(),
+            &summaries,
+            self.ai.prompt("explain"),
+        )?;
+        self.complete_cached(prompt, "explain", sha, json).await
+    }
+
+    pub async fn annotate(
+        &self,
+        command: &ExplainCommand,
+        hunks: &[DiffHunk],
+    ) -> Result<String, ProviderError> {
+        let input = command.prompt_input();
+        let prompt =
+            AIPrompt::build_annotate_prompt(hunks, input.explained.message(), input.query)?;
+        self.complete_cached(prompt, "explain", command.cache_key(), false)
+            .await
+    }
+
+    pub async fn impact(&self, command: &ExplainCommand) -> Result<String, ProviderError> {
+        let input = command.prompt_input();
+        let prompt = AIPrompt::build_impact_prompt(
+            input.diff,
+            input.explained.message(),
+            input.query,
+            input.crates,
+        )?;
+        self.complete_cached(prompt, "explain", command.cache_key(), true)
+            .await
     }
 
     pub async fn draft(&self, command: &DraftCommand) -> Result<String, ProviderError> {
-        let prompt = AIPrompt::build_draft_prompt(command)?;
-        self.complete(prompt).await
+        let prompt =
+            AIPrompt::build_draft_prompt(&command.prompt_input()?, self.ai.prompt("draft"))?;
+        self.complete_json(prompt, "draft").await
     }
 
     pub async fn operate(&self, command: &OperateCommand) -> Result<String, ProviderError> {
-        let prompt = AIPrompt::build_operate_prompt(command.query.as_str())?;
-        self.complete(prompt).await
+        let can_inspect =
+            self.supports_tools && matches!(self.backend, ProviderBackend::GenAI { .. });
+        let prompt = AIPrompt::build_operate_prompt(
+            &command.query,
+            command.dry_run,
+            self.ai.prompt("operate"),
+            can_inspect,
+        )?;
+        if can_inspect {
+            return self
+                .complete_with_tools(prompt, "operate", operate::tools(), operate::run_tool)
+                .await;
+        }
+        self.complete(prompt, "operate").await
+    }
+
+    pub async fn cherry_pick(
+        &self,
+        commit: &Commit,
+        branch_diff: &str,
+    ) -> Result<String, ProviderError> {
+        let prompt =
+            AIPrompt::build_cherry_pick_prompt(&commit.message, &commit.diff, branch_diff)?;
+        self.complete(prompt, "cherry-pick").await
+    }
+
+    /// Write a pull request title and description, as a JSON object.
+    pub async fn pr(&self, command: &PrCommand) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_pr_prompt(
+            &command.commits,
+            &command.base,
+            command.git_entity.diff(),
+            command.context.as_deref(),
+        )?;
+        self.complete_json(prompt, "pr").await
+    }
+
+    /// Write a changelog section following `layout`, as markdown.
+    pub async fn changelog(&self, commits: &str, layout: &str) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_changelog_prompt(commits, layout)?;
+        self.complete(prompt, "changelog").await
+    }
+
+    /// Tie together the explanations of several commits, for `lumen explain <sha> <sha>...`.
+    pub async fn explain_commits(
+        &self,
+        explanations: &str,
+        query: Option<&str>,
+    ) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_commits_synthesis_prompt(explanations, query)?;
+        self.complete(prompt, "explain").await
+    }
+
+    pub async fn digest(&self, commits: &str) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_digest_prompt(commits)?;
+        self.complete(prompt, "digest").await
+    }
+
+    /// Review a diff for `lumen review`, as a JSON object of findings.
+    pub async fn review(&self, diff: &str) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_structured_review_prompt(diff)?;
+        self.complete_json(prompt, "review").await
+    }
+
+    /// Group numbered hunks into commits, for `lumen split`.
+    pub async fn split(&self, hunks: &str, context: Option<&str>) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_split_prompt(hunks, context)?;
+        self.complete_json(prompt, "split").await
+    }
+
+    /// Rate the review risk of changed files for the diff viewer's sidebar.
+    pub async fn rate_risk(
+        &self,
+        files: &str,
+        diff: Option<&str>,
+    ) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_risk_prompt(files, diff)?;
+        self.complete_json(prompt, "review").await
+    }
+
+    /// Summarize the changes to one file in a line. Answers are cached by the prompt, which
+    /// holds the diff, in a directory of their own instead of a commit's.
+    pub async fn summarize_file(&self, path: &str, diff: &str) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_file_summary_prompt(path, diff)?;
+        self.complete_cached(prompt, "explain", Some("file-summaries"), false)
+            .await
+    }
+
+    /// Review a commit message's style. Answers about a commit's message are cached with the
+    /// commit's.
+    pub async fn lint_message(
+        &self,
+        message: &str,
+        sha: Option<&str>,
+    ) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_lint_message_prompt(message)?;
+        self.complete_cached(prompt, "lint-msg", sha, true).await
+    }
+
+    /// Suggest a commit subject for the staged changes, while they're being staged.
+    pub async fn suggest_commit(&self, diff: &str) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_commit_suggestion_prompt(diff)?;
+        self.complete_json(prompt, "suggest").await
+    }
+
+    /// Turn the notes taken in the diff viewer into a review summary, in markdown.
+    pub async fn polish_review_notes(&self, notes: &str) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_review_notes_prompt(notes)?;
+        self.complete(prompt, "review").await
+    }
+
+    /// Explain one hunk for the diff viewer, calling `on_delta` as the answer streams in.
+    pub async fn explain_hunk(
+        &self,
+        file: &str,
+        hunk: &str,
+        on_delta: impl FnMut(&str),
+    ) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_explain_hunk_prompt(Some(file), hunk, None)?;
+        self.complete_stream(prompt, "explain", on_delta).await
+    }
+
+    pub async fn summarize_session(
+        &self,
+        changes: &str,
+        minutes: u64,
+    ) -> Result<String, ProviderError> {
+        let prompt = AIPrompt::build_session_summary_prompt(changes, minutes)?;
+        self.complete(prompt, "summary").await
+    }
+
+    /// Token budget for a diff sent in one request
+    pub fn max_tokens(&self) -> usize {
+        self.ai.max_tokens()
+    }
+
+    /// Send a minimal request to verify the provider is reachable and the credentials work.
+    pub async fn health_check(&self) -> Result<(), ProviderError> {
+        let prompt = AIPrompt::build_health_check_prompt()?;
+        self.complete(prompt, "doctor").await.map(|_| ())
     }
 
     fn get_model(&self) -> String {
         match &self.backend {
-            ProviderBackend::GenAI { model, .. } => model.clone(),
+            ProviderBackend::GenAI { model, .. } | ProviderBackend::Bedrock { model, .. } => {
+                model.clone()
+            }
         }
     }
 }
@@ -0,0 +1,152 @@
+//! Ollama's own API, for what its OpenAI-compatible one doesn't cover: which models are
+//! installed, and pulling the ones that aren't.
+
+use serde::Deserialize;
+use serde_json::json;
+
+use super::ProviderError;
+use crate::http;
+
+/// Where Ollama listens unless told otherwise
+pub const DEFAULT_HOST: &str = "http://localhost:11434";
+
+/// The Ollama server to use: `api_base_url` from the config or command line, then
+/// `OLLAMA_HOST` as the `ollama` CLI reads it, then [`DEFAULT_HOST`]. A bare `host:port` gets
+/// `http://`, and a `/v1` suffix for the OpenAI-compatible API is dropped.
+pub fn host(api_base_url: Option<&str>) -> String {
+    let host = api_base_url
+        .map(str::to_string)
+        .or_else(|| std::env::var("OLLAMA_HOST").ok())
+        .filter(|host| !host.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_HOST.to_string());
+    let host = host.trim().trim_end_matches('/');
+    let host = host.strip_suffix("/v1").unwrap_or(host);
+    if host.contains("://") {
+        host.to_string()
+    } else {
+        format!("http://{host}")
+    }
+}
+
+/// Whether `model` is among `installed`, where a name without a tag means `:latest`.
+pub fn is_installed(installed: &[String], model: &str) -> bool {
+    let tagged = |name: &str| {
+        if name.contains(':') {
+            name.to_string()
+        } else {
+            format!("{name}:latest")
+        }
+    };
+    let model = tagged(model);
+    installed.iter().any(|name| tagged(name) == model)
+}
+
+#[derive(Deserialize)]
+struct Tags {
+    models: Vec<Model>,
+}
+
+#[derive(Deserialize)]
+struct Model {
+    name: String,
+}
+
+/// The models installed on the server at `host`, which also shows it's reachable.
+pub async fn list_models(host: &str) -> Result<Vec<String>, ProviderError> {
+    let tags: Tags = http::client()
+        .get(format!("{host}/api/tags"))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(tags.models.into_iter().map(|model| model.name).collect())
+}
+
+#[derive(Deserialize)]
+struct PullProgress {
+    #[serde(default)]
+    status: String,
+    total: Option<u64>,
+    completed: Option<u64>,
+    error: Option<String>,
+}
+
+/// Download `model` to the server at `host`, calling `on_progress` with each status line
+/// Ollama reports, like "pulling 6a0746a1ec1a" with the bytes done and in total so far.
+pub async fn pull(
+    host: &str,
+    model: &str,
+    mut on_progress: impl FnMut(&str, Option<(u64, u64)>),
+) -> Result<(), ProviderError> {
+    let mut response = http::client()
+        .post(format!("{host}/api/pull"))
+        .json(&json!({ "model": model, "stream": true }))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // One JSON object per line, which may arrive split across chunks
+    let mut buffer = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=end).collect();
+            let Ok(progress) = serde_json::from_slice::<PullProgress>(&line) else {
+                continue;
+            };
+            if let Some(error) = progress.error {
+                return Err(ProviderError::Ollama(format!(
+                    "pulling {model} failed: {error}"
+                )));
+            }
+            on_progress(&progress.status, progress.total.zip(progress.completed));
+        }
+    }
+    Ok(())
+}
+
+/// What to tell the user about a request to `host` for `model` that failed with `error`:
+/// that the server can't be reached, or that the model isn't installed, which Ollama only
+/// reports as a 404. Otherwise the error as it is.
+pub async fn diagnose(host: &str, model: &str, error: ProviderError) -> ProviderError {
+    match list_models(host).await {
+        Err(e) => ProviderError::Ollama(format!(
+            "couldn't reach Ollama at {host} ({e}); is `ollama serve` running? Set another host with `api_base_url` or OLLAMA_HOST"
+        )),
+        Ok(installed) if !is_installed(&installed, model) => ProviderError::Ollama(format!(
+            "model '{model}' isn't installed in Ollama at {host}; run `ollama pull {model}` or pick an installed one with `lumen configure --wizard`"
+        )),
+        Ok(_) => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_host_is_normalized() {
+        assert_eq!(
+            host(Some("http://gpu-box:11434/v1/")),
+            "http://gpu-box:11434"
+        );
+        assert_eq!(host(Some("127.0.0.1:8080")), "http://127.0.0.1:8080");
+        assert_eq!(
+            host(Some("https://ollama.internal")),
+            "https://ollama.internal"
+        );
+    }
+
+    #[test]
+    fn test_untagged_names_mean_latest() {
+        let installed = vec![
+            "llama3.2:latest".to_string(),
+            "qwen2.5-coder:7b".to_string(),
+        ];
+        assert!(is_installed(&installed, "llama3.2"));
+        assert!(is_installed(&installed, "qwen2.5-coder:7b"));
+        assert!(!is_installed(&installed, "qwen2.5-coder"));
+        assert!(!is_installed(&installed, "mistral"));
+    }
+}
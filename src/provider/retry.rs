@@ -0,0 +1,118 @@
+//! Deciding whether a failed request is worth retrying, and how long to wait first.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::ProviderError;
+
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+/// Longest `Retry-After` we'll honor; beyond that the user is better off trying later
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// What to do about an error: `None` if it's not transient, otherwise the delay the server
+/// asked for, if any.
+pub fn retry_hint(error: &ProviderError) -> Option<Option<Duration>> {
+    match error {
+        ProviderError::GenAIError(
+            genai::Error::WebModelCall { webc_error, .. }
+            | genai::Error::WebAdapterCall { webc_error, .. },
+        ) => match webc_error {
+            genai::webc::Error::ResponseFailedStatus {
+                status, headers, ..
+            } => is_retryable_status(status.as_u16()).then(|| {
+                headers
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(parse_retry_after)
+            }),
+            genai::webc::Error::Reqwest(e) => is_retryable_reqwest(e).then_some(None),
+            _ => None,
+        },
+        ProviderError::RequestError(e) => is_retryable_reqwest(e).then_some(None),
+        ProviderError::BedrockError {
+            status,
+            retry_after,
+            ..
+        } => {
+            is_retryable_status(*status).then(|| retry_after.as_deref().and_then(parse_retry_after))
+        }
+        _ => None,
+    }
+}
+
+/// Rate limits, overload and gateway errors. 529 is Anthropic's "overloaded".
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+fn is_retryable_reqwest(error: &reqwest::Error) -> bool {
+    error.is_timeout() || error.is_connect()
+}
+
+/// `Retry-After` in seconds. The HTTP-date form is rare for these APIs and falls back to backoff.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let seconds: f64 = value.trim().parse().ok()?;
+    (seconds.is_finite() && seconds >= 0.0)
+        .then(|| Duration::from_secs_f64(seconds).min(MAX_RETRY_AFTER))
+}
+
+/// Exponential backoff for the given 0-based attempt, plus up to 50% jitter so that several
+/// clients limited at the same time don't retry in lockstep.
+pub fn backoff_delay(attempt: u32) -> Duration {
+    let delay = BASE_DELAY
+        .saturating_mul(2u32.saturating_pow(attempt))
+        .min(MAX_DELAY);
+    delay + delay.mul_f64(jitter_fraction() * 0.5)
+}
+
+/// Pseudo-random value in [0, 1); good enough for spreading retries out.
+fn jitter_fraction() -> f64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or_default();
+    f64::from(nanos % 1000) / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_retry_after() {
+        assert_eq!(parse_retry_after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(
+            parse_retry_after(" 1.5 "),
+            Some(Duration::from_millis(1500))
+        );
+        assert_eq!(parse_retry_after("9999"), Some(MAX_RETRY_AFTER));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(parse_retry_after("-1"), None);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        for attempt in 0..3 {
+            let delay = backoff_delay(attempt);
+            let base = BASE_DELAY * 2u32.pow(attempt);
+            assert!(delay >= base && delay <= base.mul_f64(1.5));
+        }
+        assert!(backoff_delay(20) <= MAX_DELAY.mul_f64(1.5));
+    }
+
+    #[test]
+    fn test_retry_hint_for_bedrock_status() {
+        let error = |status, retry_after: Option<&str>| ProviderError::BedrockError {
+            status,
+            message: String::new(),
+            retry_after: retry_after.map(String::from),
+        };
+        assert_eq!(
+            retry_hint(&error(429, Some("2"))),
+            Some(Some(Duration::from_secs(2)))
+        );
+        assert_eq!(retry_hint(&error(503, None)), Some(None));
+        assert_eq!(retry_hint(&error(400, None)), None);
+        assert_eq!(retry_hint(&ProviderError::NoCompletionChoice), None);
+    }
+}
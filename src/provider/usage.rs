@@ -0,0 +1,187 @@
+//! Local log of token usage and estimated cost per AI request.
+//!
+//! Each request appends one JSON line to `~/.local/share/lumen/usage.jsonl` (or the
+//! platform's data directory), which `lumen usage` summarizes.
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+/// Tokens used by one request, as reported by the provider
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub input: u64,
+    pub output: u64,
+    /// The part of `input` read from the provider's prompt cache
+    pub cached: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UsageRecord {
+    /// Local time in RFC 3339, so the first 10 characters are the day
+    pub timestamp: String,
+    pub command: String,
+    pub provider: String,
+    pub model: String,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// The part of `input_tokens` read from the provider's prompt cache
+    #[serde(default)]
+    pub cached_tokens: u64,
+    /// Estimated from the pricing table; `None` for models it doesn't know
+    pub cost: Option<f64>,
+}
+
+/// USD per million input and output tokens. Model names are matched by the longest key
+/// they contain, after lowercasing and turning `.` into `-` (so `claude-sonnet-4.5` and
+/// `us.anthropic.claude-sonnet-4-5-...` both match).
+const PRICING: &[(&str, f64, f64)] = &[
+    ("gpt-5", 1.25, 10.0),
+    ("gpt-5-mini", 0.25, 2.0),
+    ("gpt-5-nano", 0.05, 0.4),
+    ("gpt-4-1", 2.0, 8.0),
+    ("gpt-4-1-mini", 0.4, 1.6),
+    ("o4-mini", 1.1, 4.4),
+    ("claude-opus-4-5", 5.0, 25.0),
+    ("claude-sonnet-4-5", 3.0, 15.0),
+    ("claude-sonnet-4", 3.0, 15.0),
+    ("claude-haiku-4-5", 1.0, 5.0),
+    ("gemini-2-5-pro", 1.25, 10.0),
+    ("gemini-2-5-flash", 0.3, 2.5),
+    ("gemini-2-5-flash-lite", 0.1, 0.4),
+    ("llama-3-3-70b-versatile", 0.59, 0.79),
+    ("llama-3-1-8b-instant", 0.05, 0.08),
+    ("deepseek-chat", 0.28, 0.42),
+    ("deepseek-reasoner", 0.28, 0.42),
+    ("grok-4", 3.0, 15.0),
+];
+
+/// What cached input tokens cost as a share of the input price, as Anthropic and OpenAI
+/// charge for their current models
+const CACHED_INPUT_DISCOUNT: f64 = 0.1;
+
+/// Providers that run on your own machine cost nothing per token
+const FREE_PROVIDERS: &[&str] = &["ollama"];
+
+pub fn usage_log_path() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("lumen").join("usage.jsonl"))
+}
+
+/// Estimated cost in USD, if the model's price is known.
+pub fn estimate_cost(provider: &str, model: &str, usage: TokenUsage) -> Option<f64> {
+    if FREE_PROVIDERS.contains(&provider) {
+        return Some(0.0);
+    }
+    let model = model.to_lowercase().replace('.', "-");
+    let (_, input_price, output_price) = PRICING
+        .iter()
+        .filter(|(key, _, _)| model.contains(key))
+        .max_by_key(|(key, _, _)| key.len())?;
+    let cached = usage.cached.min(usage.input);
+    let input = (usage.input - cached) as f64 + cached as f64 * CACHED_INPUT_DISCOUNT;
+    Some((input * input_price + usage.output as f64 * output_price) / 1_000_000.0)
+}
+
+impl UsageRecord {
+    pub fn new(command: &str, provider: &str, model: &str, usage: TokenUsage) -> Self {
+        UsageRecord {
+            timestamp: chrono::Local::now().to_rfc3339(),
+            command: command.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            input_tokens: usage.input,
+            output_tokens: usage.output,
+            cached_tokens: usage.cached,
+            cost: estimate_cost(provider, model, usage),
+        }
+    }
+
+    pub fn day(&self) -> &str {
+        self.timestamp.get(..10).unwrap_or(&self.timestamp)
+    }
+
+    /// Append to the usage log. Failures are ignored so tracking never breaks a request.
+    pub fn append(&self) {
+        let Some(path) = usage_log_path() else {
+            return;
+        };
+        if let Some(parent) = path.parent() {
+            let _ = fs::create_dir_all(parent);
+        }
+        let Ok(line) = serde_json::to_string(self) else {
+            return;
+        };
+        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
+            let _ = writeln!(file, "{line}");
+        }
+    }
+}
+
+/// Every record in the usage log, skipping lines that don't parse.
+pub fn read_log() -> io::Result<Vec<UsageRecord>> {
+    let Some(path) = usage_log_path().filter(|path| path.exists()) else {
+        return Ok(Vec::new());
+    };
+    Ok(fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(input: u64, output: u64) -> TokenUsage {
+        TokenUsage {
+            input,
+            output,
+            cached: 0,
+        }
+    }
+
+    #[test]
+    fn test_cost_uses_most_specific_price() {
+        let cost = estimate_cost("openai", "gpt-5-mini", usage(1_000_000, 1_000_000));
+        assert_eq!(cost, Some(2.25));
+        let cost = estimate_cost(
+            "bedrock",
+            "us.anthropic.claude-sonnet-4-5-20250929-v1:0",
+            usage(1_000_000, 0),
+        );
+        assert_eq!(cost, Some(3.0));
+        assert_eq!(
+            estimate_cost(
+                "openrouter",
+                "anthropic/claude-sonnet-4.5",
+                usage(0, 1_000_000)
+            ),
+            Some(15.0)
+        );
+    }
+
+    #[test]
+    fn test_cached_input_is_discounted() {
+        let usage = TokenUsage {
+            input: 1_000_000,
+            output: 0,
+            cached: 800_000,
+        };
+        let cost = estimate_cost("claude", "claude-sonnet-4-5-20250929", usage).unwrap();
+        assert!((cost - 0.84).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cost_of_unknown_and_local_models() {
+        assert_eq!(
+            estimate_cost("custom-openai", "qwen2.5-coder", usage(10, 10)),
+            None
+        );
+        assert_eq!(
+            estimate_cost("ollama", "llama3.2", usage(10, 10)),
+            Some(0.0)
+        );
+    }
+}
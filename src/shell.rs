@@ -0,0 +1,78 @@
+//! Running outside programs the same way on every platform.
+//!
+//! Command lines that come from the user or the provider, like hooks, pagers or the git
+//! command `lumen operate` suggests, run in the platform's shell: `sh -c` on Unix and
+//! `cmd /C` on Windows. Everything lumen runs itself starts the program directly instead,
+//! so it needs no shell at all.
+
+use std::io;
+use std::process::Command;
+
+/// A command that runs `line` in the platform's shell.
+pub fn command(line: &str) -> Command {
+    #[cfg(target_family = "windows")]
+    let mut shell = {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    };
+    #[cfg(not(target_family = "windows"))]
+    let mut shell = {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(line);
+    shell
+}
+
+/// The editor to use when `$EDITOR` isn't set.
+pub fn default_editor() -> &'static str {
+    if cfg!(target_family = "windows") {
+        "notepad"
+    } else {
+        "vim"
+    }
+}
+
+/// `$EDITOR`, or the platform's [`default_editor`].
+pub fn editor() -> String {
+    std::env::var("EDITOR").unwrap_or_else(|_| default_editor().to_string())
+}
+
+/// Open `url` in the default browser, without waiting for it.
+pub fn open_url(url: &str) -> io::Result<()> {
+    #[cfg(target_os = "macos")]
+    let mut command = Command::new("open");
+    // `cmd /C start` would split the URL at `&`, which query strings are full of
+    #[cfg(target_os = "windows")]
+    let mut command = {
+        let mut command = Command::new("rundll32");
+        command.arg("url.dll,FileProtocolHandler");
+        command
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let mut command = Command::new("xdg-open");
+    command.arg(url).spawn().map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_runs_in_the_shell() {
+        let line = if cfg!(target_family = "windows") {
+            "echo one&& echo two"
+        } else {
+            "echo one && echo two"
+        };
+        let output = command(line).output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert_eq!(
+            stdout.lines().map(str::trim).collect::<Vec<_>>(),
+            ["one", "two"]
+        );
+    }
+}
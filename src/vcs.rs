@@ -0,0 +1,174 @@
+//! Which version control system manages the repository.
+//!
+//! Besides git, lumen works on Jujutsu (jj) repositories colocated with git (`jj git init
+//! --colocate`, the default for `jj git clone`), where every jj commit is also a git commit.
+//! Only two things differ there: jj names commits by change IDs and revsets git doesn't know,
+//! and the working copy is a commit of its own rather than git's working tree and index.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use lumen_core::patch::{diff_files, is_excluded};
+
+use crate::error::LumenError;
+use crate::logging;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Vcs {
+    Git,
+    Jujutsu,
+}
+
+impl Vcs {
+    /// The one managing the current directory: jj when there's a `.jj` directory above it.
+    pub fn detect() -> Self {
+        match std::env::current_dir() {
+            Ok(dir) if jj_root(&dir).is_some() => Vcs::Jujutsu,
+            _ => Vcs::Git,
+        }
+    }
+
+    /// `revision` as git names it. In a jj repository, a change ID, `@`, `@-` or any other
+    /// revset naming one commit becomes that commit's hash; anything git resolves by itself,
+    /// like a SHA, a branch or `HEAD~2`, is left as it is.
+    pub fn git_revision(self, revision: &str) -> String {
+        if self == Vcs::Git || git_resolves(revision) {
+            return revision.to_string();
+        }
+        jj_commit_id(revision).unwrap_or_else(|| revision.to_string())
+    }
+
+    /// The working copy's changes limited to `paths`, when they don't live in git's working
+    /// tree and index: jj keeps them in the `@` commit, so there's nothing staged or unstaged.
+    /// `None` for git.
+    pub fn working_copy_diff(self, paths: &[String]) -> Result<Option<String>, LumenError> {
+        if self == Vcs::Git {
+            return Ok(None);
+        }
+        let output = logging::output(
+            Command::new("jj")
+                .args(["diff", "--git", "--color", "never", "-r", "@", "--"])
+                .args(paths),
+        )
+        .map_err(|e| jj_error(&e))?;
+        if !output.status.success() {
+            return Err(LumenError::CommandError(format!(
+                "jj diff failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let diff = String::from_utf8(output.stdout)?;
+        // jj takes filesets rather than git's pathspecs, so the exclusions are applied here
+        Ok(Some(
+            diff_files(&diff)
+                .into_iter()
+                .filter(|(path, _)| !is_excluded(path))
+                .map(|(_, part)| part)
+                .collect(),
+        ))
+    }
+}
+
+/// Run git for its stdout, logged for `--verbose` like every command lumen runs. Fails with
+/// git's message when it exits unsuccessfully.
+pub fn git(args: &[&str]) -> Result<String, LumenError> {
+    run_git(Command::new("git").args(args))
+}
+
+/// [`git`] with `input` on its stdin, like a patch for `git apply -`.
+pub fn git_with_input(args: &[&str], input: &str) -> Result<String, LumenError> {
+    git_result(logging::output_with_input(
+        Command::new("git").args(args),
+        input.as_bytes(),
+    ))
+}
+
+/// [`git`] for a command set up further, like with a working directory or environment.
+pub fn run_git(command: &mut Command) -> Result<String, LumenError> {
+    git_result(logging::output(command))
+}
+
+fn git_result(output: std::io::Result<Output>) -> Result<String, LumenError> {
+    let output = output.map_err(|e| LumenError::CommandError(format!("failed to run git: {e}")))?;
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).into_owned());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    // Some commands, like a refused `git commit`, explain themselves on stdout
+    let message = if stderr.is_empty() {
+        String::from_utf8_lossy(&output.stdout).trim().to_string()
+    } else {
+        stderr
+    };
+    Err(LumenError::CommandError(message))
+}
+
+/// The directory holding `.jj`, looking upwards from `dir`.
+fn jj_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| dir.join(".jj").is_dir())
+        .map(Path::to_path_buf)
+}
+
+fn git_resolves(revision: &str) -> bool {
+    logging::output(Command::new("git").args([
+        "rev-parse",
+        "--verify",
+        "--quiet",
+        "--end-of-options",
+        &format!("{revision}^{{commit}}"),
+    ]))
+    .is_ok_and(|output| output.status.success())
+}
+
+/// The commit hash of the one commit `revision` names, asking jj.
+fn jj_commit_id(revision: &str) -> Option<String> {
+    let output = logging::output(Command::new("jj").args([
+        "log",
+        "--no-graph",
+        "--color",
+        "never",
+        "-r",
+        revision,
+        "--limit",
+        "1",
+        "-T",
+        "commit_id",
+    ]))
+    .ok()?;
+    let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !id.is_empty()).then_some(id)
+}
+
+fn jj_error(e: &std::io::Error) -> LumenError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        LumenError::CommandError(
+            "this is a jj repository, but `jj` isn't installed or on PATH".to_string(),
+        )
+    } else {
+        LumenError::CommandError(format!("couldn't run jj: {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jj_root_is_found_above() {
+        let root = std::env::temp_dir().join(format!("lumen-jj-{}", std::process::id()));
+        let nested = root.join("src").join("deep");
+        std::fs::create_dir_all(&nested).unwrap();
+        assert_eq!(jj_root(&nested), None);
+        std::fs::create_dir(root.join(".jj")).unwrap();
+        assert_eq!(jj_root(&nested), Some(root.clone()));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn git_fails_with_its_message() {
+        assert!(git(&["--version"]).unwrap().starts_with("git version"));
+        let error = git(&["no-such-command"]).unwrap_err().to_string();
+        assert!(error.contains("no-such-command"), "{error}");
+    }
+}
@@ -0,0 +1,117 @@
+//! Posting generated reports to chat webhooks.
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::LumenError;
+
+/// Discord rejects messages longer than this
+const DISCORD_MAX_LEN: usize = 2000;
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookPreset {
+    #[default]
+    Slack,
+    Discord,
+    Teams,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: String,
+
+    #[serde(default)]
+    pub preset: WebhookPreset,
+}
+
+impl WebhookPreset {
+    /// Whether the target renders Slack's `*bold*` markup rather than Markdown.
+    pub fn uses_slack_markup(self) -> bool {
+        self == WebhookPreset::Slack
+    }
+
+    fn payload(self, text: &str) -> Value {
+        match self {
+            WebhookPreset::Slack => json!({ "text": text }),
+            WebhookPreset::Discord => {
+                let content = if text.chars().count() > DISCORD_MAX_LEN {
+                    let mut truncated: String = text.chars().take(DISCORD_MAX_LEN - 1).collect();
+                    truncated.push('…');
+                    truncated
+                } else {
+                    text.to_string()
+                };
+                json!({ "content": content })
+            }
+            WebhookPreset::Teams => json!({
+                "type": "message",
+                "attachments": [{
+                    "contentType": "application/vnd.microsoft.card.adaptive",
+                    "content": {
+                        "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                        "type": "AdaptiveCard",
+                        "version": "1.4",
+                        "body": [{ "type": "TextBlock", "text": text, "wrap": true }],
+                    },
+                }],
+            }),
+        }
+    }
+}
+
+impl WebhookConfig {
+    pub async fn post(&self, text: &str) -> Result<(), LumenError> {
+        let response = crate::http::client()
+            .post(&self.url)
+            .json(&self.preset.payload(text))
+            .send()
+            .await
+            .map_err(|e| LumenError::WebhookError(e.to_string()))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(LumenError::WebhookError(format!(
+                "{status}: {}",
+                body.trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payload_shapes() {
+        assert_eq!(WebhookPreset::Slack.payload("hi"), json!({ "text": "hi" }));
+        assert_eq!(
+            WebhookPreset::Discord.payload("hi"),
+            json!({ "content": "hi" })
+        );
+        let teams = WebhookPreset::Teams.payload("hi");
+        assert_eq!(
+            teams["attachments"][0]["content"]["body"][0]["text"],
+            json!("hi")
+        );
+    }
+
+    #[test]
+    fn test_discord_payload_truncated() {
+        let payload = WebhookPreset::Discord.payload(&"x".repeat(3000));
+        let content = payload["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), DISCORD_MAX_LEN);
+        assert!(content.ends_with('…'));
+    }
+
+    #[test]
+    fn test_preset_defaults_to_slack() {
+        let config: WebhookConfig =
+            serde_json::from_str(r#"{"url": "https://example.com/hook"}"#).unwrap();
+        assert_eq!(config.preset, WebhookPreset::Slack);
+    }
+}